@@ -0,0 +1,59 @@
+//! How fast the interpreter runs loops, calls and field-heavy code.
+//!
+//! The Java programs in `benches/java` are compiled with `javac`, which has to be on the `PATH`.
+//! To see the speedup of a change, run `cargo bench -- --save-baseline before` before it and
+//! `cargo bench -- --baseline before` after it. With `--features jit` the programs also run
+//! compiled.
+
+use std::{path::PathBuf, process::Command};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use jappuccino::rt::{ClassPath, Runtime};
+
+/// Compiles the benchmark programs and returns the directory of their class files
+fn compile_programs() -> PathBuf {
+    let sources = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("benches/java");
+    let classes = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("bench-classes");
+    let mut javac = Command::new("javac");
+    javac.arg("-d").arg(&classes);
+    for source in sources.read_dir().expect("benches/java should be readable") {
+        javac.arg(source.unwrap().path());
+    }
+    let status = javac.status().expect("javac should be on the PATH");
+    assert!(status.success(), "javac failed");
+    classes
+}
+
+/// Benchmarks static methods of a class that take an `int` and return an `int`
+fn bench_class(c: &mut Criterion, classes: &PathBuf, class: &str, methods: &[(&str, i32)]) {
+    bench_runtime(c, new_runtime(classes), class, class, methods);
+    #[cfg(feature = "jit")]
+    bench_runtime(c, new_runtime(classes).with_jit(100), &format!("{class}/jit"), class, methods);
+}
+
+fn new_runtime(classes: &PathBuf) -> Runtime {
+    let mut class_path = ClassPath::new();
+    class_path.push_directory(classes);
+    Runtime::new().with_class_path(class_path)
+}
+
+fn bench_runtime(c: &mut Criterion, mut runtime: Runtime, group: &str, class: &str, methods: &[(&str, i32)]) {
+    let mut ctx = runtime.new_context();
+    let mut group = c.benchmark_group(group);
+    for &(method, n) in methods {
+        group.bench_function(method, |b| b.iter(|| {
+            ctx.call_static::<i32>(class, method, "(I)I", (n,)).unwrap().unwrap()
+        }));
+    }
+    group.finish();
+}
+
+fn interpreter(c: &mut Criterion) {
+    let classes = compile_programs();
+    bench_class(c, &classes, "Loops", &[("sum", 10_000), ("nested", 150), ("arrays", 1_000)]);
+    bench_class(c, &classes, "Calls", &[("fib", 15), ("virtualCalls", 5_000), ("interfaceCalls", 2_000)]);
+    bench_class(c, &classes, "Fields", &[("particles", 100), ("allocations", 5_000)]);
+}
+
+criterion_group!(benches, interpreter);
+criterion_main!(benches);
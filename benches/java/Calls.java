@@ -0,0 +1,47 @@
+/** Static, virtual and interface calls */
+public class Calls {
+    interface Shape {
+        int area();
+    }
+
+    static class Square implements Shape {
+        final int side;
+        Square(int side) { this.side = side; }
+        public int area() { return side * side; }
+    }
+
+    static class Rect implements Shape {
+        final int width, height;
+        Rect(int width, int height) { this.width = width; this.height = height; }
+        public int area() { return width * height; }
+    }
+
+    static class Counter {
+        int count;
+        void add(int amount) { count += amount; }
+        int get() { return count; }
+    }
+
+    static int fib(int n) {
+        return n < 2 ? n : fib(n - 1) + fib(n - 2);
+    }
+
+    static int virtualCalls(int n) {
+        Counter counter = new Counter();
+        for (int i = 0; i < n; i++) {
+            counter.add(i);
+        }
+        return counter.get();
+    }
+
+    static int interfaceCalls(int n) {
+        Shape[] shapes = { new Square(3), new Rect(2, 5), new Square(4) };
+        int total = 0;
+        for (int i = 0; i < n; i++) {
+            for (Shape shape : shapes) {
+                total += shape.area();
+            }
+        }
+        return total;
+    }
+}
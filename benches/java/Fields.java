@@ -0,0 +1,43 @@
+/** Instance and static field accesses and small objects */
+public class Fields {
+    static int created;
+
+    int x, y, age;
+    Fields next;
+
+    Fields(int x, int y) {
+        this.x = x;
+        this.y = y;
+        created++;
+    }
+
+    static int particles(int n) {
+        Fields head = null;
+        for (int i = 0; i < 64; i++) {
+            Fields particle = new Fields(i, 0 - i);
+            particle.next = head;
+            head = particle;
+        }
+        for (int step = 0; step < n; step++) {
+            for (Fields p = head; p != null; p = p.next) {
+                p.x += p.y;
+                p.y -= 1;
+                p.age++;
+            }
+        }
+        int total = 0;
+        for (Fields p = head; p != null; p = p.next) {
+            total += p.x + p.age;
+        }
+        return total;
+    }
+
+    static int allocations(int n) {
+        int total = 0;
+        for (int i = 0; i < n; i++) {
+            Fields point = new Fields(i, i + 1);
+            total += point.x * point.y;
+        }
+        return total + created;
+    }
+}
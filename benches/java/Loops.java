@@ -0,0 +1,38 @@
+/** Arithmetic, locals and branches in tight loops */
+public class Loops {
+    static int sum(int n) {
+        int total = 0;
+        for (int i = 0; i < n; i++) {
+            total += i * 3 - (i / 7);
+        }
+        return total;
+    }
+
+    static int nested(int n) {
+        int count = 0;
+        for (int i = 0; i < n; i++) {
+            for (int j = i; j < n; j++) {
+                if (i + j > n) {
+                    count++;
+                } else {
+                    count -= 2;
+                }
+            }
+        }
+        return count;
+    }
+
+    static int arrays(int n) {
+        int[] values = new int[n];
+        for (int i = 0; i < n; i++) {
+            values[i] = i * i;
+        }
+        int total = 0;
+        for (int round = 0; round < 10; round++) {
+            for (int i = 0; i < values.length; i++) {
+                total += values[i] - round;
+            }
+        }
+        return total;
+    }
+}
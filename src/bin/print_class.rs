@@ -41,18 +41,18 @@ fn print_classfile(class: &ClassFile, show_constant_pool: bool) {
     if show_constant_pool {
         println!("constant pool:");
         for n in 1..=constant_pool.len() as u16 {
-            println!("  {n}: {}", display_constant(n, &constant_pool));
+            println!("  {n}: {}", display_constant(n, constant_pool));
         }
     }
     println!("access flags: {access_flags}");
-    println!("this: {}", display_constant(this_class, &constant_pool));
-    println!("super: {}", display_constant(super_class, &constant_pool));
+    println!("this: {}", display_constant(this_class, constant_pool));
+    println!("super: {}", display_constant(super_class, constant_pool));
     println!("interfaces:");
     for (n, &interface) in interfaces.iter().enumerate() {
-        println!("  {n}: {}", display_constant(interface, &constant_pool));
+        println!("  {n}: {}", display_constant(interface, constant_pool));
     }
     println!("fields:");
-    for (n, field) in fields.into_iter().enumerate() {
+    for (n, field) in fields.iter().enumerate() {
         print!("  {n}: ");
         let &Field {
             access_flags,
@@ -60,11 +60,11 @@ fn print_classfile(class: &ClassFile, show_constant_pool: bool) {
             descriptor_index,
             ref attributes,
         } = field;
-        println!("{access_flags} {} {}", display_field_descriptor(descriptor_index, &constant_pool), display_constant(name_index, &constant_pool));
-        print_attributes(attributes, 2, &constant_pool);
+        println!("{access_flags} {} {}", display_field_descriptor(descriptor_index, constant_pool), display_constant(name_index, constant_pool));
+        print_attributes(attributes, 2, constant_pool);
     }
     println!("methods:");
-    for (n, method) in methods.into_iter().enumerate() {
+    for (n, method) in methods.iter().enumerate() {
         print!("  {n}: ");
         let &Method {
             access_flags,
@@ -73,14 +73,14 @@ fn print_classfile(class: &ClassFile, show_constant_pool: bool) {
             ref attributes,
         } = method;
         println!("{access_flags} {}", display_method_descriptor(name_index, descriptor_index, constant_pool));
-        print_attributes(attributes, 2, &constant_pool);
+        print_attributes(attributes, 2, constant_pool);
     }
     println!("attributes:");
-    print_attributes(&attributes, 1, &constant_pool);
+    print_attributes(attributes, 1, constant_pool);
 }
 
 fn print_attributes(attributes: &[AttributeInfo], indent: u8, constant_pool: &[Constant]) {
-    for (n, attribute) in attributes.into_iter().enumerate() {
+    for (n, attribute) in attributes.iter().enumerate() {
         for _ in 0..indent {
             print!("  ");
         }
@@ -198,10 +198,7 @@ impl Constant {
         })
     }
     const fn is_wide(&self) -> bool {
-        match self {
-            Self::Double { .. } | Self::Long { .. } => true,
-            _ => false,
-        }
+        matches!(self, Self::Double { .. } | Self::Long { .. })
     }
     
     fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
@@ -281,7 +278,7 @@ impl Constant {
             Self::Utf8(ref s) => {
                 writer.write_all(&[1])?;
                 writer.write_all(&(s.len() as u16).to_be_bytes())?;
-                write_modified_utf8(writer, &s)?;
+                write_modified_utf8(writer, s)?;
             },
             Self::MethodHandle {
                 reference_kind,
@@ -408,8 +405,7 @@ impl RawAttribute {
             attribute_name_index: reader.read_u16()?,
             info: {
                 let attribute_length = reader.read_u32()?;
-                let info = reader.read_bytes(attribute_length as usize)?;
-                info
+                reader.read_bytes(attribute_length as usize)?
             }
         })
     }
@@ -620,6 +616,39 @@ impl AttributeInfo {
                 }
                 "Code"
             }
+            AttributeInfo::InnerClasses(classes) => {
+                info.extend((classes.len() as u16).to_be_bytes());
+                for class in classes {
+                    info.extend(class.inner_class_info_index.to_be_bytes());
+                    info.extend(class.outer_class_info_index.to_be_bytes());
+                    info.extend(class.inner_name_index.to_be_bytes());
+                    info.extend(class.inner_class_access_flags.bits().to_be_bytes());
+                }
+                "InnerClasses"
+            }
+            AttributeInfo::SourceFile { sourcefile_index } => {
+                info.extend(sourcefile_index.to_be_bytes());
+                "SourceFile"
+            }
+            AttributeInfo::LineNumberTable(line_numbers) => {
+                info.extend((line_numbers.len() as u16).to_be_bytes());
+                for entry in line_numbers {
+                    info.extend(entry.start_pc.to_be_bytes());
+                    info.extend(entry.line_number.to_be_bytes());
+                }
+                "LineNumberTable"
+            }
+            AttributeInfo::BootstrapMethods(methods) => {
+                info.extend((methods.len() as u16).to_be_bytes());
+                for method in methods {
+                    info.extend(method.bootstrap_method_ref.to_be_bytes());
+                    info.extend((method.bootstrap_arguments.len() as u16).to_be_bytes());
+                    for argument in &method.bootstrap_arguments {
+                        info.extend(argument.to_be_bytes());
+                    }
+                }
+                "BootstrapMethods"
+            }
             _ => todo!(),
         };
 
@@ -770,8 +799,8 @@ pub struct InnerClass {
 }
 #[derive(Debug, Clone, PartialEq)]
 pub struct BootstrapMethod {
-    bootstrap_method_ref: ConstIndex,
-    bootstrap_arguments: Box<[ConstIndex]>,
+    pub bootstrap_method_ref: ConstIndex,
+    pub bootstrap_arguments: Box<[ConstIndex]>,
 }
 #[derive(Clone, PartialEq)]
 pub struct RawBytes(pub Box<[u8]>);
@@ -846,7 +875,7 @@ impl Display for FieldAccess {
             write!(f, "{space}{}", name.to_lowercase())?;
             space = " ";
         }
-        if space == "" {
+        if space.is_empty() {
             write!(f, "bare")?;
         }
         Ok(())
@@ -860,7 +889,7 @@ impl Display for MethodAccess {
             write!(f, "{space}{}", name.to_lowercase())?;
             space = " ";
         }
-        if space == "" {
+        if space.is_empty() {
             write!(f, "bare")?;
         }
         Ok(())
@@ -874,7 +903,7 @@ impl Display for ClassAccess {
             write!(f, "{space}{}", name.to_lowercase())?;
             space = " ";
         }
-        if space == "" {
+        if space.is_empty() {
             write!(f, "bare")?;
         }
         Ok(())
@@ -26,7 +26,7 @@ pub fn display_code<'a>(raw_code: &'a [u8], constant_pool: &'a [Constant], ident
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
-enum PrimitiveArrayType {
+pub(crate) enum PrimitiveArrayType {
     Boolean = 4,
     Char = 5,
     Float = 6,
@@ -36,6 +36,20 @@ enum PrimitiveArrayType {
     Int = 10,
     Long = 11,
 }
+impl PrimitiveArrayType {
+    pub(crate) const fn array_class_name(self) -> &'static str {
+        match self {
+            PrimitiveArrayType::Boolean => "[Z",
+            PrimitiveArrayType::Char => "[C",
+            PrimitiveArrayType::Float => "[F",
+            PrimitiveArrayType::Double => "[D",
+            PrimitiveArrayType::Byte => "[B",
+            PrimitiveArrayType::Short => "[S",
+            PrimitiveArrayType::Int => "[I",
+            PrimitiveArrayType::Long => "[J",
+        }
+    }
+}
 
 impl Display for DisplayCode<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -57,6 +71,12 @@ impl Display for DisplayCode<'_> {
     }
 }
 
+/// The padding after a `tableswitch` or `lookupswitch` opcode at `pc`, its operands start at a
+/// multiple of four bytes from the start of the method
+pub(crate) const fn switch_padding(pc: usize) -> usize {
+    3 - pc % 4
+}
+
 fn extract_u16(bytes: &mut impl Iterator<Item=(usize, u8)>) -> Option<(usize, u16)> {
     let (n, b1) = bytes.next()?;
     let (_, b2) = bytes.next()?;
@@ -110,7 +130,7 @@ fn display_arg_const_index_byte(f: &mut fmt::Formatter<'_>, bytes: &mut impl Ite
     display_const_as_comment(f, index as u16, constant_pool)
 }
 fn display_const_as_comment(f: &mut fmt::Formatter<'_>, index: u16, constant_pool: &[Constant]) -> fmt::Result {
-    write!(f, " \t// {}", display_constant(index as u16, constant_pool))
+    write!(f, " \t// {}", display_constant(index, constant_pool))
 }
 fn display_arg(opcode: Opcode, f: &mut fmt::Formatter<'_>, bytes: &mut impl Iterator<Item=(usize, u8)>, i: usize, constant_pool: &[Constant]) -> fmt::Result {
     use Opcode::*;
@@ -42,7 +42,7 @@ pub enum FieldDescriptor {
 }
 impl FieldDescriptor {
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, DescriptorError> {
-        Ok(match bytes.get(0).ok_or(DescriptorError::Empty)? {
+        Ok(match bytes.first().ok_or(DescriptorError::Empty)? {
             b'B' => Self::Byte,
             b'C' => Self::Char,
             b'D' => Self::Double,
@@ -91,9 +91,24 @@ impl FieldDescriptor {
             FieldDescriptor::Long => 2,
         }
     }
-    pub fn display_type<'a>(&'a self) -> DisplayType<'a> {
+    /// Size in bytes of a field of this type in an object or array
+    pub fn byte_size(&self) -> u16 {
+        match self {
+            FieldDescriptor::Boolean |
+            FieldDescriptor::Byte => 1,
+            FieldDescriptor::Char |
+            FieldDescriptor::Short => 2,
+            FieldDescriptor::Int |
+            FieldDescriptor::Float |
+            FieldDescriptor::ClassRef(_) |
+            FieldDescriptor::ArrRef(_) => 4,
+            FieldDescriptor::Double |
+            FieldDescriptor::Long => 8,
+        }
+    }
+    pub fn display_type(&self) -> DisplayType<'_> {
         DisplayType(self)
-    } 
+    }
 }
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct MethodDescriptor {
@@ -116,12 +131,12 @@ impl MethodDescriptor {
         }
     }
     pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, DescriptorError> {
-        match bytes.get(0).ok_or(DescriptorError::Empty)? {
+        match bytes.first().ok_or(DescriptorError::Empty)? {
             b'(' => bytes = &bytes[1..],
             _ => return Err(DescriptorError::NoArguments),
         }
         let mut args = Vec::new();
-        while *bytes.get(0).ok_or(DescriptorError::InfiniteArguments)? != b')' {
+        while *bytes.first().ok_or(DescriptorError::InfiniteArguments)? != b')' {
             match FieldDescriptor::from_bytes(bytes) {
                 Ok(arg) => {
                     bytes = &bytes[arg.length()..];
@@ -143,6 +158,36 @@ impl MethodDescriptor {
         DisplayMethodType(name, self)
     } 
 }
+/// Class file syntax, the inverse of [`FieldDescriptor::from_bytes`]
+impl Display for FieldDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldDescriptor::Byte => write!(f, "B"),
+            FieldDescriptor::Char => write!(f, "C"),
+            FieldDescriptor::Double => write!(f, "D"),
+            FieldDescriptor::Float => write!(f, "F"),
+            FieldDescriptor::Int => write!(f, "I"),
+            FieldDescriptor::Long => write!(f, "J"),
+            FieldDescriptor::ClassRef(s) => write!(f, "L{s};"),
+            FieldDescriptor::Short => write!(f, "S"),
+            FieldDescriptor::Boolean => write!(f, "Z"),
+            FieldDescriptor::ArrRef(fd) => write!(f, "[{fd}"),
+        }
+    }
+}
+/// Class file syntax, the inverse of [`MethodDescriptor::from_bytes`]
+impl Display for MethodDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(")?;
+        for arg in &self.arg_types {
+            write!(f, "{arg}")?;
+        }
+        match &self.return_type {
+            Some(t) => write!(f, "){t}"),
+            None => write!(f, ")V"),
+        }
+    }
+}
 pub struct DisplayType<'a>(&'a FieldDescriptor);
 impl Display for DisplayType<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
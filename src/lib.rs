@@ -5,6 +5,7 @@ pub(crate) mod modified_utf8;
 pub mod descriptor;
 pub mod code;
 pub mod rt;
+pub(crate) mod zip;
 
 pub(crate) trait ReadIntExt {
     fn read_u8(&mut self) -> io::Result<u8>;
@@ -1,11 +1,187 @@
-use std::env::args;
+use std::{env::{self, args}, fs::File, io::{BufWriter, Write}, net::TcpListener, path, process::exit};
 
-use jappuccino::rt;
+use jappuccino::rt::{self, ClassPath, JdwpServer, RtError};
+
+mod repl;
+
+const USAGE: &str = "\
+usage: jappuccino [options] <main class> [args...]
+       jappuccino [options] -jar <jar file> [args...]
+options:
+    -cp, -classpath, --class-path <path>    where to search for classes
+    --release <version>                     Java version for multi-release jars
+    --debug                                 stop before the program starts and debug it
+    --profile                               print what the program spent its time on when it ends
+    --profile-stacks <file>                 write the sampled call stacks for flame graphs
+    --profile-interval <instructions>       how often to sample the call stacks, 1 by default
+    --jit                                   compile hot methods to native code, needs a build with
+                                            the jit feature
+    --jit-threshold <invocations>           how often a method runs before it is compiled, 1000 by
+                                            default
+    -agentlib:jdwp=<options>                wait for a JDWP debugger like jdb to attach, the options
+                                            are transport=dt_socket,server=y,suspend=y|n and
+                                            address=[<host>:]<port>, where * is any host";
+
+/// The options of `-agentlib:jdwp`
+struct JdwpOptions {
+    address: String,
+    suspend: bool,
+}
+
+enum Target {
+    Class(String),
+    Jar(String),
+}
 
 fn main() {
     let mut args = args().skip(1);
-    let class = args.next().unwrap();
+    let mut class_path = env::var("CLASSPATH").ok();
+    let mut release = None;
+    let mut debug = false;
+    let mut jdwp = None;
+    let mut profile = false;
+    let mut profile_stacks = None;
+    let mut profile_interval = 1;
+    let mut jit = false;
+    let mut jit_threshold = 1000;
+    let target = loop {
+        match args.next().as_deref() {
+            Some("-cp" | "-classpath" | "--class-path") => class_path = Some(expect_value(args.next())),
+            Some("--release") => match expect_value(args.next()).parse() {
+                Ok(version) => release = Some(version),
+                Err(_) => usage_error(),
+            },
+            Some("--debug") => debug = true,
+            Some("--profile") => profile = true,
+            Some("--profile-stacks") => profile_stacks = Some(expect_value(args.next())),
+            Some("--profile-interval") => match expect_value(args.next()).parse() {
+                Ok(interval) if interval > 0 => profile_interval = interval,
+                _ => usage_error(),
+            },
+            Some("--jit") => jit = true,
+            Some("--jit-threshold") => match expect_value(args.next()).parse() {
+                Ok(threshold) if threshold > 0 => jit_threshold = threshold,
+                _ => usage_error(),
+            },
+            Some(option) if option.starts_with("-agentlib:jdwp=") => jdwp = Some(parse_jdwp_options(&option["-agentlib:jdwp=".len()..])),
+            Some("-jar") => break Target::Jar(expect_value(args.next())),
+            Some(option) if option.starts_with('-') => usage_error(),
+            Some(class) => break Target::Class(class.into()),
+            None => usage_error(),
+        }
+    };
     let args: Box<[_]> = args.map(String::into_boxed_str).collect();
-    let mut rt = rt::Runtime::new();
-    rt.run(&class, args).unwrap();
+    #[cfg(not(feature = "jit"))]
+    if jit {
+        let _ = jit_threshold;
+        fail("--jit needs jappuccino built with the jit feature");
+    }
+
+    let (mut loader, class) = match target {
+        Target::Class(class) => {
+            let loader = match class_path {
+                Some(class_path) => ClassPath::parse(&class_path).unwrap_or_else(|e| fail(&e.to_string())),
+                None => ClassPath::default(),
+            };
+            (loader, class)
+        }
+        Target::Jar(jar) => {
+            // like with java, the class path is only what the jar says
+            let mut loader = ClassPath::new();
+            match loader.push_executable_jar(&jar) {
+                Ok(Some(class)) => (loader, class),
+                Ok(None) => fail(&format!("no Main-Class in the manifest of {jar}")),
+                Err(e) => fail(&e.to_string()),
+            }
+        }
+    };
+    if let Some(release) = release {
+        loader.set_release(release);
+    }
+
+    // like the JVM, wait for the debugger before loading anything
+    let jdwp = jdwp.map(|JdwpOptions { address, suspend }| {
+        let listener = TcpListener::bind(&address).unwrap_or_else(|e| fail(&format!("cannot listen at {address}: {e}")));
+        let port = listener.local_addr().map_or(0, |address| address.port());
+        println!("Listening for transport dt_socket at address: {port}");
+        let server = JdwpServer::accept(&listener, suspend).unwrap_or_else(|e| fail(&format!("debugger failed to attach: {e}")));
+        let class_path = loader.paths().map(|path| path::absolute(path).unwrap_or_else(|_| path.into()).display().to_string());
+        server.with_class_path(class_path)
+    });
+
+    let mut rt = rt::Runtime::new().with_class_path(loader);
+    if debug {
+        rt = rt.with_debugger(repl::Repl::default());
+    }
+    if let Some(server) = jdwp {
+        rt = rt.with_debugger(server);
+    }
+    if profile || profile_stacks.is_some() {
+        rt = rt.with_profiling(profile_interval);
+    }
+    #[cfg(feature = "jit")]
+    if jit {
+        rt = rt.with_jit(jit_threshold);
+    }
+    let result = rt.run(&class.replace('.', "/"), &args);
+    if let Some(report) = rt.profile() {
+        if profile {
+            eprint!("{report}");
+        }
+        if let Some(path) = profile_stacks {
+            let written = File::create(&path).and_then(|file| {
+                let mut out = BufWriter::new(file);
+                report.write_collapsed(&mut out)?;
+                out.flush()
+            });
+            if let Err(e) = written {
+                fail(&format!("cannot write {path}: {e}"));
+            }
+        }
+    }
+    match result {
+        Ok(status) => exit(status),
+        Err(RtError::UncaughtException { class_name, message }) => {
+            match message {
+                Some(message) => eprintln!("Exception in thread \"main\" {class_name}: {message}"),
+                None => eprintln!("Exception in thread \"main\" {class_name}"),
+            }
+            exit(1);
+        }
+        Err(e) => fail(&e.to_string()),
+    }
+}
+
+/// Parses the options of `-agentlib:jdwp`, only listening for a debugger over TCP is supported
+fn parse_jdwp_options(options: &str) -> JdwpOptions {
+    let mut address = None;
+    let mut suspend = true;
+    for option in options.split(',') {
+        match option.split_once('=') {
+            Some(("transport", "dt_socket") | ("server", "y")) => (),
+            Some(("suspend", "y")) => suspend = true,
+            Some(("suspend", "n")) => suspend = false,
+            Some(("address", value)) => address = Some(match value.rsplit_once(':') {
+                Some(("*", port)) => format!("0.0.0.0:{port}"),
+                Some(_) => value.into(),
+                None => format!("127.0.0.1:{value}"),
+            }),
+            _ => usage_error(),
+        }
+    }
+    JdwpOptions { address: address.unwrap_or_else(|| usage_error()), suspend }
+}
+
+fn expect_value(value: Option<String>) -> String {
+    value.unwrap_or_else(|| usage_error())
+}
+
+fn usage_error() -> ! {
+    eprintln!("{USAGE}");
+    exit(2);
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {message}");
+    exit(1);
 }
@@ -0,0 +1,230 @@
+//! The debugger of `--debug`, which reads commands from the standard input whenever the program
+//! stops
+
+use std::io::{self, BufRead, Write};
+
+use jappuccino::rt::{Breakpoint, DebugSession, Debugger, JValue, Resume, Result, RtError, StopReason, Value};
+
+const HELP: &str = "\
+break <class>:<line>                stop at a line
+break <class>.<method>[@<pc>]       stop when a method is entered, or at an instruction of it
+delete <n>                          remove breakpoint n
+breakpoints                         list the breakpoints
+continue, c                         run until a breakpoint
+step, s                             run to the next line, stepping into calls
+next, n                             run to the next line of this method
+finish                              run until this method returns
+stepi                               run one instruction
+backtrace, bt                       show the frames of the thread
+frame <n>                           select the frame locals, stack and print look at
+locals                              show the local variables
+stack                               show the operand stack
+print, p <name>[.<field>|[<i>]]...  show a local variable, a field of this or a static field
+fields <name>[.<field>|[<i>]]...    show the fields of an object or the elements of an array
+quit, q                             end the program";
+
+#[derive(Default)]
+pub struct Repl {
+    /// The frame locals, stack and print look at
+    frame: usize,
+    /// Set once the input ended, the program then runs to its end
+    detached: bool,
+}
+
+impl Debugger for Repl {
+    fn stopped(&mut self, session: &mut DebugSession<'_, '_>, reason: StopReason) -> Result<Resume> {
+        if self.detached {
+            return Ok(Resume::Continue);
+        }
+        self.frame = 0;
+        match reason {
+            StopReason::Start => println!("stopped before the program starts, type help for the commands"),
+            StopReason::Breakpoint(id) => println!("breakpoint {id} hit"),
+            _ => (),
+        }
+        self.show_frame(session);
+        let mut stdin = io::stdin().lock();
+        loop {
+            print!("> ");
+            io::stdout().flush()?;
+            let mut line = String::new();
+            if stdin.read_line(&mut line)? == 0 {
+                println!();
+                self.detached = true;
+                return Ok(Resume::Continue);
+            }
+            let (command, arg) = line.trim().split_once(char::is_whitespace).unwrap_or((line.trim(), ""));
+            let arg = arg.trim();
+            match command {
+                "" => (),
+                "continue" | "c" => return Ok(Resume::Continue),
+                "step" | "s" => return Ok(Resume::StepInto),
+                "next" | "n" => return Ok(Resume::StepOver),
+                "finish" => return Ok(Resume::StepOut),
+                "stepi" => return Ok(Resume::StepInstruction),
+                "break" | "b" => match arg.parse::<Breakpoint>() {
+                    Ok(breakpoint) => {
+                        let text = breakpoint.to_string();
+                        println!("breakpoint {} at {text}", session.add_breakpoint(breakpoint));
+                    }
+                    Err(e) => println!("{e}"),
+                },
+                "delete" => match arg.parse() {
+                    Ok(id) => match session.remove_breakpoint(id) {
+                        Some(breakpoint) => println!("deleted breakpoint {id} at {breakpoint}"),
+                        None => println!("no breakpoint {id}"),
+                    },
+                    Err(_) => println!("expected a breakpoint number"),
+                },
+                "breakpoints" => {
+                    let mut breakpoints = session.breakpoints().peekable();
+                    if breakpoints.peek().is_none() {
+                        println!("no breakpoints");
+                    }
+                    for (id, breakpoint) in breakpoints {
+                        println!("{id}: {breakpoint}");
+                    }
+                }
+                "backtrace" | "bt" => {
+                    for (i, frame) in session.backtrace().iter().enumerate() {
+                        let marker = if i == self.frame { '*' } else { ' ' };
+                        println!("{marker} #{i} {frame}, pc {}", frame.pc);
+                    }
+                }
+                "frame" => match arg.parse() {
+                    Ok(frame) if frame < session.backtrace().len() => {
+                        self.frame = frame;
+                        self.show_frame(session);
+                    }
+                    _ => println!("expected a frame number from the backtrace"),
+                },
+                "locals" => self.show_locals(session),
+                "stack" => {
+                    let stack = session.operand_stack(self.frame).unwrap_or_default();
+                    if stack.is_empty() {
+                        println!("the operand stack is empty");
+                    }
+                    for (i, value) in stack.iter().enumerate() {
+                        println!("[{i}] {} ({:#010x})", value.into_i32(), value.into_u32());
+                    }
+                }
+                "print" | "p" => match self.evaluate(session, arg) {
+                    Ok(value) => println!("{arg} = {}", session.describe(value)),
+                    Err(e) => println!("{e}"),
+                },
+                "fields" => match self.evaluate(session, arg) {
+                    Ok(JValue::Reference(object)) if object != Value::NULL => {
+                        for (name, value) in session.fields(object) {
+                            println!("{name} = {}", session.describe(value));
+                        }
+                    }
+                    Ok(value) => println!("{arg} = {} has no fields", session.describe(value)),
+                    Err(e) => println!("{e}"),
+                },
+                "help" => println!("{HELP}"),
+                "quit" | "q" => return Err(RtError::Exit(1)),
+                _ => println!("unknown command {command}, type help for the commands"),
+            }
+        }
+    }
+}
+
+/// A step in the expression of `print`
+enum Access<'a> {
+    Field(&'a str),
+    Index(u32),
+}
+
+impl Repl {
+    fn show_frame(&self, session: &DebugSession) {
+        if let Some(frame) = session.backtrace().get(self.frame) {
+            println!("thread \"{}\" at {frame}, pc {}", session.thread_name(session.selected_thread()), frame.pc);
+        }
+    }
+    fn show_locals(&self, session: &DebugSession) {
+        let locals = session.locals(self.frame).unwrap_or_default();
+        if locals.iter().any(|local| local.name.is_none()) {
+            println!("no names for the local variables, compile with -g to have them");
+        }
+        for local in locals {
+            match local.name {
+                Some(name) => println!("{name} = {}", session.describe(local.value)),
+                None => println!("slot {} = {}", local.slot, session.describe(local.value)),
+            }
+        }
+    }
+    /// The value of a local variable, a field of `this` or a static field of the class of the
+    /// frame or a named class, followed by field accesses and array indices
+    fn evaluate(&self, session: &DebugSession, expression: &str) -> Result<JValue, String> {
+        let accesses = parse_accesses(expression).ok_or_else(|| format!("cannot parse {expression:?}"))?;
+        let Some((Access::Field(name), mut rest)) = accesses.split_first() else {
+            return Err(format!("{expression:?} does not start with a name"));
+        };
+        let locals = session.locals(self.frame).unwrap_or_default();
+        let local = |name: &str| locals.iter().find(|local| local.name.as_deref() == Some(name)).map(|local| local.value);
+        let this_field = || match local("this")? {
+            JValue::Reference(this) => find(session.fields(this), name),
+            _ => None,
+        };
+        let frame_class = session.backtrace().get(self.frame).map(|frame| frame.class.clone()).unwrap_or_default();
+        let mut value = match local(name).or_else(this_field).or_else(|| find(session.static_fields(&frame_class)?, name)) {
+            Some(value) => value,
+            None => {
+                // a class name with dots followed by a static field
+                let mut class = name.to_string();
+                loop {
+                    let Some((Access::Field(field), after)) = rest.split_first() else {
+                        return Err(format!("no local variable, field or class {name}"));
+                    };
+                    rest = after;
+                    if let Some(value) = session.static_fields(&class).and_then(|fields| find(fields, field)) {
+                        break value;
+                    }
+                    class = format!("{class}.{field}");
+                }
+            }
+        };
+        for access in rest {
+            let JValue::Reference(object) = value else {
+                return Err(format!("{} is not an object", session.describe(value)));
+            };
+            if object == Value::NULL {
+                return Err("null has no fields".into());
+            }
+            let name = match access {
+                Access::Field(field) => field.to_string(),
+                Access::Index(i) => format!("[{i}]"),
+            };
+            value = find(session.fields(object), &name).ok_or_else(|| format!("{} has no {name}", session.describe(value)))?;
+        }
+        Ok(value)
+    }
+}
+
+fn find(fields: Vec<(Box<str>, JValue)>, name: &str) -> Option<JValue> {
+    fields.into_iter().find(|(field, _)| **field == *name).map(|(_, value)| value)
+}
+
+/// Splits `a.b[1].c` into its names and indices
+fn parse_accesses(expression: &str) -> Option<Vec<Access<'_>>> {
+    let mut accesses = Vec::new();
+    let mut rest = expression;
+    while !rest.is_empty() {
+        if let Some(index) = rest.strip_prefix('[') {
+            let (index, after) = index.split_once(']')?;
+            accesses.push(Access::Index(index.trim().parse().ok()?));
+            rest = after;
+            continue;
+        }
+        if !accesses.is_empty() {
+            rest = rest.strip_prefix('.')?;
+        }
+        let end = rest.find(['.', '[']).unwrap_or(rest.len());
+        if end == 0 {
+            return None;
+        }
+        accesses.push(Access::Field(&rest[..end]));
+        rest = &rest[end..];
+    }
+    Some(accesses)
+}
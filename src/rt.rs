@@ -1,27 +1,146 @@
-use std::{cmp::{Ordering, Reverse}, collections::BTreeMap, fs::File, io::{self, BufReader}, iter, mem::transmute, path::Path, str::from_utf8_unchecked};
+use std::{cmp::Reverse, collections::BTreeMap, fmt::{self, Display}, io, mem::transmute, rc::Rc, str::from_utf8_unchecked, time::{Duration, Instant}};
 
-use crate::{class::{AttributeInfo, ClassFile, ConstIndex, Constant, FieldAccess, MethodAccess}, code::opcode::Opcode, descriptor::{DescriptorError, FieldDescriptor, MethodDescriptor}};
+use crate::{class::{AttributeInfo, ClassAccess, ClassFile, ConstIndex, Constant, ExceptionEntry, FieldAccess, MethodAccess}, code::PrimitiveArrayType, descriptor::{AnyDescriptor, DescriptorError, FieldDescriptor, MethodDescriptor}};
 
+mod arith;
 mod bytes;
 mod builtin_methods;
+mod classpath;
+mod corelib;
+mod debug;
+mod embed;
+mod format;
+mod gc;
+mod header;
+mod indy;
+mod jdwp;
+#[cfg(feature = "jit")]
+mod jit;
+mod lambda;
+mod layout;
+mod limits;
+mod link;
+mod loader;
+mod native;
+mod profile;
+mod resolve;
+mod stdio;
+mod string;
+mod thread;
+#[cfg(test)]
+mod tests;
 
 pub use bytes::*;
+pub use classpath::ClassPath;
+pub use debug::{Breakpoint, ClassInfo, DebugSession, DeclaredField, Debugger, FrameInfo, Local, LocalVariable, Location, MethodInfo, ParseBreakpointError, Resume, StopReason, ThreadStatus};
+pub use embed::{FromJava, JavaArgs, ToJava};
+pub use jdwp::JdwpServer;
+#[cfg(feature = "jit")]
+pub use jit::JitStats;
+pub use limits::Limit;
+pub use loader::{ClassLoader, ClassLoaderChain, Delegating};
+pub use native::{JValue, NativeArgs, NativeFn};
+pub use profile::{AllocationProfile, MethodProfile, Profile};
+pub use stdio::SharedBuffer;
+pub use gc::GcStats;
+pub use header::{HeaderFlags, ObjectHeader, OBJECT_HEADER_SIZE};
+use self::{debug::{method_debug_info, ClassDebugInfo, DebugState, MethodDebugInfo, SharedDebugger}, gc::GcState, header::HashGenerator, indy::CallSite, layout::FieldLayout, limits::Limits, link::{Decoded, Instruction, Switch, ValueKind, VirtualCall}, loader::SharedLoader, native::{NativeMethod, NativeRegistry, NATIVE_METHOD_BASE}, resolve::Resolution, stdio::Stdio, thread::{Scheduler, THREAD_DAEMON_OFFSET, THREAD_NAME_OFFSET, THREAD_TARGET_OFFSET}, string::{Coder, STRING_CODER_OFFSET, STRING_DATA_OFFSET, STRING_LENGTH_OFFSET}};
 use collect_result::CollectResult;
-use num_enum::FromPrimitive;
 pub type Result<T, E=RtError> = std::result::Result<T, E>;
 
+/// Class ids that are fixed by `Runtime::new`
+pub const OBJECT_CLASS: u32 = 0;
+pub const STRING_CLASS: u32 = 1;
+pub const CLASS_CLASS: u32 = 2;
+
+/// Offset of the length in an array object, the elements follow right after it
+const ARRAY_LENGTH_OFFSET: u32 = OBJECT_HEADER_SIZE as u32;
+const ARRAY_DATA_OFFSET: u32 = ARRAY_LENGTH_OFFSET + 4;
+
 #[derive(Debug)]
 pub struct RuntimeCtx<'a> {
     runtime: &'a mut Runtime,
     stack: Vec<Value>,
-    return_stack: Vec<(u32, u32, usize, u16)>,
+    return_stack: Vec<Frame>,
     frame_pointer: u32,
     max_locals: u16,
     cur_class: u32,
+    cur_method: u16,
 
     heap: Vec<u32>,
+    gc: GcState,
+
+    pc: usize,
+    /// Depth of the return stack at which exceptions stop unwinding, set while [`Self::run_method`]
+    /// runs a method for native code
+    barrier: Option<usize>,
+    /// An exception that reached the barrier
+    pending_exception: Option<Value>,
+    /// References returned to the embedder, which the collector keeps in place
+    host_refs: Vec<Value>,
+
+    /// Instructions that can still be executed, `None` if unlimited
+    fuel: Option<u64>,
+    deadline: Option<Instant>,
+    instructions: u64,
+    /// Whether the last run stopped at a limit and can be resumed
+    suspended: bool,
+    /// Number of runs for native code inside each other, each takes space on the native stack
+    nested_runs: usize,
 
+    sched: Scheduler,
+    /// Monitors of the synchronized methods running in the current thread, by the depth of the
+    /// return stack in their frame
+    locked_frames: Vec<(usize, Value)>,
+    pending_main: Option<PendingMain>,
+    /// Breakpoints and steps, if the runtime has a debugger
+    debug: Option<Box<DebugState>>,
+}
+/// The class and arguments of a `main` that [`RuntimeCtx::run`] has not invoked yet
+#[derive(Debug, Clone)]
+struct PendingMain {
+    class: Box<str>,
+    args: Box<[Box<str>]>,
+}
+/// The state of a caller saved when a method is invoked
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    class: u32,
+    method: u16,
+    frame_pointer: u32,
     pc: usize,
+    max_locals: u16,
+}
+impl<'a> RuntimeCtx<'a> {
+    fn new(runtime: &'a mut Runtime) -> Self {
+        if let Some(profile) = &mut runtime.profile {
+            profile.start_run();
+        }
+        RuntimeCtx {
+            frame_pointer: 0,
+            pc: 0,
+            max_locals: 0,
+            cur_class: 0,
+            cur_method: 0,
+            stack: Vec::new(),
+            heap: Vec::new(),
+            gc: GcState::new(),
+            return_stack: Vec::with_capacity(1),
+            barrier: None,
+            pending_exception: None,
+            host_refs: Vec::new(),
+            fuel: runtime.limits.fuel,
+            deadline: runtime.limits.time_limit.map(|limit| Instant::now() + limit),
+            instructions: 0,
+            suspended: false,
+            nested_runs: 0,
+            sched: Scheduler::new(runtime.time_slice),
+            locked_frames: Vec::new(),
+            pending_main: None,
+            debug: runtime.debugger.clone().map(|debugger| Box::new(DebugState::new(debugger))),
+            runtime,
+        }
+    }
 }
 impl RuntimeCtx<'_> {
     pub fn top(&self) -> Value {
@@ -33,94 +152,289 @@ impl RuntimeCtx<'_> {
     }
     pub fn pop(&mut self) -> Value {
         assert!(self.stack.len() > (self.frame_pointer + self.max_locals as u32) as usize);
-        let val = self.stack.pop().unwrap();
-        eprintln!("pop {val:?}");
-        val
+        self.stack.pop().unwrap()
+    }
+    /// Copies the `count` words on top of the operand stack below the `depth` words on top. The
+    /// forms of the `dup` instructions for category 2 values copy as many words as the ones for
+    /// category 1 values, so the words of a `long` or `double` stay together.
+    fn dup_below(&mut self, count: usize, depth: usize) {
+        let len = self.stack.len();
+        assert!(len >= depth + (self.frame_pointer + self.max_locals as u32) as usize);
+        self.stack.extend_from_within(len - count..);
+        self.stack[len - depth..].rotate_right(count);
     }
     pub fn pop2(&mut self) -> (Value, Value) {
         assert!(self.stack.len() > 1 + (self.frame_pointer + self.max_locals as u32) as usize);
         let v2 = self.stack.pop().unwrap();
-        let val = (self.stack.pop().unwrap(), v2);
-        eprintln!("pop {val:?}");
-        val
+        (self.stack.pop().unwrap(), v2)
     }
     pub fn push(&mut self, value: impl Into<Value>) {
-        let val = value.into();
-        eprintln!("push {val:?}");
-        self.stack.push(val);
+        self.stack.push(value.into());
     }
     pub fn push2(&mut self, value: impl Into<(Value, Value)>) {
         let (v1, v2) = value.into();
-        eprintln!("push {:?}", (v1, v2));
         self.stack.push(v1);
         self.stack.push(v2);
     }
     pub fn get_local(&self, i: u16) -> Value {
         assert!(i < self.max_locals);
-        let val = self.stack[self.frame_pointer as usize + i as usize];
-        eprintln!("get {i} {val:?}");
-        val
+        self.stack[self.frame_pointer as usize + i as usize]
     }
     pub fn get_local2(&self, i: u16) -> (Value, Value) {
         assert!(i < self.max_locals);
         let i = self.frame_pointer as usize + i as usize;
-        let val = (self.stack[i], self.stack[i + 1]);
-        eprintln!("get {i} {val:?}");
-        val
+        (self.stack[i], self.stack[i + 1])
     }
     pub fn set_local(&mut self, i: u16, value: impl Into<Value>) {
         assert!(i < self.max_locals);
-        let val = value.into();
-        eprintln!("set {i} {val:?}");
-        self.stack[self.frame_pointer as usize + i as usize] = val;
+        self.stack[self.frame_pointer as usize + i as usize] = value.into();
     }
     pub fn set_local2(&mut self, i: u16, value: impl Into<(Value, Value)>) {
         assert!(i < self.max_locals);
         let i = self.frame_pointer as usize + i as usize;
         let (v1, v2) = value.into();
-        eprintln!("set {i} {:?}", (v1, v2));
         self.stack[i] = v1;
         self.stack[i + 1] = v2;
     }
-    pub fn get_class_name(&self, _reference: Value) -> &str {
-        todo!()
+    pub fn get_header(&self, reference: Value) -> ObjectHeader {
+        ObjectHeader {
+            class_id: self.read_u32_ref(reference).unwrap(),
+            word: self.read_u32_ref(reference.offset(4)).unwrap(),
+        }
     }
-    pub fn get_class_object(&self, _reference: Value) -> Value {
-        todo!()
+    fn set_header(&mut self, reference: Value, header: ObjectHeader) {
+        let [class_id, word] = header.to_words();
+        self.write_u32_ref(reference, class_id);
+        self.write_u32_ref(reference.offset(4), word);
+    }
+    pub fn get_class_id(&self, reference: Value) -> u32 {
+        self.get_header(reference).class_id
+    }
+    pub fn get_class_name(&self, reference: Value) -> &str {
+        &self.runtime.get_class(self.get_class_id(reference)).name
+    }
+    pub fn get_class_object(&self, reference: Value) -> Value {
+        Value::new_ref_static(self.runtime.get_class(self.get_class_id(reference)).class_object)
+    }
+    /// Returns the identity hash of the object, assigning one on first use
+    pub fn identity_hash(&mut self, reference: Value) -> u32 {
+        let header = self.get_header(reference);
+        match header.identity_hash() {
+            Some(hash) => hash,
+            None => {
+                let hash = self.runtime.hashes.next_hash();
+                self.set_header(reference, header.with_identity_hash(hash));
+                hash
+            }
+        }
+    }
+    /// Whether a non-null reference is an instance of the given class, see [`Runtime::is_assignable`]
+    pub fn is_instance_of(&self, reference: Value, class: u32) -> bool {
+        self.runtime.is_assignable(self.get_class_id(reference), class)
+    }
+    pub fn new_string_obj(&mut self, s: impl Into<Box<str>>) -> Value {
+        let chars: Vec<_> = s.into().encode_utf16().collect();
+        self.new_string_from_chars(&chars)
+    }
+    pub fn new_string_from_chars(&mut self, chars: &[u16]) -> Value {
+        let (coder, bytes) = string::encode(chars);
+        let r = self.alloc(STRING_CLASS, coder.object_size(chars.len() as u32) as usize);
+        self.write_u32_ref(r.offset(STRING_LENGTH_OFFSET), chars.len() as u32);
+        self.write_u8_ref(r.offset(STRING_CODER_OFFSET), coder as u8);
+        let Reference::Heap(offset) = r.into_ref() else { unreachable!() };
+        let start = (offset + STRING_DATA_OFFSET) as usize;
+        self.heap.as_bytes_32aligned_mut()[start..start + bytes.len()].copy_from_slice(&bytes);
+        r
+    }
+    /// Creates a string for a native method once [`Self::make_room`] found room for it, `None`
+    /// means an `OutOfMemoryError` was thrown.
+    ///
+    /// This can collect garbage, so the arguments should be popped with their content read first.
+    fn try_new_string(&mut self, chars: &[u16]) -> Result<Option<Value>> {
+        let size = string::coder(chars).object_size(chars.len() as u32);
+        if !self.make_room(size as usize)? {
+            return Ok(None);
+        }
+        Ok(Some(self.new_string_from_chars(chars)))
+    }
+    fn try_new_string_obj(&mut self, s: &str) -> Result<Option<Value>> {
+        self.try_new_string(&s.encode_utf16().collect::<Vec<_>>())
+    }
+    /// The UTF-16 code units of a string object, `None` for null
+    pub fn read_string_chars(&self, sref: Value) -> Option<Vec<u16>> {
+        if sref == Value::NULL {
+            return None;
+        }
+        let length = self.read_u32_ref(sref.offset(STRING_LENGTH_OFFSET))?;
+        let coder = Coder::from_u8(self.read_u8_ref(sref.offset(STRING_CODER_OFFSET))?);
+        let bytes = self.ref_bytes(sref.offset(STRING_DATA_OFFSET), (length << coder as u32) as usize)?;
+        Some(string::decode(coder, bytes))
+    }
+    /// `String.valueOf(Object)` for a reference: "null", the string itself or what `toString()`
+    /// returns.
+    ///
+    /// If `toString()` throws, the exception is thrown on and `None` is returned.
+    pub fn string_value_of(&mut self, obj: Value) -> Result<Option<Vec<u16>>> {
+        if obj == Value::NULL {
+            return Ok(Some("null".encode_utf16().collect()));
+        }
+        let class = self.get_class_id(obj);
+        if class == STRING_CLASS {
+            return Ok(self.read_string_chars(obj));
+        }
+        let to_string = MethodDescriptor::new_ret([], FieldDescriptor::ClassRef("java/lang/String".into()));
+        let (class, method_id) = self.runtime.find_method(class, "toString", &to_string.into()).unwrap();
+        self.push(obj);
+        match self.run_method(class, method_id)? {
+            Ok(()) => {
+                let string = self.pop();
+                Ok(Some(self.read_string_chars(string).unwrap_or_else(|| "null".encode_utf16().collect())))
+            }
+            Err(exception) => {
+                self.throw(exception)?;
+                Ok(None)
+            }
+        }
     }
-    pub fn new_string_obj(&self, _s: impl Into<Box<str>>) -> Value {
-        todo!()
+    /// The content of a string object, `None` for null
+    pub fn read_string_object(&self, sref: Value) -> Option<String> {
+        Some(String::from_utf16_lossy(&self.read_string_chars(sref)?))
     }
     pub fn call_named(&mut self, classpath: &str, method_name: &str, method_type: MethodDescriptor) -> Result<()> {
         let id = self.runtime.load_class(classpath)?;
-        let method_id = self.runtime.classes[id as usize].member_table()[(method_name, &method_type.into())];
-        self.invoke(id, method_id);
-        Ok(())
+        let (class, method_id) = self.runtime.find_method(id, method_name, &method_type.into()).unwrap();
+        self.invoke(class, method_id)
+    }
+    /// Initializes a class and runs its `main` method with the arguments as a `String[]`.
+    ///
+    /// Returns the exit status, 0 if `main` returns and the status passed to `System.exit`. A run
+    /// that stops at a limit of the runtime can be continued with [`Self::resume`].
+    pub fn run(&mut self, classpath: &str, args: &[Box<str>]) -> Result<i32> {
+        self.pending_main = Some(PendingMain { class: classpath.into(), args: args.into() });
+        let result = self.run_main();
+        self.finish_run(result)
+    }
+    fn finish_run(&mut self, result: Result<()>) -> Result<i32> {
+        self.runtime.stdio.flush_all()?;
+        let result = match result {
+            Ok(()) => Ok(0),
+            Err(RtError::Exit(status)) => Ok(status),
+            Err(e) => Err(e),
+        };
+        self.debug_finished(&result);
+        result
+    }
+    /// Invokes the pending `main` once its class is initialized, then runs until all threads are done
+    fn run_main(&mut self) -> Result<()> {
+        if let Some(PendingMain { class: classpath, args }) = self.pending_main.clone() {
+            let class = self.runtime.load_class(&classpath)?;
+            while !self.ensure_initialized(class, self.pc)? {
+                self.run_until(0)?;
+            }
+            self.pending_main = None;
+            let string_array = self.runtime.load_array_class(STRING_CLASS)?;
+            let array = self.new_array(string_array, args.len() as u32);
+            self.push(array);
+            for (i, arg) in args.iter().enumerate() {
+                let string = self.new_string_obj(&**arg);
+                self.write_u32_ref(array.offset(ARRAY_DATA_OFFSET + 4 * i as u32), string.into_u32());
+            }
+            self.call_named(&classpath, "main", MethodDescriptor::new_void([
+                FieldDescriptor::ArrRef(Box::new(FieldDescriptor::ClassRef("java/lang/String".into())))
+            ]))?;
+        }
+        self.run_threads()
+    }
+    /// Invokes a method with its arguments already pushed and runs it until it returns.
+    ///
+    /// An exception the method does not catch stops at this call instead of unwinding the frames
+    /// of the caller and is returned as the inner `Err`.
+    pub fn run_method(&mut self, class: u32, method_id: u16) -> Result<Result<(), Value>> {
+        let depth = self.return_stack.len();
+        self.run_guarded(|ctx| ctx.invoke(class, method_id).and_then(|()| ctx.run_until(depth)))
+    }
+    /// Runs `f` with exceptions stopping at the current frame, an exception that reaches it is
+    /// returned as the inner `Err`. The frames `f` leaves behind when it fails are dropped.
+    fn run_guarded(&mut self, f: impl FnOnce(&mut Self) -> Result<()>) -> Result<Result<(), Value>> {
+        let depth = self.return_stack.len();
+        let outer_barrier = self.barrier.replace(depth);
+        let outer_owner = self.enter_nested_run();
+        self.nested_runs += 1;
+        let result = f(self);
+        self.nested_runs -= 1;
+        self.leave_nested_run(outer_owner);
+        self.barrier = outer_barrier;
+        if result.is_err() {
+            self.abandon_frames(depth);
+        }
+        result?;
+        Ok(match self.pending_exception.take() {
+            Some(exception) => Err(exception),
+            None => Ok(()),
+        })
     }
-    pub fn invoke(&mut self, class: u32, method_id: u16) {
+    pub fn invoke(&mut self, class: u32, method_id: u16) -> Result<()> {
         match self.runtime.classes[class as usize].method(method_id) {
-            Err(f) => f(self),
-            Ok(bm) => {
-                self.stack.reserve((bm.max_locals - bm.arg_num) as usize + bm.max_stack as usize);
-                self.do_call(class, bm.arg_num, bm.max_locals, bm.code_location);
+            MethodImpl::Builtin(f) => f(self),
+            MethodImpl::Native(native) => self.call_native(&native),
+            MethodImpl::Bytecode(bm) => {
+                let &BytecodeMethod { max_stack, max_locals, arg_num, code_location, access_flags, .. } = bm;
+                if self.call_depth_exceeded() {
+                    self.stack.truncate(self.stack.len() - arg_num as usize);
+                    return self.throw_new("java/lang/StackOverflowError", None);
+                }
+                #[cfg(feature = "jit")]
+                if self.runtime.jit.is_some() && self.run_compiled(class, method_id)? {
+                    return Ok(());
+                }
+                self.stack.reserve((max_locals - arg_num) as usize + max_stack as usize);
+                self.do_call(class, method_id, arg_num, max_locals, code_location);
+                if access_flags.contains(MethodAccess::SYNCHRONIZED) {
+                    let monitor = if access_flags.contains(MethodAccess::STATIC) {
+                        Value::new_ref_static(self.runtime.get_class(class).class_object)
+                    } else {
+                        self.stack[self.frame_pointer as usize]
+                    };
+                    // a thread that has to wait for the monitor starts running the method once it owns it
+                    self.locked_frames.push((self.return_stack.len(), monitor));
+                    self.monitor_enter(monitor);
+                }
+                Ok(())
             }
         }
     }
-    pub fn do_call(&mut self, class: u32, arg_num: u16, max_locals: u16, location: usize) {
-        self.return_stack.push((self.cur_class, self.frame_pointer, self.pc, self.max_locals));
+    pub fn do_call(&mut self, class: u32, method: u16, arg_num: u16, max_locals: u16, location: usize) {
+        if self.runtime.profile.is_some() {
+            self.profile_call(class, method);
+        }
+        self.return_stack.push(Frame {
+            class: self.cur_class,
+            method: self.cur_method,
+            frame_pointer: self.frame_pointer,
+            pc: self.pc,
+            max_locals: self.max_locals,
+        });
         self.frame_pointer = (self.stack.len() - arg_num as usize) as u32;
         self.pc = location;
         self.cur_class = class;
+        self.cur_method = method;
         self.max_locals = max_locals;
         for _ in arg_num..max_locals {
             self.stack.push(Value(0));
         }
     }
     pub fn do_return(&mut self, ret_cat: ReturnCategory) {
-        let (class, fp, pc, max_locals) = self.return_stack.pop().unwrap();
-        eprintln!("return, throwing away: {:?}", &self.stack[self.frame_pointer as usize..]);
+        if let Some(&(depth, monitor)) = self.locked_frames.last() && depth == self.return_stack.len() {
+            self.locked_frames.pop();
+            self.monitor_exit(monitor);
+        }
+        if self.runtime.profile.is_some() {
+            self.profile_return();
+        }
+        let Frame { class, method, frame_pointer: fp, pc, max_locals } = self.return_stack.pop().unwrap();
         self.pc = pc;
         self.cur_class = class;
+        self.cur_method = method;
         match ret_cat {
             ReturnCategory::Void => {
                 self.stack.truncate(self.frame_pointer as usize);
@@ -141,6 +455,148 @@ impl RuntimeCtx<'_> {
         }
         self.max_locals = max_locals;
     }
+    /// Unwinds frames until a handler for the exception is found and jumps to it.
+    ///
+    /// If no frame handles it, the exception is returned as [`RtError::UncaughtException`].
+    pub fn throw(&mut self, mut exception: Value) -> Result<()> {
+        loop {
+            match self.barrier {
+                Some(depth) if self.return_stack.len() == depth => {
+                    self.pending_exception = Some(exception);
+                    return Ok(());
+                }
+                None if self.return_stack.is_empty() && !self.is_main_thread() => {
+                    self.report_uncaught(exception);
+                    return Ok(());
+                }
+                None if self.return_stack.is_empty() => {
+                    let class_name = self.get_class_name(exception).replace('/', ".").into_boxed_str();
+                    let message = self.throwable_message(exception).map(String::into_boxed_str);
+                    return Err(RtError::UncaughtException { class_name, message });
+                }
+                _ => (),
+            }
+            if let Some(handler_pc) = self.find_exception_handler(exception)? {
+                self.stack.truncate((self.frame_pointer + self.max_locals as u32) as usize);
+                self.push(exception);
+                self.pc = handler_pc;
+                return Ok(());
+            }
+            let failed_clinit = self.runtime.get_class(self.cur_class).clinit == Some(self.cur_method);
+            let class = self.cur_class;
+            self.do_return(ReturnCategory::Void);
+            if failed_clinit {
+                // the caller was about to execute the instruction that triggered the initialization
+                // again, resume it past that instruction so the exception comes from it
+                if let InitState::InProgress { resume_pc, .. } = self.runtime.get_class(class).init_state {
+                    self.pc = resume_pc;
+                }
+                self.finish_initialization(class, InitState::Erroneous);
+                let error = self.runtime.load_class("java/lang/Error")?;
+                if !self.is_instance_of(exception, error) {
+                    exception = self.new_throwable("java/lang/ExceptionInInitializerError", None, exception)?;
+                }
+            }
+        }
+    }
+    /// Creates an exception of a builtin class with an optional message and throws it
+    pub fn throw_new(&mut self, class_name: &str, message: Option<String>) -> Result<()> {
+        let exception = self.new_throwable(class_name, message, Value::NULL)?;
+        self.throw(exception)
+    }
+    fn new_throwable(&mut self, class_name: &str, message: Option<String>, cause: Value) -> Result<Value> {
+        let class = self.runtime.load_class(class_name)?;
+        let exception = self.new_object(class);
+        if let Some(message) = message {
+            let message = self.new_string_obj(message);
+            self.write_u32_ref(exception.offset(THROWABLE_MESSAGE_OFFSET), message.into_u32());
+        }
+        self.write_u32_ref(exception.offset(THROWABLE_CAUSE_OFFSET), cause.into_u32());
+        Ok(exception)
+    }
+    /// Makes sure the class is initialized before the instruction at `ins_pc` uses it.
+    ///
+    /// Returns `false` if the instruction cannot proceed yet. Either a `<clinit>` frame was pushed,
+    /// which returns to `ins_pc` to execute the instruction again, the thread waits for another
+    /// one to initialize the class and executes the instruction again once it is done, or an
+    /// error was thrown.
+    fn ensure_initialized(&mut self, class: u32, ins_pc: usize) -> Result<bool> {
+        let loaded = self.runtime.get_class(class);
+        match loaded.init_state {
+            InitState::Initialized => Ok(true),
+            // a request made while running the initializer itself proceeds right away
+            InitState::InProgress { thread, .. } if thread == self.current_thread() => Ok(true),
+            InitState::InProgress { .. } => {
+                self.wait_for_initialization(class);
+                self.pc = ins_pc;
+                Ok(false)
+            }
+            InitState::Erroneous => {
+                let message = format!("Could not initialize class {}", loaded.name.replace('/', "."));
+                self.throw_new("java/lang/NoClassDefFoundError", Some(message))?;
+                Ok(false)
+            }
+            InitState::Uninitialized => {
+                let (super_class, clinit) = (loaded.super_class, loaded.clinit);
+                if !loaded.is_interface() && !self.ensure_initialized(super_class, ins_pc)? {
+                    return Ok(false);
+                }
+                match clinit {
+                    // thrown before the pc moves back, so it comes from the instruction
+                    Some(_) if self.call_depth_exceeded() => {
+                        self.throw_new("java/lang/StackOverflowError", None)?;
+                        Ok(false)
+                    }
+                    Some(method) => {
+                        let (thread, resume_pc) = (self.current_thread(), self.pc);
+                        self.runtime.classes[class as usize].init_state = InitState::InProgress { thread, resume_pc };
+                        self.pc = ins_pc;
+                        self.invoke(class, method)?;
+                        Ok(false)
+                    }
+                    None => {
+                        self.runtime.classes[class as usize].init_state = InitState::Initialized;
+                        Ok(true)
+                    }
+                }
+            }
+        }
+    }
+    /// Ends the initialization of a class that was in progress, the threads waiting for it go on
+    fn finish_initialization(&mut self, class: u32, state: InitState) {
+        self.runtime.classes[class as usize].init_state = state;
+        self.initialization_done(class);
+    }
+    /// The message of a `Throwable`, `None` if it has none
+    pub fn throwable_message(&self, exception: Value) -> Option<String> {
+        let message = Value(self.read_u32_ref(exception.offset(THROWABLE_MESSAGE_OFFSET))?);
+        self.read_string_object(message)
+    }
+    fn find_exception_handler(&mut self, exception: Value) -> Result<Option<usize>> {
+        let (code_location, table_len) = match self.runtime.get_class(self.cur_class).method(self.cur_method) {
+            MethodImpl::Bytecode(bm) => (bm.code_location, bm.exception_table.len()),
+            _ => return Ok(None),
+        };
+        // the pc has always moved past the opcode of the instruction that threw
+        let pc = (self.pc - 1 - code_location) as u16;
+        for i in 0..table_len {
+            let MethodImpl::Bytecode(bm) = self.runtime.get_class(self.cur_class).method(self.cur_method) else { unreachable!() };
+            let ExceptionEntry { start_pc, end_pc, handler_pc, catch_type } = bm.exception_table[i];
+            if !(start_pc..end_pc).contains(&pc) {
+                continue;
+            }
+            if catch_type == 0 || {
+                let class = self.resolve_class(catch_type)?;
+                self.is_instance_of(exception, class)
+            } {
+                return Ok(Some(code_location + handler_pc as usize));
+            }
+        }
+        Ok(None)
+    }
+    fn read_utf8_constant(&self, n: ConstIndex) -> &str {
+        self.runtime.read_static_string(self.read_constant(n).utf8())
+    }
     #[track_caller]
     fn read_constant(&self, n: ConstIndex) -> RuntimeConstant {
         match &self.runtime.classes[self.cur_class as usize].runtime_info {
@@ -150,14 +606,111 @@ impl RuntimeCtx<'_> {
             _ => unimplemented!(),
         }
     }
-    fn new(&mut self, class_id: u32) -> Value {
-        let size = self.runtime.get_class(class_id).get_aligned_data_size();
-        let i = self.heap.as_bytes_32aligned().len();
-        for _ in 0..size/4 {
-            self.heap.push(0);
+    /// Allocates an object on the heap without collecting garbage or checking the maximum heap size.
+    ///
+    /// Allocations on behalf of Java code check with [`Self::make_room`] first. Only the runtime
+    /// itself allocates without, for the exceptions it throws, which includes the
+    /// `OutOfMemoryError`, to start `main` and for the embedder and the debugger.
+    fn alloc(&mut self, class_id: u32, size: usize) -> Value {
+        debug_assert_eq!(size & 3, 0);
+        let i = match self.take_heap_gap(size) {
+            Some(offset) => offset as usize,
+            None => {
+                let offset = self.heap.as_bytes_32aligned().len();
+                self.heap.resize(self.heap.len() + size / 4, 0);
+                offset
+            }
+        };
+        self.heap[i / 4..i / 4 + 2].copy_from_slice(&ObjectHeader::new(class_id).to_words());
+        self.gc.allocated(size);
+        if self.runtime.profile.is_some() {
+            self.profile_allocation(class_id, size);
         }
+        let stats = &mut self.runtime.gc_stats;
+        stats.allocated_bytes += size as u64;
+        stats.peak_heap_bytes = stats.peak_heap_bytes.max(self.heap.len() * 4);
         Value::new_ref_heap(i as u32)
     }
+    /// Checks that an allocating instruction can allocate `size` bytes, throwing an
+    /// `OutOfMemoryError` and returning `false` if it cannot
+    fn make_room(&mut self, size: usize) -> Result<bool> {
+        if self.reserve_heap(size) {
+            return Ok(true);
+        }
+        self.throw_new("java/lang/OutOfMemoryError", Some("Java heap space".into()))?;
+        Ok(false)
+    }
+    fn new_object(&mut self, class_id: u32) -> Value {
+        let size = self.runtime.get_class(class_id).get_aligned_data_size();
+        self.alloc(class_id, size as usize)
+    }
+    fn array_size(&self, class_id: u32, length: u32) -> usize {
+        let element_size = self.runtime.get_class(class_id).array_element_size().unwrap();
+        let size = ARRAY_DATA_OFFSET as usize + length as usize * element_size as usize;
+        (size + 3) & !3
+    }
+    fn new_array(&mut self, class_id: u32, length: u32) -> Value {
+        let r = self.alloc(class_id, self.array_size(class_id, length));
+        self.write_u32_ref(r.offset(ARRAY_LENGTH_OFFSET), length);
+        r
+    }
+    /// Bytes the arrays of a `multianewarray` take together, with `counts[0]` elements in the
+    /// outermost one and so on. Sizes too large for any heap saturate.
+    fn multi_array_size(&self, class: u32, counts: &[u32]) -> usize {
+        let size = self.array_size(class, counts[0]);
+        if counts.len() == 1 {
+            return size;
+        }
+        let Some(ArrayComponent::Reference(component)) = self.runtime.get_class(class).array_component else {
+            unreachable!("multianewarray creates more dimensions than the array class has")
+        };
+        size.saturating_add((counts[0] as usize).saturating_mul(self.multi_array_size(component, &counts[1..])))
+    }
+    /// Creates the arrays of a `multianewarray` once [`Self::multi_array_size`] bytes are made room
+    /// for, the arrays of the last dimension are left filled with zeroes or nulls
+    fn new_multi_array(&mut self, class: u32, counts: &[u32]) -> Value {
+        let array = self.new_array(class, counts[0]);
+        if counts.len() > 1 {
+            let Some(ArrayComponent::Reference(component)) = self.runtime.get_class(class).array_component else { unreachable!() };
+            for i in 0..counts[0] {
+                let element = self.new_multi_array(component, &counts[1..]);
+                self.write_u32_ref(array.offset(ARRAY_DATA_OFFSET + 4 * i), element.into_u32());
+            }
+        }
+        array
+    }
+    /// Pops an index and an array reference and returns a reference to the element.
+    ///
+    /// If the array is null or the index is out of bounds, the exception is thrown and `None` is returned.
+    fn pop_array_element(&mut self, element_size: u32) -> Result<Option<Value>> {
+        let index = self.pop().into_u32();
+        let arr_ref = self.pop();
+        if arr_ref == Value::NULL {
+            self.throw_new("java/lang/NullPointerException", None)?;
+            return Ok(None);
+        }
+        let length = self.read_u32_ref(arr_ref.offset(ARRAY_LENGTH_OFFSET)).unwrap();
+        if index >= length {
+            let message = format!("Index {} out of bounds for length {length}", index as i32);
+            self.throw_new("java/lang/ArrayIndexOutOfBoundsException", Some(message))?;
+            return Ok(None);
+        }
+        Ok(Some(arr_ref.offset(ARRAY_DATA_OFFSET + element_size * index)))
+    }
+    fn ref_bytes(&self, ptr: Value, len: usize) -> Option<&[u8]> {
+        Some(match ptr.into_ref() {
+            Reference::Invalid => return None,
+            Reference::Heap(offset) => &self.heap.as_bytes_32aligned()[offset as usize..][..len],
+            Reference::Static(offset) => &self.runtime.statics.as_bytes_32aligned()[offset as usize..][..len],
+        })
+    }
+    fn ref_bytes_mut(&mut self, ptr: Value, len: usize) -> Option<&mut [u8]> {
+        Some(match ptr.into_ref() {
+            Reference::Invalid => return None,
+            Reference::Heap(offset) => &mut self.heap.as_bytes_32aligned_mut()[offset as usize..][..len],
+            Reference::Static(offset) => &mut self.runtime.statics.as_bytes_32aligned_mut()[offset as usize..][..len],
+        })
+    }
     fn read_u8_ref(&self, ptr: Value) -> Option<u8> {
         Some(match ptr.into_ref() {
             Reference::Invalid => return None,
@@ -216,473 +769,209 @@ impl RuntimeCtx<'_> {
             }
         }
     }
-    fn decode_opcode(&mut self) -> Opcode {
-        let code = self.runtime.code.as_bytes_32aligned();
-        let opcode = Opcode::from_primitive(code[self.pc]);
-        self.pc += 1;
-        opcode
-    }
-    fn decode_u8(&mut self) -> u8 {
-        let code = self.runtime.code.as_bytes_32aligned();
-        let byte = code[self.pc];
-        self.pc += 1;
-        byte
-    }
-    fn decode_u16(&mut self) -> u16 {
-        let code = self.runtime.code.as_bytes_32aligned();
-        let short = u16::from_be_bytes([code[self.pc], code[self.pc+1]]);
-        self.pc += 2;
-        short
-    }
-    fn do_goto(&mut self, instruction_length: u8, offset: i16) {
-        self.pc = (self.pc - instruction_length as usize).wrapping_add(offset as isize as usize);
-    }
-    fn run_inner(&mut self) -> Result<()> {
-        while !self.return_stack.is_empty() {
-            let opcode = self.decode_opcode();
-            eprintln!("  ins {}", opcode.mnemonic());
-            match opcode {
-                Opcode::Nop => (),
-                Opcode::AconstNull |
-                Opcode::Iconst0 => self.push(0),
-                Opcode::IconstM1 => self.push(-1),
-                Opcode::Iconst1 => self.push(1),
-                Opcode::Iconst2 => self.push(2),
-                Opcode::Iconst3 => self.push(3),
-                Opcode::Iconst4 => self.push(4),
-                Opcode::Iconst5 => self.push(5),
-                Opcode::Lconst0 => self.push2(i64_into_values(0)),
-                Opcode::Lconst1 => self.push2(i64_into_values(1)),
-                Opcode::Fconst0 => self.push(0.),
-                Opcode::Fconst1 => self.push(1.),
-                Opcode::Fconst2 => self.push(2.),
-                Opcode::Dconst0 => self.push2(f64_into_values(0.)),
-                Opcode::Dconst1 => self.push2(f64_into_values(1.)),
-                Opcode::Bipush => {
-                    let imm = self.decode_u8() as i8;
-                    self.push(imm);
-                }
-                Opcode::Sipush => {
-                    let imm = self.decode_u16() as i16;
-                    self.push(imm);
-                }
-                Opcode::Ldc => {
-                    let cpn = self.decode_u8();
-                    let val = self.read_constant(cpn as u16).value();
-                    self.push(val);
-                }
-                Opcode::LdcW => {
-                    let cpn = self.decode_u16();
-                    let val = self.read_constant(cpn as u16).value();
-                    self.push(val);
-                }
-                Opcode::Ldc2W => todo!(),
-                Opcode::Iload |
-                Opcode::Fload |
-                Opcode::Aload => {
-                    let index = self.decode_u8();
-                    let val = self.get_local(index as u16);
-                    self.push(val);
-                },
-                Opcode::Lload |
-                Opcode::Dload => {
-                    let index = self.decode_u8();
-                    let val = self.get_local2(index as u16);
-                    self.push2(val);
-                }
-                Opcode::Iload0 |
-                Opcode::Fload0 |
-                Opcode::Aload0 => {
-                    let val = self.get_local(0);
-                    self.push(val);
-                }
-                Opcode::Lload0 |
-                Opcode::Dload0 => {
-                    let val = self.get_local2(0);
-                    self.push2(val);
-                }
-                Opcode::Iload1 |
-                Opcode::Fload1 |
-                Opcode::Aload1 => {
-                    let val = self.get_local(1);
-                    self.push(val);
-                }
-                Opcode::Lload1 |
-                Opcode::Dload1 => {
-                    let val = self.get_local2(1);
-                    self.push2(val);
-                }
-                Opcode::Iload2 |
-                Opcode::Fload2 |
-                Opcode::Aload2  => {
-                    let val = self.get_local(2);
-                    self.push(val);
-                }
-                Opcode::Lload2 |
-                Opcode::Dload2  => {
-                    let val = self.get_local2(2);
-                    self.push2(val);
+    /// Executes instructions until the return stack of the current thread is down to `depth`
+    /// frames, other threads take turns in between
+    fn run_until(&mut self, depth: usize) -> Result<()> {
+        let owner = self.current_thread();
+        loop {
+            match self.run_instructions(owner, depth) {
+                Err(RtError::ClassNotFound { name, searched }) => {
+                    let message = if searched.is_empty() {
+                        name.into()
+                    } else {
+                        format!("{name} (searched {})", searched.join(", "))
+                    };
+                    self.throw_new("java/lang/NoClassDefFoundError", Some(message))?;
                 }
-                Opcode::Iload3 |
-                Opcode::Fload3 |
-                Opcode::Aload3  => {
-                    let val = self.get_local(3);
+                result => return result,
+            }
+        }
+    }
+    fn run_instructions(&mut self, owner: usize, depth: usize) -> Result<()> {
+        while self.keep_running(owner, depth)? {
+            self.consume_fuel()?;
+            if self.runtime.profile.is_some() {
+                self.profile_instruction();
+            }
+            if self.debug.is_some() {
+                self.debug_hook()?;
+            }
+            let ins_pc = self.pc;
+            let Decoded { length, mut instruction } = self.runtime.instructions[ins_pc];
+            self.pc = ins_pc + length as usize;
+            if let Instruction::Unlinked(opcode, operand) = instruction {
+                let Some(linked) = self.link(opcode, operand)? else { continue };
+                self.runtime.instructions[ins_pc].instruction = linked;
+                instruction = linked;
+            }
+            match instruction {
+                Instruction::Nop => (),
+                Instruction::Push(value) => self.push(value),
+                Instruction::Push2(v1, v2) => self.push2((v1, v2)),
+                Instruction::Load(index) => {
+                    let val = self.get_local(index);
                     self.push(val);
                 }
-                Opcode::Lload3 |
-                Opcode::Dload3  => {
-                    let val = self.get_local2(3);
+                Instruction::Load2(index) => {
+                    let val = self.get_local2(index);
                     self.push2(val);
                 }
-                Opcode::Faload |
-                Opcode::Iaload |
-                Opcode::Aaload => {
-                    let index = self.pop().into_u32();
-                    let arr_ref = self.pop();
-                    let length = self.read_u32_ref(arr_ref).unwrap();
-                    if index >= length {
-                        unimplemented!("out of bounds");
-                    }
-                    let val = self.read_u32_ref(arr_ref.offset(4*index)).unwrap();
-                    self.push(Value(val));
-                }
-                Opcode::Laload |
-                Opcode::Daload => {
-                    let index = self.pop().into_u32();
-                    let arr_ref = self.pop();
-                    let length = self.read_u32_ref(arr_ref).unwrap();
-                    if index >= length {
-                        unimplemented!("out of bounds");
-                    }
-                    let val1 = self.read_u32_ref(arr_ref.offset(4*index)).unwrap();
-                    let val2 = self.read_u32_ref(arr_ref.offset(4*index+4)).unwrap();
-                    self.push2((Value(val1), Value(val2)));
-                }
-                Opcode::Baload => {
-                    let index = self.pop().into_u32();
-                    let arr_ref = self.pop();
-                    let length = self.read_u32_ref(arr_ref).unwrap();
-                    if index >= length {
-                        unimplemented!("out of bounds");
-                    }
-                    let val = self.read_u8_ref(arr_ref.offset(index)).unwrap();
-                    self.push(val as i8);
-                }
-                Opcode::Caload |
-                Opcode::Saload => {
-                    let index = self.pop().into_u32();
-                    let arr_ref = self.pop();
-                    let length = self.read_u32_ref(arr_ref).unwrap();
-                    if index >= length {
-                        unimplemented!("out of bounds");
-                    }
-                    let val = self.read_u16_ref(arr_ref.offset(index)).unwrap();
-                    self.push(val as i16);
-                }
-                Opcode::Istore |
-                Opcode::Fstore |
-                Opcode::Astore => {
-                    let index = self.decode_u8();
+                Instruction::Store(index) => {
                     let value = self.pop();
-                    self.set_local(index as u16, value);
+                    self.set_local(index, value);
                 }
-                Opcode::Lstore |
-                Opcode::Dstore => {
-                    let index = self.decode_u8();
+                Instruction::Store2(index) => {
                     let values = self.pop2();
-                    self.set_local2(index as u16, values);
+                    self.set_local2(index, values);
                 }
-                Opcode::Istore0 |
-                Opcode::Fstore0 |
-                Opcode::Astore0 => {
-                    let value = self.pop();
-                    self.set_local(0, value);
-                }
-                Opcode::Istore1 |
-                Opcode::Fstore1 |
-                Opcode::Astore1 => {
-                    let value = self.pop();
-                    self.set_local(1, value);
+                Instruction::ArrayLoad(kind) => {
+                    let Some(element) = self.pop_array_element(kind.size())? else { continue };
+                    match kind {
+                        ValueKind::Byte => {
+                            let val = self.read_u8_ref(element).unwrap();
+                            self.push(val as i8);
+                        }
+                        ValueKind::Char => {
+                            let val = self.read_u16_ref(element).unwrap();
+                            self.push(Value(val as u32));
+                        }
+                        ValueKind::Short => {
+                            let val = self.read_u16_ref(element).unwrap();
+                            self.push(val as i16);
+                        }
+                        ValueKind::Int | ValueKind::Float | ValueKind::Reference => {
+                            let val = self.read_u32_ref(element).unwrap();
+                            self.push(Value(val));
+                        }
+                        ValueKind::Long => {
+                            let val1 = self.read_u32_ref(element).unwrap();
+                            let val2 = self.read_u32_ref(element.offset(4)).unwrap();
+                            self.push2((Value(val1), Value(val2)));
+                        }
+                    }
                 }
-                Opcode::Istore2 |
-                Opcode::Fstore2 |
-                Opcode::Astore2 => {
-                    let value = self.pop();
-                    self.set_local(2, value);
+                Instruction::ArrayStore(ValueKind::Long) => {
+                    let (v1, v2) = self.pop2();
+                    let Some(element) = self.pop_array_element(8)? else { continue };
+                    self.write_u32_ref(element, v1.into_u32());
+                    self.write_u32_ref(element.offset(4), v2.into_u32());
                 }
-                Opcode::Istore3 |
-                Opcode::Fstore3 |
-                Opcode::Astore3 => {
+                Instruction::ArrayStore(kind) => {
                     let value = self.pop();
-                    self.set_local(3, value);
-                }
-                Opcode::Lstore0 |
-                Opcode::Dstore0 => {
-                    let values = self.pop2();
-                    self.set_local2(0, values);
-                }
-                Opcode::Lstore1 |
-                Opcode::Dstore1 => {
-                    let values = self.pop2();
-                    self.set_local2(1, values);
-                }
-                Opcode::Lstore2 |
-                Opcode::Dstore2 => {
-                    let values = self.pop2();
-                    self.set_local2(2, values);
-                }
-                Opcode::Lstore3 |
-                Opcode::Dstore3 => {
-                    let values = self.pop2();
-                    self.set_local2(3, values);
+                    if kind == ValueKind::Reference {
+                        let (arr_ref, _) = self.top2();
+                        if value != Value::NULL && arr_ref != Value::NULL {
+                            let component = self.runtime.get_class(self.get_class_id(arr_ref)).array_component;
+                            let Some(ArrayComponent::Reference(component)) = component else { unreachable!() };
+                            if !self.is_instance_of(value, component) {
+                                let message = self.get_class_name(value).replace('/', ".");
+                                self.pop2();
+                                self.throw_new("java/lang/ArrayStoreException", Some(message))?;
+                                continue;
+                            }
+                        }
+                    }
+                    let Some(element) = self.pop_array_element(kind.size())? else { continue };
+                    match kind {
+                        ValueKind::Byte => self.write_u8_ref(element, value.into_u8()),
+                        ValueKind::Char | ValueKind::Short => self.write_u16_ref(element, value.into_u16()),
+                        _ => self.write_u32_ref(element, value.into_u32()),
+                    }
                 }
-                Opcode::Iastore => todo!(),
-                Opcode::Lastore => todo!(),
-                Opcode::Fastore => todo!(),
-                Opcode::Dastore => todo!(),
-                Opcode::Aastore => todo!(),
-                Opcode::Bastore => todo!(),
-                Opcode::Castore => todo!(),
-                Opcode::Sastore => todo!(),
-                Opcode::Pop => {
+                Instruction::Pop => {
                     self.pop();
                 }
-                Opcode::Pop2 => {
+                Instruction::Pop2 => {
                     self.pop2();
                 }
-                Opcode::Dup => self.push(self.top()),
-                Opcode::DupX1 => todo!(),
-                Opcode::DupX2 => todo!(),
-                Opcode::Dup2 => todo!(),
-                Opcode::Dup2X1 => todo!(),
-                Opcode::Dup2X2 => todo!(),
-                Opcode::Swap => {
+                Instruction::Dup => self.push(self.top()),
+                Instruction::DupX1 => self.dup_below(1, 2),
+                Instruction::DupX2 => self.dup_below(1, 3),
+                Instruction::Dup2 => self.dup_below(2, 2),
+                Instruction::Dup2X1 => self.dup_below(2, 3),
+                Instruction::Dup2X2 => self.dup_below(2, 4),
+                Instruction::Swap => {
                     let v1 = self.pop();
                     let v2 = self.pop();
                     self.push(v1);
                     self.push(v2);
                 }
-                Opcode::Iadd => {
-                    let v2 = self.pop().into_u32() as i32;
-                    let v1 = self.pop().into_u32() as i32;
-                    self.push(v1+v2);
-                }
-                Opcode::Ladd => todo!(),
-                Opcode::Fadd => todo!(),
-                Opcode::Dadd => todo!(),
-                Opcode::Isub => {
-                    let v2 = self.pop().into_u32() as i32;
-                    let v1 = self.pop().into_u32() as i32;
-                    self.push(v1-v2);
-                }
-                Opcode::Lsub => todo!(),
-                Opcode::Fsub => todo!(),
-                Opcode::Dsub => todo!(),
-                Opcode::Imul => {
-                    let v2 = self.pop().into_u32() as i32;
-                    let v1 = self.pop().into_u32() as i32;
-                    self.push(v1*v2);
-                }
-                Opcode::Lmul => todo!(),
-                Opcode::Fmul => todo!(),
-                Opcode::Dmul => todo!(),
-                Opcode::Idiv => {
-                    let v2 = self.pop().into_u32() as i32;
-                    let v1 = self.pop().into_u32() as i32;
-                    self.push(v1/v2);
+                Instruction::Arith(kind, op) => self.arith(kind, op)?,
+                Instruction::Neg(kind) => self.negate(kind),
+                Instruction::Convert(from, to) => self.convert(from, to),
+                Instruction::Narrow(kind) => self.narrow(kind),
+                Instruction::Iinc(index, increment) => {
+                    let value = self.get_local(index).into_i32().wrapping_add(increment as i32);
+                    self.set_local(index, value);
                 }
-                Opcode::Ldiv => todo!(),
-                Opcode::Fdiv => todo!(),
-                Opcode::Ddiv => todo!(),
-                Opcode::Irem => todo!(),
-                Opcode::Lrem => todo!(),
-                Opcode::Frem => todo!(),
-                Opcode::Drem => todo!(),
-                Opcode::Ineg => todo!(),
-                Opcode::Lneg => todo!(),
-                Opcode::Fneg => todo!(),
-                Opcode::Dneg => todo!(),
-                Opcode::Ishl => todo!(),
-                Opcode::Lshl => todo!(),
-                Opcode::Ishr => todo!(),
-                Opcode::Lshr => todo!(),
-                Opcode::Iushr => todo!(),
-                Opcode::Lushr => todo!(),
-                Opcode::Iand => todo!(),
-                Opcode::Land => todo!(),
-                Opcode::Ior => todo!(),
-                Opcode::Lor => todo!(),
-                Opcode::Ixor => todo!(),
-                Opcode::Lxor => todo!(),
-                Opcode::Iinc => todo!(),
-                Opcode::I2l => todo!(),
-                Opcode::I2f => todo!(),
-                Opcode::I2d => todo!(),
-                Opcode::L2i => todo!(),
-                Opcode::L2f => todo!(),
-                Opcode::L2d => todo!(),
-                Opcode::F2i => todo!(),
-                Opcode::F2l => todo!(),
-                Opcode::F2d => todo!(),
-                Opcode::D2i => todo!(),
-                Opcode::D2l => todo!(),
-                Opcode::D2f => todo!(),
-                Opcode::I2b => todo!(),
-                Opcode::I2c => todo!(),
-                Opcode::I2s => todo!(),
-                Opcode::Lcmp => {
+                Instruction::Lcmp => {
                     let value2 = values_into_u64(self.pop2()) as i64;
                     let value1 = values_into_u64(self.pop2()) as i64;
                     self.push(value1.cmp(&value2) as i8);
                 }
-                Opcode::Fcmpl => {
+                Instruction::Fcmp(nan) => {
                     let value2 = self.pop().into_f32();
                     let value1 = self.pop().into_f32();
-                    self.push(value1.partial_cmp(&value2).unwrap_or(Ordering::Less) as i8);
+                    self.push(value1.partial_cmp(&value2).unwrap_or(nan) as i8);
                 }
-                Opcode::Fcmpg => {
-                    let value2 = self.pop().into_f32();
-                    let value1 = self.pop().into_f32();
-                    self.push(value1.partial_cmp(&value2).unwrap_or(Ordering::Greater) as i8);
-                }
-                Opcode::Dcmpl => {
-                    let value2 = values_into_f64(self.pop2());
-                    let value1 = values_into_f64(self.pop2());
-                    self.push(value1.partial_cmp(&value2).unwrap_or(Ordering::Less) as i8);
-                }
-                Opcode::Dcmpg => {
+                Instruction::Dcmp(nan) => {
                     let value2 = values_into_f64(self.pop2());
                     let value1 = values_into_f64(self.pop2());
-                    self.push(value1.partial_cmp(&value2).unwrap_or(Ordering::Greater) as i8);
+                    self.push(value1.partial_cmp(&value2).unwrap_or(nan) as i8);
                 }
-                Opcode::Ifnull |
-                Opcode::Ifeq => {
-                    let offset = self.decode_u16() as i16;
-                    if self.pop() == Value::ZERO {
-                        self.do_goto(3, offset);
+                Instruction::If(condition, target) => {
+                    if condition.holds(self.pop().into_i32(), 0) {
+                        self.pc = target as usize;
                     }
                 }
-                Opcode::Ifnonnull |
-                Opcode::Ifne => {
-                    let offset = self.decode_u16() as i16;
-                    if self.pop() != Value::ZERO {
-                        self.do_goto(3, offset);
+                Instruction::IfCmp(condition, target) => {
+                    let value2 = self.pop().into_i32();
+                    if condition.holds(self.pop().into_i32(), value2) {
+                        self.pc = target as usize;
                     }
                 }
-                Opcode::Iflt => {
-                    let offset = self.decode_u16() as i16;
-                    if self.pop().into_i32() < 0 {
-                        self.do_goto(3, offset);
-                    }
-                }
-                Opcode::Ifge => {
-                    let offset = self.decode_u16() as i16;
-                    if self.pop().into_i32() >= 0 {
-                        self.do_goto(3, offset);
-                    }
+                Instruction::Goto(target) => self.pc = target as usize,
+                Instruction::Switch(index) => {
+                    let key = self.pop().into_i32();
+                    self.pc = self.runtime.switches[index as usize].target(key) as usize;
                 }
-                Opcode::Ifgt => {
-                    let offset = self.decode_u16() as i16;
-                    if self.pop().into_i32() > 0 {
-                        self.do_goto(3, offset);
-                    }
-                }
-                Opcode::Ifle => {
-                    let offset = self.decode_u16() as i16;
-                    if self.pop().into_i32() <= 0 {
-                        self.do_goto(3, offset);
-                    }
+                Instruction::Jsr(target) => {
+                    self.push(Value(self.pc as u32));
+                    self.pc = target as usize;
                 }
-                Opcode::IfAcmpeq |
-                Opcode::IfIcmpeq => {
-                    let offset = self.decode_u16() as i16;
-                    if self.pop() == self.pop() {
-                        self.do_goto(3, offset);
+                Instruction::Ret(index) => self.pc = self.get_local(index).into_u32() as usize,
+                Instruction::Return(ReturnCategory::Void) => {
+                    let class = self.runtime.get_class(self.cur_class);
+                    if class.clinit == Some(self.cur_method) {
+                        self.finish_initialization(self.cur_class, InitState::Initialized);
                     }
+                    self.do_return(ReturnCategory::Void);
                 }
-                Opcode::IfAcmpne |
-                Opcode::IfIcmpne => {
-                    let offset = self.decode_u16() as i16;
-                    if self.pop() != self.pop() {
-                        self.do_goto(3, offset);
+                Instruction::Return(ret_cat) => self.do_return(ret_cat),
+                Instruction::Getstatic { class, offset, kind } => {
+                    if !self.ensure_initialized(class, ins_pc)? {
+                        continue;
                     }
-                }
-                Opcode::IfIcmplt => {
-                    let offset = self.decode_u16() as i16;
-                    if self.pop().into_i32() < self.pop().into_i32() {
-                        self.do_goto(3, offset);
-                    }
-                }
-                Opcode::IfIcmpge => {
-                    let offset = self.decode_u16() as i16;
-                    if self.pop().into_i32() >= self.pop().into_i32() {
-                        self.do_goto(3, offset);
-                    }
-                }
-                Opcode::IfIcmpgt => {
-                    let offset = self.decode_u16() as i16;
-                    if self.pop().into_i32() > self.pop().into_i32() {
-                        self.do_goto(3, offset);
-                    }
-                }
-                Opcode::IfIcmple => {
-                    let offset = self.decode_u16() as i16;
-                    if self.pop().into_i32() <= self.pop().into_i32() {
-                        self.do_goto(3, offset);
-                    }
-                }
-                Opcode::Goto => {
-                    let offset = self.decode_u16() as i16;
-                    self.do_goto(3, offset);
-                }
-                Opcode::Jsr => todo!(),
-                Opcode::Ret => todo!(),
-                Opcode::Tableswitch => todo!(),
-                Opcode::Lookupswitch => todo!(),
-                Opcode::Lreturn |
-                Opcode::Dreturn => self.do_return(ReturnCategory::Cat2),
-                Opcode::Ireturn |
-                Opcode::Freturn |
-                Opcode::Areturn => self.do_return(ReturnCategory::Cat1),
-                Opcode::Return => self.do_return(ReturnCategory::Void),
-                Opcode::Getstatic => {
-                    let cpn = self.decode_u16();
-                    let (class_index, name_and_type_index) = self.read_constant(cpn).fieldref();
-                    let class = self.read_constant(self.read_constant(class_index).class()).utf8();
-                    let (name, field_type) = self.read_constant(name_and_type_index).nameandtype();
-                    let name = self.read_constant(name).utf8();
-                    let field_type = self.read_constant(field_type).utf8();
-                    let field_type = FieldDescriptor::from_bytes(self.runtime.read_static_string(field_type).as_bytes())?;
-
-                    let ptr = {
-                        let class = self.runtime.read_static_string(class).to_string();
-                        let class = self.runtime.load_class(&class)?;
-                        let class = self.runtime.get_class(class);
-                        let name = self.runtime.read_static_string(name);
-                        let offset = class.member_table[(name, &field_type.clone().into())];
-                        &class.static_fields[offset as usize]
-                    };
+                    let ptr = &self.runtime.get_class(class).static_fields[offset as usize];
 
-                    match field_type {
-                        FieldDescriptor::Boolean |
-                        FieldDescriptor::Byte => {
+                    match kind {
+                        ValueKind::Byte => {
                             let value = *ptr;
                             self.push(value as i8);
                         }
-                        FieldDescriptor::Char |
-                        FieldDescriptor::Short => {
+                        ValueKind::Char => {
+                            let value = unsafe { *(ptr as *const u8 as *const u16) };
+                            self.push(Value(value as u32));
+                        }
+                        ValueKind::Short => {
                             let value = unsafe { *(ptr as *const u8 as *const u16) };
                             self.push(value as i16);
                         }
-                        FieldDescriptor::ClassRef(_) |
-                        FieldDescriptor::ArrRef(_) |
-                        FieldDescriptor::Float |
-                        FieldDescriptor::Int => {
+                        ValueKind::Int | ValueKind::Float | ValueKind::Reference => {
                             let value = unsafe { *(ptr as *const u8 as *const u32) };
                             self.push(value as i32);
                         }
-                        FieldDescriptor::Double |
-                        FieldDescriptor::Long => {
+                        ValueKind::Long => {
                             let ptr = ptr as *const u8 as *const u32;
                             let v1 = unsafe { *ptr };
                             let v2 = unsafe { *ptr.add(1) };
@@ -690,184 +979,201 @@ impl RuntimeCtx<'_> {
                         }
                     }
                 }
-                Opcode::Getfield => {
-                    let cpn = self.decode_u16();
-                    let (class_index, name_and_type_index) = self.read_constant(cpn).fieldref();
-                    let class = self.read_constant(self.read_constant(class_index).class()).utf8();
-                    let (name, field_type) = self.read_constant(name_and_type_index).nameandtype();
-                    let name = self.read_constant(name).utf8();
-                    let field_type = self.read_constant(field_type).utf8();
-                    let field_type = FieldDescriptor::from_bytes(self.runtime.read_static_string(field_type).as_bytes())?;
-
-                    let offset = {
-                        let class = self.runtime.read_static_string(class).to_string();
-                        let class = self.runtime.load_class(&class)?;
-                        let class = self.runtime.get_class(class);
-                        let name = self.runtime.read_static_string(name);
-                        class.member_table[(name, &field_type.clone().into())]
-                    };
+                Instruction::Getfield { offset, kind } => {
                     let object_ref = self.pop();
+                    if object_ref == Value::NULL {
+                        self.throw_new("java/lang/NullPointerException", None)?;
+                        continue;
+                    }
+                    let field = object_ref.offset(offset as u32);
 
-                    match field_type {
-                        FieldDescriptor::Boolean |
-                        FieldDescriptor::Byte => {
-                            let value = self.read_u8_ref(object_ref.offset(offset as u32)).unwrap();
+                    match kind {
+                        ValueKind::Byte => {
+                            let value = self.read_u8_ref(field).unwrap();
                             self.push(value as i8);
                         }
-                        FieldDescriptor::Char |
-                        FieldDescriptor::Short => {
-                            let value = self.read_u16_ref(object_ref.offset(offset as u32)).unwrap();
+                        ValueKind::Char => {
+                            let value = self.read_u16_ref(field).unwrap();
+                            self.push(Value(value as u32));
+                        }
+                        ValueKind::Short => {
+                            let value = self.read_u16_ref(field).unwrap();
                             self.push(value as i16);
                         }
-                        FieldDescriptor::ClassRef(_) |
-                        FieldDescriptor::ArrRef(_) |
-                        FieldDescriptor::Float |
-                        FieldDescriptor::Int => {
-                            let value = self.read_u32_ref(object_ref.offset(offset as u32)).unwrap();
-                            self.push(value as i32);
+                        ValueKind::Int | ValueKind::Float | ValueKind::Reference => {
+                            let value = self.read_u32_ref(field).unwrap();
+                            self.push(Value(value));
                         }
-                        FieldDescriptor::Double |
-                        FieldDescriptor::Long => {
-                            let v1 = self.read_u32_ref(object_ref.offset(offset as u32)).unwrap();
-                            let v2 = self.read_u32_ref(object_ref.offset(offset as u32 + 4)).unwrap();
+                        ValueKind::Long => {
+                            let v1 = self.read_u32_ref(field).unwrap();
+                            let v2 = self.read_u32_ref(field.offset(4)).unwrap();
                             self.push2((Value(v1), Value(v2)));
                         }
                     }
                 }
-                Opcode::Putstatic => {
+                Instruction::Putstatic { class, offset, kind } => {
+                    if !self.ensure_initialized(class, ins_pc)? {
+                        continue;
+                    }
+                    let offset = offset as usize;
 
+                    let value = match kind {
+                        ValueKind::Long => Box::new(values_into_u64(self.pop2()).to_ne_bytes()),
+                        kind => field_bytes(self.pop().into_u32(), kind.size() as u16),
+                    };
+                    let static_fields = &mut self.runtime.classes[class as usize].static_fields;
+                    static_fields[offset..offset + value.len()].copy_from_slice(&value);
                 }
-                Opcode::Putfield => {
+                Instruction::Putfield { offset, kind } => {
                     // TODO: no unsafe in rt, all unsafe should be in its own module
-                    let cpn = self.decode_u16();
-                    let (class_index, name_and_type_index) = self.read_constant(cpn).fieldref();
-                    let class = self.read_constant(self.read_constant(class_index).class()).utf8();
-                    let (name, field_type) = self.read_constant(name_and_type_index).nameandtype();
-                    let name = self.read_constant(name).utf8();
-                    let field_type = self.read_constant(field_type).utf8();
-                    let field_type = FieldDescriptor::from_bytes(self.runtime.read_static_string(field_type).as_bytes())?;
-
-                    let offset = {
-                        let class = self.runtime.read_static_string(class).to_string();
-                        let class = self.runtime.load_class(&class)?;
-                        let class = self.runtime.get_class(class);
-                        let name = self.runtime.read_static_string(name);
-                        class.member_table()[(name, &field_type.clone().into())]
-                    };
+                    let unit_size = if kind == ValueKind::Long { 2 } else { 1 };
+                    let object_ref = self.stack[self.stack.len() - unit_size - 1];
+                    if object_ref == Value::NULL {
+                        self.throw_new("java/lang/NullPointerException", None)?;
+                        continue;
+                    }
+                    let field = object_ref.offset(offset as u32);
 
-                    match field_type {
-                        FieldDescriptor::Boolean |
-                        FieldDescriptor::Byte => {
+                    match kind {
+                        ValueKind::Byte => {
                             let value = self.pop().into_u8();
-                            let object_ref = self.pop();
-                            self.write_u8_ref(object_ref.offset(offset as u32), value);
+                            self.pop();
+                            self.write_u8_ref(field, value);
                         }
-                        FieldDescriptor::Char |
-                        FieldDescriptor::Short => {
+                        ValueKind::Char | ValueKind::Short => {
                             let value = self.pop().into_u16();
-                            let object_ref = self.pop();
-                            self.write_u16_ref(object_ref.offset(offset as u32), value);
+                            self.pop();
+                            self.write_u16_ref(field, value);
                         }
-                        FieldDescriptor::ClassRef(_) |
-                        FieldDescriptor::ArrRef(_) |
-                        FieldDescriptor::Float |
-                        FieldDescriptor::Int => {
+                        ValueKind::Int | ValueKind::Float | ValueKind::Reference => {
                             let value = self.pop().into_u32();
-                            let object_ref = self.pop();
-                            self.write_u32_ref(object_ref.offset(offset as u32), value);
+                            self.pop();
+                            self.write_u32_ref(field, value);
                         }
-                        FieldDescriptor::Double |
-                        FieldDescriptor::Long => {
+                        ValueKind::Long => {
                             let (v1, v2) = self.pop2();
-                            let v1 = v1.into_u32();
-                            let v2 = v2.into_u32();
-                            let object_ref = self.pop();
-                            self.write_u32_ref(object_ref.offset(offset as u32), v1);
-                            self.write_u32_ref(object_ref.offset(offset as u32 + 4), v2);
+                            self.pop();
+                            self.write_u32_ref(field, v1.into_u32());
+                            self.write_u32_ref(field.offset(4), v2.into_u32());
                         }
                     }
                 }
-                Opcode::Invokevirtual |
-                Opcode::Invokespecial => {
-                    let cpn = self.decode_u16();
-                    let (class_index, name_and_type_index) = self.read_constant(cpn).methodref();
-                    let class_name = self.read_constant(self.read_constant(class_index).class()).utf8();
-                    let class_name = self.runtime.read_static_string(class_name);
-                    let (name_index, descriptor_index) = self.read_constant(name_and_type_index).nameandtype();
-                    let name = self.read_constant(name_index).utf8();
-                    let name = self.runtime.read_static_string(name);
-                    let method_type = self.read_constant(descriptor_index).utf8();
-                    let method_type = self.runtime.read_static_string(method_type);
-                    let method_type = MethodDescriptor::from_bytes(method_type.as_bytes())?;
-                    eprintln!("Invoking {class_name} {}", method_type.display_type(&name));
-                    let class_name = class_name.to_string(); // TODO: bad
-                    let name = name.to_string(); // TODO: bad
-                    self.call_named(&class_name, &name, method_type)?;
+                Instruction::InvokeVirtual { site, arg_size } => {
+                    let receiver = self.stack[self.stack.len() - arg_size as usize - 1];
+                    if receiver == Value::NULL {
+                        self.throw_new("java/lang/NullPointerException", None)?;
+                        continue;
+                    }
+                    let Some((class, method)) = self.dispatch(site, self.get_class_id(receiver))? else { continue };
+                    self.invoke(class, method)?;
+                }
+                Instruction::InvokeSpecial { class, method, arg_size } => {
+                    if self.stack[self.stack.len() - arg_size as usize - 1] == Value::NULL {
+                        self.throw_new("java/lang/NullPointerException", None)?;
+                        continue;
+                    }
+                    self.invoke(class, method)?;
+                }
+                Instruction::InvokeStatic { class, method } => {
+                    if !self.ensure_initialized(class, ins_pc)? {
+                        continue;
+                    }
+                    self.invoke(class, method)?;
                 }
-                Opcode::Invokestatic => todo!(),
-                Opcode::Invokeinterface => todo!(),
-                Opcode::Invokedynamic => todo!(),
-                Opcode::New => {
-                    let cpn = self.decode_u16();
-                    let class_name = self.read_constant(self.read_constant(cpn).class()).utf8();
-                    let class_name = self.runtime.read_static_string(class_name);
-                    let class_name = class_name.to_string(); // TODO: bad
-                    let id = self.runtime.load_class(&class_name)?;
-                    let r = self.new(id);
+                Instruction::Invokedynamic(cpn) => self.invoke_dynamic(cpn, ins_pc)?,
+                Instruction::New(id) => {
+                    if !self.ensure_initialized(id, ins_pc)? {
+                        continue;
+                    }
+                    let size = self.runtime.get_class(id).get_aligned_data_size();
+                    if !self.make_room(size as usize)? {
+                        continue;
+                    }
+                    let r = self.new_object(id);
                     self.push(r);
                 }
-                Opcode::Newarray => todo!(),
-                Opcode::Anewarray => todo!(),
-                Opcode::Arraylength => todo!(),
-                Opcode::Athrow => todo!(),
-                Opcode::Checkcast => {
-                    let cpn = self.decode_u16();
-                    let class_name = self.read_constant(self.read_constant(cpn).class()).utf8();
-                    let class_name = self.runtime.read_static_string(class_name);
-                    let class_name = class_name.to_string(); // TODO: bad
-                    let id = self.runtime.load_class(&class_name)?;
-
-                    let objectref = self.pop();
-                    // TODO: check its type
-                    self.push(objectref);
+                Instruction::NewArray(class) => {
+                    let count = self.pop().into_i32();
+                    if count < 0 {
+                        self.throw_new("java/lang/NegativeArraySizeException", Some(count.to_string()))?;
+                        continue;
+                    }
+                    if !self.make_room(self.array_size(class, count as u32))? {
+                        continue;
+                    }
+                    let r = self.new_array(class, count as u32);
+                    self.push(r);
+                }
+                Instruction::MultiNewArray { class, dimensions } => {
+                    let start = self.stack.len() - dimensions as usize;
+                    let counts: Vec<_> = self.stack.drain(start..).map(Value::into_i32).collect();
+                    if let Some(&count) = counts.iter().find(|&&count| count < 0) {
+                        self.throw_new("java/lang/NegativeArraySizeException", Some(count.to_string()))?;
+                        continue;
+                    }
+                    let counts: Vec<_> = counts.into_iter().map(|count| count as u32).collect();
+                    if !self.make_room(self.multi_array_size(class, &counts))? {
+                        continue;
+                    }
+                    let r = self.new_multi_array(class, &counts);
+                    self.push(r);
+                }
+                Instruction::Arraylength => {
+                    let arr_ref = self.pop();
+                    if arr_ref == Value::NULL {
+                        self.throw_new("java/lang/NullPointerException", None)?;
+                        continue;
+                    }
+                    let length = self.read_u32_ref(arr_ref.offset(ARRAY_LENGTH_OFFSET)).unwrap();
+                    self.push(Value(length));
+                }
+                Instruction::Athrow => {
+                    let exception = self.pop();
+                    if exception == Value::NULL {
+                        self.throw_new("java/lang/NullPointerException", None)?;
+                    } else {
+                        self.throw(exception)?;
+                    }
+                }
+                Instruction::Checkcast(class) => {
+                    let objectref = self.top();
+                    if objectref != Value::NULL && !self.is_instance_of(objectref, class) {
+                        let message = format!(
+                            "class {} cannot be cast to class {}",
+                            self.get_class_name(objectref).replace('/', "."),
+                            self.runtime.get_class(class).name.replace('/', "."),
+                        );
+                        self.throw_new("java/lang/ClassCastException", Some(message))?;
+                    }
                 }
-                Opcode::Instanceof => {
-                    let index = self.decode_u16();
+                Instruction::Instanceof(class) => {
                     let objectref = self.pop();
-                    // TODO: check of objectref is a index
-                    self.push(false);
+                    self.push(objectref != Value::NULL && self.is_instance_of(objectref, class));
                 }
-                Opcode::Monitorenter => todo!(),
-                Opcode::Monitorexit => todo!(),
-                Opcode::Wide => todo!(),
-                Opcode::Multianewarray => todo!(),
-                Opcode::GotoW => todo!(),
-                Opcode::JsrW => todo!(),
-
-                Opcode::Breakpoint |
-                Opcode::ReservedFuture |
-                Opcode::Impdep1 |
-                Opcode::Impdep2 => return Err(RtError::ReservedInstruction)
+                Instruction::Monitorenter => {
+                    let object = self.pop();
+                    if object == Value::NULL {
+                        self.throw_new("java/lang/NullPointerException", None)?;
+                        continue;
+                    }
+                    self.monitor_enter(object);
+                }
+                Instruction::Monitorexit => {
+                    let object = self.pop();
+                    if object == Value::NULL {
+                        self.throw_new("java/lang/NullPointerException", None)?;
+                    } else if !self.monitor_exit(object) {
+                        self.throw_new("java/lang/IllegalMonitorStateException", None)?;
+                    }
+                }
+                Instruction::Unlinked(..) => unreachable!(),
+                Instruction::Operand => return Err(RtError::InvalidCode),
+                Instruction::Reserved => return Err(RtError::ReservedInstruction),
             }
         }
         Ok(())
     }
     
-    fn read_string_object(&self, sref: Value) -> Option<&str> {
-        Some(match sref.into_ref() {
-            Reference::Invalid => return None,
-            Reference::Heap(offset) => {
-                let len = self.heap[offset as usize / 4];
-                let bytes = &self.heap.as_bytes_32aligned()[offset as usize + 4..][..len as usize];
-                unsafe {
-                    from_utf8_unchecked(bytes)
-                }
-            }
-            Reference::Static(soffset) => {
-                self.runtime.read_static_string(soffset)
-            }
-        })
-    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -877,6 +1183,7 @@ pub enum ReturnCategory {
     Cat2 = 2,
 }
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(transparent)]
 pub struct Value(u32);
 impl Value {
     pub const NULL: Self = Value(0);
@@ -921,12 +1228,23 @@ impl Value {
         Value(self.0 + offset)
     }
 }
+/// The native-endian bytes of a category 1 value stored in a field of the given size
+fn field_bytes(value: u32, size: u16) -> Box<[u8]> {
+    match size {
+        1 => Box::new((value as u8).to_ne_bytes()),
+        2 => Box::new((value as u16).to_ne_bytes()),
+        _ => Box::new(value.to_ne_bytes()),
+    }
+}
 fn values_into_u64(value: (Value, Value)) -> u64 {
     unsafe { transmute(value) }
 }
 fn values_into_f64(value: (Value, Value)) -> f64 {
     f64::from_bits(values_into_u64(value))
 }
+fn u64_into_values(value: u64) -> (Value, Value) {
+    unsafe { transmute(value) }
+}
 fn i64_into_values(value: i64) -> (Value, Value) {
     unsafe { transmute(value) }
 }
@@ -983,6 +1301,16 @@ impl RuntimeConstant {
     pub const fn value(self) -> Value {
         Value(self.0)
     }
+    /// The entries of a `Long` or `Double` constant and the `Gap` after it, holding the words of
+    /// `value` in the order the operand stack does
+    fn wide(value: u64) -> [Self; 2] {
+        let (v1, v2) = u64_into_values(value);
+        [RuntimeConstant(v1.0), RuntimeConstant(v2.0)]
+    }
+    /// The value of a `Long` or `Double` constant, with `gap` the entry after it
+    pub const fn wide_value(self, gap: Self) -> (Value, Value) {
+        (Value(self.0), Value(gap.0))
+    }
     pub const fn nameandtype(self) -> (ConstIndex, ConstIndex) {
         unsafe { transmute(self.0) }
     }
@@ -995,6 +1323,10 @@ impl RuntimeConstant {
     pub const fn methodtype(self) -> ConstIndex {
         self.0 as ConstIndex
     }
+    /// A constant made of two indices, like `Fieldref` or `NameAndType`
+    pub const fn pair(a: ConstIndex, b: ConstIndex) -> Self {
+        unsafe { transmute((a, b)) }
+    }
     pub const fn invokedynamic(self) -> (ConstIndex, ConstIndex) {
         unsafe { transmute(self.0) }
     }
@@ -1004,91 +1336,406 @@ impl RuntimeConstant {
         self.0 as ConstIndex
     }
 }
+type BuiltinMethod = fn(&mut RuntimeCtx) -> Result<()>;
 #[derive(Debug, Clone)]
 enum RuntimeInfo {
-    Builtin(Box<[fn(&mut RuntimeCtx)]>),
+    Builtin(Box<[BuiltinMethod]>),
     Bytecode {
         method_code: Box<[BytecodeMethod]>,
         constant_pool: Box<[RuntimeConstant]>,
+        /// What the entries of the constant pool were resolved to
+        resolved: Box<[Resolution]>,
+        bootstrap_methods: Box<[indy::Bootstrap]>,
     }
 }
+/// What runs when a method is invoked
+enum MethodImpl<'a> {
+    Builtin(BuiltinMethod),
+    Native(Rc<NativeMethod>),
+    Bytecode(&'a BytecodeMethod),
+}
 mod member_table;
 use self::member_table::MemberTable;
 #[derive(Debug, Clone)]
 struct LoadedClass {
+    /// Binary name with slashes, or the descriptor for array classes
+    name: Box<str>,
+    access_flags: ClassAccess,
     super_class: u32,
     interfaces: Box<[u32]>,
+    /// Set for array classes
+    array_component: Option<ArrayComponent>,
     /// name + ' ' + type -> offset (in instance fields, static fields or method table)
     member_table: MemberTable,
+    /// Methods declared without code, calls run the method of the receiver so these are only
+    /// looked up when resolving a method reference
+    abstract_methods: MemberTable,
     static_fields: Box<Bytes32Aligned>,
     runtime_info: RuntimeInfo,
+    /// Instance size including the object header
     data_size: u16,
+    /// Offsets of the instance fields holding references, including inherited ones
+    ref_offsets: Box<[u16]>,
+    /// Offsets of the static fields holding references
+    static_ref_offsets: Box<[u16]>,
+    /// Offset of the `java.lang.Class` instance in the statics
+    class_object: u32,
+    init_state: InitState,
+    /// Method id of `<clinit>`
+    clinit: Option<u16>,
+    /// Methods implemented in Rust, see [`NATIVE_METHOD_BASE`]
+    natives: Vec<Rc<NativeMethod>>,
+    debug_info: ClassDebugInfo,
 }
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InitState {
+    Uninitialized,
+    /// `<clinit>` runs on `thread`, other threads wait for it to finish. If it throws, the
+    /// exception comes from the instruction that triggered it, with the pc at `resume_pc`.
+    InProgress { thread: usize, resume_pc: usize },
+    Initialized,
+    /// `<clinit>` threw, any further use gives a `NoClassDefFoundError`
+    Erroneous,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArrayComponent {
+    Primitive(PrimitiveArrayType),
+    Reference(u32),
+}
+#[derive(Debug, Clone)]
 struct BytecodeMethod {
     max_stack: u16,
     max_locals: u16,
     arg_num: u16,
     code_location: usize,
+    exception_table: Box<[ExceptionEntry]>,
+    access_flags: MethodAccess,
+    debug: MethodDebugInfo,
 }
 
 impl LoadedClass {
+    fn new_builtin(name: &str, super_class: u32, member_table: MemberTable, methods: Box<[BuiltinMethod]>) -> Self {
+        LoadedClass {
+            name: name.into(),
+            access_flags: ClassAccess::PUBLIC,
+            super_class,
+            interfaces: Box::new([]),
+            array_component: None,
+            member_table,
+            abstract_methods: MemberTable::new(),
+            static_fields: Bytes32Aligned::new_zeroed(0),
+            runtime_info: RuntimeInfo::Builtin(methods),
+            data_size: OBJECT_HEADER_SIZE,
+            ref_offsets: Box::new([]),
+            static_ref_offsets: Box::new([]),
+            class_object: 0,
+            init_state: InitState::Initialized,
+            clinit: None,
+            natives: Vec::new(),
+            debug_info: ClassDebugInfo::default(),
+        }
+    }
     pub fn get_aligned_data_size(&self) -> u16 {
         (self.data_size + 3) & !3
     }
     fn member_table(&self) -> &MemberTable {
         &self.member_table
     }
-    fn method(&self, method_id: u16) -> Result<BytecodeMethod, fn(&mut RuntimeCtx)> {
+    fn is_interface(&self) -> bool {
+        self.access_flags.contains(ClassAccess::INTERFACE)
+    }
+    fn array_element_size(&self) -> Option<u16> {
+        Some(match self.array_component? {
+            ArrayComponent::Primitive(PrimitiveArrayType::Boolean | PrimitiveArrayType::Byte) => 1,
+            ArrayComponent::Primitive(PrimitiveArrayType::Char | PrimitiveArrayType::Short) => 2,
+            ArrayComponent::Primitive(PrimitiveArrayType::Int | PrimitiveArrayType::Float) => 4,
+            ArrayComponent::Primitive(PrimitiveArrayType::Long | PrimitiveArrayType::Double) => 8,
+            ArrayComponent::Reference(_) => 4,
+        })
+    }
+    fn method(&self, method_id: u16) -> MethodImpl<'_> {
+        if method_id >= NATIVE_METHOD_BASE {
+            return MethodImpl::Native(self.natives[(method_id - NATIVE_METHOD_BASE) as usize].clone());
+        }
         match &self.runtime_info {
-            RuntimeInfo::Builtin(method_code) => Err(method_code[method_id as usize]),
-            RuntimeInfo::Bytecode{method_code, ..} => Ok(method_code[method_id as usize]),
+            RuntimeInfo::Builtin(method_code) => MethodImpl::Builtin(method_code[method_id as usize]),
+            RuntimeInfo::Bytecode{method_code, ..} => MethodImpl::Bytecode(&method_code[method_id as usize]),
         }
     }
 }
 
+/// Instance field offsets of `detailMessage` and `cause` in `java.lang.Throwable`
+const THROWABLE_MESSAGE_OFFSET: u32 = OBJECT_HEADER_SIZE as u32;
+const THROWABLE_CAUSE_OFFSET: u32 = THROWABLE_MESSAGE_OFFSET + 4;
+/// Instance field offset of the class id in `java.lang.Class`
+const CLASS_ID_OFFSET: u32 = OBJECT_HEADER_SIZE as u32;
+/// Instance field offset of the standard stream in the builtin `java.io.PrintStream` and
+/// `java.io.InputStream`, see [`stdio::STDOUT`]
+const STREAM_ID_OFFSET: u32 = OBJECT_HEADER_SIZE as u32;
+
 #[derive(Debug, Clone)]
 pub struct Runtime {
     class_names: BTreeMap<Box<str>, u32>,
     classes: Vec<LoadedClass>,
     code: Vec<u32>,
     statics: Vec<u32>,
+    /// Offsets of the objects in the statics, their reference fields are roots for the collector
+    static_objects: Vec<u32>,
+    hashes: HashGenerator,
+    random: corelib::RandomGenerator,
+    max_heap_size: usize,
+    limits: Limits,
+    time_slice: u64,
+    gc_stats: GcStats,
+    /// Content -> offset of the string object in the statics
+    interned: BTreeMap<Box<[u16]>, u32>,
+    /// The code decoded for the interpreter, each instruction at the position of its opcode
+    instructions: Vec<Decoded>,
+    /// Linked `invokevirtual` and `invokeinterface` instructions
+    virtual_calls: Vec<VirtualCall>,
+    /// The targets of `tableswitch` and `lookupswitch` instructions
+    switches: Vec<Switch>,
+    /// Linked `invokedynamic` instructions by their position in the code
+    call_sites: BTreeMap<usize, Rc<CallSite>>,
+    /// Where classes that are not builtin come from
+    loader: SharedLoader,
+    natives: NativeRegistry,
+    stdio: Stdio,
+    debugger: Option<SharedDebugger>,
+    profile: Option<Profile>,
+    #[cfg(feature = "jit")]
+    jit: Option<jit::Jit>,
+}
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl Runtime {
     pub fn new() -> Self {
-        let mut class_names = BTreeMap::new();
-        class_names.insert("java/lang/Object".into(), 0);
-        Self {
-            class_names,
+        let mut rt = Self {
+            class_names: BTreeMap::new(),
             code: Vec::new(),
             statics: Vec::new(),
-            classes: vec![LoadedClass {
-                super_class: 0,
-                data_size: 0,
-                interfaces: Box::new([]),
-                static_fields: Bytes32Aligned::new_zeroed(0),
-                member_table: {
-                    let mut table = MemberTable::new();
-                    table.insert("<init>", MethodDescriptor::new_void([]), 0);
-                    table.insert("equals", MethodDescriptor::new_void([]), 1);
-                    table.insert("getClass", MethodDescriptor::new_ret([], FieldDescriptor::Boolean), 2);
-                    table.insert("hashCode", MethodDescriptor::new_ret([], FieldDescriptor::Int), 3);
-                    table.insert("toString", MethodDescriptor::new_ret([], FieldDescriptor::ClassRef("java/lang/String".into())), 4);
-                    table
-                },
-                runtime_info: RuntimeInfo::Builtin(Box::new([
-                    builtin_methods::obj_init,
-                    builtin_methods::obj_equals,
-                    builtin_methods::obj_get_class,
-                    builtin_methods::obj_hash_code,
-                    builtin_methods::obj_to_string,
-                ])),
-            }],
-        }
+            static_objects: Vec::new(),
+            classes: Vec::new(),
+            hashes: HashGenerator::new(),
+            random: corelib::RandomGenerator::new(),
+            max_heap_size: gc::DEFAULT_MAX_HEAP_SIZE,
+            limits: Limits::default(),
+            time_slice: thread::DEFAULT_TIME_SLICE,
+            gc_stats: GcStats::default(),
+            interned: BTreeMap::new(),
+            natives: NativeRegistry::default(),
+            instructions: Vec::new(),
+            virtual_calls: Vec::new(),
+            switches: Vec::new(),
+            call_sites: BTreeMap::new(),
+            loader: SharedLoader::new(ClassPath::default()),
+            stdio: Stdio::default(),
+            debugger: None,
+            profile: None,
+            #[cfg(feature = "jit")]
+            jit: None,
+        };
+        let object = LoadedClass::new_builtin("java/lang/Object", OBJECT_CLASS, {
+            let mut table = MemberTable::new();
+            table.insert("<init>", MethodDescriptor::new_void([]), 0);
+            table.insert("equals", MethodDescriptor::new_ret([FieldDescriptor::ClassRef("java/lang/Object".into())], FieldDescriptor::Boolean), 1);
+            table.insert("getClass", MethodDescriptor::new_ret([], FieldDescriptor::ClassRef("java/lang/Class".into())), 2);
+            table.insert("hashCode", MethodDescriptor::new_ret([], FieldDescriptor::Int), 3);
+            table.insert("toString", MethodDescriptor::new_ret([], FieldDescriptor::ClassRef("java/lang/String".into())), 4);
+            table.insert("wait", MethodDescriptor::new_void([]), 5);
+            table.insert("wait", MethodDescriptor::new_void([FieldDescriptor::Long]), 6);
+            table.insert("notify", MethodDescriptor::new_void([]), 7);
+            table.insert("notifyAll", MethodDescriptor::new_void([]), 8);
+            table.insert("clone", MethodDescriptor::new_ret([], FieldDescriptor::ClassRef("java/lang/Object".into())), 9);
+            table
+        }, Box::new([
+            builtin_methods::obj_init,
+            builtin_methods::obj_equals,
+            builtin_methods::obj_get_class,
+            builtin_methods::obj_hash_code,
+            builtin_methods::obj_to_string,
+            builtin_methods::obj_wait,
+            builtin_methods::obj_wait_timeout,
+            builtin_methods::obj_notify,
+            builtin_methods::obj_notify_all,
+            builtin_methods::obj_clone,
+        ]));
+        let id = rt.add_class(object);
+        rt.class_names.insert("java/lang/Object".into(), id);
+        assert_eq!(rt.load_class("java/lang/String").unwrap(), STRING_CLASS);
+        assert_eq!(rt.load_class("java/lang/Class").unwrap(), CLASS_CLASS);
+        // the interfaces of `String` come after the classes with fixed ids
+        let interfaces = ["java/io/Serializable", "java/lang/Comparable", "java/lang/CharSequence"]
+            .map(|interface| rt.load_class(interface).unwrap());
+        rt.classes[STRING_CLASS as usize].interfaces = Box::new(interfaces);
+        rt
+    }
+    /// Sets the size in bytes the heap may grow to before allocations throw `OutOfMemoryError`.
+    ///
+    /// Heap references can address at most 2 GiB, larger sizes are clamped to that.
+    pub fn with_max_heap_size(mut self, bytes: usize) -> Self {
+        self.max_heap_size = bytes.min(gc::HEAP_ADDRESS_LIMIT);
+        self
+    }
+    /// Sets how many frames deep calls may nest before invoking a method throws
+    /// `StackOverflowError`, 4096 by default
+    pub fn with_max_call_depth(mut self, depth: usize) -> Self {
+        self.limits.max_call_depth = depth;
+        self
+    }
+    /// Sets how many instructions a thread executes before the next one gets a turn, 10000 by
+    /// default
+    pub fn with_time_slice(mut self, instructions: u64) -> Self {
+        self.time_slice = instructions.max(1);
+        self
+    }
+    /// Gives every context the fuel to execute this many instructions, after which it stops with
+    /// [`RtError::LimitReached`] until it is given more with [`RuntimeCtx::add_fuel`]
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.limits.fuel = Some(fuel);
+        self
+    }
+    /// Stops contexts with [`RtError::LimitReached`] once this much time passed since they were
+    /// created, see [`RuntimeCtx::set_deadline`]
+    pub fn with_time_limit(mut self, limit: Duration) -> Self {
+        self.limits.time_limit = Some(limit);
+        self
+    }
+    /// Sets where classes that are not builtin are loaded from, the current directory by default
+    pub fn with_class_path(self, class_path: ClassPath) -> Self {
+        self.with_loader(class_path)
+    }
+    /// Loads classes that are not builtin with `loader` instead of the class path.
+    ///
+    /// Clones of the runtime share the loader.
+    pub fn with_loader(mut self, loader: impl ClassLoader + 'static) -> Self {
+        self.loader = SharedLoader::new(loader);
+        self
+    }
+    /// Sends what Java code prints to `System.out` to `out` instead of the standard output.
+    ///
+    /// Clones of the runtime share it, a [`SharedBuffer`] collects the output.
+    pub fn with_stdout(mut self, out: impl io::Write + 'static) -> Self {
+        self.stdio.set_out(out);
+        self
+    }
+    /// Sends what Java code prints to `System.err` to `err` instead of the standard error
+    pub fn with_stderr(mut self, err: impl io::Write + 'static) -> Self {
+        self.stdio.set_err(err);
+        self
+    }
+    /// Lets Java code read `System.in` from `input` instead of the standard input
+    pub fn with_stdin(mut self, input: impl io::Read + 'static) -> Self {
+        self.stdio.set_input(input);
+        self
+    }
+    /// Stops runs before their first instruction and at breakpoints to let `debugger` look at them.
+    ///
+    /// Clones of the runtime share the debugger.
+    pub fn with_debugger(mut self, debugger: impl Debugger + 'static) -> Self {
+        self.debugger = Some(SharedDebugger::new(debugger));
+        self
+    }
+    /// Profiles runs: counts the invocations of bytecode methods, the opcodes executed and the
+    /// allocations, and samples the call stacks every `sample_interval` instructions, 1 for exact
+    /// instruction counts per method
+    pub fn with_profiling(mut self, sample_interval: u64) -> Self {
+        self.profile = Some(Profile::new(sample_interval));
+        self
+    }
+    /// What the runs did so far, if profiling is on
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+    /// Compiles bytecode methods to native code once they were invoked `threshold` times
+    #[cfg(feature = "jit")]
+    pub fn with_jit(mut self, threshold: u32) -> Self {
+        self.jit = Some(jit::Jit::new(threshold.max(1)));
+        self
+    }
+    /// What the JIT did so far, if it is on
+    #[cfg(feature = "jit")]
+    pub fn jit_stats(&self) -> Option<&JitStats> {
+        self.jit.as_ref().map(jit::Jit::stats)
+    }
+    /// Statistics about garbage collection, accumulated over all runs
+    pub fn gc_stats(&self) -> &GcStats {
+        &self.gc_stats
     }
     fn get_class(&self, id: u32) -> &LoadedClass {
         &self.classes[id as usize]
     }
+    /// Registers a class and creates its `java.lang.Class` instance
+    fn add_class(&mut self, mut class: LoadedClass) -> u32 {
+        let id = self.classes.len() as u32;
+        class.class_object = self.new_static_obj(CLASS_CLASS, &id.to_ne_bytes());
+        self.classes.push(class);
+        id
+    }
+    /// Finds a method in the class or its superclasses and superinterfaces
+    fn find_method(&self, class: u32, name: &str, descriptor: &AnyDescriptor) -> Option<(u32, u16)> {
+        let loaded = self.get_class(class);
+        if let Some(method_id) = loaded.member_table().get(name, descriptor) {
+            return Some((class, method_id));
+        }
+        if class == OBJECT_CLASS {
+            return None;
+        }
+        self.find_method(loaded.super_class, name, descriptor)
+            .or_else(|| loaded.interfaces.iter().find_map(|&i| self.find_method(i, name, descriptor)))
+    }
+    /// Method resolution (JVMS 5.4.3.3) and interface method resolution (JVMS 5.4.3.4): whether
+    /// the class, its superclasses or superinterfaces declare the method, abstract or not
+    fn has_method(&self, class: u32, name: &str, descriptor: &AnyDescriptor) -> bool {
+        let loaded = self.get_class(class);
+        if loaded.member_table().get(name, descriptor).is_some() || loaded.abstract_methods.get(name, descriptor).is_some() {
+            return true;
+        }
+        class != OBJECT_CLASS && (self.has_method(loaded.super_class, name, descriptor)
+            || loaded.interfaces.iter().any(|&i| self.has_method(i, name, descriptor)))
+    }
+    /// Field resolution (JVMS 5.4.3.2): the class itself, then its superinterfaces, then its superclass
+    fn resolve_field(&self, class: u32, name: &str, descriptor: &AnyDescriptor) -> Option<(u32, u16)> {
+        let loaded = self.get_class(class);
+        if let Some(offset) = loaded.member_table().get(name, descriptor) {
+            return Some((class, offset));
+        }
+        loaded.interfaces.iter()
+            .find_map(|&i| self.resolve_field(i, name, descriptor))
+            .or_else(|| if class == OBJECT_CLASS {
+                None
+            } else {
+                self.resolve_field(loaded.super_class, name, descriptor)
+            })
+    }
+    /// Whether a value of class `s` can be assigned to a variable of class `t`,
+    /// following the rules of `checkcast` and `instanceof`.
+    pub fn is_assignable(&self, s: u32, t: u32) -> bool {
+        if s == t || t == OBJECT_CLASS {
+            return true;
+        }
+        let (sc, tc) = (self.get_class(s), self.get_class(t));
+        match (sc.array_component, tc.array_component) {
+            (Some(ArrayComponent::Reference(sc)), Some(ArrayComponent::Reference(tc))) => self.is_assignable(sc, tc),
+            // different primitive array types are different classes
+            (_, Some(_)) => false,
+            // arrays only have Object, Cloneable and Serializable as supertypes, which are covered here too
+            _ => self.has_supertype(s, t),
+        }
+    }
+    fn has_supertype(&self, s: u32, t: u32) -> bool {
+        if s == t {
+            return true;
+        }
+        if s == OBJECT_CLASS {
+            return false;
+        }
+        let class = self.get_class(s);
+        self.has_supertype(class.super_class, t) || class.interfaces.iter().any(|&i| self.has_supertype(i, t))
+    }
     /// The class path should separate packages with slashes (`/`)
     pub fn load_class(&mut self, classpath: &str) -> Result<u32> {
         if let Some(&v) = self.class_names.get(classpath) {
@@ -1096,21 +1743,67 @@ impl Runtime {
         }
         eprintln!("Loading {classpath}");
 
-        let id;
-        if let Some(cls) = self.load_builtin(classpath) {
-            id = self.classes.len() as u32;
-            self.classes.push(cls);
+        let id = if let Some(component) = classpath.strip_prefix('[') {
+            let component = match FieldDescriptor::from_bytes(component.as_bytes())? {
+                FieldDescriptor::ClassRef(name) => ArrayComponent::Reference(self.load_class(&name)?),
+                FieldDescriptor::ArrRef(_) => ArrayComponent::Reference(self.load_class(component)?),
+                fd => ArrayComponent::Primitive(match fd {
+                    FieldDescriptor::Boolean => PrimitiveArrayType::Boolean,
+                    FieldDescriptor::Char => PrimitiveArrayType::Char,
+                    FieldDescriptor::Float => PrimitiveArrayType::Float,
+                    FieldDescriptor::Double => PrimitiveArrayType::Double,
+                    FieldDescriptor::Byte => PrimitiveArrayType::Byte,
+                    FieldDescriptor::Short => PrimitiveArrayType::Short,
+                    FieldDescriptor::Int => PrimitiveArrayType::Int,
+                    FieldDescriptor::Long => PrimitiveArrayType::Long,
+                    FieldDescriptor::ClassRef(_) | FieldDescriptor::ArrRef(_) => unreachable!(),
+                }),
+            };
+            let interfaces = Box::new([
+                self.load_class("java/lang/Cloneable")?,
+                self.load_class("java/io/Serializable")?,
+            ]);
+            self.add_class(LoadedClass {
+                access_flags: ClassAccess::PUBLIC | ClassAccess::FINAL | ClassAccess::ABSTRACT,
+                interfaces,
+                array_component: Some(component),
+                ..LoadedClass::new_builtin(classpath, OBJECT_CLASS, MemberTable::new(), Box::new([]))
+            })
+        } else if let Some(cls) = self.load_builtin(classpath)? {
+            let id = self.add_class(cls);
+            self.add_registered_natives(id);
+            id
         } else {
-            let path: &Path = classpath.as_ref();
-            let file = File::open(path.with_extension("class"))?;
-            let class_file = ClassFile::from_reader(BufReader::new(file))?;
-            id = self.load_class_file(&class_file)?;
-        }
+            let Some(bytes) = self.loader.find_class(classpath)? else {
+                if self.natives.has_class(classpath) {
+                    let id = self.add_class(LoadedClass::new_builtin(classpath, OBJECT_CLASS, MemberTable::new(), Box::new([])));
+                    self.add_registered_natives(id);
+                    self.class_names.insert(classpath.into(), id);
+                    return Ok(id);
+                }
+                return Err(RtError::ClassNotFound {
+                    name: classpath.into(),
+                    searched: self.loader.search_locations(classpath).into(),
+                });
+            };
+            let class_file = ClassFile::from_reader(&bytes[..])?;
+            self.load_class_file(&class_file)?
+        };
         self.class_names.insert(classpath.into(), id);
         Ok(id)
     }
+    /// Loads the class of arrays with the given component class
+    pub fn load_array_class(&mut self, component: u32) -> Result<u32> {
+        let component = &self.get_class(component).name;
+        let name = if component.starts_with('[') {
+            format!("[{component}")
+        } else {
+            format!("[L{component};")
+        };
+        self.load_class(&name)
+    }
     fn load_class_file(&mut self, class_file: &ClassFile) -> Result<u32> {
-        let mut data_size = 0;
+        let name = class_file.constant_class(class_file.this_class).unwrap();
         let mut member_table = MemberTable::new();
 
         let super_class = self.load_class(class_file.constant_class(class_file.super_class).unwrap())?;
@@ -1121,154 +1814,229 @@ impl Runtime {
             })
             .collect_result()?;
 
-        for i in iter::once(super_class).chain(interfaces.iter().copied()) {
-            let super_class = self.get_class(i);
-            data_size += super_class.get_aligned_data_size();
-        }
         let mut fields_ordered: Vec<_> = class_file.fields.iter().map(|field| {
             let name = class_file.constant_utf8(field.name_index).unwrap();
             let descriptor = class_file.constant_fdescriptor(field.descriptor_index).unwrap();
-            let size: u16 = match descriptor {
-                FieldDescriptor::Boolean => 1,
-                FieldDescriptor::Byte => 1,
-                FieldDescriptor::Char => 2,
-                FieldDescriptor::Short => 2,
-                FieldDescriptor::Int => 4,
-                FieldDescriptor::Float => 4,
-                FieldDescriptor::ClassRef(_) => 4,
-                FieldDescriptor::ArrRef(_) => 4,
-                FieldDescriptor::Double => 8,
-                FieldDescriptor::Long => 8,
-            };
-            (name, size, descriptor, field.access_flags.contains(FieldAccess::STATIC))
+            let size = descriptor.byte_size();
+            (name, size, descriptor, field.access_flags)
         }).collect();
         fields_ordered.sort_by_key(|&(_, size, _, _)| Reverse(size));
 
-        let mut offset = 0;
-        let mut static_size = 0;
-        for (name, size, d, is_static) in fields_ordered {
-            if is_static {
-                member_table.insert(name, d, static_size);
-                static_size += size;
+        // instance fields follow the ones inherited from the superclass
+        let mut instance_layout = FieldLayout::starting_at(self.get_class(super_class).data_size);
+        let mut static_layout = FieldLayout::starting_at(0);
+        let mut ref_offsets = self.get_class(super_class).ref_offsets.to_vec();
+        let mut static_ref_offsets = Vec::new();
+        let mut declared_fields = Vec::with_capacity(fields_ordered.len());
+        for (name, size, d, access_flags) in fields_ordered {
+            let (layout, refs) = if access_flags.contains(FieldAccess::STATIC) {
+                (&mut static_layout, &mut static_ref_offsets)
             } else {
-                member_table.insert(name, d, offset);
-                offset += size;
+                (&mut instance_layout, &mut ref_offsets)
+            };
+            let offset = layout.place(size);
+            if let FieldDescriptor::ClassRef(_) | FieldDescriptor::ArrRef(_) = d {
+                refs.push(offset);
             }
+            declared_fields.push(DeclaredField { name: name.into(), descriptor: d.clone(), access_flags, offset });
+            member_table.insert(name, d, offset);
         }
-        data_size += offset;
+        let data_size = instance_layout.end();
+        let static_size = static_layout.end();
         let mut method_code = Vec::with_capacity(class_file.methods.len());
 
+        let mut natives = Vec::new();
+        let mut abstract_methods = MemberTable::new();
         'wasd: for method in &class_file.methods {
             let name = class_file.constant_utf8(method.name_index).unwrap();
             let d = class_file.constant_mdescriptor(method.descriptor_index).unwrap();
+            if method.access_flags.contains(MethodAccess::ABSTRACT) {
+                abstract_methods.insert(name, d, 0);
+                continue;
+            }
+            let implicit_this_arg = !method.access_flags.contains(MethodAccess::STATIC);
+            if method.access_flags.contains(MethodAccess::NATIVE) {
+                member_table.insert(name, d.clone(), NATIVE_METHOD_BASE + natives.len() as u16);
+                natives.push(Rc::new(NativeMethod {
+                    name: name.into(),
+                    function: self.natives.get(class_file.constant_class(class_file.this_class).unwrap(), name, &d),
+                    descriptor: d,
+                    is_static: !implicit_this_arg,
+                }));
+                continue;
+            }
             let id = method_code.len() as u16;
             member_table.insert(name, d.clone(), id);
-            let implicit_this_arg = !method.access_flags.contains(MethodAccess::STATIC);
 
             for attrib in &method.attributes {
-                match attrib {
-                    &AttributeInfo::Code {
-                        max_stack, max_locals, ref code, exception_table: _, attributes: _
-                    } => {
-                        let code_location = self.code.as_bytes_32aligned().len();
-                        self.code.extend_from_bytes(&code.0);
-                        method_code.push(BytecodeMethod {
-                            max_stack,
-                            max_locals,
-                            arg_num: d.arg_types
-                                .iter()
-                                .map(|arg| arg.unit_size() as u16)
-                                .chain(implicit_this_arg.then_some(1))
-                                .sum(),
-                            code_location,
-                        });
-                        continue 'wasd;
-                    }
-                    _ => (),
+                if let &AttributeInfo::Code {
+                    max_stack, max_locals, ref code, ref exception_table, ref attributes
+                } = attrib {
+                    let code_location = self.add_code(&code.0);
+                    method_code.push(BytecodeMethod {
+                        max_stack,
+                        max_locals,
+                        arg_num: d.arg_types
+                            .iter()
+                            .map(|arg| arg.unit_size() as u16)
+                            .chain(implicit_this_arg.then_some(1))
+                            .sum(),
+                        code_location,
+                        exception_table: exception_table.clone(),
+                        access_flags: method.access_flags,
+                        debug: method_debug_info(class_file, name, &d, code.0.len() as u32, attributes),
+                    });
+                    continue 'wasd;
                 }
             }
             unimplemented!();
         }
 
-        let utf8_offsets: BTreeMap<_, _> = class_file.constant_pool
+        let utf8_offsets: Vec<_> = class_file.constant_pool
             .iter()
-            .enumerate()
-            .filter_map(|(i, c)| match c {
-                Constant::Utf8(s) => Some((i, self.new_static_string_obj(s))),
+            .filter_map(|c| match c {
+                Constant::Utf8(s) => Some(self.new_static_utf8(s)),
                 _ => None,
             })
             .collect();
 
-        let mut utf8_offsets_iter = utf8_offsets.values().copied();
+        let mut utf8_offsets_iter = utf8_offsets.into_iter();
 
-        let mut gap_val = None;
-        let constant_pool = class_file.constant_pool
-            .iter()
-            .map(|c| {
-                match *c {
-                    Constant::Fieldref { class_index: a, name_and_type_index: b } |
-                    Constant::Methodref { class_index: a, name_and_type_index: b } |
-                    Constant::NameAndType { name_index: a, descriptor_index: b } |
-                    Constant::Dynamic { bootstrap_method_attr_index: a, name_and_type_index: b } |
-                    Constant::InvokeDynamic { bootstrap_method_attr_index: a, name_and_type_index: b } |
-                    Constant::InterfaceMethodref { class_index: a, name_and_type_index: b } => unsafe {
-                        RuntimeConstant(transmute((a, b)))
-                    }
-                    Constant::MethodHandle { reference_kind: a, reference_index: b } => unsafe {
-                        RuntimeConstant(transmute((a, b)))
-                    }
-                    Constant::Class { name_index: a } |
-                    Constant::MethodType { descriptor_index: a } |
-                    Constant::Module { name_index: a } |
-                    Constant::Package { name_index: a } => RuntimeConstant(a as u32),
-                    Constant::Integer { bytes } |
-                    Constant::Float { bytes } => RuntimeConstant(bytes),
-                    Constant::Double { high_bytes, low_bytes } |
-                    Constant::Long { high_bytes, low_bytes } => {
-                        if cfg!(target_endian = "little") {
-                            gap_val = Some(high_bytes);
-                            RuntimeConstant(low_bytes)
-                        } else {
-                            gap_val = Some(high_bytes);
-                            RuntimeConstant(low_bytes)
-                        }
-                    }
-                    Constant::String { string_index } => RuntimeConstant(
-                        Value::new_ref_static(utf8_offsets[&(string_index as usize - 1)]).into_u32(),
-                    ),
-                    Constant::Utf8(_) => RuntimeConstant(utf8_offsets_iter.next().unwrap()),
-                    Constant::Gap => RuntimeConstant(gap_val.take().unwrap())
+        let mut constant_pool = Vec::with_capacity(class_file.constant_pool.len());
+        for constant in &class_file.constant_pool {
+            constant_pool.push(match *constant {
+                Constant::Fieldref { class_index: a, name_and_type_index: b } |
+                Constant::Methodref { class_index: a, name_and_type_index: b } |
+                Constant::NameAndType { name_index: a, descriptor_index: b } |
+                Constant::Dynamic { bootstrap_method_attr_index: a, name_and_type_index: b } |
+                Constant::InvokeDynamic { bootstrap_method_attr_index: a, name_and_type_index: b } |
+                Constant::InterfaceMethodref { class_index: a, name_and_type_index: b } => unsafe {
+                    RuntimeConstant(transmute::<(u16, u16), u32>((a, b)))
                 }
-            })
-            .collect();
+                Constant::MethodHandle { reference_kind: a, reference_index: b } => unsafe {
+                    RuntimeConstant(transmute::<(u8, u16), u32>((a, b)))
+                }
+                Constant::Class { name_index: a } |
+                Constant::MethodType { descriptor_index: a } |
+                Constant::Module { name_index: a } |
+                Constant::Package { name_index: a } => RuntimeConstant(a as u32),
+                Constant::Integer { bytes } |
+                Constant::Float { bytes } => RuntimeConstant(bytes),
+                // the `Gap` after the constant gets its second word
+                Constant::Double { high_bytes, low_bytes } |
+                Constant::Long { high_bytes, low_bytes } => {
+                    constant_pool.extend(RuntimeConstant::wide(((high_bytes as u64) << 32) | low_bytes as u64));
+                    continue;
+                }
+                Constant::String { string_index } => RuntimeConstant(
+                    Value::new_ref_static(self.intern_str(class_file.constant_utf8(string_index).unwrap())).into_u32(),
+                ),
+                Constant::Utf8(_) => RuntimeConstant(utf8_offsets_iter.next().unwrap()),
+                Constant::Gap => continue,
+            });
+        }
+
+        let mut static_fields = Bytes32Aligned::new_zeroed((static_size as usize + 3) & !3);
+        for field in &class_file.fields {
+            if !field.access_flags.contains(FieldAccess::STATIC) {
+                continue;
+            }
+            let Some(constantvalue_index) = field.attributes.iter().find_map(|a| match *a {
+                AttributeInfo::ConstantValue { constantvalue_index } => Some(constantvalue_index),
+                _ => None,
+            }) else {
+                continue;
+            };
+            let name = class_file.constant_utf8(field.name_index).unwrap();
+            let descriptor = class_file.constant_fdescriptor(field.descriptor_index).unwrap();
+            let offset = member_table.get(name, &descriptor.clone().into()).unwrap() as usize;
+            let value = match *class_file.constant(constantvalue_index) {
+                Constant::Integer { bytes } |
+                Constant::Float { bytes } => field_bytes(bytes, descriptor.byte_size()),
+                Constant::Long { high_bytes, low_bytes } |
+                Constant::Double { high_bytes, low_bytes } => Box::new((((high_bytes as u64) << 32) | low_bytes as u64).to_ne_bytes()),
+                Constant::String { string_index } => {
+                    let string = Value::new_ref_static(self.intern_str(class_file.constant_utf8(string_index).unwrap()));
+                    field_bytes(string.into_u32(), 4)
+                }
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid ConstantValue constant").into()),
+            };
+            static_fields[offset..offset + value.len()].copy_from_slice(&value);
+        }
+        let clinit = member_table.get("<clinit>", &MethodDescriptor::new_void([]).into());
 
         // load super class, first
         let loaded = LoadedClass {
+            name: name.into(),
+            access_flags: class_file.access_flags,
             super_class,
+            array_component: None,
             interfaces: interfaces.into_boxed_slice(),
             member_table,
+            abstract_methods,
             runtime_info: RuntimeInfo::Bytecode {
                 method_code: method_code.into_boxed_slice(),
-                constant_pool,
+                resolved: resolve::unresolved_constants(&class_file.constant_pool),
+                constant_pool: constant_pool.into_boxed_slice(),
+                bootstrap_methods: class_file.attributes.iter().find_map(|a| match a {
+                    AttributeInfo::BootstrapMethods(methods) => Some(methods.iter().map(|method| indy::Bootstrap::new(class_file, method)).collect()),
+                    _ => None,
+                }).unwrap_or_default(),
             },
-            static_fields: Bytes32Aligned::new_zeroed((static_size as usize + 3) & !3),
+            static_fields,
             data_size,
+            ref_offsets: ref_offsets.into_boxed_slice(),
+            static_ref_offsets: static_ref_offsets.into_boxed_slice(),
+            class_object: 0,
+            init_state: InitState::Uninitialized,
+            clinit,
+            natives,
+            debug_info: ClassDebugInfo {
+                source_file: class_file.attributes.iter().find_map(|a| match *a {
+                    AttributeInfo::SourceFile { sourcefile_index } => class_file.constant_utf8(sourcefile_index).map(Into::into),
+                    _ => None,
+                }),
+                fields: declared_fields.into_boxed_slice(),
+            },
         };
-        let id = self.classes.len() as u32;
-        self.classes.push(loaded);
-        Ok(id)
+        Ok(self.add_class(loaded))
     }
-    pub fn new_static_string_obj(&mut self, s: &str) -> u32 {
-        let string_location = self.statics.as_bytes_32aligned().len();
+    /// Stores a length-prefixed UTF-8 blob for a `Utf8` constant, these are not Java objects
+    fn new_static_utf8(&mut self, s: &str) -> u32 {
+        let location = self.statics.as_bytes_32aligned().len();
         self.statics.push(s.len() as u32);
         self.statics.extend_from_bytes(s.as_bytes());
-        string_location as u32
+        location as u32
+    }
+    pub fn new_static_string_obj(&mut self, chars: &[u16]) -> u32 {
+        let (coder, bytes) = string::encode(chars);
+        let mut data = Vec::with_capacity(8 + bytes.len());
+        data.extend((chars.len() as u32).to_ne_bytes());
+        data.extend((coder as u32).to_ne_bytes());
+        data.extend(bytes);
+        self.new_static_obj(STRING_CLASS, &data)
+    }
+    /// Returns the canonical string object with the given content, creating it in the statics if
+    /// there is none yet
+    pub fn intern(&mut self, chars: &[u16]) -> u32 {
+        if let Some(&offset) = self.interned.get(chars) {
+            return offset;
+        }
+        let offset = self.new_static_string_obj(chars);
+        self.interned.insert(chars.into(), offset);
+        offset
+    }
+    pub fn intern_str(&mut self, s: &str) -> u32 {
+        self.intern(&s.encode_utf16().collect::<Vec<_>>())
     }
-    pub fn new_static_obj(&mut self, data: &[u8]) -> u32 {
+    pub fn new_static_obj(&mut self, class_id: u32, data: &[u8]) -> u32 {
         let location = self.statics.as_bytes_32aligned().len();
+        self.statics.extend(ObjectHeader::new(class_id).to_words());
         self.statics.extend_from_bytes(data);
+        self.static_objects.push(location as u32);
         location as u32
     }
+    /// Reads a blob stored by [`Self::new_static_utf8`]
     pub fn read_static_string(&self, offset: u32) -> &str {
         let len = self.statics[offset as usize / 4];
         let bytes = &self.statics.as_bytes_32aligned()[offset as usize + 4..][..len as usize];
@@ -1276,78 +2044,512 @@ impl Runtime {
             from_utf8_unchecked(bytes)
         }
     }
-    pub fn run(&mut self, classpath: &str, args: Box<[Box<str>]>) -> Result<()> {
-        let mut ctx = RuntimeCtx {
-            frame_pointer: 0,
-            pc: 0,
-            max_locals: 0,
-            cur_class: 0,
-            stack: Vec::new(),
-            heap: Vec::new(),
-            return_stack: Vec::with_capacity(1),
-            runtime: self,
-        };
-        // TODO: generate a real array to push to the stack
-        ctx.push(Value(args.as_ptr() as usize as u32));
-        ctx.call_named(classpath, "main", MethodDescriptor::new_void([
-            FieldDescriptor::ArrRef(Box::new(FieldDescriptor::ClassRef("java/lang/String".into())))
-        ]))?;
-        ctx.run_inner()
-    }
-    fn load_builtin(&mut self, classpath: &str) -> Option<LoadedClass> {
+    /// Runs the `main` method of a class and returns the exit status, which is the one passed to
+    /// `System.exit` or 0 if `main` returns
+    pub fn run(&mut self, classpath: &str, args: &[Box<str>]) -> Result<i32> {
+        RuntimeCtx::new(self).run(classpath, args)
+    }
+    fn load_builtin(&mut self, classpath: &str) -> Result<Option<LoadedClass>> {
         use self::FieldDescriptor::*;
-        Some(match classpath {
+        Ok(Some(match classpath {
             "java/lang/String" => LoadedClass {
-                super_class: 0,
-                interfaces: Box::new([]),
-                static_fields: Bytes32Aligned::new_zeroed(0),
-                data_size: u16::MAX,
-                member_table: MemberTable::new(),
-                runtime_info: RuntimeInfo::Builtin(Box::new([])),
+                access_flags: ClassAccess::PUBLIC | ClassAccess::FINAL,
+                // the size of the empty string, the content follows the fields
+                data_size: STRING_DATA_OFFSET as u16,
+                ..LoadedClass::new_builtin(classpath, OBJECT_CLASS, {
+                    let string = || ClassRef("java/lang/String".into());
+                    let object_array = || ArrRef(Box::new(ClassRef("java/lang/Object".into())));
+                    let mut table = MemberTable::new();
+                    table.insert("length", MethodDescriptor::new_ret([], Int), 0);
+                    table.insert("charAt", MethodDescriptor::new_ret([Int], Char), 1);
+                    table.insert("equals", MethodDescriptor::new_ret([ClassRef("java/lang/Object".into())], Boolean), 2);
+                    table.insert("hashCode", MethodDescriptor::new_ret([], Int), 3);
+                    table.insert("substring", MethodDescriptor::new_ret([Int], string()), 4);
+                    table.insert("substring", MethodDescriptor::new_ret([Int, Int], string()), 5);
+                    table.insert("indexOf", MethodDescriptor::new_ret([Int], Int), 6);
+                    table.insert("indexOf", MethodDescriptor::new_ret([string()], Int), 7);
+                    table.insert("concat", MethodDescriptor::new_ret([string()], string()), 8);
+                    table.insert("compareTo", MethodDescriptor::new_ret([string()], Int), 9);
+                    table.insert("toString", MethodDescriptor::new_ret([], string()), 10);
+                    table.insert("intern", MethodDescriptor::new_ret([], string()), 11);
+                    table.insert("valueOf", MethodDescriptor::new_ret([ClassRef("java/lang/Object".into())], string()), 12);
+                    table.insert("format", MethodDescriptor::new_ret([string(), object_array()], string()), 13);
+                    table.insert("valueOf", MethodDescriptor::new_ret([Boolean], string()), 14);
+                    table.insert("valueOf", MethodDescriptor::new_ret([Char], string()), 15);
+                    table.insert("valueOf", MethodDescriptor::new_ret([Int], string()), 16);
+                    table.insert("valueOf", MethodDescriptor::new_ret([Long], string()), 17);
+                    table.insert("valueOf", MethodDescriptor::new_ret([Float], string()), 18);
+                    table.insert("valueOf", MethodDescriptor::new_ret([Double], string()), 19);
+                    table.insert("valueOf", MethodDescriptor::new_ret([ArrRef(Box::new(Char))], string()), 20);
+                    table.insert("compareTo", MethodDescriptor::new_ret([ClassRef("java/lang/Object".into())], Int), 21);
+                    table
+                }, Box::new([
+                    builtin_methods::string_length,
+                    builtin_methods::string_char_at,
+                    builtin_methods::string_equals,
+                    builtin_methods::string_hash_code,
+                    builtin_methods::string_substring,
+                    builtin_methods::string_substring_end,
+                    builtin_methods::string_index_of_char,
+                    builtin_methods::string_index_of_string,
+                    builtin_methods::string_concat,
+                    builtin_methods::string_compare_to,
+                    builtin_methods::string_to_string,
+                    builtin_methods::string_intern,
+                    builtin_methods::string_value_of,
+                    builtin_methods::string_format,
+                    builtin_methods::string_value_of_boolean,
+                    builtin_methods::string_value_of_char,
+                    builtin_methods::string_value_of_int,
+                    builtin_methods::string_value_of_long,
+                    builtin_methods::string_value_of_float,
+                    builtin_methods::string_value_of_double,
+                    builtin_methods::string_value_of_char_array,
+                    builtin_methods::string_compare_to_object,
+                ]))
             },
-            "java/lang/System" => LoadedClass {
-                super_class: 0,
-                interfaces: Box::new([]),
-                static_fields: {
-                    let mut fields = Bytes32Aligned::new_zeroed(4*3);
-                    for (stream, i) in fields.as_u32_slice_mut().iter_mut().zip(0..) {
-                        *stream = Value::new_ref_static(self.new_static_obj(&[i])).into_u32();
-                    }
-                    fields
-                },
-                data_size: 0,
-                member_table: {
+            "java/lang/Class" => LoadedClass {
+                data_size: CLASS_ID_OFFSET as u16 + 4,
+                ..LoadedClass::new_builtin(classpath, OBJECT_CLASS, {
+                    let mut table = MemberTable::new();
+                    table.insert("getName", MethodDescriptor::new_ret([], ClassRef("java/lang/String".into())), 0);
+                    table.insert("toString", MethodDescriptor::new_ret([], ClassRef("java/lang/String".into())), 1);
+                    table
+                }, Box::new([
+                    builtin_methods::class_get_name,
+                    builtin_methods::class_to_string,
+                ]))
+            },
+            "java/lang/Cloneable" |
+            "java/io/Serializable" => LoadedClass {
+                access_flags: ClassAccess::PUBLIC | ClassAccess::INTERFACE | ClassAccess::ABSTRACT,
+                ..LoadedClass::new_builtin(classpath, OBJECT_CLASS, MemberTable::new(), Box::new([]))
+            },
+            _ if let Some(super_interfaces) = builtin_interface_supers(classpath) => {
+                let interfaces = super_interfaces.iter().map(|&name| self.load_class(name)).collect::<Result<_>>()?;
+                LoadedClass {
+                    access_flags: ClassAccess::PUBLIC | ClassAccess::INTERFACE | ClassAccess::ABSTRACT,
+                    interfaces,
+                    abstract_methods: abstract_method_table(builtin_interface_methods(classpath)),
+                    ..LoadedClass::new_builtin(classpath, OBJECT_CLASS, MemberTable::new(), Box::new([]))
+                }
+            }
+            "java/lang/Throwable" => LoadedClass {
+                data_size: THROWABLE_CAUSE_OFFSET as u16 + 4,
+                ref_offsets: Box::new([THROWABLE_MESSAGE_OFFSET as u16, THROWABLE_CAUSE_OFFSET as u16]),
+                ..LoadedClass::new_builtin(classpath, OBJECT_CLASS, {
+                    let mut table = MemberTable::new();
+                    table.insert("detailMessage", ClassRef("java/lang/String".into()), THROWABLE_MESSAGE_OFFSET as u16);
+                    table.insert("cause", ClassRef("java/lang/Throwable".into()), THROWABLE_CAUSE_OFFSET as u16);
+                    table.insert("<init>", MethodDescriptor::new_void([]), 0);
+                    table.insert("<init>", MethodDescriptor::new_void([ClassRef("java/lang/String".into())]), 1);
+                    table.insert("getMessage", MethodDescriptor::new_ret([], ClassRef("java/lang/String".into())), 2);
+                    table.insert("getCause", MethodDescriptor::new_ret([], ClassRef("java/lang/Throwable".into())), 3);
+                    table.insert("getLocalizedMessage", MethodDescriptor::new_ret([], ClassRef("java/lang/String".into())), 4);
+                    table.insert("toString", MethodDescriptor::new_ret([], ClassRef("java/lang/String".into())), 5);
+                    table
+                }, Box::new([
+                    builtin_methods::obj_init,
+                    builtin_methods::throwable_init_message,
+                    builtin_methods::throwable_get_message,
+                    builtin_methods::throwable_get_cause,
+                    builtin_methods::throwable_get_localized_message,
+                    builtin_methods::throwable_to_string,
+                ]))
+            },
+            _ if let Some(super_class) = builtin_throwable_super(classpath) => {
+                let super_class = self.load_class(super_class)?;
+                LoadedClass {
+                    data_size: self.get_class(super_class).data_size,
+                    ref_offsets: self.get_class(super_class).ref_offsets.clone(),
+                    ..LoadedClass::new_builtin(classpath, super_class, MemberTable::new(), Box::new([]))
+                }
+            }
+            "java/lang/System" => {
+                let print_stream = self.load_class("java/io/PrintStream")?;
+                let input_stream = self.load_class("java/io/InputStream")?;
+                LoadedClass {
+                    static_fields: {
+                        let mut fields = Bytes32Aligned::new_zeroed(4*3);
+                        let streams = [(print_stream, stdio::STDOUT), (print_stream, stdio::STDERR), (input_stream, stdio::STDIN)];
+                        for (field, (class, stream)) in fields.as_u32_slice_mut().iter_mut().zip(streams) {
+                            *field = Value::new_ref_static(self.new_static_obj(class, &[stream])).into_u32();
+                        }
+                        fields
+                    },
+                    ..LoadedClass::new_builtin(classpath, OBJECT_CLASS, {
+                        let mut table = MemberTable::new();
+                        table.insert("out", ClassRef("java/io/PrintStream".into()), 0);
+                        table.insert("err", ClassRef("java/io/PrintStream".into()), 4);
+                        table.insert("in", ClassRef("java/io/InputStream".into()), 8);
+                        table.insert("exit", MethodDescriptor::new_void([Int]), 0);
+                        table
+                    }, Box::new([
+                        builtin_methods::system_exit,
+                    ]))
+                }
+            }
+            "java/lang/Thread" => LoadedClass {
+                data_size: THREAD_DAEMON_OFFSET as u16 + 1,
+                ref_offsets: Box::new([THREAD_TARGET_OFFSET as u16, THREAD_NAME_OFFSET as u16]),
+                interfaces: Box::new([self.load_class("java/lang/Runnable")?]),
+                ..LoadedClass::new_builtin(classpath, OBJECT_CLASS, {
+                    let string = || ClassRef("java/lang/String".into());
+                    let runnable = || ClassRef("java/lang/Runnable".into());
                     let mut table = MemberTable::new();
-                    table.insert("out", ClassRef("java/io/PrintStream".into()),  0);
+                    table.insert("<init>", MethodDescriptor::new_void([]), 0);
+                    table.insert("<init>", MethodDescriptor::new_void([runnable()]), 1);
+                    table.insert("<init>", MethodDescriptor::new_void([runnable(), string()]), 2);
+                    table.insert("<init>", MethodDescriptor::new_void([string()]), 3);
+                    table.insert("start", MethodDescriptor::new_void([]), 4);
+                    table.insert("run", MethodDescriptor::new_void([]), 5);
+                    table.insert("join", MethodDescriptor::new_void([]), 6);
+                    table.insert("join", MethodDescriptor::new_void([Long]), 7);
+                    table.insert("sleep", MethodDescriptor::new_void([Long]), 8);
+                    table.insert("yield", MethodDescriptor::new_void([]), 9);
+                    table.insert("currentThread", MethodDescriptor::new_ret([], ClassRef("java/lang/Thread".into())), 10);
+                    table.insert("getName", MethodDescriptor::new_ret([], string()), 11);
+                    table.insert("setName", MethodDescriptor::new_void([string()]), 12);
+                    table.insert("isAlive", MethodDescriptor::new_ret([], Boolean), 13);
+                    table.insert("setDaemon", MethodDescriptor::new_void([Boolean]), 14);
+                    table.insert("isDaemon", MethodDescriptor::new_ret([], Boolean), 15);
                     table
-                },
-                runtime_info: RuntimeInfo::Builtin(Box::new([])),
+                }, Box::new([
+                    builtin_methods::thread_init,
+                    builtin_methods::thread_init_runnable,
+                    builtin_methods::thread_init_runnable_name,
+                    builtin_methods::thread_init_name,
+                    builtin_methods::thread_start,
+                    builtin_methods::thread_run,
+                    builtin_methods::thread_join,
+                    builtin_methods::thread_join_timeout,
+                    builtin_methods::thread_sleep,
+                    builtin_methods::thread_yield,
+                    builtin_methods::thread_current_thread,
+                    builtin_methods::thread_get_name,
+                    builtin_methods::thread_set_name,
+                    builtin_methods::thread_is_alive,
+                    builtin_methods::thread_set_daemon,
+                    builtin_methods::thread_is_daemon,
+                ]))
             },
             "java/io/PrintStream" => LoadedClass {
-                super_class: 0,
-                interfaces: Box::new([]),
-                static_fields: Bytes32Aligned::new_zeroed(0),
-                data_size: 1,
-                member_table: {
+                data_size: STREAM_ID_OFFSET as u16 + 1,
+                ..LoadedClass::new_builtin(classpath, OBJECT_CLASS, {
+                    let string = || ClassRef("java/lang/String".into());
+                    let print_stream = || ClassRef("java/io/PrintStream".into());
                     let mut table = MemberTable::new();
-                    table.insert("println", MethodDescriptor::new_void([ClassRef("java/lang/String".into())]), 0);
+                    let printable = [
+                        Boolean,
+                        Char,
+                        Int,
+                        Long,
+                        Float,
+                        Double,
+                        ArrRef(Box::new(Char)),
+                        string(),
+                        ClassRef("java/lang/Object".into()),
+                    ];
+                    for (arg_type, i) in printable.into_iter().zip(0..) {
+                        table.insert("print", MethodDescriptor::new_void([arg_type.clone()]), 2 * i);
+                        table.insert("println", MethodDescriptor::new_void([arg_type]), 2 * i + 1);
+                    }
+                    let object_array = ArrRef(Box::new(ClassRef("java/lang/Object".into())));
+                    table.insert("println", MethodDescriptor::new_void([]), 18);
+                    table.insert("printf", MethodDescriptor::new_ret([string(), object_array.clone()], print_stream()), 19);
+                    table.insert("format", MethodDescriptor::new_ret([string(), object_array], print_stream()), 19);
+                    table.insert("flush", MethodDescriptor::new_void([]), 20);
+                    table.insert("write", MethodDescriptor::new_void([Int]), 21);
                     table
-                },
-                runtime_info: RuntimeInfo::Builtin(Box::new([
+                }, Box::new([
+                    builtin_methods::printstream_print_boolean,
+                    builtin_methods::printstream_println_boolean,
+                    builtin_methods::printstream_print_char,
+                    builtin_methods::printstream_println_char,
+                    builtin_methods::printstream_print_int,
+                    builtin_methods::printstream_println_int,
+                    builtin_methods::printstream_print_long,
+                    builtin_methods::printstream_println_long,
+                    builtin_methods::printstream_print_float,
+                    builtin_methods::printstream_println_float,
+                    builtin_methods::printstream_print_double,
+                    builtin_methods::printstream_println_double,
+                    builtin_methods::printstream_print_char_array,
+                    builtin_methods::printstream_println_char_array,
+                    builtin_methods::printstream_print_str,
                     builtin_methods::printstream_println_str,
-                ])),
+                    builtin_methods::printstream_print_object,
+                    builtin_methods::printstream_println_object,
+                    builtin_methods::printstream_println,
+                    builtin_methods::printstream_printf,
+                    builtin_methods::printstream_flush,
+                    builtin_methods::printstream_write,
+                ]))
             },
-            _ => return None,
-        })
+            "java/io/InputStream" => LoadedClass {
+                data_size: STREAM_ID_OFFSET as u16 + 1,
+                ..LoadedClass::new_builtin(classpath, OBJECT_CLASS, {
+                    let bytes = || ArrRef(Box::new(Byte));
+                    let mut table = MemberTable::new();
+                    table.insert("read", MethodDescriptor::new_ret([], Int), 0);
+                    table.insert("read", MethodDescriptor::new_ret([bytes()], Int), 1);
+                    table.insert("read", MethodDescriptor::new_ret([bytes(), Int, Int], Int), 2);
+                    table.insert("available", MethodDescriptor::new_ret([], Int), 3);
+                    table
+                }, Box::new([
+                    builtin_methods::inputstream_read,
+                    builtin_methods::inputstream_read_array,
+                    builtin_methods::inputstream_read_range,
+                    builtin_methods::inputstream_available,
+                ]))
+            },
+            _ if let Some(class) = self.load_core_class(classpath)? => class,
+            _ => return Ok(None),
+        }))
     }
 }
 
+/// Superclass of the builtin exception classes that do not need anything beyond `Throwable`
+fn builtin_throwable_super(classpath: &str) -> Option<&'static str> {
+    Some(match classpath {
+        "java/lang/Exception" |
+        "java/lang/Error" => "java/lang/Throwable",
+        "java/lang/RuntimeException" |
+        "java/io/IOException" |
+        "java/lang/CloneNotSupportedException" |
+        "java/lang/InterruptedException" => "java/lang/Exception",
+        "java/lang/ArithmeticException" |
+        "java/lang/ArrayStoreException" |
+        "java/lang/ClassCastException" |
+        "java/lang/IllegalArgumentException" |
+        "java/lang/IllegalMonitorStateException" |
+        "java/lang/IllegalStateException" |
+        "java/lang/NegativeArraySizeException" |
+        "java/lang/NullPointerException" |
+        "java/lang/IndexOutOfBoundsException" => "java/lang/RuntimeException",
+        "java/lang/IllegalThreadStateException" |
+        "java/lang/NumberFormatException" |
+        "java/util/IllegalFormatException" => "java/lang/IllegalArgumentException",
+        "java/util/DuplicateFormatFlagsException" |
+        "java/util/FormatFlagsConversionMismatchException" |
+        "java/util/IllegalFormatCodePointException" |
+        "java/util/IllegalFormatConversionException" |
+        "java/util/IllegalFormatFlagsException" |
+        "java/util/IllegalFormatPrecisionException" |
+        "java/util/IllegalFormatWidthException" |
+        "java/util/MissingFormatArgumentException" |
+        "java/util/MissingFormatWidthException" |
+        "java/util/UnknownFormatConversionException" => "java/util/IllegalFormatException",
+        "java/lang/ArrayIndexOutOfBoundsException" |
+        "java/lang/StringIndexOutOfBoundsException" => "java/lang/IndexOutOfBoundsException",
+        "java/lang/LinkageError" |
+        "java/lang/VirtualMachineError" => "java/lang/Error",
+        "java/lang/OutOfMemoryError" => "java/lang/VirtualMachineError",
+        "java/lang/StackOverflowError" => "java/lang/VirtualMachineError",
+        "java/lang/BootstrapMethodError" |
+        "java/lang/ExceptionInInitializerError" |
+        "java/lang/IncompatibleClassChangeError" |
+        "java/lang/NoClassDefFoundError" |
+        "java/lang/UnsatisfiedLinkError" => "java/lang/LinkageError",
+        "java/lang/AbstractMethodError" |
+        "java/lang/InstantiationError" |
+        "java/lang/NoSuchFieldError" |
+        "java/lang/NoSuchMethodError" => "java/lang/IncompatibleClassChangeError",
+        _ => return None,
+    })
+}
+
+/// Superinterfaces of the builtin functional interfaces, which only declare abstract methods that
+/// lambdas implement.
+///
+/// These are all the interfaces of `java.util.function` and the few of `java.lang` and `java.util`
+/// that lambdas commonly implement. Their default and static methods, like `Function.andThen` or
+/// `Comparator.comparing`, are not there, and neither is `java.util.stream`.
+fn builtin_interface_supers(classpath: &str) -> Option<&'static [&'static str]> {
+    Some(match classpath {
+        "java/lang/Runnable" |
+        "java/lang/CharSequence" |
+        "java/lang/Comparable" |
+        "java/util/Comparator" |
+        "java/util/function/BiConsumer" |
+        "java/util/function/BiFunction" |
+        "java/util/function/BiPredicate" |
+        "java/util/function/BooleanSupplier" |
+        "java/util/function/Consumer" |
+        "java/util/function/DoubleBinaryOperator" |
+        "java/util/function/DoubleConsumer" |
+        "java/util/function/DoubleFunction" |
+        "java/util/function/DoublePredicate" |
+        "java/util/function/DoubleSupplier" |
+        "java/util/function/DoubleToIntFunction" |
+        "java/util/function/DoubleToLongFunction" |
+        "java/util/function/DoubleUnaryOperator" |
+        "java/util/function/Function" |
+        "java/util/function/IntBinaryOperator" |
+        "java/util/function/IntConsumer" |
+        "java/util/function/IntFunction" |
+        "java/util/function/IntPredicate" |
+        "java/util/function/IntSupplier" |
+        "java/util/function/IntToDoubleFunction" |
+        "java/util/function/IntToLongFunction" |
+        "java/util/function/IntUnaryOperator" |
+        "java/util/function/LongBinaryOperator" |
+        "java/util/function/LongConsumer" |
+        "java/util/function/LongFunction" |
+        "java/util/function/LongPredicate" |
+        "java/util/function/LongSupplier" |
+        "java/util/function/LongToDoubleFunction" |
+        "java/util/function/LongToIntFunction" |
+        "java/util/function/LongUnaryOperator" |
+        "java/util/function/ObjDoubleConsumer" |
+        "java/util/function/ObjIntConsumer" |
+        "java/util/function/ObjLongConsumer" |
+        "java/util/function/Predicate" |
+        "java/util/function/Supplier" |
+        "java/util/function/ToDoubleBiFunction" |
+        "java/util/function/ToDoubleFunction" |
+        "java/util/function/ToIntBiFunction" |
+        "java/util/function/ToIntFunction" |
+        "java/util/function/ToLongBiFunction" |
+        "java/util/function/ToLongFunction" => &[],
+        "java/util/function/UnaryOperator" => &["java/util/function/Function"],
+        "java/util/function/BinaryOperator" => &["java/util/function/BiFunction"],
+        _ => return None,
+    })
+}
+
+/// The abstract methods of a builtin functional interface as names and descriptors
+fn builtin_interface_methods(classpath: &str) -> &'static [(&'static str, &'static str)] {
+    match classpath {
+        "java/lang/Runnable" => &[("run", "()V")],
+        "java/lang/CharSequence" => &[
+            ("length", "()I"),
+            ("charAt", "(I)C"),
+            ("subSequence", "(II)Ljava/lang/CharSequence;"),
+            ("toString", "()Ljava/lang/String;"),
+        ],
+        "java/lang/Comparable" => &[("compareTo", "(Ljava/lang/Object;)I")],
+        "java/util/Comparator" => &[("compare", "(Ljava/lang/Object;Ljava/lang/Object;)I")],
+        "java/util/function/BiConsumer" => &[("accept", "(Ljava/lang/Object;Ljava/lang/Object;)V")],
+        "java/util/function/BiFunction" => &[("apply", "(Ljava/lang/Object;Ljava/lang/Object;)Ljava/lang/Object;")],
+        "java/util/function/BiPredicate" => &[("test", "(Ljava/lang/Object;Ljava/lang/Object;)Z")],
+        "java/util/function/BooleanSupplier" => &[("getAsBoolean", "()Z")],
+        "java/util/function/Consumer" => &[("accept", "(Ljava/lang/Object;)V")],
+        "java/util/function/DoubleBinaryOperator" => &[("applyAsDouble", "(DD)D")],
+        "java/util/function/DoubleConsumer" => &[("accept", "(D)V")],
+        "java/util/function/DoubleFunction" => &[("apply", "(D)Ljava/lang/Object;")],
+        "java/util/function/DoublePredicate" => &[("test", "(D)Z")],
+        "java/util/function/DoubleSupplier" => &[("getAsDouble", "()D")],
+        "java/util/function/DoubleToIntFunction" => &[("applyAsInt", "(D)I")],
+        "java/util/function/DoubleToLongFunction" => &[("applyAsLong", "(D)J")],
+        "java/util/function/DoubleUnaryOperator" => &[("applyAsDouble", "(D)D")],
+        "java/util/function/Function" => &[("apply", "(Ljava/lang/Object;)Ljava/lang/Object;")],
+        "java/util/function/IntBinaryOperator" => &[("applyAsInt", "(II)I")],
+        "java/util/function/IntConsumer" => &[("accept", "(I)V")],
+        "java/util/function/IntFunction" => &[("apply", "(I)Ljava/lang/Object;")],
+        "java/util/function/IntPredicate" => &[("test", "(I)Z")],
+        "java/util/function/IntSupplier" => &[("getAsInt", "()I")],
+        "java/util/function/IntToDoubleFunction" => &[("applyAsDouble", "(I)D")],
+        "java/util/function/IntToLongFunction" => &[("applyAsLong", "(I)J")],
+        "java/util/function/IntUnaryOperator" => &[("applyAsInt", "(I)I")],
+        "java/util/function/LongBinaryOperator" => &[("applyAsLong", "(JJ)J")],
+        "java/util/function/LongConsumer" => &[("accept", "(J)V")],
+        "java/util/function/LongFunction" => &[("apply", "(J)Ljava/lang/Object;")],
+        "java/util/function/LongPredicate" => &[("test", "(J)Z")],
+        "java/util/function/LongSupplier" => &[("getAsLong", "()J")],
+        "java/util/function/LongToDoubleFunction" => &[("applyAsDouble", "(J)D")],
+        "java/util/function/LongToIntFunction" => &[("applyAsInt", "(J)I")],
+        "java/util/function/LongUnaryOperator" => &[("applyAsLong", "(J)J")],
+        "java/util/function/ObjDoubleConsumer" => &[("accept", "(Ljava/lang/Object;D)V")],
+        "java/util/function/ObjIntConsumer" => &[("accept", "(Ljava/lang/Object;I)V")],
+        "java/util/function/ObjLongConsumer" => &[("accept", "(Ljava/lang/Object;J)V")],
+        "java/util/function/Predicate" => &[("test", "(Ljava/lang/Object;)Z")],
+        "java/util/function/Supplier" => &[("get", "()Ljava/lang/Object;")],
+        "java/util/function/ToDoubleBiFunction" => &[("applyAsDouble", "(Ljava/lang/Object;Ljava/lang/Object;)D")],
+        "java/util/function/ToDoubleFunction" => &[("applyAsDouble", "(Ljava/lang/Object;)D")],
+        "java/util/function/ToIntBiFunction" => &[("applyAsInt", "(Ljava/lang/Object;Ljava/lang/Object;)I")],
+        "java/util/function/ToIntFunction" => &[("applyAsInt", "(Ljava/lang/Object;)I")],
+        "java/util/function/ToLongBiFunction" => &[("applyAsLong", "(Ljava/lang/Object;Ljava/lang/Object;)J")],
+        "java/util/function/ToLongFunction" => &[("applyAsLong", "(Ljava/lang/Object;)J")],
+        _ => &[],
+    }
+}
+
+/// The [`LoadedClass::abstract_methods`] of a builtin class from names and descriptors
+fn abstract_method_table(methods: &[(&str, &str)]) -> MemberTable {
+    let mut table = MemberTable::new();
+    for &(name, descriptor) in methods {
+        table.insert(name, MethodDescriptor::from_bytes(descriptor.as_bytes()).unwrap(), 0);
+    }
+    table
+}
+
 #[derive(Debug)]
 pub enum RtError {
     Io(io::Error),
     Descriptor(DescriptorError),
     ReservedInstruction,
+    /// Control reached a byte of code that does not start an instruction, like the middle of one
+    /// or the end of a method
+    InvalidCode,
+    UncaughtException {
+        class_name: Box<str>,
+        message: Option<Box<str>>,
+    },
+    /// No entry of the class path has the class, code that runs into this gets a
+    /// `NoClassDefFoundError` instead
+    ClassNotFound {
+        name: Box<str>,
+        searched: Box<[String]>,
+    },
+    /// `System.exit` was called with this status
+    Exit(i32),
+    /// A value passed to or from Java does not have the type the descriptor asks for
+    TypeMismatch {
+        expected: Box<str>,
+        found: Box<str>,
+    },
+    /// No thread can run anymore, each entry says what one of them waits for
+    Deadlock {
+        threads: Box<[Box<str>]>,
+    },
+    /// The run stopped at a limit of the runtime, see [`RuntimeCtx::resume`]
+    LimitReached {
+        limit: Limit,
+        /// Whether [`RuntimeCtx::resume`] can continue the run
+        resumable: bool,
+    },
+}
+
+impl Display for RtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RtError::Io(e) => Display::fmt(e, f),
+            RtError::Descriptor(e) => write!(f, "invalid descriptor: {e}"),
+            RtError::ReservedInstruction => f.write_str("reserved instruction"),
+            RtError::InvalidCode => f.write_str("reached a byte of code that does not start an instruction"),
+            RtError::UncaughtException { class_name, message: Some(message) } => write!(f, "uncaught exception {class_name}: {message}"),
+            RtError::UncaughtException { class_name, message: None } => write!(f, "uncaught exception {class_name}"),
+            RtError::Exit(status) => write!(f, "exited with status {status}"),
+            RtError::TypeMismatch { expected, found } => write!(f, "expected {expected}, found {found}"),
+            RtError::LimitReached { limit, .. } => write!(f, "stopped: {limit}"),
+            RtError::Deadlock { threads } => {
+                f.write_str("deadlock:")?;
+                threads.iter().try_for_each(|thread| write!(f, "\n    {thread}"))
+            }
+            RtError::ClassNotFound { name, searched } => {
+                write!(f, "class {} not found", name.replace('/', "."))?;
+                if searched.is_empty() {
+                    f.write_str(", the class path is empty")
+                } else {
+                    write!(f, ", searched:")?;
+                    searched.iter().try_for_each(|location| write!(f, "\n    {location}"))
+                }
+            }
+        }
+    }
 }
+impl std::error::Error for RtError {}
 
 impl From<io::Error> for RtError {
     fn from(e: io::Error) -> Self {
@@ -0,0 +1,184 @@
+//! Arithmetic and conversions between numbers, with the results the JLS asks for: `int` and
+//! `long` operations wrap around, integer division by zero throws an `ArithmeticException`,
+//! floating point follows IEEE 754 and conversions to integers saturate with NaN becoming 0.
+
+use std::ops::{Add, Div, Mul, Rem, Sub};
+
+use super::{f64_into_values, i64_into_values, link::{ArithOp, NumberKind, ValueKind}, values_into_f64, values_into_u64, Result, RuntimeCtx};
+
+impl RuntimeCtx<'_> {
+    /// Pops the operands of an arithmetic instruction and pushes its result
+    pub(super) fn arith(&mut self, kind: NumberKind, op: ArithOp) -> Result<()> {
+        match kind {
+            NumberKind::Int => {
+                let v2 = self.pop().into_i32();
+                let v1 = self.pop().into_i32();
+                let Some(result) = int_op(op, v1, v2) else { return self.throw_division_by_zero() };
+                self.push(result);
+            }
+            NumberKind::Long => {
+                let v2 = match op {
+                    ArithOp::Shl | ArithOp::Shr | ArithOp::Ushr => self.pop().into_i32() as i64,
+                    _ => values_into_u64(self.pop2()) as i64,
+                };
+                let v1 = values_into_u64(self.pop2()) as i64;
+                let Some(result) = long_op(op, v1, v2) else { return self.throw_division_by_zero() };
+                self.push2(i64_into_values(result));
+            }
+            NumberKind::Float => {
+                let v2 = self.pop().into_f32();
+                let v1 = self.pop().into_f32();
+                self.push(float_op(op, v1, v2));
+            }
+            NumberKind::Double => {
+                let v2 = values_into_f64(self.pop2());
+                let v1 = values_into_f64(self.pop2());
+                self.push2(f64_into_values(float_op(op, v1, v2)));
+            }
+        }
+        Ok(())
+    }
+    pub(super) fn negate(&mut self, kind: NumberKind) {
+        match kind {
+            NumberKind::Int => {
+                let value = self.pop().into_i32();
+                self.push(value.wrapping_neg());
+            }
+            NumberKind::Long => {
+                let value = values_into_u64(self.pop2()) as i64;
+                self.push2(i64_into_values(value.wrapping_neg()));
+            }
+            NumberKind::Float => {
+                let value = self.pop().into_f32();
+                self.push(-value);
+            }
+            NumberKind::Double => {
+                let value = values_into_f64(self.pop2());
+                self.push2(f64_into_values(-value));
+            }
+        }
+    }
+    pub(super) fn convert(&mut self, from: NumberKind, to: NumberKind) {
+        // `as` has the semantics of the JVMS for all of these
+        let value = match from {
+            NumberKind::Int => Number::Int(self.pop().into_i32()),
+            NumberKind::Long => Number::Long(values_into_u64(self.pop2()) as i64),
+            NumberKind::Float => Number::Float(self.pop().into_f32()),
+            NumberKind::Double => Number::Double(values_into_f64(self.pop2())),
+        };
+        match to {
+            NumberKind::Int => self.push(value.to_i32()),
+            NumberKind::Long => self.push2(i64_into_values(value.to_i64())),
+            NumberKind::Float => self.push(value.to_f32()),
+            NumberKind::Double => self.push2(f64_into_values(value.to_f64())),
+        }
+    }
+    /// Truncates an `int` to a `byte`, `char` or `short` and extends it back
+    pub(super) fn narrow(&mut self, kind: ValueKind) {
+        let value = self.pop().into_i32();
+        self.push(match kind {
+            ValueKind::Byte => value as i8 as i32,
+            ValueKind::Char => value as u16 as i32,
+            ValueKind::Short => value as i16 as i32,
+            _ => unreachable!("only byte, char and short are narrower than int"),
+        });
+    }
+    fn throw_division_by_zero(&mut self) -> Result<()> {
+        self.throw_new("java/lang/ArithmeticException", Some("/ by zero".into()))
+    }
+}
+
+/// A value of one of the computational types of numbers
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Number {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+}
+
+impl Number {
+    pub(super) fn to_i32(self) -> i32 {
+        match self {
+            Number::Int(v) => v,
+            Number::Long(v) => v as i32,
+            Number::Float(v) => v as i32,
+            Number::Double(v) => v as i32,
+        }
+    }
+    pub(super) fn to_i64(self) -> i64 {
+        match self {
+            Number::Int(v) => v as i64,
+            Number::Long(v) => v,
+            Number::Float(v) => v as i64,
+            Number::Double(v) => v as i64,
+        }
+    }
+    pub(super) fn to_f32(self) -> f32 {
+        match self {
+            Number::Int(v) => v as f32,
+            Number::Long(v) => v as f32,
+            Number::Float(v) => v,
+            Number::Double(v) => v as f32,
+        }
+    }
+    pub(super) fn to_f64(self) -> f64 {
+        match self {
+            Number::Int(v) => v as f64,
+            Number::Long(v) => v as f64,
+            Number::Float(v) => v as f64,
+            Number::Double(v) => v,
+        }
+    }
+}
+
+/// The result of an `int` operation, `None` for a division by zero
+fn int_op(op: ArithOp, v1: i32, v2: i32) -> Option<i32> {
+    Some(match op {
+        ArithOp::Add => v1.wrapping_add(v2),
+        ArithOp::Sub => v1.wrapping_sub(v2),
+        ArithOp::Mul => v1.wrapping_mul(v2),
+        ArithOp::Div => if v2 == 0 { return None } else { v1.wrapping_div(v2) },
+        ArithOp::Rem => if v2 == 0 { return None } else { v1.wrapping_rem(v2) },
+        // the distance is masked to 5 bits
+        ArithOp::Shl => v1.wrapping_shl(v2 as u32),
+        ArithOp::Shr => v1.wrapping_shr(v2 as u32),
+        ArithOp::Ushr => (v1 as u32).wrapping_shr(v2 as u32) as i32,
+        ArithOp::And => v1 & v2,
+        ArithOp::Or => v1 | v2,
+        ArithOp::Xor => v1 ^ v2,
+    })
+}
+
+/// The result of a `long` operation, `None` for a division by zero
+fn long_op(op: ArithOp, v1: i64, v2: i64) -> Option<i64> {
+    Some(match op {
+        ArithOp::Add => v1.wrapping_add(v2),
+        ArithOp::Sub => v1.wrapping_sub(v2),
+        ArithOp::Mul => v1.wrapping_mul(v2),
+        ArithOp::Div => if v2 == 0 { return None } else { v1.wrapping_div(v2) },
+        ArithOp::Rem => if v2 == 0 { return None } else { v1.wrapping_rem(v2) },
+        // the distance is masked to 6 bits
+        ArithOp::Shl => v1.wrapping_shl(v2 as u32),
+        ArithOp::Shr => v1.wrapping_shr(v2 as u32),
+        ArithOp::Ushr => (v1 as u64).wrapping_shr(v2 as u32) as i64,
+        ArithOp::And => v1 & v2,
+        ArithOp::Or => v1 | v2,
+        ArithOp::Xor => v1 ^ v2,
+    })
+}
+
+/// The result of a `float` or `double` operation, the remainder truncates like C's `fmod`
+fn float_op<T>(op: ArithOp, v1: T, v2: T) -> T
+where
+    T: Add<Output = T> + Sub<Output = T> + Mul<Output = T> + Div<Output = T> + Rem<Output = T>,
+{
+    match op {
+        ArithOp::Add => v1 + v2,
+        ArithOp::Sub => v1 - v2,
+        ArithOp::Mul => v1 * v2,
+        ArithOp::Div => v1 / v2,
+        ArithOp::Rem => v1 % v2,
+        _ => unreachable!("{op:?} has no floating point form"),
+    }
+}
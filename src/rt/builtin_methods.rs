@@ -1,39 +1,627 @@
-use crate::rt::RuntimeCtx;
+use crate::{descriptor::{FieldDescriptor, MethodDescriptor}, rt::{string, values_into_f64, values_into_u64, Result, RtError, RuntimeCtx, Value, ARRAY_DATA_OFFSET, ARRAY_LENGTH_OFFSET, CLASS_ID_OFFSET, STREAM_ID_OFFSET, STRING_CLASS, THREAD_DAEMON_OFFSET, THREAD_NAME_OFFSET, THREAD_TARGET_OFFSET, THROWABLE_CAUSE_OFFSET, THROWABLE_MESSAGE_OFFSET, OBJECT_HEADER_SIZE}};
 
-pub fn obj_init(ctx: &mut RuntimeCtx) {
+pub fn obj_init(ctx: &mut RuntimeCtx) -> Result<()> {
     let _this = ctx.pop();
+    Ok(())
 }
-pub fn obj_equals(ctx: &mut RuntimeCtx) {
+pub fn obj_equals(ctx: &mut RuntimeCtx) -> Result<()> {
     let this = ctx.pop();
     let obj = ctx.pop();
     ctx.push(this == obj);
+    Ok(())
 }
-pub fn obj_get_class(ctx: &mut RuntimeCtx) {
+pub fn obj_get_class(ctx: &mut RuntimeCtx) -> Result<()> {
     let this = ctx.pop();
     let class_instance = ctx.get_class_object(this);
     ctx.push(class_instance);
+    Ok(())
 }
-pub fn obj_hash_code(ctx: &mut RuntimeCtx) {
+pub fn obj_hash_code(ctx: &mut RuntimeCtx) -> Result<()> {
     let this = ctx.pop();
-    ctx.push(this);
+    let hash_code = ctx.identity_hash(this);
+    ctx.push(hash_code as i32);
+    Ok(())
 }
-pub fn obj_to_string(ctx: &mut RuntimeCtx) {
+pub fn obj_to_string(ctx: &mut RuntimeCtx) -> Result<()> {
     let this = ctx.pop();
-    let hash_code = this.into_u32();
-    let class_name = ctx.get_class_name(this);
+    let hash_code = ctx.identity_hash(this);
+    let class_name = ctx.get_class_name(this).replace('/', ".");
 
-    let string = ctx.new_string_obj(format!("{class_name}@{hash_code:x}"));
+    let Some(string) = ctx.try_new_string_obj(&format!("{class_name}@{hash_code:x}"))? else { return Ok(()) };
     ctx.push(string);
+    Ok(())
 }
-pub fn printstream_println_str(ctx: &mut RuntimeCtx) {
-    let sref = ctx.pop();
+/// A shallow copy of an array or of an object whose class implements `Cloneable`
+pub fn obj_clone(ctx: &mut RuntimeCtx) -> Result<()> {
+    // the receiver stays on the stack while room is made for the copy
+    let this = ctx.top();
+    let class = ctx.get_class_id(this);
+    let cloneable = ctx.runtime.load_class("java/lang/Cloneable")?;
+    if !ctx.runtime.is_assignable(class, cloneable) {
+        let class_name = ctx.get_class_name(this).replace('/', ".");
+        ctx.pop();
+        return ctx.throw_new("java/lang/CloneNotSupportedException", Some(class_name));
+    }
+    let size = match ctx.runtime.get_class(class).array_component {
+        Some(_) => ctx.array_size(class, ctx.read_u32_ref(this.offset(ARRAY_LENGTH_OFFSET)).unwrap()),
+        None => ctx.runtime.get_class(class).get_aligned_data_size() as usize,
+    };
+    if !ctx.make_room(size)? {
+        return Ok(());
+    }
     let this = ctx.pop();
-    let n = ctx.read_u8_ref(this.offset(0)).unwrap();
-
-    let sref = ctx.read_string_object(sref).unwrap();
+    // the copy gets a header of its own, without the identity hash
+    let copy = ctx.alloc(class, size);
+    let content_size = size - OBJECT_HEADER_SIZE as usize;
+    let content = ctx.ref_bytes(this.offset(OBJECT_HEADER_SIZE as u32), content_size).unwrap().to_vec();
+    ctx.ref_bytes_mut(copy.offset(OBJECT_HEADER_SIZE as u32), content_size).unwrap().copy_from_slice(&content);
+    ctx.push(copy);
+    Ok(())
+}
+/// Pops a timeout in milliseconds, 0 meaning none, and throws if it is negative
+fn pop_timeout(ctx: &mut RuntimeCtx) -> Result<Option<Option<u64>>> {
+    let millis = values_into_u64(ctx.pop2()) as i64;
+    if millis < 0 {
+        ctx.throw_new("java/lang/IllegalArgumentException", Some("timeout value is negative".into()))?;
+        return Ok(None);
+    }
+    Ok(Some((millis > 0).then_some(millis as u64)))
+}
+fn not_owner(ctx: &mut RuntimeCtx) -> Result<()> {
+    ctx.throw_new("java/lang/IllegalMonitorStateException", Some("current thread is not owner".into()))
+}
+pub fn obj_wait(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    if !ctx.monitor_wait(this, None) {
+        not_owner(ctx)?;
+    }
+    Ok(())
+}
+pub fn obj_wait_timeout(ctx: &mut RuntimeCtx) -> Result<()> {
+    let Some(millis) = pop_timeout(ctx)? else {
+        ctx.pop();
+        return Ok(());
+    };
+    let this = ctx.pop();
+    if !ctx.monitor_wait(this, millis) {
+        not_owner(ctx)?;
+    }
+    Ok(())
+}
+pub fn obj_notify(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    if !ctx.monitor_notify(this, false) {
+        not_owner(ctx)?;
+    }
+    Ok(())
+}
+pub fn obj_notify_all(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    if !ctx.monitor_notify(this, true) {
+        not_owner(ctx)?;
+    }
+    Ok(())
+}
+/// Writes text to the stream of the `PrintStream` receiver and pops it
+fn print(ctx: &mut RuntimeCtx, chars: &[u16], newline: bool) -> Result<()> {
+    let this = ctx.pop();
+    let stream = ctx.read_u8_ref(this.offset(STREAM_ID_OFFSET)).unwrap();
+    // like `PrintStream`, failing to write is not an exception
+    let _ = ctx.runtime.stdio.write_text(stream, chars, newline);
+    Ok(())
+}
+fn pop_boolean_text(ctx: &mut RuntimeCtx) -> Result<Option<Vec<u16>>> {
+    let text = if ctx.pop().into_u32() != 0 { "true" } else { "false" };
+    Ok(Some(text.encode_utf16().collect()))
+}
+fn pop_char_text(ctx: &mut RuntimeCtx) -> Result<Option<Vec<u16>>> {
+    Ok(Some(vec![ctx.pop().into_u16()]))
+}
+fn pop_int_text(ctx: &mut RuntimeCtx) -> Result<Option<Vec<u16>>> {
+    Ok(Some(ctx.pop().into_i32().to_string().encode_utf16().collect()))
+}
+fn pop_long_text(ctx: &mut RuntimeCtx) -> Result<Option<Vec<u16>>> {
+    let long = values_into_u64(ctx.pop2()) as i64;
+    Ok(Some(long.to_string().encode_utf16().collect()))
+}
+fn pop_float_text(ctx: &mut RuntimeCtx) -> Result<Option<Vec<u16>>> {
+    Ok(Some(string::float_to_string(ctx.pop().into_f32()).encode_utf16().collect()))
+}
+fn pop_double_text(ctx: &mut RuntimeCtx) -> Result<Option<Vec<u16>>> {
+    let double = values_into_f64(ctx.pop2());
+    Ok(Some(string::double_to_string(double).encode_utf16().collect()))
+}
+fn pop_char_array_text(ctx: &mut RuntimeCtx) -> Result<Option<Vec<u16>>> {
+    let array = ctx.pop();
+    if array == Value::NULL {
+        ctx.throw_new("java/lang/NullPointerException", None)?;
+        return Ok(None);
+    }
+    let length = ctx.read_u32_ref(array.offset(ARRAY_LENGTH_OFFSET)).unwrap() as usize;
+    let bytes = ctx.ref_bytes(array.offset(ARRAY_DATA_OFFSET), 2 * length).unwrap();
+    Ok(Some(bytes.chunks_exact(2).map(|c| u16::from_ne_bytes([c[0], c[1]])).collect()))
+}
+fn pop_string_text(ctx: &mut RuntimeCtx) -> Result<Option<Vec<u16>>> {
+    let string = ctx.pop();
+    Ok(Some(ctx.read_string_chars(string).unwrap_or_else(|| "null".encode_utf16().collect())))
+}
+fn pop_object_text(ctx: &mut RuntimeCtx) -> Result<Option<Vec<u16>>> {
+    // the argument stays on the stack while `toString()` may run
+    let obj = ctx.top();
+    let Some(chars) = ctx.string_value_of(obj)? else { return Ok(None) };
+    ctx.pop();
+    Ok(Some(chars))
+}
+macro_rules! print_methods {
+    ($($print:ident, $println:ident => $pop_text:ident,)*) => {$(
+        pub fn $print(ctx: &mut RuntimeCtx) -> Result<()> {
+            let Some(chars) = $pop_text(ctx)? else { return Ok(()) };
+            print(ctx, &chars, false)
+        }
+        pub fn $println(ctx: &mut RuntimeCtx) -> Result<()> {
+            let Some(chars) = $pop_text(ctx)? else { return Ok(()) };
+            print(ctx, &chars, true)
+        }
+    )*};
+}
+print_methods! {
+    printstream_print_boolean, printstream_println_boolean => pop_boolean_text,
+    printstream_print_char, printstream_println_char => pop_char_text,
+    printstream_print_int, printstream_println_int => pop_int_text,
+    printstream_print_long, printstream_println_long => pop_long_text,
+    printstream_print_float, printstream_println_float => pop_float_text,
+    printstream_print_double, printstream_println_double => pop_double_text,
+    printstream_print_char_array, printstream_println_char_array => pop_char_array_text,
+    printstream_print_str, printstream_println_str => pop_string_text,
+    printstream_print_object, printstream_println_object => pop_object_text,
+}
+pub fn printstream_println(ctx: &mut RuntimeCtx) -> Result<()> {
+    print(ctx, &[], true)
+}
+/// `printf` and `format`, which return the stream
+pub fn printstream_printf(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.stack[ctx.stack.len() - 3];
+    let stream = ctx.read_u8_ref(this.offset(STREAM_ID_OFFSET)).unwrap();
+    let mut text = String::new();
+    let formatted = ctx.format_java(&mut text);
+    // what was formatted before an exception is still printed
+    let chars: Vec<u16> = text.encode_utf16().collect();
+    let _ = ctx.runtime.stdio.write_text(stream, &chars, false);
+    formatted.map(drop)
+}
+pub fn printstream_flush(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    let stream = ctx.read_u8_ref(this.offset(STREAM_ID_OFFSET)).unwrap();
+    let _ = ctx.runtime.stdio.flush(stream);
+    Ok(())
+}
+pub fn printstream_write(ctx: &mut RuntimeCtx) -> Result<()> {
+    let byte = ctx.pop().into_u8();
+    let this = ctx.pop();
+    let stream = ctx.read_u8_ref(this.offset(STREAM_ID_OFFSET)).unwrap();
+    let _ = ctx.runtime.stdio.write(stream, &[byte]);
+    Ok(())
+}
+/// Reads from `System.in` into a byte array, pushing the number of bytes read or -1 at the end
+fn input_stream_read(ctx: &mut RuntimeCtx, array: Value, offset: i32, length: i32) -> Result<()> {
+    if array == Value::NULL {
+        return ctx.throw_new("java/lang/NullPointerException", None);
+    }
+    let array_length = ctx.read_u32_ref(array.offset(ARRAY_LENGTH_OFFSET)).unwrap() as i64;
+    if offset < 0 || length < 0 || offset as i64 + length as i64 > array_length {
+        let message = format!("Range [{offset}, {offset} + {length}) out of bounds for length {array_length}");
+        return ctx.throw_new("java/lang/IndexOutOfBoundsException", Some(message));
+    }
+    if length == 0 {
+        ctx.push(0);
+        return Ok(());
+    }
+    let mut buf = vec![0; length as usize];
+    match ctx.runtime.stdio.read(&mut buf) {
+        Ok(0) => ctx.push(-1),
+        Ok(n) => {
+            let start = array.offset(ARRAY_DATA_OFFSET + offset as u32);
+            ctx.ref_bytes_mut(start, n).unwrap().copy_from_slice(&buf[..n]);
+            ctx.push(n as i32);
+        }
+        Err(e) => ctx.throw_new("java/io/IOException", Some(e.to_string()))?,
+    }
+    Ok(())
+}
+pub fn inputstream_read(ctx: &mut RuntimeCtx) -> Result<()> {
+    let _this = ctx.pop();
+    let mut byte = [0];
+    match ctx.runtime.stdio.read(&mut byte) {
+        Ok(0) => ctx.push(-1),
+        Ok(_) => ctx.push(byte[0] as i32),
+        Err(e) => ctx.throw_new("java/io/IOException", Some(e.to_string()))?,
+    }
+    Ok(())
+}
+pub fn inputstream_read_array(ctx: &mut RuntimeCtx) -> Result<()> {
+    let array = ctx.pop();
+    let _this = ctx.pop();
+    let length = if array == Value::NULL { 0 } else { ctx.read_u32_ref(array.offset(ARRAY_LENGTH_OFFSET)).unwrap() as i32 };
+    input_stream_read(ctx, array, 0, length)
+}
+pub fn inputstream_read_range(ctx: &mut RuntimeCtx) -> Result<()> {
+    let length = ctx.pop().into_i32();
+    let offset = ctx.pop().into_i32();
+    let array = ctx.pop();
+    let _this = ctx.pop();
+    input_stream_read(ctx, array, offset, length)
+}
+pub fn inputstream_available(ctx: &mut RuntimeCtx) -> Result<()> {
+    let _this = ctx.pop();
+    ctx.push(0);
+    Ok(())
+}
+pub fn class_get_name(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    let class_id = ctx.read_u32_ref(this.offset(CLASS_ID_OFFSET)).unwrap();
+    let name = ctx.runtime.get_class(class_id).name.replace('/', ".");
+    let Some(string) = ctx.try_new_string_obj(&name)? else { return Ok(()) };
+    ctx.push(string);
+    Ok(())
+}
+pub fn class_to_string(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    let class_id = ctx.read_u32_ref(this.offset(CLASS_ID_OFFSET)).unwrap();
+    let class = ctx.runtime.get_class(class_id);
+    let kind = if class.is_interface() { "interface" } else { "class" };
+    let string = format!("{kind} {}", class.name.replace('/', "."));
+    let Some(string) = ctx.try_new_string_obj(&string)? else { return Ok(()) };
+    ctx.push(string);
+    Ok(())
+}
+pub fn throwable_init_message(ctx: &mut RuntimeCtx) -> Result<()> {
+    let message = ctx.pop();
+    let this = ctx.pop();
+    ctx.write_u32_ref(this.offset(THROWABLE_MESSAGE_OFFSET), message.into_u32());
+    Ok(())
+}
+pub fn throwable_get_message(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    let message = ctx.read_u32_ref(this.offset(THROWABLE_MESSAGE_OFFSET)).unwrap();
+    ctx.push(Value::from(message as i32));
+    Ok(())
+}
+pub fn throwable_get_cause(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    let cause = ctx.read_u32_ref(this.offset(THROWABLE_CAUSE_OFFSET)).unwrap();
+    ctx.push(Value::from(cause as i32));
+    Ok(())
+}
+/// `getMessage()` of the receiver, which subclasses can override
+pub fn throwable_get_localized_message(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.top();
+    let descriptor = MethodDescriptor::new_ret([], FieldDescriptor::ClassRef("java/lang/String".into())).into();
+    ctx.call_virtual(this, "getMessage", &descriptor)?;
+    Ok(())
+}
+/// The name of the class, followed by `getLocalizedMessage()` unless that is null
+pub fn throwable_to_string(ctx: &mut RuntimeCtx) -> Result<()> {
+    // the receiver stays below the copy that `getLocalizedMessage` takes
+    let this = ctx.top();
+    ctx.push(this);
+    let descriptor = MethodDescriptor::new_ret([], FieldDescriptor::ClassRef("java/lang/String".into())).into();
+    if !ctx.call_virtual(this, "getLocalizedMessage", &descriptor)? {
+        return Ok(());
+    }
+    let message = ctx.pop();
+    let this = ctx.pop();
+    let mut chars: Vec<u16> = ctx.get_class_name(this).replace('/', ".").encode_utf16().collect();
+    if let Some(message) = ctx.read_string_chars(message) {
+        chars.extend(": ".encode_utf16());
+        chars.extend(message);
+    }
+    let Some(string) = ctx.try_new_string(&chars)? else { return Ok(()) };
+    ctx.push(string);
+    Ok(())
+}
 
-    match n {
-        0 => println!("{sref}"),
-        _ => unreachable!(),
+/// Pops the receiver, a string, and returns its characters
+fn pop_string_this(ctx: &mut RuntimeCtx) -> Vec<u16> {
+    let this = ctx.pop();
+    ctx.read_string_chars(this).unwrap()
+}
+/// Pops a string argument, throwing a `NullPointerException` and returning `None` if it is null
+fn pop_string_arg(ctx: &mut RuntimeCtx) -> Result<Option<Vec<u16>>> {
+    let arg = ctx.pop();
+    match ctx.read_string_chars(arg) {
+        Some(chars) => Ok(Some(chars)),
+        None => {
+            ctx.throw_new("java/lang/NullPointerException", None)?;
+            Ok(None)
+        }
+    }
+}
+fn index_of(haystack: &[u16], needle: &[u16]) -> i32 {
+    if needle.is_empty() {
+        return 0;
+    }
+    haystack.windows(needle.len()).position(|w| w == needle).map_or(-1, |i| i as i32)
+}
+pub fn string_length(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = pop_string_this(ctx);
+    ctx.push(this.len() as i32);
+    Ok(())
+}
+pub fn string_char_at(ctx: &mut RuntimeCtx) -> Result<()> {
+    let index = ctx.pop().into_i32();
+    let this = pop_string_this(ctx);
+    match this.get(index as usize) {
+        Some(&c) => ctx.push(Value::from(c as i32)),
+        None => {
+            let message = format!("String index out of range: {index}");
+            ctx.throw_new("java/lang/StringIndexOutOfBoundsException", Some(message))?;
+        }
     }
+    Ok(())
+}
+pub fn string_equals(ctx: &mut RuntimeCtx) -> Result<()> {
+    let obj = ctx.pop();
+    let this = pop_string_this(ctx);
+    let equal = obj != Value::NULL
+        && ctx.get_class_id(obj) == STRING_CLASS
+        && ctx.read_string_chars(obj).unwrap() == this;
+    ctx.push(equal);
+    Ok(())
+}
+pub fn string_hash_code(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = pop_string_this(ctx);
+    ctx.push(string::hash_code(&this));
+    Ok(())
+}
+fn substring(ctx: &mut RuntimeCtx, this: Value, begin: i32, end: i32) -> Result<()> {
+    let chars = ctx.read_string_chars(this).unwrap();
+    let length = chars.len() as i32;
+    if begin < 0 || begin > end || end > length {
+        let message = format!("begin {begin}, end {end}, length {length}");
+        return ctx.throw_new("java/lang/StringIndexOutOfBoundsException", Some(message));
+    }
+    let string = if end - begin == length {
+        this
+    } else {
+        let Some(string) = ctx.try_new_string(&chars[begin as usize..end as usize])? else { return Ok(()) };
+        string
+    };
+    ctx.push(string);
+    Ok(())
+}
+pub fn string_substring(ctx: &mut RuntimeCtx) -> Result<()> {
+    let begin = ctx.pop().into_i32();
+    let this = ctx.pop();
+    let length = ctx.read_string_chars(this).unwrap().len() as i32;
+    substring(ctx, this, begin, length)
+}
+pub fn string_substring_end(ctx: &mut RuntimeCtx) -> Result<()> {
+    let end = ctx.pop().into_i32();
+    let begin = ctx.pop().into_i32();
+    let this = ctx.pop();
+    substring(ctx, this, begin, end)
+}
+pub fn string_index_of_char(ctx: &mut RuntimeCtx) -> Result<()> {
+    let ch = ctx.pop().into_u32();
+    let this = pop_string_this(ctx);
+    let mut buf = [0; 2];
+    let needle = match char::from_u32(ch) {
+        Some(c) => &*c.encode_utf16(&mut buf),
+        // a lone surrogate can only match itself
+        None if ch <= 0xffff => &[ch as u16][..],
+        None => &[],
+    };
+    let index = if needle.is_empty() { -1 } else { index_of(&this, needle) };
+    ctx.push(index);
+    Ok(())
+}
+pub fn string_index_of_string(ctx: &mut RuntimeCtx) -> Result<()> {
+    let Some(needle) = pop_string_arg(ctx)? else { return Ok(()) };
+    let this = pop_string_this(ctx);
+    ctx.push(index_of(&this, &needle));
+    Ok(())
+}
+pub fn string_concat(ctx: &mut RuntimeCtx) -> Result<()> {
+    let Some(other) = pop_string_arg(ctx)? else { return Ok(()) };
+    let this = ctx.pop();
+    let string = if other.is_empty() {
+        this
+    } else {
+        let mut chars = ctx.read_string_chars(this).unwrap();
+        chars.extend(other);
+        let Some(string) = ctx.try_new_string(&chars)? else { return Ok(()) };
+        string
+    };
+    ctx.push(string);
+    Ok(())
+}
+pub fn string_compare_to(ctx: &mut RuntimeCtx) -> Result<()> {
+    let Some(other) = pop_string_arg(ctx)? else { return Ok(()) };
+    let this = pop_string_this(ctx);
+    let difference = this.iter()
+        .zip(&other)
+        .find(|(a, b)| a != b)
+        .map_or(this.len() as i32 - other.len() as i32, |(&a, &b)| a as i32 - b as i32);
+    ctx.push(difference);
+    Ok(())
+}
+/// The `compareTo(Object)` of `Comparable`, which only takes strings
+pub fn string_compare_to_object(ctx: &mut RuntimeCtx) -> Result<()> {
+    let other = ctx.top();
+    if other != Value::NULL && ctx.get_class_id(other) != STRING_CLASS {
+        ctx.pop2();
+        let message = format!("class {} cannot be cast to class java.lang.String", ctx.get_class_name(other).replace('/', "."));
+        return ctx.throw_new("java/lang/ClassCastException", Some(message));
+    }
+    string_compare_to(ctx)
+}
+pub fn string_to_string(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    ctx.push(this);
+    Ok(())
+}
+pub fn string_intern(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = pop_string_this(ctx);
+    let interned = ctx.runtime.intern(&this);
+    ctx.push(Value::new_ref_static(interned));
+    Ok(())
+}
+pub fn system_exit(ctx: &mut RuntimeCtx) -> Result<()> {
+    let status = ctx.pop().into_i32();
+    Err(RtError::Exit(status))
+}
+pub fn string_value_of(ctx: &mut RuntimeCtx) -> Result<()> {
+    // the argument stays on the stack while `toString()` may run
+    let obj = ctx.top();
+    let Some(chars) = ctx.string_value_of(obj)? else { return Ok(()) };
+    ctx.pop();
+    let Some(string) = ctx.try_new_string(&chars)? else { return Ok(()) };
+    ctx.push(string);
+    Ok(())
+}
+macro_rules! string_value_of_methods {
+    ($($value_of:ident => $pop_text:ident,)*) => {$(
+        pub fn $value_of(ctx: &mut RuntimeCtx) -> Result<()> {
+            let Some(chars) = $pop_text(ctx)? else { return Ok(()) };
+            let Some(string) = ctx.try_new_string(&chars)? else { return Ok(()) };
+            ctx.push(string);
+            Ok(())
+        }
+    )*};
+}
+string_value_of_methods! {
+    string_value_of_boolean => pop_boolean_text,
+    string_value_of_char => pop_char_text,
+    string_value_of_int => pop_int_text,
+    string_value_of_long => pop_long_text,
+    string_value_of_float => pop_float_text,
+    string_value_of_double => pop_double_text,
+    string_value_of_char_array => pop_char_array_text,
+}
+pub fn string_format(ctx: &mut RuntimeCtx) -> Result<()> {
+    let mut text = String::new();
+    if !ctx.format_java(&mut text)? {
+        return Ok(());
+    }
+    let Some(string) = ctx.try_new_string_obj(&text)? else { return Ok(()) };
+    ctx.push(string);
+    Ok(())
+}
+fn thread_init_fields(ctx: &mut RuntimeCtx, this: Value, target: Value, name: Value) -> Result<()> {
+    if name == Value::NULL {
+        return ctx.throw_new("java/lang/NullPointerException", Some("name cannot be null".into()));
+    }
+    ctx.write_u32_ref(this.offset(THREAD_TARGET_OFFSET), target.into_u32());
+    ctx.write_u32_ref(this.offset(THREAD_NAME_OFFSET), name.into_u32());
+    Ok(())
+}
+/// A `Thread-<n>` name for a thread made without one, while the thread is still on the stack
+fn new_thread_name(ctx: &mut RuntimeCtx) -> Result<Option<Value>> {
+    let name = ctx.next_thread_name();
+    ctx.try_new_string_obj(&name)
+}
+pub fn thread_init(ctx: &mut RuntimeCtx) -> Result<()> {
+    let Some(name) = new_thread_name(ctx)? else { return Ok(()) };
+    let this = ctx.pop();
+    thread_init_fields(ctx, this, Value::NULL, name)
+}
+pub fn thread_init_runnable(ctx: &mut RuntimeCtx) -> Result<()> {
+    let Some(name) = new_thread_name(ctx)? else { return Ok(()) };
+    let target = ctx.pop();
+    let this = ctx.pop();
+    thread_init_fields(ctx, this, target, name)
+}
+pub fn thread_init_runnable_name(ctx: &mut RuntimeCtx) -> Result<()> {
+    let name = ctx.pop();
+    let target = ctx.pop();
+    let this = ctx.pop();
+    thread_init_fields(ctx, this, target, name)
+}
+pub fn thread_init_name(ctx: &mut RuntimeCtx) -> Result<()> {
+    let name = ctx.pop();
+    let this = ctx.pop();
+    thread_init_fields(ctx, this, Value::NULL, name)
+}
+pub fn thread_start(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    if !ctx.start_thread(this)? {
+        ctx.throw_new("java/lang/IllegalThreadStateException", None)?;
+    }
+    Ok(())
+}
+pub fn thread_run(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    let target = Value(ctx.read_u32_ref(this.offset(THREAD_TARGET_OFFSET)).unwrap());
+    if target == Value::NULL {
+        return Ok(());
+    }
+    // `run` of the target returns to the caller in place of this one
+    let (class, method_id) = ctx.runtime.find_method(ctx.get_class_id(target), "run", &MethodDescriptor::new_void([]).into()).unwrap();
+    ctx.push(target);
+    ctx.invoke(class, method_id)
+}
+pub fn thread_join(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    ctx.join_thread(this, None);
+    Ok(())
+}
+pub fn thread_join_timeout(ctx: &mut RuntimeCtx) -> Result<()> {
+    let Some(millis) = pop_timeout(ctx)? else {
+        ctx.pop();
+        return Ok(());
+    };
+    let this = ctx.pop();
+    ctx.join_thread(this, millis);
+    Ok(())
+}
+pub fn thread_sleep(ctx: &mut RuntimeCtx) -> Result<()> {
+    if let Some(millis) = pop_timeout(ctx)? {
+        ctx.sleep(millis.unwrap_or(0));
+    }
+    Ok(())
+}
+pub fn thread_yield(ctx: &mut RuntimeCtx) -> Result<()> {
+    ctx.yield_thread();
+    Ok(())
+}
+pub fn thread_current_thread(ctx: &mut RuntimeCtx) -> Result<()> {
+    let Some(thread) = ctx.current_thread_object()? else { return Ok(()) };
+    ctx.push(thread);
+    Ok(())
+}
+pub fn thread_get_name(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    let name = ctx.read_u32_ref(this.offset(THREAD_NAME_OFFSET)).unwrap();
+    ctx.push(Value(name));
+    Ok(())
+}
+pub fn thread_set_name(ctx: &mut RuntimeCtx) -> Result<()> {
+    let name = ctx.pop();
+    let this = ctx.pop();
+    if name == Value::NULL {
+        return ctx.throw_new("java/lang/NullPointerException", Some("name cannot be null".into()));
+    }
+    ctx.write_u32_ref(this.offset(THREAD_NAME_OFFSET), name.into_u32());
+    Ok(())
+}
+pub fn thread_is_alive(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    let alive = ctx.is_thread_alive(this);
+    ctx.push(alive);
+    Ok(())
+}
+pub fn thread_set_daemon(ctx: &mut RuntimeCtx) -> Result<()> {
+    let on = ctx.pop().into_u32() != 0;
+    let this = ctx.pop();
+    if ctx.is_thread_alive(this) {
+        return ctx.throw_new("java/lang/IllegalThreadStateException", None);
+    }
+    ctx.write_u8_ref(this.offset(THREAD_DAEMON_OFFSET), on as u8);
+    Ok(())
+}
+pub fn thread_is_daemon(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    let daemon = ctx.read_u8_ref(this.offset(THREAD_DAEMON_OFFSET)).unwrap() != 0;
+    ctx.push(daemon);
+    Ok(())
 }
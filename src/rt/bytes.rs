@@ -20,7 +20,10 @@ impl Bytes32Aligned {
         }
     }
     pub const fn len(&self) -> usize {
-        self.slice.len() * size_of::<u32>()
+        size_of_val(&self.slice)
+    }
+    pub const fn is_empty(&self) -> bool {
+        self.slice.is_empty()
     }
     pub fn as_u32_slice(&self) -> &[u32] {
         &self.slice
@@ -0,0 +1,227 @@
+//! Where class files are loaded from.
+
+use std::{collections::BTreeMap, env, fs, io, path::{Path, PathBuf}};
+
+use crate::zip::ZipArchive;
+
+/// The Java version whose classes are preferred in multi-release jars by default
+const DEFAULT_RELEASE: u32 = 17;
+/// The first version that multi-release jars can have classes for
+const FIRST_VERSIONED_RELEASE: u32 = 9;
+const MANIFEST: &str = "META-INF/MANIFEST.MF";
+
+/// Ordered locations that classes are searched in, the first one that has a class wins
+#[derive(Debug, Clone)]
+pub struct ClassPath {
+    entries: Vec<ClassPathEntry>,
+    /// Version of the classes to take from `META-INF/versions/` in multi-release jars
+    release: u32,
+}
+
+#[derive(Debug, Clone)]
+enum ClassPathEntry {
+    /// Class files in directories that follow the packages, like `dir/com/example/Main.class`
+    Directory(PathBuf),
+    /// A `.jar` or `.zip` file laid out like a directory
+    Archive {
+        path: PathBuf,
+        archive: ZipArchive,
+        /// Whether the manifest has `Multi-Release: true`
+        multi_release: bool,
+    },
+    /// Class file contents by class name, like `com/example/Main`
+    Memory(BTreeMap<Box<str>, Box<[u8]>>),
+}
+
+impl ClassPath {
+    /// An empty class path that finds nothing
+    pub const fn new() -> Self {
+        Self { entries: Vec::new(), release: DEFAULT_RELEASE }
+    }
+    /// Sets the Java version for multi-release jars, whose `META-INF/versions/N/` directories
+    /// override the classes at the root for every version `N` up to `release`
+    pub fn set_release(&mut self, release: u32) {
+        self.release = release;
+    }
+    /// Parses a class path like the one given to `-cp`: directories and archives separated by the
+    /// platform's path separator (`:`, or `;` on Windows).
+    ///
+    /// Entries ending in `.jar` or `.zip` are archives, and an entry ending in `*` stands for all
+    /// `.jar` files in its directory.
+    pub fn parse(class_path: &str) -> io::Result<Self> {
+        let mut result = Self::new();
+        for entry in env::split_paths(class_path) {
+            if entry.file_name().is_some_and(|name| name == "*") {
+                let dir = entry.parent().unwrap();
+                let mut jars = Vec::new();
+                for file in fs::read_dir(if dir.as_os_str().is_empty() { Path::new(".") } else { dir })? {
+                    let path = file?.path();
+                    if is_archive(&path) {
+                        jars.push(path);
+                    }
+                }
+                jars.sort();
+                for jar in jars {
+                    result.push_archive(jar)?;
+                }
+            } else if is_archive(&entry) {
+                result.push_archive(entry)?;
+            } else {
+                result.push_directory(entry);
+            }
+        }
+        Ok(result)
+    }
+    pub fn push_directory(&mut self, path: impl Into<PathBuf>) {
+        self.entries.push(ClassPathEntry::Directory(path.into()));
+    }
+    /// Adds a `.jar` or `.zip` file, reading its table of contents
+    pub fn push_archive(&mut self, path: impl Into<PathBuf>) -> io::Result<()> {
+        let path = path.into();
+        self.open_archive(path)?;
+        Ok(())
+    }
+    /// Adds an executable jar like `java -jar` does, with the jars and directories listed in the
+    /// `Class-Path` of its manifest after it. Returns the `Main-Class` of the manifest.
+    pub fn push_executable_jar(&mut self, path: impl Into<PathBuf>) -> io::Result<Option<String>> {
+        let path = path.into();
+        let manifest = self.open_archive(path.clone())?.unwrap_or_default();
+        let base = path.parent().unwrap_or(Path::new(""));
+        if let Some(class_path) = manifest.get("Class-Path") {
+            // relative URLs, a missing dependency is skipped like the JVM does
+            for url in class_path.split_ascii_whitespace() {
+                let dependency = base.join(decode_url_path(url));
+                if is_archive(&dependency) && dependency.is_file() {
+                    self.push_archive(dependency)?;
+                } else if dependency.is_dir() {
+                    self.push_directory(dependency);
+                }
+            }
+        }
+        Ok(manifest.get("Main-Class").map(str::to_string))
+    }
+    /// Adds an archive and returns its manifest, if it has one
+    fn open_archive(&mut self, path: PathBuf) -> io::Result<Option<Manifest>> {
+        let with_path = |e: io::Error| io::Error::new(e.kind(), format!("{}: {e}", path.display()));
+        let archive = ZipArchive::open(&path).map_err(with_path)?;
+        let manifest = archive.read(MANIFEST).map_err(with_path)?.map(|bytes| Manifest::parse(&bytes));
+        let multi_release = manifest.as_ref().is_some_and(|manifest| {
+            manifest.get("Multi-Release").is_some_and(|value| value.eq_ignore_ascii_case("true"))
+        });
+        self.entries.push(ClassPathEntry::Archive { path, archive, multi_release });
+        Ok(manifest)
+    }
+    /// Adds class files that are already in memory, keyed by class name like `com/example/Main`
+    pub fn push_memory<N: Into<Box<str>>, B: Into<Box<[u8]>>>(&mut self, classes: impl IntoIterator<Item = (N, B)>) {
+        let classes = classes.into_iter().map(|(name, bytes)| (name.into(), bytes.into())).collect();
+        self.entries.push(ClassPathEntry::Memory(classes));
+    }
+    /// The contents of the class file of the named class, from the first entry that has it
+    pub fn find_class(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        let file_name = format!("{name}.class");
+        for entry in &self.entries {
+            let class = match entry {
+                ClassPathEntry::Directory(dir) => match fs::read(dir.join(&file_name)) {
+                    Ok(bytes) => Some(bytes),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                    Err(e) => return Err(e),
+                },
+                ClassPathEntry::Archive { archive, multi_release: false, .. } => archive.read(&file_name)?,
+                ClassPathEntry::Archive { archive, multi_release: true, .. } => {
+                    let mut class = None;
+                    for version in (FIRST_VERSIONED_RELEASE..=self.release).rev() {
+                        class = archive.read(&format!("META-INF/versions/{version}/{file_name}"))?;
+                        if class.is_some() {
+                            break;
+                        }
+                    }
+                    match class {
+                        Some(class) => Some(class),
+                        None => archive.read(&file_name)?,
+                    }
+                }
+                ClassPathEntry::Memory(classes) => classes.get(name).map(|bytes| bytes.to_vec()),
+            };
+            if class.is_some() {
+                return Ok(class);
+            }
+        }
+        Ok(None)
+    }
+    /// The directories and archives of the class path, in order
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.entries.iter().filter_map(|entry| match entry {
+            ClassPathEntry::Directory(path) | ClassPathEntry::Archive { path, .. } => Some(path.as_path()),
+            ClassPathEntry::Memory(_) => None,
+        })
+    }
+    /// Where [`Self::find_class`] looks for the named class, for error messages
+    pub fn search_locations(&self, name: &str) -> Vec<String> {
+        self.entries.iter().map(|entry| match entry {
+            ClassPathEntry::Directory(dir) => dir.join(format!("{name}.class")).display().to_string(),
+            ClassPathEntry::Archive { path, .. } => format!("{}!/{name}.class", path.display()),
+            ClassPathEntry::Memory(_) => format!("{name} in memory"),
+        }).collect()
+    }
+}
+
+/// The current directory, like the JVM uses without a class path
+impl Default for ClassPath {
+    fn default() -> Self {
+        let mut class_path = Self::new();
+        class_path.push_directory(".");
+        class_path
+    }
+}
+
+/// The main section of a jar manifest, made of `Name: value` lines
+#[derive(Debug, Default)]
+struct Manifest {
+    attributes: Vec<(String, String)>,
+}
+
+impl Manifest {
+    fn parse(bytes: &[u8]) -> Self {
+        let text = String::from_utf8_lossy(bytes);
+        let mut attributes: Vec<(String, String)> = Vec::new();
+        for line in text.lines() {
+            if line.is_empty() {
+                // the sections for single entries follow
+                break;
+            }
+            if let Some(continuation) = line.strip_prefix(' ') {
+                // long values are wrapped onto lines that start with a space
+                if let Some((_, value)) = attributes.last_mut() {
+                    value.push_str(continuation);
+                }
+            } else if let Some((name, value)) = line.split_once(':') {
+                attributes.push((name.trim().into(), value.trim_start().into()));
+            }
+        }
+        Self { attributes }
+    }
+    /// The value of an attribute, names are case-insensitive
+    fn get(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.trim_end())
+    }
+}
+
+/// Decodes the `%xx` escapes in the path of a URL
+fn decode_url_path(url: &str) -> String {
+    let mut bytes = Vec::with_capacity(url.len());
+    let mut rest = url.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        rest = tail;
+        if b == b'%' && let Some(hex) = tail.get(..2) && let Ok(byte) = u8::from_str_radix(&String::from_utf8_lossy(hex), 16) {
+            bytes.push(byte);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn is_archive(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("jar") || extension.eq_ignore_ascii_case("zip"))
+}
@@ -0,0 +1,156 @@
+//! The builtin core library.
+//!
+//! The classes of `java.lang` and `java.util` that nearly every program touches, implemented in
+//! Rust with the behavior of the JDK so that programs run without one: the boxes of the primitive
+//! types and `Number`, `Enum`, `Math`, `StringBuilder`, `Objects` and `Arrays`.
+
+mod arrays;
+mod boxes;
+mod character;
+mod enums;
+mod math;
+mod objects;
+mod parse;
+mod string_builder;
+
+use crate::{class::ClassAccess, descriptor::{AnyDescriptor, FieldDescriptor, MethodDescriptor}};
+
+use super::{member_table::MemberTable, BuiltinMethod, LoadedClass, Result, Runtime, RuntimeCtx, Value, ARRAY_DATA_OFFSET, OBJECT_CLASS};
+
+pub(super) use math::RandomGenerator;
+
+impl Runtime {
+    /// Loads a class of the core library, `None` if it is not one of them
+    pub(super) fn load_core_class(&mut self, classpath: &str) -> Result<Option<LoadedClass>> {
+        Ok(Some(match classpath {
+            "java/lang/Number" => LoadedClass {
+                access_flags: ClassAccess::PUBLIC | ClassAccess::ABSTRACT,
+                interfaces: Box::new([self.load_class("java/io/Serializable")?]),
+                // the boxes implement all of them, `byteValue` and `shortValue` too
+                abstract_methods: super::abstract_method_table(&[
+                    ("byteValue", "()B"),
+                    ("shortValue", "()S"),
+                    ("intValue", "()I"),
+                    ("longValue", "()J"),
+                    ("floatValue", "()F"),
+                    ("doubleValue", "()D"),
+                ]),
+                ..LoadedClass::new_builtin(classpath, OBJECT_CLASS, MemberTable::new(), Box::new([]))
+            },
+            "java/lang/Boolean" => self.load_box_class::<bool>()?,
+            "java/lang/Character" => self.load_box_class::<u16>()?,
+            "java/lang/Byte" => self.load_box_class::<i8>()?,
+            "java/lang/Short" => self.load_box_class::<i16>()?,
+            "java/lang/Integer" => self.load_box_class::<i32>()?,
+            "java/lang/Long" => self.load_box_class::<i64>()?,
+            "java/lang/Float" => self.load_box_class::<f32>()?,
+            "java/lang/Double" => self.load_box_class::<f64>()?,
+            "java/lang/Enum" => enums::class(self)?,
+            "java/lang/Math" |
+            "java/lang/StrictMath" => math::members().into_utility_class(classpath),
+            "java/lang/StringBuilder" => string_builder::class(self)?,
+            "java/util/Objects" => objects::members().into_utility_class(classpath),
+            "java/util/Arrays" => arrays::members().into_utility_class(classpath),
+            _ => return Ok(None),
+        }))
+    }
+}
+
+/// The member table and the methods of a builtin class, built together so the ids in the table
+/// are the indices of the methods
+struct Members {
+    table: MemberTable,
+    methods: Vec<BuiltinMethod>,
+}
+
+impl Members {
+    const fn new() -> Self {
+        Self { table: MemberTable::new(), methods: Vec::new() }
+    }
+    fn method(&mut self, name: &str, descriptor: &str, method: BuiltinMethod) -> &mut Self {
+        let descriptor = MethodDescriptor::from_bytes(descriptor.as_bytes()).unwrap();
+        self.table.insert(name, descriptor, self.methods.len() as u16);
+        self.methods.push(method);
+        self
+    }
+    /// A field at an offset in the instance or, for static fields, in the static fields
+    fn field(&mut self, name: &str, descriptor: &str, offset: u16) -> &mut Self {
+        self.table.insert(name, FieldDescriptor::from_bytes(descriptor.as_bytes()).unwrap(), offset);
+        self
+    }
+    fn into_class(self, name: &str, super_class: u32) -> LoadedClass {
+        LoadedClass::new_builtin(name, super_class, self.table, self.methods.into())
+    }
+    /// A final class with only static methods
+    fn into_utility_class(self, name: &str) -> LoadedClass {
+        LoadedClass {
+            access_flags: ClassAccess::PUBLIC | ClassAccess::FINAL,
+            ..self.into_class(name, OBJECT_CLASS)
+        }
+    }
+}
+
+/// Parses a method descriptor written in the source
+fn descriptor(descriptor: &str) -> AnyDescriptor {
+    MethodDescriptor::from_bytes(descriptor.as_bytes()).unwrap().into()
+}
+
+impl RuntimeCtx<'_> {
+    /// Calls the method the class of the receiver has for the name and descriptor and runs it
+    /// until it returns, the receiver and the arguments have to be pushed already.
+    ///
+    /// Returns `false` if the method threw, the exception is thrown on.
+    pub(super) fn call_virtual(&mut self, receiver: Value, name: &str, descriptor: &AnyDescriptor) -> Result<bool> {
+        let Some((class, method_id)) = self.runtime.find_method(self.get_class_id(receiver), name, descriptor) else {
+            let message = format!(
+                "Receiver class {} does not define or inherit an implementation of the resolved method '{}'",
+                self.get_class_name(receiver).replace('/', "."),
+                descriptor.display_type(name),
+            );
+            self.throw_new("java/lang/AbstractMethodError", Some(message))?;
+            return Ok(false);
+        };
+        match self.run_method(class, method_id)? {
+            Ok(()) => Ok(true),
+            Err(exception) => {
+                self.throw(exception)?;
+                Ok(false)
+            }
+        }
+    }
+    /// The value `depth` places below the top of the operand stack
+    fn peek(&self, depth: usize) -> Value {
+        self.stack[self.stack.len() - 1 - depth]
+    }
+    fn pop_n(&mut self, n: usize) {
+        self.stack.truncate(self.stack.len() - n);
+    }
+    /// Pops a string argument, `None` for null
+    fn pop_nullable_string(&mut self) -> Option<Vec<u16>> {
+        let string = self.pop();
+        self.read_string_chars(string)
+    }
+    /// A new `char[]` with the given content, `None` if there is no room for it and an
+    /// `OutOfMemoryError` is thrown
+    fn new_char_array(&mut self, chars: &[u16]) -> Result<Option<Value>> {
+        let class = self.runtime.load_class("[C")?;
+        if !self.make_room(self.array_size(class, chars.len() as u32))? {
+            return Ok(None);
+        }
+        let array = self.new_array(class, chars.len() as u32);
+        let bytes: Vec<u8> = chars.iter().flat_map(|c| c.to_ne_bytes()).collect();
+        self.ref_bytes_mut(array.offset(ARRAY_DATA_OFFSET), bytes.len()).unwrap().copy_from_slice(&bytes);
+        Ok(Some(array))
+    }
+    /// Pushes a new string, unless there is no room for it and an `OutOfMemoryError` is thrown
+    fn push_string(&mut self, text: &str) -> Result<()> {
+        if let Some(string) = self.try_new_string_obj(text)? {
+            self.push(string);
+        }
+        Ok(())
+    }
+    /// Throws a `NullPointerException` without a message
+    fn throw_null_pointer(&mut self) -> Result<()> {
+        self.throw_new("java/lang/NullPointerException", None)
+    }
+}
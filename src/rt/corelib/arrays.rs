@@ -0,0 +1,662 @@
+//! `java.util.Arrays`, for the arrays of every primitive type and of objects.
+//!
+//! Objects are compared, hashed and turned into strings by calling their methods, which can run
+//! a collection. The array stays on the operand stack meanwhile and its elements are read again
+//! after every call, and a sort of objects sorts indices and only moves the elements at the end.
+
+use std::cmp::Ordering;
+
+use crate::code::PrimitiveArrayType;
+
+use super::{boxes::Primitive, descriptor, Members, Result, RuntimeCtx, Value, ARRAY_DATA_OFFSET};
+use super::super::{embed, ArrayComponent, ARRAY_LENGTH_OFFSET};
+
+fn length(ctx: &RuntimeCtx, array: Value) -> u32 {
+    ctx.read_u32_ref(array.offset(ARRAY_LENGTH_OFFSET)).unwrap()
+}
+fn read_elements<T: Primitive>(ctx: &RuntimeCtx, array: Value) -> Vec<T> {
+    let length = length(ctx, array) as usize;
+    let bytes = ctx.ref_bytes(array.offset(ARRAY_DATA_OFFSET), length * size_of::<T>()).unwrap();
+    bytes.chunks_exact(size_of::<T>()).map(|bytes| T::from_jvalue(embed::read_field(bytes, &T::field_type()))).collect()
+}
+fn write_elements<T: Primitive>(ctx: &mut RuntimeCtx, array: Value, start: u32, values: &[T]) {
+    let offset = array.offset(ARRAY_DATA_OFFSET + start * size_of::<T>() as u32);
+    let bytes = ctx.ref_bytes_mut(offset, size_of_val(values)).unwrap();
+    for (bytes, &value) in bytes.chunks_exact_mut(size_of::<T>()).zip(values) {
+        bytes.copy_from_slice(&embed::field_value_bytes(value.into_jvalue()));
+    }
+}
+fn element(ctx: &RuntimeCtx, array: Value, index: u32) -> Value {
+    Value(ctx.read_u32_ref(array.offset(ARRAY_DATA_OFFSET + 4 * index)).unwrap())
+}
+fn set_element(ctx: &mut RuntimeCtx, array: Value, index: u32, value: Value) {
+    ctx.write_u32_ref(array.offset(ARRAY_DATA_OFFSET + 4 * index), value.into_u32());
+}
+
+/// The message of the exception `Arrays` throws for a range that is out of bounds
+fn range_error(length: u32, from: i32, to: i32) -> Option<(&'static str, String)> {
+    if from > to {
+        Some(("java/lang/IllegalArgumentException", format!("fromIndex({from}) > toIndex({to})")))
+    } else if from < 0 {
+        Some(("java/lang/ArrayIndexOutOfBoundsException", format!("Array index out of range: {from}")))
+    } else if to as u32 > length {
+        Some(("java/lang/ArrayIndexOutOfBoundsException", format!("Array index out of range: {to}")))
+    } else {
+        None
+    }
+}
+/// Checks the array and the range of an operation on part of an array that are on the stack
+/// below `depth` other arguments, popping all of them before it throws.
+///
+/// Returns whether the range is valid.
+fn check_range(ctx: &mut RuntimeCtx, depth: usize) -> Result<bool> {
+    let (array, from, to) = (ctx.peek(depth + 2), ctx.peek(depth + 1).into_i32(), ctx.peek(depth).into_i32());
+    if array == Value::NULL {
+        ctx.pop_n(depth + 3);
+        ctx.throw_null_pointer()?;
+        return Ok(false);
+    }
+    if let Some((class, message)) = range_error(length(ctx, array), from, to) {
+        ctx.pop_n(depth + 3);
+        ctx.throw_new(class, Some(message))?;
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// The elements of an array between brackets and separated by commas, like `Arrays.toString`
+fn bracketed(elements: impl IntoIterator<Item = String>) -> String {
+    format!("[{}]", elements.into_iter().collect::<Vec<_>>().join(", "))
+}
+fn elements_text<T: Primitive>(ctx: &RuntimeCtx, array: Value) -> String {
+    bracketed(read_elements::<T>(ctx, array).into_iter().map(T::text))
+}
+fn primitive_array_text(ctx: &RuntimeCtx, array: Value, component: PrimitiveArrayType) -> String {
+    match component {
+        PrimitiveArrayType::Boolean => elements_text::<bool>(ctx, array),
+        PrimitiveArrayType::Byte => elements_text::<i8>(ctx, array),
+        PrimitiveArrayType::Char => elements_text::<u16>(ctx, array),
+        PrimitiveArrayType::Short => elements_text::<i16>(ctx, array),
+        PrimitiveArrayType::Int => elements_text::<i32>(ctx, array),
+        PrimitiveArrayType::Long => elements_text::<i64>(ctx, array),
+        PrimitiveArrayType::Float => elements_text::<f32>(ctx, array),
+        PrimitiveArrayType::Double => elements_text::<f64>(ctx, array),
+    }
+}
+fn to_string<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let array = ctx.pop();
+    let text = if array == Value::NULL {
+        "null".into()
+    } else {
+        elements_text::<T>(ctx, array)
+    };
+    ctx.push_string(&text)
+}
+fn elements_equal<T: Primitive>(ctx: &RuntimeCtx, a: Value, b: Value) -> bool {
+    let (a, b) = (read_elements::<T>(ctx, a), read_elements::<T>(ctx, b));
+    a.len() == b.len() && a.iter().zip(&b).all(|(&a, &b)| a.compare(b) == 0)
+}
+fn primitive_arrays_equal(ctx: &RuntimeCtx, a: Value, b: Value, component: PrimitiveArrayType) -> bool {
+    match component {
+        PrimitiveArrayType::Boolean => elements_equal::<bool>(ctx, a, b),
+        PrimitiveArrayType::Byte => elements_equal::<i8>(ctx, a, b),
+        PrimitiveArrayType::Char => elements_equal::<u16>(ctx, a, b),
+        PrimitiveArrayType::Short => elements_equal::<i16>(ctx, a, b),
+        PrimitiveArrayType::Int => elements_equal::<i32>(ctx, a, b),
+        PrimitiveArrayType::Long => elements_equal::<i64>(ctx, a, b),
+        PrimitiveArrayType::Float => elements_equal::<f32>(ctx, a, b),
+        PrimitiveArrayType::Double => elements_equal::<f64>(ctx, a, b),
+    }
+}
+fn equals<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let (b, a) = (ctx.pop(), ctx.pop());
+    let equal = a == b || a != Value::NULL && b != Value::NULL && elements_equal::<T>(ctx, a, b);
+    ctx.push(equal);
+    Ok(())
+}
+fn hash_code<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let array = ctx.pop();
+    let hash = if array == Value::NULL {
+        0
+    } else {
+        read_elements::<T>(ctx, array).into_iter().fold(1i32, |hash, value| hash.wrapping_mul(31).wrapping_add(value.hash()))
+    };
+    ctx.push(hash);
+    Ok(())
+}
+fn fill<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = T::pop(ctx);
+    let array = ctx.pop();
+    if array == Value::NULL {
+        return ctx.throw_null_pointer();
+    }
+    let values = vec![value; length(ctx, array) as usize];
+    write_elements(ctx, array, 0, &values);
+    Ok(())
+}
+fn fill_range<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = T::pop(ctx);
+    if !check_range(ctx, 0)? {
+        return Ok(());
+    }
+    let (to, from, array) = (ctx.pop().into_i32(), ctx.pop().into_i32(), ctx.pop());
+    write_elements(ctx, array, from as u32, &vec![value; (to - from) as usize]);
+    Ok(())
+}
+fn sort_values<T: Primitive>(values: &mut [T]) {
+    values.sort_by(|a, b| a.compare(*b).cmp(&0));
+}
+fn sort<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let array = ctx.pop();
+    if array == Value::NULL {
+        return ctx.throw_null_pointer();
+    }
+    let mut values = read_elements::<T>(ctx, array);
+    sort_values(&mut values);
+    write_elements(ctx, array, 0, &values);
+    Ok(())
+}
+fn sort_range<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    if !check_range(ctx, 0)? {
+        return Ok(());
+    }
+    let (to, from, array) = (ctx.pop().into_i32() as usize, ctx.pop().into_i32() as usize, ctx.pop());
+    let mut values = read_elements::<T>(ctx, array);
+    sort_values(&mut values[from..to]);
+    write_elements(ctx, array, from as u32, &values[from..to]);
+    Ok(())
+}
+/// `binarySearch`: the index of the key in the sorted array, or `-(insertion point) - 1`
+fn binary_search<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let key = T::pop(ctx);
+    let array = ctx.pop();
+    if array == Value::NULL {
+        return ctx.throw_null_pointer();
+    }
+    let values = read_elements::<T>(ctx, array);
+    // the search of the JDK, which finds the same one of equal keys
+    let (mut low, mut high) = (0, values.len() as i32 - 1);
+    while low <= high {
+        let middle = (low + high) / 2;
+        match values[middle as usize].compare(key).cmp(&0) {
+            Ordering::Less => low = middle + 1,
+            Ordering::Greater => high = middle - 1,
+            Ordering::Equal => {
+                ctx.push(middle);
+                return Ok(());
+            }
+        }
+    }
+    ctx.push(-(low + 1));
+    Ok(())
+}
+
+/// The name of the type of an array in the messages of `System.arraycopy`
+fn array_type_name(descriptor: &str, length: u32) -> String {
+    let name = match descriptor {
+        "Z" => "boolean",
+        "B" => "byte",
+        "C" => "char",
+        "S" => "short",
+        "I" => "int",
+        "J" => "long",
+        "F" => "float",
+        "D" => "double",
+        _ => "object array",
+    };
+    format!("{name}[{length}]")
+}
+/// Copies part of the array below the range on the stack into a new array of the same class
+/// and of `new_length` elements, the rest of which stays zero
+fn copy_range(ctx: &mut RuntimeCtx, descriptor: &str, element_size: u32, from: i32, new_length: i32) -> Result<()> {
+    let array = ctx.top();
+    let length = length(ctx, array);
+    if from < 0 || from as u32 > length {
+        ctx.pop();
+        let message = if from < 0 {
+            format!("arraycopy: source index {from} out of bounds for {}", array_type_name(descriptor, length))
+        } else {
+            format!("arraycopy: length {} is negative", length as i32 - from)
+        };
+        return ctx.throw_new("java/lang/ArrayIndexOutOfBoundsException", Some(message));
+    }
+    let class = ctx.get_class_id(array);
+    if !ctx.make_room(ctx.array_size(class, new_length as u32))? {
+        return Ok(());
+    }
+    ctx.pop();
+    let copied = (length - from as u32).min(new_length as u32);
+    let copy = ctx.new_array(class, new_length as u32);
+    let bytes = ctx.ref_bytes(array.offset(ARRAY_DATA_OFFSET + from as u32 * element_size), (copied * element_size) as usize).unwrap().to_vec();
+    ctx.ref_bytes_mut(copy.offset(ARRAY_DATA_OFFSET), bytes.len()).unwrap().copy_from_slice(&bytes);
+    ctx.push(copy);
+    Ok(())
+}
+/// `copyOf`, which pads with zeros or nulls
+fn copy_of(ctx: &mut RuntimeCtx, descriptor: &str, element_size: u32) -> Result<()> {
+    let new_length = ctx.pop().into_i32();
+    if ctx.top() == Value::NULL {
+        ctx.pop();
+        return ctx.throw_null_pointer();
+    }
+    if new_length < 0 {
+        ctx.pop();
+        return ctx.throw_new("java/lang/NegativeArraySizeException", Some(new_length.to_string()));
+    }
+    copy_range(ctx, descriptor, element_size, 0, new_length)
+}
+fn copy_of_range(ctx: &mut RuntimeCtx, descriptor: &str, element_size: u32) -> Result<()> {
+    let to = ctx.pop().into_i32();
+    let from = ctx.pop().into_i32();
+    if ctx.top() == Value::NULL {
+        ctx.pop();
+        return ctx.throw_null_pointer();
+    }
+    if from > to {
+        ctx.pop();
+        return ctx.throw_new("java/lang/IllegalArgumentException", Some(format!("{from} > {to}")));
+    }
+    copy_range(ctx, descriptor, element_size, from, to.wrapping_sub(from))
+}
+fn primitive_copy_of<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    copy_of(ctx, T::DESCRIPTOR, size_of::<T>() as u32)
+}
+fn primitive_copy_of_range<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    copy_of_range(ctx, T::DESCRIPTOR, size_of::<T>() as u32)
+}
+fn object_copy_of(ctx: &mut RuntimeCtx) -> Result<()> {
+    copy_of(ctx, "L", 4)
+}
+fn object_copy_of_range(ctx: &mut RuntimeCtx) -> Result<()> {
+    copy_of_range(ctx, "L", 4)
+}
+
+/// `String.valueOf` of every element of the object array at the top of the stack, `None` if a
+/// `toString` threw
+fn element_strings(ctx: &mut RuntimeCtx) -> Result<Option<Vec<String>>> {
+    let array = ctx.top();
+    let mut strings = Vec::new();
+    for i in 0..length(ctx, array) {
+        let Some(chars) = ctx.string_value_of(element(ctx, array, i))? else { return Ok(None) };
+        strings.push(String::from_utf16_lossy(&chars));
+    }
+    Ok(Some(strings))
+}
+fn object_to_string(ctx: &mut RuntimeCtx) -> Result<()> {
+    if ctx.top() == Value::NULL {
+        ctx.pop();
+        return ctx.push_string("null");
+    }
+    let Some(strings) = element_strings(ctx)? else { return Ok(()) };
+    ctx.pop();
+    ctx.push_string(&bracketed(strings))
+}
+/// The component type of an object that is an array
+fn component(ctx: &RuntimeCtx, obj: Value) -> Option<ArrayComponent> {
+    (obj != Value::NULL).then(|| ctx.runtime.get_class(ctx.get_class_id(obj)).array_component).flatten()
+}
+/// Appends `deepToString` of the array at the top of the stack, which the arrays it is in are
+/// below, returning `false` if a `toString` threw
+fn deep_to_string(ctx: &mut RuntimeCtx, text: &mut String, outer: &mut Vec<Value>) -> Result<bool> {
+    let array = ctx.top();
+    outer.push(array);
+    text.push('[');
+    for i in 0..length(ctx, array) {
+        if i > 0 {
+            text.push_str(", ");
+        }
+        let element = element(ctx, array, i);
+        match component(ctx, element) {
+            _ if outer.contains(&element) && element != Value::NULL => text.push_str("[...]"),
+            Some(ArrayComponent::Reference(_)) => {
+                ctx.push(element);
+                if !deep_to_string(ctx, text, outer)? {
+                    return Ok(false);
+                }
+                ctx.pop();
+            }
+            Some(ArrayComponent::Primitive(component)) => text.push_str(&primitive_array_text(ctx, element, component)),
+            None => {
+                let Some(chars) = ctx.string_value_of(element)? else { return Ok(false) };
+                text.push_str(&String::from_utf16_lossy(&chars));
+            }
+        }
+    }
+    text.push(']');
+    outer.pop();
+    Ok(true)
+}
+fn object_deep_to_string(ctx: &mut RuntimeCtx) -> Result<()> {
+    if ctx.top() == Value::NULL {
+        ctx.pop();
+        return ctx.push_string("null");
+    }
+    let mut text = String::new();
+    if deep_to_string(ctx, &mut text, &mut Vec::new())? {
+        ctx.pop();
+        ctx.push_string(&text)?;
+    }
+    Ok(())
+}
+/// `Objects.equals` of two objects, `None` if `equals` threw
+pub(super) fn objects_equal(ctx: &mut RuntimeCtx, a: Value, b: Value) -> Result<Option<bool>> {
+    if a == b {
+        return Ok(Some(true));
+    }
+    if a == Value::NULL {
+        return Ok(Some(false));
+    }
+    ctx.push(a);
+    ctx.push(b);
+    if !ctx.call_virtual(a, "equals", &descriptor("(Ljava/lang/Object;)Z"))? {
+        return Ok(None);
+    }
+    Ok(Some(ctx.pop().into_u32() != 0))
+}
+fn object_equals(ctx: &mut RuntimeCtx) -> Result<()> {
+    let (a, b) = (ctx.peek(1), ctx.peek(0));
+    let mut equal = a == b || a != Value::NULL && b != Value::NULL && length(ctx, a) == length(ctx, b);
+    if a != b && equal {
+        for i in 0..length(ctx, a) {
+            let Some(equal_element) = objects_equal(ctx, element(ctx, a, i), element(ctx, b, i))? else { return Ok(()) };
+            if !equal_element {
+                equal = false;
+                break;
+            }
+        }
+    }
+    ctx.pop2();
+    ctx.push(equal);
+    Ok(())
+}
+/// `deepEquals` of the two object arrays at the top of the stack, which it pops, `None` if an
+/// `equals` threw
+fn deep_equals(ctx: &mut RuntimeCtx) -> Result<Option<bool>> {
+    let (a, b) = (ctx.peek(1), ctx.peek(0));
+    let mut equal = a == b || a != Value::NULL && b != Value::NULL && length(ctx, a) == length(ctx, b);
+    if a != b && equal {
+        for i in 0..length(ctx, a) {
+            let (x, y) = (element(ctx, a, i), element(ctx, b, i));
+            let equal_element = match (component(ctx, x), component(ctx, y)) {
+                _ if x == y => true,
+                _ if x == Value::NULL => false,
+                (Some(ArrayComponent::Reference(_)), Some(ArrayComponent::Reference(_))) => {
+                    ctx.push(x);
+                    ctx.push(y);
+                    let Some(equal) = deep_equals(ctx)? else { return Ok(None) };
+                    equal
+                }
+                (Some(ArrayComponent::Primitive(x_component)), Some(ArrayComponent::Primitive(y_component)))
+                    if x_component == y_component => primitive_arrays_equal(ctx, x, y, x_component),
+                _ => {
+                    let Some(equal) = objects_equal(ctx, x, y)? else { return Ok(None) };
+                    equal
+                }
+            };
+            if !equal_element {
+                equal = false;
+                break;
+            }
+        }
+    }
+    ctx.pop2();
+    Ok(Some(equal))
+}
+fn object_deep_equals(ctx: &mut RuntimeCtx) -> Result<()> {
+    if let Some(equal) = deep_equals(ctx)? {
+        ctx.push(equal);
+    }
+    Ok(())
+}
+/// `Objects.hashCode` of an object, `None` if `hashCode` threw
+pub(super) fn object_hash(ctx: &mut RuntimeCtx, obj: Value) -> Result<Option<i32>> {
+    if obj == Value::NULL {
+        return Ok(Some(0));
+    }
+    ctx.push(obj);
+    if !ctx.call_virtual(obj, "hashCode", &descriptor("()I"))? {
+        return Ok(None);
+    }
+    Ok(Some(ctx.pop().into_i32()))
+}
+/// `Arrays.hashCode` of the object array at the top of the stack, which it pops, `None` if a
+/// `hashCode` threw
+pub(super) fn object_array_hash(ctx: &mut RuntimeCtx) -> Result<Option<i32>> {
+    let array = ctx.top();
+    if array == Value::NULL {
+        ctx.pop();
+        return Ok(Some(0));
+    }
+    let mut hash = 1i32;
+    for i in 0..length(ctx, array) {
+        let Some(element_hash) = object_hash(ctx, element(ctx, array, i))? else { return Ok(None) };
+        hash = hash.wrapping_mul(31).wrapping_add(element_hash);
+    }
+    ctx.pop();
+    Ok(Some(hash))
+}
+fn object_hash_code(ctx: &mut RuntimeCtx) -> Result<()> {
+    if let Some(hash) = object_array_hash(ctx)? {
+        ctx.push(hash);
+    }
+    Ok(())
+}
+/// Stores an object in every element of a range of the array below it, throwing an
+/// `ArrayStoreException` if the array cannot hold it
+fn fill_objects(ctx: &mut RuntimeCtx, array: Value, from: u32, to: u32, value: Value) -> Result<()> {
+    if value != Value::NULL {
+        let Some(ArrayComponent::Reference(component)) = ctx.runtime.get_class(ctx.get_class_id(array)).array_component else { unreachable!() };
+        if !ctx.is_instance_of(value, component) {
+            let message = ctx.get_class_name(value).replace('/', ".");
+            return ctx.throw_new("java/lang/ArrayStoreException", Some(message));
+        }
+    }
+    for i in from..to {
+        set_element(ctx, array, i, value);
+    }
+    Ok(())
+}
+fn object_fill(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = ctx.pop();
+    let array = ctx.pop();
+    if array == Value::NULL {
+        return ctx.throw_null_pointer();
+    }
+    fill_objects(ctx, array, 0, length(ctx, array), value)
+}
+fn object_fill_range(ctx: &mut RuntimeCtx) -> Result<()> {
+    if !check_range(ctx, 1)? {
+        return Ok(());
+    }
+    let (value, to, from, array) = (ctx.pop(), ctx.pop().into_i32(), ctx.pop().into_i32(), ctx.pop());
+    fill_objects(ctx, array, from as u32, to as u32, value)
+}
+
+/// A stable merge sort with a comparison that can fail, which stops the sort and returns `false`
+fn merge_sort(items: &mut [u32], mut compare: impl FnMut(u32, u32) -> Result<Option<Ordering>>) -> Result<bool> {
+    let n = items.len();
+    let mut merged = Vec::with_capacity(n);
+    let mut width = 1;
+    while width < n {
+        merged.clear();
+        for start in (0..n).step_by(2 * width) {
+            let (middle, end) = ((start + width).min(n), (start + 2 * width).min(n));
+            let (mut i, mut j) = (start, middle);
+            while i < middle && j < end {
+                let Some(order) = compare(items[j], items[i])? else { return Ok(false) };
+                // an element of the right run goes first only if it is less, to keep equal ones in order
+                if order == Ordering::Less {
+                    merged.push(items[j]);
+                    j += 1;
+                } else {
+                    merged.push(items[i]);
+                    i += 1;
+                }
+            }
+            merged.extend_from_slice(&items[i..middle]);
+            merged.extend_from_slice(&items[j..end]);
+        }
+        items.copy_from_slice(&merged);
+        width *= 2;
+    }
+    Ok(true)
+}
+/// Compares two elements of an array with a comparator, or by their natural order if it is null,
+/// `None` if the comparison threw
+fn compare_elements(ctx: &mut RuntimeCtx, array: Value, comparator: Value, a: u32, b: u32) -> Result<Option<Ordering>> {
+    let (a, b) = (element(ctx, array, a), element(ctx, array, b));
+    compare_objects(ctx, comparator, a, b)
+}
+/// Compares two objects with a comparator, or by their natural order if it is null, `None` if the
+/// comparison threw
+fn compare_objects(ctx: &mut RuntimeCtx, comparator: Value, a: Value, b: Value) -> Result<Option<Ordering>> {
+    let called = if comparator != Value::NULL {
+        ctx.push(comparator);
+        ctx.push(a);
+        ctx.push(b);
+        ctx.call_virtual(comparator, "compare", &descriptor("(Ljava/lang/Object;Ljava/lang/Object;)I"))?
+    } else {
+        if a == Value::NULL {
+            ctx.throw_null_pointer()?;
+            return Ok(None);
+        }
+        let comparable = ctx.runtime.load_class("java/lang/Comparable")?;
+        if !ctx.is_instance_of(a, comparable) {
+            let message = format!("class {} cannot be cast to class java.lang.Comparable", ctx.get_class_name(a).replace('/', "."));
+            ctx.throw_new("java/lang/ClassCastException", Some(message))?;
+            return Ok(None);
+        }
+        ctx.push(a);
+        ctx.push(b);
+        ctx.call_virtual(a, "compareTo", &descriptor("(Ljava/lang/Object;)I"))?
+    };
+    Ok(called.then(|| ctx.pop().into_i32().cmp(&0)))
+}
+/// Sorts a range of the array below the comparator at the top of the stack, and pops both
+fn sort_objects(ctx: &mut RuntimeCtx, from: u32, to: u32) -> Result<()> {
+    let (array, comparator) = (ctx.peek(1), ctx.peek(0));
+    let mut order: Vec<u32> = (from..to).collect();
+    if !merge_sort(&mut order, |a, b| compare_elements(ctx, array, comparator, a, b))? {
+        return Ok(());
+    }
+    let sorted: Vec<Value> = order.iter().map(|&i| element(ctx, array, i)).collect();
+    for (i, value) in (from..).zip(sorted) {
+        set_element(ctx, array, i, value);
+    }
+    ctx.pop2();
+    Ok(())
+}
+/// `sort(Object[])` and `sort(Object[], Comparator)`
+fn object_sort(ctx: &mut RuntimeCtx, comparator: Value) -> Result<()> {
+    if ctx.top() == Value::NULL {
+        ctx.pop();
+        return ctx.throw_null_pointer();
+    }
+    let length = length(ctx, ctx.top());
+    ctx.push(comparator);
+    sort_objects(ctx, 0, length)
+}
+fn object_sort_natural(ctx: &mut RuntimeCtx) -> Result<()> {
+    object_sort(ctx, Value::NULL)
+}
+fn object_sort_comparator(ctx: &mut RuntimeCtx) -> Result<()> {
+    // the comparator is popped and pushed again, nothing runs in between
+    let comparator = ctx.pop();
+    object_sort(ctx, comparator)
+}
+/// `sort(Object[], int, int)` and `sort(Object[], int, int, Comparator)`
+fn object_sort_range(ctx: &mut RuntimeCtx, comparator: Value) -> Result<()> {
+    if !check_range(ctx, 0)? {
+        return Ok(());
+    }
+    let (to, from) = (ctx.pop().into_i32(), ctx.pop().into_i32());
+    ctx.push(comparator);
+    sort_objects(ctx, from as u32, to as u32)
+}
+fn object_sort_range_natural(ctx: &mut RuntimeCtx) -> Result<()> {
+    object_sort_range(ctx, Value::NULL)
+}
+fn object_sort_range_comparator(ctx: &mut RuntimeCtx) -> Result<()> {
+    let comparator = ctx.pop();
+    object_sort_range(ctx, comparator)
+}
+/// `binarySearch(Object[], Object)` and `binarySearch(Object[], Object, Comparator)` with the
+/// array, the key and the comparator on the stack, where they stay while the comparisons run
+fn object_binary_search(ctx: &mut RuntimeCtx) -> Result<()> {
+    if ctx.peek(2) == Value::NULL {
+        ctx.pop_n(3);
+        return ctx.throw_null_pointer();
+    }
+    let (mut low, mut high) = (0, length(ctx, ctx.peek(2)) as i32 - 1);
+    while low <= high {
+        let middle = (low + high) / 2;
+        let (array, key, comparator) = (ctx.peek(2), ctx.peek(1), ctx.peek(0));
+        let value = element(ctx, array, middle as u32);
+        let Some(order) = compare_objects(ctx, comparator, value, key)? else { return Ok(()) };
+        match order {
+            Ordering::Less => low = middle + 1,
+            Ordering::Greater => high = middle - 1,
+            Ordering::Equal => {
+                ctx.pop_n(3);
+                ctx.push(middle);
+                return Ok(());
+            }
+        }
+    }
+    ctx.pop_n(3);
+    ctx.push(-(low + 1));
+    Ok(())
+}
+fn object_binary_search_natural(ctx: &mut RuntimeCtx) -> Result<()> {
+    ctx.push(Value::NULL);
+    object_binary_search(ctx)
+}
+
+fn add_primitive_members<T: Primitive>(members: &mut Members) {
+    let d = T::DESCRIPTOR;
+    members
+        .method("toString", &format!("([{d})Ljava/lang/String;"), to_string::<T>)
+        .method("equals", &format!("([{d}[{d})Z"), equals::<T>)
+        .method("hashCode", &format!("([{d})I"), hash_code::<T>)
+        .method("fill", &format!("([{d}{d})V"), fill::<T>)
+        .method("fill", &format!("([{d}II{d})V"), fill_range::<T>)
+        .method("copyOf", &format!("([{d}I)[{d}"), primitive_copy_of::<T>)
+        .method("copyOfRange", &format!("([{d}II)[{d}"), primitive_copy_of_range::<T>);
+    if d != "Z" {
+        members
+            .method("sort", &format!("([{d})V"), sort::<T>)
+            .method("sort", &format!("([{d}II)V"), sort_range::<T>)
+            .method("binarySearch", &format!("([{d}{d})I"), binary_search::<T>);
+    }
+}
+
+/// The methods of `Arrays`
+pub(super) fn members() -> Members {
+    let mut members = Members::new();
+    add_primitive_members::<bool>(&mut members);
+    add_primitive_members::<i8>(&mut members);
+    add_primitive_members::<u16>(&mut members);
+    add_primitive_members::<i16>(&mut members);
+    add_primitive_members::<i32>(&mut members);
+    add_primitive_members::<i64>(&mut members);
+    add_primitive_members::<f32>(&mut members);
+    add_primitive_members::<f64>(&mut members);
+    const ARRAY: &str = "[Ljava/lang/Object;";
+    members
+        .method("toString", &format!("({ARRAY})Ljava/lang/String;"), object_to_string)
+        .method("deepToString", &format!("({ARRAY})Ljava/lang/String;"), object_deep_to_string)
+        .method("equals", &format!("({ARRAY}{ARRAY})Z"), object_equals)
+        .method("deepEquals", &format!("({ARRAY}{ARRAY})Z"), object_deep_equals)
+        .method("hashCode", &format!("({ARRAY})I"), object_hash_code)
+        .method("fill", &format!("({ARRAY}Ljava/lang/Object;)V"), object_fill)
+        .method("fill", &format!("({ARRAY}IILjava/lang/Object;)V"), object_fill_range)
+        .method("copyOf", &format!("({ARRAY}I){ARRAY}"), object_copy_of)
+        .method("copyOfRange", &format!("({ARRAY}II){ARRAY}"), object_copy_of_range)
+        .method("sort", &format!("({ARRAY})V"), object_sort_natural)
+        .method("sort", &format!("({ARRAY}II)V"), object_sort_range_natural)
+        .method("sort", &format!("({ARRAY}Ljava/util/Comparator;)V"), object_sort_comparator)
+        .method("sort", &format!("({ARRAY}IILjava/util/Comparator;)V"), object_sort_range_comparator)
+        .method("binarySearch", &format!("({ARRAY}Ljava/lang/Object;)I"), object_binary_search_natural)
+        .method("binarySearch", &format!("({ARRAY}Ljava/lang/Object;Ljava/util/Comparator;)I"), object_binary_search);
+    members
+}
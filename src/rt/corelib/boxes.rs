@@ -0,0 +1,1005 @@
+//! `Number` and the boxes of the primitive types.
+//!
+//! A box holds its value right after the object header, in a field named `value` like in the JDK.
+//! `valueOf` hands out the same box for the values the JDK caches: `Boolean.TRUE` and `FALSE`,
+//! the characters up to 127 and the integers from -128 to 127. Those are static objects made along
+//! with the class and kept in its static fields.
+
+use crate::{class::ClassAccess, descriptor::FieldDescriptor};
+
+use super::{character, parse::{self, Floating, NumberFormatError}, Members, Result, Runtime, RuntimeCtx, Value, OBJECT_CLASS};
+use super::super::{arith::Number, embed, f64_into_values, i64_into_values, native::JValue, string, values_into_f64, values_into_u64, Bytes32Aligned, LoadedClass, OBJECT_HEADER_SIZE};
+
+/// Offset of the value in a box
+const BOX_VALUE_OFFSET: u32 = OBJECT_HEADER_SIZE as u32;
+
+/// A primitive type and what its box class does with it
+pub(super) trait Primitive: Copy {
+    const BOX: &'static str;
+    const DESCRIPTOR: &'static str;
+    /// Whether the box class extends `Number`
+    const NUMERIC: bool;
+    /// How many boxes `valueOf` hands out again and again
+    const CACHED: usize;
+    /// Where the box of the value is in the cache, if it is
+    fn cache_index(self) -> Option<usize>;
+    /// The value the box at a place in the cache holds
+    fn cached(index: usize) -> Self;
+    fn field_type() -> FieldDescriptor;
+    fn into_jvalue(self) -> JValue;
+    fn from_jvalue(value: JValue) -> Self;
+    fn pop(ctx: &mut RuntimeCtx) -> Self;
+    fn push(self, ctx: &mut RuntimeCtx);
+    /// `toString`
+    fn text(self) -> String;
+    /// `hashCode`
+    fn hash(self) -> i32;
+    /// `compare`, which `equals` agrees with
+    fn compare(self, other: Self) -> i32;
+    /// Adds the members only this box class has
+    fn add_members(members: &mut Members);
+}
+
+/// A primitive type of the `Number` boxes
+pub(super) trait Numeric: Primitive {
+    fn number(self) -> Number;
+}
+
+/// `byte`, `short`, `int` and `long`
+trait Integral: Numeric {
+    const BITS: u32;
+    fn to_i64(self) -> i64;
+    /// Truncates to the type like a primitive conversion
+    fn from_i64(value: i64) -> Self;
+    /// The bits of the value, zero extended
+    fn unsigned(self) -> u64 {
+        self.to_i64() as u64 & u64::MAX >> (64 - Self::BITS)
+    }
+}
+
+/// Reads the value of a box
+pub(super) fn unbox<T: Primitive>(ctx: &RuntimeCtx, boxed: Value) -> T {
+    let bytes = ctx.ref_bytes(boxed.offset(BOX_VALUE_OFFSET), size_of::<T>()).unwrap();
+    T::from_jvalue(embed::read_field(bytes, &T::field_type()))
+}
+
+/// `valueOf`: the cached box of the value or a new one, `None` if there is no room for a new one
+/// and an `OutOfMemoryError` is thrown
+pub(super) fn box_value<T: Primitive>(ctx: &mut RuntimeCtx, value: T) -> Result<Option<Value>> {
+    let class = ctx.runtime.load_class(T::BOX)?;
+    if let Some(i) = value.cache_index() {
+        return Ok(Some(Value(ctx.runtime.get_class(class).static_fields.as_u32_slice()[i])));
+    }
+    if !ctx.make_room(ctx.runtime.get_class(class).get_aligned_data_size() as usize)? {
+        return Ok(None);
+    }
+    let boxed = ctx.new_object(class);
+    let bytes = embed::field_value_bytes(value.into_jvalue());
+    ctx.ref_bytes_mut(boxed.offset(BOX_VALUE_OFFSET), bytes.len()).unwrap().copy_from_slice(&bytes);
+    Ok(Some(boxed))
+}
+
+impl Runtime {
+    /// Loads the box class of a primitive type, with the boxes it caches
+    pub(super) fn load_box_class<T: Primitive>(&mut self) -> Result<LoadedClass> {
+        let super_class = if T::NUMERIC { self.load_class("java/lang/Number")? } else { OBJECT_CLASS };
+        let mut interfaces = vec![self.load_class("java/lang/Comparable")?];
+        if !T::NUMERIC {
+            interfaces.push(self.load_class("java/io/Serializable")?);
+        }
+        let d = T::DESCRIPTOR;
+        let this = format!("L{};", T::BOX);
+        let mut members = Members::new();
+        members
+            .field("value", d, BOX_VALUE_OFFSET as u16)
+            .method("<init>", &format!("({d})V"), box_init::<T>)
+            .method("valueOf", &format!("({d}){this}"), value_of::<T>)
+            .method("toString", "()Ljava/lang/String;", box_to_string::<T>)
+            .method("toString", &format!("({d})Ljava/lang/String;"), to_string::<T>)
+            .method("hashCode", "()I", box_hash_code::<T>)
+            .method("hashCode", &format!("({d})I"), hash_code::<T>)
+            .method("equals", "(Ljava/lang/Object;)Z", box_equals::<T>)
+            .method("compareTo", &format!("({this})I"), box_compare_to::<T>)
+            .method("compareTo", "(Ljava/lang/Object;)I", box_compare_to::<T>)
+            .method("compare", &format!("({d}{d})I"), compare::<T>);
+        T::add_members(&mut members);
+        // the class gets the next id, its cached boxes are made before it is added
+        let class = self.classes.len() as u32;
+        let mut static_fields = Bytes32Aligned::new_zeroed(4 * T::CACHED);
+        for (i, field) in static_fields.as_u32_slice_mut().iter_mut().enumerate() {
+            let bytes = embed::field_value_bytes(T::cached(i).into_jvalue());
+            *field = Value::new_ref_static(self.new_static_obj(class, &bytes)).into_u32();
+        }
+        Ok(LoadedClass {
+            access_flags: ClassAccess::PUBLIC | ClassAccess::FINAL,
+            interfaces: interfaces.into(),
+            static_fields,
+            data_size: OBJECT_HEADER_SIZE + size_of::<T>() as u16,
+            ..members.into_class(T::BOX, super_class)
+        })
+    }
+}
+
+fn box_init<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = T::pop(ctx);
+    let this = ctx.pop();
+    let bytes = embed::field_value_bytes(value.into_jvalue());
+    ctx.ref_bytes_mut(this.offset(BOX_VALUE_OFFSET), bytes.len()).unwrap().copy_from_slice(&bytes);
+    Ok(())
+}
+fn value_of<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = T::pop(ctx);
+    if let Some(boxed) = box_value(ctx, value)? {
+        ctx.push(boxed);
+    }
+    Ok(())
+}
+fn box_to_string<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    ctx.push_string(&unbox::<T>(ctx, this).text())
+}
+fn to_string<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = T::pop(ctx);
+    ctx.push_string(&value.text())
+}
+fn box_hash_code<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    ctx.push(unbox::<T>(ctx, this).hash());
+    Ok(())
+}
+fn hash_code<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = T::pop(ctx);
+    ctx.push(value.hash());
+    Ok(())
+}
+fn box_equals<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let obj = ctx.pop();
+    let this = ctx.pop();
+    let equal = obj != Value::NULL
+        && ctx.get_class_id(obj) == ctx.get_class_id(this)
+        && unbox::<T>(ctx, this).compare(unbox(ctx, obj)) == 0;
+    ctx.push(equal);
+    Ok(())
+}
+/// `compareTo`, also as the bridge method that takes any object
+fn box_compare_to<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let other = ctx.pop();
+    let this = ctx.pop();
+    if other == Value::NULL {
+        return ctx.throw_null_pointer();
+    }
+    if ctx.get_class_id(other) != ctx.get_class_id(this) {
+        let message = format!("class {} cannot be cast to class {}", ctx.get_class_name(other).replace('/', "."), T::BOX.replace('/', "."));
+        return ctx.throw_new("java/lang/ClassCastException", Some(message));
+    }
+    ctx.push(unbox::<T>(ctx, this).compare(unbox(ctx, other)));
+    Ok(())
+}
+fn compare<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let y = T::pop(ctx);
+    let x = T::pop(ctx);
+    ctx.push(x.compare(y));
+    Ok(())
+}
+
+/// The result of `compare` for types that only tell the order
+fn signum(ordering: std::cmp::Ordering) -> i32 {
+    ordering as i32
+}
+
+impl Primitive for bool {
+    const BOX: &'static str = "java/lang/Boolean";
+    const DESCRIPTOR: &'static str = "Z";
+    const NUMERIC: bool = false;
+    /// `Boolean.TRUE` and `Boolean.FALSE`, in that order
+    const CACHED: usize = 2;
+    fn cache_index(self) -> Option<usize> {
+        Some(!self as usize)
+    }
+    fn cached(index: usize) -> Self {
+        index == 0
+    }
+    fn field_type() -> FieldDescriptor {
+        FieldDescriptor::Boolean
+    }
+    fn into_jvalue(self) -> JValue {
+        JValue::Boolean(self)
+    }
+    fn from_jvalue(value: JValue) -> Self {
+        let JValue::Boolean(value) = value else { unreachable!("{value:?} is not a boolean") };
+        value
+    }
+    fn pop(ctx: &mut RuntimeCtx) -> Self {
+        ctx.pop().into_u32() != 0
+    }
+    fn push(self, ctx: &mut RuntimeCtx) {
+        ctx.push(self);
+    }
+    fn text(self) -> String {
+        self.to_string()
+    }
+    fn hash(self) -> i32 {
+        if self { 1231 } else { 1237 }
+    }
+    fn compare(self, other: Self) -> i32 {
+        signum(self.cmp(&other))
+    }
+    fn add_members(members: &mut Members) {
+        members
+            .field("TRUE", "Ljava/lang/Boolean;", 0)
+            .field("FALSE", "Ljava/lang/Boolean;", 4)
+            .method("booleanValue", "()Z", unbox_value::<bool>)
+            .method("parseBoolean", "(Ljava/lang/String;)Z", boolean_parse)
+            .method("valueOf", "(Ljava/lang/String;)Ljava/lang/Boolean;", boolean_value_of_string)
+            .method("logicalAnd", "(ZZ)Z", boolean_logical_and)
+            .method("logicalOr", "(ZZ)Z", boolean_logical_or)
+            .method("logicalXor", "(ZZ)Z", boolean_logical_xor);
+    }
+}
+
+impl Primitive for u16 {
+    const BOX: &'static str = "java/lang/Character";
+    const DESCRIPTOR: &'static str = "C";
+    const NUMERIC: bool = false;
+    const CACHED: usize = 128;
+    fn cache_index(self) -> Option<usize> {
+        (self < 128).then_some(self as usize)
+    }
+    fn cached(index: usize) -> Self {
+        index as u16
+    }
+    fn field_type() -> FieldDescriptor {
+        FieldDescriptor::Char
+    }
+    fn into_jvalue(self) -> JValue {
+        JValue::Char(self)
+    }
+    fn from_jvalue(value: JValue) -> Self {
+        let JValue::Char(value) = value else { unreachable!("{value:?} is not a char") };
+        value
+    }
+    fn pop(ctx: &mut RuntimeCtx) -> Self {
+        ctx.pop().into_u16()
+    }
+    fn push(self, ctx: &mut RuntimeCtx) {
+        ctx.push(self as i32);
+    }
+    fn text(self) -> String {
+        String::from_utf16_lossy(&[self])
+    }
+    fn hash(self) -> i32 {
+        self as i32
+    }
+    fn compare(self, other: Self) -> i32 {
+        self as i32 - other as i32
+    }
+    fn add_members(members: &mut Members) {
+        members.method("charValue", "()C", unbox_value::<u16>);
+        character::add_members(members);
+    }
+}
+
+macro_rules! integral_primitives {
+    ($($type:ty: $box:literal, $descriptor:literal, $variant:ident, $field_type:ident;)*) => {$(
+        impl Numeric for $type {
+            fn number(self) -> Number {
+                if <$type>::BITS == 64 { Number::Long(self as i64) } else { Number::Int(self as i32) }
+            }
+        }
+        impl Integral for $type {
+            const BITS: u32 = <$type>::BITS;
+            fn to_i64(self) -> i64 {
+                self as i64
+            }
+            fn from_i64(value: i64) -> Self {
+                value as $type
+            }
+        }
+        impl Primitive for $type {
+            const BOX: &'static str = $box;
+            const DESCRIPTOR: &'static str = $descriptor;
+            const NUMERIC: bool = true;
+            /// -128 to 127
+            const CACHED: usize = 256;
+            fn cache_index(self) -> Option<usize> {
+                (-128..128).contains(&(self as i64)).then(|| (self as i64 + 128) as usize)
+            }
+            fn cached(index: usize) -> Self {
+                (index as i64 - 128) as $type
+            }
+            fn field_type() -> FieldDescriptor {
+                FieldDescriptor::$field_type
+            }
+            fn into_jvalue(self) -> JValue {
+                JValue::$variant(self)
+            }
+            fn from_jvalue(value: JValue) -> Self {
+                let JValue::$variant(value) = value else { unreachable!("{value:?} is not a {}", stringify!($type)) };
+                value
+            }
+            fn pop(ctx: &mut RuntimeCtx) -> Self {
+                if <$type>::BITS == 64 {
+                    values_into_u64(ctx.pop2()) as $type
+                } else {
+                    ctx.pop().into_i32() as $type
+                }
+            }
+            fn push(self, ctx: &mut RuntimeCtx) {
+                if <$type>::BITS == 64 {
+                    ctx.push2(i64_into_values(self as i64));
+                } else {
+                    ctx.push(self as i32);
+                }
+            }
+            fn text(self) -> String {
+                self.to_string()
+            }
+            fn hash(self) -> i32 {
+                if <$type>::BITS == 64 { (self as i64 ^ (self as i64 >> 32)) as i32 } else { self as i32 }
+            }
+            fn compare(self, other: Self) -> i32 {
+                if <$type>::BITS < 32 {
+                    self as i32 - other as i32
+                } else {
+                    signum(self.cmp(&other))
+                }
+            }
+            fn add_members(members: &mut Members) {
+                add_integral_members::<$type>(members);
+            }
+        }
+    )*};
+}
+integral_primitives! {
+    i8: "java/lang/Byte", "B", Byte, Byte;
+    i16: "java/lang/Short", "S", Short, Short;
+    i32: "java/lang/Integer", "I", Int, Int;
+    i64: "java/lang/Long", "J", Long, Long;
+}
+
+impl Numeric for f32 {
+    fn number(self) -> Number {
+        Number::Float(self)
+    }
+}
+impl Primitive for f32 {
+    const BOX: &'static str = "java/lang/Float";
+    const DESCRIPTOR: &'static str = "F";
+    const NUMERIC: bool = true;
+    const CACHED: usize = 0;
+    fn cache_index(self) -> Option<usize> {
+        None
+    }
+    fn cached(_: usize) -> Self {
+        unreachable!("floats are not cached")
+    }
+    fn field_type() -> FieldDescriptor {
+        FieldDescriptor::Float
+    }
+    fn into_jvalue(self) -> JValue {
+        JValue::Float(self)
+    }
+    fn from_jvalue(value: JValue) -> Self {
+        let JValue::Float(value) = value else { unreachable!("{value:?} is not a float") };
+        value
+    }
+    fn pop(ctx: &mut RuntimeCtx) -> Self {
+        ctx.pop().into_f32()
+    }
+    fn push(self, ctx: &mut RuntimeCtx) {
+        ctx.push(self);
+    }
+    fn text(self) -> String {
+        string::float_to_string(self)
+    }
+    fn hash(self) -> i32 {
+        float_to_int_bits(self)
+    }
+    fn compare(self, other: Self) -> i32 {
+        signum(self.canonical().total_cmp(&other.canonical()))
+    }
+    fn add_members(members: &mut Members) {
+        add_floating_members::<f32>(members);
+        members
+            .method("floatToIntBits", "(F)I", float_to_int_bits_method)
+            .method("floatToRawIntBits", "(F)I", float_to_raw_int_bits)
+            .method("intBitsToFloat", "(I)F", int_bits_to_float)
+            .method("toHexString", "(F)Ljava/lang/String;", float_to_hex_string);
+    }
+}
+
+impl Numeric for f64 {
+    fn number(self) -> Number {
+        Number::Double(self)
+    }
+}
+impl Primitive for f64 {
+    const BOX: &'static str = "java/lang/Double";
+    const DESCRIPTOR: &'static str = "D";
+    const NUMERIC: bool = true;
+    const CACHED: usize = 0;
+    fn cache_index(self) -> Option<usize> {
+        None
+    }
+    fn cached(_: usize) -> Self {
+        unreachable!("doubles are not cached")
+    }
+    fn field_type() -> FieldDescriptor {
+        FieldDescriptor::Double
+    }
+    fn into_jvalue(self) -> JValue {
+        JValue::Double(self)
+    }
+    fn from_jvalue(value: JValue) -> Self {
+        let JValue::Double(value) = value else { unreachable!("{value:?} is not a double") };
+        value
+    }
+    fn pop(ctx: &mut RuntimeCtx) -> Self {
+        values_into_f64(ctx.pop2())
+    }
+    fn push(self, ctx: &mut RuntimeCtx) {
+        ctx.push2(f64_into_values(self));
+    }
+    fn text(self) -> String {
+        string::double_to_string(self)
+    }
+    fn hash(self) -> i32 {
+        let bits = double_to_long_bits(self);
+        (bits ^ (bits >> 32)) as i32
+    }
+    fn compare(self, other: Self) -> i32 {
+        signum(self.canonical().total_cmp(&other.canonical()))
+    }
+    fn add_members(members: &mut Members) {
+        add_floating_members::<f64>(members);
+        members
+            .method("doubleToLongBits", "(D)J", double_to_long_bits_method)
+            .method("doubleToRawLongBits", "(D)J", double_to_raw_long_bits)
+            .method("longBitsToDouble", "(J)D", long_bits_to_double)
+            .method("toHexString", "(D)Ljava/lang/String;", double_to_hex_string);
+    }
+}
+
+/// `float` and `double`
+pub(super) trait FloatingPoint: Numeric + PartialOrd + std::ops::Add<Output = Self> {
+    /// The name in `parseDouble` and the like
+    const NAME: &'static str;
+    /// The value with NaN as the one NaN `Double.doubleToLongBits` gives
+    fn canonical(self) -> Self;
+    fn from_parsed(parsed: &Floating) -> Self;
+    fn is_nan(self) -> bool;
+    fn is_infinite(self) -> bool;
+    fn is_finite(self) -> bool;
+    fn is_zero(self) -> bool;
+    fn is_sign_negative(self) -> bool;
+}
+
+macro_rules! floating_points {
+    ($($type:ty: $name:literal, $convert:ident;)*) => {$(
+        impl FloatingPoint for $type {
+            const NAME: &'static str = $name;
+            fn canonical(self) -> Self {
+                if self.is_nan() { <$type>::NAN } else { self }
+            }
+            fn from_parsed(parsed: &Floating) -> Self {
+                parsed.$convert()
+            }
+            fn is_nan(self) -> bool {
+                <$type>::is_nan(self)
+            }
+            fn is_infinite(self) -> bool {
+                <$type>::is_infinite(self)
+            }
+            fn is_finite(self) -> bool {
+                <$type>::is_finite(self)
+            }
+            fn is_zero(self) -> bool {
+                self == 0.
+            }
+            fn is_sign_negative(self) -> bool {
+                <$type>::is_sign_negative(self)
+            }
+        }
+    )*};
+}
+floating_points! {
+    f32: "Float", to_f32;
+    f64: "Double", to_f64;
+}
+
+/// `Math.max` for floating point: NaN if either is NaN, and 0.0 is greater than -0.0
+pub(super) fn max<T: FloatingPoint>(a: T, b: T) -> T {
+    if a.is_nan() || a.is_zero() && b.is_zero() && a.is_sign_negative() {
+        return if a.is_nan() { a } else { b };
+    }
+    if a >= b { a } else { b }
+}
+/// `Math.min` for floating point: NaN if either is NaN, and -0.0 is less than 0.0
+pub(super) fn min<T: FloatingPoint>(a: T, b: T) -> T {
+    if a.is_nan() || a.is_zero() && b.is_zero() && b.is_sign_negative() {
+        return if a.is_nan() { a } else { b };
+    }
+    if a <= b { a } else { b }
+}
+
+pub(super) fn float_to_int_bits(value: f32) -> i32 {
+    value.canonical().to_bits() as i32
+}
+pub(super) fn double_to_long_bits(value: f64) -> i64 {
+    value.canonical().to_bits() as i64
+}
+
+/// `booleanValue` and `charValue`
+fn unbox_value<T: Primitive>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    unbox::<T>(ctx, this).push(ctx);
+    Ok(())
+}
+
+fn boolean_parse(ctx: &mut RuntimeCtx) -> Result<()> {
+    let string = ctx.pop_nullable_string();
+    ctx.push(string.is_some_and(|chars| String::from_utf16_lossy(&chars).eq_ignore_ascii_case("true")));
+    Ok(())
+}
+fn boolean_value_of_string(ctx: &mut RuntimeCtx) -> Result<()> {
+    boolean_parse(ctx)?;
+    value_of::<bool>(ctx)
+}
+fn boolean_logical_and(ctx: &mut RuntimeCtx) -> Result<()> {
+    let (b, a) = (bool::pop(ctx), bool::pop(ctx));
+    ctx.push(a & b);
+    Ok(())
+}
+fn boolean_logical_or(ctx: &mut RuntimeCtx) -> Result<()> {
+    let (b, a) = (bool::pop(ctx), bool::pop(ctx));
+    ctx.push(a | b);
+    Ok(())
+}
+fn boolean_logical_xor(ctx: &mut RuntimeCtx) -> Result<()> {
+    let (b, a) = (bool::pop(ctx), bool::pop(ctx));
+    ctx.push(a ^ b);
+    Ok(())
+}
+
+fn byte_value<T: Numeric>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    ctx.push(unbox::<T>(ctx, this).number().to_i32() as i8);
+    Ok(())
+}
+fn short_value<T: Numeric>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    ctx.push(unbox::<T>(ctx, this).number().to_i32() as i16);
+    Ok(())
+}
+fn int_value<T: Numeric>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    ctx.push(unbox::<T>(ctx, this).number().to_i32());
+    Ok(())
+}
+fn long_value<T: Numeric>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    ctx.push2(i64_into_values(unbox::<T>(ctx, this).number().to_i64()));
+    Ok(())
+}
+fn float_value<T: Numeric>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    ctx.push(unbox::<T>(ctx, this).number().to_f32());
+    Ok(())
+}
+fn double_value<T: Numeric>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    ctx.push2(f64_into_values(unbox::<T>(ctx, this).number().to_f64()));
+    Ok(())
+}
+/// The methods of `Number`, converting like the primitive conversions do
+fn add_number_members<T: Numeric>(members: &mut Members) {
+    members
+        .method("byteValue", "()B", byte_value::<T>)
+        .method("shortValue", "()S", short_value::<T>)
+        .method("intValue", "()I", int_value::<T>)
+        .method("longValue", "()J", long_value::<T>)
+        .method("floatValue", "()F", float_value::<T>)
+        .method("doubleValue", "()D", double_value::<T>);
+}
+
+fn throw_number_format(ctx: &mut RuntimeCtx, NumberFormatError(message): NumberFormatError) -> Result<()> {
+    ctx.throw_new("java/lang/NumberFormatException", Some(message))
+}
+/// Pops a string and parses it as a signed integer in a radix, throwing a
+/// `NumberFormatException` and returning `None` if it is not one
+fn parse_integral<T: Integral>(ctx: &mut RuntimeCtx, radix: i32) -> Result<Option<T>> {
+    let chars = ctx.pop_nullable_string();
+    let parsed = if T::BITS < 32 {
+        // parsed as an `int` first, a value beyond the type has its own message
+        parse::parse_integer(chars.as_deref(), radix, 32).and_then(|value| match T::from_i64(value).to_i64() == value {
+            true => Ok(value),
+            false => {
+                let text = String::from_utf16_lossy(chars.as_deref().unwrap());
+                Err(NumberFormatError(format!("Value out of range. Value:\"{text}\" Radix:{radix}")))
+            }
+        })
+    } else {
+        parse::parse_integer(chars.as_deref(), radix, T::BITS)
+    };
+    match parsed {
+        Ok(value) => Ok(Some(T::from_i64(value))),
+        Err(error) => throw_number_format(ctx, error).map(|()| None),
+    }
+}
+fn parse<T: Integral>(ctx: &mut RuntimeCtx) -> Result<()> {
+    if let Some(value) = parse_integral::<T>(ctx, 10)? {
+        value.push(ctx);
+    }
+    Ok(())
+}
+fn parse_radix<T: Integral>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let radix = ctx.pop().into_i32();
+    if let Some(value) = parse_integral::<T>(ctx, radix)? {
+        value.push(ctx);
+    }
+    Ok(())
+}
+fn value_of_string<T: Integral>(ctx: &mut RuntimeCtx) -> Result<()> {
+    if let Some(value) = parse_integral::<T>(ctx, 10)? && let Some(boxed) = box_value(ctx, value)? {
+        ctx.push(boxed);
+    }
+    Ok(())
+}
+fn value_of_string_radix<T: Integral>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let radix = ctx.pop().into_i32();
+    if let Some(value) = parse_integral::<T>(ctx, radix)? && let Some(boxed) = box_value(ctx, value)? {
+        ctx.push(boxed);
+    }
+    Ok(())
+}
+fn parse_unsigned_integral<T: Integral>(ctx: &mut RuntimeCtx, radix: i32) -> Result<()> {
+    let chars = ctx.pop_nullable_string();
+    match parse::parse_unsigned(chars.as_deref(), radix, T::BITS) {
+        Ok(value) => T::from_i64(value).push(ctx),
+        Err(error) => throw_number_format(ctx, error)?,
+    }
+    Ok(())
+}
+fn parse_unsigned<T: Integral>(ctx: &mut RuntimeCtx) -> Result<()> {
+    parse_unsigned_integral::<T>(ctx, 10)
+}
+fn parse_unsigned_radix<T: Integral>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let radix = ctx.pop().into_i32();
+    parse_unsigned_integral::<T>(ctx, radix)
+}
+
+/// The digits of a number in a radix, with lowercase letters beyond 9
+fn radix_digits(mut magnitude: u64, radix: u32) -> String {
+    let mut digits = Vec::new();
+    loop {
+        digits.push(char::from_digit((magnitude % radix as u64) as u32, radix).unwrap());
+        magnitude /= radix as u64;
+        if magnitude == 0 {
+            break;
+        }
+    }
+    digits.iter().rev().collect()
+}
+/// A radix argument, 10 if it is not one `Character.digit` takes
+fn pop_radix(ctx: &mut RuntimeCtx) -> u32 {
+    let radix = ctx.pop().into_i32();
+    if (parse::MIN_RADIX..=parse::MAX_RADIX).contains(&radix) { radix as u32 } else { 10 }
+}
+fn to_string_radix<T: Integral>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let radix = pop_radix(ctx);
+    let value = T::pop(ctx).to_i64();
+    let sign = if value < 0 { "-" } else { "" };
+    ctx.push_string(&format!("{sign}{}", radix_digits(value.unsigned_abs(), radix)))
+}
+fn to_unsigned_string_radix<T: Integral>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let radix = pop_radix(ctx);
+    let value = T::pop(ctx);
+    ctx.push_string(&radix_digits(value.unsigned(), radix))
+}
+macro_rules! unsigned_strings {
+    ($($name:ident => $radix:literal,)*) => {$(
+        fn $name<T: Integral>(ctx: &mut RuntimeCtx) -> Result<()> {
+            let value = T::pop(ctx);
+            ctx.push_string(&radix_digits(value.unsigned(), $radix))
+        }
+    )*};
+}
+unsigned_strings! {
+    to_unsigned_string => 10,
+    to_hex_string => 16,
+    to_octal_string => 8,
+    to_binary_string => 2,
+}
+
+/// A static method on one value of the type that gives an `int`
+macro_rules! int_functions {
+    ($($name:ident => $f:expr,)*) => {$(
+        fn $name<T: Integral>(ctx: &mut RuntimeCtx) -> Result<()> {
+            let value = T::pop(ctx);
+            ctx.push(($f as fn(T) -> i32)(value));
+            Ok(())
+        }
+    )*};
+}
+int_functions! {
+    bit_count => |v| v.unsigned().count_ones() as i32,
+    number_of_leading_zeros => |v| (v.unsigned().leading_zeros() - (64 - T::BITS)) as i32,
+    number_of_trailing_zeros => |v| if v.unsigned() == 0 { T::BITS as i32 } else { v.unsigned().trailing_zeros() as i32 },
+    signum_of => |v| v.to_i64().signum() as i32,
+    to_unsigned_int => |v| v.unsigned() as i32,
+}
+/// A static method on one value of the type that gives one of the type
+macro_rules! unary_functions {
+    ($($name:ident => $f:expr,)*) => {$(
+        fn $name<T: Integral>(ctx: &mut RuntimeCtx) -> Result<()> {
+            let value = T::pop(ctx).unsigned();
+            T::from_i64(($f as fn(u64) -> u64)(value) as i64).push(ctx);
+            Ok(())
+        }
+    )*};
+}
+unary_functions! {
+    highest_one_bit => |u| if u == 0 { 0 } else { 1 << (63 - u.leading_zeros()) },
+    lowest_one_bit => |u| u & u.wrapping_neg(),
+    reverse => |u| u.reverse_bits() >> (64 - T::BITS),
+    reverse_bytes => |u| u.swap_bytes() >> (64 - T::BITS),
+}
+fn rotate<T: Integral>(value: T, distance: i32) -> T {
+    let (u, d) = (value.unsigned(), distance as u32 & (T::BITS - 1));
+    if d == 0 { value } else { T::from_i64((u << d | u >> (T::BITS - d)) as i64) }
+}
+fn rotate_left<T: Integral>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let distance = ctx.pop().into_i32();
+    rotate(T::pop(ctx), distance).push(ctx);
+    Ok(())
+}
+fn rotate_right<T: Integral>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let distance = ctx.pop().into_i32();
+    rotate(T::pop(ctx), distance.wrapping_neg()).push(ctx);
+    Ok(())
+}
+/// A static method on two values of the type that gives one of the type
+macro_rules! binary_functions {
+    ($($name:ident => $f:expr,)*) => {$(
+        fn $name<T: Integral>(ctx: &mut RuntimeCtx) -> Result<()> {
+            let b = T::pop(ctx).to_i64();
+            let a = T::pop(ctx).to_i64();
+            T::from_i64(($f as fn(i64, i64) -> i64)(a, b)).push(ctx);
+            Ok(())
+        }
+    )*};
+}
+binary_functions! {
+    integral_max => i64::max,
+    integral_min => i64::min,
+    integral_sum => i64::wrapping_add,
+}
+fn compare_unsigned<T: Integral>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let b = T::pop(ctx).unsigned();
+    let a = T::pop(ctx).unsigned();
+    // like `compare`, the narrow types give the difference
+    ctx.push(if T::BITS < 32 { a as i32 - b as i32 } else { signum(a.cmp(&b)) });
+    Ok(())
+}
+fn divide_unsigned<T: Integral>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let divisor = T::pop(ctx).unsigned();
+    let dividend = T::pop(ctx).unsigned();
+    let Some(quotient) = dividend.checked_div(divisor) else {
+        return ctx.throw_new("java/lang/ArithmeticException", Some("/ by zero".into()));
+    };
+    T::from_i64(quotient as i64).push(ctx);
+    Ok(())
+}
+fn remainder_unsigned<T: Integral>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let divisor = T::pop(ctx).unsigned();
+    let dividend = T::pop(ctx).unsigned();
+    let Some(remainder) = dividend.checked_rem(divisor) else {
+        return ctx.throw_new("java/lang/ArithmeticException", Some("/ by zero".into()));
+    };
+    T::from_i64(remainder as i64).push(ctx);
+    Ok(())
+}
+fn to_unsigned_long<T: Integral>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = T::pop(ctx).unsigned();
+    ctx.push2(i64_into_values(value as i64));
+    Ok(())
+}
+
+/// The static methods of `Byte`, `Short`, `Integer` and `Long`
+fn add_integral_members<T: Integral>(members: &mut Members) {
+    add_number_members::<T>(members);
+    let d = T::DESCRIPTOR;
+    let this = format!("L{};", T::BOX);
+    let name = match T::BITS {
+        8 => "Byte",
+        16 => "Short",
+        32 => "Int",
+        _ => "Long",
+    };
+    members
+        .method(&format!("parse{name}"), &format!("(Ljava/lang/String;){d}"), parse::<T>)
+        .method(&format!("parse{name}"), &format!("(Ljava/lang/String;I){d}"), parse_radix::<T>)
+        .method("valueOf", &format!("(Ljava/lang/String;){this}"), value_of_string::<T>)
+        .method("valueOf", &format!("(Ljava/lang/String;I){this}"), value_of_string_radix::<T>)
+        .method("compareUnsigned", &format!("({d}{d})I"), compare_unsigned::<T>);
+    if T::BITS < 32 {
+        members.method("toUnsignedInt", &format!("({d})I"), to_unsigned_int::<T>);
+    }
+    if T::BITS < 64 {
+        members.method("toUnsignedLong", &format!("({d})J"), to_unsigned_long::<T>);
+    }
+    if T::BITS > 8 {
+        members.method("reverseBytes", &format!("({d}){d}"), reverse_bytes::<T>);
+    }
+    if T::BITS < 32 {
+        return;
+    }
+    members
+        .method("toString", &format!("({d}I)Ljava/lang/String;"), to_string_radix::<T>)
+        .method("toUnsignedString", &format!("({d})Ljava/lang/String;"), to_unsigned_string::<T>)
+        .method("toUnsignedString", &format!("({d}I)Ljava/lang/String;"), to_unsigned_string_radix::<T>)
+        .method("toHexString", &format!("({d})Ljava/lang/String;"), to_hex_string::<T>)
+        .method("toOctalString", &format!("({d})Ljava/lang/String;"), to_octal_string::<T>)
+        .method("toBinaryString", &format!("({d})Ljava/lang/String;"), to_binary_string::<T>)
+        .method(&format!("parseUnsigned{name}"), &format!("(Ljava/lang/String;){d}"), parse_unsigned::<T>)
+        .method(&format!("parseUnsigned{name}"), &format!("(Ljava/lang/String;I){d}"), parse_unsigned_radix::<T>)
+        .method("divideUnsigned", &format!("({d}{d}){d}"), divide_unsigned::<T>)
+        .method("remainderUnsigned", &format!("({d}{d}){d}"), remainder_unsigned::<T>)
+        .method("bitCount", &format!("({d})I"), bit_count::<T>)
+        .method("numberOfLeadingZeros", &format!("({d})I"), number_of_leading_zeros::<T>)
+        .method("numberOfTrailingZeros", &format!("({d})I"), number_of_trailing_zeros::<T>)
+        .method("highestOneBit", &format!("({d}){d}"), highest_one_bit::<T>)
+        .method("lowestOneBit", &format!("({d}){d}"), lowest_one_bit::<T>)
+        .method("reverse", &format!("({d}){d}"), reverse::<T>)
+        .method("rotateLeft", &format!("({d}I){d}"), rotate_left::<T>)
+        .method("rotateRight", &format!("({d}I){d}"), rotate_right::<T>)
+        .method("signum", &format!("({d})I"), signum_of::<T>)
+        .method("max", &format!("({d}{d}){d}"), integral_max::<T>)
+        .method("min", &format!("({d}{d}){d}"), integral_min::<T>)
+        .method("sum", &format!("({d}{d}){d}"), integral_sum::<T>);
+}
+
+/// Pops a string and parses it as a float of the type, throwing and returning `None` if it is
+/// not one
+fn parse_floating<T: FloatingPoint>(ctx: &mut RuntimeCtx) -> Result<Option<T>> {
+    let Some(chars) = ctx.pop_nullable_string() else {
+        return ctx.throw_null_pointer().map(|()| None);
+    };
+    match Floating::parse(&chars) {
+        Ok(parsed) => Ok(Some(T::from_parsed(&parsed))),
+        Err(error) => throw_number_format(ctx, error).map(|()| None),
+    }
+}
+fn parse_floating_point<T: FloatingPoint>(ctx: &mut RuntimeCtx) -> Result<()> {
+    if let Some(value) = parse_floating::<T>(ctx)? {
+        value.push(ctx);
+    }
+    Ok(())
+}
+fn floating_value_of_string<T: FloatingPoint>(ctx: &mut RuntimeCtx) -> Result<()> {
+    if let Some(value) = parse_floating::<T>(ctx)? && let Some(boxed) = box_value(ctx, value)? {
+        ctx.push(boxed);
+    }
+    Ok(())
+}
+/// The predicates as static methods and, with a box name, as methods of the box
+macro_rules! floating_predicates {
+    ($($name:ident $(, $box_name:ident)? => $predicate:ident,)*) => {$(
+        fn $name<T: FloatingPoint>(ctx: &mut RuntimeCtx) -> Result<()> {
+            let value = T::pop(ctx);
+            ctx.push(value.$predicate());
+            Ok(())
+        }
+        $(fn $box_name<T: FloatingPoint>(ctx: &mut RuntimeCtx) -> Result<()> {
+            let this = ctx.pop();
+            ctx.push(unbox::<T>(ctx, this).$predicate());
+            Ok(())
+        })?
+    )*};
+}
+floating_predicates! {
+    is_nan, box_is_nan => is_nan,
+    is_infinite, box_is_infinite => is_infinite,
+    is_finite => is_finite,
+}
+fn floating_max<T: FloatingPoint>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let b = T::pop(ctx);
+    let a = T::pop(ctx);
+    max(a, b).push(ctx);
+    Ok(())
+}
+fn floating_min<T: FloatingPoint>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let b = T::pop(ctx);
+    let a = T::pop(ctx);
+    min(a, b).push(ctx);
+    Ok(())
+}
+fn floating_sum<T: FloatingPoint>(ctx: &mut RuntimeCtx) -> Result<()> {
+    let b = T::pop(ctx);
+    let a = T::pop(ctx);
+    (a + b).push(ctx);
+    Ok(())
+}
+/// The static methods `Float` and `Double` both have
+fn add_floating_members<T: FloatingPoint>(members: &mut Members) {
+    add_number_members::<T>(members);
+    let d = T::DESCRIPTOR;
+    members
+        .method(&format!("parse{}", T::NAME), &format!("(Ljava/lang/String;){d}"), parse_floating_point::<T>)
+        .method("valueOf", &format!("(Ljava/lang/String;)L{};", T::BOX), floating_value_of_string::<T>)
+        .method("isNaN", &format!("({d})Z"), is_nan::<T>)
+        .method("isNaN", "()Z", box_is_nan::<T>)
+        .method("isInfinite", &format!("({d})Z"), is_infinite::<T>)
+        .method("isInfinite", "()Z", box_is_infinite::<T>)
+        .method("isFinite", &format!("({d})Z"), is_finite::<T>)
+        .method("max", &format!("({d}{d}){d}"), floating_max::<T>)
+        .method("min", &format!("({d}{d}){d}"), floating_min::<T>)
+        .method("sum", &format!("({d}{d}){d}"), floating_sum::<T>);
+}
+
+fn float_to_int_bits_method(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = ctx.pop().into_f32();
+    ctx.push(float_to_int_bits(value));
+    Ok(())
+}
+/// The raw conversions leave the bits on the operand stack as they are
+fn float_to_raw_int_bits(_: &mut RuntimeCtx) -> Result<()> {
+    Ok(())
+}
+fn int_bits_to_float(_: &mut RuntimeCtx) -> Result<()> {
+    Ok(())
+}
+fn double_to_long_bits_method(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = values_into_f64(ctx.pop2());
+    ctx.push2(i64_into_values(double_to_long_bits(value)));
+    Ok(())
+}
+fn double_to_raw_long_bits(_: &mut RuntimeCtx) -> Result<()> {
+    Ok(())
+}
+fn long_bits_to_double(_: &mut RuntimeCtx) -> Result<()> {
+    Ok(())
+}
+
+/// `Double.toHexString`: `0x1.` and the significand without its trailing zeros for normal
+/// values, `0x0.` for subnormal ones, and the binary exponent after a `p`
+fn hex_string(value: f64) -> String {
+    if value.is_nan() {
+        return "NaN".into();
+    }
+    if value.is_infinite() {
+        return if value > 0. { "Infinity" } else { "-Infinity" }.into();
+    }
+    let sign = if value.is_sign_negative() { "-" } else { "" };
+    if value == 0. {
+        return format!("{sign}0x0.0p0");
+    }
+    let bits = value.to_bits();
+    let exponent = ((bits >> 52) & 0x7FF) as i32;
+    let significand = format!("{:013x}", bits & ((1 << 52) - 1));
+    let significand = match significand.trim_end_matches('0') {
+        "" => "0",
+        trimmed => trimmed,
+    };
+    if exponent == 0 {
+        format!("{sign}0x0.{significand}p-1022")
+    } else {
+        format!("{sign}0x1.{significand}p{}", exponent - 1023)
+    }
+}
+fn double_to_hex_string(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = values_into_f64(ctx.pop2());
+    ctx.push_string(&hex_string(value))
+}
+/// `Float.toHexString`, the subnormal floats scaled to the subnormal doubles with the same
+/// significand
+fn float_to_hex_string(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = ctx.pop().into_f32();
+    let text = if value != 0. && value.abs() < f32::MIN_POSITIVE {
+        let scaled = hex_string(value as f64 * 2f64.powi(-1022 + 126));
+        scaled.replace("p-1022", "p-126")
+    } else {
+        hex_string(value as f64)
+    };
+    ctx.push_string(&text)
+}
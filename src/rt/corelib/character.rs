@@ -0,0 +1,258 @@
+//! The static methods of `java.lang.Character`, on code points.
+//!
+//! The standard library knows the Unicode properties but not the general categories the JDK
+//! defines most of these by, so some are derived from the properties: letters are the alphabetic
+//! characters that are not numbers, which also counts the combining marks that are alphabetic.
+
+use super::{parse::{MAX_RADIX, MIN_RADIX}, Members, Result, RuntimeCtx};
+
+/// The zeros of the runs of ten decimal digits (general category Nd) of Unicode 13
+const DECIMAL_ZEROS: [u32; 65] = [
+    0x30, 0x660, 0x6F0, 0x7C0, 0x966, 0x9E6, 0xA66, 0xAE6, 0xB66, 0xBE6, 0xC66, 0xCE6, 0xD66, 0xDE6,
+    0xE50, 0xED0, 0xF20, 0x1040, 0x1090, 0x17E0, 0x1810, 0x1946, 0x19D0, 0x1A80, 0x1A90, 0x1B50,
+    0x1BB0, 0x1C40, 0x1C50, 0xA620, 0xA8D0, 0xA900, 0xA9D0, 0xA9F0, 0xAA50, 0xABF0, 0xFF10,
+    0x104A0, 0x10D30, 0x11066, 0x110F0, 0x11136, 0x111D0, 0x112F0, 0x11450, 0x114D0, 0x11650,
+    0x116C0, 0x11730, 0x118E0, 0x11950, 0x11C50, 0x11D50, 0x11DA0, 0x16A60, 0x16B50, 0x1D7CE,
+    0x1D7D8, 0x1D7E2, 0x1D7EC, 0x1D7F6, 0x1E140, 0x1E2F0, 0x1E950, 0x1FBF0,
+];
+
+/// The value of a decimal digit of any script
+fn decimal_value(code_point: u32) -> Option<u32> {
+    let i = DECIMAL_ZEROS.partition_point(|&zero| zero <= code_point).checked_sub(1)?;
+    let value = code_point - DECIMAL_ZEROS[i];
+    (value < 10).then_some(value)
+}
+
+/// The value of a Latin letter as a digit beyond 9, the fullwidth forms included
+fn letter_value(code_point: u32) -> Option<u32> {
+    match code_point {
+        0x41..=0x5A => Some(code_point - 0x41 + 10),
+        0x61..=0x7A => Some(code_point - 0x61 + 10),
+        0xFF21..=0xFF3A => Some(code_point - 0xFF21 + 10),
+        0xFF41..=0xFF5A => Some(code_point - 0xFF41 + 10),
+        _ => None,
+    }
+}
+
+/// `Character.digit`, -1 if the code point is not a digit of the radix
+pub(super) fn digit(code_point: u32, radix: i32) -> i32 {
+    if !(MIN_RADIX..=MAX_RADIX).contains(&radix) {
+        return -1;
+    }
+    match decimal_value(code_point).or_else(|| letter_value(code_point)) {
+        Some(value) if (value as i32) < radix => value as i32,
+        _ => -1,
+    }
+}
+
+fn to_char(code_point: u32) -> Option<char> {
+    char::from_u32(code_point)
+}
+
+fn is_digit(code_point: u32) -> bool {
+    decimal_value(code_point).is_some()
+}
+
+fn is_letter(code_point: u32) -> bool {
+    to_char(code_point).is_some_and(|c| c.is_alphabetic() && !c.is_numeric())
+}
+
+/// Spaces, line and paragraph separators that break lines, and the ASCII control characters
+/// that separate
+fn is_whitespace(code_point: u32) -> bool {
+    match code_point {
+        0x1C..=0x1F => true,
+        // non-breaking spaces, and the next line control that is not a separator
+        0x85 | 0xA0 | 0x2007 | 0x202F => false,
+        _ => to_char(code_point).is_some_and(char::is_whitespace),
+    }
+}
+
+/// Case mapping that maps to a single character, like the simple mappings the JDK uses
+fn map_case<I: Iterator<Item = char>>(code_point: u32, map: impl Fn(char) -> I) -> u32 {
+    let Some(c) = to_char(code_point) else { return code_point };
+    let mut mapped = map(c);
+    match (mapped.next(), mapped.next()) {
+        (Some(mapped), None) => mapped as u32,
+        // the full lowercase of the dotted capital I adds a combining dot, its simple one is i
+        (Some('i'), Some(_)) => 'i' as u32,
+        _ => code_point,
+    }
+}
+
+macro_rules! predicates {
+    ($($name:ident => $predicate:expr,)*) => {$(
+        fn $name(ctx: &mut RuntimeCtx) -> Result<()> {
+            let code_point = ctx.pop().into_u32();
+            ctx.push($predicate(code_point));
+            Ok(())
+        }
+    )*};
+}
+predicates! {
+    character_is_digit => is_digit,
+    character_is_letter => is_letter,
+    character_is_letter_or_digit => |c| is_letter(c) || is_digit(c),
+    character_is_alphabetic => |c| to_char(c).is_some_and(char::is_alphabetic),
+    character_is_upper_case => |c| to_char(c).is_some_and(char::is_uppercase),
+    character_is_lower_case => |c| to_char(c).is_some_and(char::is_lowercase),
+    character_is_whitespace => is_whitespace,
+    character_is_space_char => |c| to_char(c).is_some_and(|c| c.is_whitespace() && !c.is_control()),
+    character_is_iso_control => |c| matches!(c, 0..=0x1F | 0x7F..=0x9F),
+    character_is_surrogate => |c| matches!(c, 0xD800..=0xDFFF),
+    character_is_high_surrogate => |c| matches!(c, 0xD800..=0xDBFF),
+    character_is_low_surrogate => |c| matches!(c, 0xDC00..=0xDFFF),
+    character_is_valid_code_point => |c| c <= 0x10FFFF,
+    character_is_bmp_code_point => |c| c <= 0xFFFF,
+    character_is_supplementary_code_point => |c| (0x10000..=0x10FFFF).contains(&c),
+}
+
+fn character_to_upper_case(ctx: &mut RuntimeCtx) -> Result<()> {
+    let code_point = ctx.pop().into_u32();
+    let upper = match code_point {
+        // the full uppercase of the Greek letters with a subscript iota spells the iota out,
+        // their simple one keeps it subscript
+        0x1F80..=0x1F87 | 0x1F90..=0x1F97 | 0x1FA0..=0x1FA7 => code_point + 8,
+        0x1FB3 | 0x1FC3 | 0x1FF3 => code_point + 9,
+        _ => map_case(code_point, char::to_uppercase),
+    };
+    ctx.push(upper as i32);
+    Ok(())
+}
+fn character_to_lower_case(ctx: &mut RuntimeCtx) -> Result<()> {
+    let code_point = ctx.pop().into_u32();
+    ctx.push(map_case(code_point, char::to_lowercase) as i32);
+    Ok(())
+}
+fn character_digit(ctx: &mut RuntimeCtx) -> Result<()> {
+    let radix = ctx.pop().into_i32();
+    let code_point = ctx.pop().into_u32();
+    ctx.push(digit(code_point, radix));
+    Ok(())
+}
+/// The value of a Roman numeral, which are numbers but not digits
+fn roman_numeral_value(code_point: u32) -> Option<u32> {
+    const VALUES: [u32; 16] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 50, 100, 500, 1000];
+    match code_point {
+        0x2160..=0x216F => Some(VALUES[(code_point - 0x2160) as usize]),
+        0x2170..=0x217F => Some(VALUES[(code_point - 0x2170) as usize]),
+        _ => None,
+    }
+}
+
+/// `Character.getNumericValue` for decimal digits, Latin letters and Roman numerals, -2 for the
+/// vulgar fractions and -1 for anything else
+fn character_get_numeric_value(ctx: &mut RuntimeCtx) -> Result<()> {
+    let code_point = ctx.pop().into_u32();
+    let value = decimal_value(code_point)
+        .or_else(|| letter_value(code_point))
+        .or_else(|| roman_numeral_value(code_point));
+    let value = match (value, code_point) {
+        (Some(value), _) => value as i32,
+        (None, 0xBC..=0xBE | 0x2150..=0x215F | 0x2189) => -2,
+        (None, _) => -1,
+    };
+    ctx.push(value);
+    Ok(())
+}
+fn character_for_digit(ctx: &mut RuntimeCtx) -> Result<()> {
+    let radix = ctx.pop().into_i32();
+    let digit = ctx.pop().into_i32();
+    let valid = (MIN_RADIX..=MAX_RADIX).contains(&radix) && (0..radix).contains(&digit);
+    let c = if valid { char::from_digit(digit as u32, radix as u32).unwrap() as i32 } else { 0 };
+    ctx.push(c);
+    Ok(())
+}
+fn character_char_count(ctx: &mut RuntimeCtx) -> Result<()> {
+    let code_point = ctx.pop().into_u32();
+    ctx.push(if code_point >= 0x10000 { 2 } else { 1 });
+    Ok(())
+}
+fn character_to_code_point(ctx: &mut RuntimeCtx) -> Result<()> {
+    let low = ctx.pop().into_u32();
+    let high = ctx.pop().into_u32();
+    ctx.push((((high << 10) + low) as i32).wrapping_add(0x10000 - (0xD800 << 10) - 0xDC00));
+    Ok(())
+}
+fn character_high_surrogate(ctx: &mut RuntimeCtx) -> Result<()> {
+    let code_point = ctx.pop().into_u32();
+    ctx.push(((code_point >> 10) + (0xD800 - (0x10000 >> 10))) as u16 as i32);
+    Ok(())
+}
+fn character_low_surrogate(ctx: &mut RuntimeCtx) -> Result<()> {
+    let code_point = ctx.pop().into_u32();
+    ctx.push(((code_point & 0x3FF) + 0xDC00) as i32);
+    Ok(())
+}
+/// The UTF-16 code units of a code point, throwing an `IllegalArgumentException` for an invalid one
+fn code_point_chars(ctx: &mut RuntimeCtx, code_point: u32) -> Result<Option<Vec<u16>>> {
+    let units = match code_point {
+        0..=0xFFFF => vec![code_point as u16],
+        0x10000..=0x10FFFF => {
+            let offset = code_point - 0x10000;
+            vec![0xD800 + (offset >> 10) as u16, 0xDC00 + (offset & 0x3FF) as u16]
+        }
+        _ => {
+            let message = format!("Not a valid Unicode code point: 0x{code_point:X}");
+            ctx.throw_new("java/lang/IllegalArgumentException", Some(message))?;
+            return Ok(None);
+        }
+    };
+    Ok(Some(units))
+}
+fn character_to_chars(ctx: &mut RuntimeCtx) -> Result<()> {
+    let code_point = ctx.pop().into_u32();
+    let Some(chars) = code_point_chars(ctx, code_point)? else { return Ok(()) };
+    let Some(array) = ctx.new_char_array(&chars)? else { return Ok(()) };
+    ctx.push(array);
+    Ok(())
+}
+fn character_code_point_to_string(ctx: &mut RuntimeCtx) -> Result<()> {
+    let code_point = ctx.pop().into_u32();
+    let Some(chars) = code_point_chars(ctx, code_point)? else { return Ok(()) };
+    let Some(string) = ctx.try_new_string(&chars)? else { return Ok(()) };
+    ctx.push(string);
+    Ok(())
+}
+fn character_reverse_bytes(ctx: &mut RuntimeCtx) -> Result<()> {
+    let c = ctx.pop().into_u16();
+    ctx.push(c.swap_bytes() as i32);
+    Ok(())
+}
+
+/// The static methods that only `Character` has
+pub(super) fn add_members(members: &mut Members) {
+    for arg in ["C", "I"] {
+        let predicate = format!("({arg})Z");
+        let mapping = format!("({arg}){arg}");
+        members
+            .method("isDigit", &predicate, character_is_digit)
+            .method("isLetter", &predicate, character_is_letter)
+            .method("isLetterOrDigit", &predicate, character_is_letter_or_digit)
+            .method("isUpperCase", &predicate, character_is_upper_case)
+            .method("isLowerCase", &predicate, character_is_lower_case)
+            .method("isWhitespace", &predicate, character_is_whitespace)
+            .method("isSpaceChar", &predicate, character_is_space_char)
+            .method("isISOControl", &predicate, character_is_iso_control)
+            .method("toUpperCase", &mapping, character_to_upper_case)
+            .method("toLowerCase", &mapping, character_to_lower_case)
+            .method("digit", &format!("({arg}I)I"), character_digit)
+            .method("getNumericValue", &format!("({arg})I"), character_get_numeric_value);
+    }
+    members
+        .method("isAlphabetic", "(I)Z", character_is_alphabetic)
+        .method("isSurrogate", "(C)Z", character_is_surrogate)
+        .method("isHighSurrogate", "(C)Z", character_is_high_surrogate)
+        .method("isLowSurrogate", "(C)Z", character_is_low_surrogate)
+        .method("isValidCodePoint", "(I)Z", character_is_valid_code_point)
+        .method("isBmpCodePoint", "(I)Z", character_is_bmp_code_point)
+        .method("isSupplementaryCodePoint", "(I)Z", character_is_supplementary_code_point)
+        .method("forDigit", "(II)C", character_for_digit)
+        .method("charCount", "(I)I", character_char_count)
+        .method("toCodePoint", "(CC)I", character_to_code_point)
+        .method("highSurrogate", "(I)C", character_high_surrogate)
+        .method("lowSurrogate", "(I)C", character_low_surrogate)
+        .method("toChars", "(I)[C", character_to_chars)
+        .method("toString", "(I)Ljava/lang/String;", character_code_point_to_string)
+        .method("reverseBytes", "(C)C", character_reverse_bytes);
+}
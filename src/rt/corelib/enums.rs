@@ -0,0 +1,149 @@
+//! `java.lang.Enum`, the superclass of every enum class.
+//!
+//! | offset | content                     |
+//! |--------|-----------------------------|
+//! | 0      | object header               |
+//! | 8      | `String name`               |
+//! | 12     | `int ordinal`               |
+//!
+//! `valueOf` finds the constant among the ones the `values()` method of the enum class returns,
+//! like the JDK does, so the class is initialized first.
+
+use crate::{class::ClassAccess, descriptor::{FieldDescriptor, MethodDescriptor}};
+
+use super::{Members, Result, Runtime, RuntimeCtx, Value, ARRAY_DATA_OFFSET, OBJECT_CLASS};
+use super::super::{LoadedClass, ARRAY_LENGTH_OFFSET, CLASS_ID_OFFSET, OBJECT_HEADER_SIZE};
+
+const NAME_OFFSET: u32 = OBJECT_HEADER_SIZE as u32;
+const ORDINAL_OFFSET: u32 = NAME_OFFSET + 4;
+
+pub(super) fn class(runtime: &mut Runtime) -> Result<LoadedClass> {
+    let interfaces = ["java/lang/Comparable", "java/io/Serializable"]
+        .into_iter()
+        .map(|name| runtime.load_class(name))
+        .collect::<Result<_>>()?;
+    Ok(LoadedClass {
+        access_flags: ClassAccess::PUBLIC | ClassAccess::ABSTRACT,
+        interfaces,
+        data_size: ORDINAL_OFFSET as u16 + 4,
+        ref_offsets: Box::new([NAME_OFFSET as u16]),
+        ..members().into_class("java/lang/Enum", OBJECT_CLASS)
+    })
+}
+
+fn name(ctx: &RuntimeCtx, this: Value) -> Value {
+    Value(ctx.read_u32_ref(this.offset(NAME_OFFSET)).unwrap())
+}
+fn ordinal(ctx: &RuntimeCtx, this: Value) -> i32 {
+    ctx.read_u32_ref(this.offset(ORDINAL_OFFSET)).unwrap() as i32
+}
+/// The enum class of a constant, which is the superclass of the class of a constant with a body
+fn declaring_class(ctx: &mut RuntimeCtx, this: Value) -> Result<u32> {
+    let enum_class = ctx.runtime.load_class("java/lang/Enum")?;
+    let class = ctx.get_class_id(this);
+    let super_class = ctx.runtime.get_class(class).super_class;
+    Ok(if super_class == enum_class { class } else { super_class })
+}
+
+fn enum_init(ctx: &mut RuntimeCtx) -> Result<()> {
+    let ordinal = ctx.pop();
+    let name = ctx.pop();
+    let this = ctx.pop();
+    ctx.write_u32_ref(this.offset(NAME_OFFSET), name.into_u32());
+    ctx.write_u32_ref(this.offset(ORDINAL_OFFSET), ordinal.into_u32());
+    Ok(())
+}
+/// `name` and `toString`
+fn enum_name(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    let name = name(ctx, this);
+    ctx.push(name);
+    Ok(())
+}
+fn enum_ordinal(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    let ordinal = ordinal(ctx, this);
+    ctx.push(ordinal);
+    Ok(())
+}
+fn enum_compare_to(ctx: &mut RuntimeCtx) -> Result<()> {
+    let other = ctx.pop();
+    let this = ctx.pop();
+    if other == Value::NULL {
+        return ctx.throw_null_pointer();
+    }
+    // constants of different enums, which the bridge method lets through
+    if declaring_class(ctx, this)? != declaring_class(ctx, other)? {
+        return ctx.throw_new("java/lang/ClassCastException", None);
+    }
+    let difference = ordinal(ctx, this) - ordinal(ctx, other);
+    ctx.push(difference);
+    Ok(())
+}
+fn enum_get_declaring_class(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    let class = declaring_class(ctx, this)?;
+    ctx.push(Value::new_ref_static(ctx.runtime.get_class(class).class_object));
+    Ok(())
+}
+/// `valueOf(Class, String)`, the class and the name stay on the stack while `values()` runs
+fn enum_value_of(ctx: &mut RuntimeCtx) -> Result<()> {
+    let class_object = ctx.peek(1);
+    if class_object == Value::NULL {
+        ctx.pop_n(2);
+        return ctx.throw_null_pointer();
+    }
+    let class = ctx.read_u32_ref(class_object.offset(CLASS_ID_OFFSET)).unwrap();
+    let enum_class = ctx.runtime.load_class("java/lang/Enum")?;
+    let loaded = ctx.runtime.get_class(class);
+    let class_name = loaded.name.clone();
+    let values = MethodDescriptor::new_ret([], FieldDescriptor::ArrRef(Box::new(FieldDescriptor::ClassRef(class_name.clone())))).into();
+    let values = ctx.runtime.find_method(class, "values", &values).filter(|_| loaded.super_class == enum_class);
+    let Some((owner, method)) = values else {
+        ctx.pop_n(2);
+        return ctx.throw_new("java/lang/IllegalArgumentException", Some(format!("{} is not an enum class", class_name.replace('/', "."))));
+    };
+    if let Err(exception) = ctx.initialize_class(&class_name)? {
+        ctx.pop_n(2);
+        return ctx.throw(exception);
+    }
+    if let Err(exception) = ctx.run_method(owner, method)? {
+        ctx.pop_n(2);
+        return ctx.throw(exception);
+    }
+    let constants = ctx.pop();
+    let wanted = ctx.pop_nullable_string();
+    ctx.pop();
+    let length = if constants == Value::NULL { 0 } else { ctx.read_u32_ref(constants.offset(ARRAY_LENGTH_OFFSET)).unwrap() };
+    let found = (0..length)
+        .map(|i| Value(ctx.read_u32_ref(constants.offset(ARRAY_DATA_OFFSET + 4 * i)).unwrap()))
+        .find(|&constant| wanted.is_some() && ctx.read_string_chars(name(ctx, constant)) == wanted);
+    match (found, wanted) {
+        (Some(constant), _) => {
+            ctx.push(constant);
+            Ok(())
+        }
+        (None, None) => ctx.throw_new("java/lang/NullPointerException", Some("Name is null".into())),
+        (None, Some(wanted)) => {
+            // the canonical name of the class, with nested classes separated by dots too
+            let message = format!("No enum constant {}.{}", class_name.replace(['/', '$'], "."), String::from_utf16_lossy(&wanted));
+            ctx.throw_new("java/lang/IllegalArgumentException", Some(message))
+        }
+    }
+}
+
+fn members() -> Members {
+    let mut members = Members::new();
+    members
+        .field("name", "Ljava/lang/String;", NAME_OFFSET as u16)
+        .field("ordinal", "I", ORDINAL_OFFSET as u16)
+        .method("<init>", "(Ljava/lang/String;I)V", enum_init)
+        .method("name", "()Ljava/lang/String;", enum_name)
+        .method("toString", "()Ljava/lang/String;", enum_name)
+        .method("ordinal", "()I", enum_ordinal)
+        .method("compareTo", "(Ljava/lang/Enum;)I", enum_compare_to)
+        .method("compareTo", "(Ljava/lang/Object;)I", enum_compare_to)
+        .method("getDeclaringClass", "()Ljava/lang/Class;", enum_get_declaring_class)
+        .method("valueOf", "(Ljava/lang/Class;Ljava/lang/String;)Ljava/lang/Enum;", enum_value_of);
+    members
+}
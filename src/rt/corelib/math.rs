@@ -0,0 +1,407 @@
+//! `java.lang.Math`, also loaded as `StrictMath`.
+//!
+//! The transcendental functions are those of the platform, which can differ from the JDK in the
+//! last place; everything the JDK specifies exactly, like the rounding functions, the special
+//! cases of `pow` and the overflow of the exact arithmetic, behaves like it.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{boxes, Members, Result, RuntimeCtx};
+use super::super::{f64_into_values, i64_into_values, values_into_f64, values_into_u64};
+
+/// The generator of `Math.random`, a xorshift seeded from the clock
+#[derive(Debug, Clone)]
+pub(in super::super) struct RandomGenerator(u64);
+
+impl RandomGenerator {
+    pub fn new() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64);
+        // the state must not be 0
+        Self(nanos | 1)
+    }
+    /// A double from 0.0 up to 1.0, from the top 53 bits of the next number
+    fn next_double(&mut self) -> f64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        (x.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11) as f64 * (1. / (1u64 << 53) as f64)
+    }
+}
+
+fn pop_long(ctx: &mut RuntimeCtx) -> i64 {
+    values_into_u64(ctx.pop2()) as i64
+}
+fn push_long(ctx: &mut RuntimeCtx, value: i64) {
+    ctx.push2(i64_into_values(value));
+}
+fn pop_double(ctx: &mut RuntimeCtx) -> f64 {
+    values_into_f64(ctx.pop2())
+}
+fn push_double(ctx: &mut RuntimeCtx, value: f64) {
+    ctx.push2(f64_into_values(value));
+}
+
+/// Methods on doubles that give a double
+macro_rules! double_functions {
+    ($($name:ident($($arg:ident),*) => $f:expr,)*) => {$(
+        fn $name(ctx: &mut RuntimeCtx) -> Result<()> {
+            double_functions!(@pop ctx $($arg)*);
+            push_double(ctx, $f);
+            Ok(())
+        }
+    )*};
+    (@pop $ctx:ident) => {};
+    (@pop $ctx:ident $arg:ident $($rest:ident)*) => {
+        double_functions!(@pop $ctx $($rest)*);
+        let $arg = pop_double($ctx);
+    };
+}
+double_functions! {
+    math_sin(a) => a.sin(),
+    math_cos(a) => a.cos(),
+    math_tan(a) => a.tan(),
+    math_asin(a) => a.asin(),
+    math_acos(a) => a.acos(),
+    math_atan(a) => a.atan(),
+    math_sinh(a) => a.sinh(),
+    math_cosh(a) => a.cosh(),
+    math_tanh(a) => a.tanh(),
+    math_exp(a) => a.exp(),
+    math_expm1(a) => a.exp_m1(),
+    math_log(a) => a.ln(),
+    math_log10(a) => a.log10(),
+    math_log1p(a) => a.ln_1p(),
+    math_sqrt(a) => a.sqrt(),
+    math_cbrt(a) => a.cbrt(),
+    math_floor(a) => a.floor(),
+    math_ceil(a) => a.ceil(),
+    math_rint(a) => a.round_ties_even(),
+    math_to_radians(a) => a.to_radians(),
+    math_to_degrees(a) => a.to_degrees(),
+    math_abs_double(a) => a.abs(),
+    math_signum_double(a) => if a == 0. || a.is_nan() { a } else { 1f64.copysign(a) },
+    math_ulp_double(a) => ulp_double(a),
+    math_next_up_double(a) => a.next_up(),
+    math_next_down_double(a) => a.next_down(),
+    math_atan2(y, x) => y.atan2(x),
+    math_hypot(x, y) => x.hypot(y),
+    math_pow(a, b) => pow(a, b),
+    math_max_double(a, b) => boxes::max(a, b),
+    math_min_double(a, b) => boxes::min(a, b),
+    math_copy_sign_double(magnitude, sign) => magnitude.copysign(sign),
+    math_fma_double(a, b, c) => a.mul_add(b, c),
+}
+
+/// Methods on floats that give a float
+macro_rules! float_functions {
+    ($($name:ident($($arg:ident),*) => $f:expr,)*) => {$(
+        fn $name(ctx: &mut RuntimeCtx) -> Result<()> {
+            float_functions!(@pop ctx $($arg)*);
+            ctx.push($f);
+            Ok(())
+        }
+    )*};
+    (@pop $ctx:ident) => {};
+    (@pop $ctx:ident $arg:ident $($rest:ident)*) => {
+        float_functions!(@pop $ctx $($rest)*);
+        let $arg = $ctx.pop().into_f32();
+    };
+}
+float_functions! {
+    math_abs_float(a) => a.abs(),
+    math_signum_float(a) => if a == 0. || a.is_nan() { a } else { 1f32.copysign(a) },
+    math_ulp_float(a) => ulp_float(a),
+    math_next_up_float(a) => a.next_up(),
+    math_next_down_float(a) => a.next_down(),
+    math_max_float(a, b) => boxes::max(a, b),
+    math_min_float(a, b) => boxes::min(a, b),
+    math_copy_sign_float(magnitude, sign) => magnitude.copysign(sign),
+    math_fma_float(a, b, c) => a.mul_add(b, c),
+}
+
+/// `pow`, which unlike C is NaN for a NaN exponent and for 1 or -1 to an infinite power
+fn pow(a: f64, b: f64) -> f64 {
+    if b.is_nan() || a.abs() == 1. && b.is_infinite() { f64::NAN } else { a.powf(b) }
+}
+
+/// The distance to the next double away from zero, for a finite value
+fn ulp_double(a: f64) -> f64 {
+    if !a.is_finite() {
+        return a.abs();
+    }
+    let exponent = (a.to_bits() >> 52) & 0x7FF;
+    f64::from_bits(match exponent {
+        0 => 1,
+        1..=52 => 1 << (exponent - 1),
+        _ => (exponent - 52) << 52,
+    })
+}
+fn ulp_float(a: f32) -> f32 {
+    if !a.is_finite() {
+        return a.abs();
+    }
+    let exponent = (a.to_bits() >> 23) & 0xFF;
+    f32::from_bits(match exponent {
+        0 => 1,
+        1..=23 => 1 << (exponent - 1),
+        _ => (exponent - 23) << 23,
+    })
+}
+
+fn math_get_exponent_double(ctx: &mut RuntimeCtx) -> Result<()> {
+    let a = pop_double(ctx);
+    ctx.push(((a.to_bits() >> 52) & 0x7FF) as i32 - 1023);
+    Ok(())
+}
+fn math_get_exponent_float(ctx: &mut RuntimeCtx) -> Result<()> {
+    let a = ctx.pop().into_f32();
+    ctx.push(((a.to_bits() >> 23) & 0xFF) as i32 - 127);
+    Ok(())
+}
+/// `round`: the closest integer with ties toward positive infinity, saturated, 0 for NaN
+fn math_round_double(ctx: &mut RuntimeCtx) -> Result<()> {
+    let a = pop_double(ctx);
+    let floor = a.floor();
+    push_long(ctx, (floor as i64).saturating_add((a - floor >= 0.5) as i64));
+    Ok(())
+}
+fn math_round_float(ctx: &mut RuntimeCtx) -> Result<()> {
+    let a = ctx.pop().into_f32();
+    let floor = a.floor();
+    ctx.push((floor as i32).saturating_add((a - floor >= 0.5) as i32));
+    Ok(())
+}
+fn math_random(ctx: &mut RuntimeCtx) -> Result<()> {
+    let random = ctx.runtime.random.next_double();
+    push_double(ctx, random);
+    Ok(())
+}
+
+fn math_abs_int(ctx: &mut RuntimeCtx) -> Result<()> {
+    let a = ctx.pop().into_i32();
+    ctx.push(a.wrapping_abs());
+    Ok(())
+}
+fn math_abs_long(ctx: &mut RuntimeCtx) -> Result<()> {
+    let a = pop_long(ctx);
+    push_long(ctx, a.wrapping_abs());
+    Ok(())
+}
+fn math_max_int(ctx: &mut RuntimeCtx) -> Result<()> {
+    let (b, a) = (ctx.pop().into_i32(), ctx.pop().into_i32());
+    ctx.push(a.max(b));
+    Ok(())
+}
+fn math_min_int(ctx: &mut RuntimeCtx) -> Result<()> {
+    let (b, a) = (ctx.pop().into_i32(), ctx.pop().into_i32());
+    ctx.push(a.min(b));
+    Ok(())
+}
+fn math_max_long(ctx: &mut RuntimeCtx) -> Result<()> {
+    let (b, a) = (pop_long(ctx), pop_long(ctx));
+    push_long(ctx, a.max(b));
+    Ok(())
+}
+fn math_min_long(ctx: &mut RuntimeCtx) -> Result<()> {
+    let (b, a) = (pop_long(ctx), pop_long(ctx));
+    push_long(ctx, a.min(b));
+    Ok(())
+}
+
+fn throw_divide_by_zero(ctx: &mut RuntimeCtx) -> Result<()> {
+    ctx.throw_new("java/lang/ArithmeticException", Some("/ by zero".into()))
+}
+/// The quotient rounded toward negative infinity
+fn floor_div(a: i64, b: i64) -> i64 {
+    let quotient = a.wrapping_div(b);
+    if a.wrapping_rem(b) != 0 && (a ^ b) < 0 { quotient - 1 } else { quotient }
+}
+/// The remainder with the sign of the divisor
+fn floor_mod(a: i64, b: i64) -> i64 {
+    let remainder = a.wrapping_rem(b);
+    if remainder != 0 && (remainder ^ b) < 0 { remainder + b } else { remainder }
+}
+fn math_floor_div_int(ctx: &mut RuntimeCtx) -> Result<()> {
+    let (b, a) = (ctx.pop().into_i32(), ctx.pop().into_i32());
+    if b == 0 {
+        return throw_divide_by_zero(ctx);
+    }
+    // MIN_VALUE / -1 overflows back to MIN_VALUE
+    ctx.push(floor_div(a as i64, b as i64) as i32);
+    Ok(())
+}
+fn math_floor_mod_int(ctx: &mut RuntimeCtx) -> Result<()> {
+    let (b, a) = (ctx.pop().into_i32(), ctx.pop().into_i32());
+    if b == 0 {
+        return throw_divide_by_zero(ctx);
+    }
+    ctx.push(floor_mod(a as i64, b as i64) as i32);
+    Ok(())
+}
+fn math_floor_div_long(ctx: &mut RuntimeCtx) -> Result<()> {
+    let (b, a) = (pop_long(ctx), pop_long(ctx));
+    if b == 0 {
+        return throw_divide_by_zero(ctx);
+    }
+    push_long(ctx, floor_div(a, b));
+    Ok(())
+}
+fn math_floor_div_long_int(ctx: &mut RuntimeCtx) -> Result<()> {
+    let (b, a) = (ctx.pop().into_i32(), pop_long(ctx));
+    if b == 0 {
+        return throw_divide_by_zero(ctx);
+    }
+    push_long(ctx, floor_div(a, b as i64));
+    Ok(())
+}
+fn math_floor_mod_long(ctx: &mut RuntimeCtx) -> Result<()> {
+    let (b, a) = (pop_long(ctx), pop_long(ctx));
+    if b == 0 {
+        return throw_divide_by_zero(ctx);
+    }
+    push_long(ctx, floor_mod(a, b));
+    Ok(())
+}
+fn math_floor_mod_long_int(ctx: &mut RuntimeCtx) -> Result<()> {
+    let (b, a) = (ctx.pop().into_i32(), pop_long(ctx));
+    if b == 0 {
+        return throw_divide_by_zero(ctx);
+    }
+    ctx.push(floor_mod(a, b as i64) as i32);
+    Ok(())
+}
+
+/// The `*Exact` methods, which throw an `ArithmeticException` where the operation overflows
+macro_rules! exact_functions {
+    ($($name:ident($($arg:ident: $type:ident),*) -> $result:ident => $f:expr, $message:literal;)*) => {$(
+        fn $name(ctx: &mut RuntimeCtx) -> Result<()> {
+            exact_functions!(@pop ctx $($arg: $type)*);
+            let Some(result) = $f else {
+                return ctx.throw_new("java/lang/ArithmeticException", Some($message.into()));
+            };
+            exact_functions!(@push ctx result $result);
+            Ok(())
+        }
+    )*};
+    (@pop $ctx:ident) => {};
+    (@pop $ctx:ident $arg:ident: $type:ident $($rest:ident: $rest_type:ident)*) => {
+        exact_functions!(@pop $ctx $($rest: $rest_type)*);
+        let $arg = exact_functions!(@pop_one $ctx $type);
+    };
+    (@pop_one $ctx:ident int) => { $ctx.pop().into_i32() };
+    (@pop_one $ctx:ident long) => { pop_long($ctx) };
+    (@push $ctx:ident $value:ident int) => { $ctx.push($value) };
+    (@push $ctx:ident $value:ident long) => { push_long($ctx, $value) };
+}
+exact_functions! {
+    math_add_exact_int(a: int, b: int) -> int => a.checked_add(b), "integer overflow";
+    math_subtract_exact_int(a: int, b: int) -> int => a.checked_sub(b), "integer overflow";
+    math_multiply_exact_int(a: int, b: int) -> int => a.checked_mul(b), "integer overflow";
+    math_increment_exact_int(a: int) -> int => a.checked_add(1), "integer overflow";
+    math_decrement_exact_int(a: int) -> int => a.checked_sub(1), "integer overflow";
+    math_negate_exact_int(a: int) -> int => a.checked_neg(), "integer overflow";
+    math_abs_exact_int(a: int) -> int => a.checked_abs(), "Overflow to represent absolute value of Integer.MIN_VALUE";
+    math_to_int_exact(a: long) -> int => i32::try_from(a).ok(), "integer overflow";
+    math_add_exact_long(a: long, b: long) -> long => a.checked_add(b), "long overflow";
+    math_subtract_exact_long(a: long, b: long) -> long => a.checked_sub(b), "long overflow";
+    math_multiply_exact_long(a: long, b: long) -> long => a.checked_mul(b), "long overflow";
+    math_multiply_exact_long_int(a: long, b: int) -> long => a.checked_mul(b as i64), "long overflow";
+    math_increment_exact_long(a: long) -> long => a.checked_add(1), "long overflow";
+    math_decrement_exact_long(a: long) -> long => a.checked_sub(1), "long overflow";
+    math_negate_exact_long(a: long) -> long => a.checked_neg(), "long overflow";
+    math_abs_exact_long(a: long) -> long => a.checked_abs(), "Overflow to represent absolute value of Long.MIN_VALUE";
+}
+fn math_multiply_high(ctx: &mut RuntimeCtx) -> Result<()> {
+    let (b, a) = (pop_long(ctx), pop_long(ctx));
+    push_long(ctx, ((a as i128 * b as i128) >> 64) as i64);
+    Ok(())
+}
+
+/// The methods of `Math`
+pub(super) fn members() -> Members {
+    let mut members = Members::new();
+    for (name, method) in [
+        ("sin", math_sin as fn(&mut RuntimeCtx) -> Result<()>),
+        ("cos", math_cos),
+        ("tan", math_tan),
+        ("asin", math_asin),
+        ("acos", math_acos),
+        ("atan", math_atan),
+        ("sinh", math_sinh),
+        ("cosh", math_cosh),
+        ("tanh", math_tanh),
+        ("exp", math_exp),
+        ("expm1", math_expm1),
+        ("log", math_log),
+        ("log10", math_log10),
+        ("log1p", math_log1p),
+        ("sqrt", math_sqrt),
+        ("cbrt", math_cbrt),
+        ("floor", math_floor),
+        ("ceil", math_ceil),
+        ("rint", math_rint),
+        ("toRadians", math_to_radians),
+        ("toDegrees", math_to_degrees),
+    ] {
+        members.method(name, "(D)D", method);
+    }
+    members
+        .method("atan2", "(DD)D", math_atan2)
+        .method("hypot", "(DD)D", math_hypot)
+        .method("pow", "(DD)D", math_pow)
+        .method("random", "()D", math_random)
+        .method("abs", "(I)I", math_abs_int)
+        .method("abs", "(J)J", math_abs_long)
+        .method("abs", "(F)F", math_abs_float)
+        .method("abs", "(D)D", math_abs_double)
+        .method("max", "(II)I", math_max_int)
+        .method("max", "(JJ)J", math_max_long)
+        .method("max", "(FF)F", math_max_float)
+        .method("max", "(DD)D", math_max_double)
+        .method("min", "(II)I", math_min_int)
+        .method("min", "(JJ)J", math_min_long)
+        .method("min", "(FF)F", math_min_float)
+        .method("min", "(DD)D", math_min_double)
+        .method("round", "(D)J", math_round_double)
+        .method("round", "(F)I", math_round_float)
+        .method("signum", "(D)D", math_signum_double)
+        .method("signum", "(F)F", math_signum_float)
+        .method("ulp", "(D)D", math_ulp_double)
+        .method("ulp", "(F)F", math_ulp_float)
+        .method("nextUp", "(D)D", math_next_up_double)
+        .method("nextUp", "(F)F", math_next_up_float)
+        .method("nextDown", "(D)D", math_next_down_double)
+        .method("nextDown", "(F)F", math_next_down_float)
+        .method("copySign", "(DD)D", math_copy_sign_double)
+        .method("copySign", "(FF)F", math_copy_sign_float)
+        .method("fma", "(DDD)D", math_fma_double)
+        .method("fma", "(FFF)F", math_fma_float)
+        .method("getExponent", "(D)I", math_get_exponent_double)
+        .method("getExponent", "(F)I", math_get_exponent_float)
+        .method("floorDiv", "(II)I", math_floor_div_int)
+        .method("floorDiv", "(JJ)J", math_floor_div_long)
+        .method("floorDiv", "(JI)J", math_floor_div_long_int)
+        .method("floorMod", "(II)I", math_floor_mod_int)
+        .method("floorMod", "(JJ)J", math_floor_mod_long)
+        .method("floorMod", "(JI)I", math_floor_mod_long_int)
+        .method("addExact", "(II)I", math_add_exact_int)
+        .method("addExact", "(JJ)J", math_add_exact_long)
+        .method("subtractExact", "(II)I", math_subtract_exact_int)
+        .method("subtractExact", "(JJ)J", math_subtract_exact_long)
+        .method("multiplyExact", "(II)I", math_multiply_exact_int)
+        .method("multiplyExact", "(JJ)J", math_multiply_exact_long)
+        .method("multiplyExact", "(JI)J", math_multiply_exact_long_int)
+        .method("incrementExact", "(I)I", math_increment_exact_int)
+        .method("incrementExact", "(J)J", math_increment_exact_long)
+        .method("decrementExact", "(I)I", math_decrement_exact_int)
+        .method("decrementExact", "(J)J", math_decrement_exact_long)
+        .method("negateExact", "(I)I", math_negate_exact_int)
+        .method("negateExact", "(J)J", math_negate_exact_long)
+        .method("absExact", "(I)I", math_abs_exact_int)
+        .method("absExact", "(J)J", math_abs_exact_long)
+        .method("toIntExact", "(J)I", math_to_int_exact)
+        .method("multiplyHigh", "(JJ)J", math_multiply_high);
+    members
+}
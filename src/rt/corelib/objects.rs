@@ -0,0 +1,159 @@
+//! `java.util.Objects`.
+
+use super::{arrays, descriptor, Members, Result, RuntimeCtx, Value};
+use super::super::STRING_CLASS;
+
+fn objects_equals(ctx: &mut RuntimeCtx) -> Result<()> {
+    // both stay on the stack while `equals` runs
+    let (a, b) = (ctx.peek(1), ctx.peek(0));
+    if let Some(equal) = arrays::objects_equal(ctx, a, b)? {
+        ctx.pop2();
+        ctx.push(equal);
+    }
+    Ok(())
+}
+fn objects_hash_code(ctx: &mut RuntimeCtx) -> Result<()> {
+    let obj = ctx.top();
+    if let Some(hash) = arrays::object_hash(ctx, obj)? {
+        ctx.pop();
+        ctx.push(hash);
+    }
+    Ok(())
+}
+/// `hash(Object...)`, which is `Arrays.hashCode` of the arguments
+fn objects_hash(ctx: &mut RuntimeCtx) -> Result<()> {
+    if let Some(hash) = arrays::object_array_hash(ctx)? {
+        ctx.push(hash);
+    }
+    Ok(())
+}
+fn objects_to_string(ctx: &mut RuntimeCtx) -> Result<()> {
+    let obj = ctx.top();
+    if obj != Value::NULL && ctx.get_class_id(obj) == STRING_CLASS {
+        return Ok(());
+    }
+    let Some(chars) = ctx.string_value_of(obj)? else { return Ok(()) };
+    ctx.pop();
+    let Some(string) = ctx.try_new_string(&chars)? else { return Ok(()) };
+    ctx.push(string);
+    Ok(())
+}
+fn objects_to_string_default(ctx: &mut RuntimeCtx) -> Result<()> {
+    let default = ctx.pop();
+    if ctx.top() == Value::NULL {
+        ctx.pop();
+        ctx.push(default);
+        return Ok(());
+    }
+    objects_to_string(ctx)
+}
+fn objects_is_null(ctx: &mut RuntimeCtx) -> Result<()> {
+    let obj = ctx.pop();
+    ctx.push(obj == Value::NULL);
+    Ok(())
+}
+fn objects_non_null(ctx: &mut RuntimeCtx) -> Result<()> {
+    let obj = ctx.pop();
+    ctx.push(obj != Value::NULL);
+    Ok(())
+}
+fn objects_require_non_null(ctx: &mut RuntimeCtx) -> Result<()> {
+    if ctx.top() == Value::NULL {
+        ctx.pop();
+        return ctx.throw_null_pointer();
+    }
+    Ok(())
+}
+fn objects_require_non_null_message(ctx: &mut RuntimeCtx) -> Result<()> {
+    let message = ctx.pop();
+    if ctx.top() == Value::NULL {
+        ctx.pop();
+        let message = ctx.read_string_object(message);
+        return ctx.throw_new("java/lang/NullPointerException", message);
+    }
+    Ok(())
+}
+/// `requireNonNull(Object, Supplier)`, with the message the supplier gives
+fn objects_require_non_null_supplier(ctx: &mut RuntimeCtx) -> Result<()> {
+    let supplier = ctx.pop();
+    if ctx.top() != Value::NULL {
+        return Ok(());
+    }
+    ctx.pop();
+    let message = if supplier == Value::NULL {
+        None
+    } else {
+        ctx.push(supplier);
+        if !ctx.call_virtual(supplier, "get", &descriptor("()Ljava/lang/Object;"))? {
+            return Ok(());
+        }
+        let message = ctx.pop();
+        ctx.read_string_object(message)
+    };
+    ctx.throw_new("java/lang/NullPointerException", message)
+}
+fn objects_require_non_null_else(ctx: &mut RuntimeCtx) -> Result<()> {
+    let default = ctx.pop();
+    if ctx.top() == Value::NULL {
+        ctx.pop();
+        if default == Value::NULL {
+            return ctx.throw_new("java/lang/NullPointerException", Some("defaultObj".into()));
+        }
+        ctx.push(default);
+    }
+    Ok(())
+}
+/// `compare(a, b, comparator)`, 0 for the same object without asking the comparator
+fn objects_compare(ctx: &mut RuntimeCtx) -> Result<()> {
+    let (a, b, comparator) = (ctx.peek(2), ctx.peek(1), ctx.peek(0));
+    if a == b {
+        ctx.pop_n(3);
+        ctx.push(0);
+        return Ok(());
+    }
+    if comparator == Value::NULL {
+        ctx.pop_n(3);
+        return ctx.throw_null_pointer();
+    }
+    ctx.push(comparator);
+    ctx.push(a);
+    ctx.push(b);
+    if ctx.call_virtual(comparator, "compare", &descriptor("(Ljava/lang/Object;Ljava/lang/Object;)I"))? {
+        let result = ctx.pop();
+        ctx.pop_n(3);
+        ctx.push(result);
+    }
+    Ok(())
+}
+fn objects_check_index(ctx: &mut RuntimeCtx) -> Result<()> {
+    let length = ctx.pop().into_i32();
+    let index = ctx.top().into_i32();
+    if index < 0 || index >= length {
+        ctx.pop();
+        let message = format!("Index {index} out of bounds for length {length}");
+        return ctx.throw_new("java/lang/IndexOutOfBoundsException", Some(message));
+    }
+    Ok(())
+}
+
+/// The methods of `Objects`
+pub(super) fn members() -> Members {
+    const OBJECT: &str = "Ljava/lang/Object;";
+    const STRING: &str = "Ljava/lang/String;";
+    let mut members = Members::new();
+    members
+        .method("equals", &format!("({OBJECT}{OBJECT})Z"), objects_equals)
+        .method("hashCode", &format!("({OBJECT})I"), objects_hash_code)
+        .method("hash", &format!("([{OBJECT})I"), objects_hash)
+        .method("toString", &format!("({OBJECT}){STRING}"), objects_to_string)
+        .method("toString", &format!("({OBJECT}{STRING}){STRING}"), objects_to_string_default)
+        .method("isNull", &format!("({OBJECT})Z"), objects_is_null)
+        .method("nonNull", &format!("({OBJECT})Z"), objects_non_null)
+        .method("requireNonNull", &format!("({OBJECT}){OBJECT}"), objects_require_non_null)
+        .method("requireNonNull", &format!("({OBJECT}{STRING}){OBJECT}"), objects_require_non_null_message)
+        .method("requireNonNull", &format!("({OBJECT}Ljava/util/function/Supplier;){OBJECT}"), objects_require_non_null_supplier)
+        .method("requireNonNullElse", &format!("({OBJECT}{OBJECT}){OBJECT}"), objects_require_non_null_else)
+        .method("compare", &format!("({OBJECT}{OBJECT}Ljava/util/Comparator;)I"), objects_compare)
+        .method("checkIndex", "(II)I", objects_check_index);
+    members
+}
@@ -0,0 +1,279 @@
+//! Parsing numbers from strings with the grammars of the JDK: `Integer.parseInt` and friends,
+//! which take the decimal digits of every script, and `Double.parseDouble`, which also takes
+//! hexadecimal significands and the `NaN` and `Infinity` that `Double.toString` gives.
+
+use super::character::digit;
+
+pub(super) const MIN_RADIX: i32 = 2;
+pub(super) const MAX_RADIX: i32 = 36;
+
+/// Why a string is not a number, with the message of the `NumberFormatException`
+#[derive(Debug)]
+pub(super) struct NumberFormatError(pub(super) String);
+
+impl NumberFormatError {
+    fn input(chars: &[u16], radix: i32) -> Self {
+        let text = String::from_utf16_lossy(chars);
+        Self(if radix == 10 {
+            format!("For input string: \"{text}\"")
+        } else {
+            format!("For input string: \"{text}\" under radix {radix}")
+        })
+    }
+}
+
+type ParseResult<T> = Result<T, NumberFormatError>;
+
+fn check_radix(radix: i32) -> ParseResult<()> {
+    if radix < MIN_RADIX {
+        Err(NumberFormatError(format!("radix {radix} less than Character.MIN_RADIX")))
+    } else if radix > MAX_RADIX {
+        Err(NumberFormatError(format!("radix {radix} greater than Character.MAX_RADIX")))
+    } else {
+        Ok(())
+    }
+}
+
+/// An optional sign followed by at least one digit of the radix, with a magnitude of at most
+/// `max_positive` or `max_negative` depending on the sign
+fn parse_digits(chars: &[u16], radix: i32, max_positive: u64, max_negative: u64) -> ParseResult<i128> {
+    let (negative, digits) = match chars.first() {
+        Some(&c) if c == b'-' as u16 => (true, &chars[1..]),
+        Some(&c) if c == b'+' as u16 => (false, &chars[1..]),
+        _ => (false, chars),
+    };
+    if digits.is_empty() {
+        return Err(NumberFormatError::input(chars, radix));
+    }
+    let limit = if negative { max_negative } else { max_positive } as i128;
+    let mut magnitude = 0i128;
+    for &c in digits {
+        let d = digit(c as u32, radix);
+        magnitude = magnitude * radix as i128 + d as i128;
+        if d < 0 || magnitude > limit {
+            return Err(NumberFormatError::input(chars, radix));
+        }
+    }
+    Ok(if negative { -magnitude } else { magnitude })
+}
+
+/// `Integer.parseInt` and `Long.parseLong`, for the range of a signed type of `bits` bits
+pub(super) fn parse_integer(chars: Option<&[u16]>, radix: i32, bits: u32) -> ParseResult<i64> {
+    let chars = chars.ok_or_else(|| NumberFormatError("Cannot parse null string".into()))?;
+    check_radix(radix)?;
+    let max = (1u64 << (bits - 1)) - 1;
+    Ok(parse_digits(chars, radix, max, max + 1)? as i64)
+}
+
+/// `Integer.parseUnsignedInt` and `Long.parseUnsignedLong`, the value is returned as the signed
+/// type of `bits` bits with the same bits
+pub(super) fn parse_unsigned(chars: Option<&[u16]>, radix: i32, bits: u32) -> ParseResult<i64> {
+    let chars = chars.ok_or_else(|| NumberFormatError("Cannot parse null string".into()))?;
+    check_radix(radix)?;
+    let text = || String::from_utf16_lossy(chars);
+    if chars.first() == Some(&(b'-' as u16)) {
+        return Err(NumberFormatError(format!("Illegal leading minus sign on unsigned string {}.", text())));
+    }
+    let max = u64::MAX >> (64 - bits);
+    let name = if bits < 64 { "int" } else { "long" };
+    let exceeds = || NumberFormatError(format!("String value {} exceeds range of unsigned {name}.", text()));
+    // an `int` is parsed as a `long` first, what does not fit that is not a number at all
+    let parse_max = if bits < 64 { i64::MAX as u64 } else { u64::MAX };
+    let value = match parse_digits(chars, radix, parse_max, 0) {
+        Ok(value) => value as u64,
+        Err(_) if bits == 64 && is_unsigned_number(chars, radix) => return Err(exceeds()),
+        Err(error) => return Err(error),
+    };
+    if value > max {
+        return Err(exceeds());
+    }
+    Ok(value as i64)
+}
+
+/// Whether a string is an optional plus sign and at least one digit of the radix
+fn is_unsigned_number(chars: &[u16], radix: i32) -> bool {
+    let digits = chars.strip_prefix(&[b'+' as u16]).unwrap_or(chars);
+    !digits.is_empty() && digits.iter().all(|&c| digit(c as u32, radix) >= 0)
+}
+
+/// A string `Double.parseDouble` and `Float.parseFloat` accept, before rounding to the type
+#[derive(Debug)]
+pub(super) enum Floating {
+    NaN,
+    Infinity { negative: bool },
+    /// Decimal digits with a point and an exponent, normalized to what `str::parse` takes
+    Decimal(String),
+    /// `significand * 2^exponent`, `sticky` if the significand left out bits that are not 0
+    Hex { negative: bool, significand: u64, sticky: bool, exponent: i64 },
+}
+
+impl Floating {
+    /// Parses a string after trimming what `String.trim` does: the characters up to `' '`
+    pub(super) fn parse(chars: &[u16]) -> ParseResult<Self> {
+        let start = chars.iter().position(|&c| c > b' ' as u16).unwrap_or(chars.len());
+        let end = chars.iter().rposition(|&c| c > b' ' as u16).map_or(start, |i| i + 1);
+        let trimmed = &chars[start..end];
+        if trimmed.is_empty() {
+            return Err(NumberFormatError("empty String".into()));
+        }
+        let invalid = || NumberFormatError::input(trimmed, 10);
+        let text: String = trimmed.iter()
+            .map(|&c| char::from_u32(c as u32).filter(char::is_ascii))
+            .collect::<Option<_>>()
+            .ok_or_else(invalid)?;
+        let (negative, unsigned) = match text.as_bytes()[0] {
+            b'-' => (true, &text[1..]),
+            b'+' => (false, &text[1..]),
+            _ => (false, &text[..]),
+        };
+        match unsigned {
+            "NaN" => return Ok(Self::NaN),
+            "Infinity" => return Ok(Self::Infinity { negative }),
+            _ => (),
+        }
+        let mut scanner = Scanner(unsigned.as_bytes());
+        let parsed = if scanner.eat(b"0x") || scanner.eat(b"0X") {
+            scanner.hex(negative)
+        } else {
+            scanner.decimal(negative)
+        };
+        // a type suffix may follow, and nothing else
+        let _ = scanner.eat(b"f") || scanner.eat(b"F") || scanner.eat(b"d") || scanner.eat(b"D");
+        match parsed {
+            Some(parsed) if scanner.0.is_empty() => Ok(parsed),
+            _ => Err(invalid()),
+        }
+    }
+    pub(super) fn to_f64(&self) -> f64 {
+        match *self {
+            Self::NaN => f64::NAN,
+            Self::Infinity { negative } => if negative { f64::NEG_INFINITY } else { f64::INFINITY },
+            Self::Decimal(ref text) => text.parse().unwrap(),
+            Self::Hex { negative, significand, sticky, exponent } => {
+                let bits = round_binary(significand, sticky, exponent, 53, -1022, 1023);
+                f64::from_bits(bits | (negative as u64) << 63)
+            }
+        }
+    }
+    pub(super) fn to_f32(&self) -> f32 {
+        match *self {
+            Self::NaN => f32::NAN,
+            Self::Infinity { negative } => if negative { f32::NEG_INFINITY } else { f32::INFINITY },
+            Self::Decimal(ref text) => text.parse().unwrap(),
+            Self::Hex { negative, significand, sticky, exponent } => {
+                let bits = round_binary(significand, sticky, exponent, 24, -126, 127) as u32;
+                f32::from_bits(bits | (negative as u32) << 31)
+            }
+        }
+    }
+}
+
+struct Scanner<'a>(&'a [u8]);
+
+impl Scanner<'_> {
+    fn eat(&mut self, prefix: &[u8]) -> bool {
+        let eaten = self.0.starts_with(prefix);
+        if eaten {
+            self.0 = &self.0[prefix.len()..];
+        }
+        eaten
+    }
+    /// Takes the longest prefix of characters that match
+    fn take_while(&mut self, f: impl Fn(u8) -> bool) -> &[u8] {
+        let n = self.0.iter().position(|&c| !f(c)).unwrap_or(self.0.len());
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        taken
+    }
+    /// A signed decimal exponent, saturated far beyond what any float can have
+    fn exponent(&mut self) -> Option<i64> {
+        let negative = self.eat(b"-");
+        if !negative {
+            self.eat(b"+");
+        }
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        if digits.is_empty() {
+            return None;
+        }
+        let magnitude = digits.iter().fold(0i64, |e, &d| (e * 10 + (d - b'0') as i64).min(1 << 40));
+        Some(if negative { -magnitude } else { magnitude })
+    }
+    fn decimal(&mut self, negative: bool) -> Option<Floating> {
+        let integer = self.take_while(|c| c.is_ascii_digit()).to_vec();
+        let fraction = if self.eat(b".") { self.take_while(|c| c.is_ascii_digit()).to_vec() } else { Vec::new() };
+        if integer.is_empty() && fraction.is_empty() {
+            return None;
+        }
+        let exponent = if self.eat(b"e") || self.eat(b"E") { self.exponent()? } else { 0 };
+        let digits = |digits: Vec<u8>| if digits.is_empty() { "0".into() } else { String::from_utf8(digits).unwrap() };
+        let sign = if negative { "-" } else { "" };
+        Some(Floating::Decimal(format!("{sign}{}.{}e{exponent}", digits(integer), digits(fraction))))
+    }
+    fn hex(&mut self, negative: bool) -> Option<Floating> {
+        let integer = self.take_while(|c| c.is_ascii_hexdigit()).to_vec();
+        let fraction = if self.eat(b".") { self.take_while(|c| c.is_ascii_hexdigit()).to_vec() } else { Vec::new() };
+        if integer.is_empty() && fraction.is_empty() || !(self.eat(b"p") || self.eat(b"P")) {
+            return None;
+        }
+        let mut exponent = self.exponent()?;
+        let (mut significand, mut sticky, mut taken) = (0u64, false, 0);
+        let digits = integer.iter().map(|&d| (d, false)).chain(fraction.iter().map(|&d| (d, true)));
+        for (d, in_fraction) in digits {
+            let d = (d as char).to_digit(16).unwrap() as u64;
+            if taken < 15 && (significand != 0 || d != 0) {
+                // 15 digits are enough for a float of any precision plus the rounding bits
+                significand = significand << 4 | d;
+                taken += 1;
+                exponent -= 4 * in_fraction as i64;
+            } else if significand == 0 {
+                exponent -= 4 * in_fraction as i64;
+            } else {
+                sticky |= d != 0;
+                exponent += 4 * !in_fraction as i64;
+            }
+        }
+        Some(Floating::Hex { negative, significand, sticky, exponent })
+    }
+}
+
+/// The bits of the positive binary float nearest to `significand * 2^exponent`, ties to even.
+///
+/// The format has `precision` bits of significand including the implicit one and normal
+/// exponents from `min_exponent` to `max_exponent`.
+fn round_binary(significand: u64, sticky: bool, exponent: i64, precision: u32, min_exponent: i64, max_exponent: i64) -> u64 {
+    let infinity = ((2 * max_exponent + 1) as u64) << (precision - 1);
+    if significand == 0 {
+        return 0;
+    }
+    let top = 63 - significand.leading_zeros() as i64;
+    let value_exponent = top + exponent;
+    if value_exponent > max_exponent {
+        return infinity;
+    }
+    let normal = value_exponent >= min_exponent;
+    // the number of low bits of the significand that do not fit
+    let shift = if normal {
+        top - (precision as i64 - 1)
+    } else {
+        (min_exponent - (precision as i64 - 1)) - exponent
+    };
+    let kept = if shift <= 0 {
+        significand << -shift
+    } else if shift > 64 {
+        // less than half of the smallest subnormal
+        0
+    } else {
+        let significand = significand as u128;
+        let kept = (significand >> shift) as u64;
+        let rest = significand & ((1 << shift) - 1);
+        let half = 1 << (shift - 1);
+        let round_up = rest > half || rest == half && (sticky || kept & 1 == 1);
+        kept + round_up as u64
+    };
+    let bits = if normal {
+        (((value_exponent - min_exponent) as u64) << (precision - 1)) + kept
+    } else {
+        kept
+    };
+    bits.min(infinity)
+}
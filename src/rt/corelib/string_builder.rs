@@ -0,0 +1,613 @@
+//! `java.lang.StringBuilder`.
+//!
+//! The characters are kept in a `char[]` that grows like in the JDK, to twice its capacity plus
+//! two or to what is needed if that is more, so `capacity()` reports the same:
+//!
+//! | offset | content                   |
+//! |--------|---------------------------|
+//! | 0      | object header             |
+//! | 8      | `char[] value`            |
+//! | 12     | `int count`, the length   |
+//!
+//! An operation works on the builder at the top of the operand stack, where it stays while the
+//! array grows so that a collection in between keeps it in place. Once an exception is thrown the
+//! stack belongs to the handler, so the builder is popped before throwing and never after.
+
+use super::{Members, Result, Runtime, RuntimeCtx, Value, ARRAY_DATA_OFFSET, OBJECT_CLASS};
+use super::super::{string, values_into_f64, values_into_u64, LoadedClass, ARRAY_LENGTH_OFFSET, OBJECT_HEADER_SIZE};
+
+const VALUE_OFFSET: u32 = OBJECT_HEADER_SIZE as u32;
+const COUNT_OFFSET: u32 = VALUE_OFFSET + 4;
+const DEFAULT_CAPACITY: u32 = 16;
+/// The most characters a builder holds, the longest array the JDK makes
+const MAX_CAPACITY: u32 = i32::MAX as u32 - 8;
+
+pub(super) fn class(runtime: &mut Runtime) -> Result<LoadedClass> {
+    let interfaces = ["java/lang/CharSequence", "java/lang/Comparable"]
+        .into_iter()
+        .map(|name| runtime.load_class(name))
+        .collect::<Result<_>>()?;
+    Ok(LoadedClass {
+        interfaces,
+        data_size: COUNT_OFFSET as u16 + 4,
+        ref_offsets: Box::new([VALUE_OFFSET as u16]),
+        ..members().into_class("java/lang/StringBuilder", OBJECT_CLASS)
+    })
+}
+
+fn value(ctx: &RuntimeCtx, this: Value) -> Value {
+    Value(ctx.read_u32_ref(this.offset(VALUE_OFFSET)).unwrap())
+}
+fn count(ctx: &RuntimeCtx, this: Value) -> u32 {
+    ctx.read_u32_ref(this.offset(COUNT_OFFSET)).unwrap()
+}
+fn capacity(ctx: &RuntimeCtx, this: Value) -> u32 {
+    ctx.read_u32_ref(value(ctx, this).offset(ARRAY_LENGTH_OFFSET)).unwrap()
+}
+fn content(ctx: &RuntimeCtx, this: Value) -> Vec<u16> {
+    let count = count(ctx, this) as usize;
+    if count == 0 {
+        // a builder being initialized has no array yet
+        return Vec::new();
+    }
+    let bytes = ctx.ref_bytes(value(ctx, this).offset(ARRAY_DATA_OFFSET), 2 * count).unwrap();
+    bytes.chunks_exact(2).map(|c| u16::from_ne_bytes([c[0], c[1]])).collect()
+}
+
+/// Gives the builder at the top of the stack a new array of `capacity` characters that holds
+/// the content, returning `false` if there is no room for it and an `OutOfMemoryError` is thrown
+fn reallocate(ctx: &mut RuntimeCtx, capacity: u32) -> Result<bool> {
+    let class = ctx.runtime.load_class("[C")?;
+    if !ctx.make_room(ctx.array_size(class, capacity))? {
+        return Ok(false);
+    }
+    let this = ctx.top();
+    let chars = content(ctx, this);
+    let array = ctx.new_array(class, capacity);
+    ctx.write_u32_ref(this.offset(VALUE_OFFSET), array.into_u32());
+    write_chars(ctx, this, 0, &chars);
+    Ok(true)
+}
+/// Makes the builder at the top of the stack hold at least `min_capacity` characters, see
+/// [`reallocate`]
+fn ensure_capacity(ctx: &mut RuntimeCtx, min_capacity: u64) -> Result<bool> {
+    let this = ctx.top();
+    let capacity = capacity(ctx, this);
+    if min_capacity <= capacity as u64 {
+        return Ok(true);
+    }
+    if min_capacity > MAX_CAPACITY as u64 {
+        ctx.throw_new("java/lang/OutOfMemoryError", Some("Requested array size exceeds VM limit".into()))?;
+        return Ok(false);
+    }
+    let grown = (2 * capacity as u64 + 2).clamp(min_capacity, MAX_CAPACITY as u64);
+    reallocate(ctx, grown as u32)
+}
+fn write_chars(ctx: &mut RuntimeCtx, this: Value, index: u32, chars: &[u16]) {
+    let array = value(ctx, this);
+    let bytes = ctx.ref_bytes_mut(array.offset(ARRAY_DATA_OFFSET + 2 * index), 2 * chars.len()).unwrap();
+    for (bytes, c) in bytes.chunks_exact_mut(2).zip(chars) {
+        bytes.copy_from_slice(&c.to_ne_bytes());
+    }
+}
+/// Replaces the characters from `start` to `end` of the builder at the top of the stack, which
+/// have to be in range, returning `false` if the builder could not grow
+fn splice(ctx: &mut RuntimeCtx, start: u32, end: u32, replacement: &[u16]) -> Result<bool> {
+    let this = ctx.top();
+    let count = count(ctx, this);
+    let new_count = (count - (end - start)) as u64 + replacement.len() as u64;
+    if !ensure_capacity(ctx, new_count)? {
+        return Ok(false);
+    }
+    let array = value(ctx, this);
+    let data = ctx.ref_bytes_mut(array.offset(ARRAY_DATA_OFFSET), 2 * new_count.max(count as u64) as usize).unwrap();
+    let tail = 2 * end as usize..2 * count as usize;
+    data.copy_within(tail, 2 * (start as usize + replacement.len()));
+    write_chars(ctx, this, start, replacement);
+    ctx.write_u32_ref(this.offset(COUNT_OFFSET), new_count as u32);
+    Ok(true)
+}
+/// Appends to the builder at the top of the stack and leaves it there as the result
+fn append(ctx: &mut RuntimeCtx, chars: &[u16]) -> Result<()> {
+    let count = count(ctx, ctx.top());
+    splice(ctx, count, count, chars)?;
+    Ok(())
+}
+/// Pops the offset of an `insert` and inserts at it in the builder below, leaving that as the
+/// result
+fn insert(ctx: &mut RuntimeCtx, chars: &[u16]) -> Result<()> {
+    let offset = ctx.pop().into_i32();
+    let count = count(ctx, ctx.top());
+    if offset < 0 || offset as u32 > count {
+        ctx.pop();
+        return throw_index(ctx, format!("offset {offset}, length {count}"));
+    }
+    splice(ctx, offset as u32, offset as u32, chars)?;
+    Ok(())
+}
+
+fn throw_index(ctx: &mut RuntimeCtx, message: String) -> Result<()> {
+    ctx.throw_new("java/lang/StringIndexOutOfBoundsException", Some(message))
+}
+/// The message for an index of a character that is out of bounds
+fn index_error(index: i32, count: u32) -> Option<String> {
+    (index < 0 || index as u32 >= count).then(|| format!("index {index}, length {count}"))
+}
+/// The message for a range of characters that is out of bounds
+fn range_error(start: i32, end: i32, count: u32) -> Option<String> {
+    (start < 0 || start > end || end as u32 > count).then(|| format!("start {start}, end {end}, length {count}"))
+}
+
+/// Pops an argument for `String.valueOf`, running `toString` while it is still on the stack.
+///
+/// Returns `None` if `toString` threw.
+fn pop_string_value_of(ctx: &mut RuntimeCtx) -> Result<Option<Vec<u16>>> {
+    let obj = ctx.top();
+    let chars = ctx.string_value_of(obj)?;
+    if chars.is_some() {
+        ctx.pop();
+    }
+    Ok(chars)
+}
+/// The content of a `char[]`, `None` for null
+fn read_char_array(ctx: &RuntimeCtx, array: Value) -> Option<Vec<u16>> {
+    if array == Value::NULL {
+        return None;
+    }
+    let length = ctx.read_u32_ref(array.offset(ARRAY_LENGTH_OFFSET)).unwrap() as usize;
+    let bytes = ctx.ref_bytes(array.offset(ARRAY_DATA_OFFSET), 2 * length).unwrap();
+    Some(bytes.chunks_exact(2).map(|c| u16::from_ne_bytes([c[0], c[1]])).collect())
+}
+fn encode(text: &str) -> Vec<u16> {
+    text.encode_utf16().collect()
+}
+
+/// Initializes the builder below the arguments with a capacity and leaves it on the stack
+fn init(ctx: &mut RuntimeCtx, capacity: u32) -> Result<bool> {
+    let this = ctx.top();
+    ctx.write_u32_ref(this.offset(COUNT_OFFSET), 0);
+    reallocate(ctx, capacity)
+}
+fn builder_init(ctx: &mut RuntimeCtx) -> Result<()> {
+    if init(ctx, DEFAULT_CAPACITY)? {
+        ctx.pop();
+    }
+    Ok(())
+}
+fn builder_init_capacity(ctx: &mut RuntimeCtx) -> Result<()> {
+    let capacity = ctx.pop().into_i32();
+    if capacity < 0 {
+        ctx.pop();
+        return ctx.throw_new("java/lang/NegativeArraySizeException", Some(capacity.to_string()));
+    }
+    if init(ctx, capacity as u32)? {
+        ctx.pop();
+    }
+    Ok(())
+}
+/// `StringBuilder(String)` and `StringBuilder(CharSequence)`, with room for 16 more characters
+fn builder_init_chars(ctx: &mut RuntimeCtx) -> Result<()> {
+    if ctx.top() == Value::NULL {
+        ctx.pop();
+        ctx.pop();
+        return ctx.throw_null_pointer();
+    }
+    let Some(chars) = pop_string_value_of(ctx)? else { return Ok(()) };
+    let capacity = (chars.len() as u64 + DEFAULT_CAPACITY as u64).min(MAX_CAPACITY as u64);
+    if init(ctx, capacity as u32)? && splice(ctx, 0, 0, &chars)? {
+        ctx.pop();
+    }
+    Ok(())
+}
+
+/// `append(Object)`, `append(String)` and `append(CharSequence)`, which all append `"null"` for null
+fn builder_append_object(ctx: &mut RuntimeCtx) -> Result<()> {
+    let Some(chars) = pop_string_value_of(ctx)? else { return Ok(()) };
+    append(ctx, &chars)
+}
+fn builder_append_char_sequence_range(ctx: &mut RuntimeCtx) -> Result<()> {
+    let end = ctx.pop().into_i32();
+    let start = ctx.pop().into_i32();
+    let Some(chars) = pop_string_value_of(ctx)? else { return Ok(()) };
+    if start < 0 || start > end || end as usize > chars.len() {
+        ctx.pop();
+        let message = format!("start {start}, end {end}, length {}", chars.len());
+        return ctx.throw_new("java/lang/IndexOutOfBoundsException", Some(message));
+    }
+    append(ctx, &chars[start as usize..end as usize])
+}
+fn builder_append_char_array(ctx: &mut RuntimeCtx) -> Result<()> {
+    let array = ctx.pop();
+    let Some(chars) = read_char_array(ctx, array) else {
+        ctx.pop();
+        return ctx.throw_null_pointer();
+    };
+    append(ctx, &chars)
+}
+fn builder_append_char_array_range(ctx: &mut RuntimeCtx) -> Result<()> {
+    let length = ctx.pop().into_i32();
+    let offset = ctx.pop().into_i32();
+    let array = ctx.pop();
+    let Some(chars) = read_char_array(ctx, array) else {
+        ctx.pop();
+        return ctx.throw_null_pointer();
+    };
+    let end = offset.wrapping_add(length);
+    if let Some(message) = range_error(offset, end, chars.len() as u32) {
+        ctx.pop();
+        return throw_index(ctx, message);
+    }
+    append(ctx, &chars[offset as usize..end as usize])
+}
+fn builder_append_boolean(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = ctx.pop().into_u32() != 0;
+    append(ctx, &encode(&value.to_string()))
+}
+fn builder_append_char(ctx: &mut RuntimeCtx) -> Result<()> {
+    let c = ctx.pop().into_u16();
+    append(ctx, &[c])
+}
+fn builder_append_int(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = ctx.pop().into_i32();
+    append(ctx, &encode(&value.to_string()))
+}
+fn builder_append_long(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = values_into_u64(ctx.pop2()) as i64;
+    append(ctx, &encode(&value.to_string()))
+}
+fn builder_append_float(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = ctx.pop().into_f32();
+    append(ctx, &encode(&string::float_to_string(value)))
+}
+fn builder_append_double(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = values_into_f64(ctx.pop2());
+    append(ctx, &encode(&string::double_to_string(value)))
+}
+fn builder_append_code_point(ctx: &mut RuntimeCtx) -> Result<()> {
+    let code_point = ctx.pop().into_u32();
+    let mut buf = [0; 2];
+    let chars = match char::from_u32(code_point) {
+        Some(c) => &*c.encode_utf16(&mut buf),
+        // a lone surrogate is appended as it is
+        None if code_point <= 0xFFFF => &[code_point as u16][..],
+        None => {
+            ctx.pop();
+            let message = format!("Not a valid Unicode code point: 0x{code_point:X}");
+            return ctx.throw_new("java/lang/IllegalArgumentException", Some(message));
+        }
+    };
+    append(ctx, chars)
+}
+
+/// `insert(int, Object)`, `insert(int, String)` and `insert(int, CharSequence)`
+fn builder_insert_object(ctx: &mut RuntimeCtx) -> Result<()> {
+    let Some(chars) = pop_string_value_of(ctx)? else { return Ok(()) };
+    insert(ctx, &chars)
+}
+fn builder_insert_char_array(ctx: &mut RuntimeCtx) -> Result<()> {
+    let array = ctx.pop();
+    let Some(chars) = read_char_array(ctx, array) else {
+        ctx.pop();
+        ctx.pop();
+        return ctx.throw_null_pointer();
+    };
+    insert(ctx, &chars)
+}
+fn builder_insert_boolean(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = ctx.pop().into_u32() != 0;
+    insert(ctx, &encode(&value.to_string()))
+}
+fn builder_insert_char(ctx: &mut RuntimeCtx) -> Result<()> {
+    let c = ctx.pop().into_u16();
+    insert(ctx, &[c])
+}
+fn builder_insert_int(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = ctx.pop().into_i32();
+    insert(ctx, &encode(&value.to_string()))
+}
+fn builder_insert_long(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = values_into_u64(ctx.pop2()) as i64;
+    insert(ctx, &encode(&value.to_string()))
+}
+fn builder_insert_float(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = ctx.pop().into_f32();
+    insert(ctx, &encode(&string::float_to_string(value)))
+}
+fn builder_insert_double(ctx: &mut RuntimeCtx) -> Result<()> {
+    let value = values_into_f64(ctx.pop2());
+    insert(ctx, &encode(&string::double_to_string(value)))
+}
+
+fn builder_length(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    ctx.push(count(ctx, this) as i32);
+    Ok(())
+}
+fn builder_capacity(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    ctx.push(capacity(ctx, this) as i32);
+    Ok(())
+}
+fn builder_is_empty(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    ctx.push(count(ctx, this) == 0);
+    Ok(())
+}
+fn builder_char_at(ctx: &mut RuntimeCtx) -> Result<()> {
+    let index = ctx.pop().into_i32();
+    let this = ctx.pop();
+    if let Some(message) = index_error(index, count(ctx, this)) {
+        return throw_index(ctx, message);
+    }
+    ctx.push(content(ctx, this)[index as usize] as i32);
+    Ok(())
+}
+fn builder_code_point_at(ctx: &mut RuntimeCtx) -> Result<()> {
+    let index = ctx.pop().into_i32();
+    let this = ctx.pop();
+    if let Some(message) = index_error(index, count(ctx, this)) {
+        return throw_index(ctx, message);
+    }
+    let chars = content(ctx, this);
+    let code_point = char::decode_utf16(chars[index as usize..].iter().copied()).next().unwrap()
+        .map_or(chars[index as usize] as u32, u32::from);
+    ctx.push(code_point as i32);
+    Ok(())
+}
+fn builder_set_char_at(ctx: &mut RuntimeCtx) -> Result<()> {
+    let c = ctx.pop().into_u16();
+    let index = ctx.pop().into_i32();
+    let this = ctx.pop();
+    if let Some(message) = index_error(index, count(ctx, this)) {
+        return throw_index(ctx, message);
+    }
+    write_chars(ctx, this, index as u32, &[c]);
+    Ok(())
+}
+fn builder_delete_char_at(ctx: &mut RuntimeCtx) -> Result<()> {
+    let index = ctx.pop().into_i32();
+    if let Some(message) = index_error(index, count(ctx, ctx.top())) {
+        ctx.pop();
+        return throw_index(ctx, message);
+    }
+    splice(ctx, index as u32, index as u32 + 1, &[])?;
+    Ok(())
+}
+fn builder_delete(ctx: &mut RuntimeCtx) -> Result<()> {
+    let end = ctx.pop().into_i32();
+    let start = ctx.pop().into_i32();
+    let count = count(ctx, ctx.top());
+    // the end may be past the content
+    let end = end.min(count as i32);
+    if let Some(message) = range_error(start, end, count) {
+        ctx.pop();
+        return throw_index(ctx, message);
+    }
+    splice(ctx, start as u32, end as u32, &[])?;
+    Ok(())
+}
+fn builder_replace(ctx: &mut RuntimeCtx) -> Result<()> {
+    let string = ctx.pop();
+    let end = ctx.pop().into_i32();
+    let start = ctx.pop().into_i32();
+    let count = count(ctx, ctx.top());
+    let end = end.min(count as i32);
+    if let Some(message) = range_error(start, end, count) {
+        ctx.pop();
+        return throw_index(ctx, message);
+    }
+    let Some(chars) = ctx.read_string_chars(string) else {
+        ctx.pop();
+        return ctx.throw_null_pointer();
+    };
+    splice(ctx, start as u32, end as u32, &chars)?;
+    Ok(())
+}
+/// `reverse`, which keeps the surrogates of a pair in order
+fn builder_reverse(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.top();
+    let chars = content(ctx, this);
+    let mut reversed = Vec::with_capacity(chars.len());
+    let mut i = chars.len();
+    while i > 0 {
+        let pair = i >= 2 && (0xDC00..0xE000).contains(&chars[i - 1]) && (0xD800..0xDC00).contains(&chars[i - 2]);
+        let start = if pair { i - 2 } else { i - 1 };
+        reversed.extend_from_slice(&chars[start..i]);
+        i = start;
+    }
+    write_chars(ctx, this, 0, &reversed);
+    Ok(())
+}
+fn builder_set_length(ctx: &mut RuntimeCtx) -> Result<()> {
+    let length = ctx.pop().into_i32();
+    if length < 0 {
+        ctx.pop();
+        return throw_index(ctx, format!("String index out of range: {length}"));
+    }
+    let count = count(ctx, ctx.top());
+    // a longer builder is padded with '\0'
+    let padding = vec![0; (length as u32).saturating_sub(count) as usize];
+    if splice(ctx, count.min(length as u32), count, &padding)? {
+        ctx.pop();
+    }
+    Ok(())
+}
+fn builder_ensure_capacity(ctx: &mut RuntimeCtx) -> Result<()> {
+    let min_capacity = ctx.pop().into_i32();
+    if min_capacity <= 0 || ensure_capacity(ctx, min_capacity as u64)? {
+        ctx.pop();
+    }
+    Ok(())
+}
+fn builder_trim_to_size(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.top();
+    let count = count(ctx, this);
+    if count >= capacity(ctx, this) || reallocate(ctx, count)? {
+        ctx.pop();
+    }
+    Ok(())
+}
+
+/// Pops the string to search for, throwing a `NullPointerException` and returning `None` if it
+/// is null
+fn pop_needle(ctx: &mut RuntimeCtx) -> Result<Option<Vec<u16>>> {
+    let needle = ctx.pop();
+    let chars = ctx.read_string_chars(needle);
+    if chars.is_none() {
+        ctx.pop();
+        ctx.throw_null_pointer()?;
+    }
+    Ok(chars)
+}
+/// `indexOf`, the first match at or after `from`, which may be anywhere
+fn index_of(haystack: &[u16], needle: &[u16], from: i32) -> i32 {
+    let from = from.max(0) as usize;
+    if from > haystack.len() {
+        return if needle.is_empty() { haystack.len() as i32 } else { -1 };
+    }
+    (from..=haystack.len().saturating_sub(needle.len()))
+        .find(|&i| haystack[i..].starts_with(needle))
+        .map_or(-1, |i| i as i32)
+}
+/// `lastIndexOf`, the last match at or before `from`, which may be anywhere
+fn last_index_of(haystack: &[u16], needle: &[u16], from: i32) -> i32 {
+    let Some(last) = haystack.len().checked_sub(needle.len()) else { return -1 };
+    if from < 0 {
+        return -1;
+    }
+    (0..=last.min(from as usize))
+        .rev()
+        .find(|&i| haystack[i..].starts_with(needle))
+        .map_or(-1, |i| i as i32)
+}
+fn builder_index_of(ctx: &mut RuntimeCtx) -> Result<()> {
+    let Some(needle) = pop_needle(ctx)? else { return Ok(()) };
+    let this = ctx.pop();
+    ctx.push(index_of(&content(ctx, this), &needle, 0));
+    Ok(())
+}
+fn builder_index_of_from(ctx: &mut RuntimeCtx) -> Result<()> {
+    let from = ctx.pop().into_i32();
+    let Some(needle) = pop_needle(ctx)? else { return Ok(()) };
+    let this = ctx.pop();
+    ctx.push(index_of(&content(ctx, this), &needle, from));
+    Ok(())
+}
+fn builder_last_index_of(ctx: &mut RuntimeCtx) -> Result<()> {
+    let Some(needle) = pop_needle(ctx)? else { return Ok(()) };
+    let this = ctx.pop();
+    ctx.push(last_index_of(&content(ctx, this), &needle, i32::MAX));
+    Ok(())
+}
+fn builder_last_index_of_from(ctx: &mut RuntimeCtx) -> Result<()> {
+    let from = ctx.pop().into_i32();
+    let Some(needle) = pop_needle(ctx)? else { return Ok(()) };
+    let this = ctx.pop();
+    ctx.push(last_index_of(&content(ctx, this), &needle, from));
+    Ok(())
+}
+
+fn substring(ctx: &mut RuntimeCtx, this: Value, start: i32, end: i32) -> Result<()> {
+    if let Some(message) = range_error(start, end, count(ctx, this)) {
+        return throw_index(ctx, message);
+    }
+    let chars = content(ctx, this);
+    let Some(string) = ctx.try_new_string(&chars[start as usize..end as usize])? else { return Ok(()) };
+    ctx.push(string);
+    Ok(())
+}
+fn builder_substring(ctx: &mut RuntimeCtx) -> Result<()> {
+    let start = ctx.pop().into_i32();
+    let this = ctx.pop();
+    substring(ctx, this, start, count(ctx, this) as i32)
+}
+/// `substring(int, int)` and `subSequence`
+fn builder_substring_end(ctx: &mut RuntimeCtx) -> Result<()> {
+    let end = ctx.pop().into_i32();
+    let start = ctx.pop().into_i32();
+    let this = ctx.pop();
+    substring(ctx, this, start, end)
+}
+fn builder_to_string(ctx: &mut RuntimeCtx) -> Result<()> {
+    let this = ctx.pop();
+    let chars = content(ctx, this);
+    let Some(string) = ctx.try_new_string(&chars)? else { return Ok(()) };
+    ctx.push(string);
+    Ok(())
+}
+/// `compareTo`, lexicographically by character like `String.compareTo`
+fn builder_compare_to(ctx: &mut RuntimeCtx) -> Result<()> {
+    let other = ctx.pop();
+    let this = ctx.pop();
+    if other == Value::NULL {
+        return ctx.throw_null_pointer();
+    }
+    if ctx.get_class_id(other) != ctx.get_class_id(this) {
+        let message = format!("class {} cannot be cast to class java.lang.StringBuilder", ctx.get_class_name(other).replace('/', "."));
+        return ctx.throw_new("java/lang/ClassCastException", Some(message));
+    }
+    let (a, b) = (content(ctx, this), content(ctx, other));
+    let difference = a.iter()
+        .zip(&b)
+        .find(|(x, y)| x != y)
+        .map_or(a.len() as i32 - b.len() as i32, |(&x, &y)| x as i32 - y as i32);
+    ctx.push(difference);
+    Ok(())
+}
+
+fn members() -> Members {
+    const THIS: &str = "Ljava/lang/StringBuilder;";
+    let mut members = Members::new();
+    members
+        .method("<init>", "()V", builder_init)
+        .method("<init>", "(I)V", builder_init_capacity)
+        .method("<init>", "(Ljava/lang/String;)V", builder_init_chars)
+        .method("<init>", "(Ljava/lang/CharSequence;)V", builder_init_chars);
+    for arg in ["Ljava/lang/Object;", "Ljava/lang/String;", "Ljava/lang/CharSequence;"] {
+        members
+            .method("append", &format!("({arg}){THIS}"), builder_append_object)
+            .method("insert", &format!("(I{arg}){THIS}"), builder_insert_object);
+    }
+    members
+        .method("append", &format!("(Ljava/lang/CharSequence;II){THIS}"), builder_append_char_sequence_range)
+        .method("append", &format!("([C){THIS}"), builder_append_char_array)
+        .method("append", &format!("([CII){THIS}"), builder_append_char_array_range)
+        .method("append", &format!("(Z){THIS}"), builder_append_boolean)
+        .method("append", &format!("(C){THIS}"), builder_append_char)
+        .method("append", &format!("(I){THIS}"), builder_append_int)
+        .method("append", &format!("(J){THIS}"), builder_append_long)
+        .method("append", &format!("(F){THIS}"), builder_append_float)
+        .method("append", &format!("(D){THIS}"), builder_append_double)
+        .method("appendCodePoint", &format!("(I){THIS}"), builder_append_code_point)
+        .method("insert", &format!("(I[C){THIS}"), builder_insert_char_array)
+        .method("insert", &format!("(IZ){THIS}"), builder_insert_boolean)
+        .method("insert", &format!("(IC){THIS}"), builder_insert_char)
+        .method("insert", &format!("(II){THIS}"), builder_insert_int)
+        .method("insert", &format!("(IJ){THIS}"), builder_insert_long)
+        .method("insert", &format!("(IF){THIS}"), builder_insert_float)
+        .method("insert", &format!("(ID){THIS}"), builder_insert_double)
+        .method("length", "()I", builder_length)
+        .method("capacity", "()I", builder_capacity)
+        .method("isEmpty", "()Z", builder_is_empty)
+        .method("charAt", "(I)C", builder_char_at)
+        .method("codePointAt", "(I)I", builder_code_point_at)
+        .method("setCharAt", "(IC)V", builder_set_char_at)
+        .method("deleteCharAt", &format!("(I){THIS}"), builder_delete_char_at)
+        .method("delete", &format!("(II){THIS}"), builder_delete)
+        .method("replace", &format!("(IILjava/lang/String;){THIS}"), builder_replace)
+        .method("reverse", &format!("(){THIS}"), builder_reverse)
+        .method("setLength", "(I)V", builder_set_length)
+        .method("ensureCapacity", "(I)V", builder_ensure_capacity)
+        .method("trimToSize", "()V", builder_trim_to_size)
+        .method("indexOf", "(Ljava/lang/String;)I", builder_index_of)
+        .method("indexOf", "(Ljava/lang/String;I)I", builder_index_of_from)
+        .method("lastIndexOf", "(Ljava/lang/String;)I", builder_last_index_of)
+        .method("lastIndexOf", "(Ljava/lang/String;I)I", builder_last_index_of_from)
+        .method("substring", "(I)Ljava/lang/String;", builder_substring)
+        .method("substring", "(II)Ljava/lang/String;", builder_substring_end)
+        .method("subSequence", "(II)Ljava/lang/CharSequence;", builder_substring_end)
+        .method("toString", "()Ljava/lang/String;", builder_to_string)
+        .method("compareTo", &format!("({THIS})I"), builder_compare_to)
+        .method("compareTo", "(Ljava/lang/Object;)I", builder_compare_to);
+    members
+}
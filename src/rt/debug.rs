@@ -0,0 +1,908 @@
+//! Debugging Java code from Rust.
+//!
+//! A [`Debugger`] given to [`Runtime::with_debugger`](super::Runtime::with_debugger) is called
+//! before the first instruction of a run, when a thread reaches a breakpoint and when a step ends,
+//! and if it asks for them when classes are loaded and threads start or end. It looks at the
+//! stopped thread through a [`DebugSession`], which also manages the breakpoints, and decides how
+//! the thread goes on. Line numbers and the names of local variables come from the debug
+//! attributes of the class files, which `javac -g` writes.
+//!
+//! Threads, classes and objects are named by indices and [`Value`]s, which a debugger speaking a
+//! protocol like JDWP can use as ids. Objects the debugger wants to refer to later have to be
+//! [pinned](DebugSession::pin), the collector moves the others.
+
+use std::{cell::RefCell, cmp::Ordering, collections::BTreeMap, fmt::{self, Display}, rc::Rc, str::FromStr};
+
+use crate::{class::{AttributeInfo, ClassAccess, ClassFile, FieldAccess, LineNumberEntry, MethodAccess}, descriptor::{FieldDescriptor, MethodDescriptor}};
+
+use super::{embed::read_field, native::stack_value, thread::ThreadState, BytecodeMethod, InitState, JValue, LoadedClass, MethodImpl, Result, Runtime, RuntimeCtx, RuntimeInfo, Value, ARRAY_DATA_OFFSET, ARRAY_LENGTH_OFFSET, OBJECT_CLASS, NATIVE_METHOD_BASE, STRING_CLASS};
+
+/// Called whenever a thread stops, see the [module docs](self)
+pub trait Debugger {
+    /// Looks at the stopped thread and returns how it goes on. An error ends the run, for example
+    /// [`RtError::Exit`](super::RtError::Exit) to quit.
+    fn stopped(&mut self, session: &mut DebugSession<'_, '_>, reason: StopReason) -> Result<Resume>;
+    /// Called every few thousand instructions while the program runs, returning `true` stops the
+    /// current thread with [`StopReason::Suspended`]
+    fn poll(&mut self, _session: &mut DebugSession<'_, '_>) -> Result<bool> {
+        Ok(false)
+    }
+    /// Called when a run ends, with its exit status or error
+    fn finished(&mut self, _result: &Result<i32>) {}
+}
+
+/// Why a thread stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// Before the first instruction of the run
+    Start,
+    /// At the breakpoint with this id
+    Breakpoint(usize),
+    /// A step ended
+    Step,
+    /// The class with this id was loaded and its initializer has not run yet, only if
+    /// [`DebugSession::set_class_load_stops`] asked for it
+    ClassLoaded(u32),
+    /// The thread with this index started, only if [`DebugSession::set_thread_stops`] asked for it
+    ThreadStarted(usize),
+    /// The thread with this index terminated, only if [`DebugSession::set_thread_stops`] asked for
+    /// it
+    ThreadEnded(usize),
+    /// [`Debugger::poll`] asked to stop
+    Suspended,
+}
+
+impl StopReason {
+    /// Whether the thread stopped for a class or another thread rather than for where it is, a
+    /// step goes on over such stops
+    pub fn is_notification(self) -> bool {
+        matches!(self, StopReason::ClassLoaded(_) | StopReason::ThreadStarted(_) | StopReason::ThreadEnded(_))
+    }
+}
+
+/// How a stopped thread goes on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    /// Runs until a breakpoint
+    Continue,
+    /// Stops before the next instruction
+    StepInstruction,
+    /// Stops at the next line, or in a method it calls
+    StepInto,
+    /// Stops at the next line of the method, or in its caller once it returns
+    StepOver,
+    /// Stops in the caller once the method returns
+    StepOut,
+}
+
+/// Where in the methods of a class a breakpoint stops
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    /// The first instruction
+    Entry,
+    /// The start of the code for a line of the source file
+    Line(u16),
+    /// The instruction at this offset in the code
+    Pc(u16),
+}
+
+/// Stops any thread that reaches a location in the methods of a class.
+///
+/// Breakpoints can be set before their class is loaded, they apply once it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    /// Binary name, with dots or slashes
+    pub class: Box<str>,
+    /// Only the methods with this name, all methods if `None`
+    pub method: Option<Box<str>>,
+    pub location: Location,
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.class.replace('/', "."))?;
+        if let Some(method) = &self.method {
+            write!(f, ".{method}")?;
+        }
+        match self.location {
+            Location::Entry => Ok(()),
+            Location::Line(line) => write!(f, ":{line}"),
+            Location::Pc(pc) => write!(f, "@{pc}"),
+        }
+    }
+}
+
+/// A breakpoint that is not written as `Class:line`, `Class.method` or `Class.method@pc`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseBreakpointError;
+
+impl Display for ParseBreakpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("expected Class:line, Class.method or Class.method@pc")
+    }
+}
+
+impl std::error::Error for ParseBreakpointError {}
+
+impl FromStr for Breakpoint {
+    type Err = ParseBreakpointError;
+    /// Parses `Class:line`, `Class.method` or `Class.method@pc`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((class, line)) = s.rsplit_once(':') {
+            let line = line.parse().map_err(|_| ParseBreakpointError)?;
+            if class.is_empty() {
+                return Err(ParseBreakpointError);
+            }
+            return Ok(Breakpoint { class: class.into(), method: None, location: Location::Line(line) });
+        }
+        let (method, location) = match s.rsplit_once('@') {
+            Some((method, pc)) => (method, Location::Pc(pc.parse().map_err(|_| ParseBreakpointError)?)),
+            None => (s, Location::Entry),
+        };
+        match method.rsplit_once('.') {
+            Some((class, method)) if !class.is_empty() && !method.is_empty() => {
+                Ok(Breakpoint { class: class.into(), method: Some(method.into()), location })
+            }
+            _ => Err(ParseBreakpointError),
+        }
+    }
+}
+
+/// A frame of the stopped thread
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameInfo {
+    pub class_id: u32,
+    pub method_id: u16,
+    /// Binary name with slashes
+    pub class: Box<str>,
+    pub method: Box<str>,
+    /// Offset in the code of the method of the next instruction, which in a caller is the one after
+    /// the call
+    pub pc: u16,
+    pub line: Option<u16>,
+    pub source_file: Option<Box<str>>,
+}
+
+impl Display for FrameInfo {
+    /// Like a line of a Java stack trace, `pkg.Class.method(Class.java:12)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}(", self.class.replace('/', "."), self.method)?;
+        match (&self.source_file, self.line) {
+            (Some(file), Some(line)) => write!(f, "{file}:{line})"),
+            (Some(file), None) => write!(f, "{file})"),
+            (None, _) => f.write_str("Unknown Source)"),
+        }
+    }
+}
+
+/// A local variable of a frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct Local {
+    pub slot: u16,
+    /// `None` if the class has no `LocalVariableTable`, then all slots are listed
+    pub name: Option<Box<str>>,
+    /// The raw slot as an `int` if the local has no name
+    pub value: JValue,
+}
+
+/// What a thread is doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadStatus {
+    Runnable,
+    /// Waiting to own a monitor
+    Blocked,
+    /// In `Object.wait` or `Thread.join`
+    Waiting,
+    Sleeping,
+    Terminated,
+}
+
+/// A loaded class
+#[derive(Debug, Clone, Copy)]
+pub struct ClassInfo<'s> {
+    /// Binary name with slashes, or the descriptor for array classes
+    pub name: &'s str,
+    pub access_flags: ClassAccess,
+    /// `None` for `java.lang.Object`
+    pub super_class: Option<u32>,
+    pub interfaces: &'s [u32],
+    pub is_array: bool,
+    pub source_file: Option<&'s str>,
+    /// Whether the static initializer ran
+    pub initialized: bool,
+    /// The `java.lang.Class` of the class
+    pub class_object: Value,
+}
+
+/// A method of a loaded class, abstract ones are not kept
+#[derive(Debug, Clone, Copy)]
+pub struct MethodInfo<'s> {
+    /// The id frames refer to it by, unique within the class
+    pub id: u16,
+    pub name: &'s str,
+    pub descriptor: &'s MethodDescriptor,
+    pub access_flags: MethodAccess,
+    /// Slots the arguments take in the locals, including `this`
+    pub arg_slots: u16,
+    /// Length of the code in bytes, 0 for native methods
+    pub code_length: u32,
+    pub line_numbers: &'s [LineNumberEntry],
+    pub local_variables: &'s [LocalVariable],
+}
+
+/// The debugger of a runtime, shared by its clones
+#[derive(Clone)]
+pub(super) struct SharedDebugger(Rc<RefCell<dyn Debugger>>);
+
+impl SharedDebugger {
+    pub fn new(debugger: impl Debugger + 'static) -> Self {
+        Self(Rc::new(RefCell::new(debugger)))
+    }
+}
+
+impl fmt::Debug for SharedDebugger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedDebugger")
+    }
+}
+
+/// The debug attributes of a method
+#[derive(Debug, Clone)]
+pub(super) struct MethodDebugInfo {
+    pub name: Box<str>,
+    pub descriptor: MethodDescriptor,
+    pub code_length: u32,
+    pub line_numbers: Box<[LineNumberEntry]>,
+    pub local_variables: Box<[LocalVariable]>,
+}
+
+/// An entry of the `LocalVariableTable` of a method
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalVariable {
+    /// The code offsets the variable has a value in
+    pub start_pc: u16,
+    pub length: u16,
+    pub name: Box<str>,
+    pub descriptor: FieldDescriptor,
+    /// The slot
+    pub index: u16,
+}
+
+impl MethodDebugInfo {
+    /// The line of the instruction at a code offset
+    fn line_at(&self, pc: u16) -> Option<u16> {
+        self.line_numbers.iter()
+            .filter(|entry| entry.start_pc <= pc)
+            .max_by_key(|entry| entry.start_pc)
+            .map(|entry| entry.line_number)
+    }
+    fn starts_line(&self, pc: u16) -> bool {
+        self.line_numbers.iter().any(|entry| entry.start_pc == pc)
+    }
+}
+
+/// Collects the line numbers and local variables from the attributes of the `Code` of a method,
+/// ignoring local variables with invalid descriptors
+pub(super) fn method_debug_info(class_file: &ClassFile, name: &str, descriptor: &MethodDescriptor, code_length: u32, code_attributes: &[AttributeInfo]) -> MethodDebugInfo {
+    let mut line_numbers = Vec::new();
+    let mut local_variables = Vec::new();
+    for attribute in code_attributes {
+        match attribute {
+            AttributeInfo::LineNumberTable(entries) => line_numbers.extend_from_slice(entries),
+            AttributeInfo::LocalVariableTable(entries) => local_variables.extend(entries.iter().filter_map(|entry| Some(LocalVariable {
+                start_pc: entry.start_pc,
+                length: entry.length,
+                name: class_file.constant_utf8(entry.name_index)?.into(),
+                descriptor: class_file.constant_fdescriptor(entry.descriptor_index)?,
+                index: entry.index,
+            }))),
+            _ => (),
+        }
+    }
+    MethodDebugInfo {
+        name: name.into(),
+        descriptor: descriptor.clone(),
+        code_length,
+        line_numbers: line_numbers.into_boxed_slice(),
+        local_variables: local_variables.into_boxed_slice(),
+    }
+}
+
+/// The source file of a class and the fields it declares, which the member table does not tell
+/// apart from inherited ones
+#[derive(Debug, Clone, Default)]
+pub(super) struct ClassDebugInfo {
+    pub source_file: Option<Box<str>>,
+    pub fields: Box<[DeclaredField]>,
+}
+
+/// A field a class declares
+#[derive(Debug, Clone)]
+pub struct DeclaredField {
+    pub name: Box<str>,
+    pub descriptor: FieldDescriptor,
+    pub access_flags: FieldAccess,
+    /// In the instance or the static fields
+    pub(super) offset: u16,
+}
+
+impl DeclaredField {
+    pub fn is_static(&self) -> bool {
+        self.access_flags.contains(FieldAccess::STATIC)
+    }
+}
+
+/// Breakpoints and steps of a context with a debugger
+#[derive(Debug)]
+pub(super) struct DebugState {
+    debugger: SharedDebugger,
+    breakpoints: BTreeMap<usize, Breakpoint>,
+    next_id: usize,
+    /// Breakpoint ids by the position in the code they stop at
+    locations: BTreeMap<usize, usize>,
+    /// Number of loaded classes searched for breakpoint locations
+    classes_searched: usize,
+    step: Option<Step>,
+    /// Where the thread last stopped, an instruction that runs again after initializing a class
+    /// does not stop twice
+    last_stop: Option<Position>,
+    started: bool,
+    stop_on_class_load: bool,
+    stop_on_thread_change: bool,
+    /// Threads that started or terminated since the last stop
+    thread_changes: Vec<StopReason>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Position {
+    thread: usize,
+    depth: usize,
+    pc: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Step {
+    resume: Resume,
+    from: Position,
+    line: Option<u16>,
+}
+
+impl Step {
+    /// Whether the step ends before the instruction at `here` in `method`
+    fn ends_at(&self, here: Position, method: &BytecodeMethod) -> bool {
+        // the thread stopped again where the step started, for a class that was loaded
+        if here.thread != self.from.thread || here == self.from {
+            return false;
+        }
+        match self.resume {
+            Resume::Continue => false,
+            Resume::StepInstruction => true,
+            Resume::StepOut => here.depth < self.from.depth,
+            Resume::StepInto if here.depth > self.from.depth => true,
+            Resume::StepInto | Resume::StepOver => match here.depth.cmp(&self.from.depth) {
+                Ordering::Less => true,
+                Ordering::Greater => false,
+                Ordering::Equal => {
+                    let info = &method.debug;
+                    let pc = (here.pc - method.code_location) as u16;
+                    info.line_numbers.is_empty() || info.starts_line(pc) && info.line_at(pc) != self.line
+                }
+            },
+        }
+    }
+}
+
+impl DebugState {
+    pub fn new(debugger: SharedDebugger) -> Self {
+        Self {
+            debugger,
+            breakpoints: BTreeMap::new(),
+            next_id: 1,
+            locations: BTreeMap::new(),
+            classes_searched: 0,
+            step: None,
+            last_stop: None,
+            started: false,
+            stop_on_class_load: false,
+            stop_on_thread_change: false,
+            thread_changes: Vec::new(),
+        }
+    }
+    /// Remembers that a thread started or terminated, to stop for it if that is asked for
+    pub fn thread_changed(&mut self, change: StopReason) {
+        if self.stop_on_thread_change {
+            self.thread_changes.push(change);
+        }
+    }
+    /// Finds the locations of the breakpoints in the classes loaded since the last search, and
+    /// returns the ids of these classes
+    fn search_new_classes(&mut self, runtime: &Runtime) -> std::ops::Range<u32> {
+        let new_classes = self.classes_searched as u32..runtime.classes.len() as u32;
+        for class in &runtime.classes[self.classes_searched..] {
+            for (&id, breakpoint) in &self.breakpoints {
+                add_locations(&mut self.locations, id, breakpoint, class);
+            }
+        }
+        self.classes_searched = runtime.classes.len();
+        new_classes
+    }
+}
+
+/// Adds where a breakpoint stops in the methods of a class
+fn add_locations(locations: &mut BTreeMap<usize, usize>, id: usize, breakpoint: &Breakpoint, class: &LoadedClass) {
+    if *class.name != *breakpoint.class {
+        return;
+    }
+    let RuntimeInfo::Bytecode { method_code, .. } = &class.runtime_info else { return };
+    let methods = method_code.iter().filter(|method| breakpoint.method.as_deref().is_none_or(|name| *method.debug.name == *name));
+    for method in methods {
+        let start = method.code_location;
+        match breakpoint.location {
+            Location::Entry => {
+                locations.insert(start, id);
+            }
+            Location::Line(line) => locations.extend(method.debug.line_numbers
+                .iter()
+                .filter(|entry| entry.line_number == line)
+                .map(|entry| (start + entry.start_pc as usize, id))),
+            Location::Pc(pc) => {
+                locations.insert(start + pc as usize, id);
+            }
+        }
+    }
+}
+
+impl Runtime {
+    fn bytecode_method(&self, class: u32, method: u16) -> Option<&BytecodeMethod> {
+        match self.get_class(class).method(method) {
+            MethodImpl::Bytecode(method) => Some(method),
+            _ => None,
+        }
+    }
+}
+
+/// Instructions executed between two calls of [`Debugger::poll`]
+const POLL_INTERVAL: u64 = 4096;
+
+impl RuntimeCtx<'_> {
+    /// Stops the current thread before its next instruction if it reached a breakpoint or the end
+    /// of a step, and lets the debugger decide how it goes on
+    pub(super) fn debug_hook(&mut self) -> Result<()> {
+        let here = Position { thread: self.current_thread(), depth: self.return_stack.len(), pc: self.pc };
+        let debug = self.debug.as_deref_mut().unwrap();
+        let new_classes = debug.search_new_classes(self.runtime);
+        let stop_on_class_load = debug.stop_on_class_load;
+        let thread_changes = std::mem::take(&mut debug.thread_changes);
+        let repeated = match debug.last_stop {
+            Some(last) if last.thread == here.thread => {
+                if last != here && here.depth <= last.depth {
+                    debug.last_stop = None;
+                }
+                last == here
+            }
+            _ => false,
+        };
+        if !debug.started {
+            // the classes loaded before are not reported
+            debug.started = true;
+            self.debug_stop(here, StopReason::Start)?;
+        } else {
+            let class_loads = new_classes.filter(|_| stop_on_class_load).map(StopReason::ClassLoaded);
+            for reason in class_loads.chain(thread_changes) {
+                self.debug_stop(here, reason)?;
+            }
+        }
+        if repeated {
+            return Ok(());
+        }
+        let debug = self.debug.as_deref().unwrap();
+        let method = self.runtime.bytecode_method(self.cur_class, self.cur_method).unwrap();
+        let reason = if let Some(&id) = debug.locations.get(&here.pc) {
+            StopReason::Breakpoint(id)
+        } else if debug.step.is_some_and(|step| step.ends_at(here, method)) {
+            StopReason::Step
+        } else if self.instructions.is_multiple_of(POLL_INTERVAL) && self.debug_poll()? {
+            StopReason::Suspended
+        } else {
+            return Ok(());
+        };
+        self.debug_stop(here, reason)
+    }
+    fn debug_stop(&mut self, here: Position, reason: StopReason) -> Result<()> {
+        let method = self.runtime.bytecode_method(self.cur_class, self.cur_method).unwrap();
+        let line = method.debug.line_at((here.pc - method.code_location) as u16);
+        let debug = self.debug.as_deref_mut().unwrap();
+        let step = debug.step.take();
+        debug.last_stop = Some(here);
+        let debugger = debug.debugger.0.clone();
+
+        self.runtime.stdio.flush_all()?;
+        let resume = debugger.borrow_mut().stopped(&mut DebugSession { ctx: self, stopped_thread: here.thread }, reason);
+        self.switch_to(here.thread);
+        self.debug.as_deref_mut().unwrap().step = match resume? {
+            Resume::Continue if reason.is_notification() => step,
+            Resume::Continue => None,
+            resume => Some(Step { resume, from: here, line }),
+        };
+        Ok(())
+    }
+    fn debug_poll(&mut self) -> Result<bool> {
+        let debugger = self.debug.as_deref().unwrap().debugger.0.clone();
+        let thread = self.current_thread();
+        let result = debugger.borrow_mut().poll(&mut DebugSession { ctx: self, stopped_thread: thread });
+        self.switch_to(thread);
+        result
+    }
+    /// Tells the debugger, if there is one, that a run ended
+    pub(super) fn debug_finished(&self, result: &Result<i32>) {
+        if let Some(debug) = &self.debug {
+            debug.debugger.0.borrow_mut().finished(result);
+        }
+    }
+}
+
+/// The stopped thread and the breakpoints, given to [`Debugger::stopped`].
+///
+/// Frames are numbered from the innermost one, which is 0. They are the frames of the selected
+/// thread, which is the stopped one unless [`Self::select_thread`] selected another.
+pub struct DebugSession<'s, 'a> {
+    ctx: &'s mut RuntimeCtx<'a>,
+    stopped_thread: usize,
+}
+
+/// Where the values of a frame are
+struct FrameRegs {
+    class: u32,
+    method: u16,
+    pc: usize,
+    frame_pointer: usize,
+    max_locals: u16,
+    /// End of the operand stack, where the arguments of the callee started
+    stack_end: usize,
+    innermost: bool,
+}
+
+impl DebugSession<'_, '_> {
+    /// The thread that stopped
+    pub fn stopped_thread(&self) -> usize {
+        self.stopped_thread
+    }
+    /// Number of threads, including the terminated ones. Thread 0 runs `main`.
+    pub fn thread_count(&self) -> usize {
+        self.ctx.thread_count()
+    }
+    pub fn selected_thread(&self) -> usize {
+        self.ctx.current_thread()
+    }
+    /// Looks at the frames of another thread, the stopped thread goes on either way
+    pub fn select_thread(&mut self, thread: usize) {
+        self.ctx.switch_to(thread);
+    }
+    pub fn thread_name(&self, thread: usize) -> String {
+        self.ctx.thread_name(thread)
+    }
+    pub fn thread_status(&self, thread: usize) -> ThreadStatus {
+        match self.ctx.thread_state(thread) {
+            ThreadState::Runnable => ThreadStatus::Runnable,
+            ThreadState::Blocked { .. } => ThreadStatus::Blocked,
+            ThreadState::Waiting { .. } | ThreadState::Joining { .. } | ThreadState::Initializing { .. } => ThreadStatus::Waiting,
+            ThreadState::Sleeping { .. } => ThreadStatus::Sleeping,
+            ThreadState::Terminated => ThreadStatus::Terminated,
+        }
+    }
+    /// The `java.lang.Thread` of a thread, created for the main thread if the program did not ask
+    /// for it yet
+    pub fn thread_object(&mut self, thread: usize) -> Result<Value> {
+        self.ctx.thread_object(thread)
+    }
+    /// The thread of a started `java.lang.Thread`
+    pub fn thread_of(&self, object: Value) -> Option<usize> {
+        let thread_class = *self.ctx.runtime.class_names.get("java/lang/Thread")?;
+        if object == Value::NULL || !self.ctx.is_instance_of(object, thread_class) {
+            return None;
+        }
+        self.ctx.thread_index(object)
+    }
+    /// Keeps an object alive and in place, so its reference stays valid after the thread goes on
+    pub fn pin(&mut self, object: Value) {
+        self.ctx.pin(object);
+    }
+    /// Lets the collector free or move a pinned object again
+    pub fn release(&mut self, object: Value) {
+        self.ctx.release(object);
+    }
+    /// Stops with [`StopReason::ClassLoaded`] for every class loaded from now on
+    pub fn set_class_load_stops(&mut self, stop: bool) {
+        self.ctx.debug.as_deref_mut().unwrap().stop_on_class_load = stop;
+    }
+    /// Stops with [`StopReason::ThreadStarted`] and [`StopReason::ThreadEnded`] from now on
+    pub fn set_thread_stops(&mut self, stop: bool) {
+        let debug = self.ctx.debug.as_deref_mut().unwrap();
+        debug.stop_on_thread_change = stop;
+        if !stop {
+            debug.thread_changes.clear();
+        }
+    }
+    /// The frames of the selected thread, the innermost first
+    pub fn backtrace(&self) -> Vec<FrameInfo> {
+        (0..self.ctx.return_stack.len()).filter_map(|frame| {
+            let regs = self.frame(frame)?;
+            let class = self.ctx.runtime.get_class(regs.class);
+            let method = self.ctx.runtime.bytecode_method(regs.class, regs.method)?;
+            let (pc, at) = regs.code_offsets(method);
+            Some(FrameInfo {
+                class_id: regs.class,
+                method_id: regs.method,
+                class: class.name.clone(),
+                method: method.debug.name.clone(),
+                pc,
+                line: method.debug.line_at(at),
+                source_file: class.debug_info.source_file.clone(),
+            })
+        }).collect()
+    }
+    /// The local variables of a frame that have a value, `None` if there is no such frame
+    pub fn locals(&self, frame: usize) -> Option<Vec<Local>> {
+        let regs = self.frame(frame)?;
+        let method = self.ctx.runtime.bytecode_method(regs.class, regs.method)?;
+        let slots = &self.ctx.stack[regs.frame_pointer..][..regs.max_locals as usize];
+        if method.debug.local_variables.is_empty() {
+            return Some(slots.iter().enumerate().map(|(slot, value)| Local {
+                slot: slot as u16,
+                name: None,
+                value: JValue::Int(value.into_i32()),
+            }).collect());
+        }
+        let (_, at) = regs.code_offsets(method);
+        let mut locals: Vec<_> = method.debug.local_variables
+            .iter()
+            .filter(|variable| (variable.start_pc as u32..variable.start_pc as u32 + variable.length as u32).contains(&(at as u32)))
+            .map(|variable| Local {
+                slot: variable.index,
+                name: Some(variable.name.clone()),
+                value: stack_value(slots, variable.index as usize, &variable.descriptor),
+            })
+            .collect();
+        locals.sort_by_key(|local| local.slot);
+        Some(locals)
+    }
+    /// The value in a slot of the locals of a frame read as the given type, `None` if there is no
+    /// such frame or slot
+    pub fn local_value(&self, frame: usize, slot: u16, value_type: &FieldDescriptor) -> Option<JValue> {
+        let regs = self.frame(frame)?;
+        if slot as usize + value_type.unit_size() > regs.max_locals as usize {
+            return None;
+        }
+        Some(stack_value(&self.ctx.stack[regs.frame_pointer..], slot as usize, value_type))
+    }
+    /// The operand stack of a frame from the bottom, the values have no types
+    pub fn operand_stack(&self, frame: usize) -> Option<&[Value]> {
+        let regs = self.frame(frame)?;
+        Some(&self.ctx.stack[regs.frame_pointer + regs.max_locals as usize..regs.stack_end])
+    }
+    /// Number of loaded classes, their ids are below it
+    pub fn class_count(&self) -> u32 {
+        self.ctx.runtime.classes.len() as u32
+    }
+    /// The id of a loaded class by its binary name, with dots or slashes
+    pub fn find_class(&self, name: &str) -> Option<u32> {
+        self.ctx.runtime.class_names.get(&*name.replace('.', "/")).copied()
+    }
+    /// The class of a non-null object
+    pub fn class_of(&self, object: Value) -> u32 {
+        self.ctx.get_class_id(object)
+    }
+    pub fn class_info(&self, class: u32) -> ClassInfo<'_> {
+        let loaded = self.ctx.runtime.get_class(class);
+        ClassInfo {
+            name: &loaded.name,
+            access_flags: loaded.access_flags,
+            super_class: (class != OBJECT_CLASS).then_some(loaded.super_class),
+            interfaces: &loaded.interfaces,
+            is_array: loaded.array_component.is_some(),
+            source_file: loaded.debug_info.source_file.as_deref(),
+            initialized: loaded.init_state == InitState::Initialized,
+            class_object: Value::new_ref_static(loaded.class_object),
+        }
+    }
+    /// The methods of a class, empty for builtin classes
+    pub fn methods(&self, class: u32) -> Vec<MethodInfo<'_>> {
+        let loaded = self.ctx.runtime.get_class(class);
+        let mut methods = Vec::new();
+        if let RuntimeInfo::Bytecode { method_code, .. } = &loaded.runtime_info {
+            methods.extend(method_code.iter().enumerate().map(|(id, method)| MethodInfo {
+                id: id as u16,
+                name: &method.debug.name,
+                descriptor: &method.debug.descriptor,
+                access_flags: method.access_flags,
+                arg_slots: method.arg_num,
+                code_length: method.debug.code_length,
+                line_numbers: &method.debug.line_numbers,
+                local_variables: &method.debug.local_variables,
+            }));
+        }
+        methods.extend(loaded.natives.iter().enumerate().map(|(i, native)| MethodInfo {
+            id: NATIVE_METHOD_BASE + i as u16,
+            name: &native.name,
+            descriptor: &native.descriptor,
+            access_flags: match native.is_static {
+                true => MethodAccess::NATIVE | MethodAccess::STATIC,
+                false => MethodAccess::NATIVE,
+            },
+            arg_slots: native.descriptor.arg_types.iter().map(|arg| arg.unit_size() as u16).sum::<u16>() + !native.is_static as u16,
+            code_length: 0,
+            line_numbers: &[],
+            local_variables: &[],
+        }));
+        methods
+    }
+    /// The fields a class declares, empty for builtin classes
+    pub fn declared_fields(&self, class: u32) -> &[DeclaredField] {
+        &self.ctx.runtime.get_class(class).debug_info.fields
+    }
+    /// The value of an instance field, given by its class and index in
+    /// [`Self::declared_fields`], of a non-null object
+    pub fn field_value(&self, object: Value, class: u32, field: usize) -> JValue {
+        let field = &self.declared_fields(class)[field];
+        let bytes = self.ctx.ref_bytes(object.offset(field.offset as u32), field.descriptor.byte_size() as usize).unwrap();
+        read_field(bytes, &field.descriptor)
+    }
+    /// The value of a static field, given by its class and index in [`Self::declared_fields`]
+    pub fn static_field_value(&self, class: u32, field: usize) -> JValue {
+        let loaded = self.ctx.runtime.get_class(class);
+        let field = &loaded.debug_info.fields[field];
+        read_field(&loaded.static_fields[field.offset as usize..][..field.descriptor.byte_size() as usize], &field.descriptor)
+    }
+    /// The instance fields of an object with the inherited ones first, or the elements of an
+    /// array, nothing for null
+    pub fn fields(&self, object: Value) -> Vec<(Box<str>, JValue)> {
+        if object == Value::NULL {
+            return Vec::new();
+        }
+        let ctx = &*self.ctx;
+        let mut class_id = ctx.get_class_id(object);
+        let class = ctx.runtime.get_class(class_id);
+        if class.array_component.is_some() {
+            let element_type = FieldDescriptor::from_bytes(&class.name.as_bytes()[1..]).unwrap();
+            let size = element_type.byte_size() as u32;
+            let length = ctx.read_u32_ref(object.offset(ARRAY_LENGTH_OFFSET)).unwrap();
+            return (0..length).map(|i| {
+                let bytes = ctx.ref_bytes(object.offset(ARRAY_DATA_OFFSET + i * size), size as usize).unwrap();
+                (format!("[{i}]").into(), read_field(bytes, &element_type))
+            }).collect();
+        }
+        let mut classes = vec![class_id];
+        while class_id != OBJECT_CLASS {
+            class_id = ctx.runtime.get_class(class_id).super_class;
+            classes.push(class_id);
+        }
+        classes.iter().rev()
+            .flat_map(|&class| self.declared_fields(class).iter().enumerate().map(move |(i, field)| (class, i, field)))
+            .filter(|(_, _, field)| !field.is_static())
+            .map(|(class, i, field)| (field.name.clone(), self.field_value(object, class, i)))
+            .collect()
+    }
+    /// The static fields a loaded class declares, `None` if it is not loaded
+    pub fn static_fields(&self, class: &str) -> Option<Vec<(Box<str>, JValue)>> {
+        let class = self.find_class(class)?;
+        Some(self.declared_fields(class)
+            .iter()
+            .enumerate()
+            .filter(|(_, field)| field.is_static())
+            .map(|(i, field)| (field.name.clone(), self.static_field_value(class, i)))
+            .collect())
+    }
+    /// The length of an array, `None` for null and other objects
+    pub fn array_length(&self, array: Value) -> Option<u32> {
+        if array == Value::NULL || self.ctx.runtime.get_class(self.ctx.get_class_id(array)).array_component.is_none() {
+            return None;
+        }
+        self.ctx.read_u32_ref(array.offset(ARRAY_LENGTH_OFFSET))
+    }
+    /// The content of a string, `None` for null and other objects
+    pub fn string_value(&self, string: Value) -> Option<String> {
+        if string == Value::NULL || self.ctx.get_class_id(string) != STRING_CLASS {
+            return None;
+        }
+        self.ctx.read_string_object(string)
+    }
+    /// Describes a value like a debugger shows it: strings quoted, other objects by their class
+    pub fn describe(&self, value: JValue) -> String {
+        let object = match value {
+            JValue::Void => return "void".into(),
+            JValue::Boolean(value) => return value.to_string(),
+            JValue::Byte(value) => return value.to_string(),
+            JValue::Char(value) => return format!("{:?}", char::from_u32(value.into()).unwrap_or(char::REPLACEMENT_CHARACTER)),
+            JValue::Short(value) => return value.to_string(),
+            JValue::Int(value) => return value.to_string(),
+            JValue::Long(value) => return value.to_string(),
+            JValue::Float(value) => return format!("{value:?}"),
+            JValue::Double(value) => return format!("{value:?}"),
+            JValue::Reference(Value::NULL) => return "null".into(),
+            JValue::Reference(object) => object,
+        };
+        if let Some(string) = self.string_value(object) {
+            return format!("{string:?}");
+        }
+        let class = self.ctx.runtime.get_class(self.ctx.get_class_id(object));
+        let name = match self.array_length(object) {
+            Some(length) => {
+                let array_type = FieldDescriptor::from_bytes(class.name.as_bytes()).unwrap().display_type().to_string();
+                array_type.replacen("[]", &format!("[{length}]"), 1)
+            }
+            None => class.name.replace('/', "."),
+        };
+        format!("instance of {name} (id={:#x})", object.into_u32())
+    }
+    /// Adds a breakpoint and returns its id
+    pub fn add_breakpoint(&mut self, mut breakpoint: Breakpoint) -> usize {
+        breakpoint.class = breakpoint.class.replace('.', "/").into();
+        let debug = self.ctx.debug.as_deref_mut().unwrap();
+        let id = debug.next_id;
+        debug.next_id += 1;
+        for class in &self.ctx.runtime.classes[..debug.classes_searched] {
+            add_locations(&mut debug.locations, id, &breakpoint, class);
+        }
+        debug.breakpoints.insert(id, breakpoint);
+        id
+    }
+    /// Removes a breakpoint, returning it if there was one with that id
+    pub fn remove_breakpoint(&mut self, id: usize) -> Option<Breakpoint> {
+        let debug = self.ctx.debug.as_deref_mut().unwrap();
+        let breakpoint = debug.breakpoints.remove(&id)?;
+        // another breakpoint may stop at the same place
+        debug.locations.clear();
+        for class in &self.ctx.runtime.classes[..debug.classes_searched] {
+            for (&id, breakpoint) in &debug.breakpoints {
+                add_locations(&mut debug.locations, id, breakpoint, class);
+            }
+        }
+        Some(breakpoint)
+    }
+    /// The breakpoints by id
+    pub fn breakpoints(&self) -> impl Iterator<Item = (usize, &Breakpoint)> {
+        self.ctx.debug.as_deref().unwrap().breakpoints.iter().map(|(&id, breakpoint)| (id, breakpoint))
+    }
+    fn frame(&self, frame: usize) -> Option<FrameRegs> {
+        let ctx = &*self.ctx;
+        let depth = ctx.return_stack.len();
+        if frame >= depth {
+            return None;
+        }
+        if frame == 0 {
+            return Some(FrameRegs {
+                class: ctx.cur_class,
+                method: ctx.cur_method,
+                pc: ctx.pc,
+                frame_pointer: ctx.frame_pointer as usize,
+                max_locals: ctx.max_locals,
+                stack_end: ctx.stack.len(),
+                innermost: true,
+            });
+        }
+        // the state of a frame is saved when it calls the next one
+        let saved = ctx.return_stack[depth - frame];
+        let callee_frame_pointer = match frame {
+            1 => ctx.frame_pointer,
+            _ => ctx.return_stack[depth - frame + 1].frame_pointer,
+        };
+        Some(FrameRegs {
+            class: saved.class,
+            method: saved.method,
+            pc: saved.pc,
+            frame_pointer: saved.frame_pointer as usize,
+            max_locals: saved.max_locals,
+            stack_end: callee_frame_pointer as usize,
+            innermost: false,
+        })
+    }
+}
+
+impl FrameRegs {
+    /// The pc in the code of the method, and the offset to look up lines and locals at, which for
+    /// a caller is inside the call instruction
+    fn code_offsets(&self, method: &BytecodeMethod) -> (u16, u16) {
+        let pc = (self.pc - method.code_location) as u16;
+        (pc, if self.innermost { pc } else { pc - 1 })
+    }
+}
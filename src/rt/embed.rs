@@ -0,0 +1,545 @@
+//! Calling into Java from Rust.
+//!
+//! A [`RuntimeCtx`] made with [`Runtime::new_context`] keeps its heap between calls, so objects
+//! created by one call can be passed to the next. Arguments are converted with [`ToJava`] and
+//! results with [`FromJava`]. A Java exception that the called code does not catch comes back as
+//! the inner `Err`, while the outer one is for failures of the runtime itself and for values that
+//! do not match the descriptor.
+//!
+//! References handed out to Rust are pinned: the collector neither frees nor moves them until they
+//! are given back with [`RuntimeCtx::release`] or the context is dropped.
+
+use crate::{class::{ClassAccess, MethodAccess}, descriptor::{AnyDescriptor, FieldDescriptor, MethodDescriptor}};
+
+use super::{f64_into_values, i64_into_values, values_into_f64, values_into_u64, JValue, MethodImpl, Reference, Result, RtError, Runtime, RuntimeCtx, Value, STRING_CLASS};
+
+/// Converts a Rust value into a Java value, creating objects in the context if needed
+pub trait ToJava {
+    fn to_java(self, ctx: &mut RuntimeCtx) -> Result<JValue>;
+}
+
+/// Converts a Java value into a Rust value, failing with [`RtError::TypeMismatch`] if the value has
+/// a different type
+pub trait FromJava: Sized {
+    fn from_java(ctx: &mut RuntimeCtx, value: JValue) -> Result<Self>;
+}
+
+/// The arguments of a call: `()`, a tuple of [`ToJava`] values or already converted values
+pub trait JavaArgs {
+    fn to_java_args(self, ctx: &mut RuntimeCtx) -> Result<Vec<JValue>>;
+}
+
+fn mismatch(expected: &str, found: JValue) -> RtError {
+    RtError::TypeMismatch { expected: expected.into(), found: format!("{found:?}").into() }
+}
+
+macro_rules! primitive {
+    ($($t:ty => $variant:ident $java_name:literal,)*) => {$(
+        impl ToJava for $t {
+            fn to_java(self, _: &mut RuntimeCtx) -> Result<JValue> {
+                Ok(JValue::$variant(self))
+            }
+        }
+        impl FromJava for $t {
+            fn from_java(_: &mut RuntimeCtx, value: JValue) -> Result<Self> {
+                match value {
+                    JValue::$variant(value) => Ok(value),
+                    value => Err(mismatch($java_name, value)),
+                }
+            }
+        }
+    )*};
+}
+primitive! {
+    bool => Boolean "boolean",
+    i8 => Byte "byte",
+    u16 => Char "char",
+    i16 => Short "short",
+    i32 => Int "int",
+    i64 => Long "long",
+    f32 => Float "float",
+    f64 => Double "double",
+}
+
+impl ToJava for JValue {
+    fn to_java(self, _: &mut RuntimeCtx) -> Result<JValue> {
+        Ok(self)
+    }
+}
+/// The value itself, references are pinned
+impl FromJava for JValue {
+    fn from_java(ctx: &mut RuntimeCtx, value: JValue) -> Result<Self> {
+        if let JValue::Reference(reference) = value {
+            ctx.pin(reference);
+        }
+        Ok(value)
+    }
+}
+impl ToJava for Value {
+    fn to_java(self, _: &mut RuntimeCtx) -> Result<JValue> {
+        Ok(JValue::Reference(self))
+    }
+}
+/// Any reference, pinned until it is released
+impl FromJava for Value {
+    fn from_java(ctx: &mut RuntimeCtx, value: JValue) -> Result<Self> {
+        match value {
+            JValue::Reference(reference) => {
+                ctx.pin(reference);
+                Ok(reference)
+            }
+            value => Err(mismatch("a reference", value)),
+        }
+    }
+}
+/// The result of a `void` method
+impl FromJava for () {
+    fn from_java(_: &mut RuntimeCtx, value: JValue) -> Result<Self> {
+        match value {
+            JValue::Void => Ok(()),
+            value => Err(mismatch("void", value)),
+        }
+    }
+}
+/// A new `java.lang.String`
+impl ToJava for &str {
+    fn to_java(self, ctx: &mut RuntimeCtx) -> Result<JValue> {
+        Ok(JValue::Reference(ctx.new_string_obj(self)))
+    }
+}
+impl ToJava for String {
+    fn to_java(self, ctx: &mut RuntimeCtx) -> Result<JValue> {
+        self.as_str().to_java(ctx)
+    }
+}
+/// The content of a `java.lang.String`, which must not be null
+impl FromJava for String {
+    fn from_java(ctx: &mut RuntimeCtx, value: JValue) -> Result<Self> {
+        match value {
+            JValue::Reference(reference) if reference != Value::NULL && ctx.get_class_id(reference) == STRING_CLASS => {
+                Ok(ctx.read_string_object(reference).unwrap())
+            }
+            value => Err(mismatch("java.lang.String", value)),
+        }
+    }
+}
+/// `None` is null
+impl<T: ToJava> ToJava for Option<T> {
+    fn to_java(self, ctx: &mut RuntimeCtx) -> Result<JValue> {
+        match self {
+            Some(value) => value.to_java(ctx),
+            None => Ok(JValue::Reference(Value::NULL)),
+        }
+    }
+}
+/// Null is `None`
+impl<T: FromJava> FromJava for Option<T> {
+    fn from_java(ctx: &mut RuntimeCtx, value: JValue) -> Result<Self> {
+        match value {
+            JValue::Reference(Value::NULL) => Ok(None),
+            value => T::from_java(ctx, value).map(Some),
+        }
+    }
+}
+
+impl JavaArgs for Vec<JValue> {
+    fn to_java_args(self, _: &mut RuntimeCtx) -> Result<Vec<JValue>> {
+        Ok(self)
+    }
+}
+impl JavaArgs for &[JValue] {
+    fn to_java_args(self, _: &mut RuntimeCtx) -> Result<Vec<JValue>> {
+        Ok(self.to_vec())
+    }
+}
+macro_rules! tuple_args {
+    ($(($($name:ident),*),)*) => {$(
+        impl<$($name: ToJava),*> JavaArgs for ($($name,)*) {
+            #[allow(non_snake_case, unused_variables)]
+            fn to_java_args(self, ctx: &mut RuntimeCtx) -> Result<Vec<JValue>> {
+                let ($($name,)*) = self;
+                Ok(vec![$($name.to_java(ctx)?),*])
+            }
+        }
+    )*};
+}
+tuple_args! {
+    (),
+    (A),
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F),
+    (A, B, C, D, E, F, G),
+    (A, B, C, D, E, F, G, H),
+    (A, B, C, D, E, F, G, H, I),
+    (A, B, C, D, E, F, G, H, I, J),
+    (A, B, C, D, E, F, G, H, I, J, K),
+    (A, B, C, D, E, F, G, H, I, J, K, L),
+}
+
+/// Whether a value can be stored in a variable of the type, `None` being `void`
+fn has_type(value: JValue, field_type: Option<&FieldDescriptor>) -> bool {
+    matches!((field_type, value),
+        (None, JValue::Void) |
+        (Some(FieldDescriptor::Boolean), JValue::Boolean(_)) |
+        (Some(FieldDescriptor::Byte), JValue::Byte(_)) |
+        (Some(FieldDescriptor::Char), JValue::Char(_)) |
+        (Some(FieldDescriptor::Short), JValue::Short(_)) |
+        (Some(FieldDescriptor::Int), JValue::Int(_)) |
+        (Some(FieldDescriptor::Long), JValue::Long(_)) |
+        (Some(FieldDescriptor::Float), JValue::Float(_)) |
+        (Some(FieldDescriptor::Double), JValue::Double(_)) |
+        (Some(FieldDescriptor::ClassRef(_) | FieldDescriptor::ArrRef(_)), JValue::Reference(_))
+    )
+}
+
+/// Decodes the bytes of a field
+pub(super) fn read_field(bytes: &[u8], field_type: &FieldDescriptor) -> JValue {
+    let u16_at = || u16::from_ne_bytes(bytes[..2].try_into().unwrap());
+    let u32_at = || u32::from_ne_bytes(bytes[..4].try_into().unwrap());
+    let u64_at = || u64::from_ne_bytes(bytes[..8].try_into().unwrap());
+    match field_type {
+        FieldDescriptor::Boolean => JValue::Boolean(bytes[0] != 0),
+        FieldDescriptor::Byte => JValue::Byte(bytes[0] as i8),
+        FieldDescriptor::Char => JValue::Char(u16_at()),
+        FieldDescriptor::Short => JValue::Short(u16_at() as i16),
+        FieldDescriptor::Int => JValue::Int(u32_at() as i32),
+        FieldDescriptor::Float => JValue::Float(f32::from_bits(u32_at())),
+        FieldDescriptor::Long => JValue::Long(u64_at() as i64),
+        FieldDescriptor::Double => JValue::Double(f64::from_bits(u64_at())),
+        FieldDescriptor::ClassRef(_) |
+        FieldDescriptor::ArrRef(_) => JValue::Reference(Value(u32_at())),
+    }
+}
+
+/// Encodes a value for a field, the value must have the type of the field
+pub(super) fn field_value_bytes(value: JValue) -> Box<[u8]> {
+    match value {
+        JValue::Void => Box::new([]),
+        JValue::Boolean(value) => Box::new([value as u8]),
+        JValue::Byte(value) => Box::new(value.to_ne_bytes()),
+        JValue::Char(value) => Box::new(value.to_ne_bytes()),
+        JValue::Short(value) => Box::new(value.to_ne_bytes()),
+        JValue::Int(value) => Box::new(value.to_ne_bytes()),
+        JValue::Long(value) => Box::new(value.to_ne_bytes()),
+        JValue::Float(value) => Box::new(value.to_ne_bytes()),
+        JValue::Double(value) => Box::new(value.to_ne_bytes()),
+        JValue::Reference(value) => Box::new(value.into_u32().to_ne_bytes()),
+    }
+}
+
+impl Runtime {
+    /// A context for calling into Java with [`RuntimeCtx::call_static`] and the like.
+    ///
+    /// The heap belongs to the context, objects are gone once it is dropped. Classes and their
+    /// static fields stay with the runtime.
+    pub fn new_context(&mut self) -> RuntimeCtx<'_> {
+        RuntimeCtx::new(self)
+    }
+    /// Whether a method is static, `None` for the builtin methods, which do not record it
+    fn is_static_method(&self, class: u32, method_id: u16) -> Option<bool> {
+        match self.get_class(class).method(method_id) {
+            MethodImpl::Builtin(_) => None,
+            MethodImpl::Native(native) => Some(native.is_static),
+            MethodImpl::Bytecode(method) => Some(method.access_flags.contains(MethodAccess::STATIC)),
+        }
+    }
+    /// Whether a field the class declares is static, `None` for the fields of builtin classes,
+    /// which do not record it
+    fn is_static_field(&self, class: u32, name: &str, field_type: &FieldDescriptor) -> Option<bool> {
+        self.get_class(class).debug_info.fields.iter()
+            .find(|field| &*field.name == name && field.descriptor == *field_type)
+            .map(|field| field.is_static())
+    }
+}
+
+impl RuntimeCtx<'_> {
+    /// Keeps a heap object alive and in place until it is [released](Self::release)
+    pub(super) fn pin(&mut self, reference: Value) {
+        if let Reference::Heap(_) = reference.into_ref() {
+            self.host_refs.push(reference);
+        }
+    }
+    /// Gives back a reference that was returned to Rust, the collector may free or move the object
+    /// once it is not pinned by another result anymore
+    pub fn release(&mut self, reference: Value) {
+        if let Some(i) = self.host_refs.iter().rposition(|&pinned| pinned == reference) {
+            self.host_refs.swap_remove(i);
+        }
+    }
+    /// Initializes a class like its first use from Java would, running its static initializer
+    pub fn initialize_class(&mut self, class: &str) -> Result<Result<(), Value>> {
+        let class = self.runtime.load_class(class)?;
+        self.run_guarded(|ctx| ctx.initialize_for_host(class).map(drop))
+    }
+    /// Creates an instance of a class with the constructor of the given descriptor, like
+    /// `(ILjava/lang/String;)V`
+    pub fn new_instance(&mut self, class: &str, descriptor: &str, args: impl JavaArgs) -> Result<Result<Value, Value>> {
+        let descriptor = MethodDescriptor::from_bytes(descriptor.as_bytes())?;
+        self.with_args(args, |ctx, args| {
+            if let Err(exception) = ctx.initialize_class(class)? {
+                return Ok(Err(exception));
+            }
+            let class_id = ctx.runtime.load_class(class)?;
+            let start = ctx.stack.len();
+            let result = ctx.run_guarded(|ctx| {
+                if ctx.runtime.get_class(class_id).access_flags.intersects(ClassAccess::ABSTRACT | ClassAccess::INTERFACE) {
+                    return ctx.throw_new("java/lang/InstantiationError", Some(class.replace('/', ".")));
+                }
+                let size = ctx.runtime.get_class(class_id).get_aligned_data_size();
+                if !ctx.make_room(size as usize)? {
+                    return Ok(());
+                }
+                // like `new` and `dup`, one copy is consumed by the constructor
+                let object = ctx.new_object(class_id);
+                ctx.push(object);
+                ctx.push(object);
+                ctx.invoke_host(class_id, "<init>", &descriptor, false, args)
+            })?;
+            Ok(match result {
+                Ok(()) => {
+                    let object = ctx.pop();
+                    ctx.pin(object);
+                    Ok(object)
+                }
+                Err(exception) => {
+                    ctx.stack.truncate(start);
+                    Err(exception)
+                }
+            })
+        })
+    }
+    /// Calls a static method by name and descriptor, like `(I)Ljava/lang/String;`
+    pub fn call_static<R: FromJava>(&mut self, class: &str, name: &str, descriptor: &str, args: impl JavaArgs) -> Result<Result<R, Value>> {
+        let descriptor = MethodDescriptor::from_bytes(descriptor.as_bytes())?;
+        self.with_args(args, |ctx, args| {
+            if let Err(exception) = ctx.initialize_class(class)? {
+                return Ok(Err(exception));
+            }
+            let class = ctx.runtime.load_class(class)?;
+            ctx.call_host(&descriptor, |ctx| ctx.invoke_host(class, name, &descriptor, true, args))
+        })
+    }
+    /// Calls an instance method by name and descriptor, the method is looked up in the class of
+    /// the object so overriding methods are called
+    pub fn call_method<R: FromJava>(&mut self, object: Value, name: &str, descriptor: &str, args: impl JavaArgs) -> Result<Result<R, Value>> {
+        let descriptor = MethodDescriptor::from_bytes(descriptor.as_bytes())?;
+        self.with_args(args, |ctx, args| {
+            ctx.call_host(&descriptor, |ctx| {
+                if object == Value::NULL {
+                    return ctx.throw_new("java/lang/NullPointerException", None);
+                }
+                ctx.push(object);
+                ctx.invoke_host(ctx.get_class_id(object), name, &descriptor, false, args)
+            })
+        })
+    }
+    /// Reads an instance field by name and descriptor, like `I` or `Ljava/lang/String;`
+    pub fn get_field<R: FromJava>(&mut self, object: Value, name: &str, descriptor: &str) -> Result<Result<R, Value>> {
+        let field_type = FieldDescriptor::from_bytes(descriptor.as_bytes())?;
+        let mut value = JValue::Void;
+        let result = self.run_guarded(|ctx| {
+            let Some(offset) = ctx.host_field_offset(object, name, &field_type)? else { return Ok(()) };
+            value = read_field(ctx.ref_bytes(object.offset(offset as u32), field_type.byte_size() as usize).unwrap(), &field_type);
+            Ok(())
+        })?;
+        match result {
+            Ok(()) => R::from_java(self, value).map(Ok),
+            Err(exception) => Ok(Err(exception)),
+        }
+    }
+    /// Writes an instance field by name and descriptor
+    pub fn set_field(&mut self, object: Value, name: &str, descriptor: &str, value: impl ToJava) -> Result<Result<(), Value>> {
+        let field_type = FieldDescriptor::from_bytes(descriptor.as_bytes())?;
+        let value = value.to_java(self)?;
+        if !has_type(value, Some(&field_type)) {
+            return Err(mismatch(&field_type.display_type().to_string(), value));
+        }
+        self.run_guarded(|ctx| {
+            let Some(offset) = ctx.host_field_offset(object, name, &field_type)? else { return Ok(()) };
+            let bytes = field_value_bytes(value);
+            ctx.ref_bytes_mut(object.offset(offset as u32), bytes.len()).unwrap().copy_from_slice(&bytes);
+            Ok(())
+        })
+    }
+    /// Reads a static field by name and descriptor, initializing its class first
+    pub fn get_static_field<R: FromJava>(&mut self, class: &str, name: &str, descriptor: &str) -> Result<Result<R, Value>> {
+        let field_type = FieldDescriptor::from_bytes(descriptor.as_bytes())?;
+        let mut value = JValue::Void;
+        let result = self.run_guarded(|ctx| {
+            let Some((class, offset)) = ctx.host_static_field(class, name, &field_type)? else { return Ok(()) };
+            let bytes = &ctx.runtime.get_class(class).static_fields[offset as usize..][..field_type.byte_size() as usize];
+            value = read_field(bytes, &field_type);
+            Ok(())
+        })?;
+        match result {
+            Ok(()) => R::from_java(self, value).map(Ok),
+            Err(exception) => Ok(Err(exception)),
+        }
+    }
+    /// Writes a static field by name and descriptor, initializing its class first
+    pub fn set_static_field(&mut self, class: &str, name: &str, descriptor: &str, value: impl ToJava) -> Result<Result<(), Value>> {
+        let field_type = FieldDescriptor::from_bytes(descriptor.as_bytes())?;
+        // the static initializer may collect before the value is stored
+        self.with_args((value,), |ctx, args| {
+            let value = args[0];
+            if !has_type(value, Some(&field_type)) {
+                return Err(mismatch(&field_type.display_type().to_string(), value));
+            }
+            ctx.run_guarded(|ctx| {
+                let Some((class, offset)) = ctx.host_static_field(class, name, &field_type)? else { return Ok(()) };
+                let bytes = field_value_bytes(value);
+                ctx.runtime.classes[class as usize].static_fields[offset as usize..][..bytes.len()].copy_from_slice(&bytes);
+                Ok(())
+            })
+        })
+    }
+
+    /// Converts the arguments of a call and pins the objects they refer to while `call` runs, which
+    /// may collect before it pushes them, in static initializers or when making room for an object
+    fn with_args<T>(&mut self, args: impl JavaArgs, call: impl FnOnce(&mut Self, &[JValue]) -> Result<T>) -> Result<T> {
+        let args = args.to_java_args(self)?;
+        for &arg in &args {
+            if let JValue::Reference(reference) = arg {
+                self.pin(reference);
+            }
+        }
+        let result = call(self, &args);
+        for &arg in &args {
+            if let JValue::Reference(reference) = arg {
+                self.release(reference);
+            }
+        }
+        result
+    }
+    /// Runs `call`, which leaves the result of a method of the given descriptor on the stack, and
+    /// converts the result
+    fn call_host<R: FromJava>(&mut self, descriptor: &MethodDescriptor, call: impl FnOnce(&mut Self) -> Result<()>) -> Result<Result<R, Value>> {
+        let start = self.stack.len();
+        match self.run_guarded(call)? {
+            Ok(()) => {
+                let value = match descriptor.return_type.as_deref() {
+                    None => JValue::Void,
+                    Some(FieldDescriptor::Boolean) => JValue::Boolean(self.pop().into_u32() != 0),
+                    Some(FieldDescriptor::Byte) => JValue::Byte(self.pop().into_i8()),
+                    Some(FieldDescriptor::Char) => JValue::Char(self.pop().into_u16()),
+                    Some(FieldDescriptor::Short) => JValue::Short(self.pop().into_i16()),
+                    Some(FieldDescriptor::Int) => JValue::Int(self.pop().into_i32()),
+                    Some(FieldDescriptor::Float) => JValue::Float(self.pop().into_f32()),
+                    Some(FieldDescriptor::Long) => JValue::Long(values_into_u64(self.pop2()) as i64),
+                    Some(FieldDescriptor::Double) => JValue::Double(values_into_f64(self.pop2())),
+                    Some(FieldDescriptor::ClassRef(_) | FieldDescriptor::ArrRef(_)) => JValue::Reference(self.pop()),
+                };
+                self.stack.truncate(start);
+                R::from_java(self, value).map(Ok)
+            }
+            Err(exception) => {
+                self.stack.truncate(start);
+                Ok(Err(exception))
+            }
+        }
+    }
+    /// Pushes the arguments and invokes a method of the class or its supertypes, throwing
+    /// `NoSuchMethodError` if there is none and `IncompatibleClassChangeError` if it is static when
+    /// it should not be or the other way round
+    fn invoke_host(&mut self, class: u32, name: &str, descriptor: &MethodDescriptor, is_static: bool, args: &[JValue]) -> Result<()> {
+        if args.len() != descriptor.arg_types.len() {
+            return Err(RtError::TypeMismatch {
+                expected: format!("{} arguments", descriptor.arg_types.len()).into(),
+                found: format!("{} arguments", args.len()).into(),
+            });
+        }
+        for (&arg, arg_type) in args.iter().zip(&descriptor.arg_types) {
+            if !has_type(arg, Some(arg_type)) {
+                return Err(mismatch(&arg_type.display_type().to_string(), arg));
+            }
+            match arg {
+                JValue::Void => (),
+                JValue::Boolean(value) => self.push(value),
+                JValue::Byte(value) => self.push(value),
+                JValue::Char(value) => self.push(value as i32),
+                JValue::Short(value) => self.push(value),
+                JValue::Int(value) => self.push(value),
+                JValue::Float(value) => self.push(value),
+                JValue::Long(value) => self.push2(i64_into_values(value)),
+                JValue::Double(value) => self.push2(f64_into_values(value)),
+                JValue::Reference(value) => self.push(value),
+            }
+        }
+        let any_descriptor = AnyDescriptor::Method(descriptor.clone());
+        // constructors are not inherited
+        let method = if name == "<init>" {
+            self.runtime.get_class(class).member_table().get(name, &any_descriptor).map(|method_id| (class, method_id))
+        } else {
+            self.runtime.find_method(class, name, &any_descriptor)
+        };
+        let Some((class, method_id)) = method else {
+            let class_name = self.runtime.get_class(class).name.replace('/', ".");
+            let message = format!("'{}'", descriptor.display_type(&format!("{class_name}.{name}")));
+            return self.throw_new("java/lang/NoSuchMethodError", Some(message));
+        };
+        if self.runtime.is_static_method(class, method_id).is_some_and(|found| found != is_static) {
+            let class_name = self.runtime.get_class(class).name.replace('/', ".");
+            let qualified_name = format!("{class_name}.{name}");
+            let method = descriptor.display_type(&qualified_name);
+            let message = match is_static {
+                true => format!("Expected static method '{method}'"),
+                false => format!("Expecting non-static method '{method}'"),
+            };
+            return self.throw_new("java/lang/IncompatibleClassChangeError", Some(message));
+        }
+        self.invoke(class, method_id)?;
+        let depth = self.barrier.unwrap();
+        self.run_until(depth)
+    }
+    /// The offset of an instance field in the object, throwing if the object is null or has no
+    /// such field
+    fn host_field_offset(&mut self, object: Value, name: &str, field_type: &FieldDescriptor) -> Result<Option<u16>> {
+        if object == Value::NULL {
+            self.throw_new("java/lang/NullPointerException", None)?;
+            return Ok(None);
+        }
+        let Some((class, offset)) = self.runtime.resolve_field(self.get_class_id(object), name, &field_type.clone().into()) else {
+            self.throw_new("java/lang/NoSuchFieldError", Some(name.into()))?;
+            return Ok(None);
+        };
+        Ok(self.check_field_kind(class, name, field_type, false)?.then_some(offset))
+    }
+    /// The declaring class and offset of a static field, initializing the class first
+    fn host_static_field(&mut self, class: &str, name: &str, field_type: &FieldDescriptor) -> Result<Option<(u32, u16)>> {
+        let class = self.runtime.load_class(class)?;
+        let Some((class, offset)) = self.runtime.resolve_field(class, name, &field_type.clone().into()) else {
+            self.throw_new("java/lang/NoSuchFieldError", Some(name.into()))?;
+            return Ok(None);
+        };
+        Ok((self.check_field_kind(class, name, field_type, true)? && self.initialize_for_host(class)?).then_some((class, offset)))
+    }
+    /// Throws `IncompatibleClassChangeError` if the field is static when it should not be or the
+    /// other way round, `false` if it threw
+    fn check_field_kind(&mut self, class: u32, name: &str, field_type: &FieldDescriptor, is_static: bool) -> Result<bool> {
+        if self.runtime.is_static_field(class, name, field_type).is_none_or(|found| found == is_static) {
+            return Ok(true);
+        }
+        let class_name = self.runtime.get_class(class).name.replace('/', ".");
+        let kind = if is_static { "static" } else { "non-static" };
+        self.throw_new("java/lang/IncompatibleClassChangeError", Some(format!("Expected {kind} field {class_name}.{name}")))?;
+        Ok(false)
+    }
+    /// Runs the static initializers the class needs, `false` if one threw
+    fn initialize_for_host(&mut self, class: u32) -> Result<bool> {
+        let depth = self.return_stack.len();
+        loop {
+            if self.ensure_initialized(class, self.pc)? {
+                return Ok(true);
+            }
+            if self.pending_exception.is_none() {
+                self.run_until(depth)?;
+            }
+            if self.pending_exception.is_some() {
+                return Ok(false);
+            }
+        }
+    }
+}
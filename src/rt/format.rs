@@ -0,0 +1,597 @@
+//! Format strings of `java.util.Formatter`, used by `printf` and `String.format`.
+//!
+//! Format specifiers look like `%[index$][flags][width][.precision]conversion`. The whole format
+//! string is parsed first, so a bad specifier throws before any argument's `toString()` runs.
+//! Boxed arguments are read through their `value` field. Date and time conversions are not
+//! supported.
+
+use crate::descriptor::{FieldDescriptor, MethodDescriptor};
+
+use super::{embed, JValue, Result, RuntimeCtx, Value, ARRAY_DATA_OFFSET, ARRAY_LENGTH_OFFSET};
+
+/// A part of a parsed format string
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Piece {
+    Literal(String),
+    Specifier(Specifier),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Specifier {
+    /// The specifier as written, for error messages
+    pub text: String,
+    pub index: ArgIndex,
+    flags: Flags,
+    width: Option<usize>,
+    precision: Option<usize>,
+    /// The lower case conversion character
+    conversion: char,
+    upper_case: bool,
+}
+
+/// Which argument a specifier formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ArgIndex {
+    /// `%%` and `%n` take no argument
+    None,
+    /// The argument after the one the last ordinary specifier took
+    Ordinary,
+    /// `%<s`, the argument of the previous specifier
+    Previous,
+    /// `%2$s`, counting from 1
+    Explicit(usize),
+}
+
+bitflags::bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Flags: u8 {
+        const LEFT_JUSTIFY = 1 << 0;
+        const ALTERNATE = 1 << 1;
+        const PLUS = 1 << 2;
+        const LEADING_SPACE = 1 << 3;
+        const ZERO_PAD = 1 << 4;
+        const GROUP = 1 << 5;
+        const PARENTHESES = 1 << 6;
+    }
+}
+
+impl Flags {
+    fn from_char(c: char) -> Option<Self> {
+        Some(match c {
+            '-' => Self::LEFT_JUSTIFY,
+            '#' => Self::ALTERNATE,
+            '+' => Self::PLUS,
+            ' ' => Self::LEADING_SPACE,
+            '0' => Self::ZERO_PAD,
+            ',' => Self::GROUP,
+            '(' => Self::PARENTHESES,
+            _ => return None,
+        })
+    }
+}
+
+impl std::fmt::Display for Flags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        "-#+ 0,(".chars().filter(|&c| self.contains(Self::from_char(c).unwrap())).try_for_each(|c| write!(f, "{c}"))
+    }
+}
+
+/// What a specifier needs to know about its argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Category {
+    /// `%s`, the result of `toString()`
+    Text,
+    /// `%h`, the result of `hashCode()`
+    Hash,
+    /// `%b`, `%c`, the integral and the floating point conversions take the value of a box
+    Value,
+}
+
+/// A Java argument, reduced to what formatting needs
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum FormatArg {
+    Null,
+    Boolean(bool),
+    /// A `Character`, as a code unit
+    Char(u16),
+    /// A `Byte`, `Short`, `Integer` or `Long` and its size in bits
+    Integer { value: i64, bits: u32 },
+    /// A `Float` or `Double`
+    Float(f64),
+    /// What `toString()` or `hashCode()` gave for [`Category::Text`] and [`Category::Hash`]
+    Text(String),
+    /// Any other object, by class name with dots
+    Other(String),
+}
+
+/// An exception to throw, a subclass of `java.util.IllegalFormatException`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct FormatError {
+    pub class_name: &'static str,
+    pub message: String,
+}
+
+impl FormatError {
+    fn new(class_name: &'static str, message: String) -> Self {
+        Self { class_name, message }
+    }
+    pub fn missing_argument(specifier: &Specifier) -> Self {
+        Self::new("java/util/MissingFormatArgumentException", format!("Format specifier '{}'", specifier.text))
+    }
+    fn conversion(specifier: &Specifier, arg: &FormatArg) -> Self {
+        let class_name = match arg {
+            FormatArg::Boolean(_) => "java.lang.Boolean",
+            FormatArg::Char(_) => "java.lang.Character",
+            FormatArg::Integer { bits: 8, .. } => "java.lang.Byte",
+            FormatArg::Integer { bits: 16, .. } => "java.lang.Short",
+            FormatArg::Integer { bits: 32, .. } => "java.lang.Integer",
+            FormatArg::Integer { .. } => "java.lang.Long",
+            FormatArg::Float(_) => "java.lang.Double",
+            FormatArg::Text(_) => "java.lang.String",
+            FormatArg::Other(class_name) => class_name,
+            FormatArg::Null => unreachable!(),
+        };
+        Self::new("java/util/IllegalFormatConversionException", format!("{} != {class_name}", specifier.conversion_char()))
+    }
+}
+
+/// Splits a format string into literal text and specifiers
+pub(super) fn parse(format: &str) -> Result<Vec<Piece>, FormatError> {
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut rest = format;
+    while let Some(start) = rest.find('%') {
+        literal.push_str(&rest[..start]);
+        let (specifier, tail) = parse_specifier(&rest[start..])?;
+        rest = tail;
+        match specifier.conversion {
+            '%' => {
+                literal.push_str(&justify(&specifier, "%".into()));
+            }
+            'n' => literal.push('\n'),
+            _ => {
+                if !literal.is_empty() {
+                    pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                }
+                pieces.push(Piece::Specifier(specifier));
+            }
+        }
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        pieces.push(Piece::Literal(literal));
+    }
+    Ok(pieces)
+}
+
+/// Parses the specifier at the start of `s`, which starts with `%`
+fn parse_specifier(s: &str) -> Result<(Specifier, &str), FormatError> {
+    let bytes = s.as_bytes();
+    let mut i = 1;
+    let digits = |i: usize| bytes[i..].iter().take_while(|b| b.is_ascii_digit()).count();
+    let number = |from: usize, to: usize| s[from..to].parse::<usize>().ok();
+
+    let mut index = ArgIndex::Ordinary;
+    let n = digits(i);
+    if n > 0 && bytes.get(i + n) == Some(&b'$') {
+        index = ArgIndex::Explicit(number(i, i + n).unwrap_or(0));
+        i += n + 1;
+    }
+    let mut flags = Flags::empty();
+    while let Some(&b) = bytes.get(i) {
+        if b == b'<' {
+            index = ArgIndex::Previous;
+        } else if let Some(flag) = Flags::from_char(b as char) {
+            if flags.contains(flag) {
+                return Err(FormatError::new("java/util/DuplicateFormatFlagsException", format!("Flags = '{}'", b as char)));
+            }
+            flags |= flag;
+        } else {
+            break;
+        }
+        i += 1;
+    }
+    let n = digits(i);
+    let width = if n > 0 { number(i, i + n) } else { None };
+    i += n;
+    let mut precision = None;
+    if bytes.get(i) == Some(&b'.') {
+        let n = digits(i + 1);
+        if n == 0 {
+            return Err(unknown_conversion('.'));
+        }
+        precision = number(i + 1, i + 1 + n);
+        i += n + 1;
+    }
+    let Some(conversion) = s[i..].chars().next() else {
+        return Err(FormatError::new("java/util/UnknownFormatConversionException", "Conversion = '%'".into()));
+    };
+    let end = i + conversion.len_utf8();
+    if matches!(conversion, 't' | 'T') {
+        let suffix = s[end..].chars().next().map_or(String::new(), String::from);
+        return Err(FormatError::new("java/util/UnknownFormatConversionException", format!("Conversion = '{conversion}{suffix}'")));
+    }
+    let specifier = Specifier {
+        text: s[..end].into(),
+        index: if matches!(conversion, '%' | 'n') { ArgIndex::None } else { index },
+        flags,
+        width,
+        precision,
+        conversion: conversion.to_ascii_lowercase(),
+        upper_case: conversion.is_ascii_uppercase(),
+    };
+    specifier.check()?;
+    Ok((specifier, &s[end..]))
+}
+
+fn unknown_conversion(c: char) -> FormatError {
+    FormatError::new("java/util/UnknownFormatConversionException", format!("Conversion = '{c}'"))
+}
+
+impl Specifier {
+    pub fn category(&self) -> Category {
+        match self.conversion {
+            's' => Category::Text,
+            'h' => Category::Hash,
+            _ => Category::Value,
+        }
+    }
+    fn conversion_char(&self) -> char {
+        if self.upper_case { self.conversion.to_ascii_uppercase() } else { self.conversion }
+    }
+    /// Checks the flags, width and precision against the conversion like `Formatter` does
+    fn check(&self) -> Result<(), FormatError> {
+        let (allowed, takes_precision) = match self.conversion_char() {
+            'b' | 'B' | 'h' | 'H' | 's' | 'S' => (Flags::LEFT_JUSTIFY | Flags::ALTERNATE, true),
+            'c' | 'C' => (Flags::LEFT_JUSTIFY, false),
+            'd' => (Flags::all() - Flags::ALTERNATE, false),
+            'o' | 'x' | 'X' => (Flags::LEFT_JUSTIFY | Flags::ALTERNATE | Flags::ZERO_PAD, false),
+            'e' | 'E' => (Flags::all() - Flags::GROUP, true),
+            'f' => (Flags::all(), true),
+            'g' | 'G' => (Flags::all() - Flags::ALTERNATE, true),
+            '%' => (Flags::LEFT_JUSTIFY, false),
+            'n' => (Flags::empty(), false),
+            c => return Err(unknown_conversion(c)),
+        };
+        if self.conversion == 'n' && let Some(width) = self.width {
+            return Err(FormatError::new("java/util/IllegalFormatWidthException", width.to_string()));
+        }
+        if let Some(precision) = self.precision && !takes_precision {
+            return Err(FormatError::new("java/util/IllegalFormatPrecisionException", precision.to_string()));
+        }
+        if self.flags.contains(Flags::LEFT_JUSTIFY | Flags::ZERO_PAD)
+            || self.flags.contains(Flags::PLUS | Flags::LEADING_SPACE)
+        {
+            return Err(FormatError::new("java/util/IllegalFormatFlagsException", format!("Flags = '{}'", self.flags)));
+        }
+        let mismatched = self.flags - allowed;
+        // `#` only applies to objects that implement `Formattable`, which none do here
+        let mismatched = if self.category() != Category::Value { mismatched | (self.flags & Flags::ALTERNATE) } else { mismatched };
+        if !mismatched.is_empty() {
+            let message = format!("Conversion = {}, Flags = {mismatched}", self.conversion_char());
+            return Err(FormatError::new("java/util/FormatFlagsConversionMismatchException", message));
+        }
+        if self.flags.intersects(Flags::LEFT_JUSTIFY | Flags::ZERO_PAD) && self.width.is_none() {
+            return Err(FormatError::new("java/util/MissingFormatWidthException", self.text.clone()));
+        }
+        Ok(())
+    }
+    /// Formats an argument
+    pub fn format(&self, arg: &FormatArg) -> Result<String, FormatError> {
+        let text = match (self.conversion, arg) {
+            ('b', FormatArg::Null) => "false".into(),
+            ('b', FormatArg::Boolean(value)) => value.to_string(),
+            ('b', _) => "true".into(),
+            (_, FormatArg::Null) => "null".into(),
+            ('s' | 'h', FormatArg::Text(text)) => text.clone(),
+            ('c', FormatArg::Char(c)) => String::from_utf16_lossy(&[*c]),
+            ('c', &FormatArg::Integer { value, bits }) if bits <= 32 => match char::from_u32(value as u32) {
+                Some(c) => c.into(),
+                None => {
+                    let message = format!("Code point = 0x{:x}", value as u32);
+                    return Err(FormatError::new("java/util/IllegalFormatCodePointException", message));
+                }
+            },
+            ('d', &FormatArg::Integer { value, .. }) => return Ok(self.signed(value < 0, group_if(self.flags, value.unsigned_abs().to_string()))),
+            ('o' | 'x', &FormatArg::Integer { value, bits }) => {
+                // negative numbers are shown as their two's complement
+                let unsigned = value as u64 & (u64::MAX >> (64 - bits));
+                let (digits, prefix) = match self.conversion {
+                    'o' => (format!("{unsigned:o}"), "0"),
+                    _ => (format!("{unsigned:x}"), "0x"),
+                };
+                let prefix = if self.flags.contains(Flags::ALTERNATE) { prefix } else { "" };
+                let text = match self.width {
+                    Some(width) if self.flags.contains(Flags::ZERO_PAD) => {
+                        format!("{prefix}{digits:0>0$}", width.saturating_sub(prefix.len()))
+                    }
+                    _ => format!("{prefix}{digits}"),
+                };
+                return Ok(self.justify_cased(text));
+            }
+            ('e' | 'f' | 'g', &FormatArg::Float(value)) => return Ok(self.format_float(value)),
+            _ => return Err(FormatError::conversion(self, arg)),
+        };
+        let text = match self.precision {
+            Some(precision) => text.chars().take(precision).collect(),
+            None => text,
+        };
+        Ok(self.justify_cased(text))
+    }
+    fn justify_cased(&self, text: String) -> String {
+        let text = if self.upper_case { text.to_uppercase() } else { text };
+        justify(self, text)
+    }
+    /// Adds the sign of a number as the flags ask for, zero padding and justifies it
+    fn signed(&self, negative: bool, magnitude: String) -> String {
+        let (prefix, suffix) = match (negative, self.flags) {
+            (true, flags) if flags.contains(Flags::PARENTHESES) => ("(", ")"),
+            (true, _) => ("-", ""),
+            (false, flags) if flags.contains(Flags::PLUS) => ("+", ""),
+            (false, flags) if flags.contains(Flags::LEADING_SPACE) => (" ", ""),
+            (false, _) => ("", ""),
+        };
+        let text = match self.width {
+            Some(width) if self.flags.contains(Flags::ZERO_PAD) => {
+                let zeros = width.saturating_sub(prefix.len() + magnitude.chars().count() + suffix.len());
+                format!("{prefix}{}{magnitude}{suffix}", "0".repeat(zeros))
+            }
+            _ => format!("{prefix}{magnitude}{suffix}"),
+        };
+        self.justify_cased(text)
+    }
+    fn format_float(&self, value: f64) -> String {
+        if !value.is_finite() {
+            let text = if value.is_nan() { "NaN" } else { "Infinity" };
+            // NaN has no sign and is never padded with zeros
+            let specifier = Specifier { flags: self.flags - Flags::ZERO_PAD, ..self.clone() };
+            return if value.is_nan() { self.justify_cased(text.into()) } else { specifier.signed(value < 0.0, text.into()) };
+        }
+        let decimal = Decimal::new(value.abs());
+        let magnitude = match self.conversion {
+            'e' => decimal.scientific(self.precision.unwrap_or(6)),
+            'f' => group_if(self.flags, decimal.fixed(self.precision.unwrap_or(6))),
+            _ => {
+                let precision = match self.precision {
+                    Some(0) => 1,
+                    precision => precision.unwrap_or(6),
+                };
+                let rounded = decimal.round_significant(precision);
+                let exponent = rounded.point - 1;
+                if value == 0.0 || (-4..precision as i32).contains(&exponent) {
+                    group_if(self.flags, rounded.fixed((precision as i32 - 1 - exponent).max(0) as usize))
+                } else {
+                    rounded.scientific(precision - 1)
+                }
+            }
+        };
+        self.signed(value.is_sign_negative(), magnitude)
+    }
+}
+
+/// Pads a formatted value to the width of the specifier, on the left unless it is left-justified
+fn justify(specifier: &Specifier, text: String) -> String {
+    let Some(width) = specifier.width else { return text };
+    let padding = " ".repeat(width.saturating_sub(text.chars().count()));
+    if specifier.flags.contains(Flags::LEFT_JUSTIFY) {
+        text + &padding
+    } else {
+        padding + &text
+    }
+}
+
+/// Separates groups of three digits in the integer part with commas if the `,` flag is set
+fn group_if(flags: Flags, number: String) -> String {
+    if !flags.contains(Flags::GROUP) {
+        return number;
+    }
+    let integer_end = number.find('.').unwrap_or(number.len());
+    let (integer, fraction) = number.split_at(integer_end);
+    let mut grouped = String::new();
+    for (i, c) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped + fraction
+}
+
+/// A non-negative number as decimal digits, `0.d1d2d3... * 10^point`
+#[derive(Debug, Clone)]
+struct Decimal {
+    digits: Vec<u8>,
+    point: i32,
+}
+
+impl Decimal {
+    /// The shortest digits that identify the value, like `Double.toString` uses
+    fn new(value: f64) -> Self {
+        if value == 0.0 {
+            return Self { digits: vec![0], point: 1 };
+        }
+        let scientific = format!("{value:e}");
+        let (mantissa, exponent) = scientific.split_once('e').unwrap();
+        let digits = mantissa.bytes().filter(u8::is_ascii_digit).map(|b| b - b'0').collect();
+        Self { digits, point: exponent.parse::<i32>().unwrap() + 1 }
+    }
+    /// Rounds half up to `count` digits, `Formatter` rounds the shortest digits rather than the
+    /// exact binary value
+    fn round_to(&self, count: i32) -> Self {
+        if count >= self.digits.len() as i32 {
+            return self.clone();
+        }
+        if count < 0 {
+            return Self { digits: vec![0], point: 1 };
+        }
+        let mut digits = self.digits[..count as usize].to_vec();
+        let mut point = self.point;
+        if self.digits[count as usize] >= 5 {
+            let mut i = digits.len();
+            loop {
+                if i == 0 {
+                    digits.insert(0, 1);
+                    point += 1;
+                    break;
+                }
+                i -= 1;
+                if digits[i] == 9 {
+                    digits[i] = 0;
+                } else {
+                    digits[i] += 1;
+                    break;
+                }
+            }
+        }
+        if digits.is_empty() {
+            return Self { digits: vec![0], point: 1 };
+        }
+        Self { digits, point }
+    }
+    fn round_significant(&self, count: usize) -> Self {
+        if self.digits == [0] { self.clone() } else { self.round_to(count as i32) }
+    }
+    fn digit(&self, i: i32) -> char {
+        (b'0' + self.digits.get(i as usize).copied().unwrap_or(0)) as char
+    }
+    /// `ddd.ddd` with `precision` digits after the point
+    fn fixed(&self, precision: usize) -> String {
+        let rounded = self.round_to(self.point + precision as i32);
+        let mut text: String = if rounded.point > 0 {
+            (0..rounded.point).map(|i| rounded.digit(i)).collect()
+        } else {
+            "0".into()
+        };
+        if precision > 0 {
+            text.push('.');
+            text.extend((0..precision as i32).map(|i| {
+                let i = rounded.point + i;
+                if i < 0 { '0' } else { rounded.digit(i) }
+            }));
+        }
+        text
+    }
+    /// `d.ddde+xx` with `precision` digits after the point
+    fn scientific(&self, precision: usize) -> String {
+        let rounded = self.round_significant(precision + 1);
+        let exponent = if rounded.digits == [0] { 0 } else { rounded.point - 1 };
+        let mut text = String::from(rounded.digit(0));
+        if precision > 0 {
+            text.push('.');
+            text.extend((1..=precision as i32).map(|i| rounded.digit(i)));
+        }
+        let sign = if exponent < 0 { '-' } else { '+' };
+        text + &format!("e{sign}{:02}", exponent.abs())
+    }
+}
+
+impl RuntimeCtx<'_> {
+    /// Formats the format string and the `Object[]` of arguments on top of the stack into `result`
+    /// and pops them.
+    ///
+    /// If the format is invalid or `toString()` of an argument throws, the exception is thrown and
+    /// `false` is returned. `result` then has what was formatted before, which `printf` still
+    /// prints like `Formatter` does.
+    pub(super) fn format_java(&mut self, result: &mut String) -> Result<bool> {
+        // both stay on the stack while `toString()` and `hashCode()` may run
+        let (format, args) = (self.stack[self.stack.len() - 2], self.top());
+        let Some(format) = self.read_string_object(format) else {
+            self.throw_new("java/lang/NullPointerException", None)?;
+            return Ok(false);
+        };
+        let pieces = match parse(&format) {
+            Ok(pieces) => pieces,
+            Err(error) => return self.throw_format_error(error),
+        };
+        let arg_count = if args == Value::NULL { 0 } else { self.read_u32_ref(args.offset(ARRAY_LENGTH_OFFSET)).unwrap() as usize };
+
+        let (mut next, mut last) = (0, None);
+        for piece in pieces {
+            let specifier = match piece {
+                Piece::Literal(literal) => {
+                    result.push_str(&literal);
+                    continue;
+                }
+                Piece::Specifier(specifier) => specifier,
+            };
+            let index = match specifier.index {
+                ArgIndex::None => unreachable!(),
+                ArgIndex::Ordinary => {
+                    next += 1;
+                    Some(next - 1)
+                }
+                ArgIndex::Previous => last,
+                ArgIndex::Explicit(n) => n.checked_sub(1),
+            };
+            last = index;
+            // a null array stands for arguments that are all null
+            let arg = match index {
+                Some(_) if args == Value::NULL => Value::NULL,
+                Some(i) if i < arg_count => Value(self.read_u32_ref(args.offset(ARRAY_DATA_OFFSET + 4 * i as u32)).unwrap()),
+                _ => return self.throw_format_error(FormatError::missing_argument(&specifier)),
+            };
+            let arg = match specifier.category() {
+                _ if arg == Value::NULL => FormatArg::Null,
+                Category::Text => {
+                    let Some(chars) = self.string_value_of(arg)? else { return Ok(false) };
+                    FormatArg::Text(String::from_utf16_lossy(&chars))
+                }
+                Category::Hash => {
+                    let hash_code = MethodDescriptor::new_ret([], FieldDescriptor::Int);
+                    let (class, method_id) = self.runtime.find_method(self.get_class_id(arg), "hashCode", &hash_code.into()).unwrap();
+                    self.push(arg);
+                    match self.run_method(class, method_id)? {
+                        Ok(()) => FormatArg::Text(format!("{:x}", self.pop().into_i32())),
+                        Err(exception) => {
+                            self.throw(exception)?;
+                            return Ok(false);
+                        }
+                    }
+                }
+                Category::Value => self.unbox_format_arg(arg),
+            };
+            match specifier.format(&arg) {
+                Ok(text) => result.push_str(&text),
+                Err(error) => return self.throw_format_error(error),
+            }
+        }
+        self.pop2();
+        Ok(true)
+    }
+    /// Throws the exception for an invalid format and returns `false`
+    fn throw_format_error(&mut self, error: FormatError) -> Result<bool> {
+        self.throw_new(error.class_name, Some(error.message))?;
+        Ok(false)
+    }
+    /// The value of a box, or the class name of another object
+    fn unbox_format_arg(&self, object: Value) -> FormatArg {
+        let class = self.get_class_id(object);
+        let class_name = self.get_class_name(object);
+        let field_type = match class_name {
+            "java/lang/Boolean" => FieldDescriptor::Boolean,
+            "java/lang/Character" => FieldDescriptor::Char,
+            "java/lang/Byte" => FieldDescriptor::Byte,
+            "java/lang/Short" => FieldDescriptor::Short,
+            "java/lang/Integer" => FieldDescriptor::Int,
+            "java/lang/Long" => FieldDescriptor::Long,
+            "java/lang/Float" => FieldDescriptor::Float,
+            "java/lang/Double" => FieldDescriptor::Double,
+            _ => return FormatArg::Other(class_name.replace('/', ".")),
+        };
+        let Some((_, offset)) = self.runtime.resolve_field(class, "value", &field_type.clone().into()) else {
+            return FormatArg::Other(class_name.replace('/', "."));
+        };
+        let bytes = self.ref_bytes(object.offset(offset as u32), field_type.byte_size() as usize).unwrap();
+        match embed::read_field(bytes, &field_type) {
+            JValue::Boolean(value) => FormatArg::Boolean(value),
+            JValue::Char(value) => FormatArg::Char(value),
+            JValue::Byte(value) => FormatArg::Integer { value: value as i64, bits: 8 },
+            JValue::Short(value) => FormatArg::Integer { value: value as i64, bits: 16 },
+            JValue::Int(value) => FormatArg::Integer { value: value as i64, bits: 32 },
+            JValue::Long(value) => FormatArg::Integer { value, bits: 64 },
+            JValue::Float(value) => FormatArg::Float(value as f64),
+            JValue::Double(value) => FormatArg::Float(value),
+            JValue::Void | JValue::Reference(_) => unreachable!(),
+        }
+    }
+}
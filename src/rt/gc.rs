@@ -0,0 +1,275 @@
+//! Mark-compact garbage collector for the heap of a [`RuntimeCtx`].
+//!
+//! Objects in fields and arrays are found precisely through the reference offsets of their class.
+//! The operand stack and locals are untyped, so they are scanned conservatively: every value that
+//! is the address of an object keeps it alive and pins it in place, since the value cannot be
+//! rewritten after a move. All other live objects slide towards the start of the heap, and the gaps
+//! left in front of pinned objects are covered with filler blocks that the next collection reclaims.
+
+use std::time::{Duration, Instant};
+
+use super::{string::{Coder, STRING_CODER_OFFSET, STRING_LENGTH_OFFSET}, ArrayComponent, AsBytes32Aligned, HeaderFlags, ObjectHeader, Reference, RuntimeCtx, Value, ARRAY_DATA_OFFSET, ARRAY_LENGTH_OFFSET, STRING_CLASS};
+
+/// Class id in the header of a filler block, the second header word holds the size of the block
+const FILLER_CLASS: u32 = u32::MAX;
+/// Bytes that can at least be allocated between two collections
+const MIN_COLLECTION_BUDGET: usize = 1 << 20;
+/// Default for [`Runtime::with_max_heap_size`](super::Runtime::with_max_heap_size)
+pub(super) const DEFAULT_MAX_HEAP_SIZE: usize = 512 << 20;
+/// Heap references store the offset below `0x8000_0000`, which limits the heap to 2 GiB
+pub(super) const HEAP_ADDRESS_LIMIT: usize = 0x8000_0000 - 4;
+
+/// Counters about the collections done so far
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcStats {
+    pub collections: u64,
+    /// Bytes allocated on the heap over the whole run
+    pub allocated_bytes: u64,
+    /// Bytes reclaimed over all collections
+    pub freed_bytes: u64,
+    /// Heap size right after the last collection
+    pub live_bytes: usize,
+    /// Largest size the heap has grown to
+    pub peak_heap_bytes: usize,
+    pub total_pause: Duration,
+}
+
+/// State of the collector kept next to the heap
+#[derive(Debug, Clone)]
+pub(super) struct GcState {
+    /// Bytes that allocating instructions can allocate before the next collection
+    budget: usize,
+    /// Filler blocks in front of pinned objects as (offset, size), reused by allocations
+    gaps: Vec<(u32, u32)>,
+}
+impl GcState {
+    pub const fn new() -> Self {
+        Self { budget: MIN_COLLECTION_BUDGET, gaps: Vec::new() }
+    }
+    pub fn allocated(&mut self, size: usize) {
+        self.budget = self.budget.saturating_sub(size);
+    }
+}
+
+/// A live object found by marking
+#[derive(Debug, Clone, Copy)]
+struct LiveObject {
+    from: u32,
+    to: u32,
+    size: u32,
+    pinned: bool,
+}
+
+impl RuntimeCtx<'_> {
+    /// Size in bytes of the heap object at `offset`, including its header
+    fn heap_object_size(&self, offset: u32) -> u32 {
+        let word = offset as usize / 4;
+        let class_id = self.heap[word];
+        if class_id == FILLER_CLASS {
+            return self.heap[word + 1];
+        }
+        let length = || self.heap[word + ARRAY_LENGTH_OFFSET as usize / 4];
+        let size = if class_id == STRING_CLASS {
+            let coder = self.heap.as_bytes_32aligned()[(offset + STRING_CODER_OFFSET) as usize];
+            Coder::from_u8(coder).object_size(self.heap[word + STRING_LENGTH_OFFSET as usize / 4])
+        } else {
+            let class = self.runtime.get_class(class_id);
+            match class.array_element_size() {
+                Some(element_size) => ARRAY_DATA_OFFSET + length() * element_size as u32,
+                None => class.get_aligned_data_size() as u32,
+            }
+        };
+        (size + 3) & !3
+    }
+    /// Calls `f` with the byte offset of every reference slot in the heap object at `offset`
+    fn for_each_reference_slot(&self, offset: u32, mut f: impl FnMut(u32)) {
+        let class = self.runtime.get_class(self.heap[offset as usize / 4]);
+        if let Some(ArrayComponent::Reference(_)) = class.array_component {
+            let length = self.heap[(offset + ARRAY_LENGTH_OFFSET) as usize / 4];
+            (0..length).for_each(|i| f(offset + ARRAY_DATA_OFFSET + 4 * i));
+        } else {
+            class.ref_offsets.iter().for_each(|&field| f(offset + field as u32));
+        }
+    }
+    /// Word index in the statics of every reference field of the objects there
+    fn static_reference_slots(&self) -> Vec<usize> {
+        let runtime = &self.runtime;
+        runtime.static_objects.iter().flat_map(|&offset| {
+            let class = runtime.get_class(runtime.statics[offset as usize / 4]);
+            class.ref_offsets.iter().map(move |&field| (offset + field as u32) as usize / 4)
+        }).collect()
+    }
+    fn set_heap_flags(&mut self, offset: u32, flags: HeaderFlags) {
+        let word = offset as usize / 4;
+        let header = ObjectHeader { class_id: self.heap[word], word: self.heap[word + 1] };
+        self.heap[word + 1] = header.with_flags(flags).to_words()[1];
+    }
+    fn heap_flags(&self, offset: u32) -> HeaderFlags {
+        let word = offset as usize / 4;
+        ObjectHeader { class_id: self.heap[word], word: self.heap[word + 1] }.flags()
+    }
+
+    /// Collects garbage and compacts the heap.
+    ///
+    /// Only references on the operand stack, in locals, in fields and pinned for the embedder are
+    /// seen by the collector, so this must not be called while native code holds a heap reference
+    /// somewhere else.
+    pub fn collect_garbage(&mut self) {
+        let start_time = Instant::now();
+        let heap_size = self.heap.len() as u32 * 4;
+
+        let mut object_starts = Vec::new();
+        let mut used_bytes = 0;
+        let mut offset = 0;
+        while offset < heap_size {
+            let size = self.heap_object_size(offset);
+            if self.heap[offset as usize / 4] != FILLER_CLASS {
+                object_starts.push(offset);
+                used_bytes += size as usize;
+            }
+            offset += size;
+        }
+
+        // mark, starting from the conservative roots on the stacks of all threads, the references
+        // held by the embedder, threads and monitors, the static fields and the fields of the
+        // objects in the statics
+        let mut pinned = Vec::new();
+        let mut worklist = Vec::new();
+        for value in self.stack.iter().chain(&self.host_refs).copied().chain(self.sched.roots()) {
+            if let Reference::Heap(offset) = value.into_ref() && object_starts.binary_search(&offset).is_ok() {
+                pinned.push(offset);
+                worklist.push(offset);
+            }
+        }
+        pinned.sort_unstable();
+        pinned.dedup();
+        for class in &self.runtime.classes {
+            for &field in &class.static_ref_offsets {
+                let value = u32::from_ne_bytes(class.static_fields[field as usize..][..4].try_into().unwrap());
+                if let Reference::Heap(offset) = Value(value).into_ref() {
+                    worklist.push(offset);
+                }
+            }
+        }
+        let static_slots = self.static_reference_slots();
+        for &slot in &static_slots {
+            if let Reference::Heap(offset) = Value(self.runtime.statics[slot]).into_ref() {
+                worklist.push(offset);
+            }
+        }
+        while let Some(offset) = worklist.pop() {
+            let flags = self.heap_flags(offset);
+            if flags.contains(HeaderFlags::MARKED) {
+                continue;
+            }
+            self.set_heap_flags(offset, flags | HeaderFlags::MARKED);
+            self.for_each_reference_slot(offset, |slot| {
+                if let Reference::Heap(target) = Value(self.heap[slot as usize / 4]).into_ref() {
+                    worklist.push(target);
+                }
+            });
+        }
+
+        // compute where the live objects go
+        let mut live = Vec::new();
+        let mut free = 0;
+        for &from in &object_starts {
+            if !self.heap_flags(from).contains(HeaderFlags::MARKED) {
+                continue;
+            }
+            let size = self.heap_object_size(from);
+            let pinned = pinned.binary_search(&from).is_ok();
+            let to = if pinned { from } else { free };
+            live.push(LiveObject { from, to, size, pinned });
+            free = to + size;
+        }
+        let forward = |reference: u32| -> u32 {
+            let Reference::Heap(offset) = Value(reference).into_ref() else { return reference };
+            let i = live.binary_search_by_key(&offset, |object| object.from).unwrap();
+            Value::new_ref_heap(live[i].to).into_u32()
+        };
+
+        // update the references in live objects, static fields and objects in the statics before
+        // anything moves
+        for object in &live {
+            let mut slots = Vec::new();
+            self.for_each_reference_slot(object.from, |slot| slots.push(slot as usize / 4));
+            for slot in slots {
+                self.heap[slot] = forward(self.heap[slot]);
+            }
+        }
+        for class in &mut self.runtime.classes {
+            for &field in &class.static_ref_offsets {
+                let bytes = &mut class.static_fields[field as usize..][..4];
+                let value = forward(u32::from_ne_bytes(bytes.try_into().unwrap()));
+                bytes.copy_from_slice(&value.to_ne_bytes());
+            }
+        }
+        for slot in static_slots {
+            self.runtime.statics[slot] = forward(self.runtime.statics[slot]);
+        }
+
+        // slide the objects down, objects only ever move towards lower addresses
+        let mut free = 0;
+        let mut live_bytes = 0;
+        self.gc.gaps.clear();
+        for object in &live {
+            if object.pinned && free < object.to {
+                let filler = free as usize / 4;
+                self.heap[filler] = FILLER_CLASS;
+                self.heap[filler + 1] = object.to - free;
+                self.gc.gaps.push((free, object.to - free));
+            }
+            let (from, to, size) = (object.from as usize / 4, object.to as usize / 4, object.size as usize / 4);
+            self.heap.copy_within(from..from + size, to);
+            let flags = self.heap_flags(object.to);
+            self.set_heap_flags(object.to, flags - HeaderFlags::MARKED);
+            free = object.to + object.size;
+            live_bytes += object.size as usize;
+        }
+        self.heap.truncate(free as usize / 4);
+
+        let stats = &mut self.runtime.gc_stats;
+        stats.collections += 1;
+        stats.freed_bytes += (used_bytes - live_bytes) as u64;
+        stats.live_bytes = live_bytes;
+        stats.total_pause += start_time.elapsed();
+        self.gc.budget = live_bytes.max(MIN_COLLECTION_BUDGET);
+    }
+    /// Makes sure an allocating instruction has room for `size` more bytes, collecting garbage if
+    /// enough has been allocated since the last collection.
+    ///
+    /// Returns `false` if the maximum heap size would be exceeded even after collecting.
+    pub(super) fn reserve_heap(&mut self, size: usize) -> bool {
+        let fits = |ctx: &Self| {
+            ctx.gc.gaps.iter().any(|&(_, gap)| gap_fits(gap as usize, size))
+                || ctx.heap.len() * 4 + size <= ctx.runtime.max_heap_size
+        };
+        if size > self.gc.budget || !fits(self) {
+            self.collect_garbage();
+        }
+        fits(self)
+    }
+    /// Takes `size` zeroed bytes from a gap left by the last collection, if one is big enough
+    pub(super) fn take_heap_gap(&mut self, size: usize) -> Option<u32> {
+        let i = self.gc.gaps.iter().position(|&(_, gap)| gap_fits(gap as usize, size))?;
+        let (offset, gap) = self.gc.gaps[i];
+        let start = offset as usize / 4;
+        self.heap[start..start + size / 4].fill(0);
+        if gap as usize == size {
+            self.gc.gaps.remove(i);
+        } else {
+            // the rest of the gap stays a filler block so the heap can still be walked
+            let rest = (offset + size as u32, gap - size as u32);
+            self.heap[rest.0 as usize / 4] = FILLER_CLASS;
+            self.heap[rest.0 as usize / 4 + 1] = rest.1;
+            self.gc.gaps[i] = rest;
+        }
+        Some(offset)
+    }
+}
+
+/// Whether an object of `size` bytes can be put into a gap, the rest must fit a filler header
+const fn gap_fits(gap: usize, size: usize) -> bool {
+    gap == size || gap >= size + 8
+}
@@ -0,0 +1,74 @@
+use bitflags::bitflags;
+
+/// Size in bytes of the header in front of every object, both on the heap and in the statics area
+pub const OBJECT_HEADER_SIZE: u16 = 8;
+
+bitflags! {
+    /// Low bits of the second header word
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct HeaderFlags: u32 {
+        const LOCKED = 0x01;
+        const MARKED = 0x02;
+    }
+}
+
+/// The two words at the start of every object.
+///
+/// The first word is the id of the runtime class, the second packs the identity hash into the upper
+/// 24 bits and the lock and GC state into the lower 8. A hash of 0 means none has been assigned yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObjectHeader {
+    pub class_id: u32,
+    pub word: u32,
+}
+
+impl ObjectHeader {
+    const HASH_SHIFT: u32 = 8;
+    const FLAGS_MASK: u32 = (1 << Self::HASH_SHIFT) - 1;
+
+    pub const fn new(class_id: u32) -> Self {
+        Self { class_id, word: 0 }
+    }
+    pub const fn identity_hash(self) -> Option<u32> {
+        match self.word >> Self::HASH_SHIFT {
+            0 => None,
+            hash => Some(hash),
+        }
+    }
+    pub const fn with_identity_hash(self, hash: u32) -> Self {
+        debug_assert!(hash != 0 && hash < 1 << (32 - Self::HASH_SHIFT));
+        Self { class_id: self.class_id, word: (hash << Self::HASH_SHIFT) | (self.word & Self::FLAGS_MASK) }
+    }
+    pub const fn flags(self) -> HeaderFlags {
+        HeaderFlags::from_bits_retain(self.word & Self::FLAGS_MASK)
+    }
+    pub const fn with_flags(self, flags: HeaderFlags) -> Self {
+        Self { class_id: self.class_id, word: (self.word & !Self::FLAGS_MASK) | (flags.bits() & Self::FLAGS_MASK) }
+    }
+    pub const fn to_words(self) -> [u32; 2] {
+        [self.class_id, self.word]
+    }
+}
+
+/// Generates the 24-bit identity hashes stored in object headers (xorshift, never 0)
+#[derive(Debug, Clone)]
+pub(crate) struct HashGenerator(u32);
+
+impl HashGenerator {
+    pub const fn new() -> Self {
+        Self(0x2545_f491)
+    }
+    pub fn next_hash(&mut self) -> u32 {
+        loop {
+            let mut x = self.0;
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            self.0 = x;
+            let hash = x >> ObjectHeader::HASH_SHIFT;
+            if hash != 0 {
+                return hash;
+            }
+        }
+    }
+}
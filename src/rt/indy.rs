@@ -0,0 +1,278 @@
+//! Linking and running `invokedynamic` call sites.
+//!
+//! Bootstrap methods are not run as Java code. The runtime recognizes the bootstrap methods of the
+//! JDK that it knows and links the call site to a native implementation instead. A call site is
+//! linked the first time its instruction runs and is cached by the position of that instruction.
+
+use crate::{class::{BootstrapMethod, ClassFile, ConstIndex, Constant}, descriptor::{FieldDescriptor, MethodDescriptor}};
+
+use super::{string, values_into_f64, values_into_u64, Result, RuntimeCtx, RuntimeInfo, Value};
+
+/// Recipe tag for an argument of `StringConcatFactory.makeConcatWithConstants`
+const TAG_ARG: u16 = 1;
+/// Recipe tag for a constant of `StringConcatFactory.makeConcatWithConstants`
+const TAG_CONST: u16 = 2;
+
+/// A bootstrap method of a class with the kinds of its static arguments, which the runtime
+/// constant pool does not keep
+#[derive(Debug, Clone)]
+pub(super) struct Bootstrap {
+    pub method_ref: ConstIndex,
+    pub arguments: Box<[(ConstIndex, ConstantKind)]>,
+}
+
+/// What a static argument of a bootstrap method is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum ConstantKind {
+    String,
+    Integer,
+    Float,
+    Long,
+    Double,
+    /// A class, method handle or method type
+    Other,
+}
+
+impl Bootstrap {
+    pub fn new(class_file: &ClassFile, method: &BootstrapMethod) -> Self {
+        let arguments = method.bootstrap_arguments.iter().map(|&index| {
+            let kind = match class_file.constant(index) {
+                Constant::String { .. } => ConstantKind::String,
+                Constant::Integer { .. } => ConstantKind::Integer,
+                Constant::Float { .. } => ConstantKind::Float,
+                Constant::Long { .. } => ConstantKind::Long,
+                Constant::Double { .. } => ConstantKind::Double,
+                _ => ConstantKind::Other,
+            };
+            (index, kind)
+        });
+        Bootstrap { method_ref: method.bootstrap_method_ref, arguments: arguments.collect() }
+    }
+}
+
+#[derive(Debug)]
+pub(super) enum CallSite {
+    /// `StringConcatFactory.makeConcat` and `makeConcatWithConstants`
+    StringConcat {
+        recipe: Box<[RecipePart]>,
+        arg_types: Box<[FieldDescriptor]>,
+    },
+    /// `LambdaMetafactory` with captured arguments, a new instance of the spun `class` is created
+    /// every time with the arguments stored at the offsets of `fields`
+    Lambda {
+        class: u32,
+        fields: Box<[(FieldDescriptor, u16)]>,
+    },
+    /// A call site that always produces the same reference, like a lambda that captures nothing
+    Constant(Value),
+}
+
+/// The target of a `MethodHandle` constant
+#[derive(Debug, Clone)]
+pub(super) struct MethodRef {
+    /// The reference kind, like `REF_invokeStatic`
+    pub kind: u8,
+    pub class_name: Box<str>,
+    pub name: Box<str>,
+    pub descriptor: MethodDescriptor,
+}
+
+#[derive(Debug)]
+pub(super) enum RecipePart {
+    /// Text from the recipe and its constants
+    Literal(Box<[u16]>),
+    /// The next dynamic argument
+    Argument,
+}
+
+impl RuntimeCtx<'_> {
+    /// Executes `invokedynamic` with the call site at `ins_pc`, linking it first if needed
+    pub(super) fn invoke_dynamic(&mut self, cpn: ConstIndex, ins_pc: usize) -> Result<()> {
+        let call_site = match self.runtime.call_sites.get(&ins_pc) {
+            Some(call_site) => call_site.clone(),
+            None => {
+                let Some(call_site) = self.link_call_site(cpn)? else { return Ok(()) };
+                self.runtime.call_sites.entry(ins_pc).or_insert(call_site.into()).clone()
+            }
+        };
+        match &*call_site {
+            CallSite::StringConcat { recipe, arg_types } => self.string_concat(recipe, arg_types),
+            CallSite::Lambda { class, fields } => self.new_lambda(*class, fields),
+            &CallSite::Constant(value) => {
+                self.push(value);
+                Ok(())
+            }
+        }
+    }
+    /// Resolves the bootstrap method of an `InvokeDynamic` constant to a native call site.
+    ///
+    /// If the bootstrap method is not supported, a `BootstrapMethodError` is thrown and `None` is
+    /// returned.
+    fn link_call_site(&mut self, cpn: ConstIndex) -> Result<Option<CallSite>> {
+        let (bootstrap_index, name_and_type) = self.read_constant(cpn).invokedynamic();
+        let (method_name, descriptor) = self.read_constant(name_and_type).nameandtype();
+        let descriptor = MethodDescriptor::from_bytes(self.read_utf8_constant(descriptor).as_bytes())?;
+
+        let RuntimeInfo::Bytecode { bootstrap_methods, .. } = &self.runtime.get_class(self.cur_class).runtime_info else {
+            unreachable!()
+        };
+        let Bootstrap { method_ref, arguments } = bootstrap_methods[bootstrap_index as usize].clone();
+        let bootstrap = self.read_method_handle(method_ref)?;
+
+        Ok(Some(match (&*bootstrap.class_name, &*bootstrap.name) {
+            ("java/lang/invoke/StringConcatFactory", "makeConcat") => CallSite::StringConcat {
+                recipe: descriptor.arg_types.iter().map(|_| RecipePart::Argument).collect(),
+                arg_types: descriptor.arg_types,
+            },
+            ("java/lang/invoke/StringConcatFactory", "makeConcatWithConstants") => {
+                match self.parse_concat_recipe(&arguments, descriptor.arg_types.len()) {
+                    Ok(recipe) => CallSite::StringConcat { recipe, arg_types: descriptor.arg_types },
+                    Err(message) => {
+                        self.throw_new("java/lang/BootstrapMethodError", Some(message))?;
+                        return Ok(None);
+                    }
+                }
+            }
+            ("java/lang/invoke/LambdaMetafactory", name @ ("metafactory" | "altMetafactory")) => {
+                let method_name = self.read_utf8_constant(method_name).to_string();
+                let arguments: Vec<_> = arguments.iter().map(|&(index, _)| index).collect();
+                self.link_lambda(&method_name, descriptor, &arguments, name == "altMetafactory")?
+            }
+            (class_name, name) => {
+                let message = format!("bootstrap method {}.{name} is not supported", class_name.replace('/', "."));
+                self.throw_new("java/lang/BootstrapMethodError", Some(message))?;
+                return Ok(None);
+            }
+        }))
+    }
+    /// Parses the recipe of `StringConcatFactory.makeConcatWithConstants`, the first static
+    /// argument, with the constants that follow it into literals and arguments.
+    ///
+    /// Fails with the message of the `BootstrapMethodError` if the recipe is not a string, a
+    /// constant is not a string or a primitive, or the recipe takes more or fewer constants or
+    /// arguments than there are.
+    fn parse_concat_recipe(&self, static_args: &[(ConstIndex, ConstantKind)], arg_count: usize) -> std::result::Result<Box<[RecipePart]>, String> {
+        let recipe = match static_args.split_first() {
+            Some((&(recipe, ConstantKind::String), _)) => self.read_string_chars(self.read_constant(recipe).value()),
+            _ => None,
+        };
+        let Some(recipe) = recipe else {
+            return Err("the recipe of a string concatenation is not a string".into());
+        };
+        let mut constants = Vec::with_capacity(static_args.len() - 1);
+        for &(index, kind) in &static_args[1..] {
+            let value = self.read_constant(index).value();
+            let text = match kind {
+                ConstantKind::String => self.read_string_chars(value),
+                ConstantKind::Integer => Some(value.into_i32().to_string().encode_utf16().collect()),
+                ConstantKind::Float => Some(string::float_to_string(value.into_f32()).encode_utf16().collect()),
+                ConstantKind::Long => {
+                    let long = values_into_u64(self.read_constant(index).wide_value(self.read_constant(index + 1))) as i64;
+                    Some(long.to_string().encode_utf16().collect())
+                }
+                ConstantKind::Double => {
+                    let double = values_into_f64(self.read_constant(index).wide_value(self.read_constant(index + 1)));
+                    Some(string::double_to_string(double).encode_utf16().collect())
+                }
+                ConstantKind::Other => None,
+            };
+            let Some(text) = text else {
+                return Err(format!("constant {} of a string concatenation is not a string or a primitive", constants.len()));
+            };
+            constants.push(text);
+        }
+        let constants_passed = constants.len();
+        let mut constants = constants.into_iter();
+        let mut parts = Vec::new();
+        let mut literal = Vec::new();
+        let (mut args_wanted, mut constants_wanted) = (0, 0);
+        for c in recipe {
+            match c {
+                TAG_ARG => {
+                    if !literal.is_empty() {
+                        parts.push(RecipePart::Literal(literal.split_off(0).into()));
+                    }
+                    parts.push(RecipePart::Argument);
+                    args_wanted += 1;
+                }
+                TAG_CONST => {
+                    literal.extend(constants.next().unwrap_or_default());
+                    constants_wanted += 1;
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(RecipePart::Literal(literal.into()));
+        }
+        // the messages of the JDK's `StringConcatException`
+        if constants_wanted != constants_passed {
+            return Err(format!("Mismatched number of concat constants: recipe wants {constants_wanted} constants, but only {constants_passed} are passed"));
+        }
+        if args_wanted != arg_count {
+            return Err(format!("Mismatched number of concat arguments: recipe wants {args_wanted} arguments, but signature provides {arg_count}"));
+        }
+        Ok(parts.into())
+    }
+    /// Reads a `MethodHandle` constant of the current class
+    pub(super) fn read_method_handle(&self, n: ConstIndex) -> Result<MethodRef> {
+        let (kind, method_ref) = self.read_constant(n).methodhandle();
+        let (class_index, name_and_type) = self.read_constant(method_ref).methodref();
+        let (name, descriptor) = self.read_constant(name_and_type).nameandtype();
+        Ok(MethodRef {
+            kind,
+            class_name: self.read_utf8_constant(self.read_constant(class_index).class()).into(),
+            name: self.read_utf8_constant(name).into(),
+            descriptor: MethodDescriptor::from_bytes(self.read_utf8_constant(descriptor).as_bytes())?,
+        })
+    }
+    fn string_concat(&mut self, recipe: &[RecipePart], arg_types: &[FieldDescriptor]) -> Result<()> {
+        let args_start = self.stack.len() - arg_types.iter().map(FieldDescriptor::unit_size).sum::<usize>();
+
+        // the arguments stay on the stack until the end, so a `toString()` that runs in between
+        // cannot lose them to the collector
+        let mut args = Vec::with_capacity(arg_types.len());
+        let mut slot = args_start;
+        for arg_type in arg_types {
+            let value = self.stack[slot];
+            let chars = match arg_type {
+                FieldDescriptor::Boolean => (if value.into_u32() != 0 { "true" } else { "false" }).encode_utf16().collect(),
+                FieldDescriptor::Char => vec![value.into_u16()],
+                FieldDescriptor::Byte |
+                FieldDescriptor::Short |
+                FieldDescriptor::Int => value.into_i32().to_string().encode_utf16().collect(),
+                FieldDescriptor::Float => string::float_to_string(value.into_f32()).encode_utf16().collect(),
+                FieldDescriptor::Long => {
+                    let long = values_into_u64((value, self.stack[slot + 1])) as i64;
+                    long.to_string().encode_utf16().collect()
+                }
+                FieldDescriptor::Double => {
+                    let double = values_into_f64((value, self.stack[slot + 1]));
+                    string::double_to_string(double).encode_utf16().collect()
+                }
+                FieldDescriptor::ClassRef(_) |
+                FieldDescriptor::ArrRef(_) => {
+                    let Some(chars) = self.string_value_of(value)? else { return Ok(()) };
+                    chars
+                }
+            };
+            args.push(chars);
+            slot += arg_type.unit_size();
+        }
+        self.stack.truncate(args_start);
+
+        let mut args = args.into_iter();
+        let mut result = Vec::new();
+        for part in recipe {
+            match part {
+                RecipePart::Literal(literal) => result.extend_from_slice(literal),
+                // linking checked that the recipe takes as many arguments as there are
+                RecipePart::Argument => result.extend(args.next().unwrap_or_default()),
+            }
+        }
+        let Some(string) = self.try_new_string(&result)? else { return Ok(()) };
+        self.push(string);
+        Ok(())
+    }
+}
+
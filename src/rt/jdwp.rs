@@ -0,0 +1,1127 @@
+//! A [`Debugger`] speaking the Java Debug Wire Protocol, which `jdb` and IDEs attach with.
+//!
+//! [`JdwpServer`] serves one debugger connected over TCP. It implements the commands to look at
+//! classes, methods, threads, frames and objects, and events for breakpoints, steps, loaded
+//! classes and threads; other commands are answered with `NOT_IMPLEMENTED` and cannot change the program.
+//!
+//! All ids are 8 bytes long. Objects are their [`Value`], pinned once sent until the debugger
+//! disposes of them. Reference types and methods are their ids plus one, as 0 means null or an
+//! obsolete method. Fields are the class id and the index of the field in it, and frames are the
+//! thread and the frame index. All threads belong to a single thread group with an id no object
+//! has.
+
+use std::{collections::HashSet, io::{self, ErrorKind, Read, Write}, net::{TcpListener, TcpStream}};
+
+use crate::{class::{ClassAccess, MethodAccess}, descriptor::FieldDescriptor};
+
+use super::{Breakpoint, ClassInfo, DebugSession, Debugger, JValue, Location, MethodInfo, Resume, Result, RtError, StopReason, ThreadStatus, Value};
+
+const HANDSHAKE: &[u8] = b"JDWP-Handshake";
+const REPLY_FLAG: u8 = 0x80;
+const HEADER_SIZE: usize = 11;
+/// The id of the only thread group, above all object ids
+const THREAD_GROUP: u64 = 1 << 32;
+/// Version of the protocol, the one of Java 17
+const JDWP_VERSION: (i32, i32) = (17, 0);
+
+mod error {
+    pub const INVALID_THREAD: u16 = 10;
+    pub const THREAD_NOT_SUSPENDED: u16 = 13;
+    pub const INVALID_OBJECT: u16 = 20;
+    pub const INVALID_CLASS: u16 = 21;
+    pub const INVALID_METHODID: u16 = 23;
+    pub const INVALID_FIELDID: u16 = 25;
+    pub const INVALID_FRAMEID: u16 = 30;
+    pub const INVALID_SLOT: u16 = 35;
+    pub const NOT_IMPLEMENTED: u16 = 99;
+    pub const ABSENT_INFORMATION: u16 = 101;
+    pub const ILLEGAL_ARGUMENT: u16 = 103;
+    pub const INVALID_INDEX: u16 = 503;
+    pub const INVALID_LENGTH: u16 = 504;
+}
+
+mod event_kind {
+    pub const SINGLE_STEP: u8 = 1;
+    pub const BREAKPOINT: u8 = 2;
+    pub const EXCEPTION: u8 = 4;
+    pub const THREAD_START: u8 = 6;
+    pub const THREAD_DEATH: u8 = 7;
+    pub const CLASS_PREPARE: u8 = 8;
+    pub const CLASS_UNLOAD: u8 = 9;
+    pub const VM_START: u8 = 90;
+    pub const VM_DEATH: u8 = 99;
+}
+
+mod suspend_policy {
+    pub const NONE: u8 = 0;
+    pub const ALL: u8 = 2;
+}
+
+/// A command error code, or the data of the reply
+type Reply<T = Vec<u8>> = std::result::Result<T, u16>;
+
+/// Serves a debugger attached over JDWP, see the [module docs](self).
+///
+/// Threads are green threads, so all of them are suspended whenever one is. Once the debugger
+/// disposes of the connection or goes away the program runs on without breakpoints.
+pub struct JdwpServer {
+    /// `None` once the debugger is gone
+    stream: Option<TcpStream>,
+    suspend_on_start: bool,
+    /// Suspends asked for and not resumed yet, commands are served until it is back to 0
+    suspend_count: u32,
+    next_packet_id: u32,
+    requests: Vec<EventRequest>,
+    next_request_id: i32,
+    /// The objects sent to the debugger, which are pinned
+    objects: HashSet<u32>,
+    /// Set by `VirtualMachine.Exit`
+    exit: Option<i32>,
+    /// Set by `VirtualMachine.Dispose`
+    disposed: bool,
+    /// Told to the debugger, which finds source files next to the classes
+    class_path: Vec<String>,
+}
+
+/// A request for events from `EventRequest.Set`, with the modifiers that are supported
+#[derive(Debug, Default)]
+struct EventRequest {
+    id: i32,
+    kind: u8,
+    suspend_policy: u8,
+    /// Matches to skip before the event is reported once
+    count: Option<u32>,
+    thread: Option<usize>,
+    class: Option<u32>,
+    /// Patterns of class names, with whether they exclude the classes they match
+    class_patterns: Vec<(String, bool)>,
+    location: Option<CodeLocation>,
+    step: Option<(usize, Resume)>,
+    /// The session breakpoint of a breakpoint request
+    breakpoint: Option<usize>,
+}
+
+/// A location in the code of a method
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CodeLocation {
+    class: u32,
+    method: u16,
+    index: u64,
+}
+
+/// An event of a composite event packet, with its data after the request id
+struct Event {
+    kind: u8,
+    request: i32,
+    suspend_policy: u8,
+    data: Vec<u8>,
+}
+
+struct Packet {
+    id: u32,
+    flags: u8,
+    command_set: u8,
+    command: u8,
+    data: Vec<u8>,
+}
+
+impl JdwpServer {
+    /// Waits for a debugger to connect and exchanges the handshake. With `suspend`, the program
+    /// waits before its first instruction until the debugger resumes it.
+    pub fn accept(listener: &TcpListener, suspend: bool) -> io::Result<Self> {
+        let (mut stream, _) = listener.accept()?;
+        let mut handshake = [0; HANDSHAKE.len()];
+        stream.read_exact(&mut handshake)?;
+        if handshake != HANDSHAKE {
+            return Err(io::Error::new(ErrorKind::InvalidData, "the debugger did not send the JDWP handshake"));
+        }
+        stream.write_all(HANDSHAKE)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream: Some(stream),
+            suspend_on_start: suspend,
+            suspend_count: 0,
+            next_packet_id: 1,
+            requests: Vec::new(),
+            next_request_id: 1,
+            objects: HashSet::new(),
+            exit: None,
+            disposed: false,
+            class_path: Vec::new(),
+        })
+    }
+    /// Sets the class path told to the debugger, `jdb` looks for source files in it
+    pub fn with_class_path(mut self, class_path: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.class_path = class_path.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+impl Debugger for JdwpServer {
+    fn stopped(&mut self, session: &mut DebugSession<'_, '_>, reason: StopReason) -> Result<Resume> {
+        if self.stream.is_none() {
+            return Ok(Resume::Continue);
+        }
+        let events = match reason {
+            StopReason::Start => {
+                let thread = self.thread_id(session, session.stopped_thread());
+                let suspend_policy = if self.suspend_on_start { suspend_policy::ALL } else { suspend_policy::NONE };
+                vec![Event { kind: event_kind::VM_START, request: 0, suspend_policy, data: thread.to_be_bytes().into() }]
+            }
+            StopReason::ClassLoaded(class) => self.class_prepare_events(session, class),
+            StopReason::ThreadStarted(thread) => self.thread_events(session, event_kind::THREAD_START, thread),
+            StopReason::ThreadEnded(thread) => self.thread_events(session, event_kind::THREAD_DEATH, thread),
+            StopReason::Breakpoint(_) | StopReason::Step => self.location_events(session, reason == StopReason::Step),
+            StopReason::Suspended => Vec::new(),
+        };
+        if !events.is_empty() {
+            self.send_events(session, &events);
+        }
+        if events.iter().any(|event| event.suspend_policy != suspend_policy::NONE) {
+            self.suspend_count += 1;
+        }
+        while self.suspend_count > 0 && self.stream.is_some() {
+            self.serve(session)?;
+        }
+        let thread = session.stopped_thread();
+        let step = self.requests.iter().find_map(|request| request.step.filter(|&(step_thread, _)| step_thread == thread));
+        Ok(match step {
+            // the step that was going on goes on
+            _ if reason.is_notification() => Resume::Continue,
+            Some((_, resume)) => resume,
+            None => Resume::Continue,
+        })
+    }
+    fn poll(&mut self, session: &mut DebugSession<'_, '_>) -> Result<bool> {
+        while let Some(stream) = &self.stream {
+            stream.set_nonblocking(true)?;
+            let ready = match stream.peek(&mut [0]) {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => false,
+                // the end of the stream and errors are found when reading
+                _ => true,
+            };
+            stream.set_nonblocking(false)?;
+            if !ready {
+                break;
+            }
+            self.serve(session)?;
+        }
+        Ok(self.suspend_count > 0)
+    }
+    fn finished(&mut self, _result: &Result<i32>) {
+        let mut events = vec![Event { kind: event_kind::VM_DEATH, request: 0, suspend_policy: suspend_policy::NONE, data: Vec::new() }];
+        events.extend(self.requests
+            .iter()
+            .filter(|request| request.kind == event_kind::VM_DEATH)
+            .map(|request| Event { kind: event_kind::VM_DEATH, request: request.id, suspend_policy: suspend_policy::NONE, data: Vec::new() }));
+        let data = composite_event(&events);
+        if self.send_command(64, 100, &data).is_ok() && let Some(stream) = self.stream.take() {
+            _ = stream.shutdown(std::net::Shutdown::Both);
+        }
+    }
+}
+
+impl JdwpServer {
+    /// Reads a command and answers it
+    fn serve(&mut self, session: &mut DebugSession<'_, '_>) -> Result<()> {
+        let packet = match self.read_packet() {
+            Ok(packet) => packet,
+            Err(_) => {
+                self.detach(session);
+                return Ok(());
+            }
+        };
+        // the debugger does not answer events
+        if packet.flags & REPLY_FLAG != 0 {
+            return Ok(());
+        }
+        let reply = self.command(session, packet.command_set, packet.command, Reader(&packet.data));
+        let (error, data) = match reply {
+            Ok(data) => (0, data),
+            Err(error) => (error, Vec::new()),
+        };
+        if self.send_packet(packet.id, REPLY_FLAG, error.to_be_bytes(), &data).is_err() || self.disposed {
+            self.detach(session);
+        }
+        match self.exit {
+            Some(status) => Err(RtError::Exit(status)),
+            None => Ok(()),
+        }
+    }
+    /// Forgets the debugger, removing its breakpoints and letting the program run on
+    fn detach(&mut self, session: &mut DebugSession<'_, '_>) {
+        self.stream = None;
+        self.suspend_count = 0;
+        for request in self.requests.drain(..) {
+            if let Some(breakpoint) = request.breakpoint {
+                session.remove_breakpoint(breakpoint);
+            }
+        }
+        session.set_class_load_stops(false);
+        session.set_thread_stops(false);
+        for object in self.objects.drain() {
+            session.release(Value(object));
+        }
+    }
+    fn read_packet(&mut self) -> io::Result<Packet> {
+        let stream = self.stream.as_mut().ok_or(ErrorKind::NotConnected)?;
+        let mut header = [0; HEADER_SIZE];
+        stream.read_exact(&mut header)?;
+        let length = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+        let mut data = vec![0; length.checked_sub(HEADER_SIZE).ok_or(ErrorKind::InvalidData)?];
+        stream.read_exact(&mut data)?;
+        Ok(Packet {
+            id: u32::from_be_bytes(header[4..8].try_into().unwrap()),
+            flags: header[8],
+            command_set: header[9],
+            command: header[10],
+            data,
+        })
+    }
+    /// Sends a packet, whose last two header bytes are the command or the error code
+    fn send_packet(&mut self, id: u32, flags: u8, code: [u8; 2], data: &[u8]) -> io::Result<()> {
+        let stream = self.stream.as_mut().ok_or(ErrorKind::NotConnected)?;
+        let mut packet = Vec::with_capacity(HEADER_SIZE + data.len());
+        packet.put_u32((HEADER_SIZE + data.len()) as u32);
+        packet.put_u32(id);
+        packet.put_u8(flags);
+        packet.extend(code);
+        packet.extend(data);
+        stream.write_all(&packet)
+    }
+    fn send_command(&mut self, command_set: u8, command: u8, data: &[u8]) -> io::Result<()> {
+        let id = self.next_packet_id;
+        self.next_packet_id += 1;
+        self.send_packet(id, 0, [command_set, command], data)
+    }
+    fn send_events(&mut self, session: &mut DebugSession<'_, '_>, events: &[Event]) {
+        if self.send_command(64, 100, &composite_event(events)).is_err() {
+            self.detach(session);
+        }
+    }
+
+    /// The events of the requests for the location of the stopped thread, removing the requests
+    /// whose count ran out
+    fn location_events(&mut self, session: &mut DebugSession<'_, '_>, step_ended: bool) -> Vec<Event> {
+        let thread = session.stopped_thread();
+        session.select_thread(thread);
+        let Some(frame) = session.backtrace().into_iter().next() else { return Vec::new() };
+        let here = CodeLocation { class: frame.class_id, method: frame.method_id, index: frame.pc as u64 };
+        let mut matched = Vec::new();
+        for (i, request) in self.requests.iter().enumerate() {
+            let at = match request.kind {
+                event_kind::BREAKPOINT => request.location == Some(here),
+                event_kind::SINGLE_STEP => step_ended && request.step.is_some_and(|(step_thread, _)| step_thread == thread),
+                _ => false,
+            };
+            if at && request.accepts(session, thread, here.class) {
+                matched.push(i);
+            }
+        }
+        let matched = self.count_down(session, matched);
+        if matched.is_empty() {
+            return Vec::new();
+        }
+        let mut data = Vec::new();
+        data.put_u64(self.thread_id(session, thread));
+        self.put_location(session, &mut data, here);
+        matched.into_iter()
+            .map(|(kind, request, suspend_policy)| Event { kind, request, suspend_policy, data: data.clone() })
+            .collect()
+    }
+    fn class_prepare_events(&mut self, session: &mut DebugSession<'_, '_>, class: u32) -> Vec<Event> {
+        let thread = session.stopped_thread();
+        let info = session.class_info(class);
+        // array classes are not prepared
+        if info.is_array {
+            return Vec::new();
+        }
+        let matched: Vec<_> = self.requests
+            .iter()
+            .enumerate()
+            .filter(|(_, request)| request.kind == event_kind::CLASS_PREPARE && request.accepts(session, thread, class))
+            .map(|(i, _)| i)
+            .collect();
+        let matched = self.count_down(session, matched);
+        if matched.is_empty() {
+            return Vec::new();
+        }
+        let mut data = Vec::new();
+        data.put_u64(self.thread_id(session, thread));
+        let info = session.class_info(class);
+        data.put_u8(type_tag(&info));
+        data.put_u64(class as u64 + 1);
+        data.put_str(&signature(&info));
+        data.put_i32(class_status(&info));
+        matched.into_iter()
+            .map(|(kind, request, suspend_policy)| Event { kind, request, suspend_policy, data: data.clone() })
+            .collect()
+    }
+    fn thread_events(&mut self, session: &mut DebugSession<'_, '_>, kind: u8, thread: usize) -> Vec<Event> {
+        let matched: Vec<_> = self.requests
+            .iter()
+            .enumerate()
+            .filter(|(_, request)| request.kind == kind && request.thread.is_none_or(|only| only == thread))
+            .map(|(i, _)| i)
+            .collect();
+        let matched = self.count_down(session, matched);
+        let data = self.thread_id(session, thread).to_be_bytes();
+        matched.into_iter()
+            .map(|(kind, request, suspend_policy)| Event { kind, request, suspend_policy, data: data.into() })
+            .collect()
+    }
+    /// Counts the matches of the requests at these indices, returning the kind, id and suspend
+    /// policy of the ones that report an event
+    fn count_down(&mut self, session: &mut DebugSession<'_, '_>, matched: Vec<usize>) -> Vec<(u8, i32, u8)> {
+        let mut reported = Vec::new();
+        let mut expired = Vec::new();
+        for i in matched {
+            let request = &mut self.requests[i];
+            if let Some(count) = &mut request.count {
+                *count -= 1;
+                if *count > 0 {
+                    continue;
+                }
+                expired.push(i);
+            }
+            reported.push((request.kind, request.id, request.suspend_policy));
+        }
+        for i in expired.into_iter().rev() {
+            let request = self.requests.remove(i);
+            self.cleared(session, &request);
+        }
+        reported
+    }
+    /// Undoes what setting a request did
+    fn cleared(&mut self, session: &mut DebugSession<'_, '_>, request: &EventRequest) {
+        if let Some(breakpoint) = request.breakpoint {
+            session.remove_breakpoint(breakpoint);
+        }
+        match request.kind {
+            event_kind::CLASS_PREPARE => session.set_class_load_stops(self.requests.iter().any(|request| request.kind == event_kind::CLASS_PREPARE)),
+            event_kind::THREAD_START | event_kind::THREAD_DEATH => session.set_thread_stops(self.requests.iter().any(|request| {
+                matches!(request.kind, event_kind::THREAD_START | event_kind::THREAD_DEATH)
+            })),
+            _ => (),
+        }
+    }
+
+    fn command(&mut self, session: &mut DebugSession<'_, '_>, command_set: u8, command: u8, mut args: Reader) -> Reply {
+        let mut out = Vec::new();
+        match (command_set, command) {
+            // VirtualMachine
+            (1, 1) => {
+                out.put_str("Jappuccino JVM");
+                out.put_i32(JDWP_VERSION.0);
+                out.put_i32(JDWP_VERSION.1);
+                out.put_str("17");
+                out.put_str("Jappuccino");
+            }
+            (1, 2) => {
+                let signature = args.string()?;
+                let classes: Vec<_> = (0..session.class_count())
+                    .filter(|&class| self::signature(&session.class_info(class)) == signature)
+                    .collect();
+                out.put_i32(classes.len() as i32);
+                for class in classes {
+                    let info = session.class_info(class);
+                    out.put_u8(type_tag(&info));
+                    out.put_u64(class as u64 + 1);
+                    out.put_i32(class_status(&info));
+                }
+            }
+            (1, 3 | 20) => {
+                out.put_i32(session.class_count() as i32);
+                for class in 0..session.class_count() {
+                    let info = session.class_info(class);
+                    out.put_u8(type_tag(&info));
+                    out.put_u64(class as u64 + 1);
+                    out.put_str(&signature(&info));
+                    if command == 20 {
+                        out.put_str("");
+                    }
+                    out.put_i32(class_status(&info));
+                }
+            }
+            (1, 4) => {
+                let threads = self.live_threads(session);
+                out.put_i32(threads.len() as i32);
+                for id in threads {
+                    out.put_u64(id);
+                }
+            }
+            (1, 5) => {
+                out.put_i32(1);
+                out.put_u64(THREAD_GROUP);
+            }
+            (1, 6) => self.disposed = true,
+            (1, 7) => {
+                for _ in 0..5 {
+                    out.put_i32(8);
+                }
+            }
+            (1, 8) => self.suspend_count += 1,
+            (1, 9) => self.suspend_count = self.suspend_count.saturating_sub(1),
+            (1, 10) => self.exit = Some(args.i32()?),
+            (1, 12) => out.extend([0; 7]),
+            (1, 13) => {
+                out.put_str(&std::env::current_dir().unwrap_or_default().to_string_lossy());
+                out.put_i32(self.class_path.len() as i32);
+                for path in &self.class_path {
+                    out.put_str(path);
+                }
+                out.put_i32(0);
+            }
+            (1, 14) => {
+                for _ in 0..args.i32()? {
+                    let object = args.u64()?;
+                    args.i32()?;
+                    if let Ok(object) = u32::try_from(object) && self.objects.remove(&object) {
+                        session.release(Value(object));
+                    }
+                }
+            }
+            (1, 15 | 16) => (),
+            (1, 17) => out.extend([0; 32]),
+            // ReferenceType
+            (2, 1 | 13) => {
+                let class = self.class(session, args.u64()?)?;
+                out.put_str(&signature(&session.class_info(class)));
+                if command == 13 {
+                    out.put_str("");
+                }
+            }
+            (2, 2) => {
+                self.class(session, args.u64()?)?;
+                out.put_u64(0);
+            }
+            (2, 3) => {
+                let class = self.class(session, args.u64()?)?;
+                out.put_i32(session.class_info(class).access_flags.difference(ClassAccess::SUPER).bits() as i32);
+            }
+            (2, 4 | 14) => {
+                let class = self.class(session, args.u64()?)?;
+                let fields = session.declared_fields(class);
+                out.put_i32(fields.len() as i32);
+                for (i, field) in fields.iter().enumerate() {
+                    out.put_u64((class as u64) << 16 | i as u64);
+                    out.put_str(&field.name);
+                    out.put_str(&field.descriptor.to_string());
+                    if command == 14 {
+                        out.put_str("");
+                    }
+                    out.put_i32(field.access_flags.bits() as i32);
+                }
+            }
+            (2, 5 | 15) => {
+                let class = self.class(session, args.u64()?)?;
+                let methods = session.methods(class);
+                out.put_i32(methods.len() as i32);
+                for method in methods {
+                    out.put_u64(method.id as u64 + 1);
+                    out.put_str(method.name);
+                    out.put_str(&method.descriptor.to_string());
+                    if command == 15 {
+                        out.put_str("");
+                    }
+                    out.put_i32(method.access_flags.bits() as i32);
+                }
+            }
+            (2, 6) => {
+                self.class(session, args.u64()?)?;
+                let count = args.i32()?;
+                out.put_i32(count);
+                for _ in 0..count {
+                    let (class, field) = self.field(session, args.u64()?)?;
+                    if !session.declared_fields(class)[field].is_static() {
+                        return Err(error::INVALID_FIELDID);
+                    }
+                    let value = session.static_field_value(class, field);
+                    self.put_value(session, &mut out, value);
+                }
+            }
+            (2, 7) => {
+                let class = self.class(session, args.u64()?)?;
+                out.put_str(session.class_info(class).source_file.ok_or(error::ABSENT_INFORMATION)?);
+            }
+            (2, 9) => {
+                let class = self.class(session, args.u64()?)?;
+                out.put_i32(class_status(&session.class_info(class)));
+            }
+            (2, 10) => {
+                let class = self.class(session, args.u64()?)?;
+                let interfaces = session.class_info(class).interfaces;
+                out.put_i32(interfaces.len() as i32);
+                for &interface in interfaces {
+                    out.put_u64(interface as u64 + 1);
+                }
+            }
+            (2, 11) => {
+                let class = self.class(session, args.u64()?)?;
+                let object = session.class_info(class).class_object;
+                out.put_u64(self.object_id(session, object));
+            }
+            (2, 12) => return Err(error::ABSENT_INFORMATION),
+            // ClassType
+            (3, 1) => {
+                let class = self.class(session, args.u64()?)?;
+                out.put_u64(session.class_info(class).super_class.map_or(0, |class| class as u64 + 1));
+            }
+            // Method
+            (6, 1) => {
+                let class = self.class(session, args.u64()?)?;
+                let methods = session.methods(class);
+                let method = find_method(&methods, args.u64()?)?;
+                if method.code_length == 0 {
+                    out.put_u64(u64::MAX);
+                    out.put_u64(u64::MAX);
+                    out.put_i32(0);
+                } else {
+                    out.put_u64(0);
+                    out.put_u64(method.code_length as u64 - 1);
+                    out.put_i32(method.line_numbers.len() as i32);
+                    for entry in method.line_numbers {
+                        out.put_u64(entry.start_pc as u64);
+                        out.put_i32(entry.line_number as i32);
+                    }
+                }
+            }
+            (6, 2 | 5) => {
+                let class = self.class(session, args.u64()?)?;
+                let methods = session.methods(class);
+                let method = find_method(&methods, args.u64()?)?;
+                if method.local_variables.is_empty() {
+                    return Err(error::ABSENT_INFORMATION);
+                }
+                out.put_i32(method.arg_slots as i32);
+                out.put_i32(method.local_variables.len() as i32);
+                for variable in method.local_variables {
+                    out.put_u64(variable.start_pc as u64);
+                    out.put_str(&variable.name);
+                    out.put_str(&variable.descriptor.to_string());
+                    if command == 5 {
+                        out.put_str("");
+                    }
+                    out.put_i32(variable.length as i32);
+                    out.put_i32(variable.index as i32);
+                }
+            }
+            (6, 4) => {
+                let class = self.class(session, args.u64()?)?;
+                find_method(&session.methods(class), args.u64()?)?;
+                out.put_u8(0);
+            }
+            // ObjectReference
+            (9, 1) => {
+                let object = self.object(args.u64()?)?;
+                if object == Value::NULL {
+                    return Err(error::INVALID_OBJECT);
+                }
+                let class = session.class_of(object);
+                out.put_u8(type_tag(&session.class_info(class)));
+                out.put_u64(class as u64 + 1);
+            }
+            (9, 2) => {
+                let object = self.object(args.u64()?)?;
+                if object == Value::NULL {
+                    return Err(error::INVALID_OBJECT);
+                }
+                let count = args.i32()?;
+                out.put_i32(count);
+                for _ in 0..count {
+                    let (class, field) = self.field(session, args.u64()?)?;
+                    let value = match session.declared_fields(class)[field].is_static() {
+                        true => session.static_field_value(class, field),
+                        false if is_subclass(session, session.class_of(object), class) => session.field_value(object, class, field),
+                        false => return Err(error::INVALID_FIELDID),
+                    };
+                    self.put_value(session, &mut out, value);
+                }
+            }
+            // objects are pinned anyway and never collected while the debugger knows them
+            (9, 6 | 7) => {
+                self.object(args.u64()?)?;
+            }
+            (9, 8) => {
+                self.object(args.u64()?)?;
+                out.put_u8(0);
+            }
+            // StringReference
+            (10, 1) => {
+                let object = self.object(args.u64()?)?;
+                out.put_str(&session.string_value(object).ok_or(error::INVALID_OBJECT)?);
+            }
+            // ThreadReference
+            (11, 1) => {
+                let thread = self.thread(session, args.u64()?)?;
+                out.put_str(&session.thread_name(thread));
+            }
+            (11, 2) => {
+                self.thread(session, args.u64()?)?;
+                self.suspend_count += 1;
+            }
+            (11, 3) => {
+                self.thread(session, args.u64()?)?;
+                self.suspend_count = self.suspend_count.saturating_sub(1);
+            }
+            (11, 4) => {
+                let thread = self.thread(session, args.u64()?)?;
+                out.put_i32(match session.thread_status(thread) {
+                    ThreadStatus::Terminated => 0,
+                    ThreadStatus::Runnable => 1,
+                    ThreadStatus::Sleeping => 2,
+                    ThreadStatus::Blocked => 3,
+                    ThreadStatus::Waiting => 4,
+                });
+                out.put_i32((self.suspend_count > 0) as i32);
+            }
+            (11, 5) => {
+                self.thread(session, args.u64()?)?;
+                out.put_u64(THREAD_GROUP);
+            }
+            (11, 6) => {
+                let thread = self.suspended_thread(session, args.u64()?)?;
+                let start = args.i32()?;
+                let length = args.i32()?;
+                session.select_thread(thread);
+                let frames = session.backtrace();
+                let start = usize::try_from(start).ok().filter(|&start| start <= frames.len()).ok_or(error::INVALID_INDEX)?;
+                let end = match length {
+                    -1 => frames.len(),
+                    length => usize::try_from(length).ok()
+                        .and_then(|length| start.checked_add(length))
+                        .filter(|&end| end <= frames.len())
+                        .ok_or(error::INVALID_LENGTH)?,
+                };
+                out.put_i32((end - start) as i32);
+                for (i, frame) in frames.iter().enumerate().take(end).skip(start) {
+                    out.put_u64((thread as u64) << 32 | i as u64);
+                    // callers are inside their call, not after it
+                    let index = frame.pc - (i > 0) as u16;
+                    self.put_location(session, &mut out, CodeLocation { class: frame.class_id, method: frame.method_id, index: index as u64 });
+                }
+            }
+            (11, 7) => {
+                let thread = self.suspended_thread(session, args.u64()?)?;
+                session.select_thread(thread);
+                out.put_i32(session.backtrace().len() as i32);
+            }
+            (11, 12) => {
+                self.thread(session, args.u64()?)?;
+                out.put_i32(self.suspend_count as i32);
+            }
+            // ThreadGroupReference
+            (12, 1..=3) => {
+                if args.u64()? != THREAD_GROUP {
+                    return Err(error::INVALID_OBJECT);
+                }
+                match command {
+                    1 => out.put_str("main"),
+                    2 => out.put_u64(0),
+                    _ => {
+                        let threads = self.live_threads(session);
+                        out.put_i32(threads.len() as i32);
+                        for id in threads {
+                            out.put_u64(id);
+                        }
+                        out.put_i32(0);
+                    }
+                }
+            }
+            // ArrayReference
+            (13, 1) => {
+                let array = self.object(args.u64()?)?;
+                out.put_i32(session.array_length(array).ok_or(error::INVALID_OBJECT)? as i32);
+            }
+            (13, 2) => {
+                let array = self.object(args.u64()?)?;
+                let length = session.array_length(array).ok_or(error::INVALID_OBJECT)?;
+                let first = u32::try_from(args.i32()?).ok().filter(|&first| first <= length).ok_or(error::INVALID_INDEX)?;
+                let count = u32::try_from(args.i32()?).ok().filter(|&count| first + count <= length).ok_or(error::INVALID_LENGTH)?;
+                let element_type = FieldDescriptor::from_bytes(&session.class_info(session.class_of(array)).name.as_bytes()[1..]).unwrap();
+                let tag = value_tag(&element_type);
+                out.put_u8(tag);
+                out.put_i32(count as i32);
+                for (_, value) in session.fields(array).into_iter().skip(first as usize).take(count as usize) {
+                    match value {
+                        JValue::Reference(_) => self.put_value(session, &mut out, value),
+                        _ => put_untagged(&mut out, value),
+                    }
+                }
+            }
+            // EventRequest
+            (15, 1) => {
+                let id = self.set_request(session, &mut args)?;
+                out.put_i32(id);
+            }
+            (15, 2) => {
+                let kind = args.u8()?;
+                let id = args.i32()?;
+                if let Some(i) = self.requests.iter().position(|request| request.kind == kind && request.id == id) {
+                    let request = self.requests.remove(i);
+                    self.cleared(session, &request);
+                }
+            }
+            (15, 3) => {
+                let (breakpoints, others) = std::mem::take(&mut self.requests).into_iter().partition(|request| request.kind == event_kind::BREAKPOINT);
+                self.requests = others;
+                for request in breakpoints {
+                    self.cleared(session, &request);
+                }
+            }
+            // StackFrame
+            (16, 1) => {
+                let frame = self.frame(session, &mut args)?;
+                let count = args.i32()?;
+                out.put_i32(count);
+                for _ in 0..count {
+                    let slot = u16::try_from(args.i32()?).map_err(|_| error::INVALID_SLOT)?;
+                    let value_type = match args.u8()? {
+                        b'B' => FieldDescriptor::Byte,
+                        b'C' => FieldDescriptor::Char,
+                        b'D' => FieldDescriptor::Double,
+                        b'F' => FieldDescriptor::Float,
+                        b'I' => FieldDescriptor::Int,
+                        b'J' => FieldDescriptor::Long,
+                        b'S' => FieldDescriptor::Short,
+                        b'Z' => FieldDescriptor::Boolean,
+                        _ => FieldDescriptor::ClassRef("java/lang/Object".into()),
+                    };
+                    let value = session.local_value(frame, slot, &value_type).ok_or(error::INVALID_SLOT)?;
+                    self.put_value(session, &mut out, value);
+                }
+            }
+            (16, 3) => {
+                let frame = self.frame(session, &mut args)?;
+                let backtrace = session.backtrace();
+                let frame_info = &backtrace[frame];
+                let is_static = session.methods(frame_info.class_id)
+                    .iter()
+                    .find(|method| method.id == frame_info.method_id)
+                    .is_none_or(|method| method.access_flags.contains(MethodAccess::STATIC));
+                let this = match is_static {
+                    true => JValue::Reference(Value::NULL),
+                    false => session.local_value(frame, 0, &FieldDescriptor::ClassRef("java/lang/Object".into())).ok_or(error::INVALID_SLOT)?,
+                };
+                self.put_value(session, &mut out, this);
+            }
+            _ => return Err(error::NOT_IMPLEMENTED),
+        }
+        Ok(out)
+    }
+    /// Sets an event request from the arguments of `EventRequest.Set`, returning its id
+    fn set_request(&mut self, session: &mut DebugSession<'_, '_>, args: &mut Reader) -> Reply<i32> {
+        let mut request = EventRequest { kind: args.u8()?, suspend_policy: args.u8()?, ..EventRequest::default() };
+        for _ in 0..args.i32()? {
+            match args.u8()? {
+                1 => request.count = Some(u32::try_from(args.i32()?).ok().filter(|&count| count > 0).ok_or(error::ILLEGAL_ARGUMENT)?),
+                // reserved for the future, never sent
+                2 => _ = args.i32()?,
+                3 => request.thread = Some(self.thread(session, args.u64()?)?),
+                4 => request.class = Some(self.class(session, args.u64()?)?),
+                5 => request.class_patterns.push((args.string()?, false)),
+                6 => request.class_patterns.push((args.string()?, true)),
+                7 => {
+                    args.u8()?;
+                    let class = self.class(session, args.u64()?)?;
+                    let method = find_method(&session.methods(class), args.u64()?)?.id;
+                    request.location = Some(CodeLocation { class, method, index: args.u64()? });
+                }
+                // exceptions are not reported, so their filters do not matter
+                8 => {
+                    args.u64()?;
+                    args.u8()?;
+                    args.u8()?;
+                }
+                10 => {
+                    let thread = self.thread(session, args.u64()?)?;
+                    let resume = match (args.i32()?, args.i32()?) {
+                        (0, 2) | (1, 2) => Resume::StepOut,
+                        (0, _) => Resume::StepInstruction,
+                        (1, 0) => Resume::StepInto,
+                        (1, 1) => Resume::StepOver,
+                        _ => return Err(error::ILLEGAL_ARGUMENT),
+                    };
+                    request.step = Some((thread, resume));
+                }
+                _ => return Err(error::NOT_IMPLEMENTED),
+            }
+        }
+        match request.kind {
+            event_kind::BREAKPOINT => {
+                let location = request.location.ok_or(error::ILLEGAL_ARGUMENT)?;
+                let methods = session.methods(location.class);
+                let method = methods.iter().find(|method| method.id == location.method).unwrap();
+                if location.index >= method.code_length as u64 {
+                    return Err(error::INVALID_INDEX);
+                }
+                // stops in all overloads, the location tells them apart
+                let breakpoint = Breakpoint {
+                    class: session.class_info(location.class).name.into(),
+                    method: Some(method.name.into()),
+                    location: Location::Pc(location.index as u16),
+                };
+                request.breakpoint = Some(session.add_breakpoint(breakpoint));
+            }
+            event_kind::SINGLE_STEP if request.step.is_none() => return Err(error::ILLEGAL_ARGUMENT),
+            event_kind::CLASS_PREPARE => session.set_class_load_stops(true),
+            event_kind::THREAD_START | event_kind::THREAD_DEATH => session.set_thread_stops(true),
+            // classes are never unloaded and exceptions are not reported
+            event_kind::SINGLE_STEP | event_kind::VM_START | event_kind::VM_DEATH | event_kind::CLASS_UNLOAD | event_kind::EXCEPTION => (),
+            _ => return Err(error::NOT_IMPLEMENTED),
+        }
+        request.id = self.next_request_id;
+        self.next_request_id += 1;
+        let id = request.id;
+        self.requests.push(request);
+        Ok(id)
+    }
+
+    /// The id of an object, pinning it the first time it is sent
+    fn object_id(&mut self, session: &mut DebugSession<'_, '_>, object: Value) -> u64 {
+        if object != Value::NULL && self.objects.insert(object.into_u32()) {
+            session.pin(object);
+        }
+        object.into_u32() as u64
+    }
+    /// An object the debugger got before, or null
+    fn object(&self, id: u64) -> Reply<Value> {
+        match u32::try_from(id) {
+            Ok(0) => Ok(Value::NULL),
+            Ok(id) if self.objects.contains(&id) => Ok(Value(id)),
+            _ => Err(error::INVALID_OBJECT),
+        }
+    }
+    fn thread_id(&mut self, session: &mut DebugSession<'_, '_>, thread: usize) -> u64 {
+        match session.thread_object(thread) {
+            Ok(object) => self.object_id(session, object),
+            Err(_) => 0,
+        }
+    }
+    fn thread(&self, session: &DebugSession<'_, '_>, id: u64) -> Reply<usize> {
+        session.thread_of(self.object(id)?).ok_or(error::INVALID_THREAD)
+    }
+    /// A thread the frames of which can be looked at
+    fn suspended_thread(&self, session: &DebugSession<'_, '_>, id: u64) -> Reply<usize> {
+        let thread = self.thread(session, id)?;
+        match self.suspend_count {
+            0 => Err(error::THREAD_NOT_SUSPENDED),
+            _ => Ok(thread),
+        }
+    }
+    /// The ids of the threads that did not terminate
+    fn live_threads(&mut self, session: &mut DebugSession<'_, '_>) -> Vec<u64> {
+        let threads: Vec<_> = (0..session.thread_count())
+            .filter(|&thread| session.thread_status(thread) != ThreadStatus::Terminated)
+            .collect();
+        threads.into_iter().map(|thread| self.thread_id(session, thread)).collect()
+    }
+    fn class(&self, session: &DebugSession<'_, '_>, id: u64) -> Reply<u32> {
+        id.checked_sub(1)
+            .and_then(|class| u32::try_from(class).ok())
+            .filter(|&class| class < session.class_count())
+            .ok_or(error::INVALID_CLASS)
+    }
+    /// The class and index of a field
+    fn field(&self, session: &DebugSession<'_, '_>, id: u64) -> Reply<(u32, usize)> {
+        let class = u32::try_from(id >> 16).ok().filter(|&class| class < session.class_count()).ok_or(error::INVALID_FIELDID)?;
+        let field = (id & 0xffff) as usize;
+        if field >= session.declared_fields(class).len() {
+            return Err(error::INVALID_FIELDID);
+        }
+        Ok((class, field))
+    }
+    /// The index of the frame given by a thread and a frame id, selecting the thread
+    fn frame(&self, session: &mut DebugSession<'_, '_>, args: &mut Reader) -> Reply<usize> {
+        let thread = self.suspended_thread(session, args.u64()?)?;
+        let id = args.u64()?;
+        let frame = (id & 0xffff_ffff) as usize;
+        session.select_thread(thread);
+        if (id >> 32) as usize != thread || frame >= session.backtrace().len() {
+            return Err(error::INVALID_FRAMEID);
+        }
+        Ok(frame)
+    }
+    fn put_location(&self, session: &DebugSession<'_, '_>, out: &mut Vec<u8>, location: CodeLocation) {
+        out.put_u8(type_tag(&session.class_info(location.class)));
+        out.put_u64(location.class as u64 + 1);
+        out.put_u64(location.method as u64 + 1);
+        out.put_u64(location.index);
+    }
+    /// Writes a value with its tag, which for objects tells strings, threads, classes and arrays
+    /// apart
+    fn put_value(&mut self, session: &mut DebugSession<'_, '_>, out: &mut Vec<u8>, value: JValue) {
+        let JValue::Reference(object) = value else {
+            out.put_u8(match value {
+                JValue::Void => b'V',
+                JValue::Boolean(_) => b'Z',
+                JValue::Byte(_) => b'B',
+                JValue::Char(_) => b'C',
+                JValue::Short(_) => b'S',
+                JValue::Int(_) => b'I',
+                JValue::Long(_) => b'J',
+                JValue::Float(_) => b'F',
+                JValue::Double(_) => b'D',
+                JValue::Reference(_) => unreachable!(),
+            });
+            put_untagged(out, value);
+            return;
+        };
+        let tag = if object == Value::NULL {
+            b'L'
+        } else {
+            let class = session.class_of(object);
+            let info = session.class_info(class);
+            match info.name {
+                _ if info.is_array => b'[',
+                "java/lang/String" => b's',
+                "java/lang/Class" => b'c',
+                _ if session.find_class("java/lang/Thread").is_some_and(|thread| is_subclass(session, class, thread)) => b't',
+                _ => b'L',
+            }
+        };
+        out.put_u8(tag);
+        out.put_u64(self.object_id(session, object));
+    }
+}
+
+impl EventRequest {
+    /// Whether the filters of the request let an event in a thread and class through
+    fn accepts(&self, session: &DebugSession<'_, '_>, thread: usize, class: u32) -> bool {
+        let name = session.class_info(class).name.replace('/', ".");
+        self.thread.is_none_or(|only| only == thread)
+            && self.class.is_none_or(|only| is_subclass(session, class, only))
+            && self.class_patterns.iter().all(|(pattern, exclude)| matches_pattern(&name, pattern) != *exclude)
+    }
+}
+
+/// Whether a class name matches a pattern, which can start or end with `*`
+fn matches_pattern(name: &str, pattern: &str) -> bool {
+    if let Some(suffix) = pattern.strip_prefix('*') {
+        name.ends_with(suffix)
+    } else if let Some(prefix) = pattern.strip_suffix('*') {
+        name.starts_with(prefix)
+    } else {
+        name == pattern
+    }
+}
+
+/// Whether `class` is `ancestor` or extends or implements it
+fn is_subclass(session: &DebugSession<'_, '_>, class: u32, ancestor: u32) -> bool {
+    let info = session.class_info(class);
+    class == ancestor
+        || info.interfaces.iter().any(|&interface| is_subclass(session, interface, ancestor))
+        || info.super_class.is_some_and(|super_class| is_subclass(session, super_class, ancestor))
+}
+
+fn find_method<'m, 's>(methods: &'m [MethodInfo<'s>], id: u64) -> Reply<&'m MethodInfo<'s>> {
+    methods.iter().find(|method| method.id as u64 + 1 == id).ok_or(error::INVALID_METHODID)
+}
+
+fn type_tag(info: &ClassInfo) -> u8 {
+    if info.is_array {
+        3
+    } else if info.access_flags.contains(ClassAccess::INTERFACE) {
+        2
+    } else {
+        1
+    }
+}
+
+fn signature(info: &ClassInfo) -> String {
+    match info.is_array {
+        true => info.name.into(),
+        false => format!("L{};", info.name),
+    }
+}
+
+/// Verified and prepared, and initialized once the static initializer ran
+fn class_status(info: &ClassInfo) -> i32 {
+    if info.initialized { 7 } else { 3 }
+}
+
+/// The tag of the values of a type, objects of all classes are `L`
+fn value_tag(value_type: &FieldDescriptor) -> u8 {
+    value_type.to_string().as_bytes()[0]
+}
+
+fn put_untagged(out: &mut Vec<u8>, value: JValue) {
+    match value {
+        JValue::Void => (),
+        JValue::Boolean(value) => out.put_u8(value as u8),
+        JValue::Byte(value) => out.put_u8(value as u8),
+        JValue::Char(value) => out.extend(value.to_be_bytes()),
+        JValue::Short(value) => out.extend(value.to_be_bytes()),
+        JValue::Int(value) => out.put_i32(value),
+        JValue::Long(value) => out.put_u64(value as u64),
+        JValue::Float(value) => out.put_u32(value.to_bits()),
+        JValue::Double(value) => out.put_u64(value.to_bits()),
+        JValue::Reference(object) => out.put_u64(object.into_u32() as u64),
+    }
+}
+
+fn composite_event(events: &[Event]) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.put_u8(events.iter().map(|event| event.suspend_policy).max().unwrap_or(suspend_policy::NONE));
+    data.put_i32(events.len() as i32);
+    for event in events {
+        data.put_u8(event.kind);
+        data.put_i32(event.request);
+        data.extend(&event.data);
+    }
+    data
+}
+
+/// Reads the arguments of a command
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&mut self) -> Reply<[u8; N]> {
+        let (bytes, rest) = self.0.split_first_chunk().ok_or(error::ILLEGAL_ARGUMENT)?;
+        self.0 = rest;
+        Ok(*bytes)
+    }
+    fn u8(&mut self) -> Reply<u8> {
+        self.bytes().map(u8::from_be_bytes)
+    }
+    fn i32(&mut self) -> Reply<i32> {
+        self.bytes().map(i32::from_be_bytes)
+    }
+    fn u64(&mut self) -> Reply<u64> {
+        self.bytes().map(u64::from_be_bytes)
+    }
+    fn string(&mut self) -> Reply<String> {
+        let length = self.i32()? as usize;
+        let bytes = self.0.get(..length).ok_or(error::ILLEGAL_ARGUMENT)?;
+        self.0 = &self.0[length..];
+        String::from_utf8(bytes.to_vec()).map_err(|_| error::ILLEGAL_ARGUMENT)
+    }
+}
+
+/// Writes the fields of packets, big-endian
+trait PutExt {
+    fn put_u8(&mut self, value: u8);
+    fn put_i32(&mut self, value: i32);
+    fn put_u32(&mut self, value: u32);
+    fn put_u64(&mut self, value: u64);
+    fn put_str(&mut self, value: &str);
+}
+
+impl PutExt for Vec<u8> {
+    fn put_u8(&mut self, value: u8) {
+        self.push(value);
+    }
+    fn put_i32(&mut self, value: i32) {
+        self.extend(value.to_be_bytes());
+    }
+    fn put_u32(&mut self, value: u32) {
+        self.extend(value.to_be_bytes());
+    }
+    fn put_u64(&mut self, value: u64) {
+        self.extend(value.to_be_bytes());
+    }
+    fn put_str(&mut self, value: &str) {
+        self.put_u32(value.len() as u32);
+        self.extend(value.as_bytes());
+    }
+}
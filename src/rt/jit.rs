@@ -0,0 +1,358 @@
+//! Compiling hot methods to native code with Cranelift.
+//!
+//! A runtime made with [`Runtime::with_jit`](super::Runtime::with_jit) counts the invocations of
+//! bytecode methods and compiles a method to x86-64 code once it was invoked often enough.
+//! Compiled code keeps locals and operands in registers and calls compiled methods directly. It
+//! covers arithmetic, locals, branches, field and array access and calls. Anything else, like an
+//! allocation, an exception, a monitor or a call to a method that is not compiled, deoptimizes:
+//! the compiled frames are rebuilt as interpreter frames and the interpreter goes on from the
+//! instruction compiled code could not execute. Code that deoptimizes too often is thrown away
+//! and compiled again once the method is hot again, with the instructions the interpreter linked
+//! in the meantime.
+//!
+//! Compiled code does not count instructions, so runs with a debugger, a profile, fuel or a time
+//! limit stay in the interpreter, and so do programs that started threads, which take turns after
+//! a number of instructions.
+
+mod translate;
+
+use std::{collections::HashMap, fmt, ptr};
+
+use cranelift_codegen::{ir::{types, AbiParam, Signature}, settings::Configurable, Context};
+use cranelift_frontend::FunctionBuilderContext;
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, FuncId, Linkage, Module};
+
+use crate::class::MethodAccess;
+
+use super::{link::Instruction, MethodImpl, Result, ReturnCategory, Runtime, RuntimeCtx, Value, i64_into_values};
+
+/// Deoptimizations after which the code of a method is thrown away
+const DEOPT_LIMIT: u32 = 64;
+/// Times the code of a method is thrown away for deoptimizing too often before the method stays
+/// interpreted for good
+const MAX_DISCARDS: u32 = 4;
+/// Compiled calls inside each other, they take space on the native stack
+const MAX_COMPILED_DEPTH: usize = 1024;
+
+/// What the JIT of a runtime did so far
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JitStats {
+    /// Methods compiled, recompilations included
+    pub compiled: u64,
+    /// Methods that cannot be compiled and stay interpreted
+    pub rejected: u64,
+    /// Calls from the interpreter into compiled code
+    pub entries: u64,
+    /// Times compiled code left a method to the interpreter
+    pub deopts: u64,
+}
+
+pub(super) struct Jit {
+    module: JITModule,
+    context: Context,
+    builder_context: FunctionBuilderContext,
+    threshold: u32,
+    methods: HashMap<(u32, u16), JitMethod>,
+    helpers: Helpers,
+    stats: JitStats,
+}
+
+/// The state of a method the interpreter invoked
+#[derive(Debug, Default)]
+struct JitMethod {
+    invocations: u32,
+    code: Option<Compiled>,
+    deopts: u32,
+    discards: u32,
+    rejected: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Compiled {
+    func: FuncId,
+    code: *const u8,
+}
+
+/// Functions of the runtime compiled code calls
+#[derive(Debug, Clone, Copy)]
+struct Helpers {
+    target: FuncId,
+    virtual_target: FuncId,
+    deopt: FuncId,
+    frem: FuncId,
+    drem: FuncId,
+}
+
+/// What compiled code gets from the interpreter, shared by all compiled frames of a call
+#[repr(C)]
+struct JitEnv {
+    /// The heap and the statics, which do not move while compiled code runs as it never
+    /// allocates
+    heap: *mut u8,
+    statics: *mut u8,
+    /// The return value of the compiled method that returned last
+    result: u64,
+    /// Compiled calls that can still be made before the call depth limit
+    depth_left: u64,
+    runtime: *mut Runtime,
+    /// The frames of a deoptimization, innermost first
+    frames: Vec<DeoptFrame>,
+}
+
+/// A compiled frame to rebuild for the interpreter
+#[derive(Debug)]
+struct DeoptFrame {
+    class: u32,
+    method: u16,
+    /// Where the interpreter goes on in the method
+    pc: usize,
+    max_locals: u16,
+    /// The locals followed by the operand stack
+    words: Box<[u32]>,
+}
+
+/// Compiled methods take the environment and their arguments in the words of the operand
+/// stack, they return [`RETURNED`] with the return value in [`JitEnv::result`] or [`DEOPTED`]
+type CompiledFn = unsafe extern "C" fn(*mut JitEnv, *const Value) -> u32;
+const RETURNED: u32 = 0;
+const DEOPTED: u32 = 1;
+
+impl Jit {
+    pub(super) fn new(threshold: u32) -> Self {
+        let mut flags = cranelift_codegen::settings::builder();
+        flags.set("opt_level", "speed").unwrap();
+        let isa = cranelift_native::builder()
+            .unwrap_or_else(|message| panic!("the JIT does not support this machine: {message}"))
+            .finish(cranelift_codegen::settings::Flags::new(flags))
+            .unwrap();
+        let mut builder = JITBuilder::with_isa(isa, default_libcall_names());
+        builder.symbol("jit_target", jit_target as *const u8);
+        builder.symbol("jit_virtual_target", jit_virtual_target as *const u8);
+        builder.symbol("jit_deopt", jit_deopt as *const u8);
+        builder.symbol("jit_frem", jit_frem as *const u8);
+        builder.symbol("jit_drem", jit_drem as *const u8);
+        let mut module = JITModule::new(builder);
+
+        let mut declare = |name, params: &[types::Type], returns: &[types::Type]| {
+            let mut signature = module.make_signature();
+            signature.params.extend(params.iter().map(|&ty| AbiParam::new(ty)));
+            signature.returns.extend(returns.iter().map(|&ty| AbiParam::new(ty)));
+            module.declare_function(name, Linkage::Import, &signature).unwrap()
+        };
+        let helpers = Helpers {
+            target: declare("jit_target", &[types::I64, types::I32, types::I32], &[types::I64]),
+            virtual_target: declare("jit_virtual_target", &[types::I64, types::I32, types::I32], &[types::I64]),
+            deopt: declare("jit_deopt", &[types::I64, types::I32, types::I32, types::I64, types::I64, types::I32, types::I32], &[]),
+            frem: declare("jit_frem", &[types::F32, types::F32], &[types::F32]),
+            drem: declare("jit_drem", &[types::F64, types::F64], &[types::F64]),
+        };
+        Jit {
+            context: module.make_context(),
+            module,
+            builder_context: FunctionBuilderContext::new(),
+            threshold,
+            methods: HashMap::new(),
+            helpers,
+            stats: JitStats::default(),
+        }
+    }
+    pub(super) fn stats(&self) -> &JitStats {
+        &self.stats
+    }
+    /// The signature of compiled methods
+    fn signature(&self) -> Signature {
+        let mut signature = self.module.make_signature();
+        signature.params.extend([AbiParam::new(types::I64), AbiParam::new(types::I64)]);
+        signature.returns.push(AbiParam::new(types::I32));
+        signature
+    }
+    /// Compiles a method, `None` if it cannot be compiled
+    fn compile(&mut self, runtime: &Runtime, class: u32, method: u16) -> Option<Compiled> {
+        let loaded = runtime.get_class(class);
+        let MethodImpl::Bytecode(bytecode) = loaded.method(method) else { return None };
+        if bytecode.access_flags.contains(MethodAccess::SYNCHRONIZED) || loaded.clinit == Some(method) {
+            return None;
+        }
+        let signature = self.signature();
+        let func = self.module.declare_anonymous_function(&signature).ok()?;
+        self.context.func.signature = signature;
+        let translated = translate::translate(self, runtime, (class, method, func), bytecode);
+        if translated.is_err() {
+            // the builder stops in the middle of the function
+            self.builder_context = FunctionBuilderContext::new();
+        }
+        let defined = translated.is_ok() && self.module.define_function(func, &mut self.context).is_ok();
+        self.module.clear_context(&mut self.context);
+        if !defined {
+            return None;
+        }
+        self.module.finalize_definitions().ok()?;
+        let code = self.module.get_finalized_function(func);
+        Some(Compiled { func, code })
+    }
+}
+
+/// Compiled code is tied to the classes of its runtime, a clone of the runtime starts over with
+/// nothing compiled
+impl Clone for Jit {
+    fn clone(&self) -> Self {
+        Jit::new(self.threshold)
+    }
+}
+
+impl fmt::Debug for Jit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Jit").field("threshold", &self.threshold).field("stats", &self.stats).finish_non_exhaustive()
+    }
+}
+
+impl Runtime {
+    /// The compiled code of a method, counting the invocation and compiling the method once it
+    /// is hot. `None` if the method is interpreted.
+    fn compiled_code(&mut self, class: u32, method: u16) -> Option<Compiled> {
+        let jit = self.jit.as_mut()?;
+        let state = jit.methods.entry((class, method)).or_default();
+        if state.code.is_some() || state.rejected {
+            return state.code;
+        }
+        state.invocations += 1;
+        if state.invocations < jit.threshold {
+            return None;
+        }
+        self.compile(class, method)
+    }
+    /// Compiles a method now, `None` if it cannot be compiled
+    fn compile(&mut self, class: u32, method: u16) -> Option<Compiled> {
+        let mut jit = self.jit.take().unwrap();
+        let compiled = jit.compile(self, class, method);
+        let state = jit.methods.entry((class, method)).or_default();
+        state.code = compiled;
+        match compiled {
+            Some(_) => jit.stats.compiled += 1,
+            None => {
+                state.rejected = true;
+                jit.stats.rejected += 1;
+            }
+        }
+        self.jit = Some(jit);
+        compiled
+    }
+    /// The compiled code of a method compiled code calls, compiling it right away as its caller is
+    /// hot. `None` if the method is interpreted.
+    fn callee_code(&mut self, class: u32, method: u16) -> Option<Compiled> {
+        let state = self.jit.as_mut()?.methods.entry((class, method)).or_default();
+        if state.code.is_some() || state.rejected {
+            return state.code;
+        }
+        self.compile(class, method)
+    }
+    /// Counts a deoptimization of the code of a method at `pc` and throws the code away if it
+    /// keeps deoptimizing.
+    ///
+    /// Code that deoptimized at an instruction that was not linked yet is thrown away right away,
+    /// the interpreter links the instruction and the code compiled next runs it.
+    fn deopted(&mut self, class: u32, method: u16, pc: usize) {
+        let unlinked = matches!(self.instructions[pc].instruction, Instruction::Unlinked(..));
+        let jit = self.jit.as_mut().unwrap();
+        jit.stats.deopts += 1;
+        let state = jit.methods.get_mut(&(class, method)).unwrap();
+        state.deopts += 1;
+        if unlinked || state.deopts >= DEOPT_LIMIT {
+            if !unlinked {
+                state.discards += 1;
+                state.rejected = state.discards >= MAX_DISCARDS;
+            }
+            state.code = None;
+            state.deopts = 0;
+            state.invocations = 0;
+        }
+    }
+}
+
+impl RuntimeCtx<'_> {
+    /// Runs a bytecode method whose arguments are on the stack with its compiled code, if it
+    /// has some. Returns `false` if the interpreter has to invoke it.
+    ///
+    /// If the compiled code deoptimizes, its frames are pushed and the interpreter goes on with
+    /// them.
+    pub(super) fn run_compiled(&mut self, class: u32, method: u16) -> Result<bool> {
+        if self.debug.is_some() || self.runtime.profile.is_some() || self.fuel.is_some() || self.deadline.is_some() || self.thread_count() > 1 {
+            return Ok(false);
+        }
+        let Some(compiled) = self.runtime.compiled_code(class, method) else { return Ok(false) };
+        let MethodImpl::Bytecode(bytecode) = self.runtime.get_class(class).method(method) else { unreachable!() };
+        let arg_num = bytecode.arg_num as usize;
+        let return_category = match bytecode.debug.descriptor.return_type.as_deref() {
+            None => ReturnCategory::Void,
+            Some(return_type) if return_type.unit_size() == 2 => ReturnCategory::Cat2,
+            Some(_) => ReturnCategory::Cat1,
+        };
+        let depth_left = self.runtime.limits.max_call_depth.saturating_sub(self.return_stack.len() + 1);
+        let mut env = JitEnv {
+            heap: self.heap.as_mut_ptr().cast(),
+            statics: self.runtime.statics.as_mut_ptr().cast(),
+            result: 0,
+            depth_left: depth_left.min(MAX_COMPILED_DEPTH) as u64,
+            runtime: &mut *self.runtime as *mut Runtime,
+            frames: Vec::new(),
+        };
+        let args = self.stack.len() - arg_num;
+        // SAFETY: the code was compiled for this runtime, which compiled code only reaches
+        // through the environment while this frame does not touch it
+        let status = unsafe {
+            let code: CompiledFn = std::mem::transmute(compiled.code);
+            code(&mut env, self.stack.as_ptr().add(args))
+        };
+        self.runtime.jit.as_mut().unwrap().stats.entries += 1;
+        self.stack.truncate(args);
+        if status == RETURNED {
+            match return_category {
+                ReturnCategory::Void => (),
+                ReturnCategory::Cat1 => self.push(Value(env.result as u32)),
+                ReturnCategory::Cat2 => self.push2(i64_into_values(env.result as i64)),
+            }
+            return Ok(true);
+        }
+        let innermost = &env.frames[0];
+        self.runtime.deopted(innermost.class, innermost.method, innermost.pc);
+        for frame in env.frames.iter().rev() {
+            let (locals, operands) = frame.words.split_at(frame.max_locals as usize);
+            self.stack.extend(locals.iter().map(|&word| Value(word)));
+            self.do_call(frame.class, frame.method, frame.max_locals, frame.max_locals, frame.pc);
+            self.stack.extend(operands.iter().map(|&word| Value(word)));
+        }
+        Ok(true)
+    }
+}
+
+/// The code of a method compiled code calls, null if it has to deoptimize instead
+extern "C" fn jit_target(env: *mut JitEnv, class: u32, method: u32) -> *const u8 {
+    // SAFETY: compiled code passes the environment it got
+    let runtime = unsafe { &mut *(*env).runtime };
+    runtime.callee_code(class, method as u16).map_or(ptr::null(), |compiled| compiled.code)
+}
+
+/// The code of the method a virtual call selects for a receiver of class `receiver`, null if
+/// it has to deoptimize instead
+extern "C" fn jit_virtual_target(env: *mut JitEnv, site: u32, receiver: u32) -> *const u8 {
+    // SAFETY: compiled code passes the environment it got
+    let runtime = unsafe { &mut *(*env).runtime };
+    let Some((class, method)) = runtime.select(site, receiver) else { return ptr::null() };
+    runtime.callee_code(class, method).map_or(ptr::null(), |compiled| compiled.code)
+}
+
+/// Saves a compiled frame that is deoptimizing
+extern "C" fn jit_deopt(env: *mut JitEnv, class: u32, method: u32, pc: u64, words: *const u32, max_locals: u32, stack: u32) {
+    // SAFETY: compiled code passes the environment it got and a buffer with that many words
+    let (env, words) = unsafe { (&mut *env, std::slice::from_raw_parts(words, (max_locals + stack) as usize)) };
+    env.frames.push(DeoptFrame { class, method: method as u16, pc: pc as usize, max_locals: max_locals as u16, words: words.into() });
+}
+
+extern "C" fn jit_frem(a: f32, b: f32) -> f32 {
+    a % b
+}
+
+extern "C" fn jit_drem(a: f64, b: f64) -> f64 {
+    a % b
+}
@@ -0,0 +1,1004 @@
+//! Translating the bytecode of a method to Cranelift IR.
+//!
+//! Each local is a Cranelift variable per type it is used with, so locals end up in registers. The
+//! operand stack is tracked while translating and only goes through variables where a branch takes
+//! operands to another block, like the arms of `a ? b : c`. Before translating, a dataflow pass
+//! finds the types of the locals before each instruction, which a deoptimization needs to write the
+//! locals in the words of the interpreter.
+
+use std::{cmp::Ordering, collections::{HashMap, HashSet}, mem::offset_of};
+
+use cranelift_codegen::{entity::EntityRef, ir::{condcodes::{FloatCC, IntCC}, types, Block, FuncRef, InstBuilder, MemFlags, SigRef, StackSlot, StackSlotData, StackSlotKind, Type, Value as Ir}};
+use cranelift_frontend::{FunctionBuilder, Switch as SwitchBuilder, Variable};
+use cranelift_jit::JITModule;
+use cranelift_module::{FuncId, Module};
+use num_enum::FromPrimitive;
+
+use crate::{class::MethodAccess, code::opcode::Opcode, descriptor::{AnyDescriptor, FieldDescriptor, MethodDescriptor}};
+
+use super::{super::{bytes::AsBytes32Aligned, link::{ArithOp, Condition, Decoded, Instruction, NumberKind, Switch, ValueKind}, values_into_u64, BytecodeMethod, InitState, MethodImpl, ReturnCategory, Runtime, ARRAY_DATA_OFFSET, OBJECT_CLASS, ARRAY_LENGTH_OFFSET}, Helpers, Jit, JitEnv, JitMethod, DEOPTED, RETURNED};
+
+/// The method uses something compiled code does not support, it stays interpreted
+#[derive(Debug)]
+pub(super) struct Unsupported;
+
+type Result<T> = std::result::Result<T, Unsupported>;
+
+/// The type of a local
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Ty {
+    /// Unknown, or the second slot of a `long` or `double`
+    Top,
+    Int,
+    Float,
+    Ref,
+    Long,
+    Double,
+}
+
+impl Ty {
+    fn of(field_type: &FieldDescriptor) -> Self {
+        match field_type {
+            FieldDescriptor::Float => Ty::Float,
+            FieldDescriptor::Long => Ty::Long,
+            FieldDescriptor::Double => Ty::Double,
+            FieldDescriptor::ClassRef(_) | FieldDescriptor::ArrRef(_) => Ty::Ref,
+            _ => Ty::Int,
+        }
+    }
+    /// The type `xload` and `xstore` opcodes move, from the letter they start with
+    fn of_opcode(opcode: Opcode) -> Self {
+        match opcode.mnemonic().as_bytes()[0] {
+            b'i' => Ty::Int,
+            b'f' => Ty::Float,
+            b'l' => Ty::Long,
+            b'd' => Ty::Double,
+            _ => Ty::Ref,
+        }
+    }
+    /// The type of the Cranelift values holding it
+    fn ir(self) -> Type {
+        match self {
+            Ty::Top | Ty::Int | Ty::Ref => types::I32,
+            Ty::Float => types::F32,
+            Ty::Long => types::I64,
+            Ty::Double => types::F64,
+        }
+    }
+    fn merge(self, other: Self) -> Self {
+        if self == other { self } else { Ty::Top }
+    }
+}
+
+impl NumberKind {
+    fn ir(self) -> Type {
+        match self {
+            NumberKind::Int => types::I32,
+            NumberKind::Long => types::I64,
+            NumberKind::Float => types::F32,
+            NumberKind::Double => types::F64,
+        }
+    }
+}
+
+impl Condition {
+    fn int_cc(self) -> IntCC {
+        match self {
+            Condition::Eq => IntCC::Equal,
+            Condition::Ne => IntCC::NotEqual,
+            Condition::Lt => IntCC::SignedLessThan,
+            Condition::Ge => IntCC::SignedGreaterThanOrEqual,
+            Condition::Gt => IntCC::SignedGreaterThan,
+            Condition::Le => IntCC::SignedLessThanOrEqual,
+        }
+    }
+}
+
+/// Cranelift variables are numbered by local or stack slot and by type
+fn repr(ty: Type) -> usize {
+    match ty {
+        types::I32 => 0,
+        types::F32 => 1,
+        types::I64 => 2,
+        _ => 3,
+    }
+}
+
+/// The method a call goes to
+enum Callee {
+    /// A compiled method, or the method being compiled
+    Direct(FuncId),
+    /// A method that may be compiled by the time it is called
+    Lookup { class: u32, method: u16 },
+    /// A virtual call, with the method compiled for the class of the last receiver
+    Virtual { site: u32, guess: Option<(u32, FuncId)> },
+}
+
+/// Translates a method into the function of the context of the JIT
+pub(super) fn translate(jit: &mut Jit, runtime: &Runtime, (class, method, func): (u32, u16, FuncId), bytecode: &BytecodeMethod) -> Result<()> {
+    let location = bytecode.code_location;
+    let code_length = bytecode.debug.code_length as usize;
+    let code = &runtime.code.as_bytes_32aligned()[location..location + code_length];
+    let instructions = &runtime.instructions[location..location + code_length];
+    let flow = Flow::analyze(bytecode, code, instructions, &runtime.switches)?;
+    let signature = jit.signature();
+    let Jit { module, context, builder_context, methods, helpers, .. } = jit;
+    let mut builder = FunctionBuilder::new(&mut context.func, builder_context);
+    let method_signature = builder.import_signature(signature);
+    let max_locals = bytecode.max_locals as usize;
+    let max_stack = bytecode.max_stack as usize;
+    for n in 0..(max_locals + max_stack) * 4 {
+        builder.declare_var(Variable::new(n), [types::I32, types::F32, types::I64, types::F64][n % 4]);
+    }
+    let frame = builder.create_sized_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, ((max_locals + max_stack) * 4).max(8) as u32, 3));
+
+    let entry = builder.create_block();
+    builder.append_block_params_for_function_params(entry);
+    builder.switch_to_block(entry);
+    let (env, args) = (builder.block_params(entry)[0], builder.block_params(entry)[1]);
+    let heap = builder.ins().load(types::I64, MemFlags::trusted(), env, offset_of!(JitEnv, heap) as i32);
+    let heap = builder.ins().iadd_imm(heap, -0x8000_0000);
+    let statics = builder.ins().load(types::I64, MemFlags::trusted(), env, offset_of!(JitEnv, statics) as i32);
+    let statics = builder.ins().iadd_imm(statics, -4);
+
+    let mut translator = Translator {
+        builder,
+        module,
+        runtime,
+        methods,
+        helpers: *helpers,
+        class,
+        method,
+        func,
+        location,
+        code,
+        instructions,
+        max_locals,
+        flow,
+        blocks: HashMap::new(),
+        entry_stacks: HashMap::new(),
+        stack: Vec::new(),
+        env,
+        heap,
+        statics,
+        frame,
+        imports: HashMap::new(),
+        method_signature,
+    };
+    translator.load_args(args, bytecode.arg_num as usize);
+    let first = translator.jump_args(0)?;
+    translator.builder.ins().jump(first, &[]);
+    translator.translate_code()?;
+    translator.builder.seal_all_blocks();
+    translator.builder.finalize();
+    Ok(())
+}
+
+/// What the dataflow pass found out about the code of a method
+struct Flow {
+    /// The types of the locals before each instruction, `None` for instructions compiled code
+    /// never runs
+    locals: Vec<Option<Box<[Ty]>>>,
+    /// Instructions that start a block: branch targets and the instructions after conditional
+    /// branches
+    starts: HashSet<usize>,
+    /// Branch targets that come before the branch
+    back_edge_targets: HashSet<usize>,
+}
+
+impl Flow {
+    fn analyze(bytecode: &BytecodeMethod, code: &[u8], instructions: &[Decoded], switches: &[Switch]) -> Result<Self> {
+        let location = bytecode.code_location;
+        let mut entry = vec![Ty::Top; bytecode.max_locals as usize];
+        let mut slot = 0;
+        if !bytecode.access_flags.contains(MethodAccess::STATIC) {
+            entry[0] = Ty::Ref;
+            slot = 1;
+        }
+        for arg in &bytecode.debug.descriptor.arg_types {
+            entry[slot] = Ty::of(arg);
+            slot += arg.unit_size();
+        }
+
+        let mut flow = Flow { locals: vec![None; code.len()], starts: HashSet::from([0]), back_edge_targets: HashSet::new() };
+        flow.locals[0] = Some(entry.into());
+        let mut work = vec![0];
+        while let Some(pc) = work.pop() {
+            let mut locals = flow.locals[pc].clone().unwrap();
+            let Decoded { length, instruction } = instructions[pc];
+            let next = pc + length as usize;
+            let target = |target: u32| (target as usize).checked_sub(location).ok_or(Unsupported);
+            let successors = match instruction {
+                Instruction::Store(index) | Instruction::Store2(index) => {
+                    store(&mut locals, index as usize, Ty::of_opcode(Opcode::from_primitive(code[pc])))?;
+                    vec![next]
+                }
+                Instruction::If(_, to) | Instruction::IfCmp(_, to) => {
+                    flow.starts.extend([next, target(to)?]);
+                    vec![next, target(to)?]
+                }
+                Instruction::Goto(to) => {
+                    flow.starts.insert(target(to)?);
+                    vec![target(to)?]
+                }
+                Instruction::Switch(index) => {
+                    let switch = &switches[index as usize];
+                    let targets = switch.cases().map(|(_, to)| to).chain([switch.default()]);
+                    let targets = targets.map(target).collect::<Result<Vec<_>>>()?;
+                    flow.starts.extend(&targets);
+                    targets
+                }
+                Instruction::Return(_) | Instruction::Athrow => vec![],
+                // subroutines would need the return addresses tracked through the locals
+                Instruction::Operand | Instruction::Reserved | Instruction::Jsr(_) | Instruction::Ret(_) => return Err(Unsupported),
+                _ => vec![next],
+            };
+            for successor in successors {
+                if successor >= code.len() {
+                    return Err(Unsupported);
+                }
+                if successor <= pc && successor != next {
+                    flow.back_edge_targets.insert(successor);
+                }
+                match &mut flow.locals[successor] {
+                    Some(known) => {
+                        let merged: Box<[Ty]> = known.iter().zip(&locals).map(|(a, b)| a.merge(*b)).collect();
+                        if merged != *known {
+                            *known = merged;
+                            work.push(successor);
+                        }
+                    }
+                    unknown => {
+                        *unknown = Some(locals.clone());
+                        work.push(successor);
+                    }
+                }
+            }
+        }
+        Ok(flow)
+    }
+}
+
+/// The types of the locals after storing a value of type `ty` in local `index`
+fn store(locals: &mut [Ty], index: usize, ty: Ty) -> Result<()> {
+    let wide = matches!(ty, Ty::Long | Ty::Double);
+    if index + wide as usize >= locals.len() {
+        return Err(Unsupported);
+    }
+    // the store overwrites the second half of a `long` or `double` before it
+    if index > 0 && matches!(locals[index - 1], Ty::Long | Ty::Double) {
+        locals[index - 1] = Ty::Top;
+    }
+    locals[index] = ty;
+    if wide {
+        locals[index + 1] = Ty::Top;
+    }
+    Ok(())
+}
+
+struct Translator<'a> {
+    builder: FunctionBuilder<'a>,
+    module: &'a mut JITModule,
+    runtime: &'a Runtime,
+    methods: &'a HashMap<(u32, u16), JitMethod>,
+    helpers: Helpers,
+    class: u32,
+    method: u16,
+    func: FuncId,
+    location: usize,
+    code: &'a [u8],
+    instructions: &'a [Decoded],
+    max_locals: usize,
+    flow: Flow,
+    blocks: HashMap<usize, Block>,
+    /// The types of the operands at the start of blocks a branch was translated to
+    entry_stacks: HashMap<usize, Vec<Type>>,
+    stack: Vec<Ir>,
+    env: Ir,
+    /// The addresses heap and static references are offsets from
+    heap: Ir,
+    statics: Ir,
+    /// Where a deoptimizing frame writes its words
+    frame: StackSlot,
+    imports: HashMap<FuncId, FuncRef>,
+    method_signature: SigRef,
+}
+
+impl Translator<'_> {
+    fn local(&self, index: usize, ty: Type) -> Variable {
+        Variable::new(index * 4 + repr(ty))
+    }
+    fn stack_slot(&self, depth: usize, ty: Type) -> Variable {
+        Variable::new((self.max_locals + depth) * 4 + repr(ty))
+    }
+    fn block(&mut self, pc: usize) -> Block {
+        *self.blocks.entry(pc).or_insert_with(|| self.builder.create_block())
+    }
+    fn import(&mut self, func: FuncId) -> FuncRef {
+        *self.imports.entry(func).or_insert_with(|| self.module.declare_func_in_func(func, self.builder.func))
+    }
+    fn call(&mut self, func: FuncId, args: &[Ir]) -> Option<Ir> {
+        let func = self.import(func);
+        let call = self.builder.ins().call(func, args);
+        self.builder.inst_results(call).first().copied()
+    }
+    /// An integer constant, Cranelift wants the bits of narrow ones zero-extended
+    fn iconst(&mut self, ty: Type, value: i64) -> Ir {
+        let value = if ty == types::I32 { value as u32 as i64 } else { value };
+        self.builder.ins().iconst(ty, value)
+    }
+    fn ty(&self, value: Ir) -> Type {
+        self.builder.func.dfg.value_type(value)
+    }
+
+    fn load_args(&mut self, args: Ir, arg_num: usize) {
+        let locals = self.flow.locals[0].clone().unwrap();
+        for (index, &ty) in locals[..arg_num].iter().enumerate() {
+            if ty != Ty::Top {
+                let value = self.builder.ins().load(ty.ir(), MemFlags::trusted(), args, (index * 4) as i32);
+                let variable = self.local(index, ty.ir());
+                self.builder.def_var(variable, value);
+            }
+        }
+    }
+
+    fn push(&mut self, value: Ir) {
+        self.stack.push(value);
+    }
+    fn pop(&mut self) -> Result<Ir> {
+        self.stack.pop().ok_or(Unsupported)
+    }
+    /// Pops a value as a `ty`, reinterpreting the bits of a constant pushed as an `int`
+    fn pop_as(&mut self, ty: Type) -> Result<Ir> {
+        let value = self.pop()?;
+        self.cast(value, ty)
+    }
+    fn cast(&mut self, value: Ir, ty: Type) -> Result<Ir> {
+        let from = self.ty(value);
+        if from == ty {
+            Ok(value)
+        } else if from.bytes() == ty.bytes() {
+            Ok(self.builder.ins().bitcast(ty, MemFlags::new(), value))
+        } else {
+            Err(Unsupported)
+        }
+    }
+    /// Pops a category 1 value of any type
+    fn pop_cat1(&mut self) -> Result<Ir> {
+        let value = self.pop()?;
+        if self.is_wide(value) { Err(Unsupported) } else { Ok(value) }
+    }
+
+    /// Whether a value is a `long` or `double`, which take two words
+    fn is_wide(&self, value: Ir) -> bool {
+        self.ty(value).bytes() == 8
+    }
+
+    /// Passes the operands to the block at `pc` through the stack variables, the first branch to
+    /// a block decides their types
+    fn jump_args(&mut self, pc: usize) -> Result<Block> {
+        let stack = self.stack.clone();
+        let types = match self.entry_stacks.get(&pc) {
+            Some(types) if types.len() == stack.len() => types.clone(),
+            Some(_) => return Err(Unsupported),
+            None => {
+                let types: Vec<Type> = stack.iter().map(|&value| self.ty(value)).collect();
+                self.entry_stacks.insert(pc, types.clone());
+                types
+            }
+        };
+        for (depth, (&value, &ty)) in stack.iter().zip(&types).enumerate() {
+            let value = self.cast(value, ty)?;
+            let variable = self.stack_slot(depth, ty);
+            self.builder.def_var(variable, value);
+        }
+        Ok(self.block(pc))
+    }
+
+    fn translate_code(&mut self) -> Result<()> {
+        // whether the instruction before falls through to the current one
+        let mut open = false;
+        let mut pc = 0;
+        while pc < self.code.len() {
+            let length = self.instructions[pc].length as usize;
+            if self.flow.locals[pc].is_none() {
+                pc += length;
+                continue;
+            }
+            if self.flow.starts.contains(&pc) {
+                if open {
+                    let block = self.jump_args(pc)?;
+                    self.builder.ins().jump(block, &[]);
+                }
+                let types = match self.entry_stacks.get(&pc) {
+                    Some(types) => types.clone(),
+                    // a loop reached only by its back edge, javac leaves no operands across those
+                    None if self.flow.back_edge_targets.contains(&pc) => {
+                        self.entry_stacks.insert(pc, Vec::new());
+                        Vec::new()
+                    }
+                    // only deoptimized code gets here
+                    None => {
+                        open = false;
+                        pc += length;
+                        continue;
+                    }
+                };
+                let block = self.block(pc);
+                self.builder.switch_to_block(block);
+                self.stack.clear();
+                for (depth, ty) in types.into_iter().enumerate() {
+                    let variable = self.stack_slot(depth, ty);
+                    let value = self.builder.use_var(variable);
+                    self.stack.push(value);
+                }
+                open = true;
+            }
+            if open {
+                open = self.translate_instruction(pc)?;
+            }
+            pc += length;
+        }
+        if open {
+            return Err(Unsupported);
+        }
+        Ok(())
+    }
+
+    /// Translates the instruction at `pc`, returns whether it falls through to the next one
+    fn translate_instruction(&mut self, pc: usize) -> Result<bool> {
+        let Decoded { length, instruction } = self.instructions[pc];
+        let next = pc + length as usize;
+        let opcode = Opcode::from_primitive(self.code[pc]);
+        match instruction {
+            Instruction::Nop => (),
+            Instruction::Push(value) => {
+                let value = self.iconst(types::I32, value.into_u32() as i64);
+                self.push(value);
+            }
+            Instruction::Push2(v1, v2) => {
+                let value = self.iconst(types::I64, values_into_u64((v1, v2)) as i64);
+                self.push(value);
+            }
+            Instruction::Load(index) | Instruction::Load2(index) => {
+                let variable = self.local(index as usize, Ty::of_opcode(opcode).ir());
+                let value = self.builder.use_var(variable);
+                self.push(value);
+            }
+            Instruction::Store(index) | Instruction::Store2(index) => {
+                let ty = Ty::of_opcode(opcode).ir();
+                let value = self.pop_as(ty)?;
+                let variable = self.local(index as usize, ty);
+                self.builder.def_var(variable, value);
+            }
+            Instruction::Iinc(index, increment) => {
+                let variable = self.local(index as usize, types::I32);
+                let value = self.builder.use_var(variable);
+                let value = self.builder.ins().iadd_imm(value, increment as i64);
+                self.builder.def_var(variable, value);
+            }
+            Instruction::ArrayLoad(kind) => {
+                let operands = self.stack.clone();
+                let index = self.pop_as(types::I32)?;
+                let array = self.pop_as(types::I32)?;
+                let element = self.element(pc, &operands, array, index, kind)?;
+                let value = self.load(kind, element, ARRAY_DATA_OFFSET as i32);
+                self.push(value);
+            }
+            // storing a reference needs a type check
+            Instruction::ArrayStore(kind) if kind != ValueKind::Reference => {
+                let operands = self.stack.clone();
+                let value = self.pop()?;
+                let index = self.pop_as(types::I32)?;
+                let array = self.pop_as(types::I32)?;
+                let element = self.element(pc, &operands, array, index, kind)?;
+                self.store(kind, value, element, ARRAY_DATA_OFFSET as i32)?;
+            }
+            Instruction::Arraylength => {
+                let operands = self.stack.clone();
+                let array = self.pop_as(types::I32)?;
+                self.null_check(pc, &operands, array);
+                let address = self.address(array);
+                let length = self.builder.ins().load(types::I32, MemFlags::trusted(), address, ARRAY_LENGTH_OFFSET as i32);
+                self.push(length);
+            }
+            Instruction::Pop => {
+                self.pop_cat1()?;
+            }
+            Instruction::Pop2 => {
+                let value = self.pop()?;
+                if !self.is_wide(value) {
+                    self.pop_cat1()?;
+                }
+            }
+            Instruction::Dup => {
+                let value = self.pop_cat1()?;
+                self.stack.extend([value, value]);
+            }
+            // a `long` or `double` is a single value here, the forms for category 2 values move it
+            Instruction::DupX1 => {
+                let v1 = self.pop_cat1()?;
+                let v2 = self.pop_cat1()?;
+                self.stack.extend([v1, v2, v1]);
+            }
+            Instruction::DupX2 => {
+                let v1 = self.pop_cat1()?;
+                let v2 = self.pop()?;
+                if self.is_wide(v2) {
+                    self.stack.extend([v1, v2, v1]);
+                } else {
+                    let v3 = self.pop_cat1()?;
+                    self.stack.extend([v1, v3, v2, v1]);
+                }
+            }
+            Instruction::Dup2 => {
+                let v1 = self.pop()?;
+                if self.is_wide(v1) {
+                    self.stack.extend([v1, v1]);
+                } else {
+                    let v2 = self.pop_cat1()?;
+                    self.stack.extend([v2, v1, v2, v1]);
+                }
+            }
+            Instruction::Dup2X1 => {
+                let v1 = self.pop()?;
+                let v2 = self.pop_cat1()?;
+                if self.is_wide(v1) {
+                    self.stack.extend([v1, v2, v1]);
+                } else {
+                    let v3 = self.pop_cat1()?;
+                    self.stack.extend([v2, v1, v3, v2, v1]);
+                }
+            }
+            Instruction::Dup2X2 => {
+                let v1 = self.pop()?;
+                let mut copied = vec![v1];
+                if !self.is_wide(v1) {
+                    copied.insert(0, self.pop_cat1()?);
+                }
+                let v2 = self.pop()?;
+                let mut below = vec![v2];
+                if !self.is_wide(v2) {
+                    below.insert(0, self.pop_cat1()?);
+                }
+                self.stack.extend(copied.iter().chain(&below).chain(&copied));
+            }
+            Instruction::Swap => {
+                let v2 = self.pop_cat1()?;
+                let v1 = self.pop_cat1()?;
+                self.stack.extend([v2, v1]);
+            }
+            Instruction::Arith(kind, op) => self.arith(pc, kind, op)?,
+            Instruction::Neg(kind) => {
+                let value = self.pop_as(kind.ir())?;
+                let negated = match kind {
+                    NumberKind::Int | NumberKind::Long => self.builder.ins().ineg(value),
+                    NumberKind::Float | NumberKind::Double => self.builder.ins().fneg(value),
+                };
+                self.push(negated);
+            }
+            Instruction::Convert(from, to) => {
+                let value = self.pop_as(from.ir())?;
+                let ins = self.builder.ins();
+                let converted = match (from, to) {
+                    (NumberKind::Int, NumberKind::Long) => ins.sextend(types::I64, value),
+                    (NumberKind::Long, NumberKind::Int) => ins.ireduce(types::I32, value),
+                    (NumberKind::Int | NumberKind::Long, _) => ins.fcvt_from_sint(to.ir(), value),
+                    (NumberKind::Float, NumberKind::Double) => ins.fpromote(types::F64, value),
+                    (NumberKind::Double, NumberKind::Float) => ins.fdemote(types::F32, value),
+                    // saturating with NaN becoming 0, like the JVMS asks for
+                    (NumberKind::Float | NumberKind::Double, _) => ins.fcvt_to_sint_sat(to.ir(), value),
+                };
+                self.push(converted);
+            }
+            Instruction::Narrow(kind) => {
+                let value = self.pop_as(types::I32)?;
+                let ins = self.builder.ins();
+                let narrowed = match kind {
+                    ValueKind::Char => ins.band_imm(value, 0xffff),
+                    _ => {
+                        let narrow = if kind == ValueKind::Byte { types::I8 } else { types::I16 };
+                        let value = ins.ireduce(narrow, value);
+                        self.builder.ins().sextend(types::I32, value)
+                    }
+                };
+                self.push(narrowed);
+            }
+            Instruction::Lcmp => {
+                let v2 = self.pop_as(types::I64)?;
+                let v1 = self.pop_as(types::I64)?;
+                let greater = self.builder.ins().icmp(IntCC::SignedGreaterThan, v1, v2);
+                let less = self.builder.ins().icmp(IntCC::SignedLessThan, v1, v2);
+                let greater = self.builder.ins().uextend(types::I32, greater);
+                let less = self.builder.ins().uextend(types::I32, less);
+                let result = self.builder.ins().isub(greater, less);
+                self.push(result);
+            }
+            Instruction::Fcmp(nan) => self.fcmp(types::F32, nan)?,
+            Instruction::Dcmp(nan) => self.fcmp(types::F64, nan)?,
+            Instruction::If(condition, target) => {
+                let value = self.pop_as(types::I32)?;
+                let holds = self.builder.ins().icmp_imm(condition.int_cc(), value, 0);
+                self.branch(holds, target, next)?;
+                return Ok(false);
+            }
+            Instruction::IfCmp(condition, target) => {
+                let v2 = self.pop_as(types::I32)?;
+                let v1 = self.pop_as(types::I32)?;
+                let holds = self.builder.ins().icmp(condition.int_cc(), v1, v2);
+                self.branch(holds, target, next)?;
+                return Ok(false);
+            }
+            Instruction::Goto(target) => {
+                let block = self.jump_args(target as usize - self.location)?;
+                self.builder.ins().jump(block, &[]);
+                return Ok(false);
+            }
+            Instruction::Switch(index) => {
+                let key = self.pop_as(types::I32)?;
+                let switch = &self.runtime.switches[index as usize];
+                let mut cases = SwitchBuilder::new();
+                for (case, target) in switch.cases() {
+                    let block = self.jump_args(target as usize - self.location)?;
+                    cases.set_entry(case as u32 as u128, block);
+                }
+                let default = self.jump_args(switch.default() as usize - self.location)?;
+                cases.emit(&mut self.builder, key, default);
+                return Ok(false);
+            }
+            Instruction::Return(category) => {
+                if category != ReturnCategory::Void {
+                    let value = self.pop()?;
+                    self.builder.ins().store(MemFlags::trusted(), value, self.env, offset_of!(JitEnv, result) as i32);
+                }
+                let status = self.iconst(types::I32, RETURNED as i64);
+                self.builder.ins().return_(&[status]);
+                return Ok(false);
+            }
+            Instruction::Getstatic { class, offset, kind } if self.is_initialized(class) => {
+                let address = self.static_field(class, offset);
+                let value = self.load(kind, address, 0);
+                self.push(value);
+            }
+            Instruction::Putstatic { class, offset, kind } if self.is_initialized(class) => {
+                let value = self.pop()?;
+                let address = self.static_field(class, offset);
+                self.store(kind, value, address, 0)?;
+            }
+            Instruction::Getfield { offset, kind } => {
+                let operands = self.stack.clone();
+                let object = self.pop_as(types::I32)?;
+                self.null_check(pc, &operands, object);
+                let address = self.address(object);
+                let value = self.load(kind, address, offset as i32);
+                self.push(value);
+            }
+            Instruction::Putfield { offset, kind } => {
+                let operands = self.stack.clone();
+                let value = self.pop()?;
+                let object = self.pop_as(types::I32)?;
+                self.null_check(pc, &operands, object);
+                let address = self.address(object);
+                self.store(kind, value, address, offset as i32)?;
+            }
+            Instruction::InvokeStatic { class, method } if self.is_initialized(class) => {
+                return self.invoke(pc, next, class, method, false);
+            }
+            // the constructor of `Object`, which every constructor calls, does nothing
+            Instruction::InvokeSpecial { class: OBJECT_CLASS, method: 0, .. } => {
+                self.pop_as(types::I32)?;
+            }
+            Instruction::InvokeSpecial { class, method, .. } => return self.invoke(pc, next, class, method, true),
+            Instruction::InvokeVirtual { site, .. } => {
+                let call = &self.runtime.virtual_calls[site as usize];
+                let AnyDescriptor::Method(descriptor) = &call.selector().descriptor else { return Err(Unsupported) };
+                let guess = call.cached()
+                    .and_then(|(receiver, class, method)| Some((receiver, self.compiled(class, method)?)));
+                self.call_method(pc, next, Callee::Virtual { site, guess }, descriptor, true)?;
+            }
+            // allocations, exceptions, monitors, unlinked instructions and the like
+            _ => {
+                let operands = self.stack.clone();
+                self.deopt(pc, pc, &operands);
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn arith(&mut self, pc: usize, kind: NumberKind, op: ArithOp) -> Result<()> {
+        let ty = kind.ir();
+        let operands = self.stack.clone();
+        let v2 = match op {
+            ArithOp::Shl | ArithOp::Shr | ArithOp::Ushr => self.pop_as(types::I32)?,
+            _ => self.pop_as(ty)?,
+        };
+        let v1 = self.pop_as(ty)?;
+        let float = matches!(kind, NumberKind::Float | NumberKind::Double);
+        let ins = self.builder.ins();
+        let result = match op {
+            ArithOp::Add if float => ins.fadd(v1, v2),
+            ArithOp::Sub if float => ins.fsub(v1, v2),
+            ArithOp::Mul if float => ins.fmul(v1, v2),
+            ArithOp::Div if float => ins.fdiv(v1, v2),
+            ArithOp::Rem if float => {
+                let helper = if kind == NumberKind::Float { self.helpers.frem } else { self.helpers.drem };
+                self.call(helper, &[v1, v2]).unwrap()
+            }
+            ArithOp::Add => ins.iadd(v1, v2),
+            ArithOp::Sub => ins.isub(v1, v2),
+            ArithOp::Mul => ins.imul(v1, v2),
+            ArithOp::Div | ArithOp::Rem => {
+                // the interpreter throws the `ArithmeticException`
+                let zero = ins.icmp_imm(IntCC::Equal, v2, 0);
+                self.deopt_if(zero, pc, &operands);
+                // `MIN_VALUE / -1` overflows, which traps on x86
+                let minus_one = self.builder.ins().icmp_imm(IntCC::Equal, v2, -1);
+                let one = self.iconst(ty, 1);
+                let divisor = self.builder.ins().select(minus_one, one, v2);
+                if op == ArithOp::Div {
+                    let quotient = self.builder.ins().sdiv(v1, divisor);
+                    let negated = self.builder.ins().ineg(v1);
+                    self.builder.ins().select(minus_one, negated, quotient)
+                } else {
+                    self.builder.ins().srem(v1, divisor)
+                }
+            }
+            ArithOp::Shl => ins.ishl(v1, v2),
+            ArithOp::Shr => ins.sshr(v1, v2),
+            ArithOp::Ushr => ins.ushr(v1, v2),
+            ArithOp::And => ins.band(v1, v2),
+            ArithOp::Or => ins.bor(v1, v2),
+            ArithOp::Xor => ins.bxor(v1, v2),
+        };
+        self.push(result);
+        Ok(())
+    }
+
+    /// `fcmpl`, `fcmpg`, `dcmpl` and `dcmpg`, which differ in what a NaN compares as
+    fn fcmp(&mut self, ty: Type, nan: Ordering) -> Result<()> {
+        let v2 = self.pop_as(ty)?;
+        let v1 = self.pop_as(ty)?;
+        let (cc, ordered, unordered) = match nan {
+            Ordering::Less => (FloatCC::GreaterThan, 1, -1),
+            _ => (FloatCC::LessThan, -1, 1),
+        };
+        let holds = self.builder.ins().fcmp(cc, v1, v2);
+        let equal = self.builder.ins().fcmp(FloatCC::Equal, v1, v2);
+        let ordered = self.iconst(types::I32, ordered);
+        let unordered = self.iconst(types::I32, unordered);
+        let zero = self.iconst(types::I32, 0);
+        let otherwise = self.builder.ins().select(equal, zero, unordered);
+        let result = self.builder.ins().select(holds, ordered, otherwise);
+        self.push(result);
+        Ok(())
+    }
+
+    fn branch(&mut self, holds: Ir, target: u32, next: usize) -> Result<()> {
+        let taken = self.jump_args(target as usize - self.location)?;
+        let not_taken = self.jump_args(next)?;
+        self.builder.ins().brif(holds, taken, &[], not_taken, &[]);
+        Ok(())
+    }
+
+    fn is_initialized(&self, class: u32) -> bool {
+        self.runtime.get_class(class).init_state == InitState::Initialized
+    }
+    /// The address of a static field, the static fields of a class never move
+    fn static_field(&mut self, class: u32, offset: u16) -> Ir {
+        let fields = self.runtime.get_class(class).static_fields.as_u32_slice().as_ptr();
+        self.iconst(types::I64, fields as i64 + offset as i64)
+    }
+    /// The address of an object
+    fn address(&mut self, object: Ir) -> Ir {
+        let on_heap = self.builder.ins().icmp_imm(IntCC::UnsignedGreaterThanOrEqual, object, 0x8000_0000);
+        let base = self.builder.ins().select(on_heap, self.heap, self.statics);
+        let offset = self.builder.ins().uextend(types::I64, object);
+        self.builder.ins().iadd(base, offset)
+    }
+    /// The address of an array element minus [`ARRAY_DATA_OFFSET`], deoptimizing if the array is
+    /// null or the index out of bounds
+    fn element(&mut self, pc: usize, operands: &[Ir], array: Ir, index: Ir, kind: ValueKind) -> Result<Ir> {
+        self.null_check(pc, operands, array);
+        let address = self.address(array);
+        let length = self.builder.ins().load(types::I32, MemFlags::trusted(), address, ARRAY_LENGTH_OFFSET as i32);
+        let out_of_bounds = self.builder.ins().icmp(IntCC::UnsignedGreaterThanOrEqual, index, length);
+        self.deopt_if(out_of_bounds, pc, operands);
+        let index = self.builder.ins().uextend(types::I64, index);
+        let offset = self.builder.ins().imul_imm(index, kind.size() as i64);
+        Ok(self.builder.ins().iadd(address, offset))
+    }
+    fn load(&mut self, kind: ValueKind, address: Ir, offset: i32) -> Ir {
+        let (ins, flags) = (self.builder.ins(), MemFlags::trusted());
+        match kind {
+            ValueKind::Byte => ins.sload8(types::I32, flags, address, offset),
+            ValueKind::Char => ins.uload16(types::I32, flags, address, offset),
+            ValueKind::Short => ins.sload16(types::I32, flags, address, offset),
+            ValueKind::Int | ValueKind::Reference => ins.load(types::I32, flags, address, offset),
+            ValueKind::Float => ins.load(types::F32, flags, address, offset),
+            ValueKind::Long => ins.load(types::I64, flags, address, offset),
+        }
+    }
+    fn store(&mut self, kind: ValueKind, value: Ir, address: Ir, offset: i32) -> Result<()> {
+        let bytes = if kind == ValueKind::Long { 8 } else { 4 };
+        if self.ty(value).bytes() != bytes {
+            return Err(Unsupported);
+        }
+        let (ins, flags) = (self.builder.ins(), MemFlags::trusted());
+        match kind {
+            ValueKind::Byte => ins.istore8(flags, value, address, offset),
+            ValueKind::Char | ValueKind::Short => ins.istore16(flags, value, address, offset),
+            _ => ins.store(flags, value, address, offset),
+        };
+        Ok(())
+    }
+
+    /// The compiled code of a method, if it has some
+    fn compiled(&self, class: u32, method: u16) -> Option<FuncId> {
+        if (class, method) == (self.class, self.method) {
+            return Some(self.func);
+        }
+        Some(self.methods.get(&(class, method))?.code?.func)
+    }
+    fn invoke(&mut self, pc: usize, next: usize, class: u32, method: u16, receiver: bool) -> Result<bool> {
+        let runtime = self.runtime;
+        let rejected = self.methods.get(&(class, method)).is_some_and(|state| state.rejected);
+        let MethodImpl::Bytecode(callee) = runtime.get_class(class).method(method) else {
+            let operands = self.stack.clone();
+            self.deopt(pc, pc, &operands);
+            return Ok(false);
+        };
+        if rejected {
+            let operands = self.stack.clone();
+            self.deopt(pc, pc, &operands);
+            return Ok(false);
+        }
+        let target = match self.compiled(class, method) {
+            Some(func) => Callee::Direct(func),
+            None => Callee::Lookup { class, method },
+        };
+        self.call_method(pc, next, target, &callee.debug.descriptor, receiver)?;
+        Ok(true)
+    }
+    /// Calls compiled code, deoptimizing if the method is not compiled, the call depth limit is
+    /// reached or the callee deoptimizes
+    fn call_method(&mut self, pc: usize, next: usize, callee: Callee, descriptor: &MethodDescriptor, receiver: bool) -> Result<()> {
+        let operands = self.stack.clone();
+        let count = descriptor.arg_types.len() + receiver as usize;
+        if self.stack.len() < count {
+            return Err(Unsupported);
+        }
+        let mut args = self.stack.split_off(self.stack.len() - count);
+        let arg_types = receiver.then_some(Ty::Ref).into_iter().chain(descriptor.arg_types.iter().map(Ty::of));
+        for (arg, ty) in args.iter_mut().zip(arg_types) {
+            *arg = self.cast(*arg, ty.ir())?;
+        }
+        if receiver {
+            self.null_check(pc, &operands, args[0]);
+        }
+
+        let code = match callee {
+            Callee::Direct(_) => None,
+            Callee::Lookup { class, method } => {
+                let class = self.iconst(types::I32, class as i64);
+                let method = self.iconst(types::I32, method as i64);
+                Some(self.call(self.helpers.target, &[self.env, class, method]).unwrap())
+            }
+            Callee::Virtual { site, guess } => {
+                let address = self.address(args[0]);
+                let receiver_class = self.builder.ins().load(types::I32, MemFlags::trusted(), address, 0);
+                let lookup = self.builder.create_block();
+                let found = self.builder.create_block();
+                self.builder.append_block_param(found, types::I64);
+                if let Some((expected, func)) = guess {
+                    let guessed = self.builder.create_block();
+                    let hit = self.builder.ins().icmp_imm(IntCC::Equal, receiver_class, expected as i64);
+                    self.builder.ins().brif(hit, guessed, &[], lookup, &[]);
+                    self.builder.switch_to_block(guessed);
+                    let func = self.import(func);
+                    let code = self.builder.ins().func_addr(types::I64, func);
+                    self.builder.ins().jump(found, &[code]);
+                    self.builder.set_cold_block(lookup);
+                } else {
+                    self.builder.ins().jump(lookup, &[]);
+                }
+                self.builder.switch_to_block(lookup);
+                let site = self.iconst(types::I32, site as i64);
+                let code = self.call(self.helpers.virtual_target, &[self.env, site, receiver_class]).unwrap();
+                self.builder.ins().jump(found, &[code]);
+                self.builder.switch_to_block(found);
+                Some(self.builder.block_params(found)[0])
+            }
+        };
+        if let Some(code) = code {
+            let missing = self.builder.ins().icmp_imm(IntCC::Equal, code, 0);
+            self.deopt_if(missing, pc, &operands);
+        }
+
+        let depth_offset = offset_of!(JitEnv, depth_left) as i32;
+        let depth_left = self.builder.ins().load(types::I64, MemFlags::trusted(), self.env, depth_offset);
+        let exhausted = self.builder.ins().icmp_imm(IntCC::Equal, depth_left, 0);
+        self.deopt_if(exhausted, pc, &operands);
+        let decremented = self.builder.ins().iadd_imm(depth_left, -1);
+        self.builder.ins().store(MemFlags::trusted(), decremented, self.env, depth_offset);
+
+        let words: u32 = args.iter().map(|&arg| self.ty(arg).bytes()).sum();
+        let slot = self.builder.create_sized_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, words.max(4), 3));
+        let mut offset = 0;
+        for &arg in &args {
+            self.builder.ins().stack_store(arg, slot, offset);
+            offset += self.ty(arg).bytes() as i32;
+        }
+        let args = self.builder.ins().stack_addr(types::I64, slot, 0);
+        let status = match (callee, code) {
+            (Callee::Direct(func), _) => self.call(func, &[self.env, args]).unwrap(),
+            (_, code) => {
+                let call = self.builder.ins().call_indirect(self.method_signature, code.unwrap(), &[self.env, args]);
+                self.builder.inst_results(call)[0]
+            }
+        };
+        self.builder.ins().store(MemFlags::trusted(), depth_left, self.env, depth_offset);
+        // the interpreter goes on after the call with the frames the callee deoptimized
+        let deopted = self.builder.ins().icmp_imm(IntCC::NotEqual, status, RETURNED as i64);
+        let rest = self.stack.clone();
+        self.deopt_if_to(deopted, pc, next, &rest);
+
+        if let Some(return_type) = &descriptor.return_type {
+            let ty = Ty::of(return_type).ir();
+            let result = self.builder.ins().load(ty, MemFlags::trusted(), self.env, offset_of!(JitEnv, result) as i32);
+            self.push(result);
+        }
+        Ok(())
+    }
+
+    fn null_check(&mut self, pc: usize, operands: &[Ir], reference: Ir) {
+        let null = self.builder.ins().icmp_imm(IntCC::Equal, reference, 0);
+        self.deopt_if(null, pc, operands);
+    }
+    /// Lets the interpreter run the instruction at `pc` if `condition` holds, with the operands
+    /// it had before the instruction
+    fn deopt_if(&mut self, condition: Ir, pc: usize, operands: &[Ir]) {
+        self.deopt_if_to(condition, pc, pc, operands);
+    }
+    fn deopt_if_to(&mut self, condition: Ir, pc: usize, resume: usize, operands: &[Ir]) {
+        let deopt = self.builder.create_block();
+        let rest = self.builder.create_block();
+        self.builder.set_cold_block(deopt);
+        self.builder.ins().brif(condition, deopt, &[], rest, &[]);
+        self.builder.switch_to_block(deopt);
+        self.deopt(pc, resume, operands);
+        self.builder.switch_to_block(rest);
+    }
+    /// Writes the frame in the words of the interpreter, which goes on at `resume`, and returns
+    /// [`DEOPTED`]. The locals have the types they have before the instruction at `pc`.
+    fn deopt(&mut self, pc: usize, resume: usize, operands: &[Ir]) {
+        let locals = self.flow.locals[pc].clone().unwrap();
+        let mut index = 0;
+        while index < locals.len() {
+            let ty = locals[index];
+            let value = match ty {
+                Ty::Top => self.iconst(types::I32, 0),
+                _ => {
+                    let variable = self.local(index, ty.ir());
+                    self.builder.use_var(variable)
+                }
+            };
+            self.builder.ins().stack_store(value, self.frame, (index * 4) as i32);
+            index += if matches!(ty, Ty::Long | Ty::Double) { 2 } else { 1 };
+        }
+        let mut offset = self.max_locals * 4;
+        for &operand in operands {
+            self.builder.ins().stack_store(operand, self.frame, offset as i32);
+            offset += self.ty(operand).bytes() as usize;
+        }
+        let words = self.builder.ins().stack_addr(types::I64, self.frame, 0);
+        let class = self.iconst(types::I32, self.class as i64);
+        let method = self.iconst(types::I32, self.method as i64);
+        let resume = self.iconst(types::I64, (self.location + resume) as i64);
+        let max_locals = self.iconst(types::I32, self.max_locals as i64);
+        let stack = self.iconst(types::I32, (offset / 4 - self.max_locals) as i64);
+        self.call(self.helpers.deopt, &[self.env, class, method, resume, words, max_locals, stack]);
+        let status = self.iconst(types::I32, DEOPTED as i64);
+        self.builder.ins().return_(&[status]);
+    }
+}
@@ -0,0 +1,399 @@
+//! Lambdas and method references, linked through `LambdaMetafactory`.
+//!
+//! Like the JDK, the runtime spins a class for every lambda call site. The class implements the
+//! functional interface and keeps the captured arguments in fields. Its interface method is
+//! bytecode that pushes the captured values and its own arguments and invokes the implementation
+//! method, casting, boxing and unboxing where the types differ.
+//!
+//! The functional interface is one of the program's or a builtin one, which are all of
+//! `java.util.function` and a few of `java.lang` and `java.util` without their default and static
+//! methods.
+
+use crate::{class::{ClassAccess, ConstIndex, FieldAccess, MethodAccess}, code::opcode::Opcode, descriptor::{FieldDescriptor, MethodDescriptor}};
+
+use super::{debug::{ClassDebugInfo, DeclaredField, MethodDebugInfo}, indy::{CallSite, MethodRef}, layout::FieldLayout, member_table::MemberTable, resolve, BytecodeMethod, LoadedClass, Result, Runtime, RuntimeConstant, RuntimeCtx, RuntimeInfo, Value, OBJECT_CLASS, OBJECT_HEADER_SIZE};
+
+/// Flags of `LambdaMetafactory.altMetafactory`
+const FLAG_SERIALIZABLE: i32 = 1;
+const FLAG_MARKERS: i32 = 2;
+const FLAG_BRIDGES: i32 = 4;
+
+/// Method handle kinds that can be the implementation of a lambda (JVMS 4.4.8)
+const REF_INVOKE_VIRTUAL: u8 = 5;
+const REF_INVOKE_STATIC: u8 = 6;
+const REF_INVOKE_SPECIAL: u8 = 7;
+const REF_NEW_INVOKE_SPECIAL: u8 = 8;
+const REF_INVOKE_INTERFACE: u8 = 9;
+
+/// Everything needed to spin the class of a lambda call site
+struct LambdaShape {
+    name: String,
+    interfaces: Vec<u32>,
+    method_name: Box<str>,
+    /// Descriptor of the interface method, followed by those of its bridges
+    method_types: Vec<MethodDescriptor>,
+    captured: Box<[FieldDescriptor]>,
+    implementation: MethodRef,
+}
+
+impl RuntimeCtx<'_> {
+    /// Links a call site of `LambdaMetafactory.metafactory`, or of `altMetafactory` if `alt` is set.
+    ///
+    /// `method_name` and `invoked_type` come from the `invokedynamic` instruction: the name of the
+    /// interface method, and the captured arguments leading to the functional interface.
+    pub(super) fn link_lambda(
+        &mut self,
+        method_name: &str,
+        invoked_type: MethodDescriptor,
+        args: &[ConstIndex],
+        alt: bool,
+    ) -> Result<CallSite> {
+        let method_type = |ctx: &Self, arg: ConstIndex| {
+            MethodDescriptor::from_bytes(ctx.read_utf8_constant(ctx.read_constant(arg).methodtype()).as_bytes())
+        };
+        let mut method_types = vec![method_type(self, args[0])?];
+        let implementation = self.read_method_handle(args[1])?;
+
+        let Some(FieldDescriptor::ClassRef(interface)) = invoked_type.return_type.as_deref() else {
+            unreachable!("a lambda call site must return its functional interface")
+        };
+        let mut interfaces = vec![self.runtime.load_class(interface)?];
+        if alt {
+            let flags = self.read_constant(args[3]).value().into_i32();
+            let mut rest = args[4..].iter().copied();
+            if flags & FLAG_MARKERS != 0 {
+                let count = self.read_constant(rest.next().unwrap()).value().into_i32();
+                for marker in rest.by_ref().take(count as usize) {
+                    interfaces.push(self.resolve_class(marker)?);
+                }
+            }
+            if flags & FLAG_BRIDGES != 0 {
+                let count = self.read_constant(rest.next().unwrap()).value().into_i32();
+                for bridge in rest.by_ref().take(count as usize) {
+                    method_types.push(method_type(self, bridge)?);
+                }
+            }
+            if flags & FLAG_SERIALIZABLE != 0 {
+                interfaces.push(self.runtime.load_class("java/io/Serializable")?);
+            }
+        }
+
+        let caller = &self.runtime.get_class(self.cur_class).name;
+        let shape = LambdaShape {
+            name: format!("{caller}$$Lambda${}", self.runtime.classes.len()),
+            interfaces,
+            method_name: method_name.into(),
+            method_types,
+            captured: invoked_type.arg_types,
+            implementation,
+        };
+        let (class, fields) = self.runtime.spin_lambda_class(shape);
+        Ok(if fields.is_empty() {
+            // without captured state every evaluation can give the same instance
+            CallSite::Constant(Value::new_ref_static(self.runtime.new_static_obj(class, &[])))
+        } else {
+            CallSite::Lambda { class, fields }
+        })
+    }
+    /// Creates a lambda instance, popping the captured arguments into its fields
+    pub(super) fn new_lambda(&mut self, class: u32, fields: &[(FieldDescriptor, u16)]) -> Result<()> {
+        let size = self.runtime.get_class(class).get_aligned_data_size();
+        if !self.make_room(size as usize)? {
+            return Ok(());
+        }
+        let lambda = self.new_object(class);
+        for (field_type, offset) in fields.iter().rev() {
+            let field = lambda.offset(*offset as u32);
+            match field_type {
+                FieldDescriptor::Long |
+                FieldDescriptor::Double => {
+                    let (v1, v2) = self.pop2();
+                    self.write_u32_ref(field, v1.into_u32());
+                    self.write_u32_ref(field.offset(4), v2.into_u32());
+                }
+                FieldDescriptor::Boolean |
+                FieldDescriptor::Byte => {
+                    let value = self.pop().into_u8();
+                    self.write_u8_ref(field, value);
+                }
+                FieldDescriptor::Char |
+                FieldDescriptor::Short => {
+                    let value = self.pop().into_u16();
+                    self.write_u16_ref(field, value);
+                }
+                _ => {
+                    let value = self.pop().into_u32();
+                    self.write_u32_ref(field, value);
+                }
+            }
+        }
+        self.push(lambda);
+        Ok(())
+    }
+}
+
+impl Runtime {
+    /// Creates the class of a lambda call site and returns it with the types and offsets of the
+    /// fields for the captured arguments
+    fn spin_lambda_class(&mut self, shape: LambdaShape) -> (u32, Box<[(FieldDescriptor, u16)]>) {
+        let mut pool = ConstantPool::default();
+        let mut member_table = MemberTable::new();
+
+        let mut layout = FieldLayout::starting_at(OBJECT_HEADER_SIZE);
+        let mut ref_offsets = Vec::new();
+        let mut fields = Vec::new();
+        let mut declared_fields = Vec::new();
+        for (i, field_type) in shape.captured.iter().enumerate() {
+            let offset = layout.place(field_type.byte_size());
+            if let FieldDescriptor::ClassRef(_) | FieldDescriptor::ArrRef(_) = field_type {
+                ref_offsets.push(offset);
+            }
+            let name = format!("arg${}", i + 1);
+            member_table.insert(name.as_str(), field_type.clone(), offset);
+            let field = pool.member(self, &shape.name, &name, &field_type.to_string());
+            fields.push((field_type.clone(), offset, field));
+            declared_fields.push(DeclaredField { name: name.into(), descriptor: field_type.clone(), access_flags: FieldAccess::PRIVATE | FieldAccess::FINAL, offset });
+        }
+
+        let mut method_code = Vec::new();
+        for method_type in &shape.method_types {
+            if member_table.get(&shape.method_name, &method_type.clone().into()).is_some() {
+                continue;
+            }
+            let code = self.forwarding_code(&mut pool, &fields, &shape.implementation, method_type);
+            let arg_num = 1 + method_type.arg_types.iter().map(|arg| arg.unit_size() as u16).sum::<u16>();
+            let code_location = self.add_code(&code.bytes);
+            member_table.insert(&*shape.method_name, method_type.clone(), method_code.len() as u16);
+            method_code.push(BytecodeMethod {
+                max_stack: code.max_stack,
+                max_locals: arg_num,
+                arg_num,
+                code_location,
+                exception_table: Box::new([]),
+                access_flags: MethodAccess::PUBLIC | MethodAccess::SYNTHETIC,
+                debug: MethodDebugInfo {
+                    name: shape.method_name.clone(),
+                    descriptor: method_type.clone(),
+                    code_length: code.bytes.len() as u32,
+                    line_numbers: Box::new([]),
+                    local_variables: Box::new([]),
+                },
+            });
+        }
+
+        let class = LoadedClass {
+            access_flags: ClassAccess::FINAL | ClassAccess::SYNTHETIC,
+            interfaces: shape.interfaces.into(),
+            runtime_info: RuntimeInfo::Bytecode {
+                method_code: method_code.into(),
+                resolved: resolve::unresolved(pool.0.len()),
+                constant_pool: pool.0.into(),
+                bootstrap_methods: Box::new([]),
+            },
+            data_size: layout.end(),
+            ref_offsets: ref_offsets.into(),
+            debug_info: ClassDebugInfo { source_file: None, fields: declared_fields.into() },
+            ..LoadedClass::new_builtin(&shape.name, OBJECT_CLASS, member_table, Box::new([]))
+        };
+        let id = self.add_class(class);
+        self.class_names.insert(shape.name.into(), id);
+        (id, fields.into_iter().map(|(field_type, offset, _)| (field_type, offset)).collect())
+    }
+    /// Bytecode that loads the captured fields and the arguments of a method of the given type and
+    /// passes them on to the implementation method
+    fn forwarding_code(
+        &mut self,
+        pool: &mut ConstantPool,
+        fields: &[(FieldDescriptor, u16, ConstIndex)],
+        implementation: &MethodRef,
+        method_type: &MethodDescriptor,
+    ) -> Code {
+        let MethodRef { kind, class_name, name, descriptor } = implementation;
+        let receiver = FieldDescriptor::ClassRef(class_name.clone());
+        let params: Vec<_> = match *kind {
+            REF_INVOKE_VIRTUAL | REF_INVOKE_SPECIAL | REF_INVOKE_INTERFACE => Some(&receiver),
+            _ => None,
+        }.into_iter().chain(&descriptor.arg_types).collect();
+
+        let mut code = Code::default();
+        if *kind == REF_NEW_INVOKE_SPECIAL {
+            let class = pool.class(self, class_name);
+            code.op_u16(Opcode::New, class);
+            code.op(Opcode::Dup);
+        }
+        let mut sources = fields.iter().map(|(field_type, _, field)| (field_type, Some(*field)))
+            .chain(method_type.arg_types.iter().map(|arg| (arg, None)));
+        let mut local = 1;
+        for &param in &params {
+            let (source_type, field) = sources.next().unwrap();
+            match field {
+                Some(field) => {
+                    code.op(Opcode::Aload0);
+                    code.op_u16(Opcode::Getfield, field);
+                }
+                None => {
+                    code.op_u8(load_op(source_type), local as u8);
+                    local += source_type.unit_size();
+                }
+            }
+            self.convert(pool, &mut code, source_type, Some(param));
+        }
+
+        let arg_units = params.iter().map(|param| param.unit_size()).sum::<usize>();
+        let method = pool.member(self, class_name, name, &descriptor.to_string());
+        match *kind {
+            REF_INVOKE_STATIC => code.op_u16(Opcode::Invokestatic, method),
+            REF_INVOKE_VIRTUAL => code.op_u16(Opcode::Invokevirtual, method),
+            REF_INVOKE_INTERFACE => {
+                code.op_u16(Opcode::Invokeinterface, method);
+                code.bytes.extend([arg_units as u8, 0]);
+            }
+            _ => code.op_u16(Opcode::Invokespecial, method),
+        }
+        let result = match *kind {
+            REF_NEW_INVOKE_SPECIAL => Some(&receiver),
+            _ => descriptor.return_type.as_deref(),
+        };
+        if let Some(result) = result {
+            self.convert(pool, &mut code, result, method_type.return_type.as_deref());
+        }
+        code.op(return_op(method_type.return_type.as_deref()));
+        code.max_stack = 2 + arg_units as u16 + 2;
+        code
+    }
+    /// Converts the value on top of the stack from one type to another: primitives are widened,
+    /// boxed or unboxed, and references are cast. A value converted to `None` (void) is dropped.
+    fn convert(&mut self, pool: &mut ConstantPool, code: &mut Code, from: &FieldDescriptor, to: Option<&FieldDescriptor>) {
+        let Some(to) = to else {
+            code.op(if from.unit_size() == 2 { Opcode::Pop2 } else { Opcode::Pop });
+            return;
+        };
+        if from == to {
+            return;
+        }
+        match (box_class(from), box_class(to)) {
+            (Some(_), Some(_)) => {
+                if let Some(op) = widening_op(from, to) {
+                    code.op(op);
+                }
+            }
+            (Some((box_class, _)), None) => {
+                let box_type = FieldDescriptor::ClassRef(box_class.into());
+                let value_of = MethodDescriptor { arg_types: Box::new([from.clone()]), return_type: Some(Box::new(box_type)) };
+                let method = pool.member(self, box_class, "valueOf", &value_of.to_string());
+                code.op_u16(Opcode::Invokestatic, method);
+            }
+            (None, Some((box_class, unbox))) => {
+                let class = pool.class(self, box_class);
+                code.op_u16(Opcode::Checkcast, class);
+                let value = MethodDescriptor { arg_types: Box::new([]), return_type: Some(Box::new(to.clone())) };
+                let method = pool.member(self, box_class, unbox, &value.to_string());
+                code.op_u16(Opcode::Invokevirtual, method);
+            }
+            (None, None) => {
+                let class = match to {
+                    FieldDescriptor::ClassRef(name) if &**name == "java/lang/Object" => return,
+                    FieldDescriptor::ClassRef(name) => pool.class(self, name),
+                    array => pool.class(self, &array.to_string()),
+                };
+                code.op_u16(Opcode::Checkcast, class);
+            }
+        }
+    }
+}
+
+/// The runtime constant pool of a spun class, indices start at 1 like in class files
+#[derive(Debug, Default)]
+struct ConstantPool(Vec<RuntimeConstant>);
+
+impl ConstantPool {
+    fn push(&mut self, constant: RuntimeConstant) -> ConstIndex {
+        self.0.push(constant);
+        self.0.len() as ConstIndex
+    }
+    fn utf8(&mut self, runtime: &mut Runtime, s: &str) -> ConstIndex {
+        let offset = runtime.new_static_utf8(s);
+        self.push(RuntimeConstant(offset))
+    }
+    fn class(&mut self, runtime: &mut Runtime, name: &str) -> ConstIndex {
+        let name = self.utf8(runtime, name);
+        self.push(RuntimeConstant(name as u32))
+    }
+    /// A `Fieldref` or `Methodref`, they look the same at runtime
+    fn member(&mut self, runtime: &mut Runtime, class: &str, name: &str, descriptor: &str) -> ConstIndex {
+        let class = self.class(runtime, class);
+        let name = self.utf8(runtime, name);
+        let descriptor = self.utf8(runtime, descriptor);
+        let name_and_type = self.push(RuntimeConstant::pair(name, descriptor));
+        self.push(RuntimeConstant::pair(class, name_and_type))
+    }
+}
+
+#[derive(Debug, Default)]
+struct Code {
+    bytes: Vec<u8>,
+    max_stack: u16,
+}
+
+impl Code {
+    fn op(&mut self, op: Opcode) {
+        self.bytes.push(op.into());
+    }
+    fn op_u8(&mut self, op: Opcode, operand: u8) {
+        self.bytes.extend([op.into(), operand]);
+    }
+    fn op_u16(&mut self, op: Opcode, operand: u16) {
+        self.op(op);
+        self.bytes.extend(operand.to_be_bytes());
+    }
+}
+
+fn load_op(t: &FieldDescriptor) -> Opcode {
+    match t {
+        FieldDescriptor::Long => Opcode::Lload,
+        FieldDescriptor::Float => Opcode::Fload,
+        FieldDescriptor::Double => Opcode::Dload,
+        FieldDescriptor::ClassRef(_) |
+        FieldDescriptor::ArrRef(_) => Opcode::Aload,
+        _ => Opcode::Iload,
+    }
+}
+fn return_op(t: Option<&FieldDescriptor>) -> Opcode {
+    match t {
+        None => Opcode::Return,
+        Some(FieldDescriptor::Long) => Opcode::Lreturn,
+        Some(FieldDescriptor::Float) => Opcode::Freturn,
+        Some(FieldDescriptor::Double) => Opcode::Dreturn,
+        Some(FieldDescriptor::ClassRef(_) | FieldDescriptor::ArrRef(_)) => Opcode::Areturn,
+        Some(_) => Opcode::Ireturn,
+    }
+}
+/// The wrapper class of a primitive type and the name of its unboxing method
+fn box_class(t: &FieldDescriptor) -> Option<(&'static str, &'static str)> {
+    Some(match t {
+        FieldDescriptor::Boolean => ("java/lang/Boolean", "booleanValue"),
+        FieldDescriptor::Byte => ("java/lang/Byte", "byteValue"),
+        FieldDescriptor::Char => ("java/lang/Character", "charValue"),
+        FieldDescriptor::Short => ("java/lang/Short", "shortValue"),
+        FieldDescriptor::Int => ("java/lang/Integer", "intValue"),
+        FieldDescriptor::Long => ("java/lang/Long", "longValue"),
+        FieldDescriptor::Float => ("java/lang/Float", "floatValue"),
+        FieldDescriptor::Double => ("java/lang/Double", "doubleValue"),
+        FieldDescriptor::ClassRef(_) |
+        FieldDescriptor::ArrRef(_) => return None,
+    })
+}
+/// The instruction for a widening primitive conversion, `None` if the types share a representation
+fn widening_op(from: &FieldDescriptor, to: &FieldDescriptor) -> Option<Opcode> {
+    use FieldDescriptor::*;
+    match (from, to) {
+        (Long, Float) => Some(Opcode::L2f),
+        (Long, Double) => Some(Opcode::L2d),
+        (Float, Double) => Some(Opcode::F2d),
+        (Long | Float | Double, _) => None,
+        (_, Long) => Some(Opcode::I2l),
+        (_, Float) => Some(Opcode::I2f),
+        (_, Double) => Some(Opcode::I2d),
+        _ => None,
+    }
+}
@@ -0,0 +1,46 @@
+/// Assigns offsets to the fields of a class.
+///
+/// Fields are placed after each other, each aligned to its own size (but no more than 4 bytes, the
+/// alignment of the words that objects are made of). The padding that alignment leaves behind is
+/// remembered and filled by smaller fields placed later, so placing fields from largest to smallest
+/// packs them tightly even after an unevenly sized superclass.
+#[derive(Debug, Clone)]
+pub struct FieldLayout {
+    end: u16,
+    holes: Vec<(u16, u16)>,
+}
+
+impl FieldLayout {
+    pub const fn starting_at(start: u16) -> Self {
+        Self { end: start, holes: Vec::new() }
+    }
+    /// Reserves room for a field and returns its offset
+    pub fn place(&mut self, size: u16) -> u16 {
+        let align = size.clamp(1, 4);
+        let aligned = |offset: u16| (offset + align - 1) & !(align - 1);
+
+        let hole = self.holes.iter().position(|&(start, end)| aligned(start) + size <= end);
+        if let Some(i) = hole {
+            let (start, end) = self.holes.remove(i);
+            let offset = aligned(start);
+            if start < offset {
+                self.holes.push((start, offset));
+            }
+            if offset + size < end {
+                self.holes.push((offset + size, end));
+            }
+            return offset;
+        }
+
+        let offset = aligned(self.end);
+        if self.end < offset {
+            self.holes.push((self.end, offset));
+        }
+        self.end = offset + size;
+        offset
+    }
+    /// The offset right after the last field
+    pub const fn end(&self) -> u16 {
+        self.end
+    }
+}
@@ -0,0 +1,125 @@
+//! Limits on the resources a run may use, for running code that is not trusted.
+//!
+//! The heap size and the call depth are limited inside Java, reaching them throws
+//! `OutOfMemoryError` or `StackOverflowError` which the code can catch. Running out of fuel or
+//! time stops the run with [`RtError::LimitReached`] instead, between two instructions, and a run
+//! that stopped in its own frames rather than in code called back from native code can continue
+//! with [`RuntimeCtx::resume`] once the limit is raised.
+
+use std::{fmt::{self, Display}, time::{Duration, Instant}};
+
+use super::{InitState, Result, ReturnCategory, RtError, RuntimeCtx};
+
+/// Default for [`Runtime::with_max_call_depth`](super::Runtime::with_max_call_depth)
+pub(super) const DEFAULT_MAX_CALL_DEPTH: usize = 4096;
+/// Runs for native code that can be nested, see [`RuntimeCtx::call_depth_exceeded`]
+const MAX_NESTED_RUNS: usize = 512;
+/// Instructions executed between two looks at the clock
+const DEADLINE_CHECK_INTERVAL: u64 = 1024;
+
+/// A limit that stops a run when reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// All the fuel was used up, every instruction takes one unit
+    Fuel,
+    /// The deadline passed
+    Deadline,
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Limit::Fuel => "out of fuel",
+            Limit::Deadline => "deadline passed",
+        })
+    }
+}
+
+/// The limits a runtime gives the contexts it creates
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Limits {
+    pub fuel: Option<u64>,
+    pub time_limit: Option<Duration>,
+    pub max_call_depth: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self { fuel: None, time_limit: None, max_call_depth: DEFAULT_MAX_CALL_DEPTH }
+    }
+}
+
+impl RuntimeCtx<'_> {
+    /// The fuel left, `None` if it is unlimited
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+    /// Sets the number of instructions that can still be executed, `None` for no limit
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+    /// Adds fuel, if the fuel is limited
+    pub fn add_fuel(&mut self, fuel: u64) {
+        if let Some(left) = &mut self.fuel {
+            *left = left.saturating_add(fuel);
+        }
+    }
+    /// Sets the time after which running stops, `None` for no deadline
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+    /// Number of instructions executed so far
+    pub fn instructions_executed(&self) -> u64 {
+        self.instructions
+    }
+    /// Continues the run that stopped with a resumable [`RtError::LimitReached`], returning like
+    /// [`Self::run`].
+    ///
+    /// # Panics
+    /// If the last run did not stop at a limit or cannot be resumed.
+    pub fn resume(&mut self) -> Result<i32> {
+        assert!(std::mem::take(&mut self.suspended), "there is no suspended run to resume");
+        let result = self.run_until(0).and_then(|()| self.run_main());
+        self.finish_run(result)
+    }
+    /// Takes one unit of fuel for the next instruction and looks at the clock now and then
+    #[inline]
+    pub(super) fn consume_fuel(&mut self) -> Result<()> {
+        if self.instructions.is_multiple_of(DEADLINE_CHECK_INTERVAL) && let Some(deadline) = self.deadline && Instant::now() >= deadline {
+            return Err(self.limit_reached(Limit::Deadline));
+        }
+        if let Some(fuel) = &mut self.fuel {
+            if *fuel == 0 {
+                return Err(self.limit_reached(Limit::Fuel));
+            }
+            *fuel -= 1;
+        }
+        self.instructions += 1;
+        Ok(())
+    }
+    /// The error for a reached limit. The run can only be resumed if it stopped in the frames of
+    /// [`Self::run`], not while native code is waiting for a result in between.
+    fn limit_reached(&mut self, limit: Limit) -> RtError {
+        let resumable = self.nested_runs == 0;
+        self.suspended = resumable;
+        RtError::LimitReached { limit, resumable }
+    }
+    /// Whether invoking another method would go over the maximum call depth.
+    ///
+    /// Native code that calls back into Java, like a string concatenation calling `toString`,
+    /// recurses on the native stack too, so the number of such calls inside each other is capped
+    /// as well to turn a runaway recursion through them into a `StackOverflowError`.
+    pub(super) fn call_depth_exceeded(&self) -> bool {
+        self.return_stack.len() >= self.runtime.limits.max_call_depth || self.nested_runs > MAX_NESTED_RUNS
+    }
+    /// Drops the frames above `depth` after an error stopped them, initializers among them are
+    /// left failed
+    pub(super) fn abandon_frames(&mut self, depth: usize) {
+        while self.return_stack.len() > depth {
+            if self.runtime.get_class(self.cur_class).clinit == Some(self.cur_method) {
+                self.finish_initialization(self.cur_class, InitState::Erroneous);
+            }
+            self.do_return(ReturnCategory::Void);
+        }
+    }
+}
@@ -0,0 +1,589 @@
+//! Translating bytecode into the instructions the interpreter runs.
+//!
+//! When the code of a method is added to the runtime, each of its instructions is decoded once into
+//! an [`Instruction`] stored at the position of its opcode: immediates are sign-extended, local
+//! indices widened and branch offsets turned into positions in the code. Instructions that refer to
+//! the constant pool start out [`Instruction::Unlinked`] and are linked the first time they run,
+//! which resolves their class, field or method like the JVMS allows, and are then replaced by one
+//! holding the class id, field offset or method id.
+
+use std::{cmp::Ordering, rc::Rc};
+
+use num_enum::{FromPrimitive, TryFromPrimitive};
+
+use crate::{class::ConstIndex, code::{opcode::Opcode, switch_padding, PrimitiveArrayType}, descriptor::FieldDescriptor};
+
+use super::{bytes::{AsBytes32Aligned, VecU32AsBytes}, resolve::Selector, ReturnCategory, Result, Runtime, RuntimeCtx, Value};
+
+/// An instruction and how many bytes of code it takes
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Decoded {
+    pub length: u16,
+    pub instruction: Instruction,
+}
+
+impl Decoded {
+    /// A byte of code that does not start an instruction
+    const OPERAND: Self = Decoded { length: 1, instruction: Instruction::Operand };
+}
+
+/// How a value is stored in a field or an array element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ValueKind {
+    /// `boolean` or `byte`, sign-extended when loaded
+    Byte,
+    Char,
+    Short,
+    Int,
+    Float,
+    Reference,
+    /// `long` or `double`, which take two stack slots
+    Long,
+}
+
+impl ValueKind {
+    pub(super) fn of(field_type: &FieldDescriptor) -> Self {
+        match field_type {
+            FieldDescriptor::Boolean | FieldDescriptor::Byte => ValueKind::Byte,
+            FieldDescriptor::Char => ValueKind::Char,
+            FieldDescriptor::Short => ValueKind::Short,
+            FieldDescriptor::Int => ValueKind::Int,
+            FieldDescriptor::Float => ValueKind::Float,
+            FieldDescriptor::ClassRef(_) | FieldDescriptor::ArrRef(_) => ValueKind::Reference,
+            FieldDescriptor::Long | FieldDescriptor::Double => ValueKind::Long,
+        }
+    }
+    /// Bytes a field or array element of this kind takes
+    pub const fn size(self) -> u32 {
+        match self {
+            ValueKind::Byte => 1,
+            ValueKind::Char | ValueKind::Short => 2,
+            ValueKind::Int | ValueKind::Float | ValueKind::Reference => 4,
+            ValueKind::Long => 8,
+        }
+    }
+}
+
+/// How a conditional branch compares an `int` with zero or with another `int`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Condition {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Gt,
+    Le,
+}
+
+impl Condition {
+    pub fn holds(self, a: i32, b: i32) -> bool {
+        match self {
+            Condition::Eq => a == b,
+            Condition::Ne => a != b,
+            Condition::Lt => a < b,
+            Condition::Ge => a >= b,
+            Condition::Gt => a > b,
+            Condition::Le => a <= b,
+        }
+    }
+}
+
+/// The type of the operands of an arithmetic instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum NumberKind {
+    Int,
+    Long,
+    Float,
+    Double,
+}
+
+/// The operation of an arithmetic instruction, the shifts and bitwise ones only exist for `int`
+/// and `long`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    /// Shifts take an `int` distance, also for a `long`
+    Shl,
+    Shr,
+    Ushr,
+    And,
+    Or,
+    Xor,
+}
+
+/// What the interpreter executes, positions are indices into the code of the runtime
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Instruction {
+    /// An operand byte of the instruction before, or padding after a method. Valid code never
+    /// jumps here.
+    Operand,
+    Nop,
+    /// Pushes a category 1 constant: `aconst_null`, `iconst_<i>`, `fconst_<f>`, `bipush`, `sipush`
+    /// and linked `ldc`
+    Push(Value),
+    /// `lconst_<l>`, `dconst_<d>` and linked `ldc2_w`
+    Push2(Value, Value),
+    Load(u16),
+    Load2(u16),
+    Store(u16),
+    Store2(u16),
+    ArrayLoad(ValueKind),
+    ArrayStore(ValueKind),
+    Pop,
+    Pop2,
+    Dup,
+    DupX1,
+    DupX2,
+    Dup2,
+    Dup2X1,
+    Dup2X2,
+    Swap,
+    Arith(NumberKind, ArithOp),
+    Neg(NumberKind),
+    /// `i2l`, `f2d` and the other conversions between numbers, from and to
+    Convert(NumberKind, NumberKind),
+    /// `i2b`, `i2c` and `i2s`
+    Narrow(ValueKind),
+    Iinc(u16, i16),
+    Lcmp,
+    /// `fcmpl` and `fcmpg`, with what a NaN compares as
+    Fcmp(Ordering),
+    Dcmp(Ordering),
+    /// Compares an `int` or a reference with zero or null
+    If(Condition, u32),
+    /// Compares two `int`s or references
+    IfCmp(Condition, u32),
+    /// `goto` and `goto_w`
+    Goto(u32),
+    /// `tableswitch` and `lookupswitch`, with the targets in the [`Switch`] at this index
+    Switch(u32),
+    /// `jsr` and `jsr_w`, which push the position of the next instruction as a `returnAddress`
+    Jsr(u32),
+    /// Jumps to the `returnAddress` in a local
+    Ret(u16),
+    Return(ReturnCategory),
+    Getstatic { class: u32, offset: u16, kind: ValueKind },
+    Putstatic { class: u32, offset: u16, kind: ValueKind },
+    Getfield { offset: u16, kind: ValueKind },
+    Putfield { offset: u16, kind: ValueKind },
+    /// `invokevirtual` and `invokeinterface`, dispatched on the receiver with the
+    /// [`VirtualCall`] at `site`
+    InvokeVirtual { site: u32, arg_size: u16 },
+    InvokeSpecial { class: u32, method: u16, arg_size: u16 },
+    InvokeStatic { class: u32, method: u16 },
+    Invokedynamic(ConstIndex),
+    New(u32),
+    /// `newarray` and `anewarray`, with the class of the array
+    NewArray(u32),
+    /// `multianewarray`, with the class of the outermost array and how many of its dimensions
+    /// are created
+    MultiNewArray { class: u32, dimensions: u8 },
+    Arraylength,
+    Athrow,
+    Checkcast(u32),
+    Instanceof(u32),
+    Monitorenter,
+    Monitorexit,
+    /// An instruction that is linked the first time it runs, with its constant pool index or, for
+    /// `newarray`, its array type
+    Unlinked(Opcode, u16),
+    /// `breakpoint`, `impdep1`, `impdep2` and opcodes without an instruction
+    Reserved,
+}
+
+/// The method an `invokevirtual` or `invokeinterface` calls, with the last receiver class it saw
+#[derive(Debug, Clone)]
+pub(super) struct VirtualCall {
+    selector: Rc<Selector>,
+    /// The class of the last receiver and the class and id of the method it dispatched to
+    cached: Option<(u32, u32, u16)>,
+}
+
+/// Where a `tableswitch` or `lookupswitch` jumps, positions in the code of the runtime
+#[derive(Debug, Clone)]
+pub(super) enum Switch {
+    /// `tableswitch`, with a target for each key from `low` on
+    Table { default: u32, low: i32, targets: Box<[u32]> },
+    /// `lookupswitch`, with the keys in ascending order
+    Lookup { default: u32, keys: Box<[i32]>, targets: Box<[u32]> },
+}
+
+impl Switch {
+    /// Where the switch jumps for `key`
+    pub(super) fn target(&self, key: i32) -> u32 {
+        match self {
+            Switch::Table { default, low, targets } => {
+                let index = usize::try_from(key as i64 - *low as i64).ok();
+                index.and_then(|index| targets.get(index)).copied().unwrap_or(*default)
+            }
+            Switch::Lookup { default, keys, targets } => keys.binary_search(&key).map_or(*default, |index| targets[index]),
+        }
+    }
+    #[cfg(feature = "jit")]
+    pub(super) fn default(&self) -> u32 {
+        match self {
+            Switch::Table { default, .. } | Switch::Lookup { default, .. } => *default,
+        }
+    }
+    /// The keys with a target other than the default, and their targets
+    #[cfg(feature = "jit")]
+    pub(super) fn cases(&self) -> Box<dyn Iterator<Item = (i32, u32)> + '_> {
+        match self {
+            Switch::Table { low, targets, .. } => Box::new((*low..).zip(targets.iter().copied())),
+            Switch::Lookup { keys, targets, .. } => Box::new(keys.iter().copied().zip(targets.iter().copied())),
+        }
+    }
+}
+
+/// Decodes the code of a method that starts at `location` in the code of the runtime, adding the
+/// targets of its switches to `switches`. Bytes after an instruction that does not fit in the code
+/// are left as operands.
+pub(super) fn translate(code: &[u8], location: usize, switches: &mut Vec<Switch>) -> Vec<Decoded> {
+    let mut decoded = vec![Decoded::OPERAND; code.len()];
+    let mut pc = 0;
+    while pc < code.len() {
+        let Some((length, instruction)) = decode(code, pc, location, switches) else { break };
+        decoded[pc] = Decoded { length, instruction };
+        pc += length as usize;
+    }
+    decoded
+}
+
+/// Decodes the instruction at `pc`, `None` if its operands go past the end of the code
+fn decode(code: &[u8], pc: usize, location: usize, switches: &mut Vec<Switch>) -> Option<(u16, Instruction)> {
+    use Instruction::*;
+    let u8_at = |i: usize| code.get(pc + i).copied();
+    let u16_at = |i: usize| Some(u16::from_be_bytes([u8_at(i)?, u8_at(i + 1)?]));
+    let i32_at = |i: usize| Some(i32::from_be_bytes([u8_at(i)?, u8_at(i + 1)?, u8_at(i + 2)?, u8_at(i + 3)?]));
+    let target = |offset: i32| (location + pc).wrapping_add(offset as isize as usize) as u32;
+    let branch = |make: fn(u32) -> Instruction| Some((3, make(target(u16_at(1)? as i16 as i32))));
+
+    let opcode = Opcode::from_primitive(code[pc]);
+    Some(match opcode {
+        Opcode::Nop => (1, Nop),
+        Opcode::AconstNull => (1, Push(Value::NULL)),
+        Opcode::IconstM1 => (1, Push(Value::from(-1))),
+        Opcode::Iconst0 => (1, Push(Value::from(0))),
+        Opcode::Iconst1 => (1, Push(Value::from(1))),
+        Opcode::Iconst2 => (1, Push(Value::from(2))),
+        Opcode::Iconst3 => (1, Push(Value::from(3))),
+        Opcode::Iconst4 => (1, Push(Value::from(4))),
+        Opcode::Iconst5 => (1, Push(Value::from(5))),
+        Opcode::Lconst0 => (1, Push2(Value(0), Value(0))),
+        Opcode::Lconst1 => {
+            let (v1, v2) = super::i64_into_values(1);
+            (1, Push2(v1, v2))
+        }
+        Opcode::Fconst0 => (1, Push(Value::from(0.))),
+        Opcode::Fconst1 => (1, Push(Value::from(1.))),
+        Opcode::Fconst2 => (1, Push(Value::from(2.))),
+        Opcode::Dconst0 => (1, Push2(Value(0), Value(0))),
+        Opcode::Dconst1 => {
+            let (v1, v2) = super::f64_into_values(1.);
+            (1, Push2(v1, v2))
+        }
+        Opcode::Bipush => (2, Push(Value::from(u8_at(1)? as i8))),
+        Opcode::Sipush => (3, Push(Value::from(u16_at(1)? as i16))),
+        Opcode::Ldc => (2, Unlinked(opcode, u8_at(1)? as u16)),
+        Opcode::LdcW | Opcode::Ldc2W => (3, Unlinked(opcode, u16_at(1)?)),
+        Opcode::Iload | Opcode::Fload | Opcode::Aload => (2, Load(u8_at(1)? as u16)),
+        Opcode::Lload | Opcode::Dload => (2, Load2(u8_at(1)? as u16)),
+        Opcode::Iload0 | Opcode::Fload0 | Opcode::Aload0 => (1, Load(0)),
+        Opcode::Iload1 | Opcode::Fload1 | Opcode::Aload1 => (1, Load(1)),
+        Opcode::Iload2 | Opcode::Fload2 | Opcode::Aload2 => (1, Load(2)),
+        Opcode::Iload3 | Opcode::Fload3 | Opcode::Aload3 => (1, Load(3)),
+        Opcode::Lload0 | Opcode::Dload0 => (1, Load2(0)),
+        Opcode::Lload1 | Opcode::Dload1 => (1, Load2(1)),
+        Opcode::Lload2 | Opcode::Dload2 => (1, Load2(2)),
+        Opcode::Lload3 | Opcode::Dload3 => (1, Load2(3)),
+        Opcode::Iaload => (1, ArrayLoad(ValueKind::Int)),
+        Opcode::Laload | Opcode::Daload => (1, ArrayLoad(ValueKind::Long)),
+        Opcode::Faload => (1, ArrayLoad(ValueKind::Float)),
+        Opcode::Aaload => (1, ArrayLoad(ValueKind::Reference)),
+        Opcode::Baload => (1, ArrayLoad(ValueKind::Byte)),
+        Opcode::Caload => (1, ArrayLoad(ValueKind::Char)),
+        Opcode::Saload => (1, ArrayLoad(ValueKind::Short)),
+        Opcode::Istore | Opcode::Fstore | Opcode::Astore => (2, Store(u8_at(1)? as u16)),
+        Opcode::Lstore | Opcode::Dstore => (2, Store2(u8_at(1)? as u16)),
+        Opcode::Istore0 | Opcode::Fstore0 | Opcode::Astore0 => (1, Store(0)),
+        Opcode::Istore1 | Opcode::Fstore1 | Opcode::Astore1 => (1, Store(1)),
+        Opcode::Istore2 | Opcode::Fstore2 | Opcode::Astore2 => (1, Store(2)),
+        Opcode::Istore3 | Opcode::Fstore3 | Opcode::Astore3 => (1, Store(3)),
+        Opcode::Lstore0 | Opcode::Dstore0 => (1, Store2(0)),
+        Opcode::Lstore1 | Opcode::Dstore1 => (1, Store2(1)),
+        Opcode::Lstore2 | Opcode::Dstore2 => (1, Store2(2)),
+        Opcode::Lstore3 | Opcode::Dstore3 => (1, Store2(3)),
+        Opcode::Iastore => (1, ArrayStore(ValueKind::Int)),
+        Opcode::Lastore | Opcode::Dastore => (1, ArrayStore(ValueKind::Long)),
+        Opcode::Fastore => (1, ArrayStore(ValueKind::Float)),
+        Opcode::Aastore => (1, ArrayStore(ValueKind::Reference)),
+        Opcode::Bastore => (1, ArrayStore(ValueKind::Byte)),
+        Opcode::Castore => (1, ArrayStore(ValueKind::Char)),
+        Opcode::Sastore => (1, ArrayStore(ValueKind::Short)),
+        Opcode::Pop => (1, Pop),
+        Opcode::Pop2 => (1, Pop2),
+        Opcode::Dup => (1, Dup),
+        Opcode::DupX1 => (1, DupX1),
+        Opcode::DupX2 => (1, DupX2),
+        Opcode::Dup2 => (1, Dup2),
+        Opcode::Dup2X1 => (1, Dup2X1),
+        Opcode::Dup2X2 => (1, Dup2X2),
+        Opcode::Swap => (1, Swap),
+        Opcode::Iadd => (1, Arith(NumberKind::Int, ArithOp::Add)),
+        Opcode::Isub => (1, Arith(NumberKind::Int, ArithOp::Sub)),
+        Opcode::Imul => (1, Arith(NumberKind::Int, ArithOp::Mul)),
+        Opcode::Idiv => (1, Arith(NumberKind::Int, ArithOp::Div)),
+        Opcode::Irem => (1, Arith(NumberKind::Int, ArithOp::Rem)),
+        Opcode::Ladd => (1, Arith(NumberKind::Long, ArithOp::Add)),
+        Opcode::Lsub => (1, Arith(NumberKind::Long, ArithOp::Sub)),
+        Opcode::Lmul => (1, Arith(NumberKind::Long, ArithOp::Mul)),
+        Opcode::Ldiv => (1, Arith(NumberKind::Long, ArithOp::Div)),
+        Opcode::Lrem => (1, Arith(NumberKind::Long, ArithOp::Rem)),
+        Opcode::Fadd => (1, Arith(NumberKind::Float, ArithOp::Add)),
+        Opcode::Fsub => (1, Arith(NumberKind::Float, ArithOp::Sub)),
+        Opcode::Fmul => (1, Arith(NumberKind::Float, ArithOp::Mul)),
+        Opcode::Fdiv => (1, Arith(NumberKind::Float, ArithOp::Div)),
+        Opcode::Frem => (1, Arith(NumberKind::Float, ArithOp::Rem)),
+        Opcode::Dadd => (1, Arith(NumberKind::Double, ArithOp::Add)),
+        Opcode::Dsub => (1, Arith(NumberKind::Double, ArithOp::Sub)),
+        Opcode::Dmul => (1, Arith(NumberKind::Double, ArithOp::Mul)),
+        Opcode::Ddiv => (1, Arith(NumberKind::Double, ArithOp::Div)),
+        Opcode::Drem => (1, Arith(NumberKind::Double, ArithOp::Rem)),
+        Opcode::Ishl => (1, Arith(NumberKind::Int, ArithOp::Shl)),
+        Opcode::Ishr => (1, Arith(NumberKind::Int, ArithOp::Shr)),
+        Opcode::Iushr => (1, Arith(NumberKind::Int, ArithOp::Ushr)),
+        Opcode::Iand => (1, Arith(NumberKind::Int, ArithOp::And)),
+        Opcode::Ior => (1, Arith(NumberKind::Int, ArithOp::Or)),
+        Opcode::Ixor => (1, Arith(NumberKind::Int, ArithOp::Xor)),
+        Opcode::Lshl => (1, Arith(NumberKind::Long, ArithOp::Shl)),
+        Opcode::Lshr => (1, Arith(NumberKind::Long, ArithOp::Shr)),
+        Opcode::Lushr => (1, Arith(NumberKind::Long, ArithOp::Ushr)),
+        Opcode::Land => (1, Arith(NumberKind::Long, ArithOp::And)),
+        Opcode::Lor => (1, Arith(NumberKind::Long, ArithOp::Or)),
+        Opcode::Lxor => (1, Arith(NumberKind::Long, ArithOp::Xor)),
+        Opcode::Ineg => (1, Neg(NumberKind::Int)),
+        Opcode::Lneg => (1, Neg(NumberKind::Long)),
+        Opcode::Fneg => (1, Neg(NumberKind::Float)),
+        Opcode::Dneg => (1, Neg(NumberKind::Double)),
+        Opcode::I2l => (1, Convert(NumberKind::Int, NumberKind::Long)),
+        Opcode::I2f => (1, Convert(NumberKind::Int, NumberKind::Float)),
+        Opcode::I2d => (1, Convert(NumberKind::Int, NumberKind::Double)),
+        Opcode::L2i => (1, Convert(NumberKind::Long, NumberKind::Int)),
+        Opcode::L2f => (1, Convert(NumberKind::Long, NumberKind::Float)),
+        Opcode::L2d => (1, Convert(NumberKind::Long, NumberKind::Double)),
+        Opcode::F2i => (1, Convert(NumberKind::Float, NumberKind::Int)),
+        Opcode::F2l => (1, Convert(NumberKind::Float, NumberKind::Long)),
+        Opcode::F2d => (1, Convert(NumberKind::Float, NumberKind::Double)),
+        Opcode::D2i => (1, Convert(NumberKind::Double, NumberKind::Int)),
+        Opcode::D2l => (1, Convert(NumberKind::Double, NumberKind::Long)),
+        Opcode::D2f => (1, Convert(NumberKind::Double, NumberKind::Float)),
+        Opcode::I2b => (1, Narrow(ValueKind::Byte)),
+        Opcode::I2c => (1, Narrow(ValueKind::Char)),
+        Opcode::I2s => (1, Narrow(ValueKind::Short)),
+        Opcode::Iinc => (3, Iinc(u8_at(1)? as u16, u8_at(2)? as i8 as i16)),
+        Opcode::Lcmp => (1, Lcmp),
+        Opcode::Fcmpl => (1, Fcmp(Ordering::Less)),
+        Opcode::Fcmpg => (1, Fcmp(Ordering::Greater)),
+        Opcode::Dcmpl => (1, Dcmp(Ordering::Less)),
+        Opcode::Dcmpg => (1, Dcmp(Ordering::Greater)),
+        Opcode::Ifeq | Opcode::Ifnull => branch(|target| If(Condition::Eq, target))?,
+        Opcode::Ifne | Opcode::Ifnonnull => branch(|target| If(Condition::Ne, target))?,
+        Opcode::Iflt => branch(|target| If(Condition::Lt, target))?,
+        Opcode::Ifge => branch(|target| If(Condition::Ge, target))?,
+        Opcode::Ifgt => branch(|target| If(Condition::Gt, target))?,
+        Opcode::Ifle => branch(|target| If(Condition::Le, target))?,
+        Opcode::IfIcmpeq | Opcode::IfAcmpeq => branch(|target| IfCmp(Condition::Eq, target))?,
+        Opcode::IfIcmpne | Opcode::IfAcmpne => branch(|target| IfCmp(Condition::Ne, target))?,
+        Opcode::IfIcmplt => branch(|target| IfCmp(Condition::Lt, target))?,
+        Opcode::IfIcmpge => branch(|target| IfCmp(Condition::Ge, target))?,
+        Opcode::IfIcmpgt => branch(|target| IfCmp(Condition::Gt, target))?,
+        Opcode::IfIcmple => branch(|target| IfCmp(Condition::Le, target))?,
+        Opcode::Goto => branch(Goto)?,
+        Opcode::GotoW => (5, Goto(target(i32_at(1)?))),
+        Opcode::Jsr => branch(Jsr)?,
+        Opcode::JsrW => (5, Jsr(target(i32_at(1)?))),
+        Opcode::Ret => (2, Ret(u8_at(1)? as u16)),
+        Opcode::Tableswitch | Opcode::Lookupswitch => {
+            let operands = 1 + switch_padding(pc);
+            let default = target(i32_at(operands)?);
+            let (length, switch) = match opcode {
+                Opcode::Tableswitch => {
+                    let low = i32_at(operands + 4)?;
+                    let count = usize::try_from(i32_at(operands + 8)? as i64 - low as i64 + 1).ok()?;
+                    let targets = (0..count).map(|n| Some(target(i32_at(operands + 12 + 4 * n)?))).collect::<Option<_>>()?;
+                    (operands + 12 + 4 * count, self::Switch::Table { default, low, targets })
+                }
+                _ => {
+                    let count = usize::try_from(i32_at(operands + 4)?).ok()?;
+                    let pairs = (0..count).map(|n| Some((i32_at(operands + 8 + 8 * n)?, target(i32_at(operands + 12 + 8 * n)?))));
+                    let (keys, targets): (Vec<_>, Vec<_>) = pairs.collect::<Option<Vec<_>>>()?.into_iter().unzip();
+                    let length = operands + 8 + 8 * count;
+                    // the class file format requires the keys in ascending order, the lookup relies on it
+                    if !keys.is_sorted_by(|a, b| a < b) {
+                        return Some((u16::try_from(length).ok()?, Reserved));
+                    }
+                    (length, self::Switch::Lookup { default, keys: keys.into(), targets: targets.into() })
+                }
+            };
+            switches.push(switch);
+            (u16::try_from(length).ok()?, Switch(switches.len() as u32 - 1))
+        }
+        // a local index of two bytes, and a two byte increment for `iinc`
+        Opcode::Wide => {
+            let index = u16_at(2)?;
+            match Opcode::from_primitive(u8_at(1)?) {
+                Opcode::Iload | Opcode::Fload | Opcode::Aload => (4, Load(index)),
+                Opcode::Lload | Opcode::Dload => (4, Load2(index)),
+                Opcode::Istore | Opcode::Fstore | Opcode::Astore => (4, Store(index)),
+                Opcode::Lstore | Opcode::Dstore => (4, Store2(index)),
+                Opcode::Ret => (4, Ret(index)),
+                Opcode::Iinc => (6, Iinc(index, u16_at(4)? as i16)),
+                _ => (2, Reserved),
+            }
+        }
+        Opcode::Ireturn | Opcode::Freturn | Opcode::Areturn => (1, Return(ReturnCategory::Cat1)),
+        Opcode::Lreturn | Opcode::Dreturn => (1, Return(ReturnCategory::Cat2)),
+        Opcode::Return => (1, Return(ReturnCategory::Void)),
+        Opcode::Getstatic |
+        Opcode::Putstatic |
+        Opcode::Getfield |
+        Opcode::Putfield |
+        Opcode::Invokevirtual |
+        Opcode::Invokespecial |
+        Opcode::Invokestatic |
+        Opcode::New |
+        Opcode::Anewarray |
+        Opcode::Checkcast |
+        Opcode::Instanceof => (3, Unlinked(opcode, u16_at(1)?)),
+        // the argument count and a zero byte, both redundant with the descriptor
+        Opcode::Invokeinterface => (5, Unlinked(opcode, u16_at(1)?)),
+        Opcode::Invokedynamic => (5, Invokedynamic(u16_at(1)?)),
+        Opcode::Newarray => (2, Unlinked(opcode, u8_at(1)? as u16)),
+        Opcode::Arraylength => (1, Arraylength),
+        Opcode::Athrow => (1, Athrow),
+        Opcode::Monitorenter => (1, Monitorenter),
+        Opcode::Monitorexit => (1, Monitorexit),
+        Opcode::Breakpoint |
+        Opcode::ReservedFuture |
+        Opcode::Impdep1 |
+        Opcode::Impdep2 => (1, Reserved),
+        // the number of dimensions is read from the last byte when it is linked
+        Opcode::Multianewarray => (4, Unlinked(opcode, u16_at(1)?)),
+    })
+}
+
+impl Runtime {
+    /// Appends the code of a method to the code of the runtime and decodes it, returning where it
+    /// starts
+    pub(super) fn add_code(&mut self, code: &[u8]) -> usize {
+        let location = self.code.as_bytes_32aligned().len();
+        self.code.extend_from_bytes(code);
+        self.instructions.extend(translate(code, location, &mut self.switches));
+        self.instructions.resize(self.code.len() * 4, Decoded::OPERAND);
+        location
+    }
+}
+
+impl RuntimeCtx<'_> {
+    /// Resolves what an [`Instruction::Unlinked`] of the current method refers to and returns the
+    /// instruction to run instead.
+    ///
+    /// If resolution fails with an error the program can catch, like a `NoSuchFieldError`, it is
+    /// thrown and `None` is returned. The instruction stays unlinked then.
+    pub(super) fn link(&mut self, opcode: Opcode, operand: u16) -> Result<Option<Instruction>> {
+        Ok(Some(match opcode {
+            Opcode::Ldc | Opcode::LdcW if self.is_class_constant(operand) => {
+                let class = self.resolve_class(operand)?;
+                Instruction::Push(Value::new_ref_static(self.runtime.get_class(class).class_object))
+            }
+            Opcode::Ldc | Opcode::LdcW => Instruction::Push(self.read_constant(operand).value()),
+            Opcode::Ldc2W => {
+                let (v1, v2) = self.read_constant(operand).wide_value(self.read_constant(operand + 1));
+                Instruction::Push2(v1, v2)
+            }
+            Opcode::Getstatic | Opcode::Putstatic | Opcode::Getfield | Opcode::Putfield => {
+                let Some((class, offset, kind)) = self.resolve_field_ref(operand)? else { return Ok(None) };
+                match opcode {
+                    Opcode::Getstatic => Instruction::Getstatic { class, offset, kind },
+                    Opcode::Putstatic => Instruction::Putstatic { class, offset, kind },
+                    Opcode::Getfield => Instruction::Getfield { offset, kind },
+                    _ => Instruction::Putfield { offset, kind },
+                }
+            }
+            Opcode::Invokevirtual | Opcode::Invokeinterface => {
+                let Some(selector) = self.resolve_selector(operand, opcode == Opcode::Invokeinterface)? else { return Ok(None) };
+                let arg_size = selector.arg_size;
+                let site = self.runtime.virtual_calls.len() as u32;
+                self.runtime.virtual_calls.push(VirtualCall { selector, cached: None });
+                Instruction::InvokeVirtual { site, arg_size }
+            }
+            Opcode::Invokespecial | Opcode::Invokestatic => {
+                let Some((class, method, arg_size)) = self.resolve_method_ref(operand)? else { return Ok(None) };
+                match opcode {
+                    Opcode::Invokespecial => Instruction::InvokeSpecial { class, method, arg_size },
+                    _ => Instruction::InvokeStatic { class, method },
+                }
+            }
+            Opcode::New => Instruction::New(self.resolve_class(operand)?),
+            Opcode::Newarray => {
+                let array_type = PrimitiveArrayType::try_from_primitive(operand as u8).unwrap();
+                Instruction::NewArray(self.runtime.load_class(array_type.array_class_name())?)
+            }
+            Opcode::Anewarray => {
+                let component = self.resolve_class(operand)?;
+                Instruction::NewArray(self.runtime.load_array_class(component)?)
+            }
+            Opcode::Multianewarray => {
+                // the instruction ends with its dimensions, the pc is right after it
+                let dimensions = self.runtime.code.as_bytes_32aligned()[self.pc - 1];
+                Instruction::MultiNewArray { class: self.resolve_class(operand)?, dimensions }
+            }
+            Opcode::Checkcast => Instruction::Checkcast(self.resolve_class(operand)?),
+            Opcode::Instanceof => Instruction::Instanceof(self.resolve_class(operand)?),
+            _ => unreachable!("{} is not linked", opcode.mnemonic()),
+        }))
+    }
+    /// Selects the method a virtual call runs for a receiver of class `receiver`.
+    ///
+    /// If the class has no such method, an `AbstractMethodError` is thrown and `None` is returned.
+    pub(super) fn dispatch(&mut self, site: u32, receiver: u32) -> Result<Option<(u32, u16)>> {
+        match self.runtime.select(site, receiver) {
+            Some(target) => Ok(Some(target)),
+            None => {
+                let name = &self.runtime.virtual_calls[site as usize].selector.name;
+                let message = format!("{}.{}", self.runtime.get_class(receiver).name.replace('/', "."), name);
+                self.throw_new("java/lang/AbstractMethodError", Some(message))?;
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Runtime {
+    /// Selects the method a virtual call runs for a receiver of class `receiver`, `None` if the
+    /// class has no such method
+    pub(super) fn select(&mut self, site: u32, receiver: u32) -> Option<(u32, u16)> {
+        let call = &self.virtual_calls[site as usize];
+        if let Some((cached, class, method)) = call.cached && cached == receiver {
+            return Some((class, method));
+        }
+        let (class, method) = self.find_method(receiver, &call.selector.name, &call.selector.descriptor)?;
+        self.virtual_calls[site as usize].cached = Some((receiver, class, method));
+        Some((class, method))
+    }
+}
+
+#[cfg(feature = "jit")]
+impl VirtualCall {
+    pub(super) fn selector(&self) -> &Selector {
+        &self.selector
+    }
+    /// The class of the last receiver and the method it dispatched to
+    pub(super) fn cached(&self) -> Option<(u32, u32, u16)> {
+        self.cached
+    }
+}
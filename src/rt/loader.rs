@@ -0,0 +1,125 @@
+//! Pluggable sources of class files.
+//!
+//! The classes of the runtime itself, like `java/lang/Object`, are always builtin. Every other
+//! class is asked from the [`ClassLoader`] of the runtime, which is a [`ClassPath`] unless the
+//! embedder sets its own with [`Runtime::with_loader`](super::Runtime::with_loader).
+
+use std::{cell::RefCell, fmt, io, rc::Rc};
+
+use super::ClassPath;
+
+/// Finds the class files of classes by name
+pub trait ClassLoader {
+    /// The contents of the class file of the named class, with packages separated by slashes like
+    /// `com/example/Main`. `None` if this loader does not have the class.
+    fn find_class(&mut self, name: &str) -> io::Result<Option<Vec<u8>>>;
+    /// Where [`Self::find_class`] looks for the named class, to tell users when it is not found
+    fn search_locations(&self, name: &str) -> Vec<String> {
+        let _ = name;
+        Vec::new()
+    }
+    /// Delegates to `parent` first and only looks at `self` if the parent does not have a class,
+    /// like class loaders in Java do
+    fn with_parent<P: ClassLoader>(self, parent: P) -> Delegating<P, Self>
+    where
+        Self: Sized,
+    {
+        Delegating { parent, child: self }
+    }
+}
+
+/// Loads classes with a function, like one that reads them from a database
+impl<F: FnMut(&str) -> io::Result<Option<Vec<u8>>>> ClassLoader for F {
+    fn find_class(&mut self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        self(name)
+    }
+}
+
+impl ClassLoader for ClassPath {
+    fn find_class(&mut self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        ClassPath::find_class(self, name)
+    }
+    fn search_locations(&self, name: &str) -> Vec<String> {
+        ClassPath::search_locations(self, name)
+    }
+}
+
+/// See [`ClassLoader::with_parent`]
+#[derive(Debug, Clone)]
+pub struct Delegating<P, C> {
+    parent: P,
+    child: C,
+}
+
+impl<P: ClassLoader, C: ClassLoader> ClassLoader for Delegating<P, C> {
+    fn find_class(&mut self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        match self.parent.find_class(name)? {
+            Some(class) => Ok(Some(class)),
+            None => self.child.find_class(name),
+        }
+    }
+    fn search_locations(&self, name: &str) -> Vec<String> {
+        let mut locations = self.parent.search_locations(name);
+        locations.extend(self.child.search_locations(name));
+        locations
+    }
+}
+
+/// Asks a list of loaders in order, the first one that has a class wins
+#[derive(Default)]
+pub struct ClassLoaderChain {
+    loaders: Vec<Box<dyn ClassLoader>>,
+}
+
+impl ClassLoaderChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Appends a loader that is asked after the ones before it
+    pub fn with(mut self, loader: impl ClassLoader + 'static) -> Self {
+        self.loaders.push(Box::new(loader));
+        self
+    }
+}
+
+impl ClassLoader for ClassLoaderChain {
+    fn find_class(&mut self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        for loader in &mut self.loaders {
+            if let Some(class) = loader.find_class(name)? {
+                return Ok(Some(class));
+            }
+        }
+        Ok(None)
+    }
+    fn search_locations(&self, name: &str) -> Vec<String> {
+        self.loaders.iter().flat_map(|loader| loader.search_locations(name)).collect()
+    }
+}
+
+impl fmt::Debug for ClassLoaderChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClassLoaderChain").field("loaders", &self.loaders.len()).finish()
+    }
+}
+
+/// The loader of a runtime, shared by its clones
+#[derive(Clone)]
+pub(super) struct SharedLoader(Rc<RefCell<dyn ClassLoader>>);
+
+impl SharedLoader {
+    pub fn new(loader: impl ClassLoader + 'static) -> Self {
+        Self(Rc::new(RefCell::new(loader)))
+    }
+    pub fn find_class(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        self.0.borrow_mut().find_class(name)
+    }
+    pub fn search_locations(&self, name: &str) -> Vec<String> {
+        self.0.borrow().search_locations(name)
+    }
+}
+
+impl fmt::Debug for SharedLoader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedLoader")
+    }
+}
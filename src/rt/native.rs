@@ -0,0 +1,328 @@
+//! Java methods implemented in Rust by the embedder.
+//!
+//! Natives are registered by class, name and descriptor with [`Runtime::register_native`]. A class
+//! file method with the `native` flag is bound to the native registered for it, and calling it
+//! before one is registered throws `UnsatisfiedLinkError`. Natives can also be added to builtin
+//! classes, and natives for a class that exists nowhere else make up a class of static methods.
+
+use std::{collections::BTreeMap, fmt, rc::Rc};
+
+use crate::descriptor::{FieldDescriptor, MethodDescriptor};
+
+use super::{f64_into_values, i64_into_values, values_into_f64, values_into_u64, Result, Runtime, RuntimeCtx, RuntimeInfo, Value};
+
+/// Method ids at and above this are indices into [`LoadedClass::natives`](super::LoadedClass)
+pub(super) const NATIVE_METHOD_BASE: u16 = 0x8000;
+
+/// The signature of a native: it gets the arguments, with the receiver first for instance methods,
+/// and returns the value for the method's return type, or [`JValue::Void`].
+///
+/// A native that throws with [`RuntimeCtx::throw_new`] or [`RuntimeCtx::throw`] may return
+/// anything, its return value is dropped.
+pub type NativeFn = dyn Fn(&mut RuntimeCtx, &NativeArgs) -> Result<JValue>;
+
+/// A Java value of any type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JValue {
+    Void,
+    Boolean(bool),
+    Byte(i8),
+    Char(u16),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    /// An object, array or null
+    Reference(Value),
+}
+
+impl From<()> for JValue {
+    fn from((): ()) -> Self {
+        JValue::Void
+    }
+}
+impl From<bool> for JValue {
+    fn from(value: bool) -> Self {
+        JValue::Boolean(value)
+    }
+}
+impl From<i8> for JValue {
+    fn from(value: i8) -> Self {
+        JValue::Byte(value)
+    }
+}
+impl From<u16> for JValue {
+    fn from(value: u16) -> Self {
+        JValue::Char(value)
+    }
+}
+impl From<i16> for JValue {
+    fn from(value: i16) -> Self {
+        JValue::Short(value)
+    }
+}
+impl From<i32> for JValue {
+    fn from(value: i32) -> Self {
+        JValue::Int(value)
+    }
+}
+impl From<i64> for JValue {
+    fn from(value: i64) -> Self {
+        JValue::Long(value)
+    }
+}
+impl From<f32> for JValue {
+    fn from(value: f32) -> Self {
+        JValue::Float(value)
+    }
+}
+impl From<f64> for JValue {
+    fn from(value: f64) -> Self {
+        JValue::Double(value)
+    }
+}
+impl From<Value> for JValue {
+    fn from(value: Value) -> Self {
+        JValue::Reference(value)
+    }
+}
+
+/// The arguments of a call to a native, converted according to the method descriptor.
+///
+/// The getters panic if the argument has a different type, which means the native was registered
+/// with a descriptor it was not written for.
+#[derive(Debug, Clone)]
+pub struct NativeArgs(Vec<JValue>);
+
+impl NativeArgs {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    #[track_caller]
+    pub fn get(&self, i: usize) -> JValue {
+        self.0[i]
+    }
+    /// An `int`, or a `boolean`, `byte`, `char` or `short` widened to one
+    #[track_caller]
+    pub fn int(&self, i: usize) -> i32 {
+        match self.0[i] {
+            JValue::Boolean(value) => value as i32,
+            JValue::Byte(value) => value as i32,
+            JValue::Char(value) => value as i32,
+            JValue::Short(value) => value as i32,
+            JValue::Int(value) => value,
+            value => panic!("argument {i} is {value:?}, not an int"),
+        }
+    }
+    #[track_caller]
+    pub fn boolean(&self, i: usize) -> bool {
+        self.int(i) != 0
+    }
+    #[track_caller]
+    pub fn char(&self, i: usize) -> u16 {
+        self.int(i) as u16
+    }
+    #[track_caller]
+    pub fn long(&self, i: usize) -> i64 {
+        match self.0[i] {
+            JValue::Long(value) => value,
+            value => panic!("argument {i} is {value:?}, not a long"),
+        }
+    }
+    #[track_caller]
+    pub fn float(&self, i: usize) -> f32 {
+        match self.0[i] {
+            JValue::Float(value) => value,
+            value => panic!("argument {i} is {value:?}, not a float"),
+        }
+    }
+    #[track_caller]
+    pub fn double(&self, i: usize) -> f64 {
+        match self.0[i] {
+            JValue::Double(value) => value,
+            value => panic!("argument {i} is {value:?}, not a double"),
+        }
+    }
+    /// An object, array or null
+    #[track_caller]
+    pub fn reference(&self, i: usize) -> Value {
+        match self.0[i] {
+            JValue::Reference(value) => value,
+            value => panic!("argument {i} is {value:?}, not a reference"),
+        }
+    }
+}
+
+/// A native method of a loaded class
+pub(super) struct NativeMethod {
+    pub name: Box<str>,
+    pub descriptor: MethodDescriptor,
+    pub is_static: bool,
+    /// `None` while no native is registered for a `native` method of a class file
+    pub function: Option<Rc<NativeFn>>,
+}
+
+impl fmt::Debug for NativeMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeMethod")
+            .field("name", &self.name)
+            .field("descriptor", &self.descriptor)
+            .field("is_static", &self.is_static)
+            .field("bound", &self.function.is_some())
+            .finish()
+    }
+}
+
+/// Registered natives by class, name and descriptor
+#[derive(Clone, Default)]
+pub(super) struct NativeRegistry(BTreeMap<(Box<str>, Box<str>, MethodDescriptor), Rc<NativeFn>>);
+
+impl NativeRegistry {
+    pub fn get(&self, class: &str, name: &str, descriptor: &MethodDescriptor) -> Option<Rc<NativeFn>> {
+        self.0.get(&(class.into(), name.into(), descriptor.clone())).cloned()
+    }
+    /// The natives registered for a class, as name, descriptor and function
+    pub fn for_class<'a>(&'a self, class: &'a str) -> impl Iterator<Item = (&'a str, &'a MethodDescriptor, &'a Rc<NativeFn>)> {
+        self.0.iter()
+            .filter(move |((native_class, _, _), _)| &**native_class == class)
+            .map(|((_, name, descriptor), function)| (&**name, descriptor, function))
+    }
+    pub fn has_class(&self, class: &str) -> bool {
+        self.for_class(class).next().is_some()
+    }
+}
+
+impl fmt::Debug for NativeRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.keys().map(|(class, name, descriptor)| format!("{class}.{name}{descriptor}"))).finish()
+    }
+}
+
+impl Runtime {
+    /// Registers a Rust function as the implementation of a Java method, given by the binary name
+    /// of its class like `java/lang/Math`, its name and its descriptor like `(D)D`.
+    ///
+    /// Class files only get natives for the methods they declare `native`. Natives added to builtin
+    /// classes replace builtin methods with the same signature, and natives for a class that exists
+    /// nowhere else make up a class of its own. Natives not declared in a class file are static.
+    pub fn register_native(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+        function: impl Fn(&mut RuntimeCtx, &NativeArgs) -> Result<JValue> + 'static,
+    ) -> Result<()> {
+        let descriptor = MethodDescriptor::from_bytes(descriptor.as_bytes())?;
+        let function: Rc<NativeFn> = Rc::new(function);
+        self.natives.0.insert((class.into(), name.into(), descriptor.clone()), function.clone());
+
+        // classes that are already loaded get it right away
+        let Some(&id) = self.class_names.get(class) else { return Ok(()) };
+        let loaded = &mut self.classes[id as usize];
+        let declared = loaded.member_table.get(name, &descriptor.clone().into()).filter(|&id| id >= NATIVE_METHOD_BASE);
+        if let Some(method_id) = declared {
+            let native = &mut loaded.natives[(method_id - NATIVE_METHOD_BASE) as usize];
+            *native = Rc::new(NativeMethod {
+                name: name.into(),
+                descriptor,
+                is_static: native.is_static,
+                function: Some(function),
+            });
+        } else if let RuntimeInfo::Builtin(_) = loaded.runtime_info {
+            self.add_native(id, name, descriptor, true, Some(function));
+        }
+        Ok(())
+    }
+    /// Adds a native method to a class, replacing a method with the same signature
+    pub(super) fn add_native(&mut self, class: u32, name: &str, descriptor: MethodDescriptor, is_static: bool, function: Option<Rc<NativeFn>>) {
+        let class = &mut self.classes[class as usize];
+        let method_id = NATIVE_METHOD_BASE + class.natives.len() as u16;
+        class.member_table.insert(name, descriptor.clone(), method_id);
+        class.natives.push(Rc::new(NativeMethod { name: name.into(), descriptor, is_static, function }));
+    }
+    /// Adds the natives registered for a builtin or made up class to it
+    pub(super) fn add_registered_natives(&mut self, class: u32) {
+        let name = self.classes[class as usize].name.clone();
+        let natives: Vec<_> = self.natives.for_class(&name)
+            .map(|(name, descriptor, function)| (name.to_string(), descriptor.clone(), function.clone()))
+            .collect();
+        for (name, descriptor, function) in natives {
+            self.add_native(class, &name, descriptor, true, Some(function));
+        }
+    }
+}
+
+/// Reads a value of the given type from the slots of the stack starting at `slot`
+pub(super) fn stack_value(stack: &[Value], slot: usize, value_type: &FieldDescriptor) -> JValue {
+    let value = stack[slot];
+    match value_type {
+        FieldDescriptor::Boolean => JValue::Boolean(value.into_u32() != 0),
+        FieldDescriptor::Byte => JValue::Byte(value.into_i8()),
+        FieldDescriptor::Char => JValue::Char(value.into_u16()),
+        FieldDescriptor::Short => JValue::Short(value.into_i16()),
+        FieldDescriptor::Int => JValue::Int(value.into_i32()),
+        FieldDescriptor::Float => JValue::Float(value.into_f32()),
+        FieldDescriptor::Long => JValue::Long(values_into_u64((value, stack[slot + 1])) as i64),
+        FieldDescriptor::Double => JValue::Double(values_into_f64((value, stack[slot + 1]))),
+        FieldDescriptor::ClassRef(_) |
+        FieldDescriptor::ArrRef(_) => JValue::Reference(value),
+    }
+}
+
+impl RuntimeCtx<'_> {
+    /// Calls a native with its arguments on the operand stack, replacing them with the result
+    pub(super) fn call_native(&mut self, native: &NativeMethod) -> Result<()> {
+        let Some(function) = &native.function else {
+            let message = format!("'{}'", native.descriptor.display_type(&native.name));
+            return self.throw_new("java/lang/UnsatisfiedLinkError", Some(message));
+        };
+        let receiver_units = if native.is_static { 0 } else { 1 };
+        let start = self.stack.len() - receiver_units - native.descriptor.arg_types.iter().map(FieldDescriptor::unit_size).sum::<usize>();
+
+        let mut args = Vec::with_capacity(native.descriptor.arg_types.len() + receiver_units);
+        let mut slot = start;
+        if !native.is_static {
+            args.push(JValue::Reference(self.stack[slot]));
+            slot += 1;
+        }
+        for arg_type in &native.descriptor.arg_types {
+            args.push(stack_value(&self.stack, slot, arg_type));
+            slot += arg_type.unit_size();
+        }
+
+        // the arguments stay on the stack during the call so the collector sees them, and an
+        // exception the native throws stops at the frame of its caller, to be thrown from there
+        // once they are popped. Exceptions that Java code the native calls throws and catches
+        // do not get there.
+        let outer_barrier = self.barrier.replace(self.return_stack.len());
+        let result = function(self, &NativeArgs(args));
+        self.barrier = outer_barrier;
+        let result = result?;
+        self.stack.truncate(start);
+        if let Some(exception) = self.pending_exception.take() {
+            return self.throw(exception);
+        }
+        match (native.descriptor.return_type.as_deref(), result) {
+            (None, JValue::Void) => (),
+            (Some(FieldDescriptor::Boolean), JValue::Boolean(value)) => self.push(value),
+            (Some(FieldDescriptor::Byte), JValue::Byte(value)) => self.push(value),
+            (Some(FieldDescriptor::Char), JValue::Char(value)) => self.push(value as i32),
+            (Some(FieldDescriptor::Short), JValue::Short(value)) => self.push(value),
+            (Some(FieldDescriptor::Int), JValue::Int(value)) => self.push(value),
+            (Some(FieldDescriptor::Float), JValue::Float(value)) => self.push(value),
+            (Some(FieldDescriptor::Long), JValue::Long(value)) => self.push2(i64_into_values(value)),
+            (Some(FieldDescriptor::Double), JValue::Double(value)) => self.push2(f64_into_values(value)),
+            (Some(FieldDescriptor::ClassRef(_) | FieldDescriptor::ArrRef(_)), JValue::Reference(value)) => self.push(value),
+            (return_type, result) => panic!(
+                "native {} returned {result:?} instead of {}",
+                native.descriptor.display_type(&native.name),
+                return_type.map_or("void".into(), |t| t.to_string()),
+            ),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+//! Profiling interpreted code.
+//!
+//! A runtime made with [`Runtime::with_profiling`](super::Runtime::with_profiling) counts the
+//! invocations of bytecode methods, the opcodes executed and the objects allocated by class. It
+//! also keeps a call tree per thread and samples it every few instructions, which gives the
+//! instructions spent in each method, with and without its callees, and the stacks for flame
+//! graphs. Counts accumulate over all runs of the runtime into its [`Profile`].
+
+use std::{cmp::Reverse, collections::HashMap, fmt::{self, Display}, io};
+
+use crate::code::opcode::Opcode;
+
+use super::{MethodImpl, RuntimeCtx, bytes::AsBytes32Aligned};
+
+/// What the runs of a profiling runtime did
+#[derive(Debug, Clone)]
+pub struct Profile {
+    sample_interval: u64,
+    instructions: u64,
+    /// Every method invoked so far
+    methods: Vec<MethodEntry>,
+    method_indices: HashMap<(u32, u16), u32>,
+    /// Node 0 is the root above the outermost frames of all threads
+    nodes: Vec<CallNode>,
+    /// Nodes by their parent and method
+    children: HashMap<(u32, u32), u32>,
+    /// The call tree node of each frame, by thread
+    stacks: Vec<Vec<u32>>,
+    opcodes: Box<[u64; 256]>,
+    allocations: Vec<AllocationProfile>,
+    allocation_indices: HashMap<u32, usize>,
+}
+
+#[derive(Debug, Clone)]
+struct MethodEntry {
+    /// `pkg.Class.method`
+    name: Box<str>,
+    descriptor: Box<str>,
+    invocations: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CallNode {
+    parent: u32,
+    method: u32,
+    /// Instructions sampled while this frame was the innermost one
+    instructions: u64,
+}
+
+/// The counts of a method
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodProfile {
+    /// `pkg.Class.method`
+    pub name: Box<str>,
+    pub descriptor: Box<str>,
+    pub invocations: u64,
+    /// Instructions executed in the method itself
+    pub exclusive: u64,
+    /// Instructions executed in the method and the methods it called, with recursive calls
+    /// counted once
+    pub inclusive: u64,
+}
+
+/// The objects and arrays of a class allocated on the heap
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllocationProfile {
+    /// Binary name with dots, or the descriptor for array classes
+    pub class: Box<str>,
+    pub objects: u64,
+    pub bytes: u64,
+}
+
+impl Profile {
+    pub(super) fn new(sample_interval: u64) -> Self {
+        Self {
+            sample_interval: sample_interval.max(1),
+            instructions: 0,
+            methods: Vec::new(),
+            method_indices: HashMap::new(),
+            nodes: vec![CallNode { parent: 0, method: u32::MAX, instructions: 0 }],
+            children: HashMap::new(),
+            stacks: Vec::new(),
+            opcodes: Box::new([0; 256]),
+            allocations: Vec::new(),
+            allocation_indices: HashMap::new(),
+        }
+    }
+    /// Forgets the frames of the threads of the last run
+    pub(super) fn start_run(&mut self) {
+        self.stacks.clear();
+    }
+    /// Instructions executed between two samples of the call stacks
+    pub fn sample_interval(&self) -> u64 {
+        self.sample_interval
+    }
+    /// Instructions executed in all runs
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+    /// The methods that were invoked, the ones that executed the most instructions themselves
+    /// first
+    pub fn methods(&self) -> Vec<MethodProfile> {
+        let totals = self.node_totals();
+        let mut methods: Vec<_> = self.methods.iter().map(|method| MethodProfile {
+            name: method.name.clone(),
+            descriptor: method.descriptor.clone(),
+            invocations: method.invocations,
+            exclusive: 0,
+            inclusive: 0,
+        }).collect();
+        for (i, node) in self.nodes.iter().enumerate().skip(1) {
+            let method = &mut methods[node.method as usize];
+            method.exclusive += node.instructions;
+            // a recursive call is part of the outermost one
+            if !self.ancestors(node.parent).any(|ancestor| ancestor.method == node.method) {
+                method.inclusive += totals[i];
+            }
+        }
+        methods.sort_by(|a, b| b.exclusive.cmp(&a.exclusive).then(b.inclusive.cmp(&a.inclusive)).then(a.name.cmp(&b.name)));
+        methods
+    }
+    /// How many times each opcode was executed, the most frequent first
+    pub fn opcodes(&self) -> Vec<(Opcode, u64)> {
+        let mut opcodes: Vec<_> = (0..=u8::MAX)
+            .filter(|&opcode| self.opcodes[opcode as usize] != 0)
+            .map(|opcode| (Opcode::from(opcode), self.opcodes[opcode as usize]))
+            .collect();
+        opcodes.sort_by_key(|&(_, count)| Reverse(count));
+        opcodes
+    }
+    /// The allocations by class, the most bytes first
+    pub fn allocations(&self) -> Vec<AllocationProfile> {
+        let mut allocations = self.allocations.clone();
+        allocations.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.class.cmp(&b.class)));
+        allocations
+    }
+    /// Writes the sampled stacks in the collapsed format that flame graph tools read, one line of
+    /// frames from the outermost one separated by `;` followed by the number of instructions
+    pub fn write_collapsed(&self, out: &mut impl io::Write) -> io::Result<()> {
+        for (i, node) in self.nodes.iter().enumerate().skip(1) {
+            if node.instructions == 0 {
+                continue;
+            }
+            let mut frames: Vec<_> = self.ancestors(i as u32).map(|node| &*self.methods[node.method as usize].name).collect();
+            frames.reverse();
+            writeln!(out, "{} {}", frames.join(";"), node.instructions)?;
+        }
+        Ok(())
+    }
+    /// The nodes from this one up to the outermost frame
+    fn ancestors(&self, node: u32) -> impl Iterator<Item = &CallNode> {
+        let mut node = node;
+        std::iter::from_fn(move || {
+            let current = &self.nodes[node as usize];
+            (node != 0).then(|| {
+                node = current.parent;
+                current
+            })
+        })
+    }
+    /// The instructions sampled in each node and below it
+    fn node_totals(&self) -> Vec<u64> {
+        let mut totals: Vec<_> = self.nodes.iter().map(|node| node.instructions).collect();
+        // children are created after their parents
+        for (i, node) in self.nodes.iter().enumerate().skip(1).rev() {
+            totals[node.parent as usize] += totals[i];
+        }
+        totals
+    }
+    fn stack(&mut self, thread: usize) -> &mut Vec<u32> {
+        if self.stacks.len() <= thread {
+            self.stacks.resize_with(thread + 1, Vec::new);
+        }
+        &mut self.stacks[thread]
+    }
+}
+
+/// The report `--profile` prints: the methods, opcodes and allocations, the largest first
+impl Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} instructions, call stacks sampled every {}", self.instructions, self.sample_interval)?;
+        writeln!(f, "\n{:>12} {:>12} {:>12}  method", "exclusive", "inclusive", "invocations")?;
+        for method in self.methods() {
+            writeln!(f, "{:>12} {:>12} {:>12}  {}{}", method.exclusive, method.inclusive, method.invocations, method.name, method.descriptor)?;
+        }
+        writeln!(f, "\n{:>12} {:>7}  opcode", "executed", "share")?;
+        for (opcode, count) in self.opcodes() {
+            let share = 100. * count as f64 / self.instructions.max(1) as f64;
+            writeln!(f, "{count:>12} {share:>6.2}%  {}", opcode.mnemonic())?;
+        }
+        writeln!(f, "\n{:>12} {:>12}  class", "bytes", "objects")?;
+        for allocation in self.allocations() {
+            writeln!(f, "{:>12} {:>12}  {}", allocation.bytes, allocation.objects, allocation.class)?;
+        }
+        Ok(())
+    }
+}
+
+impl RuntimeCtx<'_> {
+    /// Counts the instruction about to be executed and samples the call stack now and then
+    pub(super) fn profile_instruction(&mut self) {
+        let opcode = self.runtime.code.as_bytes_32aligned()[self.pc];
+        let thread = self.current_thread();
+        let profile = self.runtime.profile.as_mut().unwrap();
+        profile.instructions += 1;
+        profile.opcodes[opcode as usize] += 1;
+        if self.instructions.is_multiple_of(profile.sample_interval) {
+            let node = profile.stack(thread).last().copied().unwrap_or(0);
+            profile.nodes[node as usize].instructions += profile.sample_interval;
+        }
+    }
+    /// Counts an invocation and enters the call tree node of the new frame
+    pub(super) fn profile_call(&mut self, class: u32, method: u16) {
+        let thread = self.current_thread();
+        let runtime = &mut *self.runtime;
+        let profile = runtime.profile.as_mut().unwrap();
+        let index = *profile.method_indices.entry((class, method)).or_insert_with(|| {
+            let loaded = &runtime.classes[class as usize];
+            let MethodImpl::Bytecode(bytecode) = loaded.method(method) else { unreachable!("only bytecode methods have frames") };
+            profile.methods.push(MethodEntry {
+                name: format!("{}.{}", loaded.name.replace('/', "."), bytecode.debug.name).into(),
+                descriptor: bytecode.debug.descriptor.to_string().into(),
+                invocations: 0,
+            });
+            profile.methods.len() as u32 - 1
+        });
+        profile.methods[index as usize].invocations += 1;
+        let parent = profile.stack(thread).last().copied().unwrap_or(0);
+        let node = *profile.children.entry((parent, index)).or_insert_with(|| {
+            profile.nodes.push(CallNode { parent, method: index, instructions: 0 });
+            profile.nodes.len() as u32 - 1
+        });
+        profile.stack(thread).push(node);
+    }
+    /// Leaves the call tree node of the frame that returned
+    pub(super) fn profile_return(&mut self) {
+        let thread = self.current_thread();
+        self.runtime.profile.as_mut().unwrap().stack(thread).pop();
+    }
+    /// Counts an object or array allocated on the heap
+    pub(super) fn profile_allocation(&mut self, class: u32, size: usize) {
+        let runtime = &mut *self.runtime;
+        let profile = runtime.profile.as_mut().unwrap();
+        let index = *profile.allocation_indices.entry(class).or_insert_with(|| {
+            let name = &runtime.classes[class as usize].name;
+            profile.allocations.push(AllocationProfile { class: name.replace('/', ".").into(), objects: 0, bytes: 0 });
+            profile.allocations.len() - 1
+        });
+        let allocation = &mut profile.allocations[index];
+        allocation.objects += 1;
+        allocation.bytes += size as u64;
+    }
+}
@@ -0,0 +1,202 @@
+//! Resolving the symbolic references of constant pools.
+//!
+//! Every class with bytecode keeps a [`Resolution`] next to each entry of its constant pool. The
+//! first time a class, field or method reference is resolved the outcome is stored there, so the
+//! other instructions and exception handlers that refer to the entry do not look up names again.
+//! Like the JVMS requires, a reference whose resolution failed fails with the same error every
+//! time it is resolved after that.
+
+use std::rc::Rc;
+
+use crate::{class::{ConstIndex, Constant}, descriptor::{AnyDescriptor, FieldDescriptor, MethodDescriptor}};
+
+use super::{link::ValueKind, Result, RtError, RuntimeCtx, RuntimeInfo};
+
+/// What an entry of a constant pool was resolved to
+#[derive(Debug, Clone)]
+pub(super) enum Resolution {
+    Unresolved,
+    /// A class constant that is not resolved yet, `ldc` pushes the `Class` object of these
+    UnresolvedClass,
+    Class(u32),
+    /// The class declaring the field and its offset in the objects or the static fields
+    Field { class: u32, offset: u16, kind: ValueKind },
+    /// The method `invokespecial` and `invokestatic` call
+    Method { class: u32, method: u16, arg_size: u16 },
+    /// The method `invokevirtual` and `invokeinterface` select in the class of the receiver
+    Selector(Rc<Selector>),
+    /// The class is on no entry of the class path
+    ClassNotFound { name: Box<str>, searched: Box<[String]> },
+    /// A linkage error like `NoSuchFieldError`, a new one is thrown each time
+    Error { class: &'static str, message: Box<str> },
+}
+
+/// The name and descriptor of a method that is selected when it is called
+#[derive(Debug)]
+pub(super) struct Selector {
+    pub name: Box<str>,
+    pub descriptor: AnyDescriptor,
+    /// The stack slots the arguments take, without the receiver
+    pub arg_size: u16,
+}
+
+/// The cache of a constant pool with `len` entries, none of them resolved yet
+pub(super) fn unresolved(len: usize) -> Box<[Resolution]> {
+    vec![Resolution::Unresolved; len].into()
+}
+
+/// The cache of the constant pool of a class file, none of the entries resolved yet
+pub(super) fn unresolved_constants(constant_pool: &[Constant]) -> Box<[Resolution]> {
+    constant_pool
+        .iter()
+        .map(|constant| match constant {
+            Constant::Class { .. } => Resolution::UnresolvedClass,
+            _ => Resolution::Unresolved,
+        })
+        .collect()
+}
+
+impl RuntimeCtx<'_> {
+    /// Loads the class named by a class constant of the current class
+    pub(super) fn resolve_class(&mut self, n: ConstIndex) -> Result<u32> {
+        let Some(Resolution::Class(class)) = self.resolved(n, Self::resolve_class_uncached)? else {
+            unreachable!("resolving a class only fails with ClassNotFound")
+        };
+        Ok(class)
+    }
+    /// Whether entry `n` of the constant pool of the current class is a class constant
+    pub(super) fn is_class_constant(&self, n: ConstIndex) -> bool {
+        matches!(self.resolution(n), Resolution::UnresolvedClass | Resolution::Class(_) | Resolution::ClassNotFound { .. })
+    }
+    /// Resolves a field reference constant of the current class to the class declaring the field,
+    /// the field's offset and how its value is stored.
+    ///
+    /// If no field matches, a `NoSuchFieldError` is thrown and `None` is returned.
+    pub(super) fn resolve_field_ref(&mut self, n: ConstIndex) -> Result<Option<(u32, u16, ValueKind)>> {
+        Ok(match self.resolved(n, Self::resolve_field_ref_uncached)? {
+            Some(Resolution::Field { class, offset, kind }) => Some((class, offset, kind)),
+            _ => None,
+        })
+    }
+    /// Resolves a method reference constant of the current class to the class and id of the
+    /// method and the stack slots its arguments take, without the receiver.
+    ///
+    /// If no method matches, a `NoSuchMethodError` is thrown and `None` is returned.
+    pub(super) fn resolve_method_ref(&mut self, n: ConstIndex) -> Result<Option<(u32, u16, u16)>> {
+        Ok(match self.resolved(n, Self::resolve_method_ref_uncached)? {
+            Some(Resolution::Method { class, method, arg_size }) => Some((class, method, arg_size)),
+            _ => None,
+        })
+    }
+    /// The name and descriptor of a method reference constant of the current class, for calls
+    /// that select the method by the class of the receiver. The referenced class must have the
+    /// method, which is an interface method for `invokeinterface`.
+    ///
+    /// If the class is an interface when it should not be or the other way round, an
+    /// `IncompatibleClassChangeError` is thrown, if it has no such method a `NoSuchMethodError`,
+    /// and `None` is returned.
+    pub(super) fn resolve_selector(&mut self, n: ConstIndex, interface: bool) -> Result<Option<Rc<Selector>>> {
+        let resolve = if interface { Self::resolve_interface_selector_uncached } else { Self::resolve_virtual_selector_uncached };
+        Ok(match self.resolved(n, resolve)? {
+            Some(Resolution::Selector(selector)) => Some(selector),
+            _ => None,
+        })
+    }
+    /// The resolution of entry `n` of the constant pool of the current class, resolving it with
+    /// `resolve` the first time.
+    ///
+    /// If it failed with a linkage error, the error is thrown and `None` is returned.
+    fn resolved(&mut self, n: ConstIndex, resolve: fn(&mut Self, ConstIndex) -> Result<Resolution>) -> Result<Option<Resolution>> {
+        let mut resolution = self.resolution(n).clone();
+        if let Resolution::Unresolved | Resolution::UnresolvedClass = resolution {
+            resolution = resolve(self, n)?;
+            let RuntimeInfo::Bytecode { resolved, .. } = &mut self.runtime.classes[self.cur_class as usize].runtime_info else { unreachable!() };
+            resolved[n as usize - 1] = resolution.clone();
+        }
+        match resolution {
+            Resolution::ClassNotFound { name, searched } => Err(RtError::ClassNotFound { name, searched }),
+            Resolution::Error { class, message } => {
+                self.throw_new(class, Some(message.into()))?;
+                Ok(None)
+            }
+            resolution => Ok(Some(resolution)),
+        }
+    }
+    fn resolution(&self, n: ConstIndex) -> &Resolution {
+        match &self.runtime.classes[self.cur_class as usize].runtime_info {
+            RuntimeInfo::Bytecode { resolved, .. } => &resolved[n as usize - 1],
+            _ => unimplemented!(),
+        }
+    }
+    fn resolve_class_uncached(&mut self, n: ConstIndex) -> Result<Resolution> {
+        let name = self.read_utf8_constant(self.read_constant(n).class()).to_string();
+        match self.runtime.load_class(&name) {
+            Ok(class) => Ok(Resolution::Class(class)),
+            Err(RtError::ClassNotFound { name, searched }) => Ok(Resolution::ClassNotFound { name, searched }),
+            Err(e) => Err(e),
+        }
+    }
+    fn resolve_field_ref_uncached(&mut self, n: ConstIndex) -> Result<Resolution> {
+        let (class_index, name_and_type_index) = self.read_constant(n).fieldref();
+        let class = self.resolve_class(class_index)?;
+        let (name, field_type) = self.read_constant(name_and_type_index).nameandtype();
+        let field_type = FieldDescriptor::from_bytes(self.read_utf8_constant(field_type).as_bytes())?;
+        let name = self.read_utf8_constant(name);
+        Ok(match self.runtime.resolve_field(class, name, &field_type.clone().into()) {
+            Some((class, offset)) => Resolution::Field { class, offset, kind: ValueKind::of(&field_type) },
+            None => Resolution::Error { class: "java/lang/NoSuchFieldError", message: name.into() },
+        })
+    }
+    fn resolve_method_ref_uncached(&mut self, n: ConstIndex) -> Result<Resolution> {
+        let (class_index, name_and_type_index) = self.read_constant(n).methodref();
+        let class = self.resolve_class(class_index)?;
+        let (name, descriptor) = self.name_and_descriptor(name_and_type_index)?;
+        let arg_size = arg_size(&descriptor);
+        Ok(match self.runtime.find_method(class, &name, &descriptor.into()) {
+            Some((class, method)) => Resolution::Method { class, method, arg_size },
+            None => self.no_such_method(class, &name),
+        })
+    }
+    fn resolve_virtual_selector_uncached(&mut self, n: ConstIndex) -> Result<Resolution> {
+        self.resolve_selector_uncached(n, false)
+    }
+    fn resolve_interface_selector_uncached(&mut self, n: ConstIndex) -> Result<Resolution> {
+        self.resolve_selector_uncached(n, true)
+    }
+    /// Method resolution (JVMS 5.4.3.3) or interface method resolution (JVMS 5.4.3.4), keeping
+    /// the name and descriptor the method is selected by at each call
+    fn resolve_selector_uncached(&mut self, n: ConstIndex, interface: bool) -> Result<Resolution> {
+        let (class_index, name_and_type_index) = self.read_constant(n).methodref();
+        let class = self.resolve_class(class_index)?;
+        let (name, descriptor) = self.name_and_descriptor(name_and_type_index)?;
+        let loaded = self.runtime.get_class(class);
+        if loaded.is_interface() != interface {
+            let class_name = loaded.name.replace('/', ".");
+            let message = match interface {
+                true => format!("Found class {class_name}, but interface was expected"),
+                false => format!("Found interface {class_name}, but class was expected"),
+            };
+            return Ok(Resolution::Error { class: "java/lang/IncompatibleClassChangeError", message: message.into() });
+        }
+        let arg_size = arg_size(&descriptor);
+        let descriptor = descriptor.into();
+        if !self.runtime.has_method(class, &name, &descriptor) {
+            return Ok(self.no_such_method(class, &name));
+        }
+        Ok(Resolution::Selector(Rc::new(Selector { name, descriptor, arg_size })))
+    }
+    fn no_such_method(&self, class: u32, name: &str) -> Resolution {
+        let class_name = self.runtime.get_class(class).name.replace('/', ".");
+        Resolution::Error { class: "java/lang/NoSuchMethodError", message: format!("'{class_name}.{name}'").into() }
+    }
+    fn name_and_descriptor(&self, n: ConstIndex) -> Result<(Box<str>, MethodDescriptor)> {
+        let (name, descriptor) = self.read_constant(n).nameandtype();
+        let descriptor = MethodDescriptor::from_bytes(self.read_utf8_constant(descriptor).as_bytes())?;
+        Ok((self.read_utf8_constant(name).into(), descriptor))
+    }
+}
+
+/// The stack slots the arguments of a method take, without the receiver
+fn arg_size(descriptor: &MethodDescriptor) -> u16 {
+    descriptor.arg_types.iter().map(|arg| arg.unit_size() as u16).sum()
+}
@@ -0,0 +1,111 @@
+//! Where `System.out`, `System.err` and `System.in` go.
+//!
+//! By default they are the standard streams of the process, embedders can redirect them with
+//! [`Runtime::with_stdout`](super::Runtime::with_stdout) and the like, for example into a
+//! [`SharedBuffer`] to capture the output.
+
+use std::{cell::RefCell, fmt, io::{self, Read, Write}, rc::Rc};
+
+/// The stream a `PrintStream` or `InputStream` of `System` is bound to, stored in the object
+pub(super) const STDOUT: u8 = 0;
+pub(super) const STDERR: u8 = 1;
+pub(super) const STDIN: u8 = 2;
+
+/// The standard streams of a runtime, shared by its clones
+#[derive(Clone)]
+pub(super) struct Stdio {
+    out: Rc<RefCell<dyn Write>>,
+    err: Rc<RefCell<dyn Write>>,
+    input: Rc<RefCell<dyn Read>>,
+}
+
+impl Default for Stdio {
+    fn default() -> Self {
+        Self {
+            out: Rc::new(RefCell::new(io::stdout())),
+            err: Rc::new(RefCell::new(io::stderr())),
+            input: Rc::new(RefCell::new(io::stdin())),
+        }
+    }
+}
+
+impl Stdio {
+    pub fn set_out(&mut self, out: impl Write + 'static) {
+        self.out = Rc::new(RefCell::new(out));
+    }
+    pub fn set_err(&mut self, err: impl Write + 'static) {
+        self.err = Rc::new(RefCell::new(err));
+    }
+    pub fn set_input(&mut self, input: impl Read + 'static) {
+        self.input = Rc::new(RefCell::new(input));
+    }
+    fn writer(&self, stream: u8) -> &RefCell<dyn Write> {
+        match stream {
+            STDOUT => &*self.out,
+            _ => &*self.err,
+        }
+    }
+    /// Writes bytes to `System.out` or `System.err`
+    pub fn write(&self, stream: u8, bytes: &[u8]) -> io::Result<()> {
+        self.writer(stream).borrow_mut().write_all(bytes)
+    }
+    /// Writes text encoded as UTF-8, flushing after a line like an auto-flushing `PrintStream`
+    pub fn write_text(&self, stream: u8, chars: &[u16], newline: bool) -> io::Result<()> {
+        let mut text: String = char::decode_utf16(chars.iter().copied()).map(|c| c.unwrap_or('?')).collect();
+        if newline {
+            text.push('\n');
+        }
+        let mut writer = self.writer(stream).borrow_mut();
+        writer.write_all(text.as_bytes())?;
+        if text.contains('\n') {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+    pub fn flush(&self, stream: u8) -> io::Result<()> {
+        self.writer(stream).borrow_mut().flush()
+    }
+    /// Flushes both output streams, when the program ends
+    pub fn flush_all(&self) -> io::Result<()> {
+        self.out.borrow_mut().flush()?;
+        self.err.borrow_mut().flush()
+    }
+    /// Reads from `System.in`, 0 bytes at the end of the input
+    pub fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.borrow_mut().read(buf)
+    }
+}
+
+impl fmt::Debug for Stdio {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Stdio")
+    }
+}
+
+/// A buffer that collects the output written to it, its clones share the contents
+#[derive(Debug, Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// A copy of everything written so far
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.borrow().clone()
+    }
+    /// Takes everything written so far, leaving the buffer empty
+    pub fn take(&self) -> Vec<u8> {
+        self.0.take()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+//! Layout of `java.lang.String` objects.
+//!
+//! The content is stored right in the object, compactly as Latin-1 when every character fits in a
+//! byte and as UTF-16 code units otherwise, like the JDK does:
+//!
+//! | offset | content                            |
+//! |--------|------------------------------------|
+//! | 0      | object header                      |
+//! | 8      | length in characters               |
+//! | 12     | coder, see [`Coder`]               |
+//! | 16     | one byte or two bytes per character |
+
+use super::OBJECT_HEADER_SIZE;
+
+pub(crate) const STRING_LENGTH_OFFSET: u32 = OBJECT_HEADER_SIZE as u32;
+pub(crate) const STRING_CODER_OFFSET: u32 = STRING_LENGTH_OFFSET + 4;
+pub(crate) const STRING_DATA_OFFSET: u32 = STRING_CODER_OFFSET + 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub(crate) enum Coder {
+    Latin1 = 0,
+    Utf16 = 1,
+}
+
+impl Coder {
+    pub const fn from_u8(coder: u8) -> Self {
+        match coder {
+            0 => Self::Latin1,
+            _ => Self::Utf16,
+        }
+    }
+    /// Size in bytes of a string object with `length` characters, rounded up to whole words
+    pub const fn object_size(self, length: u32) -> u32 {
+        (STRING_DATA_OFFSET + (length << self as u32) + 3) & !3
+    }
+}
+
+/// The coder of a string made of the given UTF-16 code units
+pub(crate) fn coder(chars: &[u16]) -> Coder {
+    if chars.iter().all(|&c| c <= 0xff) { Coder::Latin1 } else { Coder::Utf16 }
+}
+
+/// The coder and the bytes of the content of a string made of the given UTF-16 code units
+pub(crate) fn encode(chars: &[u16]) -> (Coder, Vec<u8>) {
+    match coder(chars) {
+        Coder::Latin1 => (Coder::Latin1, chars.iter().map(|&c| c as u8).collect()),
+        Coder::Utf16 => (Coder::Utf16, chars.iter().flat_map(|c| c.to_ne_bytes()).collect()),
+    }
+}
+
+/// The UTF-16 code units of the content of a string
+pub(crate) fn decode(coder: Coder, bytes: &[u8]) -> Vec<u16> {
+    match coder {
+        Coder::Latin1 => bytes.iter().map(|&b| b as u16).collect(),
+        Coder::Utf16 => bytes.chunks_exact(2).map(|c| u16::from_ne_bytes([c[0], c[1]])).collect(),
+    }
+}
+
+/// `String.hashCode`, `s[0]*31^(n-1) + s[1]*31^(n-2) + ... + s[n-1]`
+pub(crate) fn hash_code(chars: &[u16]) -> i32 {
+    chars.iter().fold(0i32, |hash, &c| hash.wrapping_mul(31).wrapping_add(c as i32))
+}
+
+/// `Double.toString`: plain notation from 10^-3 up to 10^7, computerized scientific notation
+/// (`1.0E10`) outside of it, and always at least one digit after the point
+pub(crate) fn double_to_string(d: f64) -> String {
+    floating_to_string(d, format!("{d}"), format!("{d:e}"), || format!("{d:.1e}"))
+}
+/// `Float.toString`, see [`double_to_string`]
+pub(crate) fn float_to_string(f: f32) -> String {
+    floating_to_string(f as f64, format!("{f}"), format!("{f:e}"), || format!("{f:.1e}"))
+}
+/// A single shortest digit is written as the closest two digits instead, which differ for the
+/// subnormals so coarse that the digit is far from the value (`4.9E-324`, not `5.0E-324`)
+fn floating_to_string(
+    value: f64,
+    plain: String,
+    scientific: String,
+    two_digits: impl FnOnce() -> String,
+) -> String {
+    if value.is_nan() {
+        "NaN".into()
+    } else if value.is_infinite() {
+        if value > 0. { "Infinity" } else { "-Infinity" }.into()
+    } else if value == 0. || (1e-3..1e7).contains(&value.abs()) {
+        if plain.contains('.') { plain } else { plain + ".0" }
+    } else {
+        let scientific = if scientific.contains('.') { scientific } else { two_digits() };
+        let (mantissa, exponent) = scientific.split_once('e').unwrap();
+        format!("{mantissa}E{exponent}")
+    }
+}
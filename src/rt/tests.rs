@@ -0,0 +1,1072 @@
+//! Tests of the runtime that look at its internals, like where fields are laid out.
+//!
+//! The Java classes are compiled from the sources given to [`compile`], `javac` has to be on the
+//! `PATH`.
+
+use std::{fs, path::{Path, PathBuf}, process::Command};
+
+use crate::{class::{AttributeInfo, ClassFile, ConstIndex, Constant}, code::Code, descriptor::FieldDescriptor};
+
+use super::{string::{Coder, STRING_CODER_OFFSET}, ClassPath, JValue, RtError, Runtime, RuntimeCtx, Value, OBJECT_HEADER_SIZE};
+
+/// Compiles Java sources, given by class name, into a directory of their own for the test, against
+/// the classes compiled there before
+fn compile(test: &str, sources: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("jappuccino-{test}-{}", std::process::id()));
+    let src = dir.join("src");
+    fs::create_dir_all(&src).unwrap();
+    let mut javac = Command::new("javac");
+    javac.arg("-encoding").arg("UTF-8").arg("-d").arg(&dir).arg("-cp").arg(&dir);
+    for (class, source) in sources {
+        let path = src.join(format!("{class}.java"));
+        fs::write(&path, source).unwrap();
+        javac.arg(path);
+    }
+    let status = javac.status().expect("javac should be on the PATH");
+    assert!(status.success(), "javac failed");
+    dir
+}
+
+fn runtime(classes: &PathBuf) -> Runtime {
+    let mut class_path = ClassPath::new();
+    class_path.push_directory(classes);
+    Runtime::new().with_class_path(class_path)
+}
+
+/// Three levels of classes with fields of every width and interfaces with a static field
+const HIERARCHY: &[(&str, &str)] = &[
+    ("Named", "interface Named { int[] UNITS = { 8 }; }"),
+    ("Sized", "interface Sized extends Named {}"),
+    ("Base", "class Base { byte flag; long id; }"),
+    ("Middle", "class Middle extends Base implements Sized { short count; double weight; char grade; }"),
+    ("Leaf", "class Leaf extends Middle { boolean done; int total; byte tag; Object link; }"),
+    ("Fields", "
+        class Fields {
+            static long sum() {
+                Leaf leaf = new Leaf();
+                leaf.flag = 1;
+                leaf.id = 1L << 40;
+                leaf.count = -3;
+                leaf.weight = 2.5;
+                leaf.grade = 'A';
+                leaf.done = true;
+                leaf.total = 1000;
+                leaf.tag = -7;
+                leaf.link = leaf;
+                return leaf.flag + leaf.id + leaf.count + (long) (leaf.weight * 2) + leaf.grade
+                    + (leaf.done ? 10 : 0) + leaf.total + leaf.tag + (leaf.link == leaf ? 100 : 0)
+                    + Leaf.UNITS[0];
+            }
+        }
+    "),
+];
+
+#[test]
+fn subclass_fields_follow_inherited_ones() {
+    let classes = compile("layout", HIERARCHY);
+    let mut runtime = runtime(&classes);
+    let leaf = runtime.load_class("Leaf").unwrap();
+    let (base, middle) = (runtime.load_class("Base").unwrap(), runtime.load_class("Middle").unwrap());
+
+    // each class starts where its superclass ends, fields go from the largest to the smallest and
+    // the smaller ones fill the padding that aligning the larger ones left
+    let expected = [
+        (base, "id", FieldDescriptor::Long, 8),
+        (base, "flag", FieldDescriptor::Byte, 16),
+        (middle, "weight", FieldDescriptor::Double, 20),
+        (middle, "count", FieldDescriptor::Short, 18),
+        (middle, "grade", FieldDescriptor::Char, 28),
+        (leaf, "total", FieldDescriptor::Int, 32),
+        (leaf, "link", FieldDescriptor::ClassRef("java/lang/Object".into()), 36),
+        (leaf, "done", FieldDescriptor::Boolean, 30),
+        (leaf, "tag", FieldDescriptor::Byte, 31),
+    ];
+    let mut taken = Vec::new();
+    for (class, name, descriptor, offset) in expected {
+        let resolved = runtime.resolve_field(leaf, name, &descriptor.clone().into());
+        assert_eq!(resolved, Some((class, offset)), "{name}");
+        let size = descriptor.byte_size();
+        assert_eq!(offset % size.min(4), 0, "{name} is not aligned");
+        assert!(offset >= OBJECT_HEADER_SIZE, "{name} overlaps the header");
+        assert!(taken.iter().all(|&(start, end)| offset + size <= start || end <= offset), "{name} overlaps another field");
+        taken.push((offset, offset + size));
+    }
+    assert_eq!(runtime.get_class(base).data_size, 17);
+    assert_eq!(runtime.get_class(middle).data_size, 30);
+    assert_eq!(runtime.get_class(leaf).data_size, 40);
+    assert_eq!(&*runtime.get_class(leaf).ref_offsets, &[36]);
+}
+
+#[test]
+fn inherited_fields_resolve() {
+    let classes = compile("inherited", HIERARCHY);
+    let mut runtime = runtime(&classes);
+    let mut ctx = runtime.new_context();
+    let sum = ctx.call_static::<i64>("Fields", "sum", "()J", ()).unwrap().unwrap();
+    assert_eq!(sum, 1 + (1 << 40) - 3 + 5 + 'A' as i64 + 10 + 1000 - 7 + 100 + 8);
+
+    // through the host as well, from the superclasses and the superinterfaces
+    let leaf = ctx.new_instance("Leaf", "()V", ()).unwrap().unwrap();
+    ctx.set_field(leaf, "weight", "D", 0.5).unwrap().unwrap();
+    assert_eq!(ctx.get_field::<f64>(leaf, "weight", "D").unwrap().unwrap(), 0.5);
+    let units = ctx.get_static_field::<Value>("Leaf", "UNITS", "[I").unwrap().unwrap();
+    assert_ne!(units, Value::NULL);
+}
+
+#[test]
+fn missing_field_throws_no_such_field_error() {
+    let holder = ("Holder", "class Holder { static int kept = 1; int gone; static int goneStatic; }");
+    let user = ("User", "
+        class User {
+            static int kept() { return Holder.kept; }
+            static int gone() { return new Holder().gone; }
+            static int goneStatic() { return Holder.goneStatic; }
+        }
+    ");
+    compile("missing", &[holder, user]);
+    // the class the user was compiled against changes and loses the fields
+    let classes = compile("missing", &[("Holder", "class Holder { static int kept = 1; }")]);
+    let mut runtime = runtime(&classes);
+    let mut ctx = runtime.new_context();
+    assert_eq!(ctx.call_static::<i32>("User", "kept", "()I", ()).unwrap(), Ok(1));
+    for method in ["gone", "goneStatic"] {
+        let exception = ctx.call_static::<i32>("User", method, "()I", ()).unwrap().unwrap_err();
+        assert_eq!(ctx.get_class_name(exception), "java/lang/NoSuchFieldError", "{method}");
+        assert_eq!(ctx.throwable_message(exception).as_deref(), Some(method));
+    }
+    let holder = ctx.new_instance("Holder", "()V", ()).unwrap().unwrap();
+    let exception = ctx.get_field::<i32>(holder, "gone", "I").unwrap().unwrap_err();
+    assert_eq!(ctx.get_class_name(exception), "java/lang/NoSuchFieldError");
+}
+
+#[test]
+fn native_allocations_throw_out_of_memory_error() {
+    let hog = ("Hog", "
+        class Hog {
+            static int grow() {
+                String text = \"x\";
+                try {
+                    while (true) {
+                        text = text.concat(text);
+                    }
+                } catch (OutOfMemoryError e) {
+                    return text.length();
+                }
+            }
+        }
+    ");
+    let classes = compile("heap", &[hog]);
+    let max_heap_size = 4 << 20;
+    let mut runtime = runtime(&classes).with_max_heap_size(max_heap_size);
+    let mut ctx = runtime.new_context();
+    // the string that no longer fits is made by the native `concat`
+    let length = ctx.call_static::<i32>("Hog", "grow", "()I", ()).unwrap().unwrap() as usize;
+    assert!((1 << 19..max_heap_size).contains(&length), "{length}");
+    drop(ctx);
+    assert!(runtime.gc_stats().peak_heap_bytes <= max_heap_size);
+}
+
+#[test]
+fn virtual_calls_resolve_the_referenced_method() {
+    let api = ("Api", "class Api { int kept() { return 1; } int gone() { return 2; } }");
+    let shape = ("Shape", "class Shape { int sides() { return 3; } }");
+    let named = ("Named", "interface Named { int name(); }");
+    let caller = ("Caller", "
+        class Caller {
+            static int kept() { return new Api().kept(); }
+            static int gone(Api api) { return api.gone(); }
+            static int sides(Shape shape) { return shape.sides(); }
+            static int name(Named named) { return named.name(); }
+        }
+    ");
+    compile("selector", &[api, shape, named, caller]);
+    // the classes the caller was compiled against change without it being compiled again
+    let classes = compile("selector", &[
+        ("Api", "class Api { int kept() { return 1; } }"),
+        ("Shape", "interface Shape { int sides(); }"),
+        ("Named", "abstract class Named { abstract int name(); }"),
+    ]);
+    let mut runtime = runtime(&classes);
+    let mut ctx = runtime.new_context();
+    assert_eq!(ctx.call_static::<i32>("Caller", "kept", "()I", ()).unwrap(), Ok(1));
+    let expected = [
+        ("gone", "(LApi;)I", "java/lang/NoSuchMethodError", "'Api.gone'"),
+        ("sides", "(LShape;)I", "java/lang/IncompatibleClassChangeError", "Found interface Shape, but class was expected"),
+        ("name", "(LNamed;)I", "java/lang/IncompatibleClassChangeError", "Found class Named, but interface was expected"),
+    ];
+    for (method, descriptor, error, message) in expected {
+        // the receiver is null, the reference fails to resolve before the call gets to it, each
+        // time with the same error
+        for _ in 0..2 {
+            let exception = ctx.call_static::<i32>("Caller", method, descriptor, (Value::NULL,)).unwrap().unwrap_err();
+            assert_eq!(ctx.get_class_name(exception), error, "{method}");
+            assert_eq!(ctx.throwable_message(exception).as_deref(), Some(message), "{method}");
+        }
+    }
+}
+
+#[test]
+fn multianewarray_creates_nested_arrays() {
+    let grids = ("Grids", "
+        class Grids {
+            static int shape() {
+                int[][][] cube = new int[2][3][4];
+                cube[1][2][3] = 5;
+                String[][][] rows = new String[2][3][];
+                long[][][] empty = new long[2][0][7];
+                return cube.length * 1000 + cube[1].length * 100 + cube[1][2].length * 10 + cube[1][2][3]
+                    + (rows[1][2] == null ? 10000 : 0) + empty[1].length;
+            }
+            static int negative(int rows, int columns) {
+                return new byte[rows][columns].length;
+            }
+        }
+    ");
+    let classes = compile("multianewarray", &[grids]);
+    let mut runtime = runtime(&classes);
+    let mut ctx = runtime.new_context();
+    assert_eq!(ctx.call_static::<i32>("Grids", "shape", "()I", ()).unwrap(), Ok(12345));
+    // every count is checked, also after one that is zero
+    let exception = ctx.call_static::<i32>("Grids", "negative", "(II)I", (0, -2)).unwrap().unwrap_err();
+    assert_eq!(ctx.get_class_name(exception), "java/lang/NegativeArraySizeException");
+    assert_eq!(ctx.throwable_message(exception).as_deref(), Some("-2"));
+}
+
+/// A static initializer that leaves enough garbage behind to collect the heap on the way
+const GARBAGE: (&str, &str) = ("Garbage", "
+    class Garbage {
+        static int[] last;
+        static void make() {
+            for (int i = 0; i < 4000; i++) {
+                last = new int[1000];
+            }
+        }
+    }
+");
+
+#[test]
+fn host_arguments_survive_static_initializers() {
+    let echo = ("Echo", "
+        class Echo {
+            static { Garbage.make(); }
+            static String echo(String first, String second) { return first + \"|\" + second; }
+        }
+    ");
+    let pair = ("Pair", "
+        class Pair {
+            static { Garbage.make(); }
+            String first, second;
+            Pair(String first, String second) { this.first = first; this.second = second; }
+        }
+    ");
+    let slot = ("Slot", "class Slot { static String value; static { Garbage.make(); } }");
+    let classes = compile("host-args", &[GARBAGE, echo, pair, slot]);
+    let mut runtime = runtime(&classes).with_max_heap_size(4 << 20);
+    let mut ctx = runtime.new_context();
+    let descriptor = "(Ljava/lang/String;Ljava/lang/String;)Ljava/lang/String;";
+    let echoed = ctx.call_static::<String>("Echo", "echo", descriptor, ("first-argument", "second-argument")).unwrap();
+    assert_eq!(echoed.unwrap(), "first-argument|second-argument");
+    let pair = ctx.new_instance("Pair", "(Ljava/lang/String;Ljava/lang/String;)V", ("first", "second")).unwrap().unwrap();
+    assert_eq!(ctx.get_field::<String>(pair, "second", "Ljava/lang/String;").unwrap().unwrap(), "second");
+    ctx.set_static_field("Slot", "value", "Ljava/lang/String;", "stored").unwrap().unwrap();
+    assert_eq!(ctx.get_static_field::<String>("Slot", "value", "Ljava/lang/String;").unwrap().unwrap(), "stored");
+    drop(ctx);
+    assert!(runtime.gc_stats().collections >= 3, "{:?}", runtime.gc_stats());
+}
+
+#[test]
+fn host_calls_check_static_members() {
+    let counter = ("Counter", "
+        class Counter {
+            static int total = 4;
+            int count = 3;
+            int get() { return count; }
+            static int twice(int value) { return 2 * value; }
+        }
+    ");
+    let classes = compile("host-static", &[counter]);
+    let mut runtime = runtime(&classes);
+    let mut ctx = runtime.new_context();
+    let counter = ctx.new_instance("Counter", "()V", ()).unwrap().unwrap();
+    fn check(ctx: &mut RuntimeCtx, result: Result<i32, Value>, message: &str) {
+        let exception = result.unwrap_err();
+        assert_eq!(ctx.get_class_name(exception), "java/lang/IncompatibleClassChangeError", "{message}");
+        assert_eq!(ctx.throwable_message(exception).as_deref(), Some(message));
+    }
+    let result = ctx.call_static::<i32>("Counter", "get", "()I", ()).unwrap();
+    check(&mut ctx, result, "Expected static method 'int Counter.get()'");
+    let result = ctx.call_method::<i32>(counter, "twice", "(I)I", (1,)).unwrap();
+    check(&mut ctx, result, "Expecting non-static method 'int Counter.twice(int)'");
+    let result = ctx.get_field::<i32>(counter, "total", "I").unwrap();
+    check(&mut ctx, result, "Expected non-static field Counter.total");
+    let result = ctx.get_static_field::<i32>("Counter", "count", "I").unwrap();
+    check(&mut ctx, result, "Expected static field Counter.count");
+    let result = ctx.set_static_field("Counter", "count", "I", 5).unwrap().map(|()| 0);
+    check(&mut ctx, result, "Expected static field Counter.count");
+
+    // the members are still there the right way
+    assert_eq!(ctx.call_method::<i32>(counter, "get", "()I", ()).unwrap(), Ok(3));
+    assert_eq!(ctx.call_static::<i32>("Counter", "twice", "(I)I", (4,)).unwrap(), Ok(8));
+    assert_eq!(ctx.get_static_field::<i32>("Counter", "total", "I").unwrap(), Ok(4));
+}
+
+#[test]
+fn natives_calling_back_into_java_keep_the_stack() {
+    let bridge = ("Bridge", "
+        class Bridge {
+            static native int callBack(int value);
+            static native int fail(int value);
+            static int catcher(int value) {
+                try {
+                    throw new IllegalStateException();
+                } catch (IllegalStateException e) {
+                    return value + 1;
+                }
+            }
+            static int thrower(int value) { throw new IllegalStateException(\"from Java\"); }
+            static int sum() { return 100 + callBack(1) + callBack(-1); }
+            static int caught() {
+                try {
+                    return fail(1);
+                } catch (IllegalArgumentException e) {
+                    return -1;
+                }
+            }
+            static int uncaught() { return 5 + fail(2); }
+        }
+    ");
+    let classes = compile("natives", &[bridge]);
+    let mut runtime = runtime(&classes);
+    // the Java it calls catches its exception, or lets it out to the native which handles it
+    runtime.register_native("Bridge", "callBack", "(I)I", |ctx, args| {
+        let value = args.int(0);
+        let method = if value > 0 { "catcher" } else { "thrower" };
+        Ok(match ctx.call_static::<i32>("Bridge", method, "(I)I", (value,))? {
+            Ok(result) => JValue::Int(result + 10),
+            Err(_) => JValue::Int(1000),
+        })
+    }).unwrap();
+    runtime.register_native("Bridge", "fail", "(I)I", |ctx, _| {
+        ctx.throw_new("java/lang/IllegalArgumentException", None)?;
+        Ok(JValue::Int(0))
+    }).unwrap();
+    let mut ctx = runtime.new_context();
+    assert_eq!(ctx.call_static::<i32>("Bridge", "sum", "()I", ()).unwrap(), Ok(100 + 12 + 1000));
+    assert_eq!(ctx.call_static::<i32>("Bridge", "caught", "()I", ()).unwrap(), Ok(-1));
+    let exception = ctx.call_static::<i32>("Bridge", "uncaught", "()I", ()).unwrap().unwrap_err();
+    assert_eq!(ctx.get_class_name(exception), "java/lang/IllegalArgumentException");
+    assert_eq!(ctx.call_static::<i32>("Bridge", "sum", "()I", ()).unwrap(), Ok(100 + 12 + 1000));
+}
+
+/// Reads a compiled class, lets `edit` change it and writes it back
+fn edit_class(classes: &Path, class: &str, edit: impl FnOnce(&mut ClassFile)) {
+    let path = classes.join(format!("{class}.class"));
+    let mut class_file = ClassFile::from_reader(fs::File::open(&path).unwrap()).unwrap();
+    edit(&mut class_file);
+    let mut bytes = Vec::new();
+    class_file.write(&mut bytes).unwrap();
+    fs::write(path, bytes).unwrap();
+}
+
+/// The index of the `Utf8` constant with the text
+fn utf8_index(class_file: &ClassFile, text: &str) -> ConstIndex {
+    let i = class_file.constant_pool.iter().position(|c| matches!(c, Constant::Utf8(s) if &**s == text)).unwrap();
+    i as ConstIndex + 1
+}
+
+#[test]
+fn string_concat_recipes_are_checked() {
+    let concat = ("Concat", "
+        class Concat {
+            static String args(int value) { return \"<\" + value + \">\"; }
+            static String constants(int value) { return \"[\" + value + \"]\"; }
+            static String recipe(int value) { return \"{\" + value + \"}\"; }
+            static String integer(int value) { return \"(\" + value + \")\"; }
+        }
+    ");
+    let classes = compile("concat", &[concat]);
+    edit_class(&classes, "Concat", |class_file| {
+        fn set_utf8(class_file: &mut ClassFile, from: &str, to: &str) -> ConstIndex {
+            let i = utf8_index(class_file, from);
+            class_file.constant_pool[i as usize - 1] = Constant::Utf8(to.into());
+            i
+        }
+        // more arguments and constants than the call sites have
+        set_utf8(class_file, "<\u{1}>", "<\u{1}\u{1}>");
+        set_utf8(class_file, "[\u{1}]", "[\u{1}\u{2}]");
+        // a recipe that is a class instead of a string
+        let recipe = utf8_index(class_file, "{\u{1}}");
+        let string = class_file.constant_pool.iter().position(|c| matches!(*c, Constant::String { string_index } if string_index == recipe)).unwrap();
+        class_file.constant_pool[string] = Constant::Class { name_index: recipe };
+        // an int constant, which is concatenated like `String.valueOf` does
+        let recipe = set_utf8(class_file, "(\u{1})", "(\u{1}\u{2})");
+        let mut constant_pool = class_file.constant_pool.to_vec();
+        constant_pool.push(Constant::Integer { bytes: -7i32 as u32 });
+        class_file.constant_pool = constant_pool.into();
+        let integer = class_file.constant_pool.len() as ConstIndex;
+        for attribute in &mut *class_file.attributes {
+            let AttributeInfo::BootstrapMethods(methods) = attribute else { continue };
+            for method in &mut **methods {
+                if matches!(class_file.constant_pool[method.bootstrap_arguments[0] as usize - 1], Constant::String { string_index } if string_index == recipe) {
+                    method.bootstrap_arguments = [method.bootstrap_arguments[0], integer].into();
+                }
+            }
+        }
+    });
+    let mut runtime = runtime(&classes);
+    let mut ctx = runtime.new_context();
+    let expected = [
+        ("args", "Mismatched number of concat arguments: recipe wants 2 arguments, but signature provides 1"),
+        ("constants", "Mismatched number of concat constants: recipe wants 1 constants, but only 0 are passed"),
+        ("recipe", "the recipe of a string concatenation is not a string"),
+    ];
+    for (method, message) in expected {
+        // linking fails the same way each time
+        for _ in 0..2 {
+            let exception = ctx.call_static::<String>("Concat", method, "(I)Ljava/lang/String;", (5,)).unwrap().unwrap_err();
+            assert_eq!(ctx.get_class_name(exception), "java/lang/BootstrapMethodError", "{method}");
+            assert_eq!(ctx.throwable_message(exception).as_deref(), Some(message));
+        }
+    }
+    assert_eq!(ctx.call_static::<String>("Concat", "integer", "(I)Ljava/lang/String;", (5,)).unwrap().unwrap(), "(5-7)");
+}
+
+#[test]
+fn switches_find_their_targets_at_every_alignment() {
+    // each variable before the switch moves it by three bytes, so the padding after its opcode
+    // is different in every method
+    let mut source = String::from("class Switches {");
+    for (n, variables) in ["", "int k = 7;", "int k = 7, m = 7;", "int k = 7, m = 7, o = 7;"].iter().enumerate() {
+        source += &format!("
+            static int table{n}(int i) {{
+                {variables}
+                switch (i) {{
+                    case -2: return 10;
+                    case 0: return 20;
+                    case 1: return 30;
+                    case 2: case 3: return 40;
+                    default: return -1;
+                }}
+            }}
+            static int lookup{n}(int i) {{
+                {variables}
+                switch (i) {{
+                    case Integer.MIN_VALUE: return 10;
+                    case -1000000: return 20;
+                    case 7: return 30;
+                    case 100000: return 40;
+                    case Integer.MAX_VALUE: return 50;
+                    default: return -1;
+                }}
+            }}
+        ");
+    }
+    source += "}";
+    let classes = compile("switches", &[("Switches", &source)]);
+    let mut runtime = runtime(&classes);
+    let mut ctx = runtime.new_context();
+    let keys = [i32::MIN, i32::MIN + 1, -1000000, -3, -2, -1, 0, 1, 2, 3, 4, 7, 100000, i32::MAX - 1, i32::MAX];
+    for n in 0..4 {
+        for key in keys {
+            let table = match key {
+                -2 => 10,
+                0 => 20,
+                1 => 30,
+                2 | 3 => 40,
+                _ => -1,
+            };
+            let lookup = match key {
+                i32::MIN => 10,
+                -1000000 => 20,
+                7 => 30,
+                100000 => 40,
+                i32::MAX => 50,
+                _ => -1,
+            };
+            assert_eq!(ctx.call_static::<i32>("Switches", &format!("table{n}"), "(I)I", (key,)).unwrap(), Ok(table), "table{n}({key})");
+            assert_eq!(ctx.call_static::<i32>("Switches", &format!("lookup{n}"), "(I)I", (key,)).unwrap(), Ok(lookup), "lookup{n}({key})");
+        }
+    }
+}
+
+#[test]
+fn wide_reaches_every_local() {
+    // 130 longs push the last locals past the 256 that a byte indexes
+    let longs: String = (1..130).map(|k| format!("long l{k} = l{} + 1;", k - 1)).collect();
+    let wide = ("Wide", &*format!("
+        class Wide {{
+            static long locals(int n) {{
+                long l0 = n;
+                {longs}
+                int x = (int) l129;
+                x += 1000;
+                x -= 200;
+                double d = x;
+                d *= 2;
+                return l0 + l129 + x + (long) d;
+            }}
+        }}
+    "));
+    let classes = compile("wide", &[wide]);
+    let mut runtime = runtime(&classes);
+    let mut ctx = runtime.new_context();
+    for n in [0, 5, -1000] {
+        let x = n as i64 + 929;
+        assert_eq!(ctx.call_static::<i64>("Wide", "locals", "(I)J", (n,)).unwrap(), Ok(n as i64 + (n as i64 + 129) + 3 * x));
+    }
+}
+
+/// Replaces the code of a method with `code`, for instructions that `javac` does not emit
+fn set_code(class_file: &mut ClassFile, method: &str, max_stack: u16, max_locals: u16, code: &[u8]) {
+    let name = utf8_index(class_file, method);
+    let method = class_file.methods.iter_mut().find(|m| m.name_index == name).unwrap();
+    for attribute in &mut *method.attributes {
+        if let AttributeInfo::Code { max_stack: stack, max_locals: locals, code: old, exception_table, attributes } = attribute {
+            (*stack, *locals) = (max_stack, max_locals);
+            *old = Code(code.into());
+            *exception_table = [].into();
+            *attributes = [].into();
+        }
+    }
+}
+
+#[test]
+fn subroutines_return_where_they_were_called() {
+    let subroutines = ("Subroutines", "
+        class Subroutines {
+            static int twice(int n) { return n; }
+            static int thrice(int n) { return n; }
+        }
+    ");
+    let classes = compile("subroutines", &[subroutines]);
+    edit_class(&classes, "Subroutines", |class_file| {
+        // verifiers since Java 7 reject `jsr` and `ret`
+        class_file.version = (49, 0);
+        // a subroutine that adds the argument to local 1, called with `jsr` and `jsr_w`
+        let twice = [
+            0x03, 0x3c, // iconst_0, istore_1
+            0xa8, 0x00, 0x0c, // jsr 14
+            0xc9, 0x00, 0x00, 0x00, 0x09, // jsr_w 14
+            0x1b, 0xac, // iload_1, ireturn
+            0x00, 0x00, // nop, nop
+            0x4d, // astore_2
+            0x1b, 0x1a, 0x60, 0x3c, // iload_1, iload_0, iadd, istore_1
+            0xa9, 0x02, // ret 2
+        ];
+        set_code(class_file, "twice", 2, 3, &twice);
+        // the same with the return address in local 300
+        let thrice = [
+            0x03, 0x3c, // iconst_0, istore_1
+            0xa8, 0x00, 0x0b, // jsr 13
+            0xa8, 0x00, 0x08, // jsr 13
+            0xa8, 0x00, 0x05, // jsr 13
+            0x1b, 0xac, // iload_1, ireturn
+            0xc4, 0x3a, 0x01, 0x2c, // wide astore 300
+            0x1b, 0x1a, 0x60, 0x3c, // iload_1, iload_0, iadd, istore_1
+            0xc4, 0xa9, 0x01, 0x2c, // wide ret 300
+        ];
+        set_code(class_file, "thrice", 2, 301, &thrice);
+    });
+    let mut runtime = runtime(&classes);
+    let mut ctx = runtime.new_context();
+    for n in [0, 7, -40] {
+        assert_eq!(ctx.call_static::<i32>("Subroutines", "twice", "(I)I", (n,)).unwrap(), Ok(2 * n));
+        assert_eq!(ctx.call_static::<i32>("Subroutines", "thrice", "(I)I", (n,)).unwrap(), Ok(3 * n));
+    }
+}
+
+#[test]
+fn dups_keep_the_words_of_wide_values_together() {
+    let dups = ("Dups", "
+        class Dups {
+            int i;
+            long l;
+            static long sl;
+            static double sd;
+            static int dupX1() {
+                Dups d = new Dups();
+                int r = d.i = 5;
+                return r * 10 + d.i;
+            }
+            static int dupX2() {
+                int[] a = new int[3];
+                int r = a[1] = 7;
+                return r * 100 + a[1] * 10 + a.length;
+            }
+            static int dup2() {
+                int[] a = { 4, 5 };
+                a[1] += 3;
+                return a[0] * 10 + a[1];
+            }
+            static long dup2Long() {
+                sl = 40;
+                long r = sl++;
+                return r * 1000 + sl;
+            }
+            static double dup2Double() {
+                sd = 1.5;
+                double r = ++sd;
+                return r + sd * 10;
+            }
+            static long dup2X1() {
+                Dups d = new Dups();
+                long r = d.l = -3000000000L;
+                return r + d.l;
+            }
+            static long dup2X2() {
+                long[] a = new long[2];
+                long r = a[1] = 1L << 40;
+                return r + a[1] + a.length;
+            }
+            static double dup2X2Double() {
+                double[] a = new double[2];
+                double r = a[0] = -0.25;
+                return r + a[0];
+            }
+            static long dupX2Long() { return 0; }
+            static int dup2X1Ints() { return 0; }
+            static int dup2X2Ints() { return 0; }
+            static long dup2X2IntsLong() { return 0; }
+            static long dup2X2Longs() { return 0; }
+        }
+    ");
+    let classes = compile("dups", &[dups]);
+    // the forms that `javac` does not emit, the values left on the stack are combined into the
+    // digits of the result, from the bottom to the top
+    edit_class(&classes, "Dups", |class_file| {
+        // the ints on the stack as digits of an int
+        // swap, then bipush 10, imul as many times as the place of the value below, iadd
+        let fold = |count| (1..count).flat_map(|place| [0x5f].into_iter().chain([0x10, 10, 0x68].repeat(place)).chain([0x60]));
+        // [7L, 3] -> [3, 7L, 3]
+        let dup_x2_long = [
+            0x10, 7, 0x85, 0x10, 3, // bipush 7, i2l, bipush 3
+            0x5b, // dup_x2
+            0x3b, 0x40, // istore_0, lstore_1
+            0x85, 0x10, 100, 0x85, 0x69, // i2l, bipush 100, i2l, lmul
+            0x1f, 0x10, 10, 0x85, 0x69, 0x61, // lload_1, bipush 10, i2l, lmul, ladd
+            0x1a, 0x85, 0x61, 0xad, // iload_0, i2l, ladd, lreturn
+        ];
+        set_code(class_file, "dupX2Long", 4, 3, &dup_x2_long);
+        // [1, 2, 3] -> [2, 3, 1, 2, 3]
+        let dup2_x1_ints: Vec<u8> = [0x10, 1, 0x10, 2, 0x10, 3, 0x5d].into_iter().chain(fold(5)).chain([0xac]).collect();
+        set_code(class_file, "dup2X1Ints", 5, 0, &dup2_x1_ints);
+        // [1, 2, 3, 4] -> [3, 4, 1, 2, 3, 4]
+        let dup2_x2_ints: Vec<u8> = [0x10, 1, 0x10, 2, 0x10, 3, 0x10, 4, 0x5e].into_iter().chain(fold(6)).chain([0xac]).collect();
+        set_code(class_file, "dup2X2Ints", 6, 0, &dup2_x2_ints);
+        // [7L, 1, 2] -> [1, 2, 7L, 1, 2]
+        let dup2_x2_ints_long: Vec<u8> = [
+            0x10, 7, 0x85, 0x10, 1, 0x10, 2, // bipush 7, i2l, bipush 1, bipush 2
+            0x5e, // dup2_x2
+        ].into_iter().chain(fold(2)).chain([
+            0x3b, 0x40, // istore_0, lstore_1
+        ]).chain(fold(2)).chain([
+            0x85, 0x11, 0x03, 0xe8, 0x85, 0x69, // i2l, sipush 1000, i2l, lmul
+            0x1f, 0x10, 100, 0x85, 0x69, 0x61, // lload_1, bipush 100, i2l, lmul, ladd
+            0x1a, 0x85, 0x61, 0xad, // iload_0, i2l, ladd, lreturn
+        ]).collect();
+        set_code(class_file, "dup2X2IntsLong", 8, 3, &dup2_x2_ints_long);
+        // [7L, 5L] -> [5L, 7L, 5L]
+        let dup2_x2_longs = [
+            0x10, 7, 0x85, 0x10, 5, 0x85, // bipush 7, i2l, bipush 5, i2l
+            0x5e, // dup2_x2
+            0x3f, 0x41, // lstore_0, lstore_2
+            0x10, 100, 0x85, 0x69, // bipush 100, i2l, lmul
+            0x20, 0x10, 10, 0x85, 0x69, 0x61, // lload_2, bipush 10, i2l, lmul, ladd
+            0x1e, 0x61, 0xad, // lload_0, ladd, lreturn
+        ];
+        set_code(class_file, "dup2X2Longs", 6, 4, &dup2_x2_longs);
+    });
+    let mut runtime = runtime(&classes);
+    let mut ctx = runtime.new_context();
+    assert_eq!(ctx.call_static::<i32>("Dups", "dupX1", "()I", ()).unwrap(), Ok(55));
+    assert_eq!(ctx.call_static::<i32>("Dups", "dupX2", "()I", ()).unwrap(), Ok(773));
+    assert_eq!(ctx.call_static::<i32>("Dups", "dup2", "()I", ()).unwrap(), Ok(48));
+    assert_eq!(ctx.call_static::<i64>("Dups", "dup2Long", "()J", ()).unwrap(), Ok(40041));
+    assert_eq!(ctx.call_static::<f64>("Dups", "dup2Double", "()D", ()).unwrap(), Ok(27.5));
+    assert_eq!(ctx.call_static::<i64>("Dups", "dup2X1", "()J", ()).unwrap(), Ok(-6000000000));
+    assert_eq!(ctx.call_static::<i64>("Dups", "dup2X2", "()J", ()).unwrap(), Ok((1 << 41) + 2));
+    assert_eq!(ctx.call_static::<f64>("Dups", "dup2X2Double", "()D", ()).unwrap(), Ok(-0.5));
+    assert_eq!(ctx.call_static::<i64>("Dups", "dupX2Long", "()J", ()).unwrap(), Ok(373));
+    assert_eq!(ctx.call_static::<i32>("Dups", "dup2X1Ints", "()I", ()).unwrap(), Ok(23123));
+    assert_eq!(ctx.call_static::<i32>("Dups", "dup2X2Ints", "()I", ()).unwrap(), Ok(341234));
+    assert_eq!(ctx.call_static::<i64>("Dups", "dup2X2IntsLong", "()J", ()).unwrap(), Ok(12712));
+    assert_eq!(ctx.call_static::<i64>("Dups", "dup2X2Longs", "()J", ()).unwrap(), Ok(575));
+}
+
+#[test]
+fn wide_constants_keep_their_bits() {
+    let constants = ("Constants", "
+        class Constants {
+            static long min() { return Long.MIN_VALUE; }
+            static long minusOne() { return -1L; }
+            static long negative() { return -1234567890123L; }
+            static String after() { return \"after\"; }
+            static double negativeDouble() { return -2.5e-300; }
+            static double negativeZero() { return -0.0; }
+            static double nan() { return Double.NaN; }
+            static double payload() { return 1.75; }
+            static double negativeNan() { return 3.25; }
+        }
+    ");
+    let classes = compile("constants", &[constants]);
+    // NaNs that are not the canonical one, which Java source cannot write as constants
+    let (payload, negative_nan) = (0x7ff0_0000_dead_beef_u64, 0xfff8_0000_0000_0001_u64);
+    edit_class(&classes, "Constants", |class_file| {
+        for constant in &mut *class_file.constant_pool {
+            let &mut Constant::Double { high_bytes, low_bytes } = constant else { continue };
+            let bits = match f64::from_bits(((high_bytes as u64) << 32) | low_bytes as u64) {
+                1.75 => payload,
+                3.25 => negative_nan,
+                _ => continue,
+            };
+            *constant = Constant::Double { high_bytes: (bits >> 32) as u32, low_bytes: bits as u32 };
+        }
+    });
+    let mut runtime = runtime(&classes);
+    let mut ctx = runtime.new_context();
+    assert_eq!(ctx.call_static::<i64>("Constants", "min", "()J", ()).unwrap(), Ok(i64::MIN));
+    assert_eq!(ctx.call_static::<i64>("Constants", "minusOne", "()J", ()).unwrap(), Ok(-1));
+    assert_eq!(ctx.call_static::<i64>("Constants", "negative", "()J", ()).unwrap(), Ok(-1234567890123));
+    assert_eq!(ctx.call_static::<String>("Constants", "after", "()Ljava/lang/String;", ()).unwrap().unwrap(), "after");
+    let double = |ctx: &mut RuntimeCtx, method| ctx.call_static::<f64>("Constants", method, "()D", ()).unwrap().unwrap().to_bits();
+    assert_eq!(double(&mut ctx, "negativeDouble"), (-2.5e-300f64).to_bits());
+    assert_eq!(double(&mut ctx, "negativeZero"), (-0.0f64).to_bits());
+    assert_eq!(double(&mut ctx, "nan"), f64::NAN.to_bits());
+    assert_eq!(double(&mut ctx, "payload"), payload);
+    assert_eq!(double(&mut ctx, "negativeNan"), negative_nan);
+}
+
+#[test]
+fn lambdas_implement_functional_interfaces() {
+    let shout = ("Shout", "interface Shout extends java.util.function.Function<String, String> { String apply(String s); }");
+    let lambdas = ("Lambdas", "
+        import java.io.Serializable;
+        import java.util.function.*;
+        class Lambdas {
+            int base;
+            Lambdas(int base) { this.base = base; }
+            int plus(int x) { return base + x; }
+            static int twice(int x) { return 2 * x; }
+            static String references(String prefix, int n) {
+                Lambdas lambdas = new Lambdas(n);
+                IntUnaryOperator bound = lambdas::plus;
+                IntUnaryOperator unbound = Lambdas::twice;
+                BiFunction<Lambdas, Integer, Integer> boxed = Lambdas::plus;
+                Function<Integer, Lambdas> constructor = Lambdas::new;
+                Supplier<String> captured = () -> prefix + n;
+                // through the bridge the compiler asks for
+                Function<String, String> shout = (Shout) s -> s + \"!\";
+                UnaryOperator<String> twice = s -> s + s;
+                Runnable serializable = (Runnable & Serializable) () -> {};
+                return bound.applyAsInt(1) + \" \" + unbound.applyAsInt(5) + \" \" + boxed.apply(lambdas, 2) + \" \"
+                    + constructor.apply(9).base + \" \" + captured.get() + \" \" + shout.apply(\"hi\") + \" \"
+                    + twice.apply(\"ab\") + \" \" + (serializable instanceof Serializable);
+            }
+            static long primitives() {
+                ToLongFunction<String> length = s -> s.length() * 1_000_000_000_000L;
+                ToDoubleFunction<Integer> half = i -> i / 2.0;
+                ObjIntConsumer<int[]> add = (cell, i) -> cell[0] += i;
+                IntToLongFunction widen = i -> i;
+                LongToIntFunction high = l -> (int) (l >> 32);
+                DoubleSupplier supplier = () -> 3.5;
+                BooleanSupplier yes = () -> true;
+                int[] cell = { 0 };
+                add.accept(cell, 7);
+                return length.applyAsLong(\"abc\") + (long) (half.applyAsDouble(9) * 2) + cell[0] + widen.applyAsLong(-1)
+                    + high.applyAsInt(5L << 32) + (long) supplier.getAsDouble() + (yes.getAsBoolean() ? 100 : 0);
+            }
+        }
+    ");
+    let classes = compile("lambdas", &[shout, lambdas]);
+    let mut runtime = runtime(&classes);
+    let mut ctx = runtime.new_context();
+    let references = ctx.call_static::<String>("Lambdas", "references", "(Ljava/lang/String;I)Ljava/lang/String;", ("x", 4)).unwrap();
+    assert_eq!(references.unwrap(), "5 10 6 9 x4 hi! abab true");
+    let primitives = ctx.call_static::<i64>("Lambdas", "primitives", "()J", ()).unwrap();
+    assert_eq!(primitives.unwrap(), 3_000_000_000_000 + 9 + 7 - 1 + 5 + 3 + 100);
+}
+
+#[test]
+fn headers_give_objects_their_class() {
+    let types = ("Types", "
+        interface Pet {}
+        class Animal {}
+        class Dog extends Animal implements Pet {}
+        class Types {
+            static String checks() {
+                Object dog = new Dog(), animal = new Animal(), dogs = new Dog[1], ints = new int[1];
+                boolean[] checks = {
+                    dog instanceof Animal, dog instanceof Pet, !(animal instanceof Pet), !(animal instanceof Dog),
+                    dogs instanceof Animal[], dogs instanceof Pet[], dogs instanceof Object[], !(dogs instanceof Dog),
+                    dogs instanceof Cloneable, dogs instanceof java.io.Serializable, ints instanceof Object,
+                    !(ints instanceof long[]), !(ints instanceof Object[]), !(null instanceof Object),
+                    dog.getClass() == Dog.class, dog.getClass() != animal.getClass(), dog.hashCode() == dog.hashCode(),
+                };
+                String result = \"\";
+                for (boolean check : checks) {
+                    result += check ? \"+\" : \"-\";
+                }
+                try {
+                    Pet pet = (Pet) animal;
+                    result += \"-\";
+                } catch (ClassCastException e) {
+                    result += \"+\";
+                }
+                Animal[] animals = (Animal[]) dogs;
+                return result + \" \" + animals.length;
+            }
+        }
+    ");
+    let classes = compile("headers", &[types]);
+    let mut runtime = runtime(&classes);
+    let mut ctx = runtime.new_context();
+    assert_eq!(ctx.call_static::<String>("Types", "checks", "()Ljava/lang/String;", ()).unwrap().unwrap(), format!("{} 1", "+".repeat(18)));
+
+    let dog = ctx.new_instance("Dog", "()V", ()).unwrap().unwrap();
+    assert_eq!(ctx.get_class_name(dog), "Dog");
+    assert_eq!(ctx.get_header(dog).identity_hash(), None, "the hash is assigned on first use");
+    let hash = ctx.call_method::<i32>(dog, "hashCode", "()I", ()).unwrap().unwrap();
+    assert_eq!(ctx.get_header(dog).identity_hash(), Some(hash as u32));
+    assert_eq!(ctx.identity_hash(dog), hash as u32);
+    let string = ctx.call_method::<String>(dog, "toString", "()Ljava/lang/String;", ()).unwrap().unwrap();
+    assert_eq!(string, format!("Dog@{hash:x}"));
+    let other = ctx.new_instance("Dog", "()V", ()).unwrap().unwrap();
+    assert_ne!(ctx.identity_hash(other), hash as u32);
+}
+
+#[test]
+fn static_initializers_run_once_in_order() {
+    let sources = [
+        ("Log", "class Log { static String log = \"\"; static int note(String s) { log += s + \" \"; return 1; } }"),
+        ("Base", "class Base { static int base = Log.note(\"Base\"); }"),
+        ("ByNew", "class ByNew extends Base { static int x = Log.note(\"ByNew\"); }"),
+        ("ByGet", "class ByGet { static int x = Log.note(\"ByGet\"); static final int CONSTANT = 3; }"),
+        ("ByPut", "class ByPut { static int x = Log.note(\"ByPut\"); }"),
+        ("ByCall", "class ByCall extends Base { static int x = Log.note(\"ByCall\"); static int call() { return x; } }"),
+        ("First", "class First { static int first = Second.second + 1; }"),
+        ("Second", "class Second { static int second = First.first + 10; }"),
+        ("Bad", "class Bad { static int x = 1 / Integer.parseInt(\"0\"); }"),
+        ("Preloaded", "class Preloaded { static int seen = Peek.peek(); static int kept = 7; }"),
+        ("Peek", "class Peek { static int peek() { return Preloaded.kept; } }"),
+        ("Init", "
+            class Init {
+                static String order() {
+                    int constant = ByGet.CONSTANT;
+                    Log.note(\"start\");
+                    new ByNew();
+                    new ByNew();
+                    int x = ByGet.x;
+                    ByPut.x = 2;
+                    ByCall.call();
+                    return Log.log;
+                }
+                static int recursive() { return First.first * 100 + Second.second; }
+                static String failing() {
+                    String result = \"\";
+                    for (int i = 0; i < 2; i++) {
+                        try {
+                            result += Bad.x;
+                        } catch (Throwable e) {
+                            result += e.getClass().getName() + \" \";
+                        }
+                    }
+                    return result;
+                }
+                static int preloaded() { return Preloaded.seen; }
+            }
+        "),
+    ];
+    compile("clinit", &sources);
+    // the field becomes a constant the other class does not inline, so it is preloaded from its
+    // ConstantValue before the initializer reads it
+    let classes = compile("clinit", &[("Preloaded", "class Preloaded { static int seen = Peek.peek(); static final int kept = 7; }")]);
+    let mut runtime = runtime(&classes);
+    let mut ctx = runtime.new_context();
+    let order = ctx.call_static::<String>("Init", "order", "()Ljava/lang/String;", ()).unwrap().unwrap();
+    assert_eq!(order, "start Base ByNew ByGet ByPut ByCall ");
+    // each sees the other before it is initialized
+    assert_eq!(ctx.call_static::<i32>("Init", "recursive", "()I", ()).unwrap(), Ok(11 * 100 + 10));
+    let failing = ctx.call_static::<String>("Init", "failing", "()Ljava/lang/String;", ()).unwrap().unwrap();
+    assert_eq!(failing, "java.lang.ExceptionInInitializerError java.lang.NoClassDefFoundError ");
+    assert_eq!(ctx.call_static::<i32>("Init", "preloaded", "()I", ()).unwrap(), Ok(7));
+}
+
+#[test]
+fn strings_are_compact_objects_with_an_intern_table() {
+    let sources = [
+        ("Other", "class Other { static String literal() { return \"jappuccino\"; } }"),
+        ("Strings", "
+            class Strings {
+                static String identity() {
+                    String literal = \"jappuccino\", built = \"jap\".concat(\"puccino\");
+                    return (literal == Other.literal()) + \" \" + (built == literal) + \" \" + built.equals(literal)
+                        + \" \" + (built.intern() == literal) + \" \" + (built.toString() == built);
+                }
+                static String operations(String s) {
+                    return s.length() + \" \" + s.charAt(1) + \" \" + s.substring(2) + \" \" + s.substring(1, 3) + \" \"
+                        + s.indexOf('l') + \" \" + s.indexOf(\"lo\") + \" \" + s.indexOf('z') + \" \"
+                        + Integer.signum(s.compareTo(\"help\")) + \" \" + \"a\".compareTo(\"abc\") + \" \" + s.hashCode();
+                }
+                static String outOfBounds(String s) {
+                    try {
+                        return \"\" + s.charAt(s.length());
+                    } catch (StringIndexOutOfBoundsException e) {
+                        return \"thrown\";
+                    }
+                }
+                static String copy(String s) { return s.concat(\"\"); }
+            }
+        "),
+    ];
+    let classes = compile("strings", &sources);
+    let mut runtime = runtime(&classes);
+    let mut ctx = runtime.new_context();
+    let identity = ctx.call_static::<String>("Strings", "identity", "()Ljava/lang/String;", ()).unwrap().unwrap();
+    assert_eq!(identity, "true false true true true");
+    for (s, expected) in [("hello", "5 e llo el 2 3 -1 -1 -2"), ("hé€lo", "5 é €lo é€ 3 3 -1 1 -2")] {
+        let chars: Vec<u16> = s.encode_utf16().collect();
+        let expected = format!("{expected} {}", super::string::hash_code(&chars));
+        let operations = ctx.call_static::<String>("Strings", "operations", "(Ljava/lang/String;)Ljava/lang/String;", (s,)).unwrap().unwrap();
+        assert_eq!(operations, expected);
+    }
+    assert_eq!(ctx.call_static::<String>("Strings", "outOfBounds", "(Ljava/lang/String;)Ljava/lang/String;", ("ab",)).unwrap().unwrap(), "thrown");
+
+    // one byte per character while they all fit, two otherwise
+    for (s, coder) in [("plain", Coder::Latin1), ("café", Coder::Latin1), ("€uro", Coder::Utf16), ("😀", Coder::Utf16)] {
+        let string = ctx.call_static::<Value>("Strings", "copy", "(Ljava/lang/String;)Ljava/lang/String;", (s,)).unwrap().unwrap();
+        assert_eq!(Coder::from_u8(ctx.read_u8_ref(string.offset(STRING_CODER_OFFSET)).unwrap()), coder, "{s}");
+        assert_eq!(ctx.read_string_chars(string).unwrap(), s.encode_utf16().collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn natives_get_typed_arguments() {
+    let hasher = ("Hasher", "
+        class Hasher {
+            int factor = 3;
+            static native long mix(int a, long b, double c, boolean d, char e, String s);
+            native int scaled(int x);
+            static native void unbound();
+            static long callMix() { return mix(-2, 1L << 40, 0.5, true, 'x', \"héllo\"); }
+            static int callScaled() { return new Hasher().scaled(7); }
+            static String callUnbound() {
+                try {
+                    unbound();
+                    return \"bound\";
+                } catch (UnsatisfiedLinkError e) {
+                    return e.getClass().getName();
+                }
+            }
+            static double root(double d) { return Math.sqrt(d); }
+        }
+    ");
+    let classes = compile("typed-natives", &[hasher]);
+    let mut runtime = runtime(&classes);
+    // registered before the class is loaded
+    runtime.register_native("Hasher", "mix", "(IJDZCLjava/lang/String;)J", |ctx, args| {
+        assert_eq!(args.len(), 6);
+        let text = ctx.read_string_object(args.reference(5)).unwrap();
+        assert_eq!((args.int(0), args.double(2), args.boolean(3), args.char(4), &*text), (-2, 0.5, true, 'x' as u16, "héllo"));
+        Ok(JValue::Long(args.long(1) + text.chars().count() as i64))
+    }).unwrap();
+    runtime.load_class("Hasher").unwrap();
+    // and after, for an instance method that gets the receiver first
+    runtime.register_native("Hasher", "scaled", "(I)I", |ctx, args| {
+        let factor = ctx.get_field::<i32>(args.reference(0), "factor", "I")?.unwrap();
+        Ok(JValue::Int(args.int(1) * factor))
+    }).unwrap();
+    // builtin methods can be replaced, and natives of no class make up one
+    runtime.register_native("java/lang/Math", "sqrt", "(D)D", |_, args| Ok(JValue::Double(-args.double(0)))).unwrap();
+    runtime.register_native("Host", "answer", "()I", |_, _| Ok(JValue::Int(42))).unwrap();
+
+    let mut ctx = runtime.new_context();
+    assert_eq!(ctx.call_static::<i64>("Hasher", "callMix", "()J", ()).unwrap(), Ok((1 << 40) + 5));
+    assert_eq!(ctx.call_static::<i32>("Hasher", "callScaled", "()I", ()).unwrap(), Ok(21));
+    let unbound = ctx.call_static::<String>("Hasher", "callUnbound", "()Ljava/lang/String;", ()).unwrap();
+    assert_eq!(unbound.unwrap(), "java.lang.UnsatisfiedLinkError");
+    assert_eq!(ctx.call_static::<f64>("Hasher", "root", "(D)D", (4.0,)).unwrap(), Ok(-4.0));
+    assert_eq!(ctx.call_static::<i32>("Host", "answer", "()I", ()).unwrap(), Ok(42));
+}
+
+#[test]
+fn host_values_convert_both_ways() {
+    let rules = ("Rules", "
+        class Rules {
+            static String describe(boolean z, byte b, char c, short s, int i, long j, float f, double d, String text, Object nothing) {
+                return z + \" \" + b + \" \" + c + \" \" + s + \" \" + i + \" \" + j + \" \" + f + \" \" + d + \" \" + text + \" \" + nothing;
+            }
+            static String nothing() { return null; }
+            static char next(char c) { return (char) (c + 1); }
+            static double half(long value) { return value / 2.0; }
+            static int fail(String message) { throw new IllegalArgumentException(message); }
+            int total;
+            Rules(int start) { total = start; }
+            long add(long value) { total += (int) value; return total; }
+        }
+    ");
+    let classes = compile("host-values", &[rules]);
+    let mut runtime = runtime(&classes);
+    let mut ctx = runtime.new_context();
+    let descriptor = "(ZBCSIJFDLjava/lang/String;Ljava/lang/Object;)Ljava/lang/String;";
+    let args = (true, -5i8, 'é' as u16, -300i16, 1 << 20, -(1i64 << 40), 1.5f32, -0.25, "text", None::<Value>);
+    let described = ctx.call_static::<String>("Rules", "describe", descriptor, args).unwrap();
+    assert_eq!(described.unwrap(), "true -5 é -300 1048576 -1099511627776 1.5 -0.25 text null");
+    assert_eq!(ctx.call_static::<Option<String>>("Rules", "nothing", "()Ljava/lang/String;", ()).unwrap(), Ok(None));
+    assert_eq!(ctx.call_static::<u16>("Rules", "next", "(C)C", ('a' as u16,)).unwrap(), Ok('b' as u16));
+    assert_eq!(ctx.call_static::<f64>("Rules", "half", "(J)D", (7i64,)).unwrap(), Ok(3.5));
+
+    // the exception comes back with its message
+    let exception = ctx.call_static::<i32>("Rules", "fail", "(Ljava/lang/String;)I", ("no",)).unwrap().unwrap_err();
+    assert_eq!(ctx.get_class_name(exception), "java/lang/IllegalArgumentException");
+    assert_eq!(ctx.throwable_message(exception).as_deref(), Some("no"));
+
+    // objects live on between calls
+    let rules = ctx.new_instance("Rules", "(I)V", (10,)).unwrap().unwrap();
+    assert_eq!(ctx.call_method::<i64>(rules, "add", "(J)J", (5i64,)).unwrap(), Ok(15));
+    assert_eq!(ctx.call_method::<i64>(rules, "add", "(J)J", (-20i64,)).unwrap(), Ok(-5));
+    ctx.set_field(rules, "total", "I", 100).unwrap().unwrap();
+    assert_eq!(ctx.get_field::<i32>(rules, "total", "I").unwrap(), Ok(100));
+
+    // values of the wrong type are errors of the runtime, not exceptions
+    for result in [
+        ctx.call_static::<i32>("Rules", "nothing", "()Ljava/lang/String;", ()).map(drop),
+        ctx.call_static::<f64>("Rules", "half", "(J)D", (7,)).map(drop),
+        ctx.call_static::<String>("Rules", "nothing", "()Ljava/lang/String;", ()).map(drop),
+        ctx.set_field(rules, "total", "I", 1.5).map(drop),
+    ] {
+        assert!(matches!(result, Err(RtError::TypeMismatch { .. })), "{result:?}");
+    }
+}
+
+#[test]
+fn string_concatenation_formats_every_type() {
+    let concat = ("Joiner", "
+        class Joiner {
+            public String toString() { return \"joiner\"; }
+            static String all(int i, long j, char c, boolean z, float f, double d, byte b, short s, String text, Object o) {
+                return \"i=\" + i + \" j=\" + j + \" c=\" + c + \" z=\" + z + \" f=\" + f + \" d=\" + d + \" b=\" + b + \" s=\" + s
+                    + \" text=\" + text + \" o=\" + o + \" tags=\\u0001\\u0002 \" + new Joiner() + (char[]) null;
+            }
+            static String repeat(int n) {
+                String result = \"\";
+                for (int i = 0; i < n; i++) {
+                    result += i;
+                }
+                return result;
+            }
+        }
+    ");
+    let classes = compile("concat-types", &[concat]);
+    let mut runtime = runtime(&classes);
+    let mut ctx = runtime.new_context();
+    let descriptor = "(IJCZFDBSLjava/lang/String;Ljava/lang/Object;)Ljava/lang/String;";
+    let args = (-1, 1i64 << 40, 'ß' as u16, false, 0.1f32, 1e21, -8i8, 300i16, None::<String>, None::<Value>);
+    let all = ctx.call_static::<String>("Joiner", "all", descriptor, args).unwrap().unwrap();
+    assert_eq!(all, "i=-1 j=1099511627776 c=ß z=false f=0.1 d=1.0E21 b=-8 s=300 text=null o=null tags=\u{1}\u{2} joinernull");
+
+    // a call site is linked once and then reused
+    assert_eq!(ctx.call_static::<String>("Joiner", "repeat", "(I)Ljava/lang/String;", (12,)).unwrap().unwrap(), "01234567891011");
+    let sites = ctx.runtime.call_sites.len();
+    assert_eq!(ctx.call_static::<String>("Joiner", "repeat", "(I)Ljava/lang/String;", (3,)).unwrap().unwrap(), "012");
+    assert_eq!(ctx.runtime.call_sites.len(), sites);
+}
@@ -0,0 +1,548 @@
+//! Green threads and monitors.
+//!
+//! Every started `java.lang.Thread` gets its own operand and return stack. The context runs one
+//! thread at a time on the OS thread of the embedder: the registers of the running thread live in
+//! the [`RuntimeCtx`] and those of the others are saved in their [`JavaThread`]. Threads switch
+//! round-robin between two instructions, when the running one blocks or has executed a time slice
+//! of instructions, so a program runs the same way every time.
+//!
+//! Time is virtual as well: the clock advances with every instruction executed, and jumps ahead to
+//! the next wake-up when all threads are sleeping. When no thread can ever run again the run stops
+//! with [`RtError::Deadlock`].
+//!
+//! A monitor that is held sets [`HeaderFlags::LOCKED`] in the header of its object, its owner and
+//! recursion count are kept on the side.
+//!
+//! Native code waiting for a method it called only continues once that method returned, so a
+//! thread in such a call can only be switched to while it is the innermost caller.
+
+use std::{collections::BTreeMap, mem::take};
+
+use crate::descriptor::MethodDescriptor;
+
+use super::{stdio, string::Coder, Frame, HeaderFlags, InitState, Result, RtError, RuntimeCtx, StopReason, Value, OBJECT_HEADER_SIZE};
+
+/// The thread that runs `main`
+pub(super) const MAIN_THREAD: usize = 0;
+const MAIN_THREAD_NAME: &str = "main";
+/// Default for [`Runtime::with_time_slice`](super::Runtime::with_time_slice)
+pub(super) const DEFAULT_TIME_SLICE: u64 = 10_000;
+/// Ticks of the virtual clock, which are instructions, in a millisecond
+const TICKS_PER_MILLI: u64 = 1000;
+
+/// Instance field offsets of the builtin `java.lang.Thread`
+pub(super) const THREAD_TARGET_OFFSET: u32 = OBJECT_HEADER_SIZE as u32;
+pub(super) const THREAD_NAME_OFFSET: u32 = THREAD_TARGET_OFFSET + 4;
+/// Index of the thread plus one, 0 until it is started
+pub(super) const THREAD_ID_OFFSET: u32 = THREAD_NAME_OFFSET + 4;
+pub(super) const THREAD_DAEMON_OFFSET: u32 = THREAD_ID_OFFSET + 4;
+
+/// What a thread is doing. Threads that wait keep the number they were blocked or started waiting
+/// in `seq`, the one that waited longest goes first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ThreadState {
+    Runnable,
+    /// Waiting to own a monitor, which it then holds `count` times
+    Blocked { monitor: Value, count: u32, seq: u64 },
+    /// In `Object.wait`, until notified or the clock reaches `until`
+    Waiting { monitor: Value, count: u32, until: Option<u64>, seq: u64 },
+    Sleeping { until: u64 },
+    /// In `Thread.join`, until the thread terminates or the clock reaches `until`
+    Joining { thread: usize, until: Option<u64> },
+    /// Waiting for another thread to finish running the static initializer of a class
+    Initializing { class: u32 },
+    Terminated,
+}
+
+impl ThreadState {
+    /// When the thread wakes up by itself
+    fn until(self) -> Option<u64> {
+        match self {
+            ThreadState::Sleeping { until } => Some(until),
+            ThreadState::Waiting { until, .. } | ThreadState::Joining { until, .. } => until,
+            _ => None,
+        }
+    }
+}
+
+/// The registers of a thread while another one runs
+#[derive(Debug, Default)]
+struct Registers {
+    stack: Vec<Value>,
+    return_stack: Vec<Frame>,
+    frame_pointer: u32,
+    max_locals: u16,
+    cur_class: u32,
+    cur_method: u16,
+    pc: usize,
+    barrier: Option<usize>,
+    pending_exception: Option<Value>,
+    locked_frames: Vec<(usize, Value)>,
+}
+
+#[derive(Debug)]
+pub(super) struct JavaThread {
+    /// The `java.lang.Thread`, null for the main thread until `Thread.currentThread()` asks for it
+    object: Value,
+    state: ThreadState,
+    daemon: bool,
+    saved: Registers,
+}
+
+impl JavaThread {
+    fn new(object: Value, daemon: bool) -> Self {
+        Self { object, state: ThreadState::Runnable, daemon, saved: Registers::default() }
+    }
+    /// Values the collector has to keep in place for this thread
+    pub fn roots(&self) -> impl Iterator<Item = Value> + '_ {
+        let monitor = match self.state {
+            ThreadState::Blocked { monitor, .. } | ThreadState::Waiting { monitor, .. } => Some(monitor),
+            _ => None,
+        };
+        self.saved.stack.iter().copied().chain([self.object]).chain(monitor)
+    }
+}
+
+/// The owner of a monitor that is held
+#[derive(Debug, Clone, Copy)]
+struct Monitor {
+    owner: usize,
+    count: u32,
+}
+
+/// The threads of a context and the monitors they hold
+#[derive(Debug)]
+pub(super) struct Scheduler {
+    threads: Vec<JavaThread>,
+    current: usize,
+    /// The thread that native code waits for in the innermost nested run
+    run_owner: usize,
+    /// Held monitors by their object
+    monitors: BTreeMap<u32, Monitor>,
+    time_slice: u64,
+    /// Instruction count at which the running thread is switched out
+    slice_end: u64,
+    /// Ticks the clock skipped while all threads were sleeping
+    idle_ticks: u64,
+    seq: u64,
+    /// Number for the name of the next unnamed thread
+    next_number: u32,
+}
+
+impl Scheduler {
+    pub fn new(time_slice: u64) -> Self {
+        Self {
+            threads: vec![JavaThread::new(Value::NULL, false)],
+            current: MAIN_THREAD,
+            run_owner: MAIN_THREAD,
+            monitors: BTreeMap::new(),
+            time_slice,
+            slice_end: time_slice,
+            idle_ticks: 0,
+            seq: 0,
+            next_number: 0,
+        }
+    }
+    /// Values the collector has to keep in place, besides the stack of the running thread
+    pub fn roots(&self) -> impl Iterator<Item = Value> + '_ {
+        self.threads.iter().flat_map(JavaThread::roots).chain(self.monitors.keys().map(|&object| Value(object)))
+    }
+    fn next_seq(&mut self) -> u64 {
+        self.seq += 1;
+        self.seq
+    }
+}
+
+impl RuntimeCtx<'_> {
+    pub(super) fn current_thread(&self) -> usize {
+        self.sched.current
+    }
+    pub(super) fn thread_count(&self) -> usize {
+        self.sched.threads.len()
+    }
+    pub(super) fn thread_state(&self, thread: usize) -> ThreadState {
+        self.sched.threads[thread].state
+    }
+    fn state(&self) -> ThreadState {
+        self.sched.threads[self.sched.current].state
+    }
+    fn set_state(&mut self, state: ThreadState) {
+        self.sched.threads[self.sched.current].state = state;
+    }
+    /// The virtual time in ticks
+    fn clock(&self) -> u64 {
+        self.instructions + self.sched.idle_ticks
+    }
+    /// Whether the run loop of `owner` has to execute more instructions to get the return stack of
+    /// `owner` down to `depth`, switching threads first when the running one cannot go on
+    pub(super) fn keep_running(&mut self, owner: usize, depth: usize) -> Result<bool> {
+        loop {
+            let runnable = self.state() == ThreadState::Runnable;
+            if self.sched.threads[owner].state == ThreadState::Terminated
+                || (self.sched.current == owner && runnable && self.return_stack.len() <= depth) {
+                return Ok(false);
+            }
+            if runnable && !self.return_stack.is_empty() && self.instructions < self.sched.slice_end {
+                return Ok(true);
+            }
+            if runnable && self.return_stack.is_empty() {
+                self.end_thread();
+                // a run started by native code has to get back to its caller, the top level stops
+                // once only daemon threads are left
+                if self.nested_runs == 0 && !self.has_user_threads() {
+                    return Ok(false);
+                }
+            }
+            self.schedule()?;
+        }
+    }
+    /// Runs the threads until only daemon threads are left.
+    ///
+    /// An exception `main` does not catch ends the main thread like any other, and is returned
+    /// once the others are done.
+    pub(super) fn run_threads(&mut self) -> Result<()> {
+        let mut uncaught = Ok(());
+        loop {
+            match self.run_until(0) {
+                Ok(()) => (),
+                Err(e @ RtError::UncaughtException { .. }) if self.sched.current == MAIN_THREAD => uncaught = Err(e),
+                Err(e) => return Err(e),
+            }
+            if self.return_stack.is_empty() && self.state() != ThreadState::Terminated {
+                self.end_thread();
+            }
+            if !self.has_user_threads() {
+                return uncaught;
+            }
+            self.schedule()?;
+        }
+    }
+    /// Whether a thread that is not a daemon has not terminated yet
+    fn has_user_threads(&self) -> bool {
+        self.sched.threads.iter().any(|t| !t.daemon && t.state != ThreadState::Terminated)
+    }
+    /// Makes the current thread the one the nested run started by native code waits for, returns
+    /// the previous one for [`Self::leave_nested_run`]
+    pub(super) fn enter_nested_run(&mut self) -> usize {
+        std::mem::replace(&mut self.sched.run_owner, self.sched.current)
+    }
+    /// Switches back to the thread of a nested run that is over, which stopped with an error if
+    /// another thread is running
+    pub(super) fn leave_nested_run(&mut self, outer_owner: usize) {
+        let owner = std::mem::replace(&mut self.sched.run_owner, outer_owner);
+        self.switch_to(owner);
+    }
+    fn end_thread(&mut self) {
+        let current = self.sched.current;
+        self.set_state(ThreadState::Terminated);
+        if let Some(debug) = &mut self.debug {
+            debug.thread_changed(StopReason::ThreadEnded(current));
+        }
+        for thread in &mut self.sched.threads {
+            if let ThreadState::Joining { thread: joined, .. } = thread.state && joined == current {
+                thread.state = ThreadState::Runnable;
+            }
+        }
+    }
+    /// Whether the thread can run now. Only the main thread runs before `main` is invoked, and a
+    /// thread native code waits for only while it is the innermost one.
+    fn is_schedulable(&self, thread: usize) -> bool {
+        let barrier = if thread == self.sched.current { self.barrier } else { self.sched.threads[thread].saved.barrier };
+        self.sched.threads[thread].state == ThreadState::Runnable
+            && (barrier.is_none() || thread == self.sched.run_owner)
+            && (self.pending_main.is_none() || thread == MAIN_THREAD)
+    }
+    /// Switches to the next thread that can run, the current one last
+    fn schedule(&mut self) -> Result<()> {
+        loop {
+            self.wake_up_timed();
+            let count = self.sched.threads.len();
+            let current = self.sched.current;
+            if let Some(next) = (1..=count).map(|i| (current + i) % count).find(|&t| self.is_schedulable(t)) {
+                self.switch_to(next);
+                self.sched.slice_end = self.instructions + self.sched.time_slice;
+                return Ok(());
+            }
+            // nothing can run until the next thread wakes up, skip the time in between
+            let Some(until) = self.sched.threads.iter().filter_map(|t| t.state.until()).min() else {
+                return Err(self.deadlock());
+            };
+            self.sched.idle_ticks += until - self.clock();
+        }
+    }
+    fn wake_up_timed(&mut self) {
+        let now = self.clock();
+        for t in 0..self.sched.threads.len() {
+            let state = self.sched.threads[t].state;
+            if state.until().is_none_or(|until| until > now) {
+                continue;
+            }
+            if let ThreadState::Waiting { monitor, count, .. } = state {
+                let seq = self.sched.next_seq();
+                self.sched.threads[t].state = ThreadState::Blocked { monitor, count, seq };
+                if !self.sched.monitors.contains_key(&monitor.into_u32()) {
+                    self.grant_monitor(monitor);
+                }
+            } else {
+                self.sched.threads[t].state = ThreadState::Runnable;
+            }
+        }
+    }
+    pub(super) fn switch_to(&mut self, thread: usize) {
+        if thread == self.sched.current {
+            return;
+        }
+        let saved = Registers {
+            stack: take(&mut self.stack),
+            return_stack: take(&mut self.return_stack),
+            frame_pointer: self.frame_pointer,
+            max_locals: self.max_locals,
+            cur_class: self.cur_class,
+            cur_method: self.cur_method,
+            pc: self.pc,
+            barrier: self.barrier,
+            pending_exception: self.pending_exception,
+            locked_frames: take(&mut self.locked_frames),
+        };
+        self.sched.threads[self.sched.current].saved = saved;
+        let next = take(&mut self.sched.threads[thread].saved);
+        self.stack = next.stack;
+        self.return_stack = next.return_stack;
+        self.frame_pointer = next.frame_pointer;
+        self.max_locals = next.max_locals;
+        self.cur_class = next.cur_class;
+        self.cur_method = next.cur_method;
+        self.pc = next.pc;
+        self.barrier = next.barrier;
+        self.pending_exception = next.pending_exception;
+        self.locked_frames = next.locked_frames;
+        self.sched.current = thread;
+    }
+    fn deadlock(&self) -> RtError {
+        let threads = self.sched.threads.iter().enumerate().filter_map(|(t, thread)| {
+            let name = self.thread_name(t);
+            Some(match thread.state {
+                ThreadState::Blocked { monitor, .. } => match self.sched.monitors.get(&monitor.into_u32()) {
+                    Some(holder) => format!("\"{name}\" waiting to lock a monitor held by \"{}\"", self.thread_name(holder.owner)),
+                    None => format!("\"{name}\" waiting to lock a monitor"),
+                },
+                ThreadState::Waiting { .. } => format!("\"{name}\" waiting to be notified"),
+                ThreadState::Joining { thread, .. } => format!("\"{name}\" joining \"{}\"", self.thread_name(thread)),
+                ThreadState::Initializing { class } => match self.runtime.get_class(class).init_state {
+                    InitState::InProgress { thread, .. } => format!(
+                        "\"{name}\" waiting for \"{}\" to initialize {}",
+                        self.thread_name(thread),
+                        self.runtime.get_class(class).name.replace('/', "."),
+                    ),
+                    _ => format!("\"{name}\" waiting for a class to be initialized"),
+                },
+                ThreadState::Runnable => format!("\"{name}\" waiting for native code to return"),
+                ThreadState::Sleeping { .. } | ThreadState::Terminated => return None,
+            }.into_boxed_str())
+        }).collect();
+        RtError::Deadlock { threads }
+    }
+    pub(super) fn thread_name(&self, thread: usize) -> String {
+        let object = self.sched.threads[thread].object;
+        if object == Value::NULL {
+            return "main".into();
+        }
+        let name = Value(self.read_u32_ref(object.offset(THREAD_NAME_OFFSET)).unwrap());
+        self.read_string_object(name).unwrap_or_else(|| "null".into())
+    }
+    /// Prints an exception that ended a thread other than the main thread, like the default
+    /// uncaught exception handler
+    pub(super) fn report_uncaught(&mut self, exception: Value) {
+        let class_name = self.get_class_name(exception).replace('/', ".");
+        let mut line = format!("Exception in thread \"{}\" {class_name}", self.thread_name(self.sched.current));
+        if let Some(message) = self.throwable_message(exception) {
+            line = format!("{line}: {message}");
+        }
+        line.push('\n');
+        let _ = self.runtime.stdio.write(stdio::STDERR, line.as_bytes());
+    }
+    pub(super) fn is_main_thread(&self) -> bool {
+        self.sched.current == MAIN_THREAD
+    }
+
+    /// Makes the current thread own the monitor of a non-null object, or blocks it until it can
+    pub(super) fn monitor_enter(&mut self, object: Value) {
+        let current = self.sched.current;
+        match self.sched.monitors.get_mut(&object.into_u32()) {
+            Some(monitor) if monitor.owner == current => monitor.count += 1,
+            Some(_) => {
+                let seq = self.sched.next_seq();
+                self.set_state(ThreadState::Blocked { monitor: object, count: 1, seq });
+            }
+            None => self.lock(object, current, 1),
+        }
+    }
+    /// Leaves the monitor of a non-null object once, returns `false` if the current thread does
+    /// not own it
+    pub(super) fn monitor_exit(&mut self, object: Value) -> bool {
+        match self.sched.monitors.get_mut(&object.into_u32()) {
+            Some(monitor) if monitor.owner == self.sched.current => {
+                monitor.count -= 1;
+                if monitor.count == 0 {
+                    self.grant_monitor(object);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+    /// Releases the monitor of the object for `Object.wait`, for `millis` or until notified if
+    /// `None`. Returns `false` if the current thread does not own it.
+    pub(super) fn monitor_wait(&mut self, object: Value, millis: Option<u64>) -> bool {
+        let Some(&monitor) = self.sched.monitors.get(&object.into_u32()) else { return false };
+        if monitor.owner != self.sched.current {
+            return false;
+        }
+        let until = millis.map(|millis| self.clock().saturating_add(millis.saturating_mul(TICKS_PER_MILLI)));
+        let seq = self.sched.next_seq();
+        self.set_state(ThreadState::Waiting { monitor: object, count: monitor.count, until, seq });
+        self.grant_monitor(object);
+        true
+    }
+    /// Wakes the thread that waited longest on the monitor, or all of them, to compete for it once
+    /// the current thread leaves. Returns `false` if the current thread does not own it.
+    pub(super) fn monitor_notify(&mut self, object: Value, all: bool) -> bool {
+        if self.sched.monitors.get(&object.into_u32()).is_none_or(|monitor| monitor.owner != self.sched.current) {
+            return false;
+        }
+        let mut waiting: Vec<_> = self.sched.threads.iter().enumerate().filter_map(|(t, thread)| match thread.state {
+            ThreadState::Waiting { monitor, count, seq, .. } if monitor == object => Some((seq, t, count)),
+            _ => None,
+        }).collect();
+        waiting.sort_unstable();
+        if !all {
+            waiting.truncate(1);
+        }
+        for (_, t, count) in waiting {
+            let seq = self.sched.next_seq();
+            self.sched.threads[t].state = ThreadState::Blocked { monitor: object, count, seq };
+        }
+        true
+    }
+    /// Hands a monitor that was just released to the thread blocked on it the longest
+    fn grant_monitor(&mut self, object: Value) {
+        let next = self.sched.threads.iter().enumerate().filter_map(|(t, thread)| match thread.state {
+            ThreadState::Blocked { monitor, count, seq } if monitor == object => Some((seq, t, count)),
+            _ => None,
+        }).min();
+        match next {
+            Some((_, t, count)) => {
+                self.sched.threads[t].state = ThreadState::Runnable;
+                self.lock(object, t, count);
+            }
+            None => {
+                self.sched.monitors.remove(&object.into_u32());
+                let header = self.get_header(object);
+                self.set_header(object, header.with_flags(header.flags() - HeaderFlags::LOCKED));
+            }
+        }
+    }
+    fn lock(&mut self, object: Value, owner: usize, count: u32) {
+        self.sched.monitors.insert(object.into_u32(), Monitor { owner, count });
+        let header = self.get_header(object);
+        self.set_header(object, header.with_flags(header.flags() | HeaderFlags::LOCKED));
+    }
+
+    /// Starts a thread that runs the `run` method of the `java.lang.Thread`, returns `false` if it
+    /// was started before
+    pub(super) fn start_thread(&mut self, object: Value) -> Result<bool> {
+        if self.read_u32_ref(object.offset(THREAD_ID_OFFSET)).unwrap() != 0 {
+            return Ok(false);
+        }
+        let thread = self.sched.threads.len();
+        self.write_u32_ref(object.offset(THREAD_ID_OFFSET), thread as u32 + 1);
+        let daemon = self.read_u8_ref(object.offset(THREAD_DAEMON_OFFSET)).unwrap() != 0;
+        self.sched.threads.push(JavaThread::new(object, daemon));
+        if let Some(debug) = &mut self.debug {
+            debug.thread_changed(StopReason::ThreadStarted(thread));
+        }
+
+        // the first frame of the new thread is the call of `run`
+        let current = self.sched.current;
+        self.switch_to(thread);
+        let run = self.runtime.find_method(self.get_class_id(object), "run", &MethodDescriptor::new_void([]).into()).unwrap();
+        self.push(object);
+        let result = self.invoke(run.0, run.1);
+        self.switch_to(current);
+        result.map(|()| true)
+    }
+    /// The index of a started thread, `None` before it is started
+    pub(super) fn thread_index(&self, object: Value) -> Option<usize> {
+        (self.read_u32_ref(object.offset(THREAD_ID_OFFSET)).unwrap() as usize).checked_sub(1)
+    }
+    pub(super) fn is_thread_alive(&self, object: Value) -> bool {
+        self.thread_index(object).is_some_and(|t| self.sched.threads[t].state != ThreadState::Terminated)
+    }
+    /// Blocks the current thread until the thread of the object terminates, or for `millis`
+    pub(super) fn join_thread(&mut self, object: Value, millis: Option<u64>) {
+        if !self.is_thread_alive(object) {
+            return;
+        }
+        let thread = self.thread_index(object).unwrap();
+        let until = millis.map(|millis| self.clock().saturating_add(millis.saturating_mul(TICKS_PER_MILLI)));
+        self.set_state(ThreadState::Joining { thread, until });
+    }
+    /// Blocks the current thread until the thread that runs the static initializer of the class
+    /// is done with it
+    pub(super) fn wait_for_initialization(&mut self, class: u32) {
+        self.set_state(ThreadState::Initializing { class });
+    }
+    /// Lets the threads that waited for the static initializer of the class go on, which either
+    /// finished or failed
+    pub(super) fn initialization_done(&mut self, class: u32) {
+        for thread in &mut self.sched.threads {
+            if thread.state == (ThreadState::Initializing { class }) {
+                thread.state = ThreadState::Runnable;
+            }
+        }
+    }
+    /// Lets the current thread sleep for `millis`, or gives the others a turn for 0
+    pub(super) fn sleep(&mut self, millis: u64) {
+        if millis > 0 {
+            let until = self.clock().saturating_add(millis.saturating_mul(TICKS_PER_MILLI));
+            self.set_state(ThreadState::Sleeping { until });
+        }
+        self.yield_thread();
+    }
+    pub(super) fn yield_thread(&mut self) {
+        self.sched.slice_end = self.instructions;
+    }
+    /// The name of an unnamed thread
+    pub(super) fn next_thread_name(&mut self) -> String {
+        let number = self.sched.next_number;
+        self.sched.next_number += 1;
+        format!("Thread-{number}")
+    }
+    /// The `java.lang.Thread` of the current thread for `Thread.currentThread()`, created for the
+    /// main thread on first use. `None` means there was no room for it and an `OutOfMemoryError`
+    /// was thrown.
+    pub(super) fn current_thread_object(&mut self) -> Result<Option<Value>> {
+        if self.sched.threads[self.sched.current].object == Value::NULL {
+            let class = self.runtime.load_class("java/lang/Thread")?;
+            let name_size = Coder::Latin1.object_size(MAIN_THREAD_NAME.len() as u32);
+            if !self.make_room((self.runtime.get_class(class).get_aligned_data_size() as u32 + name_size) as usize)? {
+                return Ok(None);
+            }
+        }
+        self.thread_object(self.sched.current).map(Some)
+    }
+    /// The `java.lang.Thread` of a thread, created for the main thread on first use
+    pub(super) fn thread_object(&mut self, thread: usize) -> Result<Value> {
+        let object = self.sched.threads[thread].object;
+        if object != Value::NULL {
+            return Ok(object);
+        }
+        let class = self.runtime.load_class("java/lang/Thread")?;
+        let object = self.new_object(class);
+        let name = self.new_string_obj(MAIN_THREAD_NAME);
+        self.write_u32_ref(object.offset(THREAD_NAME_OFFSET), name.into_u32());
+        self.write_u32_ref(object.offset(THREAD_ID_OFFSET), MAIN_THREAD as u32 + 1);
+        self.sched.threads[MAIN_THREAD].object = object;
+        Ok(object)
+    }
+}
@@ -0,0 +1,127 @@
+//! A minimal reader for ZIP archives like JAR files.
+//!
+//! Only what loading classes needs is supported: the central directory, entries that are stored or
+//! compressed with DEFLATE, and checking their CRC-32. ZIP64, encryption and archives split over
+//! several files are not.
+
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+mod inflate;
+#[cfg(test)]
+mod tests;
+
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x0201_4b50;
+const LOCAL_FILE_HEADER: u32 = 0x0403_4b50;
+/// Size of the end of central directory record without the trailing comment
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+#[derive(Debug, Clone)]
+pub(crate) struct ZipArchive {
+    data: Box<[u8]>,
+    entries: BTreeMap<Box<str>, ZipEntry>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ZipEntry {
+    method: u16,
+    crc32: u32,
+    compressed_size: u32,
+    size: u32,
+    local_header_offset: u32,
+}
+
+impl ZipArchive {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_bytes(fs::read(path)?.into())
+    }
+    pub fn from_bytes(data: Box<[u8]>) -> io::Result<Self> {
+        // the record is at the very end, followed by a comment of up to 64 KiB
+        let search_start = data.len().saturating_sub(END_OF_CENTRAL_DIRECTORY_SIZE + u16::MAX as usize);
+        let end = (search_start..=data.len().saturating_sub(END_OF_CENTRAL_DIRECTORY_SIZE))
+            .rev()
+            .find(|&i| read_u32(&data, i) == Some(END_OF_CENTRAL_DIRECTORY))
+            .ok_or_else(|| invalid("no end of central directory record"))?;
+        let entry_count = read_u16(&data, end + 10).unwrap();
+        let mut offset = read_u32(&data, end + 16).unwrap() as usize;
+
+        let mut entries = BTreeMap::new();
+        for _ in 0..entry_count {
+            if read_u32(&data, offset) != Some(CENTRAL_DIRECTORY_HEADER) {
+                return Err(invalid("bad central directory header"));
+            }
+            let field = |at: usize| read_u32(&data, offset + at).ok_or_else(|| invalid("truncated central directory"));
+            let short = |at: usize| read_u16(&data, offset + at).ok_or_else(|| invalid("truncated central directory"));
+            let entry = ZipEntry {
+                method: short(10)?,
+                crc32: field(16)?,
+                compressed_size: field(20)?,
+                size: field(24)?,
+                local_header_offset: field(42)?,
+            };
+            let (name_length, extra_length, comment_length) = (short(28)? as usize, short(30)? as usize, short(32)? as usize);
+            let name = data.get(offset + 46..offset + 46 + name_length).ok_or_else(|| invalid("truncated central directory"))?;
+            let name = String::from_utf8_lossy(name);
+            entries.insert(name.into(), entry);
+            offset += 46 + name_length + extra_length + comment_length;
+        }
+        Ok(Self { data, entries })
+    }
+    /// The uncompressed content of an entry, `None` if there is no entry with that name
+    pub fn read(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        let Some(&entry) = self.entries.get(name) else { return Ok(None) };
+        let compressed = self.compressed_data(entry)?;
+        let content = match entry.method {
+            METHOD_STORED => compressed.to_vec(),
+            METHOD_DEFLATED => inflate::inflate(compressed, entry.size as usize)?,
+            method => return Err(invalid(&format!("{name} uses unsupported compression method {method}"))),
+        };
+        if content.len() != entry.size as usize || crc32(&content) != entry.crc32 {
+            return Err(invalid(&format!("{name} is corrupt")));
+        }
+        Ok(Some(content))
+    }
+    /// The data of an entry as it is stored, after its local file header
+    fn compressed_data(&self, entry: ZipEntry) -> io::Result<&[u8]> {
+        let header = entry.local_header_offset as usize;
+        if read_u32(&self.data, header) != Some(LOCAL_FILE_HEADER) {
+            return Err(invalid("bad local file header"));
+        }
+        let name_length = read_u16(&self.data, header + 26).ok_or_else(|| invalid("truncated local file header"))? as usize;
+        let extra_length = read_u16(&self.data, header + 28).ok_or_else(|| invalid("truncated local file header"))? as usize;
+        let start = header + 30 + name_length + extra_length;
+        self.data.get(start..start + entry.compressed_size as usize).ok_or_else(|| invalid("truncated entry"))
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid zip archive: {message}"))
+}
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().unwrap()))
+}
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().unwrap()))
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &b| CRC32_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8))
+}
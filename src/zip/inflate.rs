@@ -0,0 +1,219 @@
+//! Decompression of DEFLATE streams (RFC 1951).
+
+use std::io;
+
+/// Base lengths for the length symbols 257..=285
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/// Order in which the code lengths of the code length alphabet are stored in a dynamic block
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+const MAX_BITS: usize = 15;
+/// The most a DEFLATE stream can expand, a copy of 258 bytes takes at least two bits
+const MAX_RATIO: usize = 1032;
+
+/// Decompresses a raw DEFLATE stream whose output is `size` bytes, failing as soon as it would be
+/// longer so a stream cannot make it allocate more than that
+pub(super) fn inflate(input: &[u8], size: usize) -> io::Result<Vec<u8>> {
+    if size / MAX_RATIO > input.len() {
+        return Err(invalid("the stream is too short for its size"));
+    }
+    let mut bits = BitReader { input, pos: 0, buffer: 0, count: 0 };
+    let mut out = Output { data: Vec::with_capacity(size), size };
+    loop {
+        let last = bits.take(1)? == 1;
+        match bits.take(2)? {
+            0 => stored_block(&mut bits, &mut out)?,
+            1 => {
+                let (literals, distances) = fixed_codes();
+                compressed_block(&mut bits, &mut out, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut bits)?;
+                compressed_block(&mut bits, &mut out, &literals, &distances)?;
+            }
+            _ => return Err(invalid("reserved block type")),
+        }
+        if last {
+            return Ok(out.data);
+        }
+    }
+}
+
+/// The decompressed bytes, which may not grow past `size`
+struct Output {
+    data: Vec<u8>,
+    size: usize,
+}
+
+impl Output {
+    /// Fails if `n` more bytes would not fit
+    fn reserve(&self, n: usize) -> io::Result<()> {
+        match self.data.len() + n <= self.size {
+            true => Ok(()),
+            false => Err(invalid("the output is longer than its size")),
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid deflate stream: {message}"))
+}
+
+/// Reads bits starting from the least significant bit of each byte
+struct BitReader<'a> {
+    input: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitReader<'_> {
+    fn take(&mut self, n: u32) -> io::Result<u32> {
+        while self.count < n {
+            let &byte = self.input.get(self.pos).ok_or_else(|| invalid("unexpected end"))?;
+            self.pos += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+    /// Drops the bits up to the next byte boundary
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// A canonical Huffman code, stored as the number of codes of each length and the symbols ordered
+/// by their codes
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; offsets[MAX_BITS + 1] as usize];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+    fn decode(&self, bits: &mut BitReader) -> io::Result<u16> {
+        // codes of each length are consecutive and follow the codes of the previous length
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for length in 1..=MAX_BITS {
+            code |= bits.take(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("bad code"))
+    }
+}
+
+fn stored_block(bits: &mut BitReader, out: &mut Output) -> io::Result<()> {
+    bits.align();
+    let length = bits.take(16)?;
+    if bits.take(16)? != !length & 0xffff {
+        return Err(invalid("stored block length does not match its complement"));
+    }
+    let data = bits.input.get(bits.pos..bits.pos + length as usize).ok_or_else(|| invalid("unexpected end"))?;
+    out.reserve(data.len())?;
+    out.data.extend_from_slice(data);
+    bits.pos += length as usize;
+    Ok(())
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [8; 288];
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(bits: &mut BitReader) -> io::Result<(Huffman, Huffman)> {
+    let literal_count = bits.take(5)? as usize + 257;
+    let distance_count = bits.take(5)? as usize + 1;
+    let code_length_count = bits.take(4)? as usize + 4;
+
+    let mut code_lengths = [0; 19];
+    for &i in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[i] = bits.take(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths);
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (length, repeat) = match code_lengths.decode(bits)? {
+            16 => (*lengths.last().ok_or_else(|| invalid("repeat without a previous length"))?, 3 + bits.take(2)?),
+            17 => (0, 3 + bits.take(3)?),
+            18 => (0, 11 + bits.take(7)?),
+            length => (length as u8, 1),
+        };
+        lengths.extend((0..repeat).map(|_| length));
+    }
+    if lengths.len() != literal_count + distance_count {
+        return Err(invalid("code lengths overflow"));
+    }
+    let (literals, distances) = lengths.split_at(literal_count);
+    Ok((Huffman::new(literals), Huffman::new(distances)))
+}
+
+fn compressed_block(bits: &mut BitReader, out: &mut Output, literals: &Huffman, distances: &Huffman) -> io::Result<()> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        match symbol {
+            0..256 => {
+                out.reserve(1)?;
+                out.data.push(symbol as u8);
+            }
+            256 => return Ok(()),
+            257..286 => {
+                let i = symbol - 257;
+                let length = LENGTH_BASE[i] as usize + bits.take(LENGTH_EXTRA[i] as u32)? as usize;
+                let i = distances.decode(bits)? as usize;
+                if i >= DISTANCE_BASE.len() {
+                    return Err(invalid("bad distance symbol"));
+                }
+                let distance = DISTANCE_BASE[i] as usize + bits.take(DISTANCE_EXTRA[i] as u32)? as usize;
+                if distance > out.data.len() {
+                    return Err(invalid("distance too far back"));
+                }
+                out.reserve(length)?;
+                // the copy may overlap the bytes it produces
+                let start = out.data.len() - distance;
+                for i in 0..length {
+                    out.data.push(out.data[start + i]);
+                }
+            }
+            _ => return Err(invalid("bad literal/length symbol")),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Archives made by the JDK's `jar` tool, which has to be on the `PATH`, read back.
+
+use std::{fs, io, process::Command};
+
+use super::{ZipArchive, METHOD_DEFLATED, METHOD_STORED};
+
+/// Files that `jar` compresses with each kind of DEFLATE block: short text gets the fixed codes,
+/// longer text dynamic ones and random bytes are stored as they are
+fn files() -> [(&'static str, Vec<u8>, Option<u32>); 4] {
+    let words = ["class", "java", "lang", "Object", "String", "method", "field", "constant", "pool", "code"];
+    let mut state = 0x2545_f491_u32;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state
+    };
+    let text: String = (0..3000).map(|i| format!("{}{}", words[next() as usize % words.len()], if i % 12 == 11 { '\n' } else { ' ' })).collect();
+    let random = (0..70_000).map(|_| next() as u8).collect();
+    [
+        ("fixed.txt", b"hello hello hello hello\n".to_vec(), Some(1)),
+        ("dynamic.txt", text.into_bytes(), Some(2)),
+        ("random.bin", random, Some(0)),
+        ("empty.txt", Vec::new(), None),
+    ]
+}
+
+/// Makes a jar of the files, compressed or stored
+fn jar(test: &str, compress: bool) -> ZipArchive {
+    let dir = std::env::temp_dir().join(format!("jappuccino-zip-{test}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut jar = Command::new("jar");
+    jar.current_dir(&dir).arg(if compress { "cfM" } else { "cfM0" }).arg("test.jar");
+    for (name, content, _) in files() {
+        fs::write(dir.join(name), content).unwrap();
+        jar.arg(name);
+    }
+    let status = jar.status().expect("jar should be on the PATH");
+    assert!(status.success(), "jar failed");
+    ZipArchive::open(dir.join("test.jar")).unwrap()
+}
+
+fn assert_invalid<T>(result: io::Result<T>, name: &str) {
+    match result {
+        Ok(_) => panic!("{name} should not be read"),
+        Err(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidData, "{name}: {e}"),
+    }
+}
+
+#[test]
+fn deflated_entries_round_trip() {
+    let archive = jar("deflated", true);
+    for (name, content, block_type) in files() {
+        let entry = archive.entries[name];
+        assert_eq!(archive.read(name).unwrap().as_deref(), Some(&*content), "{name}");
+        // the first block has the type the file is there to cover
+        if let Some(block_type) = block_type {
+            assert_eq!(entry.method, METHOD_DEFLATED, "{name}");
+            let first = archive.compressed_data(entry).unwrap()[0];
+            assert_eq!((first >> 1) as u32 & 3, block_type, "{name}");
+        }
+    }
+    assert_eq!(archive.read("missing.txt").unwrap(), None);
+}
+
+#[test]
+fn stored_entries_round_trip() {
+    let archive = jar("stored", false);
+    for (name, content, _) in files() {
+        assert_eq!(archive.entries[name].method, METHOD_STORED, "{name}");
+        assert_eq!(archive.read(name).unwrap().as_deref(), Some(&*content), "{name}");
+    }
+}
+
+#[test]
+fn truncated_entries_fail() {
+    for compress in [true, false] {
+        let mut archive = jar("truncated", compress);
+        for (name, content, _) in files() {
+            if content.is_empty() {
+                continue;
+            }
+            archive.entries.get_mut(name).unwrap().compressed_size /= 2;
+            assert_invalid(archive.read(name), name);
+        }
+        // the end of the archive is cut off, with the central directory
+        let half = archive.data.len() / 2;
+        assert_invalid(ZipArchive::from_bytes(archive.data[..half].into()), "the archive");
+        // an entry runs past the end of the archive
+        let mut archive = jar("truncated", compress);
+        archive.entries.get_mut("random.bin").unwrap().compressed_size = u32::MAX;
+        assert_invalid(archive.read("random.bin"), "random.bin");
+    }
+}
+
+#[test]
+fn oversized_entries_fail() {
+    for compress in [true, false] {
+        let mut archive = jar("oversized", compress);
+        for (name, content, _) in files() {
+            if content.is_empty() {
+                continue;
+            }
+            // the content is longer than the size the archive declares
+            archive.entries.get_mut(name).unwrap().size -= 1;
+            assert_invalid(archive.read(name), name);
+        }
+    }
+    // a declared size the stream cannot have is not allocated
+    let mut archive = jar("oversized", true);
+    archive.entries.get_mut("fixed.txt").unwrap().size = u32::MAX;
+    assert_invalid(archive.read("fixed.txt"), "fixed.txt");
+}
@@ -0,0 +1,132 @@
+//! The `jappuccino` command runs programs like `java` does. `javac` and `jar` have to be on the
+//! `PATH`.
+
+use std::{fs, path::{Path, PathBuf}, process::{Command, Output}};
+
+/// An empty directory for a test
+fn work_dir(test: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("cli-{test}"));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Compiles Java sources, given by class name, into `classes` against the classes on `class_path`
+fn javac(classes: &Path, class_path: &Path, sources: &[(&str, &str)]) {
+    let src = classes.with_extension("src");
+    fs::create_dir_all(&src).unwrap();
+    let mut javac = Command::new("javac");
+    javac.arg("-d").arg(classes).arg("-cp").arg(class_path);
+    for (class, source) in sources {
+        let path = src.join(format!("{class}.java"));
+        fs::write(&path, source).unwrap();
+        javac.arg(path);
+    }
+    assert!(javac.status().expect("javac should be on the PATH").success(), "javac failed");
+}
+
+/// Packs a directory of classes into a jar with the given manifest
+fn jar(jar: &Path, manifest: &str, classes: &Path) {
+    let manifest_file = jar.with_extension("mf");
+    fs::write(&manifest_file, manifest).unwrap();
+    let status = Command::new("jar")
+        .arg("--create")
+        .arg("--file").arg(jar)
+        .arg("--manifest").arg(&manifest_file)
+        .arg("-C").arg(classes).arg(".")
+        .status()
+        .expect("jar should be on the PATH");
+    assert!(status.success(), "jar failed");
+}
+
+fn jappuccino(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_jappuccino")).current_dir(dir).env_remove("CLASSPATH").args(args).output().unwrap()
+}
+
+/// The exit status and the standard output of a run
+fn run(dir: &Path, args: &[&str]) -> (i32, String) {
+    let output = jappuccino(dir, args);
+    (output.status.code().unwrap(), String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn executable_jars_run_with_their_manifest() {
+    let dir = work_dir("jar");
+    let dep = dir.join("dep");
+    javac(&dep, &dep, &[("Dep", "public class Dep { public static String name() { return \"dep\"; } }")]);
+    fs::create_dir(dir.join("lib")).unwrap();
+    jar(&dir.join("lib/dep.jar"), "", &dep);
+
+    // the classes for later versions replace those at the root
+    let app = dir.join("app");
+    let greeting = |version: &str| format!("class Greeting {{ static String version() {{ return \"{version}\"; }} }}");
+    javac(&app, &dep, &[
+        ("Greeting", &greeting("base")),
+        ("Main", "
+            public class Main {
+                public static void main(String[] args) {
+                    String line = Dep.name() + \" \" + Greeting.version() + \" \" + args.length;
+                    for (String arg : args) {
+                        line += \" \" + arg;
+                    }
+                    System.out.println(line);
+                }
+            }
+        "),
+    ]);
+    for (version, name) in [(11, "eleven"), (21, "twenty-one")] {
+        let classes = dir.join(format!("release-{version}"));
+        javac(&classes, &classes, &[("Greeting", &greeting(name))]);
+        let versioned = app.join(format!("META-INF/versions/{version}"));
+        fs::create_dir_all(&versioned).unwrap();
+        fs::copy(classes.join("Greeting.class"), versioned.join("Greeting.class")).unwrap();
+    }
+    jar(&dir.join("app.jar"), "Main-Class: Main\nMulti-Release: true\nClass-Path: lib/dep.jar\n", &app);
+
+    assert_eq!(run(&dir, &["-jar", "app.jar", "one", "two"]), (0, "dep eleven 2 one two\n".into()));
+    assert_eq!(run(&dir, &["--release", "8", "-jar", "app.jar"]), (0, "dep base 0\n".into()));
+    assert_eq!(run(&dir, &["--release", "21", "-jar", "app.jar", "-cp"]), (0, "dep twenty-one 1 -cp\n".into()));
+
+    // without the attribute the versions are ignored
+    jar(&dir.join("single.jar"), "Main-Class: Main\nClass-Path: lib/dep.jar\n", &app);
+    assert_eq!(run(&dir, &["--release", "21", "-jar", "single.jar"]), (0, "dep base 0\n".into()));
+
+    jar(&dir.join("library.jar"), "", &dep);
+    let output = jappuccino(&dir, &["-jar", "library.jar"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no Main-Class in the manifest of library.jar"));
+}
+
+#[test]
+fn main_gets_its_arguments_and_sets_the_exit_status() {
+    let dir = work_dir("args");
+    javac(&dir, &dir, &[("Main", "
+        public class Main {
+            public static void main(String[] args) {
+                System.out.println(args.length);
+                for (String arg : args) {
+                    System.out.println(\"[\" + arg + \"] \" + arg.length());
+                }
+                switch (args.length > 0 ? args[0] : \"\") {
+                    case \"exit\":
+                        try {
+                            System.exit(Integer.parseInt(args[1]));
+                        } finally {
+                            System.out.println(\"not printed\");
+                        }
+                    case \"throw\":
+                        throw new IllegalStateException(args[1]);
+                }
+            }
+        }
+    ")]);
+    assert_eq!(run(&dir, &["Main"]), (0, "0\n".into()));
+    assert_eq!(run(&dir, &["-cp", ".", "Main", "", "two words", "héllo €"]), (0, "3\n[] 0\n[two words] 9\n[héllo €] 7\n".into()));
+    assert_eq!(run(&dir, &["Main", "exit", "42"]), (42, "2\n[exit] 4\n[42] 2\n".into()));
+    assert_eq!(run(&dir, &["Main", "exit", "0"]).0, 0);
+
+    let output = jappuccino(&dir, &["Main", "throw", "broken"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Exception in thread \"main\" java.lang.IllegalStateException: broken\n"), "{stderr}");
+}
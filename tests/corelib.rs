@@ -0,0 +1,467 @@
+//! Programs that use the builtin core library print what they print on the JDK.
+//!
+//! Each test compiles its program from the source given to [`run`], `javac` has to be on the
+//! `PATH`.
+
+use std::{fs, path::PathBuf, process::Command};
+
+use jappuccino::rt::{ClassPath, Runtime, SharedBuffer};
+
+/// Compiles a program into a directory of its own and runs its class `Main`, returning the exit
+/// status and what it printed
+fn run(test: &str, source: &str) -> (i32, String) {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("corelib-{test}"));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("Main.java");
+    fs::write(&path, source).unwrap();
+    let status = Command::new("javac").arg("-d").arg(&dir).arg(&path).status().expect("javac should be on the PATH");
+    assert!(status.success(), "javac failed");
+
+    let out = SharedBuffer::new();
+    let mut class_path = ClassPath::new();
+    class_path.push_directory(&dir);
+    let mut runtime = Runtime::new().with_class_path(class_path).with_stdout(out.clone());
+    let status = runtime.run("Main", &[]).unwrap();
+    (status, String::from_utf8(out.contents()).unwrap())
+}
+
+#[test]
+fn arrays_and_cloneables_clone() {
+    let (status, out) = run("clone", "
+        import java.util.Arrays;
+        class Point implements Cloneable {
+            int x;
+            long y;
+            Point(int x, long y) { this.x = x; this.y = y; }
+            Point copy() throws CloneNotSupportedException { return (Point) clone(); }
+        }
+        class Plain {
+            Object copy() throws CloneNotSupportedException { return clone(); }
+        }
+        public class Main {
+            public static void main(String[] args) throws Exception {
+                int[] ints = { 1, 2, 3 };
+                int[] copy = ints.clone();
+                copy[0] = 9;
+                System.out.println(Arrays.toString(ints) + \" \" + Arrays.toString(copy));
+                String[][] nested = { { \"a\" }, { \"b\", \"c\" } };
+                String[][] shallow = nested.clone();
+                System.out.println((shallow != nested) + \" \" + (shallow[1] == nested[1]) + \" \" + shallow.length);
+                System.out.println(new double[0].clone().length);
+
+                Point point = new Point(4, 1L << 40);
+                Point other = point.copy();
+                other.x = 5;
+                System.out.println((other != point) + \" \" + point.x + \" \" + other.x + \" \" + other.y);
+                try {
+                    new Plain().copy();
+                } catch (CloneNotSupportedException e) {
+                    System.out.println(e);
+                }
+            }
+        }
+    ");
+    assert_eq!(status, 0);
+    assert_eq!(out, "\
+        [1, 2, 3] [9, 2, 3]\n\
+        true true 2\n\
+        0\n\
+        true 4 5 1099511627776\n\
+        java.lang.CloneNotSupportedException: Plain\n\
+    ");
+}
+
+#[test]
+fn enums_behave_like_the_jdk() {
+    let (status, out) = run("enum", "
+        class Outer {
+            enum Color {
+                RED,
+                GREEN { public String toString() { return \"green\"; } },
+                BLUE
+            }
+        }
+        enum Empty {}
+        public class Main {
+            static String describe(Outer.Color color) {
+                switch (color) {
+                    case RED: return \"warm\";
+                    case GREEN: return \"fresh\";
+                    default: return \"cold\";
+                }
+            }
+            public static void main(String[] args) {
+                for (Outer.Color color : Outer.Color.values()) {
+                    System.out.println(color.name() + \" \" + color.ordinal() + \" \" + color + \" \"
+                        + color.compareTo(Outer.Color.GREEN) + \" \" + describe(color) + \" \"
+                        + (color.getDeclaringClass() == Outer.Color.class));
+                }
+                System.out.println(Outer.Color.values() != Outer.Color.values());
+                System.out.println(Outer.Color.valueOf(\"BLUE\") == Outer.Color.BLUE);
+                System.out.println(Enum.valueOf(Outer.Color.class, \"GREEN\").name());
+                System.out.println(Empty.values().length);
+                try {
+                    Outer.Color.valueOf(\"PINK\");
+                } catch (IllegalArgumentException e) {
+                    System.out.println(e.getMessage());
+                }
+                try {
+                    Outer.Color.valueOf(null);
+                } catch (NullPointerException e) {
+                    System.out.println(e.getMessage());
+                }
+                try {
+                    Outer.Color.RED.compareTo(null);
+                } catch (NullPointerException e) {
+                    System.out.println(\"null\");
+                }
+            }
+        }
+    ");
+    assert_eq!(status, 0);
+    assert_eq!(out, "\
+        RED 0 RED -1 warm true\n\
+        GREEN 1 green 0 fresh true\n\
+        BLUE 2 BLUE 1 cold true\n\
+        true\n\
+        true\n\
+        GREEN\n\
+        0\n\
+        No enum constant Outer.Color.PINK\n\
+        Name is null\n\
+        null\n\
+    ");
+}
+
+#[test]
+fn throwables_print_like_the_jdk() {
+    let (status, out) = run("throwable", "
+        class Loud extends Exception {
+            Loud() { super(\"quiet\"); }
+            public String getMessage() { return \"loud\"; }
+        }
+        public class Main {
+            public static void main(String[] args) {
+                System.out.println(new RuntimeException(\"boom\"));
+                System.out.println(new IllegalStateException());
+                System.out.println(new RuntimeException(\"boom\").getLocalizedMessage());
+                System.out.println(new Loud());
+                System.out.println(new Loud().getLocalizedMessage());
+                try {
+                    System.out.println(1 / args.length);
+                } catch (ArithmeticException e) {
+                    System.out.println(e);
+                }
+            }
+        }
+    ");
+    assert_eq!(status, 0);
+    assert_eq!(out, "\
+        java.lang.RuntimeException: boom\n\
+        java.lang.IllegalStateException\n\
+        boom\n\
+        Loud: loud\n\
+        loud\n\
+        java.lang.ArithmeticException: / by zero\n\
+    ");
+}
+
+#[test]
+fn boxes_cache_parse_and_print_like_the_jdk() {
+    let (status, out) = run("boxes", "
+        public class Main {
+            public static void main(String[] args) {
+                Integer small = 127, same = 127, big = 128, other = 128;
+                System.out.println((small == same) + \" \" + (big == other) + \" \" + big.equals(other));
+                System.out.println((Long.valueOf(-128) == Long.valueOf(-128)) + \" \" + (Character.valueOf('a') == Character.valueOf('a')) + \" \" + (Boolean.valueOf(true) == Boolean.TRUE));
+                System.out.println(Integer.parseInt(\"-42\") + \" \" + Integer.parseInt(\"ff\", 16) + \" \" + Long.parseLong(\"9223372036854775807\") + \" \" + Short.parseShort(\"+12\") + \" \" + Byte.parseByte(\"-128\"));
+                System.out.println(Double.parseDouble(\"1e3\") + \" \" + Float.parseFloat(\"-0.5\") + \" \" + Boolean.parseBoolean(\"TRUE\") + \" \" + Boolean.parseBoolean(\"yes\"));
+                try {
+                    Integer.parseInt(\"12a\");
+                } catch (NumberFormatException e) {
+                    System.out.println(e.getMessage());
+                }
+                try {
+                    Byte.parseByte(\"200\");
+                } catch (NumberFormatException e) {
+                    System.out.println(e.getMessage());
+                }
+                System.out.println(Integer.toString(255, 2) + \" \" + Integer.toHexString(-1) + \" \" + Integer.toOctalString(8) + \" \" + Integer.toBinaryString(10) + \" \" + Long.toHexString(1L << 40));
+                System.out.println(Integer.MAX_VALUE + \" \" + Integer.MIN_VALUE + \" \" + Long.MIN_VALUE + \" \" + Double.MAX_VALUE + \" \" + Float.MIN_VALUE);
+                System.out.println(Integer.compare(3, 7) + \" \" + Long.compare(7, 3) + \" \" + Double.compare(0.0, -0.0) + \" \" + Double.compare(Double.NaN, 1) + \" \" + Boolean.compare(true, false) + \" \" + Character.compare('a', 'b'));
+                System.out.println(big.compareTo(small) + \" \" + Integer.valueOf(5).hashCode() + \" \" + Long.valueOf(1L << 32).hashCode() + \" \" + Boolean.TRUE.hashCode() + \" \" + Double.valueOf(1.5).hashCode());
+                System.out.println(Integer.bitCount(255) + \" \" + Integer.reverse(1) + \" \" + Integer.highestOneBit(100) + \" \" + Integer.numberOfLeadingZeros(1) + \" \" + Long.numberOfTrailingZeros(64) + \" \" + Integer.rotateLeft(1, 33));
+                System.out.println(Integer.toUnsignedString(-1) + \" \" + Integer.divideUnsigned(-1, 2) + \" \" + Integer.compareUnsigned(-1, 1) + \" \" + Long.toUnsignedString(-1L) + \" \" + Integer.toUnsignedLong(-2));
+                System.out.println(Double.isNaN(0.0 / 0) + \" \" + Double.valueOf(1 / 0.0).isInfinite() + \" \" + Double.isFinite(1e308) + \" \" + Float.intBitsToFloat(Float.floatToIntBits(2.5f)) + \" \" + Double.doubleToLongBits(1.0));
+                System.out.println(Double.toString(100.0) + \" \" + 1e7 + \" \" + 1.0e-4 + \" \" + 0.001 + \" \" + 123456789.0f + \" \" + (0.1 + 0.2) + \" \" + Double.toHexString(1.0));
+                Object[] boxes = { (byte) 1, (short) 2, 'c', 4L, 5.0f, 6.0, false };
+                for (Object box : boxes) {
+                    System.out.print(box + \" \");
+                }
+                System.out.println();
+                Number number = 3.75;
+                System.out.println(number.intValue() + \" \" + number.longValue() + \" \" + number.floatValue() + \" \" + ((Integer) 300).byteValue() + \" \" + Integer.sum(2, 3) + \" \" + Long.max(2, 3) + \" \" + Double.min(-0.0, 0.0));
+            }
+        }
+    ");
+    assert_eq!(status, 0);
+    assert_eq!(out, "\
+        true false true\n\
+        true true true\n\
+        -42 255 9223372036854775807 12 -128\n\
+        1000.0 -0.5 true false\n\
+        For input string: \"12a\"\n\
+        Value out of range. Value:\"200\" Radix:10\n\
+        11111111 ffffffff 10 1010 10000000000\n\
+        2147483647 -2147483648 -9223372036854775808 1.7976931348623157E308 1.4E-45\n\
+        -1 1 1 1 1 -1\n\
+        1 5 1 1231 1073217536\n\
+        8 -2147483648 64 31 6 2\n\
+        4294967295 2147483647 1 18446744073709551615 4294967294\n\
+        true true true 2.5 4607182418800017408\n\
+        100.0 1.0E7 1.0E-4 0.001 1.23456792E8 0.30000000000000004 0x1.0p0\n\
+        1 2 c 4 5.0 6.0 false \n\
+        3 3 3.75 44 5 3 -0.0\n\
+    ");
+}
+
+#[test]
+fn characters_are_classified_like_the_jdk() {
+    let (status, out) = run("character", "
+        public class Main {
+            public static void main(String[] args) {
+                for (char c : new char[] { 'a', 'Z', '5', ' ', '\\t', '_', '\\u00e9', '\\u0660' }) {
+                    System.out.println((int) c + \" \" + Character.isLetter(c) + \" \" + Character.isDigit(c) + \" \" + Character.isLetterOrDigit(c)
+                        + \" \" + Character.isWhitespace(c) + \" \" + Character.isSpaceChar(c) + \" \" + Character.isUpperCase(c) + \" \" + Character.isLowerCase(c)
+                        + \" \" + (int) Character.toUpperCase(c) + \" \" + (int) Character.toLowerCase(c) + \" \" + Character.getNumericValue(c) + \" \" + Character.digit(c, 16));
+                }
+                System.out.println(Character.forDigit(11, 16) + \" \" + Character.forDigit(5, 10) + \" \" + (int) Character.forDigit(20, 10) + \" \" + Character.digit('z', 36) + \" \" + Character.isISOControl('\\n'));
+                int smile = 0x1F600;
+                char[] pair = Character.toChars(smile);
+                System.out.println(pair.length + \" \" + Character.charCount(smile) + \" \" + Character.isHighSurrogate(pair[0]) + \" \" + Character.isLowSurrogate(pair[1])
+                    + \" \" + Character.isSurrogate('a') + \" \" + Character.toCodePoint(pair[0], pair[1]) + \" \" + (int) Character.highSurrogate(smile) + \" \" + (int) Character.lowSurrogate(smile));
+                System.out.println(Character.isValidCodePoint(0x110000) + \" \" + Character.isBmpCodePoint(0xFFFF) + \" \" + Character.isSupplementaryCodePoint(smile) + \" \" + Character.toString(smile).length() + \" \" + Character.isAlphabetic('x'));
+                System.out.println(Character.MAX_VALUE + 0 + \" \" + Character.MIN_RADIX + \" \" + Character.MAX_RADIX + \" \" + (int) Character.reverseBytes('\\u0102') + \" \" + Character.toString('q') + \" \" + Character.valueOf('q').charValue() + \" \" + Character.valueOf('q').compareTo('a'));
+                System.out.println(Character.toUpperCase(0x1F600) + \" \" + Character.toLowerCase((int) 'A') + \" \" + Character.isLetter(0x10400) + \" \" + (int) Character.toUpperCase('\\u00df'));
+            }
+        }
+    ");
+    assert_eq!(status, 0);
+    assert_eq!(out, "\
+        97 true false true false false false true 65 97 10 10\n\
+        90 true false true false false true false 90 122 35 -1\n\
+        53 false true true false false false false 53 53 5 5\n\
+        32 false false false true true false false 32 32 -1 -1\n\
+        9 false false false true false false false 9 9 -1 -1\n\
+        95 false false false false false false false 95 95 -1 -1\n\
+        233 true false true false false false true 201 233 -1 -1\n\
+        1632 false true true false false false false 1632 1632 0 0\n\
+        b 5 0 35 true\n\
+        2 2 true true false 128512 55357 56832\n\
+        false true true 2 true\n\
+        65535 2 36 513 q q 16\n\
+        128512 97 true 223\n\
+    ");
+}
+
+#[test]
+fn math_rounds_and_overflows_like_the_jdk() {
+    let (status, out) = run("math", "
+        public class Main {
+            public static void main(String[] args) {
+                System.out.println(Math.abs(-5) + \" \" + Math.abs(Integer.MIN_VALUE) + \" \" + Math.abs(-2.5) + \" \" + Math.abs(-0.0f) + \" \" + Math.max(3L, 9L) + \" \" + Math.min(-0.0, 0.0) + \" \" + Math.max(Double.NaN, 1));
+                System.out.println(Math.sqrt(2) + \" \" + Math.cbrt(27) + \" \" + Math.pow(2, 10) + \" \" + Math.pow(2, -1) + \" \" + Math.hypot(3, 4) + \" \" + Math.exp(1) + \" \" + Math.log(Math.E) + \" \" + Math.log10(1000));
+                System.out.println(Math.sin(0) + \" \" + Math.cos(0) + \" \" + Math.tan(0) + \" \" + Math.atan2(1, 1) + \" \" + Math.toDegrees(Math.PI) + \" \" + Math.toRadians(180) + \" \" + Math.asin(1) + \" \" + Math.sinh(0) + \" \" + Math.cosh(0));
+                System.out.println(Math.floor(-1.5) + \" \" + Math.ceil(-1.5) + \" \" + Math.rint(2.5) + \" \" + Math.round(2.5) + \" \" + Math.round(-2.5) + \" \" + Math.round(0.49999999999999994) + \" \" + Math.round(1.5f) + \" \" + Math.round(Double.NaN));
+                System.out.println(Math.signum(-3.0) + \" \" + Math.signum(0.0f) + \" \" + Math.copySign(3.0, -0.0) + \" \" + Math.ulp(1.0) + \" \" + Math.nextUp(1.0f) + \" \" + Math.nextDown(1.0) + \" \" + Math.getExponent(1024.0) + \" \" + Math.fma(2.0, 3.0, 1.0));
+                System.out.println(Math.floorDiv(-7, 2) + \" \" + Math.floorMod(-7, 2) + \" \" + Math.floorDiv(7L, -2) + \" \" + Math.floorMod(7, -2) + \" \" + (-7 / 2) + \" \" + (-7 % 2) + \" \" + Math.multiplyHigh(Long.MAX_VALUE, 4));
+                System.out.println(Math.addExact(1, 2) + \" \" + Math.multiplyExact(1L << 31, 2) + \" \" + Math.negateExact(-5) + \" \" + Math.toIntExact(42L) + \" \" + Math.incrementExact(9) + \" \" + Math.absExact(-8L));
+                String[] names = { \"addExact\", \"multiplyExact\", \"toIntExact\", \"negateExact\", \"incrementExact\", \"absExact\" };
+                for (int i = 0; i < names.length; i++) {
+                    try {
+                        switch (i) {
+                            case 0: Math.addExact(Integer.MAX_VALUE, 1); break;
+                            case 1: Math.multiplyExact(Long.MAX_VALUE, 2L); break;
+                            case 2: Math.toIntExact(1L << 31); break;
+                            case 3: Math.negateExact(Integer.MIN_VALUE); break;
+                            case 4: Math.incrementExact(Long.MAX_VALUE); break;
+                            default: Math.absExact(Integer.MIN_VALUE);
+                        }
+                    } catch (ArithmeticException e) {
+                        System.out.println(names[i] + \": \" + e.getMessage());
+                    }
+                }
+                double random = Math.random();
+                System.out.println((random >= 0 && random < 1) + \" \" + StrictMath.sqrt(16) + \" \" + Math.PI + \" \" + Math.E);
+            }
+        }
+    ");
+    assert_eq!(status, 0);
+    assert_eq!(out, "\
+        5 -2147483648 2.5 0.0 9 -0.0 NaN\n\
+        1.4142135623730951 3.0 1024.0 0.5 5.0 2.718281828459045 1.0 3.0\n\
+        0.0 1.0 0.0 0.7853981633974483 180.0 3.141592653589793 1.5707963267948966 0.0 1.0\n\
+        -2.0 -1.0 2.0 3 -2 0 2 0\n\
+        -1.0 0.0 -3.0 2.220446049250313E-16 1.0000001 0.9999999999999999 10 7.0\n\
+        -4 1 -4 -1 -3 -1 1\n\
+        3 4294967296 5 42 10 8\n\
+        addExact: integer overflow\n\
+        multiplyExact: long overflow\n\
+        toIntExact: integer overflow\n\
+        negateExact: integer overflow\n\
+        incrementExact: long overflow\n\
+        absExact: Overflow to represent absolute value of Integer.MIN_VALUE\n\
+        true 4.0 3.141592653589793 2.718281828459045\n\
+    ");
+}
+
+#[test]
+fn string_builders_edit_like_the_jdk() {
+    let (status, out) = run("sb", "
+        public class Main {
+            public static void main(String[] args) {
+                StringBuilder sb = new StringBuilder();
+                System.out.println(sb.length() + \" \" + sb.capacity() + \" \" + sb.isEmpty());
+                sb.append(\"abc\").append(1).append('x').append(2.5).append(true).append((Object) null).append(3L).append(1.5f).append(new char[] { 'h', 'i' });
+                System.out.println(sb + \" \" + sb.length());
+                sb.setLength(3);
+                sb.insert(0, \"<\").insert(sb.length(), '>').insert(2, 42).insert(1, false);
+                System.out.println(sb);
+                sb.reverse();
+                System.out.println(sb + \" \" + sb.charAt(0) + \" \" + sb.indexOf(\"2\") + \" \" + sb.lastIndexOf(\"e\") + \" \" + sb.indexOf(\"z\"));
+                sb.delete(0, 2).deleteCharAt(0).replace(1, 3, \"REPLACED\").setCharAt(0, '#');
+                System.out.println(sb + \" \" + sb.substring(1, 4) + \" \" + sb.subSequence(0, 2) + \" \" + sb.substring(5));
+                StringBuilder unicode = new StringBuilder(\"h\\u00e9\").appendCodePoint(0x1F600).append(\"!\");
+                System.out.println(unicode.length() + \" \" + unicode.codePointAt(2) + \" \" + new StringBuilder(unicode).reverse().codePointAt(1));
+                StringBuilder sized = new StringBuilder(100);
+                System.out.println(sized.capacity() + \" \" + new StringBuilder(\"four\").capacity() + \" \" + new StringBuilder((CharSequence) \"cs\").append(\"x\", 0, 1));
+                sized.append(\"ab\");
+                sized.trimToSize();
+                System.out.println(sized.capacity() + \" \" + sized.compareTo(new StringBuilder(\"ac\")) + \" \" + \"ab\".equals(sized.toString()));
+                try {
+                    sb.charAt(100);
+                } catch (IndexOutOfBoundsException e) {
+                    System.out.println(e.getClass().getName());
+                }
+                try {
+                    sb.insert(-1, \"x\");
+                } catch (StringIndexOutOfBoundsException e) {
+                    System.out.println(e.getMessage());
+                }
+            }
+        }
+    ");
+    assert_eq!(status, 0);
+    assert_eq!(out, "\
+        0 16 true\n\
+        abc1x2.5truenull31.5hi 22\n\
+        <falsea42bc>\n\
+        >cb24aeslaf< > 3 6 -1\n\
+        #REPLACEDeslaf< REP #R ACEDeslaf<\n\
+        5 128512 128512\n\
+        100 20 csx\n\
+        2 -1 true\n\
+        java.lang.StringIndexOutOfBoundsException\n\
+        offset -1, length 15\n\
+    ");
+}
+
+#[test]
+fn objects_helpers_behave_like_the_jdk() {
+    let (status, out) = run("objects", "
+        import java.util.Objects;
+        public class Main {
+            public static void main(String[] args) {
+                System.out.println(Objects.equals(null, null) + \" \" + Objects.equals(\"a\", null) + \" \" + Objects.equals(\"a\", String.valueOf(new char[] { 'a' })));
+                System.out.println(Objects.hashCode(null) + \" \" + Objects.hashCode(\"a\") + \" \" + Objects.hash(1, \"a\", null) + \" \" + Objects.hash());
+                System.out.println(Objects.toString(null) + \" \" + Objects.toString(null, \"default\") + \" \" + Objects.toString(5, \"default\"));
+                System.out.println(Objects.isNull(null) + \" \" + Objects.nonNull(null) + \" \" + Objects.requireNonNullElse(null, \"else\") + \" \" + Objects.requireNonNull(\"kept\"));
+                System.out.println(Objects.compare(\"a\", \"bb\", (a, b) -> a.length() - b.length()) + \" \" + Objects.checkIndex(2, 3));
+                try {
+                    Objects.requireNonNull(null, \"message\");
+                } catch (NullPointerException e) {
+                    System.out.println(e.getMessage());
+                }
+                try {
+                    Objects.requireNonNull(null);
+                } catch (NullPointerException e) {
+                    System.out.println(e.getMessage());
+                }
+                try {
+                    Objects.checkIndex(3, 3);
+                } catch (IndexOutOfBoundsException e) {
+                    System.out.println(e.getMessage());
+                }
+            }
+        }
+    ");
+    assert_eq!(status, 0);
+    assert_eq!(out, "\
+        true false true\n\
+        0 97 33759 1\n\
+        null default 5\n\
+        true false else kept\n\
+        -1 2\n\
+        message\n\
+        null\n\
+        Index 3 out of bounds for length 3\n\
+    ");
+}
+
+#[test]
+fn arrays_sort_search_copy_and_print_like_the_jdk() {
+    let (status, out) = run("arrays", "
+        import java.util.Arrays;
+        public class Main {
+            public static void main(String[] args) {
+                int[] ints = { 5, -1, 3, 3, 0 };
+                Arrays.sort(ints);
+                System.out.println(Arrays.toString(ints) + \" \" + Arrays.binarySearch(ints, 3) + \" \" + Arrays.binarySearch(ints, 4) + \" \" + Arrays.binarySearch(ints, -5));
+                double[] doubles = { 2.5, Double.NaN, -0.0, 0.0, -1 };
+                Arrays.sort(doubles);
+                System.out.println(Arrays.toString(doubles));
+                String[] strings = { \"pear\", \"apple\", \"fig\" };
+                Arrays.sort(strings);
+                System.out.println(Arrays.toString(strings) + \" \" + Arrays.binarySearch(strings, \"fig\") + \" \" + Arrays.binarySearch(strings, \"kiwi\") + \" \" + Arrays.binarySearch(new String[] { \"pear\", \"fig\", \"apple\" }, \"fig\", (a, b) -> b.compareTo(a)));
+                char[] chars = { 'h', 'e', 'l', 'l', 'o' };
+                Arrays.sort(chars, 1, 4);
+                System.out.println(String.valueOf(chars) + \" \" + Arrays.toString(chars));
+                long[] longs = new long[4];
+                Arrays.fill(longs, 7);
+                Arrays.fill(longs, 1, 3, -1);
+                System.out.println(Arrays.toString(longs) + \" \" + Arrays.toString((int[]) null) + \" \" + Arrays.toString(new boolean[] { true, false }) + \" \" + Arrays.toString(new Object[0]));
+                int[] longer = Arrays.copyOf(ints, 7);
+                String[] range = Arrays.copyOfRange(strings, 1, 4);
+                System.out.println(Arrays.toString(longer) + \" \" + Arrays.toString(range) + \" \" + Arrays.toString(Arrays.copyOf(new byte[] { 1, 2, 3 }, 2)));
+                System.out.println(Arrays.equals(ints, new int[] { -1, 0, 3, 3, 5 }) + \" \" + Arrays.equals(ints, longer) + \" \" + Arrays.equals(new String[] { \"a\" }, new String[] { String.valueOf(new char[] { 'a' }) }) + \" \" + Arrays.equals((int[]) null, null));
+                System.out.println(Arrays.hashCode(new int[] { 1, 2 }) + \" \" + Arrays.hashCode(new String[] { \"a\", null }) + \" \" + Arrays.hashCode((long[]) null) + \" \" + Arrays.hashCode(new double[] { 1.5 }));
+                Object[] nested = { 1, new int[] { 2, 3 }, new String[][] { { \"x\" }, {} }, null };
+                System.out.println(Arrays.deepToString(nested) + \" \" + Arrays.deepEquals(nested, new Object[] { 1, new int[] { 2, 3 }, new String[][] { { \"x\" }, {} }, null }));
+                try {
+                    Arrays.copyOfRange(ints, 3, 1);
+                } catch (IllegalArgumentException e) {
+                    System.out.println(e.getMessage());
+                }
+                try {
+                    Arrays.fill(ints, 2, 9, 0);
+                } catch (ArrayIndexOutOfBoundsException e) {
+                    System.out.println(e.getMessage());
+                }
+            }
+        }
+    ");
+    assert_eq!(status, 0);
+    assert_eq!(out, "\
+        [-1, 0, 3, 3, 5] 2 -5 -1\n\
+        [-1.0, -0.0, 0.0, 2.5, NaN]\n\
+        [apple, fig, pear] 1 -3 1\n\
+        hello [h, e, l, l, o]\n\
+        [7, -1, -1, 7] null [true, false] []\n\
+        [-1, 0, 3, 3, 5, 0, 0] [fig, pear, null] [1, 2]\n\
+        true false true true\n\
+        994 3968 0 1073217567\n\
+        [1, [2, 3], [[x], []], null] true\n\
+        3 > 1\n\
+        Array index out of range: 9\n\
+    ");
+}
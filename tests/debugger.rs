@@ -0,0 +1,190 @@
+//! Debuggers stop programs at breakpoints, step through them and look at their frames, both
+//! through the [`Debugger`] API and the `--debug` REPL.
+//!
+//! The program is compiled with `javac -g`, which has to be on the `PATH`.
+
+use std::{cell::RefCell, collections::VecDeque, fs, io::Write, path::PathBuf, process::{Command, Stdio}, rc::Rc, sync::OnceLock};
+
+use jappuccino::rt::{Breakpoint, ClassPath, DebugSession, Debugger, Location, Resume, Result, Runtime, SharedBuffer, StopReason};
+
+/// Starts right away, so `total += square(i);` is on line 5
+const MAIN: &str = "\
+class Squares {
+    static int sum(int n) {
+        int total = 0;
+        for (int i = 0; i < n; i++) {
+            total += square(i);
+        }
+        return total;
+    }
+    static int square(int x) {
+        return x * x;
+    }
+}
+class Point {
+    int x = 3;
+    String name = \"p\";
+}
+public class Main {
+    public static void main(String[] args) {
+        Point point = new Point();
+        System.out.println(Squares.sum(3) + point.x);
+    }
+}
+";
+
+/// Compiles the program once with the tables of lines and local variables and returns the
+/// directory of its class files
+fn classes() -> &'static PathBuf {
+    static CLASSES: OnceLock<PathBuf> = OnceLock::new();
+    CLASSES.get_or_init(|| {
+        let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("debugger");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Main.java");
+        fs::write(&path, MAIN).unwrap();
+        let status = Command::new("javac").arg("-g").arg("-d").arg(&dir).arg(&path).status().expect("javac should be on the PATH");
+        assert!(status.success(), "javac failed");
+        dir
+    })
+}
+
+/// Sets breakpoints at the start, then records every stop and goes on as planned, continuing
+/// once the plan is done
+struct Script {
+    breakpoints: Vec<Breakpoint>,
+    plan: VecDeque<Resume>,
+    stops: Rc<RefCell<Vec<String>>>,
+}
+
+impl Debugger for Script {
+    fn stopped(&mut self, session: &mut DebugSession<'_, '_>, reason: StopReason) -> Result<Resume> {
+        if reason == StopReason::Start {
+            for breakpoint in self.breakpoints.drain(..) {
+                session.add_breakpoint(breakpoint);
+            }
+            return Ok(Resume::Continue);
+        }
+        let frames = session.backtrace();
+        let locals = session.locals(0).unwrap().iter()
+            // objects without their id, which depends on where they are
+            .map(|local| format!("{}={}", local.name.as_deref().unwrap(), session.describe(local.value).split(" (id=").next().unwrap()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.stops.borrow_mut().push(format!("{reason:?} {} [{locals}] depth {}", frames[0], frames.len()));
+        Ok(self.plan.pop_front().unwrap_or(Resume::Continue))
+    }
+}
+
+/// Runs the program under a script and returns what it printed and the stops
+fn debug(breakpoints: &[&str], plan: &[Resume]) -> (String, Vec<String>) {
+    let out = SharedBuffer::new();
+    let stops = Rc::new(RefCell::new(Vec::new()));
+    let mut class_path = ClassPath::new();
+    class_path.push_directory(classes());
+    let script = Script {
+        breakpoints: breakpoints.iter().map(|breakpoint| breakpoint.parse().unwrap()).collect(),
+        plan: plan.iter().copied().collect(),
+        stops: stops.clone(),
+    };
+    let mut runtime = Runtime::new().with_class_path(class_path).with_stdout(out.clone()).with_debugger(script);
+    assert_eq!(runtime.run("Main", &[]).unwrap(), 0);
+    let stops = stops.borrow().clone();
+    (String::from_utf8(out.contents()).unwrap(), stops)
+}
+
+#[test]
+fn breakpoints_parse_and_print() {
+    let line: Breakpoint = "pkg.Squares:5".parse().unwrap();
+    assert_eq!(line, Breakpoint { class: "pkg.Squares".into(), method: None, location: Location::Line(5) });
+    let entry: Breakpoint = "pkg/Squares.sum".parse().unwrap();
+    assert_eq!((entry.method.as_deref(), entry.location), (Some("sum"), Location::Entry));
+    let pc: Breakpoint = "Squares.sum@12".parse().unwrap();
+    assert_eq!(pc.location, Location::Pc(12));
+    for breakpoint in [line, entry, pc] {
+        assert_eq!(breakpoint.to_string().parse::<Breakpoint>().unwrap().to_string(), breakpoint.to_string());
+    }
+    for bad in ["Squares", ":5", "Squares:x", ".sum", "Squares.", "Squares.sum@x"] {
+        assert!(bad.parse::<Breakpoint>().is_err(), "{bad}");
+    }
+}
+
+#[test]
+fn breakpoints_stop_at_lines_and_methods() {
+    let (out, stops) = debug(&["Squares:5", "Squares.square"], &[]);
+    assert_eq!(out, "8\n");
+    assert_eq!(stops, [
+        "Breakpoint(1) Squares.sum(Main.java:5) [n=3 total=0 i=0] depth 2",
+        "Breakpoint(2) Squares.square(Main.java:10) [x=0] depth 3",
+        "Breakpoint(1) Squares.sum(Main.java:5) [n=3 total=0 i=1] depth 2",
+        "Breakpoint(2) Squares.square(Main.java:10) [x=1] depth 3",
+        "Breakpoint(1) Squares.sum(Main.java:5) [n=3 total=1 i=2] depth 2",
+        "Breakpoint(2) Squares.square(Main.java:10) [x=2] depth 3",
+    ]);
+}
+
+#[test]
+fn steps_go_into_over_and_out_of_calls() {
+    let (out, stops) = debug(&["Squares.sum"], &[Resume::StepOver, Resume::StepOver, Resume::StepInto, Resume::StepOut, Resume::StepOver, Resume::StepOut]);
+    assert_eq!(out, "8\n");
+    assert_eq!(stops, [
+        "Breakpoint(1) Squares.sum(Main.java:3) [n=3] depth 2",
+        "Step Squares.sum(Main.java:4) [n=3 total=0] depth 2",
+        "Step Squares.sum(Main.java:5) [n=3 total=0 i=0] depth 2",
+        "Step Squares.square(Main.java:10) [x=0] depth 3",
+        // back in the middle of the line that called it
+        "Step Squares.sum(Main.java:5) [n=3 total=0 i=0] depth 2",
+        "Step Squares.sum(Main.java:4) [n=3 total=0 i=0] depth 2",
+        "Step Main.main(Main.java:20) [args=instance of java.lang.String[0] point=instance of Point] depth 1",
+    ]);
+}
+
+#[test]
+fn the_repl_reads_commands_from_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jappuccino"))
+        .arg("--debug")
+        .arg("-cp").arg(classes())
+        .arg("Main")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let commands = "\
+        break Squares.square\n\
+        break Main:20\n\
+        breakpoints\n\
+        c\n\
+        p point.name\n\
+        fields point\n\
+        delete 2\n\
+        c\n\
+        bt\n\
+        locals\n\
+        p nothing\n\
+        finish\n\
+        stack\n\
+        delete 1\n\
+        c\n\
+    ";
+    child.stdin.take().unwrap().write_all(commands.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let out = String::from_utf8(output.stdout).unwrap();
+    for expected in [
+        "stopped before the program starts",
+        "1: Squares.square\n2: Main:20\n",
+        "breakpoint 2 hit",
+        "point.name = \"p\"\n",
+        "x = 3\nname = \"p\"\n",
+        "deleted breakpoint 2 at Main:20",
+        "breakpoint 1 hit",
+        "* #0 Squares.square(Main.java:10), pc 0\n  #1 Squares.sum(Main.java:5)",
+        "  #2 Main.main(Main.java:20)",
+        "x = 0\n",
+        "no local variable, field or class nothing",
+        "[0] 0 (0x00000000)\n",
+        "8\n",
+    ] {
+        assert!(out.contains(expected), "{expected:?} missing from\n{out}");
+    }
+}
@@ -0,0 +1,291 @@
+/** Methods the JIT compiles, the tests compare their results with the interpreter's */
+public class Jit {
+    interface Shape {
+        int area();
+    }
+
+    static class Square implements Shape {
+        final int side;
+        Square(int side) { this.side = side; }
+        public int area() { return side * side; }
+    }
+
+    static class Rect implements Shape {
+        final int width, height;
+        Rect(int width, int height) { this.width = width; this.height = height; }
+        public int area() { return width * height; }
+    }
+
+    static int counter;
+    static long total;
+    int count;
+    long sum;
+    float scale;
+    double weight;
+    short small;
+    char letter;
+    byte tiny;
+
+    static int ints(int n) {
+        int s = 0;
+        for (int i = 0; i < n; i++) {
+            s += i * i - (i >> 1) ^ (i << 3);
+            s |= i >>> 2;
+            s &= ~(i * 7);
+            s -= -i;
+        }
+        return s;
+    }
+
+    static int divide(int a, int b) {
+        return a / b + a % b;
+    }
+
+    static long longs(int n) {
+        long acc = 1;
+        int k = 31;
+        for (int i = 0; i < n; i++) {
+            acc = acc * k + i;
+            acc ^= acc >>> 7;
+            acc <<= i & 3;
+            acc -= acc >> 11;
+        }
+        return acc + acc / (n + 1) - acc % k;
+    }
+
+    static long longDivide(long a, long b, int shift) {
+        return a / b + a % b + (a >> shift) + (a >>> shift) + (a << shift) - -a;
+    }
+
+    static int compareLongs(long a, long b) {
+        int r = 0;
+        if (a < b) r |= 1;
+        if (a > b) r |= 2;
+        if (a == b) r |= 4;
+        return r;
+    }
+
+    static float floats(int n) {
+        float f = 0.5f;
+        for (int i = 0; i < n; i++) {
+            f = f * 1.5f + i / 3.0f;
+            if (f > 1000f) f -= 999.25f;
+            f %= 300f;
+        }
+        return -f;
+    }
+
+    static double doubles(int n) {
+        // javac puts double constants other than 0 and 1 in the constant pool
+        int two = 2, three = 3, five = 5, seven = 7;
+        double d = 1;
+        double third = 1 / (double) three;
+        for (int i = 0; i < n; i++) {
+            d = d * third + (double) i / seven;
+            d %= five;
+            d = d - -d / two;
+        }
+        return d;
+    }
+
+    static int compare(double a, double b) {
+        int r = 0;
+        if (a < b) r |= 1;
+        if (a > b) r |= 2;
+        if (a == b) r |= 4;
+        if (a <= b) r |= 8;
+        if (a >= b) r |= 16;
+        float fa = (float) a, fb = (float) b;
+        if (fa < fb) r |= 32;
+        if (fa > fb) r |= 64;
+        if (fa != fb) r |= 128;
+        return r;
+    }
+
+    static int conversions(double d) {
+        int i = (int) d;
+        long l = (long) d;
+        float f = (float) d;
+        return i + (int) l + (int) f + (int) (long) f + (byte) i + (char) i + (short) i + (int) (l >> 32);
+    }
+
+    static double widening(long l, int i) {
+        return (double) l + (float) l + (double) i + (float) i + (long) (double) l + (long) (float) i;
+    }
+
+    static int ternaries(int n) {
+        int s = 0;
+        for (int i = 0; i < n; i++) {
+            s += (i % 3 == 0 ? i : -i) * (i > 5 ? 2 : 1);
+        }
+        return s;
+    }
+
+    static int fib(int n) {
+        return n < 2 ? n : fib(n - 1) + fib(n - 2);
+    }
+
+    static long mix(int a, long b, float c, double d) {
+        return a + b + (long) c + (long) d;
+    }
+
+    static long calls(int n) {
+        long s = 0;
+        int k = 3;
+        for (int i = 0; i < n; i++) {
+            s += mix(i, s, i * 0.5f, (double) i / k);
+        }
+        return s;
+    }
+
+    static int depth(int n) {
+        return n == 0 ? 0 : 1 + depth(n - 1);
+    }
+
+    static void accumulate(Jit target, int n) {
+        for (int i = 0; i < n; i++) {
+            target.count += i;
+            target.sum += (long) target.count * i;
+            target.scale = target.scale / 2 + i;
+            target.weight = target.weight * target.scale + target.sum;
+            target.small += (short) (i * 1000);
+            target.letter = (char) (target.letter + 7);
+            target.tiny ^= (byte) i;
+            counter++;
+            total += target.sum;
+        }
+    }
+
+    static long fields(int n) {
+        Jit target = new Jit();
+        accumulate(target, n);
+        accumulate(target, n);
+        return target.count + target.sum + (long) target.scale + (long) target.weight + target.small + target.letter + target.tiny + counter + total;
+    }
+
+    static long sumArrays(int[] ints, long[] longs, double[] doubles, byte[] bytes, char[] chars, short[] shorts, float[] floats) {
+        long s = 0;
+        for (int i = 0; i < ints.length; i++) {
+            ints[i] = ints[i] * 3 + i;
+            longs[i] = longs[i] + ints[i];
+            doubles[i] = doubles[i] / ints.length + longs[i];
+            bytes[i] = (byte) (bytes[i] + ints[i]);
+            chars[i] = (char) (chars[i] - i);
+            shorts[i] = (short) (shorts[i] * 31 + i);
+            floats[i] = floats[i] + 1.5f;
+            s += ints[i] + longs[i] + (long) doubles[i] + bytes[i] + chars[i] + shorts[i] + (long) floats[i];
+        }
+        return s;
+    }
+
+    static long arrays(int n) {
+        int[] ints = new int[n];
+        long[] longs = new long[n];
+        double[] doubles = new double[n];
+        byte[] bytes = new byte[n];
+        char[] chars = new char[n];
+        short[] shorts = new short[n];
+        float[] floats = new float[n];
+        long s = 0;
+        for (int round = 0; round < 5; round++) {
+            s += sumArrays(ints, longs, doubles, bytes, chars, shorts, floats);
+        }
+        return s;
+    }
+
+    static int element(int[] array, int i) {
+        return array[i];
+    }
+
+    static int outOfBounds(int i) {
+        int[] array = new int[4];
+        int s = 0;
+        for (int j = 0; j < 10; j++) {
+            s += element(array, j % 4);
+        }
+        return s + element(array, i);
+    }
+
+    static int count(Jit target) {
+        return target.count;
+    }
+
+    static int nullField(boolean pass) {
+        return count(pass ? new Jit() : null);
+    }
+
+    static int areas(Shape[] shapes, int n) {
+        int total = 0;
+        for (int i = 0; i < n; i++) {
+            for (Shape shape : shapes) {
+                total += shape.area();
+            }
+        }
+        return total;
+    }
+
+    static int virtualCalls(int n) {
+        Shape[] same = { new Square(3), new Square(4) };
+        Shape[] mixed = { new Square(3), new Rect(2, 5), new Square(4) };
+        return areas(same, n) + areas(mixed, n);
+    }
+
+    static int inner(int i) {
+        // `length` is built in, compiled code leaves it to the interpreter
+        return i == 7 ? "seven".length() : i * 2;
+    }
+
+    static int outer(int n) {
+        int s = 0;
+        long wide = n;
+        double d = 1;
+        for (int i = 0; i < n; i++) {
+            s += inner(i) + (int) wide + (int) d;
+            wide += s;
+            d /= n;
+        }
+        return s;
+    }
+
+    static int switches(int n) {
+        int s = 0;
+        for (int i = -3; i < n; i++) {
+            switch (i) {
+                case -1: s += 1;
+                case 0: s += 10; break;
+                case 1: s *= 3; break;
+                case 2: case 3: s -= i; break;
+                default: s += 2;
+            }
+            switch (i * 1000) {
+                case Integer.MIN_VALUE: return -1;
+                case -3000: s ^= 0x55; break;
+                case 5000: s += 5000; break;
+                case 100000: s <<= 1; break;
+            }
+        }
+        return s;
+    }
+
+    static long stackOps(int n) {
+        long[] longs = new long[4];
+        double[] doubles = new double[4];
+        int[] ints = new int[4];
+        Jit jit = new Jit();
+        long s = 0;
+        for (int i = 0; i < n; i++) {
+            int k = i & 3;
+            longs[k] += 1234567890123L * i;
+            long old = longs[k]++;
+            double d = doubles[k] *= 1.5;
+            doubles[k] += 0.25;
+            int x = ints[k] = i * 7;
+            long y = jit.sum = 0x123456789ABCDEFL + i;
+            int z = jit.count = i - 5;
+            double w = jit.weight += 3.141592653589793;
+            long t = total += 3000000000L;
+            s += old + (long) d + x + y + z + (long) w + t + jit.sum++;
+        }
+        return s;
+    }
+}
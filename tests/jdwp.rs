@@ -0,0 +1,269 @@
+//! A scripted debugger attaches over JDWP like `jdb` does, stops at a breakpoint and looks at the
+//! frames and locals there. `javac` has to be on the `PATH`.
+
+use std::{io::{Read, Write}, net::{SocketAddr, TcpListener, TcpStream}, path::PathBuf, process::Command, thread, time::Duration};
+
+use jappuccino::rt::{ClassPath, JdwpServer, Runtime, SharedBuffer};
+
+/// The line `total += i * i;` is on
+const LOOP_LINE: i32 = 5;
+/// `Squares` is loaded after the program starts, `Debuggee` before
+const DEBUGGEE: &str = "class Squares {
+    static int sum(int n) {
+        int total = 0;
+        for (int i = 0; i < n; i++) {
+            total += i * i;
+        }
+        return total;
+    }
+}
+public class Debuggee {
+    public static void main(String[] args) {
+        System.out.println(Squares.sum(4));
+    }
+}
+";
+
+const BREAKPOINT: u8 = 2;
+const CLASS_PREPARE: u8 = 8;
+const VM_START: u8 = 90;
+const VM_DEATH: u8 = 99;
+const SUSPEND_ALL: u8 = 2;
+
+/// Compiles the debuggee with the tables of lines and local variables
+fn classes() -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("jdwp-classes");
+    std::fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("Debuggee.java");
+    std::fs::write(&source, DEBUGGEE).unwrap();
+    let status = Command::new("javac").arg("-g").arg("-d").arg(&dir).arg(&source).status().expect("javac should be on the PATH");
+    assert!(status.success(), "javac failed");
+    dir
+}
+
+/// The data of a packet from the debuggee, read from the front
+struct Reader(Vec<u8>, usize);
+
+impl Reader {
+    fn bytes<const N: usize>(&mut self) -> [u8; N] {
+        let bytes = self.0[self.1..self.1 + N].try_into().unwrap();
+        self.1 += N;
+        bytes
+    }
+    fn u8(&mut self) -> u8 {
+        self.bytes::<1>()[0]
+    }
+    fn i32(&mut self) -> i32 {
+        i32::from_be_bytes(self.bytes())
+    }
+    fn u64(&mut self) -> u64 {
+        u64::from_be_bytes(self.bytes())
+    }
+    fn string(&mut self) -> String {
+        let length = self.i32() as usize;
+        let string = String::from_utf8(self.0[self.1..self.1 + length].to_vec()).unwrap();
+        self.1 += length;
+        string
+    }
+    /// A location, as its class, method and code index
+    fn location(&mut self) -> (u64, u64, u64) {
+        assert_eq!(self.u8(), 1, "the location should be in a class");
+        (self.u64(), self.u64(), self.u64())
+    }
+}
+
+/// Builds the data of a command
+#[derive(Default)]
+struct Data(Vec<u8>);
+
+impl Data {
+    fn u8(mut self, value: u8) -> Self {
+        self.0.push(value);
+        self
+    }
+    fn i32(mut self, value: i32) -> Self {
+        self.0.extend(value.to_be_bytes());
+        self
+    }
+    fn u64(mut self, value: u64) -> Self {
+        self.0.extend(value.to_be_bytes());
+        self
+    }
+    fn string(self, value: &str) -> Self {
+        let mut data = self.i32(value.len() as i32);
+        data.0.extend(value.as_bytes());
+        data
+    }
+}
+
+struct Client {
+    stream: TcpStream,
+    next_id: u32,
+}
+
+impl Client {
+    fn attach(address: SocketAddr) -> Self {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(30))).unwrap();
+        stream.write_all(b"JDWP-Handshake").unwrap();
+        let mut handshake = [0; 14];
+        stream.read_exact(&mut handshake).unwrap();
+        assert_eq!(&handshake, b"JDWP-Handshake");
+        Client { stream, next_id: 1 }
+    }
+    /// Reads a packet, returning its flags, the command or error code and the data
+    fn read_packet(&mut self) -> (u32, u8, [u8; 2], Reader) {
+        let mut header = [0; 11];
+        self.stream.read_exact(&mut header).unwrap();
+        let length = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+        let mut data = vec![0; length - header.len()];
+        self.stream.read_exact(&mut data).unwrap();
+        (u32::from_be_bytes(header[4..8].try_into().unwrap()), header[8], [header[9], header[10]], Reader(data, 0))
+    }
+    /// Sends a command and returns the data of its reply, which has to succeed
+    fn command(&mut self, command_set: u8, command: u8, data: Data) -> Reader {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut packet = Vec::new();
+        packet.extend((11 + data.0.len() as u32).to_be_bytes());
+        packet.extend(id.to_be_bytes());
+        packet.extend([0, command_set, command]);
+        packet.extend(data.0);
+        self.stream.write_all(&packet).unwrap();
+        let (reply_id, flags, error, reply) = self.read_packet();
+        assert_eq!((reply_id, flags), (id, 0x80), "the reply to {command_set}/{command} should come next");
+        assert_eq!(u16::from_be_bytes(error), 0, "{command_set}/{command} failed");
+        reply
+    }
+    /// Reads the next composite event, which has to be a single one of `kind`, and returns the
+    /// id of its request and its data
+    fn event(&mut self, kind: u8) -> (i32, Reader) {
+        let (_, flags, command, mut data) = self.read_packet();
+        assert_eq!((flags, command), (0, [64, 100]), "an event should come next");
+        let _suspend_policy = data.u8();
+        assert_eq!(data.i32(), 1);
+        assert_eq!(data.u8(), kind);
+        (data.i32(), data)
+    }
+    fn resume(&mut self) {
+        self.command(1, 9, Data::default());
+    }
+}
+
+/// The id of a method of a class
+fn find_method(client: &mut Client, class: u64, name: &str, signature: &str) -> u64 {
+    let mut methods = client.command(2, 5, Data::default().u64(class));
+    for _ in 0..methods.i32() {
+        let (id, method_name, method_signature, _) = (methods.u64(), methods.string(), methods.string(), methods.i32());
+        if (&*method_name, &*method_signature) == (name, signature) {
+            return id;
+        }
+    }
+    panic!("there should be a method {name}{signature}")
+}
+
+/// Debugs `Debuggee` like a user of `jdb` who stops in the loop of `Squares.sum` a few times
+fn debug(address: SocketAddr) {
+    let mut client = Client::attach(address);
+    let (request, mut start) = client.event(VM_START);
+    assert_eq!(request, 0);
+    let main_thread = start.u64();
+
+    let mut version = client.command(1, 1, Data::default());
+    version.string();
+    assert_eq!(version.i32(), 17);
+
+    // the main class is loaded already
+    let mut found = client.command(1, 2, Data::default().string("LDebuggee;"));
+    assert_eq!(found.i32(), 1);
+    assert_eq!(found.u8(), 1);
+    let debuggee = found.u64();
+    let main = find_method(&mut client, debuggee, "main", "([Ljava/lang/String;)V");
+
+    // the other class is not, so wait for it
+    let modifier = Data::default().u8(CLASS_PREPARE).u8(SUSPEND_ALL).i32(1).u8(5).string("Squares");
+    let prepare_request = client.command(15, 1, modifier).i32();
+    client.resume();
+    let (request, mut prepared) = client.event(CLASS_PREPARE);
+    assert_eq!(request, prepare_request);
+    assert_eq!(prepared.u64(), main_thread);
+    assert_eq!(prepared.u8(), 1);
+    let squares = prepared.u64();
+    assert_eq!(prepared.string(), "LSquares;");
+    client.command(15, 2, Data::default().u8(CLASS_PREPARE).i32(prepare_request));
+    let sum = find_method(&mut client, squares, "sum", "(I)I");
+
+    // the breakpoint goes where the line starts
+    let mut lines = client.command(6, 1, Data::default().u64(squares).u64(sum));
+    let (_start, _end) = (lines.u64(), lines.u64());
+    let index = (0..lines.i32()).map(|_| (lines.u64(), lines.i32())).find(|&(_, line)| line == LOOP_LINE).unwrap().0;
+    let mut variables = client.command(6, 2, Data::default().u64(squares).u64(sum));
+    assert_eq!(variables.i32(), 1, "sum should have one slot of arguments");
+    let (mut total_slot, mut i_slot) = (None, None);
+    for _ in 0..variables.i32() {
+        let (_, name, signature, _, slot) = (variables.u64(), variables.string(), variables.string(), variables.i32(), variables.i32());
+        assert_eq!(signature, "I");
+        match &*name {
+            "total" => total_slot = Some(slot),
+            "i" => i_slot = Some(slot),
+            _ => (),
+        }
+    }
+    let (total_slot, i_slot) = (total_slot.unwrap(), i_slot.unwrap());
+
+    let modifier = Data::default().u8(BREAKPOINT).u8(SUSPEND_ALL).i32(1).u8(7).u8(1).u64(squares).u64(sum).u64(index);
+    let breakpoint = client.command(15, 1, modifier).i32();
+    client.resume();
+    for i in 0..3 {
+        let (request, mut hit) = client.event(BREAKPOINT);
+        assert_eq!(request, breakpoint);
+        let thread = hit.u64();
+        assert_eq!(thread, main_thread);
+        assert_eq!(hit.location(), (squares, sum, index));
+
+        let mut frames = client.command(11, 6, Data::default().u64(thread).i32(0).i32(-1));
+        assert_eq!(frames.i32(), 2, "sum should be called by main");
+        let frame = frames.u64();
+        assert_eq!(frames.location(), (squares, sum, index));
+        let _caller = frames.u64();
+        let (caller_class, caller_method, _) = frames.location();
+        assert_eq!((caller_class, caller_method), (debuggee, main));
+
+        let slots = Data::default().u64(thread).u64(frame).i32(3).i32(0).u8(b'I').i32(total_slot).u8(b'I').i32(i_slot).u8(b'I');
+        let mut values = client.command(16, 1, slots);
+        assert_eq!(values.i32(), 3);
+        let mut value = || {
+            assert_eq!(values.u8(), b'I');
+            values.i32()
+        };
+        assert_eq!((value(), value(), value()), (4, (0..i).map(|k| k * k).sum(), i), "the locals at iteration {i}");
+
+        if i == 2 {
+            client.command(15, 2, Data::default().u8(BREAKPOINT).i32(breakpoint));
+        }
+        client.resume();
+    }
+    // without the breakpoint the program runs to the end
+    client.event(VM_DEATH);
+}
+
+#[test]
+fn debugger_stops_at_breakpoint_and_reads_frames() {
+    let classes = classes();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let client = thread::spawn(move || debug(address));
+
+    let server = JdwpServer::accept(&listener, true).unwrap();
+    let out = SharedBuffer::new();
+    let mut class_path = ClassPath::new();
+    class_path.push_directory(&classes);
+    let mut runtime = Runtime::new().with_class_path(class_path).with_stdout(out.clone()).with_debugger(server);
+    let status = runtime.run("Debuggee", &[]);
+
+    if let Err(panic) = client.join() {
+        std::panic::resume_unwind(panic);
+    }
+    assert_eq!(status.unwrap(), 0);
+    assert_eq!(String::from_utf8(out.contents()).unwrap(), "14\n");
+}
@@ -0,0 +1,142 @@
+//! Compiled code has to compute what the interpreter computes.
+//!
+//! The methods of `tests/java/Jit.java` run in a runtime that only interprets and in one that
+//! compiles them after two invocations, a few times each so the compiled code runs, and the results
+//! have to be the same, exceptions included. `javac` has to be on the `PATH`.
+
+#![cfg(feature = "jit")]
+
+use std::{fmt::Debug, path::PathBuf, process::Command, sync::OnceLock};
+
+use jappuccino::rt::{ClassPath, FromJava, JavaArgs, JitStats, Runtime};
+
+/// Compiles the test programs once and returns the directory of their class files
+fn classes() -> &'static PathBuf {
+    static CLASSES: OnceLock<PathBuf> = OnceLock::new();
+    CLASSES.get_or_init(|| {
+        let sources = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/java");
+        let classes = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("jit-classes");
+        let mut javac = Command::new("javac");
+        javac.arg("-d").arg(&classes);
+        for source in sources.read_dir().expect("tests/java should be readable") {
+            javac.arg(source.unwrap().path());
+        }
+        let status = javac.status().expect("javac should be on the PATH");
+        assert!(status.success(), "javac failed");
+        classes
+    })
+}
+
+fn runtime() -> Runtime {
+    let mut class_path = ClassPath::new();
+    class_path.push_directory(classes());
+    Runtime::new().with_class_path(class_path)
+}
+
+/// Calls a static method of `Jit`, with exceptions by the name of their class
+fn call<R: FromJava>(runtime: &mut Runtime, method: &str, descriptor: &str, args: impl JavaArgs) -> Result<R, String> {
+    let mut ctx = runtime.new_context();
+    let result = ctx.call_static("Jit", method, descriptor, args).unwrap();
+    result.map_err(|exception| ctx.get_class_name(exception).to_string())
+}
+
+/// Calls a method interpreted and compiled with each of the arguments and compares the results,
+/// returns what the JIT did
+fn compare<R, A>(method: &str, descriptor: &str, args: &[A]) -> JitStats
+where
+    R: FromJava + PartialEq + Debug,
+    A: JavaArgs + Clone,
+{
+    let mut interpreted = runtime();
+    let mut compiled = runtime().with_jit(2);
+    for _ in 0..4 {
+        for args in args {
+            let expected = call::<R>(&mut interpreted, method, descriptor, args.clone());
+            let actual = call::<R>(&mut compiled, method, descriptor, args.clone());
+            assert_eq!(actual, expected, "{method}{descriptor}");
+        }
+    }
+    let stats = compiled.jit_stats().unwrap().clone();
+    assert!(stats.compiled > 0 && stats.entries > 0, "{method} never ran compiled: {stats:?}");
+    stats
+}
+
+#[test]
+fn int_arithmetic() {
+    compare::<i32, _>("ints", "(I)I", &[(0,), (10,), (1000,)]);
+    compare::<i32, _>("ternaries", "(I)I", &[(0,), (50,)]);
+}
+
+#[test]
+fn division_by_zero_deoptimizes() {
+    let stats = compare::<i32, _>("divide", "(II)I", &[(7, 2), (i32::MIN, -1), (-7, 3), (1, 0)]);
+    assert!(stats.deopts > 0);
+}
+
+#[test]
+fn long_arithmetic() {
+    compare::<i64, _>("longs", "(I)J", &[(0,), (10,), (100,)]);
+    compare::<i64, _>("longDivide", "(JJI)J", &[(1i64 << 40, 3i64, 5), (i64::MIN, -1, 63), (-7, 2, 70)]);
+    compare::<i32, _>("compareLongs", "(JJ)I", &[(1i64, 2i64), (2, 1), (i64::MIN, i64::MIN)]);
+}
+
+#[test]
+fn floating_point_arithmetic() {
+    compare::<f32, _>("floats", "(I)F", &[(0,), (10,), (200,)]);
+    compare::<f64, _>("doubles", "(I)D", &[(0,), (10,), (200,)]);
+}
+
+#[test]
+fn comparisons_with_nan() {
+    compare::<i32, _>("compare", "(DD)I", &[(1.0, 2.0), (2.0, 1.0), (0.0, -0.0), (f64::NAN, 1.0), (1.0, f64::NAN)]);
+}
+
+#[test]
+fn conversions() {
+    compare::<i32, _>("conversions", "(D)I", &[(-3.7,), (1e20,), (-1e20,), (f64::NAN,), (65537.9,)]);
+    compare::<f64, _>("widening", "(JI)D", &[(i64::MAX, i32::MIN), (-12345, 777)]);
+}
+
+#[test]
+fn calls() {
+    compare::<i32, _>("fib", "(I)I", &[(15,)]);
+    compare::<i64, _>("calls", "(I)J", &[(50,)]);
+    compare::<i32, _>("virtualCalls", "(I)I", &[(20,)]);
+}
+
+#[test]
+fn switches() {
+    compare::<i32, _>("switches", "(I)I", &[(-5,), (0,), (4,), (200,)]);
+}
+
+#[test]
+fn fields() {
+    compare::<i64, _>("fields", "(I)J", &[(10,), (100,)]);
+}
+
+#[test]
+fn stack_operations() {
+    compare::<i64, _>("stackOps", "(I)J", &[(0,), (10,), (100,)]);
+}
+
+#[test]
+fn arrays() {
+    compare::<i64, _>("arrays", "(I)J", &[(0,), (16,)]);
+}
+
+#[test]
+fn exceptions_in_compiled_code() {
+    compare::<i32, _>("outOfBounds", "(I)I", &[(2,), (4,), (-1,)]);
+    compare::<i32, _>("nullField", "(Z)I", &[(true,), (false,)]);
+}
+
+#[test]
+fn deoptimized_frames_go_on_interpreted() {
+    let stats = compare::<i32, _>("outer", "(I)I", &[(20,)]);
+    assert!(stats.deopts > 0);
+}
+
+#[test]
+fn stack_overflow_in_compiled_recursion() {
+    compare::<i32, _>("depth", "(I)I", &[(100,), (3000,), (100_000,)]);
+}
@@ -0,0 +1,159 @@
+//! Untrusted programs stop at the limits the embedder sets.
+//!
+//! The programs are compiled with `javac`, which has to be on the `PATH`.
+
+use std::{fs, path::PathBuf, process::Command, sync::OnceLock, time::{Duration, Instant}};
+
+use jappuccino::rt::{ClassPath, Limit, RtError, Runtime, SharedBuffer};
+
+const PROGRAMS: &[(&str, &str)] = &[
+    ("Count", "
+        public class Count {
+            public static void main(String[] args) {
+                long sum = 0;
+                for (int i = 0; i < 100000; i++) {
+                    sum += i;
+                }
+                System.out.println(sum);
+            }
+        }
+    "),
+    ("Spin", "
+        public class Spin {
+            public static void main(String[] args) {
+                while (true) {}
+            }
+        }
+    "),
+    ("Recurse", "
+        public class Recurse {
+            static int depth;
+            static void down() {
+                depth++;
+                down();
+            }
+            public static void main(String[] args) {
+                try {
+                    down();
+                } catch (StackOverflowError e) {
+                    System.out.println(\"overflow at \" + depth);
+                }
+                depth = 0;
+                try {
+                    down();
+                } catch (StackOverflowError e) {
+                    System.out.println(\"again at \" + depth);
+                }
+            }
+        }
+    "),
+    ("Hoard", "
+        public class Hoard {
+            public static void main(String[] args) {
+                Object[] kept = new Object[1000];
+                int i = 0;
+                try {
+                    for (; i < kept.length; i++) {
+                        kept[i] = new long[1024];
+                    }
+                } catch (OutOfMemoryError e) {
+                    kept = null;
+                    System.out.println(\"out of memory: \" + (i > 0 && i < 1000));
+                }
+                // the garbage is collected, so there is room again
+                long[] again = new long[1024];
+                System.out.println(again.length);
+            }
+        }
+    "),
+    ("Nested", "
+        public class Nested {
+            public String toString() {
+                while (true) {}
+            }
+            public static void main(String[] args) {
+                System.out.println(\"\" + new Nested());
+            }
+        }
+    "),
+];
+
+/// Compiles the programs once and returns the directory of their class files
+fn classes() -> &'static PathBuf {
+    static CLASSES: OnceLock<PathBuf> = OnceLock::new();
+    CLASSES.get_or_init(|| {
+        let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("limits");
+        fs::create_dir_all(&dir).unwrap();
+        let mut javac = Command::new("javac");
+        javac.arg("-d").arg(&dir);
+        for (class, source) in PROGRAMS {
+            let path = dir.join(format!("{class}.java"));
+            fs::write(&path, source).unwrap();
+            javac.arg(path);
+        }
+        assert!(javac.status().expect("javac should be on the PATH").success(), "javac failed");
+        dir
+    })
+}
+
+fn runtime(out: &SharedBuffer) -> Runtime {
+    let mut class_path = ClassPath::new();
+    class_path.push_directory(classes());
+    Runtime::new().with_class_path(class_path).with_stdout(out.clone())
+}
+
+#[test]
+fn runs_stop_when_out_of_fuel_and_resume_with_more() {
+    let out = SharedBuffer::new();
+    let mut runtime = runtime(&out).with_fuel(50_000);
+    let mut ctx = runtime.new_context();
+    let mut result = ctx.run("Count", &[]);
+    let mut stops = 0;
+    while let Err(RtError::LimitReached { limit, resumable }) = result {
+        assert_eq!((limit, resumable), (Limit::Fuel, true));
+        assert_eq!(ctx.fuel(), Some(0));
+        stops += 1;
+        ctx.add_fuel(50_000);
+        result = ctx.resume();
+    }
+    assert_eq!(result.unwrap(), 0);
+    // the loop takes a few instructions per iteration
+    assert!(stops > 4, "stopped {stops} times");
+    assert!(ctx.instructions_executed() > 50_000 * stops);
+    assert_eq!(String::from_utf8(out.contents()).unwrap(), format!("{}\n", (0..100000u64).sum::<u64>()));
+}
+
+#[test]
+fn runs_stop_at_the_deadline() {
+    let out = SharedBuffer::new();
+    let mut runtime = runtime(&out).with_time_limit(Duration::from_millis(200));
+    let start = Instant::now();
+    let result = runtime.run("Spin", &[]);
+    assert!(matches!(result, Err(RtError::LimitReached { limit: Limit::Deadline, resumable: true })), "{result:?}");
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn deep_recursion_throws_stack_overflow_error() {
+    let out = SharedBuffer::new();
+    let mut runtime = runtime(&out).with_max_call_depth(100);
+    assert_eq!(runtime.run("Recurse", &[]).unwrap(), 0);
+    // main takes a frame of its own, the same depth is reached again after unwinding
+    assert_eq!(String::from_utf8(out.contents()).unwrap(), "overflow at 99\nagain at 99\n");
+}
+
+#[test]
+fn full_heaps_throw_out_of_memory_error() {
+    let out = SharedBuffer::new();
+    let mut runtime = runtime(&out).with_max_heap_size(1 << 20);
+    assert_eq!(runtime.run("Hoard", &[]).unwrap(), 0);
+    assert_eq!(String::from_utf8(out.contents()).unwrap(), "out of memory: true\n1024\n");
+}
+
+#[test]
+fn limits_in_code_called_from_native_code_cannot_be_resumed() {
+    let out = SharedBuffer::new();
+    let mut runtime = runtime(&out).with_fuel(10_000);
+    let result = runtime.run("Nested", &[]);
+    assert!(matches!(result, Err(RtError::LimitReached { limit: Limit::Fuel, resumable: false })), "{result:?}");
+}
@@ -0,0 +1,106 @@
+//! Embedders supply class files from anywhere with a [`ClassLoader`], like a database.
+//!
+//! The classes are compiled with `javac`, which has to be on the `PATH`, and then kept in memory.
+
+use std::{cell::RefCell, collections::HashMap, fs, io, path::PathBuf, process::Command, rc::Rc};
+
+use jappuccino::rt::{ClassLoader, ClassLoaderChain, ClassPath, Runtime};
+
+const MAIN: &str = "
+    public class Main {
+        static String greet() { return Greeting.text(); }
+        static String missing() {
+            try {
+                return Gone.text();
+            } catch (NoClassDefFoundError e) {
+                return e.getMessage();
+            }
+        }
+    }
+";
+
+/// A version of `Greeting` that says who loaded it
+fn greeting(by: &str) -> String {
+    format!("class Greeting {{ static String text() {{ return \"hello from the {by}\"; }} }}")
+}
+
+/// Compiles the sources into a directory of their own and returns it with the class files by the
+/// name of their class. `Main` is compiled against a class `Gone` that is deleted afterwards.
+fn compile(test: &str, sources: &[(&str, &str)]) -> (PathBuf, HashMap<String, Vec<u8>>) {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("loader-{test}"));
+    fs::create_dir_all(&dir).unwrap();
+    let mut javac = Command::new("javac");
+    javac.arg("-d").arg(&dir);
+    let gone = ("Gone", "class Gone { static String text() { return \"gone\"; } }");
+    for (class, source) in sources.iter().chain([&gone]) {
+        let path = dir.join(format!("{class}.java"));
+        fs::write(&path, source).unwrap();
+        javac.arg(path);
+    }
+    assert!(javac.status().expect("javac should be on the PATH").success(), "javac failed");
+    fs::remove_file(dir.join("Gone.class")).unwrap();
+    let classes = sources.iter().map(|(class, _)| (class.to_string(), fs::read(dir.join(format!("{class}.class"))).unwrap())).collect();
+    (dir, classes)
+}
+
+/// A loader of the given classes that records the names it is asked for
+fn database(classes: HashMap<String, Vec<u8>>, asked: Rc<RefCell<Vec<String>>>) -> impl ClassLoader {
+    move |name: &str| -> io::Result<Option<Vec<u8>>> {
+        asked.borrow_mut().push(name.to_string());
+        Ok(classes.get(name).cloned())
+    }
+}
+
+fn call(runtime: &mut Runtime, method: &str) -> String {
+    runtime.new_context().call_static::<String>("Main", method, "()Ljava/lang/String;", ()).unwrap().unwrap()
+}
+
+#[test]
+fn classes_load_from_memory() {
+    let (_, classes) = compile("memory", &[("Main", MAIN), ("Greeting", &greeting("database"))]);
+    let asked = Rc::new(RefCell::new(Vec::new()));
+    let mut runtime = Runtime::new().with_loader(database(classes, asked.clone()));
+    assert_eq!(call(&mut runtime, "greet"), "hello from the database");
+    assert_eq!(call(&mut runtime, "greet"), "hello from the database");
+    // without a class path there is nowhere else to look
+    assert_eq!(call(&mut runtime, "missing"), "Gone");
+    // each class is asked for once, the builtin ones never
+    for name in ["Main", "Greeting", "Gone"] {
+        assert_eq!(asked.borrow().iter().filter(|&asked| asked == name).count(), 1, "{name}");
+    }
+    assert!(!asked.borrow().iter().any(|name| name.starts_with("java/")), "{asked:?}");
+}
+
+#[test]
+fn parents_come_first() {
+    let (_, parent) = compile("parent", &[("Main", MAIN), ("Greeting", &greeting("parent"))]);
+    let (_, child) = compile("child", &[("Greeting", &greeting("child"))]);
+    let asked = Rc::new(RefCell::new(Vec::new()));
+    let loader = database(child, asked.clone()).with_parent(database(parent, Rc::default()));
+    let mut runtime = Runtime::new().with_loader(loader);
+    assert_eq!(call(&mut runtime, "greet"), "hello from the parent");
+    // the child is only asked for what the parent does not have
+    assert!(asked.borrow().is_empty(), "{asked:?}");
+    call(&mut runtime, "missing");
+    assert_eq!(*asked.borrow(), ["Gone"]);
+}
+
+#[test]
+fn chains_ask_their_loaders_in_order() {
+    let (main_dir, _) = compile("chain-main", &[("Main", MAIN), ("Greeting", &greeting("class path"))]);
+    // the class path only has `Main`
+    fs::remove_file(main_dir.join("Greeting.class")).unwrap();
+    let (_, first) = compile("chain-first", &[("Greeting", &greeting("first"))]);
+    let (_, second) = compile("chain-second", &[("Greeting", &greeting("second"))]);
+    let mut class_path = ClassPath::new();
+    class_path.push_directory(&main_dir);
+    let chain = ClassLoaderChain::new()
+        .with(class_path)
+        .with(database(first, Rc::default()))
+        .with(database(second, Rc::default()));
+    let mut runtime = Runtime::new().with_loader(chain);
+    assert_eq!(call(&mut runtime, "greet"), "hello from the first");
+    // the error says where the class path looked
+    let location = main_dir.join("Gone.class").display().to_string();
+    assert_eq!(call(&mut runtime, "missing"), format!("Gone (searched {location})"));
+}
@@ -0,0 +1,172 @@
+//! Profiling runtimes count what programs execute and allocate.
+//!
+//! The program is compiled with `javac`, which has to be on the `PATH`.
+
+use std::{fs, path::PathBuf, process::Command, sync::OnceLock};
+
+use jappuccino::{code::opcode::Opcode, rt::{ClassPath, MethodProfile, Profile, Runtime, SharedBuffer}};
+
+const MAIN: &str = "
+    class Point {
+        int x, y;
+    }
+    public class Main {
+        static int fib(int n) {
+            return n < 2 ? n : fib(n - 1) + fib(n - 2);
+        }
+        static int leaf(int x) {
+            return x + 1;
+        }
+        static int caller() {
+            int sum = 0;
+            for (int i = 0; i < 10; i++) {
+                sum = leaf(sum);
+            }
+            return sum;
+        }
+        public static void main(String[] args) {
+            int[][] grid = new int[3][];
+            for (int i = 0; i < grid.length; i++) {
+                grid[i] = new int[4];
+            }
+            for (int i = 0; i < 5; i++) {
+                new Point();
+            }
+            System.out.println(fib(10) + caller());
+        }
+    }
+";
+
+/// Compiles the program once and returns the directory of its class files
+fn classes() -> &'static PathBuf {
+    static CLASSES: OnceLock<PathBuf> = OnceLock::new();
+    CLASSES.get_or_init(|| {
+        let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("profile");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Main.java");
+        fs::write(&path, MAIN).unwrap();
+        let status = Command::new("javac").arg("-d").arg(&dir).arg(&path).status().expect("javac should be on the PATH");
+        assert!(status.success(), "javac failed");
+        dir
+    })
+}
+
+/// Runs the program the given number of times with profiling
+fn run(sample_interval: u64, runs: usize) -> Profile {
+    let out = SharedBuffer::new();
+    let mut class_path = ClassPath::new();
+    class_path.push_directory(classes());
+    let mut runtime = Runtime::new().with_class_path(class_path).with_stdout(out.clone()).with_profiling(sample_interval);
+    for _ in 0..runs {
+        assert_eq!(runtime.run("Main", &[]).unwrap(), 0);
+    }
+    assert_eq!(String::from_utf8(out.contents()).unwrap(), "65\n".repeat(runs));
+    runtime.profile().unwrap().clone()
+}
+
+fn method<'p>(methods: &'p [MethodProfile], name: &str) -> &'p MethodProfile {
+    methods.iter().find(|method| &*method.name == name).unwrap_or_else(|| panic!("no {name} in {methods:?}"))
+}
+
+#[test]
+fn methods_are_counted_with_and_without_their_callees() {
+    let profile = run(1, 1);
+    let methods = profile.methods();
+    let [main, fib, caller, leaf] = ["Main.main", "Main.fib", "Main.caller", "Main.leaf"].map(|name| method(&methods, name));
+    assert_eq!([main.invocations, fib.invocations, caller.invocations, leaf.invocations], [1, 177, 1, 10]);
+    assert_eq!(&*leaf.descriptor, "(I)I");
+    // iload_0, iconst_1, iadd, ireturn
+    assert_eq!((leaf.exclusive, leaf.inclusive), (40, 40));
+    assert_eq!(caller.inclusive, caller.exclusive + leaf.inclusive);
+    // the recursive calls are counted once
+    assert_eq!(fib.inclusive, fib.exclusive);
+    let init = method(&methods, "Point.<init>");
+    assert_eq!(init.invocations, 5);
+    assert_eq!(main.inclusive, main.exclusive + fib.inclusive + caller.inclusive + init.inclusive);
+    assert_eq!(main.inclusive, profile.instructions());
+    assert_eq!(methods.iter().map(|method| method.exclusive).sum::<u64>(), profile.instructions());
+    // the most instructions first
+    assert_eq!(&*methods[0].name, "Main.fib");
+    assert!(methods.windows(2).all(|pair| pair[0].exclusive >= pair[1].exclusive));
+}
+
+#[test]
+fn opcodes_are_counted() {
+    let profile = run(1, 1);
+    let opcodes = profile.opcodes();
+    assert_eq!(opcodes.iter().map(|(_, count)| count).sum::<u64>(), profile.instructions());
+    let count = |opcode| opcodes.iter().find(|&&(executed, _)| executed == opcode).map_or(0, |&(_, count)| count);
+    // fib calls itself twice unless n < 2, main calls fib and caller, caller calls leaf
+    assert_eq!(count(Opcode::Invokestatic), 176 + 2 + 10);
+    assert_eq!(count(Opcode::Anewarray) + count(Opcode::Newarray), 4);
+    assert_eq!(count(Opcode::New), 5);
+    assert!(opcodes.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+}
+
+#[test]
+fn allocations_are_counted_by_class() {
+    let profile = run(1, 1);
+    let allocations = profile.allocations();
+    let allocation = |class: &str| allocations.iter().find(|allocation| &*allocation.class == class).unwrap_or_else(|| panic!("no {class} in {allocations:?}"));
+    assert_eq!(allocation("Point").objects, 5);
+    assert_eq!(allocation("[I").objects, 3);
+    assert_eq!(allocation("[[I").objects, 1);
+    assert_eq!(allocation("Point").bytes % 5, 0);
+    assert!(allocation("[I").bytes >= 3 * 4 * 4);
+    assert!(allocations.windows(2).all(|pair| pair[0].bytes >= pair[1].bytes));
+}
+
+#[test]
+fn stacks_are_sampled_for_flame_graphs() {
+    let profile = run(1, 1);
+    let mut collapsed = Vec::new();
+    profile.write_collapsed(&mut collapsed).unwrap();
+    let collapsed = String::from_utf8(collapsed).unwrap();
+    let stacks: Vec<(&str, u64)> = collapsed.lines().map(|line| {
+        let (stack, count) = line.rsplit_once(' ').unwrap();
+        (stack, count.parse().unwrap())
+    }).collect();
+    assert!(stacks.contains(&("Main.main;Main.caller;Main.leaf", 40)), "{collapsed}");
+    assert!(stacks.iter().any(|(stack, _)| stack.starts_with("Main.main;Main.fib;Main.fib;Main.fib")), "{collapsed}");
+    assert_eq!(stacks.iter().map(|(_, count)| count).sum::<u64>(), profile.instructions());
+
+    // fewer samples, each counting for the instructions since the last one
+    let sampled = run(100, 1);
+    assert_eq!(sampled.sample_interval(), 100);
+    assert_eq!(sampled.instructions(), profile.instructions());
+    assert!(sampled.methods().iter().all(|method| method.exclusive % 100 == 0));
+    assert_eq!(method(&sampled.methods(), "Main.fib").invocations, 177);
+}
+
+#[test]
+fn counts_add_up_over_runs() {
+    let once = run(1, 1);
+    let twice = run(1, 2);
+    assert_eq!(twice.instructions(), 2 * once.instructions());
+    assert_eq!(method(&twice.methods(), "Main.fib").invocations, 2 * 177);
+    let report = twice.to_string();
+    assert!(report.starts_with(&format!("{} instructions, call stacks sampled every 1\n", twice.instructions())), "{report}");
+    assert!(report.contains("354  Main.fib(I)I\n"), "{report}");
+    assert!(report.contains("  invokestatic\n"), "{report}");
+    assert!(report.contains("  Point\n"), "{report}");
+}
+
+#[test]
+fn the_command_prints_the_report_and_writes_the_stacks() {
+    let stacks = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("profile-stacks.txt");
+    let output = Command::new(env!("CARGO_BIN_EXE_jappuccino"))
+        .arg("--profile")
+        .arg("--profile-stacks").arg(&stacks)
+        .arg("--profile-interval").arg("10")
+        .arg("-cp").arg(classes())
+        .arg("Main")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "65\n");
+    let report = String::from_utf8(output.stderr).unwrap();
+    assert!(report.contains(" instructions, call stacks sampled every 10\n"), "{report}");
+    assert!(report.contains("177  Main.fib(I)I\n"), "{report}");
+    let stacks = fs::read_to_string(&stacks).unwrap();
+    assert!(stacks.lines().any(|line| line.starts_with("Main.main;Main.fib;Main.fib ")), "{stacks}");
+}
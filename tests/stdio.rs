@@ -0,0 +1,91 @@
+//! `System.out`, `System.err` and `System.in` behave like on the JDK and can be redirected.
+//!
+//! The program is compiled with `javac`, which has to be on the `PATH`.
+
+use std::{fs, path::PathBuf, process::Command};
+
+use jappuccino::rt::{ClassPath, Runtime, SharedBuffer};
+
+/// Every overload of `print` and `println`, `printf` and `format` with most conversions and flags,
+/// and both ways of reading `System.in`
+const MAIN: &str = "
+    public class Main {
+        public static void main(String[] args) throws Exception {
+            System.out.print(1);
+            System.out.print(2L);
+            System.out.print('c');
+            System.out.print(true);
+            System.out.print(1.5);
+            System.out.print(2.5f);
+            System.out.print(new char[] { 'x', 'y' });
+            System.out.print((Object) null);
+            System.out.print((String) null);
+            System.out.println();
+            System.out.println(42);
+            System.out.println(-7L);
+            System.out.println('z');
+            System.out.println(false);
+            System.out.println(0.1);
+            System.out.println(1e-5f);
+            System.out.println(new char[] { 'a', 'é' });
+            System.out.println(new Object() { public String toString() { return \"object\"; } });
+            System.out.println((String) null);
+            System.out.printf(\"%5d|%-5s|%07.2f|%x|%X|%c|%b|%,d|%e|%10.3e|%%|%s%n\", 42, \"ab\", 3.14159, 255, 255, 'q', null, 1234567, 12345.678, -0.000123, 'é');
+            System.out.format(\"%2$s %1$s %s%n\", \"a\", \"b\");
+            System.out.println(String.format(\"%08.3f|%+d|%o|%h\", -2.5, 5, 8, \"hi\"));
+            try {
+                System.out.printf(\"%d%n\", \"x\");
+            } catch (java.util.IllegalFormatException e) {
+                System.out.println(e.getClass().getName() + \": \" + e.getMessage());
+            }
+            System.out.flush();
+            System.err.println(\"to err\");
+            System.err.printf(\"%s!%n\", \"e\");
+            System.err.print('x');
+            System.err.flush();
+            int first = System.in.read();
+            byte[] rest = new byte[16];
+            int n = System.in.read(rest);
+            System.out.println(first + \" \" + n + \" \" + rest[0] + \" \" + rest[n - 1] + \" \" + System.in.read());
+        }
+    }
+";
+
+#[test]
+fn streams_print_and_read_like_the_jdk() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("stdio");
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("Main.java");
+    fs::write(&source, MAIN).unwrap();
+    let status = Command::new("javac").arg("-encoding").arg("UTF-8").arg("-d").arg(&dir).arg(&source).status().expect("javac should be on the PATH");
+    assert!(status.success(), "javac failed");
+
+    let (out, err) = (SharedBuffer::new(), SharedBuffer::new());
+    let mut class_path = ClassPath::new();
+    class_path.push_directory(&dir);
+    let mut runtime = Runtime::new()
+        .with_class_path(class_path)
+        .with_stdout(out.clone())
+        .with_stderr(err.clone())
+        .with_stdin("héllo".as_bytes());
+    assert_eq!(runtime.run("Main", &[]).unwrap(), 0);
+    // what `java` prints with UTF-8 streams
+    assert_eq!(String::from_utf8(out.contents()).unwrap(), "\
+        12ctrue1.52.5xynullnull\n\
+        42\n\
+        -7\n\
+        z\n\
+        false\n\
+        0.1\n\
+        1.0E-5\n\
+        aé\n\
+        object\n\
+        null\n\
+        \x20  42|ab   |0003.14|ff|FF|q|false|1,234,567|1.234568e+04|-1.230e-04|%|é\n\
+        b a a\n\
+        -002.500|+5|10|d01\n\
+        java.util.IllegalFormatConversionException: d != java.lang.String\n\
+        104 5 -61 111 -1\n\
+    ");
+    assert_eq!(String::from_utf8(err.contents()).unwrap(), "to err\ne!\nx");
+}
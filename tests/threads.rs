@@ -0,0 +1,203 @@
+//! Java threads run as green threads, the same way every time.
+//!
+//! The programs are compiled with `javac`, which has to be on the `PATH`.
+
+use std::{fs, path::PathBuf, process::Command, sync::OnceLock};
+
+use jappuccino::rt::{ClassPath, RtError, Runtime, SharedBuffer};
+
+const PROGRAMS: &[(&str, &str)] = &[
+    ("Counter", "
+        public class Counter {
+            static int count;
+            static final Object lock = new Object();
+            static synchronized void increment() {
+                // entered again by the thread that holds it
+                synchronized (Counter.class) {
+                    count++;
+                }
+            }
+            public static void main(String[] args) throws InterruptedException {
+                Thread[] threads = new Thread[4];
+                for (int t = 0; t < threads.length; t++) {
+                    threads[t] = new Thread(() -> {
+                        for (int i = 0; i < 1000; i++) {
+                            increment();
+                            synchronized (lock) {
+                                lock.notifyAll();
+                            }
+                        }
+                    });
+                    threads[t].start();
+                }
+                for (Thread thread : threads) {
+                    thread.join();
+                }
+                System.out.println(count + \" \" + threads[0].isAlive());
+            }
+        }
+    "),
+    ("Queue", "
+        public class Queue {
+            static final int[] slot = { -1 };
+            public static void main(String[] args) throws InterruptedException {
+                Thread consumer = new Thread(() -> {
+                    int sum = 0;
+                    for (int i = 0; i < 5; i++) {
+                        synchronized (slot) {
+                            while (slot[0] < 0) {
+                                try {
+                                    slot.wait();
+                                } catch (InterruptedException e) {
+                                    return;
+                                }
+                            }
+                            sum += slot[0];
+                            slot[0] = -1;
+                            slot.notifyAll();
+                        }
+                    }
+                    System.out.println(Thread.currentThread().getName() + \" got \" + sum);
+                }, \"consumer\");
+                consumer.start();
+                for (int i = 1; i <= 5; i++) {
+                    synchronized (slot) {
+                        while (slot[0] >= 0) {
+                            slot.wait();
+                        }
+                        slot[0] = i * i;
+                        slot.notifyAll();
+                    }
+                }
+                consumer.join();
+                System.out.println(\"done\");
+            }
+        }
+    "),
+    ("Sleepers", "
+        public class Sleepers {
+            public static void main(String[] args) throws InterruptedException {
+                for (int delay : new int[] { 30, 10, 20 }) {
+                    new Thread(() -> {
+                        try {
+                            Thread.sleep(delay);
+                        } catch (InterruptedException e) {}
+                        System.out.println(\"woke after \" + delay);
+                    }).start();
+                }
+                Thread failing = new Thread(() -> { throw new IllegalStateException(\"thrown\"); }, \"failing\");
+                failing.start();
+                failing.join();
+                System.out.println(\"main goes on\");
+            }
+        }
+    "),
+    ("Race", "
+        public class Race {
+            static int count;
+            public static void main(String[] args) throws InterruptedException {
+                Thread[] threads = new Thread[4];
+                for (int t = 0; t < threads.length; t++) {
+                    threads[t] = new Thread(() -> {
+                        for (int i = 0; i < 1000; i++) {
+                            int seen = count;
+                            Thread.yield();
+                            count = seen + 1;
+                        }
+                    });
+                    threads[t].start();
+                }
+                for (Thread thread : threads) {
+                    thread.join();
+                }
+                System.out.println(count);
+            }
+        }
+    "),
+    ("Deadlock", "
+        public class Deadlock {
+            static final Object first = new Object(), second = new Object();
+            public static void main(String[] args) {
+                new Thread(() -> {
+                    synchronized (second) {
+                        Thread.yield();
+                        synchronized (first) {}
+                    }
+                }).start();
+                synchronized (first) {
+                    Thread.yield();
+                    synchronized (second) {}
+                }
+            }
+        }
+    "),
+];
+
+/// Compiles the programs once and returns the directory of their class files
+fn classes() -> &'static PathBuf {
+    static CLASSES: OnceLock<PathBuf> = OnceLock::new();
+    CLASSES.get_or_init(|| {
+        let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("threads");
+        fs::create_dir_all(&dir).unwrap();
+        let mut javac = Command::new("javac");
+        javac.arg("-d").arg(&dir);
+        for (class, source) in PROGRAMS {
+            let path = dir.join(format!("{class}.java"));
+            fs::write(&path, source).unwrap();
+            javac.arg(path);
+        }
+        assert!(javac.status().expect("javac should be on the PATH").success(), "javac failed");
+        dir
+    })
+}
+
+/// Runs a program with a short time slice, so threads switch often, and returns the result of the
+/// run and what it printed to the standard output and error
+fn run(class: &str) -> (Result<i32, RtError>, String, String) {
+    let (out, err) = (SharedBuffer::new(), SharedBuffer::new());
+    let mut class_path = ClassPath::new();
+    class_path.push_directory(classes());
+    let mut runtime = Runtime::new().with_class_path(class_path).with_stdout(out.clone()).with_stderr(err.clone()).with_time_slice(7);
+    let result = runtime.run(class, &[]);
+    (result, String::from_utf8(out.contents()).unwrap(), String::from_utf8(err.contents()).unwrap())
+}
+
+#[test]
+fn synchronized_updates_are_not_lost() {
+    let (result, out, _) = run("Counter");
+    assert_eq!(result.unwrap(), 0);
+    assert_eq!(out, "4000 false\n");
+}
+
+#[test]
+fn threads_wait_for_notifications() {
+    let (result, out, _) = run("Queue");
+    assert_eq!(result.unwrap(), 0);
+    assert_eq!(out, "consumer got 55\ndone\n");
+}
+
+#[test]
+fn sleepers_wake_in_order_and_failures_stay_in_their_thread() {
+    let (result, out, err) = run("Sleepers");
+    assert_eq!(result.unwrap(), 0);
+    assert_eq!(out, "main goes on\nwoke after 10\nwoke after 20\nwoke after 30\n");
+    assert_eq!(err, "Exception in thread \"failing\" java.lang.IllegalStateException: thrown\n");
+}
+
+#[test]
+fn deadlocks_stop_the_run() {
+    let (result, _, _) = run("Deadlock");
+    let Err(RtError::Deadlock { threads }) = result else { panic!("{result:?}") };
+    assert_eq!(threads.len(), 2, "{threads:?}");
+}
+
+#[test]
+fn races_lose_the_same_updates_every_time() {
+    let (result, first, _) = run("Race");
+    assert_eq!(result.unwrap(), 0);
+    let lost: i32 = 4000 - first.trim().parse::<i32>().unwrap();
+    assert!(lost > 0, "the threads should switch between reading and writing");
+    for _ in 0..3 {
+        assert_eq!(run("Race").1, first);
+    }
+}